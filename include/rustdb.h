@@ -0,0 +1,61 @@
+/* sqldb-rs C 接口，使用 `cargo build --release --features capi` 编译得到动态库 */
+#ifndef RUSTDB_H
+#define RUSTDB_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 返回码，接口内部发生 panic 时返回 RUSTDB_ERROR，
+ * 返回指针或数值的函数返回 NULL 或 0 */
+#define RUSTDB_OK 0
+#define RUSTDB_ERROR 1
+#define RUSTDB_PARSE 2
+#define RUSTDB_CONFLICT 3
+#define RUSTDB_READONLY 4
+#define RUSTDB_ABORTED 5
+#define RUSTDB_DEADLOCK 6
+#define RUSTDB_CANCELLED 7
+#define RUSTDB_TIMEOUT 8
+#define RUSTDB_LIMIT 9
+#define RUSTDB_CONSTRAINT 10
+#define RUSTDB_MISUSE 21
+#define RUSTDB_ROW 100
+#define RUSTDB_DONE 101
+
+/* 列的类型 */
+#define RUSTDB_NULL 0
+#define RUSTDB_BOOLEAN 1
+#define RUSTDB_INTEGER 2
+#define RUSTDB_FLOAT 3
+#define RUSTDB_TEXT 4
+
+typedef struct RustdbDatabase RustdbDatabase;
+typedef struct RustdbStmt RustdbStmt;
+
+/* path 为 NULL 时使用内存存储 */
+int rustdb_open(const char *path, RustdbDatabase **db);
+int rustdb_close(RustdbDatabase *db);
+const char *rustdb_errmsg(const RustdbDatabase *db);
+
+int rustdb_execute(RustdbDatabase *db, const char *sql, RustdbStmt **stmt);
+int rustdb_step(RustdbStmt *stmt);
+int rustdb_finalize(RustdbStmt *stmt);
+int64_t rustdb_changes(const RustdbStmt *stmt);
+
+int rustdb_column_count(const RustdbStmt *stmt);
+const char *rustdb_column_name(const RustdbStmt *stmt, int i);
+int rustdb_column_type(const RustdbStmt *stmt, int i);
+int64_t rustdb_column_int64(const RustdbStmt *stmt, int i);
+double rustdb_column_double(const RustdbStmt *stmt, int i);
+/* 返回的指针在下一次 rustdb_step 或 rustdb_finalize 之前有效，
+ * 同一行中多次获取同一列返回相同的指针 */
+const char *rustdb_column_text(RustdbStmt *stmt, int i);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,408 @@
+// C 语言接口，C/C++ 程序可以通过这些函数嵌入数据库
+// 使用方式和 sqlite 类似：
+//   rustdb_open -> rustdb_execute -> rustdb_step / rustdb_column_* -> rustdb_finalize -> rustdb_close
+// 所有指针参数都需要是由对应函数返回的有效指针，字符串需要以 \0 结尾
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::{kv::KVEngine, Engine, Session},
+        executor::ResultSet,
+        types::{Row, Value},
+    },
+    storage::{disk::DiskEngine, memory::MemoryEngine},
+};
+
+// 返回码
+pub const RUSTDB_OK: c_int = 0;
+pub const RUSTDB_ERROR: c_int = 1;
+pub const RUSTDB_PARSE: c_int = 2;
+pub const RUSTDB_CONFLICT: c_int = 3;
+pub const RUSTDB_READONLY: c_int = 4;
+pub const RUSTDB_ABORTED: c_int = 5;
+pub const RUSTDB_DEADLOCK: c_int = 6;
+pub const RUSTDB_CANCELLED: c_int = 7;
+pub const RUSTDB_TIMEOUT: c_int = 8;
+pub const RUSTDB_LIMIT: c_int = 9;
+pub const RUSTDB_CONSTRAINT: c_int = 10;
+pub const RUSTDB_MISUSE: c_int = 21;
+pub const RUSTDB_ROW: c_int = 100;
+pub const RUSTDB_DONE: c_int = 101;
+
+// 列的类型
+pub const RUSTDB_NULL: c_int = 0;
+pub const RUSTDB_BOOLEAN: c_int = 1;
+pub const RUSTDB_INTEGER: c_int = 2;
+pub const RUSTDB_FLOAT: c_int = 3;
+pub const RUSTDB_TEXT: c_int = 4;
+
+fn error_code(err: &Error) -> c_int {
+    match err {
+        Error::Parse(_) => RUSTDB_PARSE,
+        Error::Internal(_) => RUSTDB_ERROR,
+        Error::WriteConflict => RUSTDB_CONFLICT,
+        Error::ReadOnly => RUSTDB_READONLY,
+        Error::Aborted => RUSTDB_ABORTED,
+        Error::Deadlock => RUSTDB_DEADLOCK,
+        Error::Cancelled => RUSTDB_CANCELLED,
+        Error::Timeout => RUSTDB_TIMEOUT,
+        Error::ResourceLimit(_) => RUSTDB_LIMIT,
+        Error::ConstraintViolation(_) => RUSTDB_CONSTRAINT,
+    }
+}
+
+// panic 不能跨越 C 接口展开，捕获后返回 default
+fn guard<T>(default: T, f: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(default)
+}
+
+enum Sessions {
+    Memory(Session<KVEngine<MemoryEngine>>),
+    Disk(Session<KVEngine<DiskEngine>>),
+}
+
+// 数据库连接
+pub struct RustdbDatabase {
+    session: Sessions,
+    // 最近一次的错误信息
+    errmsg: CString,
+}
+
+impl RustdbDatabase {
+    fn execute(&mut self, sql: &str) -> Result<ResultSet> {
+        catch_unwind(AssertUnwindSafe(|| match &mut self.session {
+            Sessions::Memory(s) => s.execute(sql),
+            Sessions::Disk(s) => s.execute(sql),
+        }))
+        .unwrap_or_else(|_| Err(Error::Internal("panic while executing statement".into())))
+    }
+
+    fn set_error(&mut self, err: &Error) -> c_int {
+        self.errmsg = CString::new(err.to_string().replace('\0', "")).unwrap_or_default();
+        error_code(err)
+    }
+}
+
+// 语句的执行结果，通过 rustdb_step 逐行读取
+pub struct RustdbStmt {
+    columns: Vec<CString>,
+    rows: std::vec::IntoIter<Row>,
+    // 当前行
+    row: Option<Row>,
+    // 当前行中字符串列的缓存，每列只生成一次，
+    // rustdb_column_text 返回的指针在下一次 step 或 finalize 之前有效
+    text: Vec<Option<CString>>,
+    // 影响的行数
+    changes: i64,
+}
+
+impl RustdbStmt {
+    fn value(&self, i: c_int) -> Option<&Value> {
+        self.row.as_ref()?.get(usize::try_from(i).ok()?)
+    }
+}
+
+// 打开数据库，path 为 NULL 时使用内存存储
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_open(path: *const c_char, db: *mut *mut RustdbDatabase) -> c_int {
+    if db.is_null() {
+        return RUSTDB_MISUSE;
+    }
+    *db = ptr::null_mut();
+    guard(RUSTDB_ERROR, || open(path, db))
+}
+
+unsafe fn open(path: *const c_char, db: *mut *mut RustdbDatabase) -> c_int {
+    let session = if path.is_null() {
+        KVEngine::new(MemoryEngine::new())
+            .session()
+            .map(Sessions::Memory)
+    } else {
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => return RUSTDB_MISUSE,
+        };
+        DiskEngine::new(path)
+            .and_then(|eng| KVEngine::new(eng).session())
+            .map(Sessions::Disk)
+    };
+    match session {
+        Ok(session) => {
+            *db = Box::into_raw(Box::new(RustdbDatabase {
+                session,
+                errmsg: CString::default(),
+            }));
+            RUSTDB_OK
+        }
+        Err(err) => error_code(&err),
+    }
+}
+
+// 关闭数据库
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_close(db: *mut RustdbDatabase) -> c_int {
+    if !db.is_null() {
+        return guard(RUSTDB_ERROR, || {
+            drop(Box::from_raw(db));
+            RUSTDB_OK
+        });
+    }
+    RUSTDB_OK
+}
+
+// 最近一次错误的信息，指针在下一次调用 rustdb_execute 之前有效
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_errmsg(db: *const RustdbDatabase) -> *const c_char {
+    match db.as_ref() {
+        Some(db) => db.errmsg.as_ptr(),
+        None => c"invalid database handle".as_ptr(),
+    }
+}
+
+// 执行一条 SQL 语句，结果通过 stmt 返回，需要调用 rustdb_finalize 释放
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_execute(
+    db: *mut RustdbDatabase,
+    sql: *const c_char,
+    stmt: *mut *mut RustdbStmt,
+) -> c_int {
+    let (Some(db), false, false) = (db.as_mut(), sql.is_null(), stmt.is_null()) else {
+        return RUSTDB_MISUSE;
+    };
+    *stmt = ptr::null_mut();
+    let Ok(sql) = CStr::from_ptr(sql).to_str() else {
+        return RUSTDB_MISUSE;
+    };
+    guard(RUSTDB_ERROR, || execute(db, sql, stmt))
+}
+
+unsafe fn execute(db: &mut RustdbDatabase, sql: &str, stmt: *mut *mut RustdbStmt) -> c_int {
+    let (columns, rows, changes) = match db.execute(sql) {
+        Ok(ResultSet::Scan { columns, rows }) => (columns, rows, 0),
+        Ok(
+            ResultSet::Insert { count } | ResultSet::Update { count } | ResultSet::Delete { count },
+        ) => (Vec::new(), Vec::new(), count as i64),
+        Ok(_) => (Vec::new(), Vec::new(), 0),
+        Err(err) => return db.set_error(&err),
+    };
+    db.errmsg = CString::default();
+    *stmt = Box::into_raw(Box::new(RustdbStmt {
+        columns: columns
+            .into_iter()
+            .map(|c| CString::new(c).unwrap_or_default())
+            .collect(),
+        rows: rows.into_iter(),
+        row: None,
+        text: Vec::new(),
+        changes,
+    }));
+    RUSTDB_OK
+}
+
+// 移动到下一行，有数据返回 RUSTDB_ROW，没有更多数据返回 RUSTDB_DONE
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_step(stmt: *mut RustdbStmt) -> c_int {
+    let Some(stmt) = stmt.as_mut() else {
+        return RUSTDB_MISUSE;
+    };
+    guard(RUSTDB_ERROR, || {
+        stmt.row = stmt.rows.next();
+        stmt.text.clear();
+        match &stmt.row {
+            Some(row) => {
+                stmt.text.resize(row.len(), None);
+                RUSTDB_ROW
+            }
+            None => RUSTDB_DONE,
+        }
+    })
+}
+
+// 释放语句
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_finalize(stmt: *mut RustdbStmt) -> c_int {
+    if !stmt.is_null() {
+        return guard(RUSTDB_ERROR, || {
+            drop(Box::from_raw(stmt));
+            RUSTDB_OK
+        });
+    }
+    RUSTDB_OK
+}
+
+// Insert 等语句影响的行数
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_changes(stmt: *const RustdbStmt) -> i64 {
+    guard(0, || stmt.as_ref().map_or(0, |s| s.changes))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_column_count(stmt: *const RustdbStmt) -> c_int {
+    guard(0, || stmt.as_ref().map_or(0, |s| s.columns.len() as c_int))
+}
+
+// 列名，下标越界时返回 NULL
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_column_name(stmt: *const RustdbStmt, i: c_int) -> *const c_char {
+    guard(ptr::null(), || {
+        stmt.as_ref()
+            .and_then(|s| s.columns.get(usize::try_from(i).ok()?))
+            .map_or(ptr::null(), |c| c.as_ptr())
+    })
+}
+
+// 当前行中列的类型
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_column_type(stmt: *const RustdbStmt, i: c_int) -> c_int {
+    guard(RUSTDB_NULL, || {
+        match stmt.as_ref().and_then(|s| s.value(i)) {
+            Some(Value::Boolean(_)) => RUSTDB_BOOLEAN,
+            Some(Value::Integer(_)) => RUSTDB_INTEGER,
+            Some(Value::Float(_)) => RUSTDB_FLOAT,
+            Some(Value::String(_))
+            | Some(Value::Point(..))
+            | Some(Value::Decimal(_))
+            | Some(Value::Uuid(_))
+            | Some(Value::Json(_)) => RUSTDB_TEXT,
+            Some(Value::Null) | None => RUSTDB_NULL,
+        }
+    })
+}
+
+// 获取整数列，布尔值转换为 0/1，其他类型返回 0
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_column_int64(stmt: *const RustdbStmt, i: c_int) -> i64 {
+    guard(0, || match stmt.as_ref().and_then(|s| s.value(i)) {
+        Some(Value::Integer(v)) => *v,
+        Some(Value::Float(v)) => *v as i64,
+        Some(Value::Decimal(d)) => d.to_i64().unwrap_or(0),
+        Some(Value::Boolean(b)) => *b as i64,
+        _ => 0,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_column_double(stmt: *const RustdbStmt, i: c_int) -> f64 {
+    guard(0.0, || match stmt.as_ref().and_then(|s| s.value(i)) {
+        Some(Value::Float(v)) => *v,
+        Some(Value::Integer(v)) => *v as f64,
+        Some(Value::Decimal(d)) => d.to_f64(),
+        _ => 0.0,
+    })
+}
+
+// 获取字符串列，NULL 返回空指针，其他类型转换为字符串
+#[no_mangle]
+pub unsafe extern "C" fn rustdb_column_text(stmt: *mut RustdbStmt, i: c_int) -> *const c_char {
+    let Some(stmt) = stmt.as_mut() else {
+        return ptr::null();
+    };
+    guard(ptr::null(), || {
+        let text = match stmt.value(i) {
+            None | Some(Value::Null) => return ptr::null(),
+            // 同一行中已经取过的列直接返回之前的指针，保证其在下一次 step 之前有效
+            Some(_) if stmt.text[i as usize].is_some() => {
+                return stmt.text[i as usize]
+                    .as_ref()
+                    .map_or(ptr::null(), |c| c.as_ptr())
+            }
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Boolean(b)) => b.to_string(),
+            Some(Value::Integer(v)) => v.to_string(),
+            Some(Value::Float(v)) => v.to_string(),
+            Some(Value::Point(x, y)) => format!("POINT({} {})", x, y),
+            Some(Value::Decimal(d)) => d.to_string(),
+            Some(Value::Uuid(u)) => u.to_string(),
+            Some(Value::Json(j)) => j.to_string(),
+        };
+        stmt.text[i as usize]
+            .insert(CString::new(text.replace('\0', "")).unwrap_or_default())
+            .as_ptr()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{CStr, CString},
+        ptr,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_capi() {
+        unsafe {
+            let mut db = ptr::null_mut();
+            assert_eq!(rustdb_open(ptr::null(), &mut db), RUSTDB_OK);
+
+            let exec = |sql: &str, stmt: &mut *mut RustdbStmt| {
+                let sql = CString::new(sql).unwrap();
+                rustdb_execute(db, sql.as_ptr(), stmt)
+            };
+
+            let mut stmt = ptr::null_mut();
+            assert_eq!(
+                exec("create table t (a int, b text, c float);", &mut stmt),
+                RUSTDB_OK
+            );
+            rustdb_finalize(stmt);
+            assert_eq!(
+                exec(
+                    "insert into t values (1, 'x', 1.5), (2, null, 2.5);",
+                    &mut stmt
+                ),
+                RUSTDB_OK
+            );
+            assert_eq!(rustdb_changes(stmt), 2);
+            rustdb_finalize(stmt);
+
+            assert_eq!(exec("select * from t;", &mut stmt), RUSTDB_OK);
+            assert_eq!(rustdb_column_count(stmt), 3);
+            assert_eq!(CStr::from_ptr(rustdb_column_name(stmt, 1)), c"b");
+            assert!(rustdb_column_name(stmt, 3).is_null());
+
+            assert_eq!(rustdb_step(stmt), RUSTDB_ROW);
+            assert_eq!(rustdb_column_type(stmt, 0), RUSTDB_INTEGER);
+            assert_eq!(rustdb_column_int64(stmt, 0), 1);
+            let text = rustdb_column_text(stmt, 1);
+            assert_eq!(CStr::from_ptr(text), c"x");
+            // 同一行中再次获取同一列，之前返回的指针仍然有效
+            assert_eq!(rustdb_column_text(stmt, 1), text);
+            assert_eq!(CStr::from_ptr(rustdb_column_text(stmt, 0)), c"1");
+            assert_eq!(CStr::from_ptr(text), c"x");
+            assert_eq!(rustdb_column_double(stmt, 2), 1.5);
+
+            assert_eq!(rustdb_step(stmt), RUSTDB_ROW);
+            assert_eq!(rustdb_column_type(stmt, 1), RUSTDB_NULL);
+            assert!(rustdb_column_text(stmt, 1).is_null());
+            assert_eq!(rustdb_step(stmt), RUSTDB_DONE);
+            rustdb_finalize(stmt);
+
+            // 错误码和错误信息
+            assert_eq!(exec("select * from;", &mut stmt), RUSTDB_PARSE);
+            assert!(stmt.is_null());
+            assert_eq!(exec("select * from x;", &mut stmt), RUSTDB_ERROR);
+            assert!(!CStr::from_ptr(rustdb_errmsg(db)).is_empty());
+
+            assert_eq!(rustdb_close(db), RUSTDB_OK);
+        }
+    }
+
+    #[test]
+    fn test_capi_guard() {
+        // panic 被捕获并转换为返回值，不会跨越 C 接口
+        let code = guard(RUSTDB_ERROR, || -> c_int { panic!("boom") });
+        assert_eq!(code, RUSTDB_ERROR);
+        assert_eq!(guard(RUSTDB_ERROR, || RUSTDB_OK), RUSTDB_OK);
+        assert!(guard(ptr::null(), || -> *const c_char { panic!("boom") }).is_null());
+    }
+}
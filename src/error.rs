@@ -11,6 +11,19 @@ pub enum Error {
     Parse(String),
     Internal(String),
     WriteConflict,
+    ReadOnly,
+    // 事务已经被强制中止
+    Aborted,
+    // 等待写冲突时检测到了死锁
+    Deadlock,
+    // 语句被 KILL 取消
+    Cancelled,
+    // 语句执行超过了 statement_timeout
+    Timeout,
+    // 语句超过了资源限制
+    ResourceLimit(String),
+    // 写入的数据违反了约束，例如 UNIQUE 列中出现了重复的值
+    ConstraintViolation(String),
 }
 
 impl From<std::num::ParseIntError> for Error {
@@ -69,6 +82,13 @@ impl Display for Error {
             Error::Parse(err) => write!(f, "parse error {}", err),
             Error::Internal(err) => write!(f, "internal error {}", err),
             Error::WriteConflict => write!(f, "write conflict, try transaction"),
+            Error::ReadOnly => write!(f, "read-only transaction"),
+            Error::Aborted => write!(f, "transaction was aborted"),
+            Error::Deadlock => write!(f, "deadlock detected, try transaction"),
+            Error::Cancelled => write!(f, "statement was cancelled"),
+            Error::Timeout => write!(f, "statement timeout"),
+            Error::ResourceLimit(err) => write!(f, "resource limit exceeded, {}", err),
+            Error::ConstraintViolation(err) => write!(f, "constraint violation, {}", err),
         }
     }
 }
@@ -1,3 +1,9 @@
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod error;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod sql;
 pub mod storage;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,287 @@
+// Python 接口，接口风格参考 DB-API (PEP 249)
+//   import sqldb_rs
+//   conn = sqldb_rs.connect()
+//   cur = conn.execute("select * from t;")
+//   cur.fetchall()
+// 没有执行 BEGIN 时每条语句都在单独的事务中自动提交，commit/rollback 为空操作
+// 执行 BEGIN 之后 commit/rollback 提交或者回滚 BEGIN 开启的事务
+use std::path::PathBuf;
+
+use pyo3::{
+    create_exception, exceptions::PyException, prelude::*, types::PyTuple, IntoPyObjectExt,
+};
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::{kv::KVEngine, Engine, Session},
+        executor::ResultSet,
+        types::{Row, Value},
+    },
+    storage::{disk::DiskEngine, memory::MemoryEngine},
+};
+
+create_exception!(sqldb_rs, DatabaseError, PyException);
+create_exception!(sqldb_rs, ProgrammingError, DatabaseError);
+create_exception!(sqldb_rs, OperationalError, DatabaseError);
+create_exception!(sqldb_rs, InternalError, DatabaseError);
+create_exception!(sqldb_rs, IntegrityError, DatabaseError);
+
+fn to_pyerr(err: Error) -> PyErr {
+    match err {
+        Error::Parse(_) => ProgrammingError::new_err(err.to_string()),
+        Error::WriteConflict
+        | Error::ReadOnly
+        | Error::Aborted
+        | Error::Deadlock
+        | Error::Cancelled
+        | Error::Timeout
+        | Error::ResourceLimit(_) => OperationalError::new_err(err.to_string()),
+        Error::Internal(_) => InternalError::new_err(err.to_string()),
+        Error::ConstraintViolation(_) => IntegrityError::new_err(err.to_string()),
+    }
+}
+
+fn to_pyobject(py: Python<'_>, value: Value) -> PyResult<PyObject> {
+    match value {
+        Value::Null => Ok(py.None()),
+        Value::Boolean(b) => b.into_py_any(py),
+        Value::Integer(i) => i.into_py_any(py),
+        Value::Float(f) => f.into_py_any(py),
+        Value::String(s) => s.into_py_any(py),
+        Value::Point(x, y) => (x, y).into_py_any(py),
+        // 转换成 decimal.Decimal，不丢失精度
+        Value::Decimal(d) => py
+            .import("decimal")?
+            .getattr("Decimal")?
+            .call1((d.to_string(),))?
+            .into_py_any(py),
+        Value::Uuid(u) => py
+            .import("uuid")?
+            .getattr("UUID")?
+            .call1((u.to_string(),))?
+            .into_py_any(py),
+        // 转换成 Python 的 dict、list 等
+        Value::Json(j) => py
+            .import("json")?
+            .getattr("loads")?
+            .call1((j.to_string(),))?
+            .into_py_any(py),
+    }
+}
+
+fn to_pytuple(py: Python<'_>, row: Row) -> PyResult<Py<PyTuple>> {
+    let values = row
+        .into_iter()
+        .map(|v| to_pyobject(py, v))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(PyTuple::new(py, values)?.unbind())
+}
+
+enum Sessions {
+    Memory(Session<KVEngine<MemoryEngine>>),
+    Disk(Session<KVEngine<DiskEngine>>),
+}
+
+impl Sessions {
+    fn execute(&mut self, sql: &str) -> Result<ResultSet> {
+        match self {
+            Sessions::Memory(s) => s.execute(sql),
+            Sessions::Disk(s) => s.execute(sql),
+        }
+    }
+
+    fn in_transaction(&self) -> bool {
+        match self {
+            Sessions::Memory(s) => s.in_transaction(),
+            Sessions::Disk(s) => s.in_transaction(),
+        }
+    }
+}
+
+// 数据库连接
+#[pyclass(module = "sqldb_rs")]
+pub struct Connection {
+    session: Option<Sessions>,
+}
+
+impl Connection {
+    fn execute_sql(&mut self, sql: &str) -> PyResult<ResultSet> {
+        match &mut self.session {
+            Some(session) => session.execute(sql).map_err(to_pyerr),
+            None => Err(ProgrammingError::new_err(
+                "cannot operate on a closed connection",
+            )),
+        }
+    }
+
+    // 结束 BEGIN 开启的事务，没有事务时不需要执行
+    fn end_transaction(&mut self, sql: &str) -> PyResult<()> {
+        if self.session.as_ref().is_some_and(Sessions::in_transaction) {
+            self.execute_sql(sql)?;
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl Connection {
+    fn cursor(slf: Py<Self>) -> Cursor {
+        Cursor::new(slf)
+    }
+
+    // 创建游标并执行 SQL 语句
+    fn execute(slf: Py<Self>, py: Python<'_>, sql: &str) -> PyResult<Py<Cursor>> {
+        let cursor = Py::new(py, Cursor::new(slf))?;
+        cursor.borrow_mut(py).run(py, sql)?;
+        Ok(cursor)
+    }
+
+    fn commit(&mut self) -> PyResult<()> {
+        self.end_transaction("COMMIT;")
+    }
+
+    fn rollback(&mut self) -> PyResult<()> {
+        self.end_transaction("ROLLBACK;")
+    }
+
+    fn close(&mut self) {
+        self.session = None;
+    }
+}
+
+// 游标，保存查询的结果
+#[pyclass(module = "sqldb_rs")]
+pub struct Cursor {
+    connection: Py<Connection>,
+    columns: Vec<String>,
+    rows: std::vec::IntoIter<Row>,
+    #[pyo3(get)]
+    rowcount: i64,
+    #[pyo3(get, set)]
+    arraysize: usize,
+}
+
+impl Cursor {
+    fn new(connection: Py<Connection>) -> Self {
+        Self {
+            connection,
+            columns: Vec::new(),
+            rows: Vec::new().into_iter(),
+            rowcount: -1,
+            arraysize: 1,
+        }
+    }
+
+    fn run(&mut self, py: Python<'_>, sql: &str) -> PyResult<()> {
+        let result = self.connection.borrow_mut(py).execute_sql(sql)?;
+        (self.columns, self.rows, self.rowcount) = match result {
+            ResultSet::Scan { columns, rows } => {
+                let count = rows.len() as i64;
+                (columns, rows.into_iter(), count)
+            }
+            ResultSet::Insert { count }
+            | ResultSet::Update { count }
+            | ResultSet::Delete { count } => (Vec::new(), Vec::new().into_iter(), count as i64),
+            _ => (Vec::new(), Vec::new().into_iter(), -1),
+        };
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl Cursor {
+    fn execute<'py>(mut slf: PyRefMut<'py, Self>, sql: &str) -> PyResult<PyRefMut<'py, Self>> {
+        let py = slf.py();
+        slf.run(py, sql)?;
+        Ok(slf)
+    }
+
+    // 每一列为 (name, type_code, display_size, internal_size, precision, scale, null_ok)
+    #[getter]
+    fn description(&self, py: Python<'_>) -> PyResult<PyObject> {
+        if self.columns.is_empty() {
+            return Ok(py.None());
+        }
+        self.columns
+            .iter()
+            .map(|c| {
+                (
+                    c.as_str(),
+                    py.None(),
+                    py.None(),
+                    py.None(),
+                    py.None(),
+                    py.None(),
+                    py.None(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .into_py_any(py)
+    }
+
+    fn fetchone(&mut self, py: Python<'_>) -> PyResult<Option<Py<PyTuple>>> {
+        self.rows.next().map(|row| to_pytuple(py, row)).transpose()
+    }
+
+    #[pyo3(signature = (size = None))]
+    fn fetchmany(&mut self, py: Python<'_>, size: Option<usize>) -> PyResult<Vec<Py<PyTuple>>> {
+        let size = size.unwrap_or(self.arraysize);
+        self.rows
+            .by_ref()
+            .take(size)
+            .map(|row| to_pytuple(py, row))
+            .collect()
+    }
+
+    fn fetchall(&mut self, py: Python<'_>) -> PyResult<Vec<Py<PyTuple>>> {
+        self.rows.by_ref().map(|row| to_pytuple(py, row)).collect()
+    }
+
+    fn close(&mut self) {
+        self.rows = Vec::new().into_iter();
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<Py<PyTuple>>> {
+        self.fetchone(py)
+    }
+}
+
+// 打开数据库，不指定路径时使用内存存储
+#[pyfunction]
+#[pyo3(signature = (path = None))]
+fn connect(path: Option<PathBuf>) -> PyResult<Connection> {
+    let session = match path {
+        None => KVEngine::new(MemoryEngine::new())
+            .session()
+            .map(Sessions::Memory),
+        Some(path) => DiskEngine::new(path)
+            .and_then(|eng| KVEngine::new(eng).session())
+            .map(Sessions::Disk),
+    }
+    .map_err(to_pyerr)?;
+    Ok(Connection {
+        session: Some(session),
+    })
+}
+
+#[pymodule]
+fn sqldb_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("apilevel", "2.0")?;
+    m.add("threadsafety", 1)?;
+    m.add("paramstyle", "qmark")?;
+    m.add("DatabaseError", py.get_type::<DatabaseError>())?;
+    m.add("ProgrammingError", py.get_type::<ProgrammingError>())?;
+    m.add("OperationalError", py.get_type::<OperationalError>())?;
+    m.add("InternalError", py.get_type::<InternalError>())?;
+    m.add("IntegrityError", py.get_type::<IntegrityError>())?;
+    m.add_class::<Connection>()?;
+    m.add_class::<Cursor>()?;
+    m.add_function(wrap_pyfunction!(connect, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use crate::sql::parser::ast::{Consts, Direction, Expression, Operation, SelectItem, Statement};
+
+// 通过代码构造查询语句，不需要经过 SQL 解析
+// let stmt = Query::select("t").filter(col("id").eq(1)).limit(10).build();
+pub struct Query {
+    table_name: String,
+    select: Vec<SelectItem>,
+    distinct: bool,
+    filter: Option<Expression>,
+    group_by: Vec<Expression>,
+    having: Option<Expression>,
+    order_by: Vec<(Expression, Direction)>,
+    limit: Option<u64>,
+    as_of: Option<u64>,
+}
+
+impl Query {
+    // 查询表中的所有列
+    pub fn select(table_name: &str) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            select: vec![SelectItem::Wildcard(None)],
+            distinct: false,
+            filter: None,
+            group_by: vec![],
+            having: None,
+            order_by: vec![],
+            limit: None,
+            as_of: None,
+        }
+    }
+
+    // 指定 select 列表
+    pub fn columns(mut self, exprs: impl IntoIterator<Item = Expression>) -> Self {
+        self.select = exprs
+            .into_iter()
+            .map(|e| SelectItem::Expr(e, None))
+            .collect();
+        self
+    }
+
+    // 去掉结果中重复的行
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    pub fn filter(mut self, predicate: Expression) -> Self {
+        self.filter = Some(predicate);
+        self
+    }
+
+    pub fn group_by(mut self, exprs: impl IntoIterator<Item = Expression>) -> Self {
+        self.group_by = exprs.into_iter().collect();
+        self
+    }
+
+    pub fn having(mut self, predicate: Expression) -> Self {
+        self.having = Some(predicate);
+        self
+    }
+
+    // 按照表达式排序，可以多次调用，先添加的表达式优先
+    pub fn order_by(mut self, expr: Expression, direction: Direction) -> Self {
+        self.order_by.push((expr, direction));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn as_of(mut self, version: u64) -> Self {
+        self.as_of = Some(version);
+        self
+    }
+
+    pub fn build(self) -> Statement {
+        Statement::Select {
+            select: self.select,
+            distinct: self.distinct,
+            table_name: Some(self.table_name.into()),
+            table_args: None,
+            subquery: None,
+            alias: None,
+            joins: vec![],
+            filter: self.filter,
+            group_by: self.group_by,
+            having: self.having,
+            order_by: self.order_by,
+            limit: self.limit,
+            as_of: self.as_of,
+        }
+    }
+}
+
+impl From<Query> for Statement {
+    fn from(query: Query) -> Self {
+        query.build()
+    }
+}
+
+// 列名，可以带上表名，例如 col("t.a")
+pub fn col(name: &str) -> Expression {
+    match name.split_once('.') {
+        Some((table, name)) => Expression::Field(Some(table.to_string()), name.to_string()),
+        None => Expression::Field(None, name.to_string()),
+    }
+}
+
+impl Expression {
+    // 构造 self = other
+    pub fn eq(self, other: impl Into<Expression>) -> Expression {
+        Expression::Operation(Operation::Equal(Box::new(self), Box::new(other.into())))
+    }
+}
+
+impl From<bool> for Expression {
+    fn from(value: bool) -> Self {
+        Consts::Boolean(value).into()
+    }
+}
+
+impl From<i64> for Expression {
+    fn from(value: i64) -> Self {
+        Consts::Integer(value).into()
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(value: f64) -> Self {
+        Consts::Float(value).into()
+    }
+}
+
+impl From<&str> for Expression {
+    fn from(value: &str) -> Self {
+        Consts::String(value.to_string()).into()
+    }
+}
+
+impl From<String> for Expression {
+    fn from(value: String) -> Self {
+        Consts::String(value).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{col, Query};
+    use crate::{
+        error::Result,
+        sql::{
+            engine::{kv::KVEngine, Engine},
+            executor::ResultSet,
+            parser::{ast, Parser},
+            types::Value,
+        },
+        storage::memory::MemoryEngine,
+    };
+
+    #[test]
+    fn test_query_build() -> Result<()> {
+        assert_eq!(
+            Query::select("t").build(),
+            Parser::new("select * from t;").parse()?
+        );
+        assert_eq!(
+            Query::select("t")
+                .columns([col("a"), col("t.b")])
+                .as_of(3)
+                .build(),
+            Parser::new("select a, t.b from t as of version 3;").parse()?
+        );
+        assert_eq!(
+            Query::select("t").filter(col("id").eq(1)).limit(10).build(),
+            ast::Statement::Select {
+                select: vec![ast::SelectItem::Wildcard(None)],
+                distinct: false,
+                table_name: Some("t".into()),
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: Some(ast::Expression::Operation(ast::Operation::Equal(
+                    Box::new(ast::Expression::Field(None, "id".to_string())),
+                    Box::new(ast::Consts::Integer(1).into()),
+                ))),
+                group_by: vec![],
+                having: None,
+                order_by: vec![],
+                limit: Some(10),
+                as_of: None,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_execute() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, age int);")?;
+        s.execute("insert into t values (1, 'a', 10), (2, 'b', 20), (3, 'c', 20), (4, null, 20);")?;
+
+        let rows = |result| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            rows(s.execute_statement(Query::select("t").filter(col("id").eq(2)).into())?),
+            vec![vec![
+                Value::Integer(2),
+                Value::String("b".to_string()),
+                Value::Integer(20)
+            ]]
+        );
+        assert_eq!(
+            rows(
+                s.execute_statement(
+                    Query::select("t")
+                        .columns([col("id")])
+                        .filter(col("age").eq(20))
+                        .limit(2)
+                        .into()
+                )?
+            ),
+            vec![vec![Value::Integer(2)], vec![Value::Integer(3)]]
+        );
+        // LIMIT 在去重之后
+        assert_eq!(
+            rows(
+                s.execute_statement(
+                    Query::select("t")
+                        .columns([col("age")])
+                        .distinct()
+                        .limit(2)
+                        .into()
+                )?
+            ),
+            vec![vec![Value::Integer(10)], vec![Value::Integer(20)]]
+        );
+        // NULL 的比较结果不满足条件
+        assert_eq!(
+            rows(s.execute_statement(Query::select("t").filter(col("name").eq("d")).into())?),
+            Vec::<Vec<Value>>::new()
+        );
+        assert!(s
+            .execute_statement(Query::select("t").filter(col("x").eq(1)).into())
+            .is_err());
+        assert!(s
+            .execute_statement(Query::select("t").filter(col("id").eq("a")).into())
+            .is_err());
+
+        Ok(())
+    }
+}
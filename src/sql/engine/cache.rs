@@ -0,0 +1,165 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    error::Result,
+    sql::{executor::ResultSet, ident::Ident},
+};
+
+// 只读查询的结果缓存，以 SQL 文本作为 key，同一个引擎上的 session 共享
+// 每个表记录已提交的写入次数，事务提交时增加写入过的表的次数并删除这些表的缓存
+// 查询开始之前读取表的写入次数，写入缓存时如果次数发生了变化，说明执行期间有写入提交，结果不会被缓存
+#[derive(Debug, Default)]
+pub struct QueryCache {
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    // 最多缓存的查询数量，0 表示不缓存
+    capacity: usize,
+    // 每次访问递增，用于淘汰最久没有使用的结果
+    tick: u64,
+    // 表名 -> 已提交的写入次数
+    generations: HashMap<Ident, u64>,
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    table_name: Ident,
+    result: ResultSet,
+    last_used: u64,
+}
+
+impl QueryCache {
+    pub fn capacity(&self) -> Result<usize> {
+        Ok(self.state.lock()?.capacity)
+    }
+
+    // 修改缓存的大小，超出的结果按照最久没有使用的顺序淘汰
+    pub fn set_capacity(&self, capacity: usize) -> Result<()> {
+        let mut state = self.state.lock()?;
+        state.capacity = capacity;
+        while state.entries.len() > capacity {
+            state.evict();
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, sql: &str) -> Result<Option<ResultSet>> {
+        let mut state = self.state.lock()?;
+        state.tick += 1;
+        let tick = state.tick;
+        Ok(state.entries.get_mut(sql).map(|entry| {
+            entry.last_used = tick;
+            entry.result.clone()
+        }))
+    }
+
+    // 表已提交的写入次数，需要在查询开启事务之前读取
+    pub fn generation(&self, table_name: &str) -> Result<u64> {
+        Ok(self
+            .state
+            .lock()?
+            .generations
+            .get(table_name)
+            .copied()
+            .unwrap_or(0))
+    }
+
+    // 缓存查询的结果，generation 是查询开始之前读取的表的写入次数
+    pub fn insert(
+        &self,
+        sql: String,
+        table_name: Ident,
+        generation: u64,
+        result: ResultSet,
+    ) -> Result<()> {
+        let mut state = self.state.lock()?;
+        let current = state.generations.get(&table_name).copied().unwrap_or(0);
+        if state.capacity == 0 || current != generation {
+            return Ok(());
+        }
+        if !state.entries.contains_key(&sql) && state.entries.len() >= state.capacity {
+            state.evict();
+        }
+        state.tick += 1;
+        let last_used = state.tick;
+        state.entries.insert(
+            sql,
+            CacheEntry {
+                table_name,
+                result,
+                last_used,
+            },
+        );
+        Ok(())
+    }
+
+    // 写入这些表的事务已经提交，删除查询这些表的结果
+    pub fn invalidate<'a>(&self, tables: impl IntoIterator<Item = &'a Ident>) -> Result<()> {
+        let mut state = self.state.lock()?;
+        for table_name in tables {
+            *state.generations.entry(table_name.clone()).or_default() += 1;
+            state
+                .entries
+                .retain(|_, entry| &entry.table_name != table_name);
+        }
+        Ok(())
+    }
+}
+
+impl CacheState {
+    // 淘汰最久没有使用的结果
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(sql, _)| sql.clone());
+        if let Some(sql) = oldest {
+            self.entries.remove(&sql);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Result, sql::executor::ResultSet};
+
+    use super::QueryCache;
+
+    #[test]
+    fn test_query_cache() -> Result<()> {
+        let result = |n| ResultSet::Insert { count: n };
+        let cache = QueryCache::default();
+        // 默认不缓存
+        cache.insert("q1".to_string(), "t1".into(), 0, result(1))?;
+        assert_eq!(cache.get("q1")?, None);
+
+        cache.set_capacity(2)?;
+        cache.insert("q1".to_string(), "t1".into(), 0, result(1))?;
+        cache.insert("q2".to_string(), "t2".into(), 0, result(2))?;
+        assert_eq!(cache.get("q1")?, Some(result(1)));
+        // 缓存已满，淘汰最久没有使用的 q2
+        cache.insert("q3".to_string(), "t1".into(), 0, result(3))?;
+        assert_eq!(cache.get("q2")?, None);
+        assert_eq!(cache.get("q3")?, Some(result(3)));
+
+        // 写入 t1 之后，查询 t1 的结果都失效
+        let generation = cache.generation("t1")?;
+        cache.invalidate(&["t1".into()])?;
+        assert_eq!(cache.get("q1")?, None);
+        assert_eq!(cache.get("q3")?, None);
+        assert_eq!(cache.generation("t1")?, generation + 1);
+        // 执行期间有写入提交的结果不会被缓存
+        cache.insert("q1".to_string(), "t1".into(), generation, result(1))?;
+        assert_eq!(cache.get("q1")?, None);
+        cache.insert("q1".to_string(), "t1".into(), generation + 1, result(1))?;
+        assert_eq!(cache.get("q1")?, Some(result(1)));
+
+        cache.set_capacity(0)?;
+        assert_eq!(cache.get("q1")?, None);
+        Ok(())
+    }
+}
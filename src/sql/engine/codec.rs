@@ -0,0 +1,207 @@
+use crate::{
+    error::{Error, Result},
+    sql::types::{decimal::Decimal, json::Json, uuid::Uuid, Row, Value},
+};
+
+// 压缩的行编码格式，每个值以一个字节的类型标记开头
+//   NULL / FALSE / TRUE: 只有类型标记
+//   INTEGER: zigzag 变长编码
+//   FLOAT: 8 字节小端
+//   STRING: 字典中的编号，变长编码
+//   POINT: x 和 y 各 8 字节小端
+//   DECIMAL: 1 字节的小数位数和 16 字节小端的 mantissa
+//   UUID: 16 字节
+//   JSON: 文本的长度，变长编码，和 UTF-8 的文本
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_FLOAT: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_POINT: u8 = 6;
+const TAG_DECIMAL: u8 = 7;
+const TAG_UUID: u8 = 8;
+const TAG_JSON: u8 = 9;
+
+// 编码一行数据，dict 返回字符串在字典中的编号
+pub fn encode_row<F>(row: &[Value], mut dict: F) -> Result<Vec<u8>>
+where
+    F: FnMut(&str) -> Result<u64>,
+{
+    let mut buf = Vec::new();
+    for value in row {
+        match value {
+            Value::Null => buf.push(TAG_NULL),
+            Value::Boolean(false) => buf.push(TAG_FALSE),
+            Value::Boolean(true) => buf.push(TAG_TRUE),
+            Value::Integer(i) => {
+                buf.push(TAG_INTEGER);
+                // zigzag 编码，绝对值小的负数也能编码成较短的字节
+                encode_varint(&mut buf, ((i << 1) ^ (i >> 63)) as u64);
+            }
+            Value::Float(f) => {
+                buf.push(TAG_FLOAT);
+                buf.extend_from_slice(&f.to_le_bytes());
+            }
+            Value::String(s) => {
+                buf.push(TAG_STRING);
+                encode_varint(&mut buf, dict(s)?);
+            }
+            Value::Point(x, y) => {
+                buf.push(TAG_POINT);
+                buf.extend_from_slice(&x.to_le_bytes());
+                buf.extend_from_slice(&y.to_le_bytes());
+            }
+            Value::Decimal(d) => {
+                buf.push(TAG_DECIMAL);
+                buf.push(d.scale());
+                buf.extend_from_slice(&d.mantissa().to_le_bytes());
+            }
+            Value::Uuid(u) => {
+                buf.push(TAG_UUID);
+                buf.extend_from_slice(u.as_bytes());
+            }
+            Value::Json(j) => {
+                let text = j.to_string();
+                buf.push(TAG_JSON);
+                encode_varint(&mut buf, text.len() as u64);
+                buf.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+    Ok(buf)
+}
+
+// 解码一行数据，dict 根据编号返回字典中的字符串
+pub fn decode_row<F>(mut bytes: &[u8], mut dict: F) -> Result<Row>
+where
+    F: FnMut(u64) -> Result<String>,
+{
+    let mut row = Vec::new();
+    while let Some((&tag, rest)) = bytes.split_first() {
+        bytes = rest;
+        row.push(match tag {
+            TAG_NULL => Value::Null,
+            TAG_FALSE => Value::Boolean(false),
+            TAG_TRUE => Value::Boolean(true),
+            TAG_INTEGER => {
+                let v = decode_varint(&mut bytes)?;
+                Value::Integer((v >> 1) as i64 ^ -((v & 1) as i64))
+            }
+            TAG_FLOAT => Value::Float(decode_f64(&mut bytes)?),
+            TAG_STRING => Value::String(dict(decode_varint(&mut bytes)?)?),
+            TAG_POINT => Value::Point(decode_f64(&mut bytes)?, decode_f64(&mut bytes)?),
+            TAG_DECIMAL => {
+                let (scale, mantissa, rest) = bytes
+                    .split_first()
+                    .and_then(|(scale, rest)| Some((*scale, rest.split_first_chunk::<16>()?)))
+                    .map(|(scale, (mantissa, rest))| (scale, i128::from_le_bytes(*mantissa), rest))
+                    .ok_or(Error::Internal("unexpected end of row".to_string()))?;
+                bytes = rest;
+                Value::Decimal(Decimal::new(mantissa, scale)?)
+            }
+            TAG_UUID => {
+                let (uuid, rest) = bytes
+                    .split_first_chunk::<16>()
+                    .ok_or(Error::Internal("unexpected end of row".to_string()))?;
+                bytes = rest;
+                Value::Uuid(Uuid::from_bytes(*uuid))
+            }
+            TAG_JSON => {
+                let len = decode_varint(&mut bytes)? as usize;
+                let text = bytes
+                    .get(..len)
+                    .ok_or(Error::Internal("unexpected end of row".to_string()))?;
+                bytes = &bytes[len..];
+                let text = std::str::from_utf8(text)
+                    .map_err(|e| Error::Internal(format!("invalid JSON text: {}", e)))?;
+                Value::Json(Json::parse(text)?)
+            }
+            tag => return Err(Error::Internal(format!("invalid value tag {}", tag))),
+        });
+    }
+    Ok(row)
+}
+
+fn decode_f64(bytes: &mut &[u8]) -> Result<f64> {
+    let (f, rest) = bytes
+        .split_first_chunk::<8>()
+        .ok_or(Error::Internal("unexpected end of row".to_string()))?;
+    *bytes = rest;
+    Ok(f64::from_le_bytes(*f))
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn decode_varint(bytes: &mut &[u8]) -> Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&b, rest) = bytes
+            .split_first()
+            .ok_or(Error::Internal("unexpected end of row".to_string()))?;
+        *bytes = rest;
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(Error::Internal("varint is too long".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_row, encode_row};
+    use crate::{
+        error::Result,
+        sql::types::{decimal::Decimal, json::Json, uuid::Uuid, Value},
+    };
+
+    #[test]
+    fn test_codec() -> Result<()> {
+        let mut dict = Vec::<String>::new();
+        let row = vec![
+            Value::Null,
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Integer(0),
+            Value::Integer(-1),
+            Value::Integer(i64::MAX),
+            Value::Integer(i64::MIN),
+            Value::Float(1.5),
+            Value::String("hello".to_string()),
+            Value::String("hello".to_string()),
+            Value::String("".to_string()),
+            Value::Point(1.5, -2.0),
+            Value::Decimal(Decimal::parse("-12345678901234567890.1234").unwrap()),
+            Value::Decimal(Decimal::parse("0.00").unwrap()),
+            Value::Uuid(Uuid::new_v4()),
+            Value::Json(Json::parse(r#"{"a": [1, "中文"]}"#)?),
+        ];
+        let bytes = encode_row(&row, |s| {
+            Ok(match dict.iter().position(|d| d == s) {
+                Some(i) => i as u64,
+                None => {
+                    dict.push(s.to_string());
+                    dict.len() as u64 - 1
+                }
+            })
+        })?;
+        assert_eq!(dict, vec!["hello".to_string(), "".to_string()]);
+        assert!(bytes.len() < bincode::serialize(&row)?.len());
+        assert_eq!(decode_row(&bytes, |i| Ok(dict[i as usize].clone()))?, row);
+
+        // 小整数只需要两个字节
+        assert_eq!(encode_row(&[Value::Integer(-3)], |_| Ok(0))?, vec![3, 5]);
+
+        assert!(decode_row(&[3, 0x80], |_| Ok(String::new())).is_err());
+        assert!(decode_row(&[4, 0, 0], |_| Ok(String::new())).is_err());
+        assert!(decode_row(&[9], |_| Ok(String::new())).is_err());
+        Ok(())
+    }
+}
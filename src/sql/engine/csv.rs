@@ -0,0 +1,258 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        schema::{ExternalTable, Table},
+        types::{DataType, Row, Value},
+    },
+};
+
+// 按行读取外部表的 CSV 文件，每次只解析一条记录，不会把整个文件读入内存
+pub struct ExternalScan<'a> {
+    table: &'a Table,
+    reader: CsvReader<BufReader<File>>,
+    // 错误信息中数据的来源
+    source: String,
+}
+
+impl<'a> ExternalScan<'a> {
+    pub fn new(table: &'a Table, external: &ExternalTable) -> Result<Self> {
+        Self::open(table, external, format!("external table {}", table.name))
+    }
+
+    // 按照表的定义读取任意的 CSV 文件，例如 COPY FROM 导入的数据
+    pub fn open(table: &'a Table, external: &ExternalTable, source: String) -> Result<Self> {
+        let file = File::open(&external.location).map_err(|e| {
+            Error::Internal(format!(
+                "cannot open file {} for {}: {}",
+                external.location, source, e
+            ))
+        })?;
+        let mut reader = CsvReader::new(BufReader::new(file), external.delimiter);
+        // 跳过列名
+        if external.header {
+            reader.next_record()?;
+        }
+        Ok(Self {
+            table,
+            reader,
+            source,
+        })
+    }
+
+    // 把一条记录转换成表中的一行，没有引号的空字段是 NULL
+    fn parse_row(&self, record: Vec<Option<String>>) -> Result<Row> {
+        let line = self.reader.line;
+        if record.len() != self.table.columns.len() {
+            return Err(Error::Internal(format!(
+                "line {} of {} has {} fields, but the table has {} columns",
+                line,
+                self.source,
+                record.len(),
+                self.table.columns.len()
+            )));
+        }
+        let row = record
+            .into_iter()
+            .zip(&self.table.columns)
+            .map(|(field, col)| {
+                let Some(field) = field else {
+                    return Ok(Value::Null);
+                };
+                parse_value(&field, &col.datatype).ok_or_else(|| {
+                    Error::Internal(format!(
+                        "line {} of {}: invalid value {:?} for column {}",
+                        line, self.source, field, col.name
+                    ))
+                })
+            })
+            .collect::<Result<Row>>()?;
+        self.table
+            .validate_row(&row)
+            .map_err(|e| Error::Internal(format!("line {} of {}: {}", line, self.source, e)))?;
+        Ok(row)
+    }
+}
+
+impl Iterator for ExternalScan<'_> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.next_record() {
+            Ok(Some(record)) => Some(self.parse_row(record)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+// 字段转换成列的类型，和 CAST 的规则一致，字符串保留两端的空白
+fn parse_value(field: &str, datatype: &DataType) -> Option<Value> {
+    match datatype {
+        DataType::String => Some(Value::String(field.to_string())),
+        datatype => Value::String(field.to_string()).cast(*datatype).ok(),
+    }
+}
+
+// CSV 解析，字段可以用双引号括起来，引号中的双引号写成两个，引号中可以包含分隔符和换行
+struct CsvReader<R> {
+    reader: R,
+    delimiter: char,
+    // 最近一条记录开始的行号，从 1 开始
+    line: usize,
+    // 已经读取的行数
+    lines: usize,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    fn new(reader: R, delimiter: char) -> Self {
+        Self {
+            reader,
+            delimiter,
+            line: 0,
+            lines: 0,
+        }
+    }
+
+    // 读取下一条记录，跳过空行，文件结束时返回 None
+    fn next_record(&mut self) -> Result<Option<Vec<Option<String>>>> {
+        let mut record = String::new();
+        loop {
+            if record.is_empty() {
+                self.line = self.lines + 1;
+            }
+            if self.reader.read_line(&mut record)? == 0 {
+                if record.is_empty() {
+                    return Ok(None);
+                }
+                return Err(Error::Internal(format!(
+                    "unterminated quoted field at line {}",
+                    self.line
+                )));
+            }
+            self.lines += 1;
+            if record.trim_end_matches(['\r', '\n']).is_empty() {
+                record.clear();
+                continue;
+            }
+            // 引号中的换行属于字段的内容，需要继续读取下一行
+            if let Some(fields) = self.parse(&record)? {
+                return Ok(Some(fields));
+            }
+        }
+    }
+
+    // 解析一条完整的记录，引号没有结束时返回 None
+    fn parse(&self, record: &str) -> Result<Option<Vec<Option<String>>>> {
+        let record = record.strip_suffix('\n').unwrap_or(record);
+        let record = record.strip_suffix('\r').unwrap_or(record);
+        let mut chars = record.chars().peekable();
+        let mut fields = Vec::new();
+        loop {
+            let mut field = String::new();
+            if chars.next_if_eq(&'"').is_some() {
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                        None => return Ok(None),
+                    }
+                }
+                fields.push(Some(field));
+            } else {
+                while let Some(c) = chars.next_if(|&c| c != self.delimiter) {
+                    field.push(c);
+                }
+                fields.push((!field.is_empty()).then_some(field));
+            }
+            match chars.next() {
+                None => return Ok(Some(fields)),
+                Some(c) if c == self.delimiter => {}
+                Some(c) => {
+                    return Err(Error::Internal(format!(
+                        "unexpected character {:?} after quoted field at line {}",
+                        c, self.line
+                    )))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Result,
+        sql::types::{DataType, Value},
+    };
+
+    use super::{parse_value, CsvReader};
+
+    #[test]
+    fn test_csv_reader() -> Result<()> {
+        let data = "a,b,c\n1,,\"x, \"\"y\"\"\"\r\n\n2,\"\",\"multi\nline\"\n3;4\n";
+        let mut reader = CsvReader::new(data.as_bytes(), ',');
+        let field = |s: &str| Some(s.to_string());
+        assert_eq!(
+            reader.next_record()?,
+            Some(vec![field("a"), field("b"), field("c")])
+        );
+        // 没有引号的空字段是 None，引号中的空字符串保留
+        assert_eq!(
+            reader.next_record()?,
+            Some(vec![field("1"), None, field("x, \"y\"")])
+        );
+        assert_eq!(
+            reader.next_record()?,
+            Some(vec![field("2"), field(""), field("multi\nline")])
+        );
+        assert_eq!(reader.line, 4);
+        assert_eq!(reader.next_record()?, Some(vec![field("3;4")]));
+        assert_eq!(reader.line, 6);
+        assert_eq!(reader.next_record()?, None);
+
+        let mut reader = CsvReader::new("3;4;\n".as_bytes(), ';');
+        assert_eq!(
+            reader.next_record()?,
+            Some(vec![field("3"), field("4"), None])
+        );
+
+        // 格式错误
+        assert!(CsvReader::new("\"a\"b\n".as_bytes(), ',')
+            .next_record()
+            .is_err());
+        assert!(CsvReader::new("\"a\n".as_bytes(), ',')
+            .next_record()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            parse_value(" 12 ", &DataType::Integer),
+            Some(Value::Integer(12))
+        );
+        assert_eq!(
+            parse_value("1.5", &DataType::Float),
+            Some(Value::Float(1.5))
+        );
+        assert_eq!(
+            parse_value("TRUE", &DataType::Boolean),
+            Some(Value::Boolean(true))
+        );
+        assert_eq!(
+            parse_value("point(1 -2.5)", &DataType::Point),
+            Some(Value::Point(1.0, -2.5))
+        );
+        assert_eq!(parse_value("1.5", &DataType::Integer), None);
+        assert_eq!(parse_value("yes", &DataType::Boolean), None);
+        assert_eq!(parse_value("POINT(1)", &DataType::Point), None);
+        assert_eq!(parse_value("PT(1 2)", &DataType::Point), None);
+    }
+}
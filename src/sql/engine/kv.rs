@@ -1,108 +1,1066 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
+
 use serde::{Deserialize, Serialize};
 
 use crate::{
     error::{Error, Result},
     sql::{
-        schema::Table,
-        types::{Row, Value},
+        function::{TableFunction, TableFunctions},
+        ident::Ident,
+        parser::Parser,
+        schema::{Column, Compression, ExternalTable, Index, StorageLayout, Table, View},
+        types::{DataType, Row, Value},
+    },
+    storage::{
+        self,
+        engine::{prefix_end, Engine as StorageEngine},
+        keycode,
+        mvcc::{now_millis, BackupOptions, BackupProgress},
     },
-    storage::{self, engine::Engine as StorageEngine},
 };
 
-use super::{Engine, Transaction};
+use super::{
+    cache::QueryCache, codec, csv, Engine, Interrupt, RowChange, SessionRegistry, Transaction,
+};
 
 // KV Engine 定义
 pub struct KVEngine<E: StorageEngine> {
     pub kv: storage::mvcc::Mvcc<E>,
+    sessions: Arc<SessionRegistry>,
+    access: AccessStats,
+    cache: Arc<QueryCache>,
+    functions: Arc<TableFunctions>,
 }
 
 impl<E: StorageEngine> Clone for KVEngine<E> {
     fn clone(&self) -> Self {
         Self {
             kv: self.kv.clone(),
+            sessions: self.sessions.clone(),
+            access: self.access.clone(),
+            cache: self.cache.clone(),
+            functions: self.functions.clone(),
         }
     }
 }
 
-impl<E: StorageEngine> KVEngine<E> {
+impl<E: StorageEngine + 'static> KVEngine<E> {
     pub fn new(engine: E) -> Self {
+        Self::new_with_options(engine, storage::mvcc::MvccOptions::default())
+    }
+
+    pub fn new_with_options(engine: E, options: storage::mvcc::MvccOptions) -> Self {
         Self {
-            kv: storage::mvcc::Mvcc::new(engine),
+            kv: storage::mvcc::Mvcc::new_with_options(engine, options),
+            sessions: Arc::default(),
+            access: AccessStats::default(),
+            cache: Arc::default(),
+            functions: Arc::default(),
+        }
+    }
+
+    // 注册表函数，之后这个引擎上的 session 可以在 FROM 中使用
+    pub fn register_table_function(
+        &self,
+        name: &str,
+        function: impl TableFunction + 'static,
+    ) -> Result<()> {
+        self.functions.register(name, Arc::new(function))
+    }
+
+    // 关闭前调用，回滚所有还没有结束的事务并持久化数据，返回被回滚的事务版本号
+    pub fn shutdown(&self) -> Result<Vec<u64>> {
+        self.kv.shutdown()
+    }
+
+    // 订阅表的数据变更，从 from_version 版本开始获取已提交的行变更
+    pub fn watch(&self, table_name: Ident, from_version: u64) -> Result<Watch<E>> {
+        let txn = self.begin()?;
+        let table = txn.must_get_table(table_name.clone());
+        txn.commit()?;
+        let table = table?;
+        if table.storage == StorageLayout::Columnar {
+            return Err(Error::Internal(format!(
+                "cannot watch columnar table {}",
+                table_name
+            )));
+        }
+        if table.external.is_some() {
+            return Err(Error::Internal(format!(
+                "cannot watch external table {}",
+                table_name
+            )));
         }
+
+        Ok(Watch {
+            kv: self.kv.clone(),
+            table_name,
+            next_version: from_version,
+        })
+    }
+}
+
+// 表的数据变更订阅
+pub struct Watch<E: StorageEngine> {
+    kv: storage::mvcc::Mvcc<E>,
+    table_name: Ident,
+    next_version: u64,
+}
+
+impl<E: StorageEngine> Watch<E> {
+    // 获取上一次调用之后新提交的行变更，没有新的变更则返回空
+    pub fn poll(&mut self) -> Result<Vec<RowChange>> {
+        let prefix = KeyPrefix::Row(self.table_name.clone());
+        let (changes, next_version) = self
+            .kv
+            .changes(bincode::serialize(&prefix)?, self.next_version)?;
+        self.next_version = next_version;
+
+        // 压缩的行需要读取表的字典进行解码，只读事务不会分配版本号，也不会写入活跃事务列表
+        let txn = KVTransaction::new(self.kv.begin_read_only()?);
+        let decode = || -> Result<Vec<RowChange>> {
+            let table = txn.must_get_table(self.table_name.clone())?;
+            let mut dict = HashMap::new();
+            let mut results = Vec::new();
+            for change in changes {
+                let key = match bincode::deserialize(&change.key)? {
+                    Key::Row(_, value) => value,
+                    k => return Err(Error::Internal(format!("unexpected key {:?}", k))),
+                };
+                results.push(RowChange {
+                    version: change.version,
+                    key,
+                    row: change
+                        .value
+                        .map(|v| txn.decode_values(&table, &v, &mut dict))
+                        .transpose()?,
+                });
+            }
+            Ok(results)
+        };
+        let results = decode();
+        txn.commit()?;
+        results
     }
 }
 
-impl<E: StorageEngine> Engine for KVEngine<E> {
+impl<E: StorageEngine + 'static> Engine for KVEngine<E> {
     type Transaction = KVTransaction<E>;
 
     fn begin(&self) -> Result<Self::Transaction> {
-        Ok(Self::Transaction::new(self.kv.begin()?))
+        Ok(Self::Transaction::with_shared(
+            self.kv.begin()?,
+            self.access.clone(),
+            self.cache.clone(),
+            self.functions.clone(),
+        ))
+    }
+
+    fn begin_as_of(&self, version: u64) -> Result<Self::Transaction> {
+        Ok(Self::Transaction::with_shared(
+            self.kv.begin_as_of(version)?,
+            self.access.clone(),
+            self.cache.clone(),
+            self.functions.clone(),
+        ))
+    }
+
+    fn options(&self) -> Result<storage::mvcc::MvccOptions> {
+        self.kv.options()
+    }
+
+    fn set_options(&self, options: storage::mvcc::MvccOptions) -> Result<()> {
+        self.kv.set_options(options)
+    }
+
+    fn sessions(&self) -> Arc<SessionRegistry> {
+        self.sessions.clone()
+    }
+
+    fn query_cache(&self) -> Arc<QueryCache> {
+        self.cache.clone()
+    }
+
+    fn open(&self, path: &str) -> Result<Self> {
+        Ok(Self::new(E::open(path.into())?))
     }
 }
 
 // KV Transaction 定义，实际上对存储引擎中 MvccTransaction 的封装
 pub struct KVTransaction<E: StorageEngine> {
     txn: storage::mvcc::MvccTransaction<E>,
+    interrupt: Interrupt,
+    access: AccessStats,
+    cache: Arc<QueryCache>,
+    functions: Arc<TableFunctions>,
+    // 当前事务写入过的表，提交之后需要删除查询这些表的缓存
+    written: HashSet<Ident>,
+    // 当前事务读取过的表信息，事务中修改表结构时更新，不存在的表也会缓存
+    schemas: Mutex<HashMap<Ident, Option<Table>>>,
+    // 当前事务已经分配的字典编号的个数
+    dictionary_ids: u64,
 }
 
 impl<E: StorageEngine> KVTransaction<E> {
     pub fn new(txn: storage::mvcc::MvccTransaction<E>) -> Self {
-        Self { txn }
+        Self::with_shared(txn, AccessStats::default(), Arc::default(), Arc::default())
     }
-}
 
-impl<E: StorageEngine> Transaction for KVTransaction<E> {
-    fn commit(&self) -> Result<()> {
-        Ok(())
+    // 表的访问统计记录到 access 中，查询缓存使用 cache，表函数使用 functions，同一个引擎上的事务共享
+    fn with_shared(
+        txn: storage::mvcc::MvccTransaction<E>,
+        access: AccessStats,
+        cache: Arc<QueryCache>,
+        functions: Arc<TableFunctions>,
+    ) -> Self {
+        Self {
+            txn,
+            interrupt: Interrupt::default(),
+            access,
+            cache,
+            functions,
+            written: HashSet::new(),
+            schemas: Mutex::default(),
+            dictionary_ids: 0,
+        }
     }
 
-    fn rollback(&self) -> Result<()> {
+    // 记录一次对表的访问
+    fn record_access(&self, table_name: &Ident, f: impl FnOnce(&mut TableAccess)) -> Result<()> {
+        let mut access = self.access.lock()?;
+        f(access.entry(table_name.clone()).or_default());
         Ok(())
     }
 
-    fn create_row(&mut self, table_name: String, row: Row) -> Result<()> {
+    // 获取可以写入数据的表，系统表和外部表都是只读的
+    fn must_get_writable_table(&self, table_name: Ident) -> Result<Table> {
         let table = self.must_get_table(table_name.clone())?;
-        // 校验行的有效性
-        for (i, col) in table.columns.iter().enumerate() {
-            match row[i].datatype() {
-                None if col.nullable => {}
+        if is_system_table(&table.name) {
+            return Err(Error::Internal(format!(
+                "system table {} is read-only",
+                table_name
+            )));
+        }
+        if table.external.is_some() {
+            return Err(Error::Internal(format!(
+                "external table {} is read-only",
+                table_name
+            )));
+        }
+        Ok(table)
+    }
+
+    // 读取索引项指向的行，过滤掉已经过期的行，seek 表示这次访问是等值查找
+    fn index_rows(
+        &self,
+        table: &Table,
+        index: &Index,
+        keys: Vec<Vec<u8>>,
+        seek: bool,
+    ) -> Result<Vec<Row>> {
+        let mut dict = HashMap::new();
+        let mut rows = Vec::new();
+        for value in self.txn.get_many(keys)? {
+            match value {
+                Some(v) => rows.push(self.decode_values(table, &v, &mut dict)?),
                 None => {
                     return Err(Error::Internal(format!(
-                        "column {} cannot be null",
-                        col.name
+                        "index {} of table {} refers to a missing row",
+                        index.name, table.name
                     )))
                 }
-                Some(dt) if dt != col.datatype => {
-                    return Err(Error::Internal(format!(
-                        "column {} type mismatch",
-                        col.name
-                    )))
+            }
+        }
+        self.interrupt.examine(&rows)?;
+        self.record_access(&table.name, |a| {
+            a.rows_read += rows.len() as u64;
+            let access = a.indexes.entry(index.name.clone()).or_default();
+            if seek {
+                access.seeks += 1;
+            } else {
+                access.scans += 1;
+            }
+            access.rows_read += rows.len() as u64;
+        })?;
+        if let Some(ttl) = table.ttl {
+            let now = now_secs();
+            rows.retain(|row| !ttl.is_expired(&row[ttl.column], now));
+        }
+        Ok(rows)
+    }
+
+    // 查询语句中读取了这个表或者视图的视图，column 不为 None 时只查找还使用了这个列名的视图
+    // 视图保存的是查询语句的原文，修改表名或者列名之后视图就无法再展开
+    fn dependent_view(&self, table_name: &Ident, column: Option<&str>) -> Result<Option<Ident>> {
+        for result in self
+            .txn
+            .scan_prefix(bincode::serialize(&KeyPrefix::View)?)?
+        {
+            let view: View = bincode::deserialize(&result.value)?;
+            let stmt = Parser::new(&format!("{};", view.query)).parse()?;
+            if stmt.references_table(table_name)
+                && column.is_none_or(|column| stmt.references_column(column))
+            {
+                return Ok(Some(view.name));
+            }
+        }
+        Ok(None)
+    }
+
+    // 将一行数据编码成存储的 key value
+    // 暂时以第一列作为主键，一行数据的唯一标识，todo
+    fn encode_row(&mut self, table: &Table, row: &Row) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        match table.storage {
+            StorageLayout::Row => {
+                let id = Key::Row(table.name.clone(), row[0].clone());
+                let value = self.encode_values(table, row)?;
+                Ok(vec![(bincode::serialize(&id)?, value)])
+            }
+            // 列存的每一列分别保存，key 中的主键保证各列的顺序一致
+            StorageLayout::Columnar => row
+                .iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    let id = Key::Column(table.name.clone(), i, row[0].clone());
+                    let value = self.encode_values(table, std::slice::from_ref(value))?;
+                    Ok((bincode::serialize(&id)?, value))
+                })
+                .collect(),
+        }
+    }
+
+    // 一行数据保存的所有 key，列存的表每一列一个 key
+    fn row_keys(&self, table: &Table, id: &Value) -> Result<Vec<Vec<u8>>> {
+        match table.storage {
+            StorageLayout::Row => Ok(vec![bincode::serialize(&Key::Row(
+                table.name.clone(),
+                id.clone(),
+            ))?]),
+            StorageLayout::Columnar => (0..table.columns.len())
+                .map(|i| {
+                    Ok(bincode::serialize(&Key::Column(
+                        table.name.clone(),
+                        i,
+                        id.clone(),
+                    ))?)
+                })
+                .collect(),
+        }
+    }
+
+    // 行在所有二级索引中的 key 和 value
+    fn index_entries(&self, table: &Table, row: &Row) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        for index in &table.indexes {
+            entries.extend(index_entry(&table.name, index, row)?);
+        }
+        Ok(entries)
+    }
+
+    // 行改变之后更新二级索引，删除旧的行中不再需要的索引项，写入新的行中增加的索引项
+    fn update_indexes(
+        &mut self,
+        table: &Table,
+        old: Option<&Row>,
+        new: Option<&Row>,
+    ) -> Result<()> {
+        let old = match old {
+            Some(row) => self.index_entries(table, row)?,
+            None => Vec::new(),
+        };
+        let new = match new {
+            Some(row) => self.index_entries(table, row)?,
+            None => Vec::new(),
+        };
+        for (key, _) in old.iter().filter(|entry| !new.contains(entry)) {
+            self.txn.delete(key.clone())?;
+        }
+        for (key, value) in new.into_iter().filter(|entry| !old.contains(entry)) {
+            self.txn.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    // 行在唯一索引中的值不能被其他行使用，exclude 中的主键的行就是正在修改的行，它们的索引项不算作冲突
+    fn check_unique(
+        &self,
+        table: &Table,
+        row: &Row,
+        exclude: impl Fn(&Value) -> bool,
+    ) -> Result<()> {
+        for (index, prefix) in unique_keys(table, row)? {
+            for result in self.txn.scan_prefix(prefix)? {
+                let id: Value = bincode::deserialize(&result.value)?;
+                if id != row[0] && !exclude(&id) {
+                    return Err(unique_violation(table, index, row));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 读取行存的表中主键是 id 的行
+    fn get_row(&self, table: &Table, id: &Value) -> Result<Option<Row>> {
+        let key = bincode::serialize(&Key::Row(table.name.clone(), id.clone()))?;
+        self.txn
+            .get(key)?
+            .map(|v| self.decode_values(table, &v, &mut HashMap::new()))
+            .transpose()
+    }
+
+    // 删除表的所有二级索引项
+    fn clear_indexes(&mut self, table: &Table) -> Result<()> {
+        for index in &table.indexes {
+            let prefix = KeyPrefix::Index(table.name.clone(), index.name.clone());
+            for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+                self.check_interrupt()?;
+                self.txn.delete(result.key)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 按照表的压缩方式编码一组值
+    fn encode_values(&mut self, table: &Table, values: &[Value]) -> Result<Vec<u8>> {
+        match table.compression {
+            Compression::None => Ok(bincode::serialize(values)?),
+            Compression::Dictionary => {
+                codec::encode_row(values, |s| self.dictionary_id(&table.name, s))
+            }
+        }
+    }
+
+    // 解码一组值，dict 缓存已经读取过的字典项
+    fn decode_values(
+        &self,
+        table: &Table,
+        bytes: &[u8],
+        dict: &mut HashMap<u64, String>,
+    ) -> Result<Row> {
+        match table.compression {
+            Compression::None => Ok(bincode::deserialize(bytes)?),
+            Compression::Dictionary => codec::decode_row(bytes, |id| {
+                if let Some(s) = dict.get(&id) {
+                    return Ok(s.clone());
+                }
+                let key = Key::Dictionary(table.name.clone(), id);
+                let s: String = match self.txn.get(bincode::serialize(&key)?)? {
+                    Some(v) => bincode::deserialize(&v)?,
+                    None => {
+                        return Err(Error::Internal(format!(
+                            "dictionary entry {} of table {} does not exist",
+                            id, table.name
+                        )))
+                    }
+                };
+                dict.insert(id, s.clone());
+                Ok(s)
+            }),
+        }
+    }
+
+    // 系统表 sys_tables，每个表一行统计信息
+    fn scan_system_tables(&self) -> Result<Vec<Row>> {
+        let tables = self
+            .txn
+            .scan_prefix(bincode::serialize(&KeyPrefix::Table)?)?
+            .into_iter()
+            .map(|result| Ok(bincode::deserialize(&result.value)?))
+            .collect::<Result<Vec<Table>>>()?;
+        // 所有表的统计信息一次读取
+        let stats = self.txn.get_many(
+            tables
+                .iter()
+                .map(|table| Ok(bincode::serialize(&Key::Statistics(table.name.clone()))?))
+                .collect::<Result<_>>()?,
+        )?;
+        let mut rows = Vec::new();
+        for (table, stats) in tables.into_iter().zip(stats) {
+            // 行存的表只有一个前缀，列存的表每一列一个前缀，第一列是主键
+            let prefixes = match table.storage {
+                StorageLayout::Row => vec![KeyPrefix::Row(table.name.clone())],
+                StorageLayout::Columnar => (0..table.columns.len())
+                    .map(|i| KeyPrefix::Column(table.name.clone(), i))
+                    .collect(),
+            };
+            let (mut count, mut bytes, mut versions) = (0, 0, 0);
+            for (i, prefix) in prefixes.iter().enumerate() {
+                let prefix = bincode::serialize(prefix)?;
+                let results = self.txn.scan_prefix(prefix.clone())?;
+                if i == 0 {
+                    count = results.len();
                 }
-                _ => {}
+                bytes += results
+                    .iter()
+                    .map(|r| r.key.len() + r.value.len())
+                    .sum::<usize>();
+                versions += self.txn.version_count(prefix)?;
+            }
+            let stats: Option<TableStatistics> =
+                stats.map(|v| bincode::deserialize(&v)).transpose()?;
+            let access = self
+                .access
+                .lock()?
+                .get(&table.name)
+                .cloned()
+                .unwrap_or_default();
+
+            rows.push(vec![
+                Value::String(table.name.into()),
+                Value::Integer(count as i64),
+                Value::Integer(bytes as i64),
+                Value::Integer(versions as i64),
+                stats.map_or(Value::Null, |s| Value::Integer(s.version as i64)),
+                Value::Integer(access.scans as i64),
+                Value::Integer(access.rows_read as i64),
+                Value::Integer(access.rows_written as i64),
+            ]);
+        }
+        Ok(rows)
+    }
+
+    // 系统表 sys_indexes，每个二级索引一行访问统计，主键的隐式索引不在其中
+    fn scan_system_indexes(&self) -> Result<Vec<Row>> {
+        let access = self.access.lock()?;
+        let mut rows = Vec::new();
+        for result in self
+            .txn
+            .scan_prefix(bincode::serialize(&KeyPrefix::Table)?)?
+        {
+            let table: Table = bincode::deserialize(&result.value)?;
+            for index in &table.indexes {
+                let stats = access
+                    .get(&table.name)
+                    .and_then(|a| a.indexes.get(&index.name))
+                    .copied()
+                    .unwrap_or_default();
+                rows.push(vec![
+                    Value::String(table.name.to_string()),
+                    Value::String(index.name.to_string()),
+                    Value::Integer(stats.scans as i64),
+                    Value::Integer(stats.seeks as i64),
+                    Value::Integer(stats.rows_read as i64),
+                ]);
+            }
+        }
+        Ok(rows)
+    }
+
+    // 读取外部表的数据文件，边读取边解析，每解析一批行检查一次中断
+    fn scan_external(&self, table: &Table, external: &ExternalTable) -> Result<Vec<Row>> {
+        let mut scan = csv::ExternalScan::new(table, external)?;
+        let mut rows = Vec::new();
+        loop {
+            self.check_interrupt()?;
+            let start = rows.len();
+            for row in scan.by_ref().take(1024) {
+                rows.push(row?);
             }
+            if rows.len() == start {
+                break;
+            }
+            self.interrupt.examine(&rows[start..])?;
+        }
+        self.record_access(&table.name, |a| {
+            a.scans += 1;
+            a.rows_read += rows.len() as u64;
+        })?;
+        Ok(rows)
+    }
+
+    // 获取字符串在表字典中的编号，不存在则分配一个新的编号
+    fn dictionary_id(&mut self, table_name: &Ident, s: &str) -> Result<u64> {
+        let key = bincode::serialize(&Key::DictionaryId(table_name.clone(), s.to_string()))?;
+        if let Some(v) = self.txn.get(key.clone())? {
+            return Ok(bincode::deserialize(&v)?);
+        }
+
+        // 每个事务使用按照版本号划分的一段编号，并发写入新字符串的事务不会修改同一个 key
+        if self.dictionary_ids >= 1 << DICTIONARY_ID_BITS {
+            return Err(Error::Internal(format!(
+                "too many new strings for the dictionary of table {} in one transaction",
+                table_name
+            )));
         }
+        let id = self
+            .txn
+            .version()
+            .checked_mul(1 << DICTIONARY_ID_BITS)
+            .ok_or_else(|| Error::Internal("dictionary id is out of range".to_string()))?
+            + self.dictionary_ids;
+        self.dictionary_ids += 1;
+        self.txn.set(key, bincode::serialize(&id)?)?;
+        self.txn.set(
+            bincode::serialize(&Key::Dictionary(table_name.clone(), id))?,
+            bincode::serialize(s)?,
+        )?;
+        Ok(id)
+    }
+}
 
-        // 存放数据
-        // 暂时以第一列作为主键，一行数据的唯一标识，todo
-        let id = Key::Row(table_name.clone(), row[0].clone());
-        let value = bincode::serialize(&row)?;
-        self.txn.set(bincode::serialize(&id)?, value)?;
+impl<E: StorageEngine> Transaction for KVTransaction<E> {
+    fn version(&self) -> u64 {
+        self.txn.version()
+    }
 
+    fn commit(&self) -> Result<()> {
+        self.txn.commit()?;
+        // 写入已经可见之后再删除缓存，避免查询缓存提交之前的结果
+        if !self.written.is_empty() {
+            self.cache.invalidate(&self.written)?;
+        }
         Ok(())
     }
 
-    fn scan_table(&self, table_name: String) -> Result<Vec<Row>> {
+    fn rollback(&self) -> Result<()> {
+        self.txn.rollback()
+    }
+
+    fn abort_transaction(&self, version: u64) -> Result<()> {
+        self.txn.abort(version)
+    }
+
+    fn set_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt = interrupt;
+    }
+
+    fn check_interrupt(&self) -> Result<()> {
+        self.interrupt.check()
+    }
+
+    fn allocate_rows(&self, rows: &[Row]) -> Result<()> {
+        self.interrupt.allocate(rows)
+    }
+
+    fn checkpoint(&self) -> Result<()> {
+        self.txn.checkpoint()
+    }
+
+    fn backup(&self, path: &str, options: BackupOptions) -> Result<BackupProgress> {
+        // wasm32 下没有文件系统
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = options;
+            Err(Error::Internal(format!(
+                "cannot backup to {}, backup is not supported on wasm32",
+                path
+            )))
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            // 备份写入到新的文件中，可以直接作为 DiskEngine 打开
+            let path = std::path::PathBuf::from(path);
+            if path.exists() {
+                return Err(Error::Internal(format!(
+                    "backup file {} already exists",
+                    path.display()
+                )));
+            }
+            let mut dst = storage::disk::DiskEngine::new(path.clone())?;
+            let result = self.txn.backup(&mut dst, options, |_| {});
+            drop(dst);
+            // 备份失败时删除写了一半的文件
+            if result.is_err() {
+                std::fs::remove_file(storage::disk::DiskEngine::hint_path(&path))?;
+                std::fs::remove_file(&path)?;
+            }
+            result
+        }
+    }
+
+    fn create_row(&mut self, table_name: Ident, row: Row) -> Result<()> {
+        let table = self.must_get_writable_table(table_name.clone())?;
+        // 校验行的有效性
+        table.validate_row(&row)?;
+        check_primary_key(&table, &row)?;
+
+        // 主键不能和已有的行重复，列存的表只需要判断主键列是否存在
+        let key = self.row_keys(&table, &row[0])?.swap_remove(0);
+        if self.txn.get(key)?.is_some() {
+            return Err(duplicate_primary_key(&table, &row[0]));
+        }
+        if !table.indexes.is_empty() {
+            self.check_unique(&table, &row, |_| false)?;
+            self.update_indexes(&table, None, Some(&row))?;
+        }
+        for (key, value) in self.encode_row(&table, &row)? {
+            self.txn.set(key, value)?;
+        }
+
+        self.written.insert(table_name.clone());
+        self.record_access(&table_name, |a| a.rows_written += 1)
+    }
+
+    // 写入的数据合并成一次批量写入，冲突时不等待锁
+    fn insert_rows(&mut self, table_name: Ident, rows: Vec<Row>) -> Result<()> {
+        let table = self.must_get_writable_table(table_name.clone())?;
+        // 主键不能是 NULL，也不能和这一批中前面的行或者表中已有的行重复
+        let mut keys = Vec::with_capacity(rows.len());
+        let mut seen = HashSet::with_capacity(rows.len());
+        for row in &rows {
+            table.validate_row(row)?;
+            check_primary_key(&table, row)?;
+            let key = self.row_keys(&table, &row[0])?.swap_remove(0);
+            if !seen.insert(key.clone()) {
+                return Err(duplicate_primary_key(&table, &row[0]));
+            }
+            keys.push(key);
+        }
+        for (row, value) in rows.iter().zip(self.txn.get_many(keys)?) {
+            if value.is_some() {
+                return Err(duplicate_primary_key(&table, &row[0]));
+            }
+        }
+
+        let mut batch = Vec::with_capacity(rows.len());
+        // 这一批中的行在唯一索引中的值
+        let mut unique = HashSet::new();
+        for row in &rows {
+            for (key, value) in self.encode_row(&table, row)? {
+                batch.push((key, Some(value.into())));
+            }
+            if table.indexes.is_empty() {
+                continue;
+            }
+            self.check_unique(&table, row, |_| false)?;
+            for (index, key) in unique_keys(&table, row)? {
+                if !unique.insert(key) {
+                    return Err(unique_violation(&table, index, row));
+                }
+            }
+            for (key, value) in self.index_entries(&table, row)? {
+                batch.push((key, Some(value.into())));
+            }
+        }
+        self.txn.write_batch(batch)?;
+
+        self.written.insert(table_name.clone());
+        self.record_access(&table_name, |a| a.rows_written += rows.len() as u64)
+    }
+
+    fn update_row(&mut self, table_name: Ident, id: &Value, row: Row) -> Result<()> {
+        let table = self.must_get_writable_table(table_name.clone())?;
+        table.validate_row(&row)?;
+        check_primary_key(&table, &row)?;
+        let old = match table.indexes.is_empty() {
+            true => None,
+            false => self.get_row(&table, id)?,
+        };
+
+        // 主键改变时删除原来的行，新的主键不能和其他行重复
+        if *id != row[0] {
+            // 列存的表只需要判断主键列是否存在
+            let key = self.row_keys(&table, &row[0])?.swap_remove(0);
+            if self.txn.get(key)?.is_some() {
+                return Err(duplicate_primary_key(&table, &row[0]));
+            }
+            for key in self.row_keys(&table, id)? {
+                self.txn.delete(key)?;
+            }
+        }
+        if old.is_some() {
+            self.check_unique(&table, &row, |other| other == id)?;
+        }
+        for (key, value) in self.encode_row(&table, &row)? {
+            self.txn.set(key, value)?;
+        }
+        if old.is_some() {
+            self.update_indexes(&table, old.as_ref(), Some(&row))?;
+        }
+
+        self.written.insert(table_name.clone());
+        self.record_access(&table_name, |a| a.rows_written += 1)
+    }
+
+    fn delete_row(&mut self, table_name: Ident, id: &Value) -> Result<()> {
+        let table = self.must_get_writable_table(table_name.clone())?;
+        if !table.indexes.is_empty() {
+            let old = self.get_row(&table, id)?;
+            self.update_indexes(&table, old.as_ref(), None)?;
+        }
+        for key in self.row_keys(&table, id)? {
+            self.txn.delete(key)?;
+        }
+
+        self.written.insert(table_name.clone());
+        self.record_access(&table_name, |a| a.rows_written += 1)
+    }
+
+    fn scan_table(&self, table_name: Ident) -> Result<Vec<Row>> {
+        if table_name == SYSTEM_TABLES {
+            return self.scan_system_tables();
+        }
+        if table_name == SYSTEM_INDEXES {
+            return self.scan_system_indexes();
+        }
+        let table = self.must_get_table(table_name.clone())?;
+        if let Some(external) = &table.external {
+            return self.scan_external(&table, external);
+        }
+        if table.storage == StorageLayout::Columnar {
+            let columns = (0..table.columns.len()).collect::<Vec<_>>();
+            return self.scan_table_columns(table_name, &columns);
+        }
+
         let prefix = KeyPrefix::Row(table_name.clone());
         let results = self.txn.scan_prefix(bincode::serialize(&prefix)?)?;
 
+        let mut dict = HashMap::new();
         let mut rows = Vec::new();
-        for result in results {
-            let row: Row = bincode::deserialize(&result.value)?;
-            rows.push(row);
+        for batch in results.chunks(1024) {
+            // 解码大表时也需要能够及时中断
+            self.check_interrupt()?;
+            let start = rows.len();
+            for result in batch {
+                rows.push(self.decode_values(&table, &result.value, &mut dict)?);
+            }
+            self.interrupt.examine(&rows[start..])?;
+        }
+        self.record_access(&table_name, |a| {
+            a.scans += 1;
+            a.rows_read += rows.len() as u64;
+        })?;
+        if let Some(ttl) = table.ttl {
+            let now = now_secs();
+            rows.retain(|row| !ttl.is_expired(&row[ttl.column], now));
+        }
+        Ok(rows)
+    }
+
+    fn scan_table_columns(&self, table_name: Ident, columns: &[usize]) -> Result<Vec<Row>> {
+        let table = self.must_get_table(table_name.clone())?;
+        if table.storage == StorageLayout::Row {
+            return self.scan_table(table_name);
+        }
+
+        // 第一列是主键，用来确定表中的行数
+        let mut dict = HashMap::new();
+        let mut scan_column = |i: usize| -> Result<Vec<Value>> {
+            let prefix = KeyPrefix::Column(table_name.clone(), i);
+            let mut values = Vec::new();
+            for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+                match self.decode_values(&table, &result.value, &mut dict)?.pop() {
+                    Some(value) => values.push(value),
+                    None => return Err(Error::Internal("empty column value".to_string())),
+                }
+            }
+            Ok(values)
+        };
+        let keys = scan_column(0)?;
+        let mut rows = vec![vec![Value::Null; table.columns.len()]; keys.len()];
+        for (row, key) in rows.iter_mut().zip(keys) {
+            row[0] = key;
+        }
+        // 判断行是否过期需要读取时间列
+        let ttl_column = table.ttl.map(|ttl| ttl.column);
+        for &i in columns
+            .iter()
+            .chain(ttl_column.iter().filter(|i| !columns.contains(i)))
+            .filter(|&&i| i != 0)
+        {
+            if i >= table.columns.len() {
+                return Err(Error::Internal(format!("column index {} out of bound", i)));
+            }
+            self.check_interrupt()?;
+            let values = scan_column(i)?;
+            if values.len() != rows.len() {
+                return Err(Error::Internal(format!(
+                    "column {} of table {} is corrupted",
+                    table.columns[i].name, table_name
+                )));
+            }
+            for (row, value) in rows.iter_mut().zip(values) {
+                row[i] = value;
+            }
+        }
+        self.interrupt.examine(&rows)?;
+        self.record_access(&table_name, |a| {
+            a.scans += 1;
+            a.rows_read += rows.len() as u64;
+        })?;
+        if let Some(ttl) = table.ttl {
+            let now = now_secs();
+            rows.retain(|row| !ttl.is_expired(&row[ttl.column], now));
         }
         Ok(rows)
     }
 
+    fn scan_index(
+        &self,
+        table_name: Ident,
+        index: &Ident,
+        prefix: &[Value],
+        range: (Bound<Value>, Bound<Value>),
+    ) -> Result<Vec<Row>> {
+        let table = self.must_get_table(table_name.clone())?;
+        let Some(index) = table.indexes.iter().find(|i| i.name == *index) else {
+            return Err(Error::Internal(format!(
+                "index {} does not exist on table {}",
+                index, table_name
+            )));
+        };
+        if index.grid.is_some() {
+            return Err(Error::Internal(format!(
+                "index {} on table {} is a spatial index",
+                index.name, table_name
+            )));
+        }
+        let ranged = range != (Bound::Unbounded, Bound::Unbounded);
+        if prefix.is_empty() && !ranged || prefix.len() + ranged as usize > index.columns.len() {
+            return Err(Error::Internal(format!(
+                "invalid key for index {} on table {}",
+                index.name, table_name
+            )));
+        }
+        let seek = prefix.len() == index.columns.len();
+        // NULL 不等于任何值，也不在任何范围中
+        let bounds = [&range.0, &range.1];
+        if prefix
+            .iter()
+            .chain(bounds.into_iter().filter_map(|b| match b {
+                Bound::Included(v) | Bound::Excluded(v) => Some(v),
+                Bound::Unbounded => None,
+            }))
+            .any(|v| *v == Value::Null)
+        {
+            return self.index_rows(&table, index, Vec::new(), seek);
+        }
+
+        // 索引的 key 按照列的值排序，前面的列相等的项是连续的，之后的一列在范围内的项也是连续的
+        let base = index_key(&table_name, &index.name, &prefix.iter().collect::<Vec<_>>())?;
+        let with_value = |v: &Value| -> Result<Vec<u8>> {
+            let mut key = base.clone();
+            key.extend(keycode::serialize_key(&index_value(v))?);
+            Ok(key)
+        };
+        // 以 key 开头的所有项之后的位置，key 的开头是表名，总是存在
+        let after =
+            |key: Vec<u8>| prefix_end(&key).ok_or(Error::Internal("invalid index key".to_string()));
+        let start = match &range.0 {
+            Bound::Included(v) => with_value(v)?,
+            Bound::Excluded(v) => after(with_value(v)?)?,
+            Bound::Unbounded => base.clone(),
+        };
+        let end = match &range.1 {
+            Bound::Included(v) => after(with_value(v)?)?,
+            Bound::Excluded(v) => with_value(v)?,
+            Bound::Unbounded => after(base.clone())?,
+        };
+        let mut keys = Vec::new();
+        if start < end {
+            for result in self.txn.scan(start, Some(end))? {
+                let id = bincode::deserialize(&result.value)?;
+                keys.push(bincode::serialize(&Key::Row(table_name.clone(), id))?);
+            }
+        }
+        self.index_rows(&table, index, keys, seek)
+    }
+
+    fn scan_spatial_index(
+        &self,
+        table_name: Ident,
+        index: &Ident,
+        area: [f64; 4],
+    ) -> Result<Vec<Row>> {
+        let table = self.must_get_table(table_name.clone())?;
+        let Some(index) = table.indexes.iter().find(|i| i.name == *index) else {
+            return Err(Error::Internal(format!(
+                "index {} does not exist on table {}",
+                index, table_name
+            )));
+        };
+        let [xmin, ymin, xmax, ymax] = area;
+        let (Some((x0, y0)), Some((x1, y1))) =
+            (index.grid_cell(xmin, ymin), index.grid_cell(xmax, ymax))
+        else {
+            return Err(Error::Internal(format!(
+                "index {} on table {} is not a spatial index",
+                index.name, table_name
+            )));
+        };
+        // 范围是空的，NaN 和任何值比较都是 false
+        if !(xmin <= xmax && ymin <= ymax) {
+            return self.index_rows(&table, index, Vec::new(), false);
+        }
+
+        // 索引项按照网格的 x、y 坐标排序，一列网格中 y 在范围内的项是连续的
+        let cell = |x: i64, y: i64| {
+            index_key(
+                &table_name,
+                &index.name,
+                &[&Value::Integer(x), &Value::Integer(y)],
+            )
+        };
+        let mut keys = Vec::new();
+        for x in x0..=x1 {
+            self.check_interrupt()?;
+            let end = prefix_end(&cell(x, y1)?)
+                .ok_or(Error::Internal("invalid index key".to_string()))?;
+            for result in self.txn.scan(cell(x, y0)?, Some(end))? {
+                let id = bincode::deserialize(&result.value)?;
+                keys.push(bincode::serialize(&Key::Row(table_name.clone(), id))?);
+            }
+        }
+        self.index_rows(&table, index, keys, false)
+    }
+
+    fn purge_expired(&mut self) -> Result<usize> {
+        let now = now_secs();
+        let mut purged = 0;
+        for result in self
+            .txn
+            .scan_prefix(bincode::serialize(&KeyPrefix::Table)?)?
+        {
+            let table: Table = bincode::deserialize(&result.value)?;
+            let Some(ttl) = table.ttl else {
+                continue;
+            };
+            // 行存直接删除过期的行，列存根据时间列找到过期的主键，再删除每一列的值
+            let mut dict = HashMap::new();
+            let prefix = match table.storage {
+                StorageLayout::Row => KeyPrefix::Row(table.name.clone()),
+                StorageLayout::Columnar => KeyPrefix::Column(table.name.clone(), ttl.column),
+            };
+            let mut delete_keys = Vec::new();
+            for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+                let values = self.decode_values(&table, &result.value, &mut dict)?;
+                let value = match table.storage {
+                    StorageLayout::Row => values.get(ttl.column),
+                    StorageLayout::Columnar => values.first(),
+                };
+                if !value.is_some_and(|v| ttl.is_expired(v, now)) {
+                    continue;
+                }
+                match bincode::deserialize(&result.key)? {
+                    Key::Row(..) => {
+                        let entries = self.index_entries(&table, &values)?;
+                        delete_keys.extend(entries.into_iter().map(|(key, _)| key));
+                        delete_keys.push(result.key);
+                    }
+                    Key::Column(_, _, id) => {
+                        for i in 0..table.columns.len() {
+                            let key = Key::Column(table.name.clone(), i, id.clone());
+                            delete_keys.push(bincode::serialize(&key)?);
+                        }
+                    }
+                    k => return Err(Error::Internal(format!("unexpected key {:?}", k))),
+                }
+                purged += 1;
+            }
+            if !delete_keys.is_empty() {
+                self.written.insert(table.name.clone());
+            }
+            for key in delete_keys {
+                self.txn.delete(key)?;
+            }
+        }
+        Ok(purged)
+    }
+
     fn create_table(&mut self, table: Table) -> Result<()> {
         // 判断表是否已经存在
         if self.get_table(table.name.clone())?.is_some() {
@@ -111,6 +1069,12 @@ impl<E: StorageEngine> Transaction for KVTransaction<E> {
                 table.name
             )));
         }
+        if self.get_view(table.name.clone())?.is_some() {
+            return Err(Error::Internal(format!(
+                "view {} already exists",
+                table.name
+            )));
+        }
 
         // 判断表的有效性
         if table.columns.is_empty() {
@@ -124,36 +1088,861 @@ impl<E: StorageEngine> Transaction for KVTransaction<E> {
         let value = bincode::serialize(&table)?;
         self.txn.set(bincode::serialize(&key)?, value)?;
 
+        self.schemas.lock()?.insert(table.name.clone(), Some(table));
         Ok(())
     }
 
-    fn get_table(&self, table_name: String) -> Result<Option<Table>> {
-        let key = Key::Table(table_name);
+    fn add_column(&mut self, table_name: Ident, column: Column) -> Result<()> {
+        let mut table = self.must_get_writable_table(table_name.clone())?;
+        if table.columns.iter().any(|c| c.name == column.name) {
+            return Err(Error::Internal(format!(
+                "column {} already exists in table {}",
+                column.name, table_name
+            )));
+        }
+        let mut value = column.default_value()?.unwrap_or(Value::Null);
+        if value != Value::Null && !column.datatype.matches(&value) {
+            return Err(Error::Internal(format!(
+                "default value of column {} type mismatch",
+                column.name
+            )));
+        }
+        table.columns.push(column.clone());
+
+        // 使用默认值填充已有的行，行存的每一行需要重新编码，列存只需要写入新的一列
+        let mut dict = HashMap::new();
+        let prefix = match table.storage {
+            StorageLayout::Row => KeyPrefix::Row(table_name.clone()),
+            StorageLayout::Columnar => KeyPrefix::Column(table_name.clone(), 0),
+        };
+        for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+            self.check_interrupt()?;
+            // 表达式的默认值对每一行分别计算
+            if column.default_expr.is_some() {
+                value = column.default_value()?.unwrap_or(Value::Null);
+            }
+            if value == Value::Null && !column.nullable {
+                return Err(Error::Internal(format!(
+                    "column {} cannot be null, table {} is not empty",
+                    column.name, table_name
+                )));
+            }
+            let (key, row) = match table.storage {
+                StorageLayout::Row => {
+                    let mut row = self.decode_values(&table, &result.value, &mut dict)?;
+                    row.push(value.clone());
+                    table.validate_row(&row)?;
+                    (result.key, row)
+                }
+                StorageLayout::Columnar => {
+                    let Key::Column(_, _, id) = bincode::deserialize(&result.key)? else {
+                        return Err(Error::Internal("unexpected column key".to_string()));
+                    };
+                    let key = Key::Column(table_name.clone(), table.columns.len() - 1, id);
+                    (bincode::serialize(&key)?, vec![value.clone()])
+                }
+            };
+            let value = self.encode_values(&table, &row)?;
+            self.txn.set(key, value)?;
+        }
+
+        let key = Key::Table(table_name.clone());
+        self.txn
+            .set(bincode::serialize(&key)?, bincode::serialize(&table)?)?;
+        self.written.insert(table_name.clone());
+        self.schemas.lock()?.insert(table_name, Some(table));
+        Ok(())
+    }
+
+    fn rename_table(&mut self, table_name: Ident, new_name: Ident) -> Result<()> {
+        let mut table = self.must_get_writable_table(table_name.clone())?;
+        if self.get_table(new_name.clone())?.is_some() {
+            return Err(Error::Internal(format!(
+                "table {} already exists",
+                new_name
+            )));
+        }
+        if self.get_view(new_name.clone())?.is_some() {
+            return Err(Error::Internal(format!("view {} already exists", new_name)));
+        }
+        if let Some(view) = self.dependent_view(&table_name, None)? {
+            return Err(Error::Internal(format!(
+                "cannot rename table {}, view {} depends on it",
+                table_name, view
+            )));
+        }
+
+        // 表的数据、字典和统计信息的 key 中都有表名，需要全部移动到新的表名下
+        let mut prefixes = match table.storage {
+            StorageLayout::Row => vec![KeyPrefix::Row(table_name.clone())],
+            StorageLayout::Columnar => (0..table.columns.len())
+                .map(|i| KeyPrefix::Column(table_name.clone(), i))
+                .collect(),
+        };
+        prefixes.push(KeyPrefix::Dictionary(table_name.clone()));
+        prefixes.push(KeyPrefix::DictionaryId(table_name.clone()));
+        let mut moved = Vec::new();
+        for prefix in prefixes {
+            self.check_interrupt()?;
+            for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+                moved.push((result.key, result.value));
+            }
+        }
+        for key in [
+            Key::DictionaryNext(table_name.clone()),
+            Key::Statistics(table_name.clone()),
+        ] {
+            let key = bincode::serialize(&key)?;
+            if let Some(value) = self.txn.get(key.clone())? {
+                moved.push((key, value));
+            }
+        }
+        for (key, value) in moved {
+            let new_key = bincode::deserialize::<Key>(&key)?.with_table_name(new_name.clone());
+            self.txn.delete(key)?;
+            self.txn.set(bincode::serialize(&new_key)?, value)?;
+        }
+        // 索引项的 key 不是完整的 Key，替换开头的表名和索引名，之后的编码不变
+        for index in &table.indexes {
+            let prefix =
+                bincode::serialize(&KeyPrefix::Index(table_name.clone(), index.name.clone()))?;
+            let new_prefix =
+                bincode::serialize(&KeyPrefix::Index(new_name.clone(), index.name.clone()))?;
+            for result in self.txn.scan_prefix(prefix.clone())? {
+                self.check_interrupt()?;
+                let mut new_key = new_prefix.clone();
+                new_key.extend(&result.key[prefix.len()..]);
+                self.txn.delete(result.key)?;
+                self.txn.set(new_key, result.value)?;
+            }
+        }
+
+        table.name = new_name.clone();
+        self.txn
+            .delete(bincode::serialize(&Key::Table(table_name.clone()))?)?;
+        self.txn.set(
+            bincode::serialize(&Key::Table(new_name.clone()))?,
+            bincode::serialize(&table)?,
+        )?;
+        self.written.insert(table_name.clone());
+        self.written.insert(new_name.clone());
+        let mut schemas = self.schemas.lock()?;
+        schemas.insert(table_name, None);
+        schemas.insert(new_name, Some(table));
+        Ok(())
+    }
+
+    fn rename_column(&mut self, table_name: Ident, column: Ident, new_name: Ident) -> Result<()> {
+        let mut table = self.must_get_writable_table(table_name.clone())?;
+        if table.columns.iter().any(|c| c.name == new_name) {
+            return Err(Error::Internal(format!(
+                "column {} already exists in table {}",
+                new_name, table_name
+            )));
+        }
+        // 行中的值按照列的下标保存，只需要修改表信息
+        let Some(col) = table.columns.iter_mut().find(|c| c.name == column) else {
+            return Err(Error::Internal(format!(
+                "column {} does not exist in table {}",
+                column, table_name
+            )));
+        };
+        col.name = new_name;
+        if let Some(view) = self.dependent_view(&table_name, Some(&column))? {
+            return Err(Error::Internal(format!(
+                "cannot rename column {} of table {}, view {} depends on it",
+                column, table_name, view
+            )));
+        }
+
+        let key = Key::Table(table_name.clone());
+        self.txn
+            .set(bincode::serialize(&key)?, bincode::serialize(&table)?)?;
+        self.written.insert(table_name.clone());
+        self.schemas.lock()?.insert(table_name, Some(table));
+        Ok(())
+    }
+
+    fn create_view(&mut self, view: View) -> Result<()> {
+        if self.get_table(view.name.clone())?.is_some() {
+            return Err(Error::Internal(format!(
+                "table {} already exists",
+                view.name
+            )));
+        }
+        if self.get_view(view.name.clone())?.is_some() {
+            return Err(Error::Internal(format!(
+                "view {} already exists",
+                view.name
+            )));
+        }
+        let key = Key::View(view.name.clone());
+        self.txn
+            .set(bincode::serialize(&key)?, bincode::serialize(&view)?)?;
+        Ok(())
+    }
+
+    fn drop_view(&mut self, name: Ident) -> Result<()> {
+        if self.get_view(name.clone())?.is_none() {
+            return Err(Error::Internal(format!("view {} does not exist", name)));
+        }
+        if let Some(view) = self.dependent_view(&name, None)? {
+            return Err(Error::Internal(format!(
+                "cannot drop view {}, view {} depends on it",
+                name, view
+            )));
+        }
+        self.txn.delete(bincode::serialize(&Key::View(name))?)
+    }
+
+    fn get_view(&self, name: Ident) -> Result<Option<View>> {
+        self.txn
+            .get(bincode::serialize(&Key::View(name))?)?
+            .map(|v| Ok(bincode::deserialize(&v)?))
+            .transpose()
+    }
+
+    fn create_index(&mut self, table_name: Ident, index: Index) -> Result<usize> {
+        let mut table = self.must_get_writable_table(table_name.clone())?;
+        // 列存的表没有按照主键读取整行的 key
+        if table.storage == StorageLayout::Columnar {
+            return Err(Error::Internal(format!(
+                "cannot create index on columnar table {}",
+                table_name
+            )));
+        }
+        if let Some(&i) = index.columns.iter().find(|&&i| i >= table.columns.len()) {
+            return Err(Error::Internal(format!("column index {} out of bound", i)));
+        }
+        if table.indexes.iter().any(|i| i.name == index.name) {
+            return Err(Error::Internal(format!(
+                "index {} already exists on table {}",
+                index.name, table_name
+            )));
+        }
+        if let Some(other) = table
+            .indexes
+            .iter()
+            .find(|i| i.columns == index.columns && i.grid.is_some() == index.grid.is_some())
+        {
+            return Err(Error::Internal(format!(
+                "index {} on table {} already has the same columns",
+                other.name, table_name
+            )));
+        }
+
+        let prefix = KeyPrefix::Row(table_name.clone());
+        let mut dict = HashMap::new();
+        let mut count = 0;
+        for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+            self.check_interrupt()?;
+            let row = self.decode_values(&table, &result.value, &mut dict)?;
+            if let Some((key, value)) = index_entry(&table_name, &index, &row)? {
+                self.txn.set(key, value)?;
+                count += 1;
+            }
+        }
+
+        table.indexes.push(index);
+        let key = Key::Table(table_name.clone());
+        self.txn
+            .set(bincode::serialize(&key)?, bincode::serialize(&table)?)?;
+        self.written.insert(table_name.clone());
+        self.schemas.lock()?.insert(table_name, Some(table));
+        Ok(count)
+    }
+
+    fn drop_index(&mut self, table_name: Ident, name: Ident) -> Result<()> {
+        let mut table = self.must_get_writable_table(table_name.clone())?;
+        let Some(pos) = table.indexes.iter().position(|i| i.name == name) else {
+            return Err(Error::Internal(format!(
+                "index {} does not exist on table {}",
+                name, table_name
+            )));
+        };
+        if table.indexes[pos].unique {
+            return Err(Error::Internal(format!(
+                "index {} is used by a UNIQUE constraint on table {}",
+                name, table_name
+            )));
+        }
+        let index = table.indexes.remove(pos);
+        let prefix = KeyPrefix::Index(table_name.clone(), index.name);
+        for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+            self.check_interrupt()?;
+            self.txn.delete(result.key)?;
+        }
+
+        let key = Key::Table(table_name.clone());
+        self.txn
+            .set(bincode::serialize(&key)?, bincode::serialize(&table)?)?;
+        self.written.insert(table_name.clone());
+        self.schemas.lock()?.insert(table_name, Some(table));
+        Ok(())
+    }
+
+    fn analyze_table(&mut self, table_name: Ident) -> Result<usize> {
+        let table = self.must_get_table(table_name.clone())?;
+        if is_system_table(&table.name) {
+            return Err(Error::Internal(format!(
+                "cannot analyze system table {}",
+                table_name
+            )));
+        }
+        let rows = self.scan_table_columns(table_name.clone(), &[0])?.len();
+        let stats = TableStatistics {
+            version: self.txn.version(),
+            rows,
+        };
+        self.txn.set(
+            bincode::serialize(&Key::Statistics(table_name))?,
+            bincode::serialize(&stats)?,
+        )?;
+        Ok(rows)
+    }
+
+    fn check_table(&self, table_name: Ident) -> Result<Vec<String>> {
+        let table = self.must_get_table(table_name.clone())?;
+        if is_system_table(&table.name) {
+            return Err(Error::Internal(format!(
+                "cannot check system table {}",
+                table_name
+            )));
+        }
+        // 外部表只检查文件能否按照表的定义解析
+        if let Some(external) = &table.external {
+            return Ok(match self.scan_external(&table, external) {
+                Ok(_) => Vec::new(),
+                Err(Error::Internal(e)) => vec![e],
+                Err(e) => return Err(e),
+            });
+        }
+
+        let mut problems = Vec::new();
+        let mut dict = HashMap::new();
+        let prefixes = match table.storage {
+            StorageLayout::Row => vec![KeyPrefix::Row(table_name.clone())],
+            StorageLayout::Columnar => (0..table.columns.len())
+                .map(|i| KeyPrefix::Column(table_name.clone(), i))
+                .collect(),
+        };
+        // 列存的每一列按照主键保存各自的值，主键 -> 每一列的值
+        let mut columns: Vec<(Value, Vec<Option<Value>>)> = Vec::new();
+        let mut positions = HashMap::new();
+        // 按照行数据应该有的二级索引项，key -> 索引的位置和行的主键
+        let mut index_entries = HashMap::new();
+        for (i, prefix) in prefixes.iter().enumerate() {
+            let prefix = bincode::serialize(prefix)?;
+            // 底层的版本数据需要完整，否则下面的扫描可能读取到错误的数据
+            problems.extend(self.txn.verify(prefix.clone())?);
+            let results = match self.txn.scan_prefix(prefix) {
+                Ok(results) => results,
+                Err(err) => {
+                    problems.push(format!("cannot scan table {}: {}", table_name, err));
+                    continue;
+                }
+            };
+            for result in results {
+                self.check_interrupt()?;
+                let id = match bincode::deserialize(&result.key) {
+                    Ok(Key::Row(_, id)) if table.storage == StorageLayout::Row => id,
+                    Ok(Key::Column(_, j, id)) if j == i => id,
+                    Ok(k) => {
+                        problems.push(format!("unexpected key {:?}", k));
+                        continue;
+                    }
+                    Err(err) => {
+                        problems.push(format!("invalid key {:?}: {}", result.key, err));
+                        continue;
+                    }
+                };
+                let values = match self.decode_values(&table, &result.value, &mut dict) {
+                    Ok(values) => values,
+                    Err(err) => {
+                        problems.push(format!("row {:?}: {}", id, err));
+                        continue;
+                    }
+                };
+                match table.storage {
+                    StorageLayout::Row => {
+                        // 数据按照第一列保存，key 中的主键需要和行中的值一致
+                        if values.first() != Some(&id) {
+                            problems
+                                .push(format!("row {:?}: primary key does not match the row", id));
+                        }
+                        if let Err(err) = table.validate_row(&values) {
+                            problems.push(format!("row {:?}: {}", id, err));
+                        }
+                        if values.len() == table.columns.len() {
+                            for (j, index) in table.indexes.iter().enumerate() {
+                                let Some((key, _)) = index_entry(&table_name, index, &values)?
+                                else {
+                                    continue;
+                                };
+                                // 只有唯一索引中值相同的行会有相同的 key
+                                if let Some((_, other)) = index_entries.get(&key) {
+                                    problems.push(format!(
+                                        "index {}: duplicate value for rows {:?} and {:?}",
+                                        index.name, other, id
+                                    ));
+                                    continue;
+                                }
+                                index_entries.insert(key, (j, id.clone()));
+                            }
+                        }
+                    }
+                    StorageLayout::Columnar => {
+                        if values.len() != 1 {
+                            problems.push(format!(
+                                "row {:?}: column {} has {} values",
+                                id,
+                                table.columns[i].name,
+                                values.len()
+                            ));
+                            continue;
+                        }
+                        let value = values.into_iter().next();
+                        let key = bincode::serialize(&id)?;
+                        if i == 0 {
+                            if value.as_ref() != Some(&id) {
+                                problems.push(format!(
+                                    "row {:?}: primary key does not match the row",
+                                    id
+                                ));
+                            }
+                            positions.insert(key, columns.len());
+                            let mut row = vec![None; table.columns.len()];
+                            row[0] = value;
+                            columns.push((id, row));
+                        } else if let Some(&pos) = positions.get(&key) {
+                            columns[pos].1[i] = value;
+                        } else {
+                            problems.push(format!(
+                                "row {:?}: column {} has a value but the primary key does not exist",
+                                id, table.columns[i].name
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        // 列存的每一行需要在所有列中都有值
+        for (id, row) in columns {
+            if let Some(i) = row.iter().position(Option::is_none) {
+                problems.push(format!(
+                    "row {:?}: column {} is missing",
+                    id, table.columns[i].name
+                ));
+                continue;
+            }
+            let row = row.into_iter().flatten().collect();
+            if let Err(err) = table.validate_row(&row) {
+                problems.push(format!("row {:?}: {}", id, err));
+            }
+        }
+
+        // 二级索引项需要和行数据一一对应，value 是行的主键
+        for index in &table.indexes {
+            let prefix = KeyPrefix::Index(table_name.clone(), index.name.clone());
+            let prefix = bincode::serialize(&prefix)?;
+            problems.extend(self.txn.verify(prefix.clone())?);
+            for result in self.txn.scan_prefix(prefix)? {
+                self.check_interrupt()?;
+                // 唯一索引的 key 中没有主键，还需要比较 value 中的主键
+                let value = bincode::deserialize::<Value>(&result.value);
+                if let Some((_, id)) = index_entries.get(&result.key) {
+                    if value.as_ref().ok() == Some(id) {
+                        index_entries.remove(&result.key);
+                        continue;
+                    }
+                }
+                match value {
+                    Ok(id) => problems.push(format!(
+                        "index {}: unexpected entry for row {:?}",
+                        index.name, id
+                    )),
+                    Err(err) => problems.push(format!(
+                        "index {}: invalid entry {:?}: {}",
+                        index.name, result.key, err
+                    )),
+                }
+            }
+        }
+        let mut missing = index_entries.into_iter().collect::<Vec<_>>();
+        missing.sort_by(|(a, (i, _)), (b, (j, _))| (i, a).cmp(&(j, b)));
+        for (_, (i, id)) in missing {
+            problems.push(format!(
+                "index {}: missing entry for row {:?}",
+                table.indexes[i].name, id
+            ));
+        }
+        Ok(problems)
+    }
+
+    fn reindex_table(&mut self, table_name: Ident) -> Result<usize> {
+        let table = self.must_get_table(table_name.clone())?;
+        if is_system_table(&table.name) {
+            return Err(Error::Internal(format!(
+                "cannot reindex system table {}",
+                table_name
+            )));
+        }
+        if table.external.is_some() {
+            return Err(Error::Internal(format!(
+                "cannot reindex external table {}",
+                table_name
+            )));
+        }
+        // 列存的每一列都按照主键保存，无法从其他数据重建
+        if table.storage == StorageLayout::Columnar {
+            return Err(Error::Internal(format!(
+                "cannot reindex columnar table {}",
+                table_name
+            )));
+        }
+
+        // 先删除所有的行，再按照第一列的值重新生成 key，同时使用表当前的编码方式重新编码
+        // 二级索引也全部删除之后按照行数据重新生成
+        self.clear_indexes(&table)?;
+        let prefix = KeyPrefix::Row(table_name.clone());
+        let mut dict = HashMap::new();
+        let mut rows = Vec::new();
+        for result in self.txn.scan_prefix(bincode::serialize(&prefix)?)? {
+            self.check_interrupt()?;
+            let row = self.decode_values(&table, &result.value, &mut dict)?;
+            table.validate_row(&row)?;
+            self.txn.delete(result.key)?;
+            rows.push(row);
+        }
+        let mut keys = HashSet::new();
+        for row in &rows {
+            let key = bincode::serialize(&Key::Row(table_name.clone(), row[0].clone()))?;
+            if !keys.insert(key.clone()) {
+                return Err(Error::Internal(format!(
+                    "duplicate primary key {:?} in table {}",
+                    row[0], table_name
+                )));
+            }
+            let value = self.encode_values(&table, row)?;
+            self.txn.set(key, value)?;
+            for (key, value) in self.index_entries(&table, row)? {
+                self.txn.set(key, value)?;
+            }
+        }
+        self.written.insert(table_name);
+        Ok(rows.len())
+    }
+
+    fn is_cacheable(&self, table_name: Ident) -> Result<bool> {
+        // 系统表中的访问统计每次查询都会变化，有过期时间的表中的行会随时间过期
+        // 外部表的文件可能在数据库之外被修改
+        if is_system_table(&table_name) {
+            return Ok(false);
+        }
         Ok(self
+            .get_table(table_name)?
+            .is_some_and(|table| table.ttl.is_none() && table.external.is_none()))
+    }
+
+    fn table_function(&self, name: &str) -> Result<Option<Arc<dyn TableFunction>>> {
+        self.functions.get(name)
+    }
+
+    fn estimated_rows(&self, table_name: Ident) -> Result<Option<usize>> {
+        let stats: Option<TableStatistics> = self
+            .txn
+            .get(bincode::serialize(&Key::Statistics(table_name))?)?
+            .map(|v| bincode::deserialize(&v))
+            .transpose()?;
+        Ok(stats.map(|s| s.rows))
+    }
+
+    fn get_table(&self, table_name: Ident) -> Result<Option<Table>> {
+        if table_name == SYSTEM_TABLES {
+            return Ok(Some(system_tables_schema()));
+        }
+        if table_name == SYSTEM_INDEXES {
+            return Ok(Some(system_indexes_schema()));
+        }
+        // 事务只能看到自己的快照，表信息只会被当前事务的 DDL 修改，可以一直缓存到事务结束
+        if let Some(table) = self.schemas.lock()?.get(&table_name) {
+            return Ok(table.clone());
+        }
+        let key = Key::Table(table_name.clone());
+        let table: Option<Table> = self
             .txn
             .get(bincode::serialize(&key)?)?
             .map(|v| bincode::deserialize(&v))
-            .transpose()?)
+            .transpose()?;
+        self.schemas.lock()?.insert(table_name, table.clone());
+        Ok(table)
+    }
+}
+
+// 系统表，查询所有表的行数、数据大小、版本数量和最近一次 ANALYZE 的版本
+const SYSTEM_TABLES: &str = "sys_tables";
+
+// 系统表，查询所有二级索引的扫描次数、等值查找次数和读取的行数
+const SYSTEM_INDEXES: &str = "sys_indexes";
+
+// 一个事务最多可以分配的字典编号是 2^24 个，编号是版本号 * 2^24 加上事务中的序号
+const DICTIONARY_ID_BITS: u32 = 24;
+
+fn is_system_table(table_name: &Ident) -> bool {
+    *table_name == SYSTEM_TABLES || *table_name == SYSTEM_INDEXES
+}
+
+fn system_column(name: &str, datatype: DataType, nullable: bool) -> Column {
+    Column {
+        name: name.into(),
+        datatype,
+        nullable,
+        default: None,
+        default_expr: None,
+    }
+}
+
+fn system_table(name: &str, columns: Vec<Column>) -> Table {
+    Table {
+        name: name.into(),
+        columns,
+        storage: StorageLayout::Row,
+        compression: Compression::None,
+        ttl: None,
+        external: None,
+        indexes: Vec::new(),
     }
 }
 
+fn system_tables_schema() -> Table {
+    system_table(
+        SYSTEM_TABLES,
+        vec![
+            system_column("name", DataType::String, false),
+            system_column("rows", DataType::Integer, false),
+            system_column("bytes", DataType::Integer, false),
+            system_column("versions", DataType::Integer, false),
+            system_column("last_analyze", DataType::Integer, true),
+            system_column("scans", DataType::Integer, false),
+            system_column("rows_read", DataType::Integer, false),
+            system_column("rows_written", DataType::Integer, false),
+        ],
+    )
+}
+
+fn system_indexes_schema() -> Table {
+    system_table(
+        SYSTEM_INDEXES,
+        vec![
+            system_column("table_name", DataType::String, false),
+            system_column("name", DataType::String, false),
+            system_column("scans", DataType::Integer, false),
+            system_column("seeks", DataType::Integer, false),
+            system_column("rows_read", DataType::Integer, false),
+        ],
+    )
+}
+
+// 当前的 unix 时间戳（秒），用于判断行是否过期
+fn now_secs() -> i64 {
+    (now_millis() / 1000) as i64
+}
+
+// 表的访问统计，只保存在内存中，从引擎创建时开始计数
+#[derive(Debug, Default, Clone)]
+struct TableAccess {
+    // 扫描表的次数
+    scans: u64,
+    // 扫描时读取的行数，包括通过索引读取的行
+    rows_read: u64,
+    // 写入的行数，包括之后回滚的写入
+    rows_written: u64,
+    // 每个二级索引的访问统计
+    indexes: HashMap<Ident, IndexAccess>,
+}
+
+// 索引的访问统计，所有索引列都是等值条件时是一次查找，其他情况是一次扫描
+#[derive(Debug, Default, Clone, Copy)]
+struct IndexAccess {
+    scans: u64,
+    seeks: u64,
+    // 通过索引读取的行数
+    rows_read: u64,
+}
+
+type AccessStats = Arc<Mutex<HashMap<Ident, TableAccess>>>;
+
+// ANALYZE 收集到的表统计信息
+#[derive(Debug, Serialize, Deserialize)]
+struct TableStatistics {
+    // 收集统计信息的事务版本
+    version: u64,
+    rows: usize,
+}
+
+// Key 和 KeyPrefix 中对应的枚举需要保持相同的顺序，保证编码后的前缀一致
 #[derive(Debug, Serialize, Deserialize)]
 enum Key {
-    Table(String),
-    Row(String, Value),
+    Table(Ident),
+    Row(Ident, Value),
+    // 列存表中的一个值：表名、列下标、主键
+    Column(Ident, usize, Value),
+    // 表的字符串字典：编号 -> 字符串
+    Dictionary(Ident, u64),
+    // 表的字符串字典：字符串 -> 编号
+    DictionaryId(Ident, String),
+    // 表的字典中下一个可用的编号，已经不再写入，保留是为了兼容已有的数据
+    DictionaryNext(Ident),
+    // 表的统计信息
+    Statistics(Ident),
+    // 视图的定义
+    View(Ident),
+    // 二级索引项的开头：表名、索引名，和 KeyPrefix::Index 的编码相同
+    // 之后是使用 keycode 保序编码的索引列的值和主键，value 是 bincode 编码的主键
+    // 不能按照 Key 解码，修改表名时单独处理
+    Index(Ident, Ident),
+}
+
+impl Key {
+    // 换成另一个表的相同的 key，修改表名时使用
+    fn with_table_name(self, name: Ident) -> Self {
+        match self {
+            Key::Table(_) => Key::Table(name),
+            Key::Row(_, id) => Key::Row(name, id),
+            Key::Column(_, i, id) => Key::Column(name, i, id),
+            Key::Dictionary(_, id) => Key::Dictionary(name, id),
+            Key::DictionaryId(_, s) => Key::DictionaryId(name, s),
+            Key::DictionaryNext(_) => Key::DictionaryNext(name),
+            Key::Statistics(_) => Key::Statistics(name),
+            Key::View(_) => Key::View(name),
+            Key::Index(_, index) => Key::Index(name, index),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 enum KeyPrefix {
     Table,
-    Row(String),
+    Row(Ident),
+    Column(Ident, usize),
+    Dictionary(Ident),
+    DictionaryId(Ident),
+    // 只用来占位，保证 Index 和 Key::Index 的编号相同
+    DictionaryNext,
+    Statistics,
+    View,
+    // 一个索引的所有项，后面再加上前几列的值时是这些值的所有项
+    Index(Ident, Ident),
+}
+
+// 索引中保存的值，0.0 和 -0.0 相等，需要使用相同的 key
+fn index_value(value: &Value) -> Value {
+    match value {
+        Value::Float(f) if *f == 0.0 => Value::Float(0.0),
+        value => value.clone(),
+    }
+}
+
+// 索引项的 key 中主键之前的部分，values 是索引的前几列的值，按照顺序保序编码
+fn index_key(table_name: &Ident, index: &Ident, values: &[&Value]) -> Result<Vec<u8>> {
+    let mut key = bincode::serialize(&KeyPrefix::Index(table_name.clone(), index.clone()))?;
+    for value in values {
+        key.extend(keycode::serialize_key(&index_value(value))?);
+    }
+    Ok(key)
+}
+
+// 行在一个二级索引中的 key 和 value，索引的第一列是 NULL 的行不写入索引
+// 主键放在 key 的最后，索引列的值相同的多行各自有一项
+// 唯一索引中没有 NULL 的值不加主键，两个事务写入相同的值时会写同一个 key 而发生写冲突
+fn index_entry(table_name: &Ident, index: &Index, row: &Row) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    if row[index.columns[0]] == Value::Null {
+        return Ok(None);
+    }
+    // 空间索引中是点所在的网格的坐标
+    let cell = match (&row[index.columns[0]], index.grid) {
+        (Value::Point(x, y), Some(_)) => index
+            .grid_cell(*x, *y)
+            .map(|(x, y)| [Value::Integer(x), Value::Integer(y)]),
+        _ => None,
+    };
+    let values = match &cell {
+        Some(cell) => cell.iter().collect(),
+        None => index.columns.iter().map(|&i| &row[i]).collect::<Vec<_>>(),
+    };
+    let mut key = index_key(table_name, &index.name, &values)?;
+    if !index.unique || values.iter().any(|v| **v == Value::Null) {
+        key.extend(keycode::serialize_key(&row[0])?);
+    }
+    Ok(Some((key, bincode::serialize(&row[0])?)))
+}
+
+// 行在唯一索引中的值，不包括主键，有 NULL 的值不和其他行重复
+fn unique_keys<'a>(table: &'a Table, row: &Row) -> Result<Vec<(&'a Index, Vec<u8>)>> {
+    let mut keys = Vec::new();
+    for index in table.indexes.iter().filter(|i| i.unique) {
+        let values = index.columns.iter().map(|&i| &row[i]).collect::<Vec<_>>();
+        if values.iter().all(|v| **v != Value::Null) {
+            keys.push((index, index_key(&table.name, &index.name, &values)?));
+        }
+    }
+    Ok(keys)
+}
+
+// 第一列是主键，不能是 NULL
+fn check_primary_key(table: &Table, row: &Row) -> Result<()> {
+    match row[0] {
+        Value::Null => Err(Error::ConstraintViolation(format!(
+            "primary key {} of table {} cannot be null",
+            table.columns[0].name, table.name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn duplicate_primary_key(table: &Table, id: &Value) -> Error {
+    Error::ConstraintViolation(format!(
+        "duplicate primary key {:?} in table {}",
+        id, table.name
+    ))
+}
+
+fn unique_violation(table: &Table, index: &Index, row: &Row) -> Error {
+    let columns = index
+        .columns
+        .iter()
+        .map(|&i| table.columns[i].name.to_string())
+        .collect::<Vec<_>>();
+    let values = match &index.columns[..] {
+        [i] => format!("{:?}", row[*i]),
+        columns => format!("{:?}", columns.iter().map(|&i| &row[i]).collect::<Vec<_>>()),
+    };
+    Error::ConstraintViolation(format!(
+        "duplicate value {} for UNIQUE column {} of table {}",
+        values,
+        columns.join(", "),
+        table.name
+    ))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{error::Result, sql::engine::Engine, storage::memory::MemoryEngine};
+    use crate::{
+        error::{Error, Result},
+        sql::{
+            builder::{col, Query},
+            engine::{Engine, OnError, Session, Transaction},
+            executor::ResultSet,
+            function::TableFunction,
+            parser::ast::{Direction, ExplainFormat, Statement},
+            schema::{Column, Compression, StorageLayout, Table},
+            types::{decimal::Decimal, json::Json, uuid::Uuid, DataType, Row, Value},
+        },
+        storage::{disk::DiskEngine, memory::MemoryEngine, mvcc::restore_backup},
+    };
 
-    use super::KVEngine;
+    use super::{index_key, now_secs, KVEngine, Key};
 
     #[test]
     fn test_create_table() -> Result<()> {
@@ -169,4 +1958,5830 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_select_columns() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text, c bool);")?;
+        s.execute("insert into t1 values(1, 'a', true);")?;
+
+        assert_eq!(
+            s.execute("select c, t1.*, 10 from t1;")?,
+            ResultSet::Scan {
+                columns: vec![
+                    "c".to_string(),
+                    "a".to_string(),
+                    "b".to_string(),
+                    "c".to_string(),
+                    "10".to_string(),
+                ],
+                rows: vec![vec![
+                    Value::Boolean(true),
+                    Value::Integer(1),
+                    Value::String("a".to_string()),
+                    Value::Boolean(true),
+                    Value::Integer(10),
+                ]],
+            }
+        );
+
+        // select 列表中的表达式可以引用列
+        assert_eq!(
+            s.execute("select (a + 1) * 2, b = 'a' and not c from t1;")?,
+            ResultSet::Scan {
+                columns: vec!["(a + 1) * 2".to_string(), "b = 'a' AND NOT c".to_string()],
+                rows: vec![vec![Value::Integer(4), Value::Boolean(false)]],
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_default() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (id int default 0, a int default 1, b text default 'x');")?;
+        s.execute("insert into t1 default values;")?;
+        s.execute("insert into t1 values (1, default, 'y');")?;
+        s.execute("insert into t1 (b, id) values (default, 2);")?;
+
+        assert_eq!(
+            s.execute("select * from t1;")?,
+            ResultSet::Scan {
+                columns: vec!["id".to_string(), "a".to_string(), "b".to_string()],
+                rows: vec![
+                    vec![
+                        Value::Integer(0),
+                        Value::Integer(1),
+                        Value::String("x".to_string())
+                    ],
+                    vec![
+                        Value::Integer(1),
+                        Value::Integer(1),
+                        Value::String("y".to_string())
+                    ],
+                    vec![
+                        Value::Integer(2),
+                        Value::Integer(1),
+                        Value::String("x".to_string())
+                    ],
+                ],
+            }
+        );
+
+        s.execute("create table t2 (a int not null, b int);")?;
+        assert!(s.execute("insert into t2 default values;").is_err());
+        assert!(s.execute("insert into t2 values (default, 1);").is_err());
+        assert_eq!(
+            s.execute("values (default);"),
+            Err(Error::Internal(
+                "expression DEFAULT is not a constant".to_string()
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_as() -> Result<()> {
+        #[derive(Debug, PartialEq)]
+        struct User {
+            id: i64,
+            name: Option<String>,
+        }
+        crate::impl_from_row!(User { id, name });
+
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name text, age int);")?;
+        s.execute("insert into users values (1, 'a', 10), (2, null, 20);")?;
+
+        assert_eq!(
+            s.query_as::<User>("select * from users;")?,
+            vec![
+                User {
+                    id: 1,
+                    name: Some("a".to_string())
+                },
+                User { id: 2, name: None },
+            ]
+        );
+        assert_eq!(
+            s.query_as::<(i64, i64)>("select age, id from users;")?,
+            vec![(10, 1), (20, 2)]
+        );
+        assert!(s
+            .query_as::<(i64,)>("insert into users values (3, 'c', 30);")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_columnar_table() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (a int, b text, c float) with (storage = 'columnar');")?;
+        s.execute("insert into t values (2, 'b', 2.0), (1, null, 1.0);")?;
+        s.execute("insert into t (a, c) values (3, 3.0);")?;
+
+        let rows = |result| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            rows(s.execute("select * from t;")?),
+            vec![
+                vec![Value::Integer(1), Value::Null, Value::Float(1.0)],
+                vec![
+                    Value::Integer(2),
+                    Value::String("b".to_string()),
+                    Value::Float(2.0)
+                ],
+                vec![Value::Integer(3), Value::Null, Value::Float(3.0)],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute("select c, a from t;")?),
+            vec![
+                vec![Value::Float(1.0), Value::Integer(1)],
+                vec![Value::Float(2.0), Value::Integer(2)],
+                vec![Value::Float(3.0), Value::Integer(3)],
+            ]
+        );
+
+        // 只读取部分列时，其他列填充为 NULL
+        let txn = kvengine.begin()?;
+        assert_eq!(
+            txn.scan_table_columns("t".into(), &[2])?[0],
+            vec![Value::Integer(1), Value::Null, Value::Float(1.0)]
+        );
+        txn.commit()?;
+
+        assert!(s
+            .execute("create table t2 (a int) with (storage = 'other');")
+            .is_err());
+        assert!(s
+            .execute("create table t2 (a int) with (format = 'row');")
+            .is_err());
+        assert!(kvengine.watch("t".into(), 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dictionary_compression() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text, c text) with (compression = 'dictionary');")?;
+        s.execute(
+            "create table t2 (a int, b text) with (storage = 'columnar', compression = 'dictionary');",
+        )?;
+        let mut watch = kvengine.watch("t1".into(), 1)?;
+
+        s.execute("insert into t1 values (1, 'x', 'y'), (2, 'y', null), (3, 'x', 'x');")?;
+        s.execute("insert into t2 values (1, 'x'), (2, 'x');")?;
+
+        let values = |v: Vec<Vec<Option<&str>>>| -> Vec<Vec<Value>> {
+            v.into_iter()
+                .map(|r| {
+                    r.into_iter()
+                        .map(|v| v.map_or(Value::Null, |s| Value::String(s.to_string())))
+                        .collect()
+                })
+                .collect()
+        };
+        match s.execute("select b, c from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                values(vec![
+                    vec![Some("x"), Some("y")],
+                    vec![Some("y"), None],
+                    vec![Some("x"), Some("x")],
+                ])
+            ),
+            _ => unreachable!(),
+        }
+        match s.execute("select b from t2;")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows, values(vec![vec![Some("x")], vec![Some("x")]]))
+            }
+            _ => unreachable!(),
+        }
+
+        // 订阅的变更同样会被解码
+        let changes = watch.poll()?;
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes[0].row,
+            Some(vec![
+                Value::Integer(1),
+                Value::String("x".to_string()),
+                Value::String("y".to_string())
+            ])
+        );
+
+        assert!(s
+            .execute("create table t3 (a int) with (compression = 'zstd');")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollback_transaction() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (a int);")?;
+
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t".into(), vec![Value::Integer(1)])?;
+        let version = txn.txn.version();
+
+        assert_eq!(
+            s.execute(&format!("rollback transaction {};", version))?,
+            ResultSet::RollbackTransaction { version }
+        );
+        assert_eq!(txn.commit(), Err(Error::Aborted));
+        assert_eq!(
+            s.execute("select * from t;")?,
+            ResultSet::Scan {
+                columns: vec!["a".to_string()],
+                rows: vec![],
+            }
+        );
+        assert!(s
+            .execute(&format!("rollback transaction {};", version))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (a int, b text);")?;
+        s.execute("insert into t values (1, 'a'), (2, 'b');")?;
+
+        // 备份时其他事务未提交的数据不会被备份
+        let mut txn = kvengine.begin()?;
+        txn.create_row(
+            "t".into(),
+            vec![Value::Integer(3), Value::String("c".to_string())],
+        )?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("backup");
+        let sql = format!("backup to '{}' with (batch_size = 1);", path.display());
+        let version = match s.execute(&sql)? {
+            ResultSet::Backup { version, keys, .. } => {
+                assert_eq!(keys, 3);
+                version
+            }
+            _ => unreachable!(),
+        };
+        txn.commit()?;
+        // 备份文件已经存在
+        assert!(s.execute(&sql).is_err());
+        assert!(s
+            .execute("backup to 'other' with (batch_size = 0);")
+            .is_err());
+
+        let rows = |engine: DiskEngine| -> Result<Vec<Row>> {
+            match KVEngine::new(engine)
+                .session()?
+                .execute("select a from t;")?
+            {
+                ResultSet::Scan { rows, .. } => Ok(rows),
+                _ => unreachable!(),
+            }
+        };
+        assert_eq!(
+            rows(DiskEngine::new(path.clone())?)?,
+            vec![vec![Value::Integer(1)], vec![Value::Integer(2)]]
+        );
+
+        // 增量备份包含基础备份时还没有提交的数据，合并之后可以读取到
+        let incremental = dir.path().join("incremental");
+        s.execute(&format!(
+            "backup to '{}' since version {};",
+            incremental.display(),
+            version
+        ))?;
+        let mut base = DiskEngine::new(path)?;
+        restore_backup(&mut base, &mut DiskEngine::new(incremental)?)?;
+        assert_eq!(
+            rows(base)?,
+            vec![
+                vec![Value::Integer(1)],
+                vec![Value::Integer(2)],
+                vec![Value::Integer(3)]
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint() -> Result<()> {
+        let path = tempfile::tempdir()?.keep().join("sqldb-log");
+        let kvengine = KVEngine::new(DiskEngine::new(path.clone())?);
+        let mut s = kvengine.session()?;
+        s.execute("create table t (a int);")?;
+        s.execute("insert into t values (1), (2);")?;
+
+        // 检查点不影响活跃的事务
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t".into(), vec![Value::Integer(3)])?;
+        assert_eq!(s.execute("checkpoint;")?, ResultSet::Checkpoint);
+        txn.commit()?;
+        drop((txn, s, kvengine));
+
+        let mut s = KVEngine::new(DiskEngine::new(path.clone())?).session()?;
+        match s.execute("select * from t;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows.len(), 3),
+            _ => unreachable!(),
+        }
+        std::fs::remove_dir_all(path.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_show_indexes() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text);")?;
+        s.execute("create table t2 (c int, d text) with (storage = 'columnar');")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'b');")?;
+        s.execute("insert into t2 values (1, 'a');")?;
+
+        for (table, column, size) in [("t1", "a", 2), ("t2", "c", 1)] {
+            assert_eq!(
+                s.execute(&format!("show indexes from {};", table))?,
+                ResultSet::Scan {
+                    columns: ["name", "columns", "unique", "size", "usable"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect(),
+                    rows: vec![vec![
+                        Value::String("primary".to_string()),
+                        Value::String(column.to_string()),
+                        Value::Boolean(true),
+                        Value::Integer(size),
+                        Value::Boolean(false),
+                    ]],
+                }
+            );
+        }
+        assert!(s.execute("show indexes from t3;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_system_tables() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text);")?;
+        s.execute("create table t2 (c int, d text) with (storage = 'columnar');")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'b');")?;
+        s.execute("insert into t1 values (3, 'c');")?;
+        s.execute("insert into t2 values (1, 'a');")?;
+        assert_eq!(
+            s.execute("analyze t1;")?,
+            ResultSet::Analyze {
+                table_name: "t1".to_string(),
+                rows: 3
+            }
+        );
+
+        let rows = match s.execute("select name, rows, versions, last_analyze from sys_tables;")? {
+            ResultSet::Scan { rows, .. } => rows,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::String("t1".to_string()),
+                    Value::Integer(3),
+                    Value::Integer(3),
+                    Value::Integer(6),
+                ],
+                vec![
+                    Value::String("t2".to_string()),
+                    Value::Integer(1),
+                    Value::Integer(2),
+                    Value::Null,
+                ],
+            ]
+        );
+        match s.execute("select bytes from sys_tables;")? {
+            ResultSet::Scan { rows, .. } => assert!(rows
+                .iter()
+                .all(|r| matches!(r[0], Value::Integer(b) if b > 0))),
+            _ => unreachable!(),
+        }
+
+        // 系统表是只读的
+        assert!(s
+            .execute("insert into sys_tables values ('t3', 0, 0, 0, null);")
+            .is_err());
+        assert!(s.execute("create table sys_tables (a int);").is_err());
+        assert!(s.execute("analyze sys_tables;").is_err());
+
+        // 表的访问统计，ANALYZE 同样会扫描表
+        s.execute("select * from t2;")?;
+        s.execute("select c from t2;")?;
+        let rows =
+            match s.execute("select name, scans, rows_read, rows_written from sys_tables;")? {
+                ResultSet::Scan { rows, .. } => rows,
+                _ => unreachable!(),
+            };
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::String("t1".to_string()),
+                    Value::Integer(1),
+                    Value::Integer(3),
+                    Value::Integer(3),
+                ],
+                vec![
+                    Value::String("t2".to_string()),
+                    Value::Integer(2),
+                    Value::Integer(2),
+                    Value::Integer(1),
+                ],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_system_indexes() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int, code int unique);")?;
+        s.execute("insert into t values (1, 'a', 10, 1), (2, 'b', 20, 2), (3, 'a', 30, 3);")?;
+        s.execute("create index idx_name on t (name);")?;
+        s.execute("create index idx_score on t (score, name);")?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let stats = |table: &str, name: &str, scans: i64, seeks: i64, rows_read: i64| {
+            vec![
+                Value::String(table.to_string()),
+                Value::String(name.to_string()),
+                Value::Integer(scans),
+                Value::Integer(seeks),
+                Value::Integer(rows_read),
+            ]
+        };
+
+        // 没有使用过的索引计数都是 0
+        assert_eq!(
+            rows(&mut s, "select * from sys_indexes;")?,
+            vec![
+                stats("t", "t_code_key", 0, 0, 0),
+                stats("t", "idx_name", 0, 0, 0),
+                stats("t", "idx_score", 0, 0, 0),
+            ]
+        );
+
+        // 所有索引列都是等值条件时是查找，只有前面的列或者范围条件时是扫描
+        rows(&mut s, "select id from t where name = 'a';")?;
+        rows(&mut s, "select id from t where name = 'x';")?;
+        rows(&mut s, "select id from t where code = 2;")?;
+        rows(&mut s, "select id from t where score = 20;")?;
+        rows(&mut s, "select id from t where score = 30 and name = 'a';")?;
+        rows(&mut s, "select id from t;")?;
+        assert_eq!(
+            rows(&mut s, "select * from sys_indexes;")?,
+            vec![
+                stats("t", "t_code_key", 0, 1, 1),
+                stats("t", "idx_name", 0, 2, 2),
+                stats("t", "idx_score", 1, 1, 2),
+            ]
+        );
+        // 通过索引读取的行也计入表的 rows_read
+        assert_eq!(
+            rows(&mut s, "select scans, rows_read from sys_tables;")?,
+            vec![vec![Value::Integer(1), Value::Integer(8)]]
+        );
+
+        assert!(s
+            .execute("insert into sys_indexes values ('t', 'i', 0, 0, 0);")
+            .is_err());
+        assert!(s.execute("create table sys_indexes (a int);").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_statement_timeout() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (a int);")?;
+        let values = (0..20000)
+            .map(|i| format!("({})", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!("insert into t values {};", values);
+
+        assert_eq!(
+            s.execute("set statement_timeout = 1;")?,
+            ResultSet::Set {
+                name: "statement_timeout".to_string(),
+                value: Value::Integer(1),
+            }
+        );
+        assert_eq!(s.execute(&sql), Err(Error::Timeout));
+
+        // 超时的语句已经回滚，关闭超时之后可以正常执行
+        s.execute("set statement_timeout = 0;")?;
+        match s.execute("select * from t;")? {
+            ResultSet::Scan { rows, .. } => assert!(rows.is_empty()),
+            _ => unreachable!(),
+        }
+        assert_eq!(s.execute(&sql)?, ResultSet::Insert { count: 20000 });
+
+        assert!(s.execute("set statement_timeout = -1;").is_err());
+        assert!(s.execute("set statement_timeout = 'a';").is_err());
+        assert!(s.execute("set unknown = 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_limits() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (a int, b text);")?;
+        s.execute("insert into t values (1, 'a'), (2, 'b'), (3, 'c');")?;
+
+        let limited = |r: Result<ResultSet>| matches!(r, Err(Error::ResourceLimit(_)));
+        s.execute("set max_rows_examined = 2;")?;
+        assert!(limited(s.execute("select a from t;")));
+        s.execute("set max_rows_examined = 3;")?;
+        s.execute("select a from t;")?;
+
+        s.execute("set max_result_rows = 2;")?;
+        assert!(limited(s.execute("select * from t;")));
+        assert!(limited(s.execute("values (1), (2), (3);")));
+        s.execute("values (1), (2);")?;
+        s.execute("set max_result_rows = 0;")?;
+
+        s.execute("set max_memory = 64;")?;
+        assert!(limited(s.execute("select a, b from t;")));
+        s.execute("set max_memory = 0;")?;
+        s.execute("select a, b from t;")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_kill() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s1 = kvengine.session()?;
+        let mut s2 = kvengine.session()?;
+        assert_ne!(s1.id(), s2.id());
+        s1.execute("create table t (a int);")?;
+        let values = (0..20000)
+            .map(|i| format!("({})", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!("insert into t values {};", values);
+
+        // 不断 KILL s1，直到 s1 正在执行的语句被取消
+        let id = s1.id();
+        let result = std::thread::scope(|scope| {
+            let handle = scope.spawn(|| s1.execute(&sql));
+            while !handle.is_finished() {
+                s2.execute(&format!("kill {};", id))?;
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            handle.join().unwrap()
+        });
+        assert_eq!(result, Err(Error::Cancelled));
+        match s2.execute("select * from t;")? {
+            ResultSet::Scan { rows, .. } => assert!(rows.is_empty()),
+            _ => unreachable!(),
+        }
+
+        // 之后的语句不受影响
+        s1.execute("insert into t values (1);")?;
+        drop(s1);
+        assert!(s2.execute(&format!("kill {};", id)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_values() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        assert_eq!(
+            s.execute("values (1, 'a'), (2, null);")?,
+            ResultSet::Scan {
+                columns: vec!["column1".to_string(), "column2".to_string()],
+                rows: vec![
+                    vec![Value::Integer(1), Value::String("a".to_string())],
+                    vec![Value::Integer(2), Value::Null],
+                ],
+            }
+        );
+        // values 中不能引用列
+        assert!(s.execute("values (a);").is_err());
+
+        // FROM 和 JOIN 中的 VALUES，列名是 column1、column2 ...
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        assert_eq!(
+            rows(
+                &mut s,
+                "select v.column2, column1 * 10 from (values (1, 'a'), (2, 'b')) v where column1 > 1;"
+            )?,
+            vec![vec![Value::String("b".to_string()), Value::Integer(20)]]
+        );
+        s.execute("create table t (id int, name text, score float default 1.5);")?;
+        s.execute("insert into t values (1, 'x', 2.0), (2, 'y', 3.0), (3, 'z', 4.0);")?;
+        assert_eq!(
+            rows(
+                &mut s,
+                "select t.name, v.column2 from t join (values (1, 'a'), (3, 'c')) as v \
+                 on t.id = v.column1 order by t.id;"
+            )?,
+            vec![
+                vec![
+                    Value::String("x".to_string()),
+                    Value::String("a".to_string())
+                ],
+                vec![
+                    Value::String("z".to_string()),
+                    Value::String("c".to_string())
+                ],
+            ]
+        );
+        assert_eq!(
+            rows(
+                &mut s,
+                "select t.id, v.column2 from t left join (values (2, true)) v \
+                 on t.id = v.column1 order by t.id;"
+            )?,
+            vec![
+                vec![Value::Integer(1), Value::Null],
+                vec![Value::Integer(2), Value::Boolean(true)],
+                vec![Value::Integer(3), Value::Null],
+            ]
+        );
+
+        // INSERT ... SELECT，值的类型和列不同时进行隐式转换，没有给出的列使用默认值
+        assert_eq!(
+            s.execute("insert into t (id, name) select column1, column2 from (values (4, 'p'), (5, 'q')) v;")?,
+            ResultSet::Insert { count: 2 }
+        );
+        assert_eq!(
+            s.execute("insert into t select id + 10, name, 7 from t where id < 3;")?,
+            ResultSet::Insert { count: 2 }
+        );
+        assert_eq!(
+            rows(&mut s, "select * from t where id > 3 order by id;")?,
+            vec![
+                vec![
+                    Value::Integer(4),
+                    Value::String("p".to_string()),
+                    Value::Float(1.5)
+                ],
+                vec![
+                    Value::Integer(5),
+                    Value::String("q".to_string()),
+                    Value::Float(1.5)
+                ],
+                vec![
+                    Value::Integer(11),
+                    Value::String("x".to_string()),
+                    Value::Float(7.0)
+                ],
+                vec![
+                    Value::Integer(12),
+                    Value::String("y".to_string()),
+                    Value::Float(7.0)
+                ],
+            ]
+        );
+        for sql in [
+            "insert into t (id, name) select 6 from t;",
+            "insert into t select id, name, score, 1 from t;",
+            "insert into t select id + 100, name, score from t as of version 1;",
+            "insert into t select * from t;",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text);")?;
+        assert!(kvengine.watch("t2".into(), 0).is_err());
+
+        let mut watch = kvengine.watch("t1".into(), 0)?;
+        assert_eq!(watch.poll()?, vec![]);
+
+        s.execute("insert into t1 values(1, 'a');")?;
+        s.execute("insert into t1 values(2, 'b');")?;
+        let changes = watch.poll()?;
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| (c.key.clone(), c.row.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Value::Integer(1),
+                    Some(vec![Value::Integer(1), Value::String("a".to_string())])
+                ),
+                (
+                    Value::Integer(2),
+                    Some(vec![Value::Integer(2), Value::String("b".to_string())])
+                ),
+            ]
+        );
+        assert!(changes[0].version < changes[1].version);
+
+        // 已经返回过的变更不会再次返回
+        assert_eq!(watch.poll()?, vec![]);
+
+        // 未提交的变更不可见
+        let mut txn = kvengine.begin()?;
+        txn.create_row(
+            "t1".into(),
+            vec![Value::Integer(3), Value::String("c".to_string())],
+        )?;
+        assert_eq!(watch.poll()?, vec![]);
+        txn.commit()?;
+        assert_eq!(watch.poll()?.len(), 1);
+
+        // 读取变更使用只读事务，不会分配版本号
+        let version = |engine: &KVEngine<MemoryEngine>| -> Result<u64> {
+            let txn = engine.begin()?;
+            let version = txn.version();
+            txn.rollback()?;
+            Ok(version)
+        };
+        let before = version(&kvengine)?;
+        for _ in 0..3 {
+            assert_eq!(watch.poll()?, vec![]);
+        }
+        assert_eq!(version(&kvengine)?, before + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_as_of() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+
+        // version 1
+        s.execute("create table t1 (a int, b text);")?;
+        // version 2
+        s.execute("insert into t1 values(1, 'a');")?;
+        // version 3
+        s.execute("insert into t1 values(2, 'b');")?;
+
+        let rows = |result: ResultSet| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            rows(s.execute("select * from t1 as of version 3;")?),
+            vec![vec![Value::Integer(1), Value::String("a".to_string())]]
+        );
+        assert_eq!(rows(s.execute("select * from t1;")?).len(), 2);
+
+        // 表在 version 1 开启时还不存在
+        assert!(s.execute("select * from t1 as of version 1;").is_err());
+        assert_eq!(
+            s.execute("select * from t1 as of version 100;"),
+            Err(Error::Internal("version 100 does not exist".to_string()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_execute_batch() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let sql = "create table t (a int);
+            insert into t values (1);
+            insert into t1 values (1);
+            insert into t values (2);";
+
+        // 遇到错误之后停止执行
+        let results = s.execute_batch(sql, OnError::Abort)?;
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0],
+            Ok(ResultSet::CreateTable {
+                table_name: "t".to_string()
+            })
+        );
+        assert_eq!(results[1], Ok(ResultSet::Insert { count: 1 }));
+        assert!(results[2].is_err());
+
+        // 遇到错误之后继续执行，表已经存在，建表失败
+        let sql = sql.replace("(1)", "(3)").replace("(2)", "(4)");
+        let results = s.execute_batch(&sql, OnError::Continue)?;
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_err());
+        assert_eq!(results[1], Ok(ResultSet::Insert { count: 1 }));
+        assert!(results[2].is_err());
+        assert_eq!(results[3], Ok(ResultSet::Insert { count: 1 }));
+
+        // 解析失败时不执行任何语句
+        assert!(s
+            .execute_batch("insert into t values (5); select", OnError::Continue)
+            .is_err());
+        match s.execute("select * from t;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1)],
+                    vec![Value::Integer(3)],
+                    vec![Value::Integer(4)]
+                ]
+            ),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_variables() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s1 = kvengine.session()?;
+        let variables = |s: &mut Session<KVEngine<MemoryEngine>>, sql: &str| match s.execute(sql) {
+            Ok(ResultSet::Scan { rows, .. }) => Ok(rows
+                .into_iter()
+                .map(|row| match &row[..] {
+                    [Value::String(name), value] => (name.clone(), value.clone()),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()),
+            Ok(_) => unreachable!(),
+            Err(err) => Err(err),
+        };
+        assert_eq!(
+            variables(&mut s1, "show variables;")?,
+            vec![
+                ("lock_timeout".to_string(), Value::Integer(0)),
+                ("max_memory".to_string(), Value::Integer(0)),
+                ("max_result_rows".to_string(), Value::Integer(0)),
+                ("max_rows_examined".to_string(), Value::Integer(0)),
+                ("max_txn_age".to_string(), Value::Integer(0)),
+                ("prune_versions".to_string(), Value::Boolean(false)),
+                ("query_cache_size".to_string(), Value::Integer(0)),
+                ("statement_timeout".to_string(), Value::Integer(0)),
+            ]
+        );
+
+        // SET GLOBAL 修改新 session 的默认值，不影响已有的 session
+        s1.execute("set max_result_rows = 5;")?;
+        s1.execute("set global max_result_rows = 10;")?;
+        s1.execute("set global lock_timeout = 100;")?;
+        s1.execute("set global prune_versions = true;")?;
+        let mut s2 = kvengine.session()?;
+        let get = |rows: Vec<(String, Value)>, name: &str| {
+            rows.into_iter().find(|(n, _)| n == name).unwrap().1
+        };
+        let rows = variables(&mut s1, "show variables;")?;
+        assert_eq!(get(rows, "max_result_rows"), Value::Integer(5));
+        let rows = variables(&mut s1, "show global variables;")?;
+        assert_eq!(get(rows, "max_result_rows"), Value::Integer(10));
+        let rows = variables(&mut s2, "show variables;")?;
+        assert_eq!(get(rows.clone(), "max_result_rows"), Value::Integer(10));
+        assert_eq!(get(rows.clone(), "lock_timeout"), Value::Integer(100));
+        assert_eq!(get(rows, "prune_versions"), Value::Boolean(true));
+
+        // 全局配置对之后开启的事务生效
+        let options = kvengine.kv.options()?;
+        assert_eq!(
+            options.lock_timeout,
+            Some(std::time::Duration::from_millis(100))
+        );
+        assert!(options.prune_versions);
+
+        for sql in [
+            "set lock_timeout = 100;",
+            "set global prune_versions = 1;",
+            "set global max_txn_age = -1;",
+            "set global unknown = 1;",
+        ] {
+            assert!(s2.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_table() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text not null, c float);")?;
+        s.execute("create table t2 (a int, b text) with (storage = 'columnar');")?;
+        s.execute("insert into t1 values (1, 'a', 1.0), (2, 'b', 2.0), (3, 'c', null);")?;
+        s.execute("insert into t2 values (1, 'a'), (2, 'b');")?;
+
+        let check = |s: &mut Session<_>, table: &str| -> Result<Vec<String>> {
+            match s.execute(&format!("check table {};", table))? {
+                ResultSet::Scan { columns, rows } => {
+                    assert_eq!(columns, vec!["problem".to_string()]);
+                    Ok(rows
+                        .into_iter()
+                        .map(|row| match &row[0] {
+                            Value::String(problem) => problem.clone(),
+                            v => panic!("unexpected value {:?}", v),
+                        })
+                        .collect())
+                }
+                r => panic!("unexpected result {:?}", r),
+            }
+        };
+        assert!(check(&mut s, "t1")?.is_empty());
+        assert!(check(&mut s, "t2")?.is_empty());
+        assert!(s.execute("check table t3;").is_err());
+        assert!(s.execute("check table sys_tables;").is_err());
+
+        // 绕过 SQL 层直接写入损坏的数据
+        let txn = kvengine.kv.begin()?;
+        let row_key = |id| bincode::serialize(&Key::Row("t1".into(), Value::Integer(id)));
+        let row = vec![Value::Integer(4), Value::Null, Value::Float(1.0)];
+        txn.set(row_key(1)?, bincode::serialize(&row)?)?;
+        txn.set(row_key(2)?, vec![0xff])?;
+        let row = vec![Value::Integer(3), Value::String("c".to_string())];
+        txn.set(row_key(3)?, bincode::serialize(&row)?)?;
+        let column_key =
+            |i, id| bincode::serialize(&Key::Column("t2".into(), i, Value::Integer(id)));
+        txn.delete(column_key(1, 1)?)?;
+        txn.set(
+            column_key(1, 3)?,
+            bincode::serialize(&vec![Value::String("c".to_string())])?,
+        )?;
+        txn.commit()?;
+
+        let problems = check(&mut s, "t1")?;
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].contains("primary key does not match"));
+        assert!(problems[1].contains("cannot be null"));
+        assert!(problems[2].starts_with("row Integer(2)"));
+        assert!(problems[3].contains("has 3 columns, but row has 2 values"));
+
+        let problems = check(&mut s, "t2")?;
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("primary key does not exist"));
+        assert_eq!(problems[1], "row Integer(1): column b is missing");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reindex() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text) with (compression = 'dictionary');")?;
+        s.execute("create table t2 (a int, b text) with (storage = 'columnar');")?;
+        s.execute("insert into t1 values (1, 'a'), (2, 'b');")?;
+
+        // 行保存在了错误的主键下
+        let mut txn = kvengine.begin()?;
+        let row = vec![Value::Integer(3), Value::String("c".to_string())];
+        let value = txn.encode_values(&txn.must_get_table("t1".into())?, &row)?;
+        txn.txn.set(
+            bincode::serialize(&Key::Row("t1".into(), Value::Integer(1)))?,
+            value,
+        )?;
+        txn.commit()?;
+        assert_eq!(
+            s.execute("check table t1;")?,
+            ResultSet::Scan {
+                columns: vec!["problem".to_string()],
+                rows: vec![vec![Value::String(
+                    "row Integer(1): primary key does not match the row".to_string()
+                )]],
+            }
+        );
+
+        assert_eq!(
+            s.execute("reindex table t1;")?,
+            ResultSet::Reindex {
+                table_name: "t1".to_string(),
+                rows: 2,
+            }
+        );
+        assert_eq!(
+            s.execute("check table t1;")?,
+            ResultSet::Scan {
+                columns: vec!["problem".to_string()],
+                rows: vec![],
+            }
+        );
+        match s.execute("select * from t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(2), Value::String("b".to_string())],
+                    vec![Value::Integer(3), Value::String("c".to_string())],
+                ]
+            ),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(s.execute("reindex index primary on t1;").is_ok());
+
+        // 两行的主键相同时无法重建
+        let mut txn = kvengine.begin()?;
+        let row = vec![Value::Integer(2), Value::String("d".to_string())];
+        let value = txn.encode_values(&txn.must_get_table("t1".into())?, &row)?;
+        txn.txn.set(
+            bincode::serialize(&Key::Row("t1".into(), Value::Integer(3)))?,
+            value,
+        )?;
+        txn.commit()?;
+        assert!(s.execute("reindex table t1;").is_err());
+
+        assert!(s.execute("reindex index idx on t1;").is_err());
+        assert!(s.execute("reindex table t2;").is_err());
+        assert!(s.execute("reindex table t3;").is_err());
+        assert!(s.execute("reindex table sys_tables;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_cache() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text);")?;
+        s.execute("create table t2 (a int, b text);")?;
+        s.execute("create table t3 (a int, t int) with (ttl = '1 hours', ttl_column = 't');")?;
+        s.execute("insert into t1 values (1, 'a');")?;
+        assert!(s.execute("set query_cache_size = 10;").is_err());
+        s.execute("set global query_cache_size = 10;")?;
+
+        // 命中缓存的查询不会扫描表
+        let scans = |table: &str| -> Result<i64> {
+            let rows: Vec<(String, i64)> = kvengine
+                .session()?
+                .query_as("select name, scans from sys_tables;")?;
+            Ok(rows.into_iter().find(|(name, _)| name == table).unwrap().1)
+        };
+        let select = "select * from t1;";
+        let r1 = s.execute(select)?;
+        assert_eq!(s.execute(select)?, r1);
+        assert_eq!(scans("t1")?, 1);
+        // 不同的 SQL 文本分别缓存
+        s.execute("select a from t1;")?;
+        assert_eq!(scans("t1")?, 2);
+
+        // 写入其他表不影响缓存，写入查询的表之后缓存失效
+        s.execute("insert into t2 values (1, 'a');")?;
+        assert_eq!(s.execute(select)?, r1);
+        assert_eq!(scans("t1")?, 2);
+        s.execute("insert into t1 values (2, 'b');")?;
+        match s.execute(select)? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows.len(), 2),
+            _ => unreachable!(),
+        }
+        assert_eq!(scans("t1")?, 3);
+
+        // 回滚的写入不会让缓存失效
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t1".into(), vec![Value::Integer(3), Value::Null])?;
+        txn.rollback()?;
+        s.execute(select)?;
+        assert_eq!(scans("t1")?, 3);
+
+        // 有过期时间的表和系统表不会缓存
+        s.execute("select * from t3;")?;
+        s.execute("select * from t3;")?;
+        assert_eq!(scans("t3")?, 2);
+
+        // 命中缓存时同样检查返回的行数
+        s.execute("set max_result_rows = 1;")?;
+        assert!(s.execute(select).is_err());
+        s.execute("set max_result_rows = 0;")?;
+
+        s.execute("set global query_cache_size = 0;")?;
+        s.execute(select)?;
+        assert_eq!(scans("t1")?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_advisory_locks() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s1 = kvengine.session()?;
+        let mut s2 = kvengine.session()?;
+        let call = |s: &mut Session<KVEngine<MemoryEngine>>, sql: &str| -> Result<Value> {
+            match s.execute(sql)? {
+                ResultSet::Scan { mut rows, .. } => Ok(rows.remove(0).remove(0)),
+                r => panic!("unexpected result {:?}", r),
+            }
+        };
+
+        // 没有 FROM 的查询计算常量表达式
+        assert_eq!(
+            s1.execute("select 1 + 2, 'a';")?,
+            ResultSet::Scan {
+                columns: vec!["1 + 2".to_string(), "'a'".to_string()],
+                rows: vec![vec![Value::Integer(3), Value::String("a".to_string())]],
+            }
+        );
+        assert!(s1.execute("select *;").is_err());
+        assert!(s1.execute("select a;").is_err());
+        assert!(s1.execute("select unknown(1);").is_err());
+
+        assert_eq!(
+            s1.execute("select get_lock('a', 10);")?,
+            ResultSet::Scan {
+                columns: vec!["get_lock('a', 10)".to_string()],
+                rows: vec![vec![Value::Integer(1)]],
+            }
+        );
+        assert_eq!(
+            call(&mut s1, "select get_lock('a', 0);")?,
+            Value::Integer(1)
+        );
+        assert_eq!(
+            call(&mut s2, "select get_lock('a', 0);")?,
+            Value::Integer(0)
+        );
+        assert_eq!(
+            call(&mut s2, "select is_free_lock('a');")?,
+            Value::Integer(0)
+        );
+        assert_eq!(
+            call(&mut s2, "select release_lock('a');")?,
+            Value::Integer(0)
+        );
+        assert_eq!(call(&mut s2, "select release_lock('b');")?, Value::Null);
+        assert_eq!(
+            call(&mut s1, "select release_lock('a');")?,
+            Value::Integer(1)
+        );
+        assert_eq!(
+            call(&mut s2, "select get_lock('a', 0);")?,
+            Value::Integer(0)
+        );
+        assert_eq!(
+            call(&mut s1, "select release_all_locks();")?,
+            Value::Integer(1)
+        );
+        assert_eq!(
+            call(&mut s2, "select get_lock('a', 0);")?,
+            Value::Integer(1)
+        );
+
+        // session 结束时释放持有的锁
+        drop(s2);
+        assert_eq!(
+            call(&mut s1, "select is_free_lock('a');")?,
+            Value::Integer(1)
+        );
+
+        // 锁函数可以出现在任意表达式中，每条语句只计算一次
+        assert_eq!(
+            call(&mut s1, "select get_lock('a', 0) + 1;")?,
+            Value::Integer(2)
+        );
+        s1.execute("create table t (id int);")?;
+        s1.execute("insert into t values (1), (2);")?;
+        assert_eq!(
+            s1.execute("select id, release_lock('a') from t;")?,
+            ResultSet::Scan {
+                columns: vec!["id".to_string(), "release_lock('a')".to_string()],
+                rows: vec![
+                    vec![Value::Integer(1), Value::Integer(1)],
+                    vec![Value::Integer(2), Value::Integer(1)],
+                ],
+            }
+        );
+        assert_eq!(
+            call(&mut s1, "select is_free_lock('a');")?,
+            Value::Integer(1)
+        );
+        // 参数需要是常量，类型需要正确
+        assert!(s1.execute("select get_lock('a');").is_err());
+        assert!(s1.execute("select get_lock(1, 0);").is_err());
+        assert!(s1.execute("select get_lock('a', id) from t;").is_err());
+        assert!(s1
+            .execute("create view v as select get_lock('a', 0) from t;")
+            .and_then(|_| s1.execute("select * from v;"))
+            .is_err());
+        assert_eq!(
+            call(&mut s1, "select is_free_lock('a');")?,
+            Value::Integer(1)
+        );
+
+        // 锁函数的结果不会写入查询缓存
+        let mut s2 = kvengine.session()?;
+        s1.execute("set global query_cache_size = 10;")?;
+        let sql = "select id, get_lock('b', 0) from t;";
+        for _ in 0..2 {
+            s2.execute(sql)?;
+            assert!(kvengine.query_cache().get(sql)?.is_none());
+        }
+        assert_eq!(
+            call(&mut s1, "select get_lock('b', 0);")?,
+            Value::Integer(0)
+        );
+        assert_eq!(
+            call(&mut s2, "select release_all_locks();")?,
+            Value::Integer(2)
+        );
+
+        // get_xact_lock 获取的锁在事务提交或者回滚时释放，没有 BEGIN 时在语句结束时释放
+        assert_eq!(
+            call(&mut s1, "select get_xact_lock('c', 0);")?,
+            Value::Integer(1)
+        );
+        assert_eq!(
+            call(&mut s2, "select get_lock('c', 0);")?,
+            Value::Integer(1)
+        );
+        assert_eq!(
+            call(&mut s1, "select get_xact_lock('c', 0);")?,
+            Value::Integer(0)
+        );
+        s2.execute("select release_lock('c');")?;
+        for end in ["commit;", "rollback;"] {
+            s1.execute("begin;")?;
+            assert_eq!(
+                call(&mut s1, "select get_xact_lock('c', 0);")?,
+                Value::Integer(1)
+            );
+            s1.execute("select id from t;")?;
+            // 事务级的获取不能通过 release_lock 释放
+            assert_eq!(
+                call(&mut s1, "select release_lock('c');")?,
+                Value::Integer(0)
+            );
+            assert_eq!(
+                call(&mut s2, "select get_lock('c', 0);")?,
+                Value::Integer(0)
+            );
+            s1.execute(end)?;
+            assert_eq!(
+                call(&mut s2, "select is_free_lock('c');")?,
+                Value::Integer(1)
+            );
+        }
+        // 出错回滚的事务在 COMMIT 或者 ROLLBACK 之前仍然持有锁
+        s1.execute("begin;")?;
+        s1.execute("select get_xact_lock('c', 0), get_lock('d', 0);")?;
+        assert!(s1.execute("select * from missing;").is_err());
+        assert_eq!(
+            call(&mut s2, "select is_free_lock('c');")?,
+            Value::Integer(0)
+        );
+        assert!(s1.execute("commit;").is_err());
+        assert_eq!(
+            call(&mut s2, "select is_free_lock('c');")?,
+            Value::Integer(1)
+        );
+        // session 级的锁不受事务影响
+        assert_eq!(
+            call(&mut s2, "select is_free_lock('d');")?,
+            Value::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_attach() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let other = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text);")?;
+        s.execute("insert into t1 values (1, 'main');")?;
+        other
+            .session()?
+            .execute("create table t1 (a int, b text);")?;
+
+        s.attach("other", other.clone())?;
+        assert!(s.attach("other", other.clone()).is_err());
+        assert!(s.attach("main", other.clone()).is_err());
+        s.execute("insert into other.t1 values (2, 'other');")?;
+        s.execute("create table other.t2 (a int);")?;
+
+        let rows = |result: ResultSet| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            r => panic!("unexpected result {:?}", r),
+        };
+        assert_eq!(
+            rows(s.execute("select b from other.t1;")?),
+            vec![vec![Value::String("other".to_string())]]
+        );
+        assert_eq!(
+            rows(s.execute("select b from main.t1;")?),
+            vec![vec![Value::String("main".to_string())]]
+        );
+        // 写入的是附加的数据库
+        assert_eq!(
+            rows(other.session()?.execute("select * from t1;")?),
+            vec![vec![Value::Integer(2), Value::String("other".to_string())]]
+        );
+        assert!(other.begin()?.get_table("t2".into())?.is_some());
+        assert!(kvengine.begin()?.get_table("t2".into())?.is_none());
+
+        assert_eq!(
+            s.execute("detach database other;")?,
+            ResultSet::Detach {
+                alias: "other".to_string()
+            }
+        );
+        assert!(s.execute("select * from other.t1;").is_err());
+        assert!(s.execute("detach database other;").is_err());
+        // 内存中的引擎无法打开文件
+        assert!(s.execute("attach database '/tmp/db' as other;").is_err());
+
+        // 附加磁盘上的数据库
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("other.db");
+        let mut s = KVEngine::new(DiskEngine::new(dir.path().join("main.db"))?).session()?;
+        let sql = format!("attach database '{}' as other;", path.display());
+        assert_eq!(
+            s.execute(&sql)?,
+            ResultSet::Attach {
+                alias: "other".to_string()
+            }
+        );
+        // 同一个文件不能同时被打开两次
+        assert!(s.execute(&sql.replace("as other", "as other2")).is_err());
+        s.execute("create table other.t1 (a int);")?;
+        s.execute("insert into other.t1 values (1);")?;
+        s.execute("detach database other;")?;
+        assert_eq!(
+            rows(
+                KVEngine::new(DiskEngine::new(path)?)
+                    .session()?
+                    .execute("select * from t1;")?
+            ),
+            vec![vec![Value::Integer(1)]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_point() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, loc point);")?;
+        s.execute(
+            "create table t2 (a int, loc geometry not null) with (storage = 'columnar', compression = 'dictionary');",
+        )?;
+        for t in ["t1", "t2"] {
+            s.execute(&format!(
+                "insert into {} values (1, st_point(0, 0)), (2, st_point(3.0, 4));",
+                t
+            ))?;
+            assert_eq!(
+                s.execute(&format!(
+                    "select a, st_distance(loc, st_point(0, 0)), st_dwithin(loc, st_point(1, 1), 2) from {};",
+                    t
+                ))?,
+                ResultSet::Scan {
+                    columns: vec![
+                        "a".to_string(),
+                        "st_distance(loc, st_point(0, 0))".to_string(),
+                        "st_dwithin(loc, st_point(1, 1), 2)".to_string(),
+                    ],
+                    rows: vec![
+                        vec![Value::Integer(1), Value::Float(0.0), Value::Boolean(true)],
+                        vec![Value::Integer(2), Value::Float(5.0), Value::Boolean(false)],
+                    ],
+                }
+            );
+            // 列的类型是 POINT
+            assert!(s
+                .execute(&format!("insert into {} values (3, 1);", t))
+                .is_err());
+        }
+        s.execute("insert into t1 values (3, null);")?;
+        assert!(s.execute("insert into t2 values (3, null);").is_err());
+        assert_eq!(
+            s.execute("select st_x(st_point(1, 2)), st_y(st_point(1, 2));")?,
+            ResultSet::Scan {
+                columns: vec![
+                    "st_x(st_point(1, 2))".to_string(),
+                    "st_y(st_point(1, 2))".to_string(),
+                ],
+                rows: vec![vec![Value::Float(1.0), Value::Float(2.0)]],
+            }
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regexp() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text);")?;
+        s.execute("insert into t1 values (1, 'apple'), (2, 'banana'), (3, null);")?;
+        assert_eq!(
+            s.execute("select a, b regexp '^a', regexp_matches(b, 'an+a$') from t1;")?,
+            ResultSet::Scan {
+                columns: vec![
+                    "a".to_string(),
+                    "b REGEXP '^a'".to_string(),
+                    "b REGEXP 'an+a$'".to_string(),
+                ],
+                rows: vec![
+                    vec![
+                        Value::Integer(1),
+                        Value::Boolean(true),
+                        Value::Boolean(false)
+                    ],
+                    vec![
+                        Value::Integer(2),
+                        Value::Boolean(false),
+                        Value::Boolean(true)
+                    ],
+                    vec![Value::Integer(3), Value::Null, Value::Null],
+                ],
+            }
+        );
+        assert!(s.execute("select b regexp '[' from t1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text, c int);")?;
+        s.execute("create table t2 (a int, b text, c int) with (storage = 'columnar');")?;
+        for t in ["t1", "t2"] {
+            s.execute(&format!(
+                "insert into {} values (1, 'x', 10), (2, 'y', 20), (3, null, 20), (4, 'z', 10), (5, 'w', null);",
+                t
+            ))?;
+            assert_eq!(
+                s.execute(&format!(
+                    "select c, count(*), count(b), sum(a), avg(a), min(b), max(b), group_concat(b) from {} group by c;",
+                    t
+                ))?,
+                ResultSet::Scan {
+                    columns: vec![
+                        "c".to_string(),
+                        "count(*)".to_string(),
+                        "count(b)".to_string(),
+                        "sum(a)".to_string(),
+                        "avg(a)".to_string(),
+                        "min(b)".to_string(),
+                        "max(b)".to_string(),
+                        "group_concat(b)".to_string(),
+                    ],
+                    rows: vec![
+                        vec![
+                            Value::Integer(10),
+                            Value::Integer(2),
+                            Value::Integer(2),
+                            Value::Integer(5),
+                            Value::Float(2.5),
+                            Value::String("x".to_string()),
+                            Value::String("z".to_string()),
+                            Value::String("x,z".to_string()),
+                        ],
+                        vec![
+                            Value::Integer(20),
+                            Value::Integer(2),
+                            Value::Integer(1),
+                            Value::Integer(5),
+                            Value::Float(2.5),
+                            Value::String("y".to_string()),
+                            Value::String("y".to_string()),
+                            Value::String("y".to_string()),
+                        ],
+                        vec![
+                            Value::Null,
+                            Value::Integer(1),
+                            Value::Integer(1),
+                            Value::Integer(5),
+                            Value::Float(5.0),
+                            Value::String("w".to_string()),
+                            Value::String("w".to_string()),
+                            Value::String("w".to_string()),
+                        ],
+                    ],
+                }
+            );
+        }
+
+        // 指定顺序和分隔符
+        let rows = |result| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(
+            rows(s.execute(
+                "select string_agg(b, ' | ' order by c desc, a), group_concat(a order by b desc) from t1;"
+            )?),
+            vec![vec![
+                Value::String("y | x | z | w".to_string()),
+                Value::String("4,2,1,5,3".to_string()),
+            ]]
+        );
+        // select 列表中可以使用聚合结果和分组列组成的表达式
+        assert_eq!(
+            rows(s.execute("select c + 1, count(*) * 10 from t1 group by c;")?),
+            vec![
+                vec![Value::Integer(11), Value::Integer(20)],
+                vec![Value::Integer(21), Value::Integer(20)],
+                vec![Value::Null, Value::Integer(10)],
+            ]
+        );
+        // 没有 GROUP BY 时即使没有行也返回一行
+        s.execute("create table t3 (a int);")?;
+        assert_eq!(
+            rows(s.execute("select count(*), sum(a), group_concat(a) from t3;")?),
+            vec![vec![Value::Integer(0), Value::Null, Value::Null]]
+        );
+        assert_eq!(
+            rows(s.execute("select a, count(*) from t3 group by a;")?),
+            Vec::<Vec<Value>>::new()
+        );
+
+        for sql in [
+            "select a, count(*) from t1;",
+            "select * from t1 group by a;",
+            "select sum(count(*)) from t1;",
+            "select a from t1 group by count(*);",
+            "select sum(a, c) from t1;",
+            "select string_agg(b) from t1;",
+            "select sum(b) from t1;",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        // 浮点数的和溢出时报错
+        s.execute("create table t4 (id int, a float);")?;
+        s.execute("insert into t4 values (1, 1e308), (2, 1e308);")?;
+        assert!(matches!(
+            s.execute("select sum(a) from t4;"),
+            Err(Error::Internal(e)) if e.contains("not a finite number")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_distinct_aggregates() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b text, c int);")?;
+        s.execute(
+            "insert into t1 values (1, 'x', 1), (2, 'y', 1), (3, 'x', 2), (4, 'x', 1), (5, null, 1), (6, 'y', null);",
+        )?;
+        assert_eq!(
+            s.execute(
+                "select count(distinct b), count(distinct b, c), sum(distinct c), group_concat(distinct b order by b desc) from t1;"
+            )?,
+            ResultSet::Scan {
+                columns: vec![
+                    "count(DISTINCT b)".to_string(),
+                    "count(DISTINCT b, c)".to_string(),
+                    "sum(DISTINCT c)".to_string(),
+                    "group_concat(DISTINCT b ORDER BY b DESC)".to_string(),
+                ],
+                rows: vec![vec![
+                    Value::Integer(2),
+                    Value::Integer(3),
+                    Value::Integer(3),
+                    Value::String("y,x".to_string()),
+                ]],
+            }
+        );
+        match s.execute("select c, count(distinct b) from t1 group by c;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1), Value::Integer(2)],
+                    vec![Value::Integer(2), Value::Integer(1)],
+                    vec![Value::Null, Value::Integer(1)],
+                ]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+        // 多个参数只能用于 count(DISTINCT ...)
+        assert!(s.execute("select count(b, c) from t1;").is_err());
+        assert!(s.execute("select count(distinct *) from t1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistical_aggregates() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int, b int, c float);")?;
+        s.execute(
+            "insert into t1 values (1, 1, 2.0), (2, 1, 4.0), (3, 1, 4.0), (4, 1, 4.0), (5, 1, 5.0), (6, 1, 5.0), (7, 1, 7.0), (8, 1, 9.0), (9, 2, 3.0), (10, 2, null);",
+        )?;
+        let rows = |result| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(
+            rows(s.execute(
+                "select b, var_pop(c), stddev_pop(c), variance(c), percentile_cont(c, 0.5), percentile_cont(a, 0.25), approx_percentile(c, 1) from t1 group by b;"
+            )?),
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Float(4.0),
+                    Value::Float(2.0),
+                    Value::Float(32.0 / 7.0),
+                    Value::Float(4.5),
+                    Value::Float(2.75),
+                    Value::Float(9.0),
+                ],
+                // 跳过 NULL，只有一个值时样本方差是 NULL
+                vec![
+                    Value::Integer(2),
+                    Value::Float(0.0),
+                    Value::Float(0.0),
+                    Value::Null,
+                    Value::Float(3.0),
+                    Value::Float(9.25),
+                    Value::Float(3.0),
+                ],
+            ]
+        );
+        s.execute("create table t2 (a int, b text);")?;
+        assert_eq!(
+            rows(s.execute(
+                "select stddev(a), stddev_samp(a), var_samp(a), percentile_cont(a, 0.5), approx_percentile(a, 0.5) from t2;"
+            )?),
+            vec![vec![Value::Null; 5]]
+        );
+        s.execute("insert into t2 values (1, 'a');")?;
+        for sql in [
+            "select percentile_cont(a, 1.5) from t2;",
+            "select approx_percentile(a, b) from t2;",
+            "select percentile_cont(a) from t2;",
+            "select stddev(b) from t2;",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ttl() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let now = super::now_secs();
+        for (name, storage) in [("t1", "row"), ("t2", "columnar")] {
+            s.execute(&format!(
+                "create table {} (a int, b text, ts int) with (storage = '{}', ttl = '7 days', ttl_column = 'ts');",
+                name, storage
+            ))?;
+            s.execute(&format!(
+                "insert into {} values (1, 'a', {}), (2, 'b', {}), (3, 'c', null);",
+                name,
+                now - 8 * 24 * 3600,
+                now
+            ))?;
+
+            // 过期的行读取时被过滤，只读取部分列时同样生效
+            for sql in [
+                format!("select * from {};", name),
+                format!("select a from {};", name),
+            ] {
+                match s.execute(&sql)? {
+                    ResultSet::Scan { rows, .. } => {
+                        assert_eq!(
+                            rows.iter().map(|r| r[0].clone()).collect::<Vec<_>>(),
+                            vec![Value::Integer(2), Value::Integer(3)]
+                        )
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        // CHECKPOINT 时删除过期的行
+        let rows = |s: &mut Session<KVEngine<MemoryEngine>>| match s
+            .execute("select rows from sys_tables;")
+        {
+            Ok(ResultSet::Scan { rows, .. }) => rows,
+            _ => unreachable!(),
+        };
+        assert_eq!(rows(&mut s), vec![vec![Value::Integer(3)]; 2]);
+        s.execute("checkpoint;")?;
+        assert_eq!(rows(&mut s), vec![vec![Value::Integer(2)]; 2]);
+
+        for sql in [
+            "create table t3 (a int, ts int) with (ttl = '7 days');",
+            "create table t3 (a int, ts int) with (ttl_column = 'ts');",
+            "create table t3 (a int, ts text) with (ttl = '7 days', ttl_column = 'ts');",
+            "create table t3 (a int, ts int) with (ttl = '7 days', ttl_column = 'b');",
+            "create table t3 (a int, ts int) with (ttl = '7 weeks', ttl_column = 'ts');",
+            "create table t3 (a int, ts int) with (ttl = '0 days', ttl_column = 'ts');",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_table() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("data.csv");
+        std::fs::write(
+            &path,
+            "id;name;score\n1;\"a;b\";1.5\n2;;\n\n3;\"line\nbreak\";3\n",
+        )?;
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute(&format!(
+            "create external table t1 (id int, name text, score float) location '{}' with (header = 'true', delimiter = ';');",
+            path.display()
+        ))?;
+        assert_eq!(
+            s.execute("select name, score from t1;")?,
+            ResultSet::Scan {
+                columns: vec!["name".to_string(), "score".to_string()],
+                rows: vec![
+                    vec![Value::String("a;b".to_string()), Value::Float(1.5)],
+                    vec![Value::Null, Value::Null],
+                    vec![Value::String("line\nbreak".to_string()), Value::Float(3.0)],
+                ],
+            }
+        );
+        assert_eq!(
+            s.execute("select count(*), sum(score) from t1;")?,
+            ResultSet::Scan {
+                columns: vec!["count(*)".to_string(), "sum(score)".to_string()],
+                rows: vec![vec![Value::Integer(3), Value::Float(4.5)]],
+            }
+        );
+        // 外部表是只读的
+        assert!(s.execute("insert into t1 values (4, 'd', 4.0);").is_err());
+        assert!(s.execute("reindex table t1;").is_err());
+
+        // 每次查询重新读取文件
+        std::fs::write(&path, "id;name;score\n5;e;x\n")?;
+        let err = s.execute("select * from t1;").unwrap_err();
+        assert_eq!(
+            err,
+            Error::Internal(
+                "line 2 of external table t1: invalid value \"x\" for column score".to_string()
+            )
+        );
+        match s.execute("check table t1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows.len(), 1),
+            r => panic!("unexpected result {:?}", r),
+        }
+        std::fs::remove_file(&path)?;
+        assert!(s.execute("select * from t1;").is_err());
+
+        // 没有表头、NOT NULL 列和字段个数的校验
+        std::fs::write(&path, "1,a\n,b\n")?;
+        s.execute(&format!(
+            "create external table t2 (id int not null, name text) location '{}';",
+            path.display()
+        ))?;
+        assert!(s.execute("select * from t2;").is_err());
+        std::fs::write(&path, "1,a,x\n")?;
+        assert!(s.execute("select * from t2;").is_err());
+        std::fs::write(&path, "1,a\n")?;
+        assert_eq!(
+            s.execute("select * from t2;")?,
+            ResultSet::Scan {
+                columns: vec!["id".to_string(), "name".to_string()],
+                rows: vec![vec![Value::Integer(1), Value::String("a".to_string())]],
+            }
+        );
+
+        // 外部表只支持文件格式的选项
+        for sql in [
+            "create external table t3 (a int) location 'a.csv' with (storage = 'columnar');",
+            "create external table t3 (a int) location 'a.csv' with (delimiter = ';;');",
+            "create external table t3 (a int) location 'a.csv' with (header = 'yes');",
+            "create table t3 (a int) with (header = 'true');",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_cache() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (a int);")?;
+
+        let mut txn = kvengine.begin()?;
+        assert!(txn.get_table("t1".into())?.is_some());
+        assert!(txn.get_table("t2".into())?.is_none());
+        // 其他事务的 DDL 对当前事务不可见
+        s.execute("create table t2 (a int);")?;
+        assert!(txn.get_table("t2".into())?.is_none());
+
+        // 当前事务的 DDL 更新缓存
+        let table = Table {
+            name: "t3".into(),
+            columns: vec![Column {
+                name: "a".into(),
+                datatype: DataType::Integer,
+                nullable: true,
+                default: Some(Value::Null),
+                default_expr: None,
+            }],
+            storage: StorageLayout::Row,
+            compression: Compression::None,
+            ttl: None,
+            external: None,
+            indexes: Vec::new(),
+        };
+        assert!(txn.get_table("t3".into())?.is_none());
+        txn.create_table(table.clone())?;
+        assert_eq!(txn.get_table("t3".into())?, Some(table.clone()));
+        assert!(txn.create_table(table).is_err());
+        txn.create_row("t3".into(), vec![Value::Integer(1)])?;
+        txn.rollback()?;
+
+        let txn = kvengine.begin()?;
+        assert!(txn.get_table("t2".into())?.is_some());
+        assert!(txn.get_table("t3".into())?.is_none());
+        txn.commit()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("data.csv");
+        let mut data = "id|name\n".to_string();
+        for i in 1..=3000 {
+            data.push_str(&format!("{}|n{}\n", i, i % 10));
+        }
+        std::fs::write(&path, data)?;
+
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (id int, name text);")?;
+        s.execute("create table t2 (id int, name text) with (storage = 'columnar', compression = 'dictionary');")?;
+        let stats = |s: &mut Session<_>, table: &str| -> Result<ResultSet> {
+            s.execute(&format!(
+                "select count(*), sum(id), count(distinct name) from {};",
+                table
+            ))
+        };
+        let expected = |count: i64, sum: i64| ResultSet::Scan {
+            columns: vec![
+                "count(*)".to_string(),
+                "sum(id)".to_string(),
+                "count(DISTINCT name)".to_string(),
+            ],
+            rows: vec![vec![
+                Value::Integer(count),
+                Value::Integer(sum),
+                Value::Integer(10),
+            ]],
+        };
+        for table in ["t1", "t2"] {
+            assert_eq!(
+                s.execute(&format!(
+                    "copy {} from '{}' with (header = 'true', delimiter = '|');",
+                    table,
+                    path.display()
+                ))?,
+                ResultSet::Insert { count: 3000 }
+            );
+            assert_eq!(stats(&mut s, table)?, expected(3000, 4501500));
+        }
+
+        // 导入失败时已经写入的数据都回滚
+        std::fs::write(&path, "4001,n1\n4002,n2\nx,n3\n")?;
+        let err = s
+            .execute(&format!("copy t1 from '{}';", path.display()))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::Internal(
+                "line 3 of COPY into t1: invalid value \"x\" for column id".to_string()
+            )
+        );
+        assert_eq!(stats(&mut s, "t1")?, expected(3000, 4501500));
+
+        // 没有设置 lock_timeout 时和未提交的事务冲突直接报错
+        std::fs::write(&path, "4001,n1\n")?;
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t1".into(), vec![Value::Integer(4001), Value::Null])?;
+        assert_eq!(
+            s.execute(&format!("copy t1 from '{}';", path.display())),
+            Err(Error::WriteConflict)
+        );
+        txn.rollback()?;
+        s.execute(&format!("copy t1 from '{}';", path.display()))?;
+        assert_eq!(stats(&mut s, "t1")?, expected(3001, 4505501));
+
+        // 设置 lock_timeout 之后和 INSERT 一样等待冲突的事务结束
+        std::fs::write(&path, "id,name\n4002,n2\n")?;
+        s.execute("set global lock_timeout = 5000;")?;
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t1".into(), vec![Value::Integer(4002), Value::Null])?;
+        let sql = format!("copy t1 from '{}' with (header true);", path.display());
+        std::thread::scope(|scope| {
+            let handle = scope.spawn(|| s.execute(&sql));
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            txn.rollback()?;
+            handle.join().unwrap()
+        })?;
+        s.execute("set global lock_timeout = 0;")?;
+        assert_eq!(stats(&mut s, "t1")?, expected(3002, 4509503));
+
+        // 只读的表和错误的选项
+        for sql in [
+            "copy t3 from 'a.csv';",
+            "copy sys_tables from 'a.csv';",
+            "copy t1 from 'a.csv' with (storage = 'row');",
+            "copy t1 from 'a.csv' with (delimiter = '');",
+            "copy t1 from 'a.csv' with (header 1);",
+            "copy t1 from 'a.csv' with (delimiter 1);",
+            "copy t1 from 'missing.csv';",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_functions() -> Result<()> {
+        // 重复字符串 n 次，返回序号和字符串两列
+        struct Repeat;
+        impl TableFunction for Repeat {
+            fn columns(&self, _: &[Value]) -> Result<Vec<String>> {
+                Ok(vec!["i".to_string(), "s".to_string()])
+            }
+
+            fn rows(&self, args: Vec<Value>) -> Result<Box<dyn Iterator<Item = Result<Row>> + '_>> {
+                match &args[..] {
+                    [Value::String(s), Value::Integer(n)] => {
+                        let s = s.clone();
+                        Ok(Box::new((1..=*n).map(move |i| {
+                            Ok(vec![Value::Integer(i), Value::String(s.clone())])
+                        })))
+                    }
+                    // 列数不一致的行
+                    _ => Ok(Box::new(std::iter::once(Ok(vec![Value::Null])))),
+                }
+            }
+        }
+
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        kvengine.register_table_function("repeat", Repeat)?;
+        assert!(kvengine.register_table_function("repeat", Repeat).is_err());
+        let mut s = kvengine.session()?;
+        let ints = |v: &[i64]| {
+            v.iter()
+                .map(|i| vec![Value::Integer(*i)])
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            s.execute("select * from generate_series(1, 3);")?,
+            ResultSet::Scan {
+                columns: vec!["generate_series".to_string()],
+                rows: ints(&[1, 2, 3]),
+            }
+        );
+        assert_eq!(
+            s.execute("select generate_series * 2 from generate_series(10, 2 * 3, -2);")?,
+            ResultSet::Scan {
+                columns: vec!["generate_series * 2".to_string()],
+                rows: ints(&[20, 16, 12]),
+            }
+        );
+        assert_eq!(
+            s.execute("select count(*), sum(generate_series) from generate_series(1, 100);")?,
+            ResultSet::Scan {
+                columns: vec!["count(*)".to_string(), "sum(generate_series)".to_string()],
+                rows: vec![vec![Value::Integer(100), Value::Integer(5050)]],
+            }
+        );
+        assert_eq!(
+            s.execute("select s, max(repeat.i) from repeat('ab', 1 + 2) group by s;")?,
+            ResultSet::Scan {
+                columns: vec!["s".to_string(), "max(repeat.i)".to_string()],
+                rows: vec![vec![Value::String("ab".to_string()), Value::Integer(3)]],
+            }
+        );
+        // 每个引擎分别注册
+        assert!(KVEngine::new(MemoryEngine::new())
+            .session()?
+            .execute("select * from repeat('ab', 1);")
+            .is_err());
+
+        s.execute("create table t1 (a int);")?;
+        for sql in [
+            "select * from unknown(1);",
+            "select * from t1(1);",
+            "select * from generate_series(a, 3);",
+            "select * from generate_series(1);",
+            "select a from generate_series(1, 3);",
+            "select * from repeat(1, 1);",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+
+        // 生成的行也受到内存限制
+        s.execute("set max_memory = 1024;")?;
+        assert!(matches!(
+            s.execute("select * from generate_series(1, 1000000);"),
+            Err(Error::ResourceLimit(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int);")?;
+        s.execute("create table tc (a int, b text, c float) with (storage = 'columnar');")?;
+        s.execute("insert into t values (1, 'a', 10), (2, 'b', 20), (3, 'a', 30);")?;
+        let lines = |result: ResultSet| match result {
+            ResultSet::Scan { columns, rows } => {
+                assert_eq!(columns, vec!["QUERY PLAN".to_string()]);
+                rows.into_iter()
+                    .map(|row| match &row[..] {
+                        [Value::String(line)] => line.clone(),
+                        row => panic!("unexpected row {:?}", row),
+                    })
+                    .collect::<Vec<_>>()
+            }
+            result => panic!("unexpected result {:?}", result),
+        };
+        let explain = |format, verbose| Statement::Explain {
+            statement: Box::new(
+                Query::select("t")
+                    .columns([col("name")])
+                    .filter(col("id").eq(2))
+                    .limit(5)
+                    .build(),
+            ),
+            format,
+            verbose,
+        };
+
+        assert_eq!(
+            lines(s.execute_statement(explain(ExplainFormat::Text, false))?),
+            vec![
+                "Projection (exprs: [name])",
+                "  -> Limit (limit: 5)",
+                "    -> Filter (predicate: id = 2)",
+                "      -> Scan (table: t, columns: [id, name, score])",
+            ]
+        );
+        assert_eq!(
+            lines(s.execute_statement(explain(ExplainFormat::Json, false))?),
+            vec![concat!(
+                r#"{"node":"Projection","exprs":["name"],"children":[{"node":"Limit","limit":5,"#,
+                r#""children":[{"node":"Filter","predicate":"id = 2","children":[{"node":"Scan","#,
+                r#""table":"t","columns":["id","name","score"]}]}]}]}"#
+            )]
+        );
+
+        // 没有统计信息时使用默认的行数，ANALYZE 之后使用表中的行数
+        let verbose = lines(s.execute_statement(explain(ExplainFormat::Text, true))?);
+        assert_eq!(
+            verbose[3],
+            "      -> Scan (table: t, columns: [id, name, score], storage: row, index: none) \
+             rows=1000 cost=3000.00"
+        );
+        s.execute("analyze t;")?;
+        assert_eq!(
+            lines(s.execute_statement(explain(ExplainFormat::Text, true))?),
+            vec![
+                "Projection (exprs: [name]) rows=1 cost=13.00",
+                "  -> Limit (limit: 5) rows=1 cost=12.00",
+                "    -> Filter (predicate: id = 2) rows=1 cost=12.00",
+                "      -> Scan (table: t, columns: [id, name, score], storage: row, index: none) \
+                 rows=3 cost=9.00",
+            ]
+        );
+        assert_eq!(
+            lines(s.execute_statement(explain(ExplainFormat::Json, true))?)[0]
+                .matches(r#""rows":"#)
+                .count(),
+            4
+        );
+
+        // SQL 语句
+        assert_eq!(
+            lines(s.execute("explain select name, count(*) from t group by name;")?),
+            vec![
+                "Projection (exprs: [name, count(*)])",
+                "  -> Aggregate (group_by: [name], aggregates: [count(*)])",
+                "    -> Scan (table: t, columns: [id, name, score])",
+            ]
+        );
+        assert_eq!(
+            lines(s.execute("explain (format json, verbose) select c from tc;")?),
+            vec![concat!(
+                r#"{"node":"Projection","exprs":["c"],"rows":1000,"cost":2000,"children":["#,
+                r#"{"node":"Scan","table":"tc","columns":["c"],"storage":"columnar","index":null,"#,
+                r#""rows":1000,"cost":1000}]}"#
+            )]
+        );
+        assert_eq!(
+            lines(s.execute("explain select * from generate_series(1, 3);")?),
+            vec!["TableFunction (function: generate_series, args: [1, 3])"]
+        );
+
+        // 只生成执行计划，不执行语句
+        assert_eq!(
+            lines(s.execute("explain insert into t values (4, 'c', 40);")?),
+            vec!["Insert (table: t, values: 1)"]
+        );
+        match s.execute("select * from t;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows.len(), 3),
+            _ => unreachable!(),
+        }
+
+        assert!(s.execute("explain explain select * from t;").is_err());
+        assert!(s.execute("explain set max_result_rows = 1;").is_err());
+        assert!(s.execute("explain select * from t2;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_where() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int);")?;
+        s.execute("insert into t values (1, 'a', 10), (2, 'b', null), (3, 'a', 30);")?;
+        let rows = |result: ResultSet| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            result => panic!("unexpected result {:?}", result),
+        };
+
+        assert_eq!(
+            rows(s.execute("select * from t where id = 1;")?),
+            vec![vec![
+                Value::Integer(1),
+                Value::String("a".to_string()),
+                Value::Integer(10),
+            ]]
+        );
+        // 条件是 NULL 的行被过滤掉
+        assert_eq!(
+            rows(s.execute("select id from t where score > 5 or name = 'b';")?),
+            vec![
+                vec![Value::Integer(1)],
+                vec![Value::Integer(2)],
+                vec![Value::Integer(3)],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute("select id from t where score > 5;")?),
+            vec![vec![Value::Integer(1)], vec![Value::Integer(3)]]
+        );
+        assert_eq!(
+            rows(s.execute("select name, sum(score) from t where id > 1 group by name;")?),
+            vec![
+                vec![Value::String("b".to_string()), Value::Null],
+                vec![Value::String("a".to_string()), Value::Integer(30)],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute("select * from generate_series(1, 5) where generate_series % 2 = 0;")?),
+            vec![vec![Value::Integer(2)], vec![Value::Integer(4)]]
+        );
+
+        // 没有 FROM 时条件只能是常量
+        assert_eq!(rows(s.execute("select 1 where 1 = 2;")?), Vec::<Row>::new());
+        assert_eq!(
+            rows(s.execute("select 1 where true;")?),
+            vec![vec![Value::Integer(1)]]
+        );
+        assert!(s.execute("select 1 where id = 1;").is_err());
+        assert!(s.execute("select * from t where missing = 1;").is_err());
+        assert!(s.execute("select * from t where id;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_update() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text not null, score int);")?;
+        s.execute(
+            "create table tc (id int, name text, score int) \
+             with (storage = 'columnar', compression = 'dictionary');",
+        )?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let row = |id: i64, name: &str, score: i64| {
+            vec![
+                Value::Integer(id),
+                Value::String(name.to_string()),
+                Value::Integer(score),
+            ]
+        };
+
+        for table in ["t", "tc"] {
+            s.execute(&format!(
+                "insert into {} values (1, 'a', 10), (2, 'b', 20), (3, 'c', 30);",
+                table
+            ))?;
+            assert_eq!(
+                s.execute(&format!(
+                    "update {} set score = score + id, name = 'x' where score >= 20;",
+                    table
+                ))?,
+                ResultSet::Update { count: 2 }
+            );
+            assert_eq!(
+                rows(&mut s, &format!("select * from {};", table))?,
+                vec![row(1, "a", 10), row(2, "x", 22), row(3, "x", 33)]
+            );
+
+            // 修改主键
+            s.execute(&format!("update {} set id = id + 10 where id = 1;", table))?;
+            assert_eq!(
+                rows(&mut s, &format!("select * from {};", table))?,
+                vec![row(2, "x", 22), row(3, "x", 33), row(11, "a", 10)]
+            );
+            // 新的主键和其他行重复时整条语句都不生效
+            assert!(s
+                .execute(&format!("update {} set id = 3 where id = 2;", table))
+                .is_err());
+            assert!(s.execute(&format!("update {} set id = 4;", table)).is_err());
+            assert_eq!(
+                rows(&mut s, &format!("select id from {};", table))?,
+                vec![
+                    vec![Value::Integer(2)],
+                    vec![Value::Integer(3)],
+                    vec![Value::Integer(11)]
+                ]
+            );
+            assert_eq!(
+                s.execute(&format!("update {} set score = 0 where id = 100;", table))?,
+                ResultSet::Update { count: 0 }
+            );
+        }
+
+        // 表达式使用修改之前的值
+        s.execute("update t set name = 'y', score = id, id = score where id = 2;")?;
+        assert_eq!(
+            rows(&mut s, "select * from t where id = 22;")?,
+            vec![row(22, "y", 2)]
+        );
+
+        assert!(s.execute("update t set name = null;").is_err());
+        assert!(s.execute("update t set missing = 1;").is_err());
+        assert!(s.execute("update t set score = 1, score = 2;").is_err());
+        assert!(s.execute("update t set score = count(*);").is_err());
+        assert!(s.execute("update t set score = 'a';").is_err());
+        assert!(s.execute("update t2 set score = 1;").is_err());
+        assert!(s.execute("update sys_tables set rows = 1;").is_err());
+
+        assert_eq!(
+            rows(
+                &mut s,
+                "explain update t set score = score + 1 where id = 3;"
+            )?,
+            vec![
+                vec![Value::String(
+                    "Update (table: t, set: [score = score + 1])".to_string()
+                )],
+                vec![Value::String("  -> Filter (predicate: id = 3)".to_string())],
+                vec![Value::String(
+                    "    -> Scan (table: t, columns: [id, name, score])".to_string()
+                )],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int);")?;
+        s.execute(
+            "create table tc (id int, name text, score int) \
+             with (storage = 'columnar', compression = 'dictionary');",
+        )?;
+        let ids = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+
+        for table in ["t", "tc"] {
+            s.execute(&format!(
+                "insert into {} values (1, 'a', 10), (2, 'b', 20), (3, 'c', 30), (4, 'b', 40);",
+                table
+            ))?;
+            assert_eq!(
+                s.execute(&format!(
+                    "delete from {} where name = 'b' or score < 20;",
+                    table
+                ))?,
+                ResultSet::Delete { count: 3 }
+            );
+            assert_eq!(
+                ids(&mut s, &format!("select * from {};", table))?,
+                vec![Value::Integer(3)]
+            );
+            assert_eq!(
+                s.execute(&format!("delete from {} where id = 100;", table))?,
+                ResultSet::Delete { count: 0 }
+            );
+
+            // 删除之后可以重新插入相同的主键
+            s.execute(&format!("insert into {} values (1, 'x', 1);", table))?;
+            assert_eq!(
+                ids(&mut s, &format!("select * from {};", table))?,
+                vec![Value::Integer(1), Value::Integer(3)]
+            );
+            assert_eq!(
+                s.execute(&format!("delete from {};", table))?,
+                ResultSet::Delete { count: 2 }
+            );
+            assert!(ids(&mut s, &format!("select * from {};", table))?.is_empty());
+        }
+
+        // 条件出错时不删除任何行
+        s.execute("insert into t values (5, 'e', 50), (6, 'f', 0);")?;
+        assert!(s.execute("delete from t where 100 / score > 1;").is_err());
+        assert_eq!(
+            ids(&mut s, "select * from t;")?,
+            vec![Value::Integer(5), Value::Integer(6)]
+        );
+
+        assert!(s.execute("delete from t where missing = 1;").is_err());
+        assert!(s.execute("delete from t2;").is_err());
+        assert!(s.execute("delete from sys_tables;").is_err());
+
+        assert_eq!(
+            ids(&mut s, "explain delete from t where id = 5;")?,
+            vec![
+                Value::String("Delete (table: t)".to_string()),
+                Value::String("  -> Filter (predicate: id = 5)".to_string()),
+                Value::String("    -> Scan (table: t, columns: [id, name, score])".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_alter_table() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text);")?;
+        s.execute(
+            "create table tc (id int, name text) \
+             with (storage = 'columnar', compression = 'dictionary');",
+        )?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let row = |id: i64, name: &str, score: Value, tag: &str| {
+            vec![
+                Value::Integer(id),
+                Value::String(name.to_string()),
+                score,
+                Value::String(tag.to_string()),
+            ]
+        };
+
+        for table in ["t", "tc"] {
+            s.execute(&format!("insert into {} values (1, 'a'), (2, 'b');", table))?;
+            assert_eq!(
+                s.execute(&format!("alter table {} add column score int;", table))?,
+                ResultSet::AlterTable {
+                    table_name: table.to_string()
+                }
+            );
+            s.execute(&format!(
+                "alter table {} add tag text not null default 'x';",
+                table
+            ))?;
+            s.execute(&format!("insert into {} values (3, 'c', 30, 'y');", table))?;
+            s.execute(&format!(
+                "insert into {} (id, name) values (4, 'd');",
+                table
+            ))?;
+            assert_eq!(
+                rows(&mut s, &format!("select * from {};", table))?,
+                vec![
+                    row(1, "a", Value::Null, "x"),
+                    row(2, "b", Value::Null, "x"),
+                    row(3, "c", Value::Integer(30), "y"),
+                    row(4, "d", Value::Null, "x"),
+                ]
+            );
+            assert!(rows(&mut s, &format!("check table {};", table))?.is_empty());
+
+            // 已有的行无法填充时不修改表结构
+            assert!(s
+                .execute(&format!("alter table {} add column n int not null;", table))
+                .is_err());
+            assert!(s
+                .execute(&format!("alter table {} add column name int;", table))
+                .is_err());
+            assert!(s
+                .execute(&format!(
+                    "alter table {} add column n int default 'a';",
+                    table
+                ))
+                .is_err());
+            assert_eq!(
+                rows(&mut s, &format!("select * from {} where id = 1;", table))?,
+                vec![row(1, "a", Value::Null, "x")]
+            );
+        }
+
+        // 空表可以添加没有默认值的 NOT NULL 列
+        s.execute("create table e (id int);")?;
+        s.execute("alter table e add column n int not null;")?;
+        assert!(s.execute("insert into e values (1);").is_err());
+        s.execute("insert into e values (1, 2);")?;
+        assert_eq!(
+            rows(&mut s, "select * from e;")?,
+            vec![vec![Value::Integer(1), Value::Integer(2)]]
+        );
+
+        assert!(s.execute("alter table t2 add column n int;").is_err());
+        assert!(s
+            .execute("alter table sys_tables add column n int;")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_table() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text) with (compression = 'dictionary');")?;
+        s.execute(
+            "create table tc (id int, name text) \
+             with (storage = 'columnar', compression = 'dictionary');",
+        )?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let row = |id: i64, name: &str| vec![Value::Integer(id), Value::String(name.to_string())];
+
+        for (table, new_name) in [("t", "t2"), ("tc", "tc2")] {
+            s.execute(&format!("insert into {} values (1, 'a'), (2, 'b');", table))?;
+            s.execute(&format!("analyze {};", table))?;
+            // 修改之前开始的事务仍然看到原来的表
+            let txn = kvengine.begin()?;
+            assert_eq!(
+                s.execute(&format!("alter table {} rename to {};", table, new_name))?,
+                ResultSet::AlterTable {
+                    table_name: new_name.to_string()
+                }
+            );
+            assert_eq!(
+                txn.scan_table(table.into())?,
+                vec![row(1, "a"), row(2, "b")]
+            );
+            assert!(txn.get_table(new_name.into())?.is_none());
+            txn.commit()?;
+
+            assert!(s.execute(&format!("select * from {};", table)).is_err());
+            // 字典一起移动，新写入的字符串不会和已有的编号冲突
+            s.execute(&format!(
+                "insert into {} values (3, 'c'), (4, 'a');",
+                new_name
+            ))?;
+            assert_eq!(
+                rows(&mut s, &format!("select * from {};", new_name))?,
+                vec![row(1, "a"), row(2, "b"), row(3, "c"), row(4, "a")]
+            );
+            assert!(rows(&mut s, &format!("check table {};", new_name))?.is_empty());
+
+            // 原来的表名可以重新使用
+            s.execute(&format!("create table {} (id int);", table))?;
+            assert!(rows(&mut s, &format!("select * from {};", table))?.is_empty());
+        }
+        // 统计信息一起移动
+        let stats = rows(&mut s, "select name, last_analyze from sys_tables;")?;
+        assert_eq!(
+            stats
+                .iter()
+                .map(|r| (r[0].clone(), r[1] != Value::Null))
+                .collect::<Vec<_>>(),
+            vec![
+                (Value::String("t".to_string()), false),
+                (Value::String("t2".to_string()), true),
+                (Value::String("tc".to_string()), false),
+                (Value::String("tc2".to_string()), true),
+            ]
+        );
+
+        assert!(s.execute("alter table t2 rename to tc2;").is_err());
+        assert!(s.execute("alter table t2 rename to sys_tables;").is_err());
+        assert!(s.execute("alter table sys_tables rename to t3;").is_err());
+        assert!(s.execute("alter table t3 rename to t4;").is_err());
+
+        // 修改列名
+        s.execute("alter table t2 rename column name to title;")?;
+        assert_eq!(
+            rows(&mut s, "select title from t2 where id = 3;")?,
+            vec![vec![Value::String("c".to_string())]]
+        );
+        assert!(s.execute("select name from t2;").is_err());
+        s.execute("alter table tc2 rename name to title;")?;
+        assert_eq!(
+            rows(&mut s, "select title from tc2 where id = 1;")?,
+            vec![vec![Value::String("a".to_string())]]
+        );
+        assert!(s
+            .execute("alter table t2 rename column id to title;")
+            .is_err());
+        assert!(s
+            .execute("alter table t2 rename column name to n;")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_with_views() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int);")?;
+        s.execute("create table u (id int, t_id int, note text);")?;
+        s.execute("insert into t values (1, 'a', 10), (2, 'b', 20);")?;
+        s.execute("insert into u values (1, 2, null);")?;
+        s.execute("create view v as select id, name from t where score > 15;")?;
+        s.execute(
+            "create view w as select id from u where exists (select * from t where t.id = u.t_id);",
+        )?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+
+        // 视图依赖的表和列不能修改名字，包括子查询中引用的
+        assert_eq!(
+            s.execute("alter table t rename to t2;"),
+            Err(Error::Internal(
+                "cannot rename table t, view v depends on it".to_string()
+            ))
+        );
+        assert_eq!(
+            s.execute("alter table t rename column score to points;"),
+            Err(Error::Internal(
+                "cannot rename column score of table t, view v depends on it".to_string()
+            ))
+        );
+        assert_eq!(
+            s.execute("alter table u rename column t_id to tid;"),
+            Err(Error::Internal(
+                "cannot rename column t_id of table u, view w depends on it".to_string()
+            ))
+        );
+        assert_eq!(
+            rows(&mut s, "select * from v;")?,
+            vec![vec![Value::Integer(2), Value::String("b".to_string())]]
+        );
+        assert_eq!(
+            rows(&mut s, "select * from w;")?,
+            vec![vec![Value::Integer(1)]]
+        );
+
+        // 视图没有使用的列可以修改名字
+        s.execute("alter table u rename column note to memo;")?;
+        assert_eq!(
+            rows(&mut s, "select * from w;")?,
+            vec![vec![Value::Integer(1)]]
+        );
+        s.execute("drop view w;")?;
+        s.execute("alter table u rename to u2;")?;
+
+        // 被其他视图使用的视图不能删除
+        s.execute("create view v2 as select name from v;")?;
+        assert_eq!(
+            s.execute("drop view v;"),
+            Err(Error::Internal(
+                "cannot drop view v, view v2 depends on it".to_string()
+            ))
+        );
+        assert_eq!(
+            rows(&mut s, "select * from v2;")?,
+            vec![vec![Value::String("b".to_string())]]
+        );
+        s.execute("drop view v2;")?;
+
+        // 删除视图之后可以修改
+        s.execute("drop view v;")?;
+        s.execute("alter table t rename column score to points;")?;
+        s.execute("alter table t rename to t2;")?;
+        assert_eq!(
+            rows(&mut s, "select name from t2 where points > 15;")?,
+            vec![vec![Value::String("b".to_string())]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score float);")?;
+        s.execute(
+            "insert into t values (1, 'b', 2.5), (2, 'a', null), (3, 'c', 1.0), \
+             (4, 'a', 3.0), (5, null, 2.5);",
+        )?;
+        let ids = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ints = |ids: &[i64]| ids.iter().map(|&i| Value::Integer(i)).collect::<Vec<_>>();
+
+        // NULL 在升序时排在最前面，降序时排在最后面
+        assert_eq!(
+            ids(&mut s, "select id from t order by score;")?,
+            ints(&[2, 3, 1, 5, 4])
+        );
+        assert_eq!(
+            ids(&mut s, "select id from t order by score desc;")?,
+            ints(&[4, 1, 5, 3, 2])
+        );
+        // 多个排序表达式，相同的值保持原来的顺序
+        assert_eq!(
+            ids(&mut s, "select id from t order by name asc, score desc;")?,
+            ints(&[5, 4, 2, 1, 3])
+        );
+        assert_eq!(
+            ids(
+                &mut s,
+                "select * from t where id > 1 order by id % 2, id desc;"
+            )?,
+            ints(&[4, 2, 5, 3])
+        );
+        // 排序的列不需要出现在 select 列表中
+        assert_eq!(
+            ids(
+                &mut s,
+                "select name from t where score > 2 order by id desc;"
+            )?,
+            vec![
+                Value::Null,
+                Value::String("a".to_string()),
+                Value::String("b".to_string())
+            ]
+        );
+
+        // 聚合之后排序
+        match s
+            .execute("select name, count(*) from t group by name order by count(*) desc, name;")?
+        {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::String("a".to_string()), Value::Integer(2)],
+                    vec![Value::Null, Value::Integer(1)],
+                    vec![Value::String("b".to_string()), Value::Integer(1)],
+                    vec![Value::String("c".to_string()), Value::Integer(1)],
+                ]
+            ),
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+        assert_eq!(
+            ids(
+                &mut s,
+                "select name from t group by name order by max(score);"
+            )?,
+            vec![
+                Value::String("c".to_string()),
+                Value::String("b".to_string()),
+                Value::Null,
+                Value::String("a".to_string())
+            ]
+        );
+        assert!(s
+            .execute("select name from t group by name order by id;")
+            .is_err());
+        assert!(s.execute("select id from t order by count(*);").is_err());
+        assert!(s.execute("select id from t order by missing;").is_err());
+
+        // 列存的表需要读取排序用到的列
+        s.execute("create table tc (id int, name text) with (storage = 'columnar');")?;
+        s.execute("insert into tc values (1, 'b'), (2, 'a'), (3, 'c');")?;
+        assert_eq!(
+            ids(&mut s, "select id from tc order by name;")?,
+            ints(&[2, 1, 3])
+        );
+
+        // LIMIT 在排序之后
+        let stmt = Query::select("t")
+            .columns([col("id")])
+            .order_by(col("score"), Direction::Desc)
+            .limit(2)
+            .build();
+        match s.execute_statement(stmt)? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![ints(&[4]), ints(&[1])]),
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+
+        assert_eq!(
+            ids(&mut s, "explain select id from t order by score desc, id;")?,
+            vec![
+                Value::String("Projection (exprs: [id])".to_string()),
+                Value::String("  -> Order (order_by: [score DESC, id])".to_string()),
+                Value::String("    -> Scan (table: t, columns: [id, name, score])".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_alias() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int);")?;
+        s.execute("insert into t values (1, 'b', 20), (2, 'a', 10), (3, 'c', 30);")?;
+
+        match s.execute("select id as user_id, name n, score + 1 from t order by n;")? {
+            ResultSet::Scan { columns, rows } => {
+                assert_eq!(columns, vec!["user_id", "n", "score + 1"]);
+                assert_eq!(
+                    rows.iter().map(|r| r[0].clone()).collect::<Vec<_>>(),
+                    vec![Value::Integer(2), Value::Integer(1), Value::Integer(3)]
+                );
+            }
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+        // 别名优先于表中同名的列
+        match s.execute("select id, -score as id2, name as score from t order by score desc;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows.iter().map(|r| r[0].clone()).collect::<Vec<_>>(),
+                vec![Value::Integer(3), Value::Integer(1), Value::Integer(2)]
+            ),
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+        // 聚合的结果也可以使用别名
+        match s.execute(
+            "select score > 15 as high, count(*) as c from t group by score > 15 order by c desc;",
+        )? {
+            ResultSet::Scan { columns, rows } => {
+                assert_eq!(columns, vec!["high", "c"]);
+                assert_eq!(
+                    rows,
+                    vec![
+                        vec![Value::Boolean(true), Value::Integer(2)],
+                        vec![Value::Boolean(false), Value::Integer(1)],
+                    ]
+                );
+            }
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+        match s.execute("select 1 + 2 as three, 'hello' greeting;")? {
+            ResultSet::Scan { columns, .. } => assert_eq!(columns, vec!["three", "greeting"]),
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+        // 别名不能在 WHERE 中使用
+        assert!(s.execute("select id as x from t where x = 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_alias() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name text);")?;
+        s.execute("insert into users values (1, 'a'), (2, 'b');")?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { columns, rows } => Ok((columns, rows)),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+
+        assert_eq!(
+            rows(&mut s, "select u.id, name from users u where u.name = 'b';")?,
+            (
+                vec!["id".to_string(), "name".to_string()],
+                vec![vec![Value::Integer(2), Value::String("b".to_string())]]
+            )
+        );
+        assert_eq!(
+            rows(&mut s, "select u.* from users as u order by u.id desc;")?.1,
+            vec![
+                vec![Value::Integer(2), Value::String("b".to_string())],
+                vec![Value::Integer(1), Value::String("a".to_string())],
+            ]
+        );
+        assert_eq!(
+            rows(
+                &mut s,
+                "select g.generate_series from generate_series(1, 2) g;"
+            )?
+            .1,
+            vec![vec![Value::Integer(1)], vec![Value::Integer(2)]]
+        );
+        // 有别名之后不能再使用原来的表名
+        assert!(s.execute("select users.id from users u;").is_err());
+        assert!(s.execute("select users.* from users u;").is_err());
+        assert!(s.execute("select x.id from users u;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_having() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int);")?;
+        s.execute("insert into t values (1, 'a', 10), (2, 'b', 20), (3, 'a', 30), (4, 'c', 5);")?;
+        let rows = |result| match result {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+
+        // HAVING 中可以使用 select 列表中没有的聚合函数
+        assert_eq!(
+            rows(s.execute(
+                "select name, sum(score) from t group by name having count(*) > 1 or max(score) >= 20 order by name;"
+            )?)?,
+            vec![
+                vec![Value::String("a".to_string()), Value::Integer(40)],
+                vec![Value::String("b".to_string()), Value::Integer(20)],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute(
+                "select name from t group by name having name <> 'a' order by name desc;"
+            )?)?,
+            vec![
+                vec![Value::String("c".to_string())],
+                vec![Value::String("b".to_string())],
+            ]
+        );
+        // 没有 GROUP BY 时所有行是一个分组
+        assert_eq!(
+            rows(s.execute("select count(*) from t having sum(score) > 100;")?)?,
+            Vec::<Row>::new()
+        );
+        assert_eq!(
+            rows(s.execute("select count(*) from t having sum(score) > 10;")?)?,
+            vec![vec![Value::Integer(4)]]
+        );
+        // 分组之外的列不能在 HAVING 中使用
+        assert!(s
+            .execute("select name from t group by name having score > 1;")
+            .is_err());
+        assert!(s.execute("select * from t having count(*) > 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_distinct() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, a int, b text);")?;
+        s.execute(
+            "insert into t values (1, 2, 'x'), (2, 1, 'y'), (3, 2, 'x'), (4, 1, null), (5, 3, 'y'), (6, 1, null);",
+        )?;
+        let rows = |result| match result {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let row = |a: i64, b: Option<&str>| {
+            vec![
+                Value::Integer(a),
+                b.map_or(Value::Null, |b| Value::String(b.to_string())),
+            ]
+        };
+
+        // 保留第一次出现的顺序，NULL 和 NULL 是重复的
+        assert_eq!(
+            rows(s.execute("select distinct a, b from t;")?)?,
+            vec![
+                row(2, Some("x")),
+                row(1, Some("y")),
+                row(1, None),
+                row(3, Some("y"))
+            ]
+        );
+        assert_eq!(
+            rows(s.execute("select distinct b from t order by b desc;")?)?,
+            vec![
+                vec![Value::String("y".to_string())],
+                vec![Value::String("x".to_string())],
+                vec![Value::Null],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute("select distinct count(*) > 1 from t group by a;")?)?,
+            vec![vec![Value::Boolean(true)], vec![Value::Boolean(false)]]
+        );
+        match s.execute("explain select distinct a from t;")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows[0][0], Value::String("Distinct".to_string()))
+            }
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_join() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name text);")?;
+        s.execute(
+            "create table orders (id int, uid int, amount int) with (storage = 'columnar');",
+        )?;
+        s.execute("insert into users values (1, 'a'), (2, 'b'), (3, 'c');")?;
+        s.execute("insert into orders values (10, 1, 5), (11, 2, 7), (12, 1, 3), (13, 4, 1);")?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { columns, rows } => Ok((columns, rows)),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+
+        assert_eq!(
+            result(
+                &mut s,
+                "select name, o.id from users join orders o on users.id = o.uid order by o.id;"
+            )?,
+            (
+                vec!["name".to_string(), "id".to_string()],
+                vec![
+                    vec![Value::String("a".to_string()), Value::Integer(10)],
+                    vec![Value::String("b".to_string()), Value::Integer(11)],
+                    vec![Value::String("a".to_string()), Value::Integer(12)],
+                ]
+            )
+        );
+        // SELECT * 的列名带上表名
+        let (columns, rows) = result(
+            &mut s,
+            "select * from users u inner join orders on u.id = uid where amount > 4;",
+        )?;
+        assert_eq!(
+            columns,
+            vec!["u.id", "u.name", "orders.id", "orders.uid", "orders.amount"]
+        );
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            result(
+                &mut s,
+                "select name, sum(amount) from users join orders on users.id = orders.uid group by name order by name;"
+            )?
+            .1,
+            vec![
+                vec![Value::String("a".to_string()), Value::Integer(8)],
+                vec![Value::String("b".to_string()), Value::Integer(7)],
+            ]
+        );
+        // 连接多个表，同一个表可以使用不同的别名
+        assert_eq!(
+            result(
+                &mut s,
+                "select a.name, b.name from users a join orders on a.id = orders.uid join users b on b.id = orders.uid + 1 order by orders.id;"
+            )?
+            .1,
+            vec![
+                vec![Value::String("a".to_string()), Value::String("b".to_string())],
+                vec![Value::String("b".to_string()), Value::String("c".to_string())],
+                vec![Value::String("a".to_string()), Value::String("b".to_string())],
+            ]
+        );
+
+        // 相同的列名需要带上表名，连接条件不能引用后面的表
+        assert!(s
+            .execute("select id from users join orders on users.id = orders.uid;")
+            .is_err());
+        assert!(s
+            .execute(
+                "select * from users join orders on users.id = x.uid join users x on x.id = 1;"
+            )
+            .is_err());
+        assert!(s
+            .execute("select * from users join orders on count(*) > 1;")
+            .is_err());
+        assert!(s
+            .execute("select * from users join orders on users.name;")
+            .is_err());
+
+        // 列存的表只读取用到的列
+        match s.execute(
+            "explain select name, amount from users join orders on users.id > orders.uid;",
+        )? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows.into_iter()
+                    .map(|mut row| match row.remove(0) {
+                        Value::String(line) => line,
+                        value => panic!("unexpected value {:?}", value),
+                    })
+                    .collect::<Vec<_>>(),
+                vec![
+                    "Projection (exprs: [name, amount])",
+                    "  -> NestedLoopJoin (type: inner, predicate: id > uid)",
+                    "    -> Scan (table: users, columns: [id, name])",
+                    "    -> Scan (table: orders, columns: [uid, amount])",
+                ]
+            ),
+            result => return Err(Error::Internal(format!("unexpected result {:?}", result))),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_outer_join() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name text);")?;
+        s.execute("create table orders (id int, uid int);")?;
+        s.execute("insert into users values (1, 'a'), (2, 'b');")?;
+        s.execute("insert into orders values (10, 1), (11, 3), (12, 1);")?;
+        let rows = |result| match result {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let name = |n: &str| Value::String(n.to_string());
+
+        assert_eq!(
+            rows(s.execute(
+                "select name, orders.id from users left join orders on users.id = uid;"
+            )?)?,
+            vec![
+                vec![name("a"), Value::Integer(10)],
+                vec![name("a"), Value::Integer(12)],
+                vec![name("b"), Value::Null],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute(
+                "select name, orders.id from users right outer join orders on users.id = uid;"
+            )?)?,
+            vec![
+                vec![name("a"), Value::Integer(10)],
+                vec![name("a"), Value::Integer(12)],
+                vec![Value::Null, Value::Integer(11)],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute("select name, orders.id from users full join orders on users.id = uid order by orders.id;")?)?,
+            vec![
+                vec![name("b"), Value::Null],
+                vec![name("a"), Value::Integer(10)],
+                vec![Value::Null, Value::Integer(11)],
+                vec![name("a"), Value::Integer(12)],
+            ]
+        );
+        // ON 中的条件在连接时判断，WHERE 在连接之后过滤
+        assert_eq!(
+            rows(s.execute("select name, orders.id from users left join orders on users.id = uid and orders.id > 10;")?)?,
+            vec![
+                vec![name("a"), Value::Integer(12)],
+                vec![name("b"), Value::Null],
+            ]
+        );
+        assert_eq!(
+            rows(s.execute("select count(*), count(orders.id) from users left join orders on users.id = uid where name = 'b';")?)?,
+            vec![vec![Value::Integer(1), Value::Integer(0)]]
+        );
+        // 没有行的表
+        s.execute("create table empty (id int);")?;
+        assert_eq!(
+            rows(s.execute("select * from users left join empty on true;")?)?,
+            vec![
+                vec![Value::Integer(1), name("a"), Value::Null],
+                vec![Value::Integer(2), name("b"), Value::Null],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_join() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table a (id int, k int);")?;
+        s.execute("create table b (id int, k float);")?;
+        s.execute("create table c (id int, k int);")?;
+        s.execute("insert into a values (1, 2), (2, null), (3, 1), (4, 2), (5, -1);")?;
+        s.execute("insert into b values (1, 2.0), (2, 3.0), (3, null), (4, 2.0), (5, -1.5);")?;
+        s.execute("insert into c values (1, 2), (2, 1);")?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+
+        // 两边都按 key 排好序时使用排序合并连接，结果和嵌套循环连接相同
+        for join in ["join", "left join", "right join", "full join"] {
+            let nested = format!(
+                "select a.id, b.id from a {} b on a.k = b.k and b.id < 4 order by a.id, b.id;",
+                join
+            );
+            let merge = nested
+                .replace("from a ", "from (select * from a order by k) a ")
+                .replace(" b on", " (select * from b order by k) b on");
+            assert_eq!(
+                result(&mut s, &merge)?,
+                result(&mut s, &nested)?,
+                "{}",
+                join
+            );
+        }
+        assert_eq!(
+            result(&mut s, "select a.id, b.id from a join b on b.k = a.k;")?,
+            vec![
+                vec![Value::Integer(1), Value::Integer(1)],
+                vec![Value::Integer(1), Value::Integer(4)],
+                vec![Value::Integer(4), Value::Integer(1)],
+                vec![Value::Integer(4), Value::Integer(4)],
+            ]
+        );
+
+        let lines = |s: &mut Session<_>, sql: &str| -> Result<Vec<String>> {
+            Ok(result(s, sql)?
+                .into_iter()
+                .map(|mut row| match row.remove(0) {
+                    Value::String(line) => line,
+                    value => panic!("unexpected value {:?}", value),
+                })
+                .collect())
+        };
+        assert_eq!(
+            lines(
+                &mut s,
+                "explain select * from a join b on a.k = b.k join c on c.k = a.k and c.id > 1;"
+            )?,
+            vec![
+                "Projection (exprs: [id, k, id, k, id, k])",
+                "  -> NestedLoopJoin (type: inner, predicate: k = k AND id > 1)",
+                "    -> NestedLoopJoin (type: inner, predicate: k = k)",
+                "      -> Scan (table: a, columns: [id, k])",
+                "      -> Scan (table: b, columns: [id, k])",
+                "    -> Scan (table: c, columns: [id, k])",
+            ]
+        );
+
+        // 已经排好序的输入（包括排序合并连接本身的输出）不需要再排序
+        assert_eq!(
+            lines(
+                &mut s,
+                "explain select * from (select * from a order by k) a \
+                 join (select * from b order by k) b on a.k = b.k \
+                 join (select * from c order by k) c on c.k = a.k and c.id > 1;"
+            )?,
+            vec![
+                "Projection (exprs: [id, k, id, k, id, k])",
+                "  -> MergeJoin (type: inner, key: k = k, predicate: k = k AND id > 1)",
+                "    -> MergeJoin (type: inner, key: k = k, predicate: k = k)",
+                "      -> Order (order_by: [k])",
+                "        -> Scan (table: a, columns: [id, k])",
+                "      -> Order (order_by: [k])",
+                "        -> Scan (table: b, columns: [id, k])",
+                "    -> Order (order_by: [k])",
+                "      -> Scan (table: c, columns: [id, k])",
+            ]
+        );
+
+        // 索引扫描按照索引列有序
+        s.execute("create index a_k on a (k);")?;
+        s.execute("create index c_k on c (k);")?;
+        let sql = "select a.id, c.id from a join c on a.k = c.k where a.k > 0 and c.k > 0;";
+        assert_eq!(
+            lines(&mut s, &format!("explain {}", sql))?,
+            vec![
+                "Projection (exprs: [id, id])",
+                "  -> Filter (predicate: k > 0 AND k > 0)",
+                "    -> MergeJoin (type: inner, key: k = k, predicate: k = k)",
+                "      -> IndexScan (table: a, index: a_k, key: [k > 0], columns: [id, k])",
+                "      -> IndexScan (table: c, index: c_k, key: [k > 0], columns: [id, k])",
+            ]
+        );
+        let mut rows = result(&mut s, sql)?;
+        rows.sort_by_key(|row| format!("{:?}", row));
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(1), Value::Integer(1)],
+                vec![Value::Integer(3), Value::Integer(2)],
+                vec![Value::Integer(4), Value::Integer(1)],
+            ]
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select a.id, b.id, c.id from a join b on a.k = b.k join c on c.k = a.k and c.id > 1;"
+            )?,
+            Vec::<Row>::new()
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select a.id, c.id from a right join c on a.k = c.k join b on b.k = a.k;"
+            )?
+            .len(),
+            4
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_subquery_in_from() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name string, age int);")?;
+        s.execute("create table orders (id int, uid int, amount int);")?;
+        s.execute("insert into users values (1, 'a', 20), (2, 'b', 30), (3, 'c', 40);")?;
+        s.execute("insert into orders values (1, 1, 10), (2, 1, 20), (3, 3, 5);")?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { columns, rows } => Ok((columns, rows)),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+
+        // 子查询的列名是子查询结果中的列名，可以使用别名引用
+        assert_eq!(
+            result(
+                &mut s,
+                "select * from (select id, name as n from users where age > 25) as sub order by id;"
+            )?,
+            (
+                vec!["id".to_string(), "n".to_string()],
+                vec![
+                    vec![Value::Integer(2), Value::String("b".to_string())],
+                    vec![Value::Integer(3), Value::String("c".to_string())],
+                ]
+            )
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select s.name, s.age * 2 from (select * from users) s where s.id = 1;"
+            )?
+            .1,
+            vec![vec![Value::String("a".to_string()), Value::Integer(40)]]
+        );
+
+        // 子查询中可以聚合，外层查询再过滤和排序
+        assert_eq!(
+            result(
+                &mut s,
+                "select t.uid, t.total from (select uid, sum(amount) as total from orders group by uid) t \
+                 where t.total > 6 order by t.total desc;"
+            )?
+            .1,
+            vec![vec![Value::Integer(1), Value::Integer(30)]]
+        );
+        // 子查询之上再嵌套子查询
+        assert_eq!(
+            result(
+                &mut s,
+                "select count(*) from (select * from (select id from users where id > 1) a) b;"
+            )?
+            .1,
+            vec![vec![Value::Integer(2)]]
+        );
+
+        // 子查询和表连接
+        assert_eq!(
+            result(
+                &mut s,
+                "select users.name, o.amount from users \
+                 join (select uid, amount from orders where amount >= 10) o on users.id = o.uid \
+                 order by o.amount;"
+            )?,
+            (
+                vec!["name".to_string(), "amount".to_string()],
+                vec![
+                    vec![Value::String("a".to_string()), Value::Integer(10)],
+                    vec![Value::String("a".to_string()), Value::Integer(20)],
+                ]
+            )
+        );
+        // 子查询中的两列同名时不能不带表名引用
+        assert_eq!(
+            s.execute("select id from (select * from users) u join (select * from orders) o on u.id = o.uid;"),
+            Err(Error::Internal("column id is ambiguous".to_string()))
+        );
+        // 子查询的别名之外不能再使用里面的表名
+        assert_eq!(
+            s.execute("select users.id from (select id from users) u;"),
+            Err(Error::Internal(
+                "column users.id does not exist".to_string()
+            ))
+        );
+        assert_eq!(
+            s.execute("select * from (select * from users as of version 1) u;"),
+            Err(Error::Internal(
+                "AS OF is not allowed in a subquery".to_string()
+            ))
+        );
+
+        // 子查询的结果不进入查询缓存
+        s.execute("set global query_cache_size = 10;")?;
+        let sql = "select count(*) from (select id from users) u;";
+        assert_eq!(result(&mut s, sql)?.1, vec![vec![Value::Integer(3)]]);
+        s.execute("insert into users values (4, 'd', 50);")?;
+        assert_eq!(result(&mut s, sql)?.1, vec![vec![Value::Integer(4)]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_exists() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name string);")?;
+        s.execute("create table orders (id int, uid int, amount int);")?;
+        s.execute("insert into users values (1, 'a'), (2, 'b'), (3, 'c'), (4, null);")?;
+        s.execute("insert into orders values (1, 1, 10), (2, 1, 20), (3, 3, 5), (4, null, 7);")?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ids = |ids: &[i64]| ids.iter().map(|i| Value::Integer(*i)).collect::<Vec<_>>();
+
+        // 关联子查询，子查询中的列名优先在子查询的表中查找
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where exists (select * from orders where uid = users.id) order by id;"
+            )?,
+            ids(&[1, 3])
+        );
+        // 反连接，子查询中 uid 是 NULL 的行不和任何行匹配
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users u where not exists (select 1 from orders o where o.uid = u.id) order by id;"
+            )?,
+            ids(&[2, 4])
+        );
+        // 子查询中只引用自己的表的条件在子查询中过滤，和其他条件一起使用
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where id > 1 and exists \
+                 (select * from orders where orders.uid = users.id and amount > 6) order by id;"
+            )?,
+            Vec::<Value>::new()
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where exists \
+                 (select * from orders where orders.uid = users.id and amount > 15) order by id;"
+            )?,
+            ids(&[1])
+        );
+        // 没有等值条件的关联子查询
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where exists (select * from orders where orders.amount > users.id * 5) order by id;"
+            )?,
+            ids(&[1, 2, 3])
+        );
+        // 不引用外层查询的子查询
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where exists (select * from orders where amount > 100) order by id;"
+            )?,
+            Vec::<Value>::new()
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select count(*) from users where not exists (select count(*) from orders where amount > 100);"
+            )?,
+            ids(&[0])
+        );
+        // 多个 EXISTS 和连接一起使用
+        assert_eq!(
+            result(
+                &mut s,
+                "select users.id from users join orders on users.id = orders.uid \
+                 where exists (select * from orders o where o.uid = users.id and o.id <> orders.id) \
+                 and not exists (select * from users x where x.id = orders.amount - 8) order by orders.id;"
+            )?,
+            ids(&[1])
+        );
+
+        // 有等值条件时右边按照 key 查找，右边只执行一次
+        let lines = |s: &mut Session<_>, sql: &str| -> Result<Vec<String>> {
+            match s.execute(sql)? {
+                ResultSet::Scan { rows, .. } => Ok(rows
+                    .into_iter()
+                    .map(|row| match &row[0] {
+                        Value::String(line) => line.clone(),
+                        v => format!("{:?}", v),
+                    })
+                    .collect()),
+                result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+            }
+        };
+        assert_eq!(
+            lines(
+                &mut s,
+                "explain select * from users where name <> 'b' and not exists \
+                 (select * from orders where uid = users.id and amount > 6);"
+            )?,
+            vec![
+                "SemiJoin (type: anti, key: id = uid, predicate: uid = id)",
+                "  -> Filter (predicate: name != 'b')",
+                "    -> Scan (table: users, columns: [id, name])",
+                "  -> Filter (predicate: amount > 6)",
+                "    -> Scan (table: orders, columns: [id, uid, amount])",
+            ]
+        );
+
+        // EXISTS 只能在 WHERE 中使用 AND 连接
+        for sql in [
+            "select exists (select * from orders) from users;",
+            "select * from users where id = 1 or exists (select * from orders);",
+            "select * from users where exists (select * from orders where exists (select * from users));",
+            "delete from users where exists (select * from orders);",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        // 有聚合的子查询不能引用外层查询的列
+        assert_eq!(
+            s.execute("select * from users where exists (select count(*) from orders where uid = users.id);"),
+            Err(Error::Internal("column users.id does not exist".to_string()))
+        );
+        assert_eq!(
+            s.execute("select * from users where exists (select nothing from orders);"),
+            Err(Error::Internal("column nothing does not exist".to_string()))
+        );
+
+        // 不缓存有子查询的查询
+        s.execute("set global query_cache_size = 10;")?;
+        let sql = "select id from users where exists (select * from orders where uid = users.id) order by id;";
+        assert_eq!(result(&mut s, sql)?, ids(&[1, 3]));
+        s.execute("insert into orders values (5, 2, 1);")?;
+        assert_eq!(result(&mut s, sql)?, ids(&[1, 2, 3]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_in() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name string, age int);")?;
+        s.execute("create table orders (id int, uid int, amount int);")?;
+        s.execute(
+            "insert into users values (1, 'a', 20), (2, 'b', null), (3, 'c', 40), (4, null, 20);",
+        )?;
+        s.execute("insert into orders values (1, 1, 10), (2, 1, 20), (3, 3, 5);")?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ids = |ids: &[i64]| ids.iter().map(|i| Value::Integer(*i)).collect::<Vec<_>>();
+
+        // 值列表
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where name in ('a', 'c', 'x') order by id;"
+            )?,
+            ids(&[1, 3])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where age not in (20, 30) order by id;"
+            )?,
+            ids(&[3])
+        );
+        // 列表中有 NULL 时没有相等的值的结果是 NULL
+        assert_eq!(
+            result(&mut s, "select id from users where age not in (20, null);")?,
+            Vec::<Value>::new()
+        );
+        assert_eq!(
+            s.execute("select 2 in (1, null), null in (1), 1 not in (2, 3), 1.0 in (1);")?,
+            ResultSet::Scan {
+                columns: vec![
+                    "2 IN (1, NULL)".to_string(),
+                    "NULL IN (1)".to_string(),
+                    "NOT 1 IN (2, 3)".to_string(),
+                    "1.0 IN (1)".to_string(),
+                ],
+                rows: vec![vec![
+                    Value::Null,
+                    Value::Null,
+                    Value::Boolean(true),
+                    Value::Boolean(true)
+                ]],
+            }
+        );
+        assert!(s
+            .execute("select id from users where id in ('a');")
+            .is_err());
+
+        // 子查询
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where id in (select uid from orders) order by id;"
+            )?,
+            ids(&[1, 3])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where id not in (select uid from orders) order by id;"
+            )?,
+            ids(&[2, 4])
+        );
+        // 关联子查询和子查询中的表达式
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users u where id * 10 in (select amount from orders where orders.uid = u.id) order by id;"
+            )?,
+            ids(&[1])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where age in (select amount + 10 from orders where id < 3) order by id;"
+            )?,
+            ids(&[1, 4])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where age in (select users.id * 10 from orders where orders.uid = users.id) order by id;"
+            )?,
+            Vec::<Value>::new()
+        );
+        // 有聚合的子查询
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where age in (select max(amount) * 2 from orders) order by id;"
+            )?,
+            ids(&[3])
+        );
+
+        // NOT IN 的子查询结果中有 NULL 时不返回行，左边的值是 NULL 时只在子查询没有结果时返回
+        s.execute("insert into orders values (4, null, 1);")?;
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where id not in (select uid from orders) order by id;"
+            )?,
+            Vec::<Value>::new()
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where id in (select uid from orders) order by id;"
+            )?,
+            ids(&[1, 3])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where age not in (select amount from orders where amount > 30) order by id;"
+            )?,
+            ids(&[1, 2, 3, 4])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users where age not in (select amount * 3 from orders where amount > 1) order by id;"
+            )?,
+            ids(&[1, 3, 4])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from users u where id not in (select uid from orders o where o.amount = u.id * 10) order by id;"
+            )?,
+            ids(&[2, 3, 4])
+        );
+
+        // 执行计划
+        let lines = |s: &mut Session<_>, sql: &str| -> Result<Vec<String>> {
+            match s.execute(sql)? {
+                ResultSet::Scan { rows, .. } => Ok(rows
+                    .into_iter()
+                    .map(|row| match &row[0] {
+                        Value::String(line) => line.clone(),
+                        v => format!("{:?}", v),
+                    })
+                    .collect()),
+                result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+            }
+        };
+        assert_eq!(
+            lines(
+                &mut s,
+                "explain select id from users where id not in (select uid from orders where amount > 1);"
+            )?,
+            vec![
+                "Projection (exprs: [id])",
+                "  -> SemiJoin (type: null-aware anti, key: id = uid)",
+                "    -> Scan (table: users, columns: [id, name, age])",
+                "    -> Filter (predicate: amount > 1)",
+                "      -> Scan (table: orders, columns: [id, uid, amount])",
+            ]
+        );
+
+        for sql in [
+            "select id in (select uid from orders) from users;",
+            "select * from users where id in (select id, uid from orders);",
+            "select * from users where id in (select * from orders);",
+            "select * from users where id not in (select users.id from orders);",
+            "select * from users where id in (select count(*), 1 from orders);",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_between() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, v int, name string);")?;
+        s.execute(
+            "insert into t values (1, 5, 'apple'), (2, 10, 'banana'), (3, null, 'cherry'), (4, 15, null);",
+        )?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ids = |ids: &[i64]| ids.iter().map(|i| Value::Integer(*i)).collect::<Vec<_>>();
+
+        // 包括两端的值，NULL 不在任何范围中
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where v between 5 and 10 order by id;"
+            )?,
+            ids(&[1, 2])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where v not between 5 and 10 order by id;"
+            )?,
+            ids(&[4])
+        );
+        assert_eq!(
+            result(&mut s, "select id from t where v between 10 and 5;")?,
+            Vec::<Value>::new()
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where name between 'b' and 'c' and id > 0 order by id;"
+            )?,
+            ids(&[2])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where v between id * 5 and 2.5 * 4 order by id;"
+            )?,
+            ids(&[1, 2])
+        );
+        assert_eq!(
+            result(&mut s, "select 3 between 1 and null, 0 between 1 and null;")?,
+            vec![Value::Null]
+        );
+        assert_eq!(
+            s.execute("select 0 between 1 and null;")?,
+            ResultSet::Scan {
+                columns: vec!["0 >= 1 AND 0 <= NULL".to_string()],
+                rows: vec![vec![Value::Boolean(false)]],
+            }
+        );
+
+        // 改写成的范围比较和直接写的比较的执行计划相同
+        let plan = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        assert_eq!(
+            plan(&mut s, "explain select * from t where v between 5 and 10;")?,
+            plan(&mut s, "explain select * from t where v >= 5 and v <= 10;")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_like() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name string);")?;
+        s.execute(
+            "insert into t values (1, 'apple'), (2, 'Apple pie'), (3, 'banana'), (4, '100%'), (5, null), (6, 'a_b');",
+        )?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ids = |ids: &[i64]| ids.iter().map(|i| Value::Integer(*i)).collect::<Vec<_>>();
+
+        // 匹配整个字符串并且区分大小写，NULL 不和任何模式匹配
+        assert_eq!(
+            result(&mut s, "select id from t where name like 'a%' order by id;")?,
+            ids(&[1, 6])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where name like '%p%' order by id;"
+            )?,
+            ids(&[1, 2])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where name like '_pple' order by id;"
+            )?,
+            ids(&[1])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where name not like '%a%' order by id;"
+            )?,
+            ids(&[2, 4])
+        );
+        assert_eq!(
+            result(&mut s, "select id from t where name like 'banan';")?,
+            Vec::<Value>::new()
+        );
+
+        // 转义之后的 % 和 _ 按照原样匹配
+        assert_eq!(
+            result(&mut s, "select id from t where name like '%!%' escape '!';")?,
+            ids(&[4])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where name like '%\\_%' escape '\\';"
+            )?,
+            ids(&[6])
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where name like '%_%' order by id;"
+            )?,
+            ids(&[1, 2, 3, 4, 6])
+        );
+        assert_eq!(
+            result(&mut s, "select 'abc' like null, null like '%';")?,
+            vec![Value::Null]
+        );
+
+        // 转义字符只能是一个字符，模式不能以转义字符结尾，操作数必须是字符串
+        for sql in [
+            "select id from t where name like 'a%' escape 'ab';",
+            "select id from t where name like 'a!' escape '!';",
+            "select id from t where id like '1';",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_null() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, v int);")?;
+        s.execute("create table u (id int, t_id int);")?;
+        s.execute("insert into t values (1, 10), (2, null), (3, 30), (4, null);")?;
+        s.execute("insert into u values (1, 1), (2, 3);")?;
+        let result = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ids = |ids: &[i64]| ids.iter().map(|i| Value::Integer(*i)).collect::<Vec<_>>();
+
+        assert_eq!(
+            result(&mut s, "select id from t where v is null order by id;")?,
+            ids(&[2, 4])
+        );
+        assert_eq!(
+            result(&mut s, "select id from t where v is not null order by id;")?,
+            ids(&[1, 3])
+        );
+        // 和 NULL 比较的结果是 NULL，不返回任何行
+        assert_eq!(
+            result(&mut s, "select id from t where v = null;")?,
+            Vec::<Value>::new()
+        );
+        assert_eq!(
+            result(
+                &mut s,
+                "select id from t where v + 1 is null or id = 1 order by id;"
+            )?,
+            ids(&[1, 2, 4])
+        );
+        // 结果不会是 NULL
+        assert_eq!(
+            s.execute("select null is null, 1 is null, null is not null;")?,
+            ResultSet::Scan {
+                columns: vec![
+                    "NULL IS NULL".to_string(),
+                    "1 IS NULL".to_string(),
+                    "NOT NULL IS NULL".to_string(),
+                ],
+                rows: vec![vec![
+                    Value::Boolean(true),
+                    Value::Boolean(false),
+                    Value::Boolean(false),
+                ]],
+            }
+        );
+        // 外连接中没有匹配的行
+        assert_eq!(
+            result(
+                &mut s,
+                "select t.id from t left join u on t.id = u.t_id where u.id is null order by t.id;"
+            )?,
+            ids(&[2, 4])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_functions() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name string);")?;
+        s.execute("insert into t values (1, '  Apple '), (2, 'banana'), (3, null);")?;
+        let string = |s: &str| Value::String(s.to_string());
+
+        assert_eq!(
+            s.execute(
+                "select upper(trim(name)), length(name), substr(replace(name, 'an', 'AN'), 2, 4) from t order by id;"
+            )?,
+            ResultSet::Scan {
+                columns: vec![
+                    "upper(trim(name))".to_string(),
+                    "length(name)".to_string(),
+                    "substr(replace(name, 'an', 'AN'), 2, 4)".to_string(),
+                ],
+                rows: vec![
+                    vec![string("APPLE"), Value::Integer(8), string(" App")],
+                    vec![string("BANANA"), Value::Integer(6), string("ANAN")],
+                    vec![Value::Null, Value::Null, Value::Null],
+                ],
+            }
+        );
+        // 函数可以用在 WHERE 和 ORDER BY 中
+        match s.execute("select id from t where lower(trim(name)) = 'apple' or length(name) > 5 order by length(name);")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows, vec![vec![Value::Integer(2)], vec![Value::Integer(1)]])
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(s.execute("select upper(id) from t;").is_err());
+        assert!(s.execute("select lower() from t;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_functions() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, i int, f float);")?;
+        s.execute("insert into t values (1, -7, 2.25), (2, 12, -0.5), (3, null, null);")?;
+
+        assert_eq!(
+            s.execute(
+                "select abs(i), mod(i, 5), round(f, 1), ceil(f), sqrt(abs(f)) from t order by id;"
+            )?,
+            ResultSet::Scan {
+                columns: vec![
+                    "abs(i)".to_string(),
+                    "mod(i, 5)".to_string(),
+                    "round(f, 1)".to_string(),
+                    "ceil(f)".to_string(),
+                    "sqrt(abs(f))".to_string(),
+                ],
+                rows: vec![
+                    vec![
+                        Value::Integer(7),
+                        Value::Integer(-2),
+                        Value::Float(2.3),
+                        Value::Float(3.0),
+                        Value::Float(1.5),
+                    ],
+                    vec![
+                        Value::Integer(12),
+                        Value::Integer(2),
+                        Value::Float(-0.5),
+                        Value::Float(-0.0),
+                        Value::Float(0.5f64.sqrt()),
+                    ],
+                    vec![Value::Null; 5],
+                ],
+            }
+        );
+        match s.execute("select id from t where power(i, 2) > 100 or floor(f) = 2;")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows, vec![vec![Value::Integer(1)], vec![Value::Integer(2)]])
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(s.execute("select power(i, 30) from t;").is_err());
+        assert!(s.execute("select sqrt(f) from t;").is_err());
+        // 结果不是有限的浮点数时报错
+        for sql in [
+            "select power(0, -1);",
+            "select 0.0 ^ -2;",
+            "select power(-8, 0.5);",
+            "select power(10.0, 400);",
+            "select power(cast(0 as decimal(2, 1)), -1);",
+            "select 1e308 * 10;",
+            "select -1e308 - 1e308;",
+            "select 1.0 / 0;",
+            "select 0.0 / 0.0;",
+            "select 1.5 % 0;",
+            "select cast(2 as decimal(2, 1)) * 1e308;",
+        ] {
+            assert!(
+                matches!(s.execute(sql), Err(Error::Internal(e)) if e.contains("not a finite number")),
+                "{}",
+                sql
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cast() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        // 整数自动转换成浮点数，包括列的默认值
+        s.execute("create table t (id int, f float default 0, s string, b bool);")?;
+        s.execute("insert into t values (1, 2, '10', true), (2, 2.5, ' -3 ', false);")?;
+        s.execute("insert into t (id, s) values (3, 'x');")?;
+        s.execute("update t set f = id * 10 where id = 2;")?;
+
+        assert_eq!(
+            s.execute(
+                "select f, cast(f as int), cast(s as int) + 1, cast(b as integer), cast(id as string) from t where id < 3 order by id;"
+            )?,
+            ResultSet::Scan {
+                columns: vec![
+                    "f".to_string(),
+                    "CAST(f AS INTEGER)".to_string(),
+                    "CAST(s AS INTEGER) + 1".to_string(),
+                    "CAST(b AS INTEGER)".to_string(),
+                    "CAST(id AS STRING)".to_string(),
+                ],
+                rows: vec![
+                    vec![
+                        Value::Float(2.0),
+                        Value::Integer(2),
+                        Value::Integer(11),
+                        Value::Integer(1),
+                        Value::String("1".to_string()),
+                    ],
+                    vec![
+                        Value::Float(20.0),
+                        Value::Integer(20),
+                        Value::Integer(-2),
+                        Value::Integer(0),
+                        Value::String("2".to_string()),
+                    ],
+                ],
+            }
+        );
+        match s.execute("select f, cast(b as string) from t where id = 3;")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows, vec![vec![Value::Float(0.0), Value::Null]])
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // 其他类型不会自动转换，需要使用 CAST
+        assert!(s.execute("insert into t values (4, 1, 2, true);").is_err());
+        assert!(s.execute("insert into t values (4, 1, '2', 1);").is_err());
+        s.execute("insert into t values (4, 1, cast(2 as string), cast(1 as bool));")?;
+        assert!(s.execute("update t set id = 1.5 where id = 4;").is_err());
+        assert!(s.execute("select cast(s as int) from t;").is_err());
+        // 字符串不能转换成无穷大或者 NaN
+        for sql in [
+            "select cast('nan' as float);",
+            "select cast('inf' as float);",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decimal() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let d = |s: &str| Value::Decimal(Decimal::parse(s).unwrap());
+        // 写入时按照列的 scale 四舍五入，包括默认值
+        s.execute("create table t (id int, price decimal(6, 2) default 0.005, qty numeric);")?;
+        s.execute("insert into t values (1, 0.1, 3), (2, 0.2, 1), (3, 1234.565, 2);")?;
+        s.execute("insert into t (id, qty) values (4, 10);")?;
+        s.execute("update t set qty = qty + 0.5 where id = 2;")?;
+
+        assert_eq!(
+            s.execute("select id, price, qty, price * qty from t order by price desc;")?,
+            ResultSet::Scan {
+                columns: vec![
+                    "id".to_string(),
+                    "price".to_string(),
+                    "qty".to_string(),
+                    "price * qty".to_string(),
+                ],
+                rows: vec![
+                    vec![Value::Integer(3), d("1234.57"), d("2"), d("2469.14")],
+                    vec![Value::Integer(2), d("0.20"), d("2"), d("0.40")],
+                    vec![Value::Integer(1), d("0.10"), d("3"), d("0.30")],
+                    vec![Value::Integer(4), d("0.01"), d("10"), d("0.10")],
+                ],
+            }
+        );
+        // 精确求和，和整数、浮点数比较
+        match s.execute(
+            "select sum(price), sum(price) = 1234.88, max(price), avg(qty) from t where price < 1000;",
+        )? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![
+                    d("0.31"),
+                    Value::Boolean(false),
+                    d("0.20"),
+                    d("5.000000"),
+                ]]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match s.execute("select id from t where price + cast(0.2 as decimal(2, 1)) = 0.3 or qty = 2 order by id;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1)],
+                    vec![Value::Integer(2)],
+                    vec![Value::Integer(3)],
+                ]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match s.execute("select price / 3, cast(price as string), cast('9.999' as decimal(4, 2)) from t where id = 1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![d("0.03333333"), Value::String("0.10".to_string()), d("10.00")]]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // 浮点数常量转换成 DECIMAL 计算，浮点数的列仍然使用浮点数计算
+        match s.execute(
+            "select price + 0.1, 0.3 - price, price * 1e-1, avg(price), price + cast(0.1 as float) \
+             from t where id = 1 group by price;",
+        )? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![
+                    d("0.20"),
+                    d("0.20"),
+                    d("0.010"),
+                    d("0.10000000"),
+                    Value::Float(0.1 + 0.1),
+                ]]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // 整数部分超出范围
+        assert!(s.execute("insert into t values (5, 10000, 1);").is_err());
+        assert!(s
+            .execute("update t set price = price * 100 where id = 3;")
+            .is_err());
+        assert!(s.execute("insert into t values (5, '1.5', 1);").is_err());
+        assert!(s.execute("select price / 0 from t;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_uuid() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let u = |s: &str| Value::Uuid(Uuid::parse(s).unwrap());
+        // UUID 作为主键，字符串写入时自动转换
+        s.execute("create table t (id uuid, name string);")?;
+        s.execute("insert into t values (gen_uuid(), 'a'), (gen_uuid(), 'b');")?;
+        s.execute("insert into t values ('ffffffff-9c0b-4ef8-bb6d-6bb9bd380a11', 'e');")?;
+        s.execute("insert into t values ('00000000000040008000000000000000', 'd');")?;
+        // 大写的字符串和小写的是同一个 UUID，主键重复
+        assert!(matches!(
+            s.execute("insert into t values ('FFFFFFFF-9C0B-4EF8-BB6D-6BB9BD380A11', 'c');"),
+            Err(Error::ConstraintViolation(_))
+        ));
+        assert!(s
+            .execute("insert into t values ('not a uuid', 'e');")
+            .is_err());
+        assert!(s.execute("insert into t values (1, 'e');").is_err());
+
+        match s.execute("select id from t where name < 'c';")? {
+            ResultSet::Scan { rows, .. } => {
+                assert_eq!(rows.len(), 2);
+                assert_ne!(rows[0], rows[1]);
+                assert!(rows.iter().all(|r| matches!(r[0], Value::Uuid(_))));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        s.execute("update t set name = 'x' where id = 'ffffffff-9c0b-4ef8-bb6d-6bb9bd380a11';")?;
+        assert_eq!(
+            s.execute("select id, name, cast(id as string) from t where name > 'b' order by id;")?,
+            ResultSet::Scan {
+                columns: vec![
+                    "id".to_string(),
+                    "name".to_string(),
+                    "CAST(id AS STRING)".to_string(),
+                ],
+                rows: vec![
+                    vec![
+                        u("00000000-0000-4000-8000-000000000000"),
+                        Value::String("d".to_string()),
+                        Value::String("00000000-0000-4000-8000-000000000000".to_string()),
+                    ],
+                    vec![
+                        u("ffffffff-9c0b-4ef8-bb6d-6bb9bd380a11"),
+                        Value::String("x".to_string()),
+                        Value::String("ffffffff-9c0b-4ef8-bb6d-6bb9bd380a11".to_string()),
+                    ],
+                ],
+            }
+        );
+        assert!(s.execute("select * from t where id = 'x';").is_err());
+
+        // gen_uuid() 作为默认值时每一行分别生成
+        s.execute("create table u (id int, u uuid default gen_uuid());")?;
+        s.execute("insert into u (id) values (1), (2);")?;
+        match s.execute("select count(distinct u) from u;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![vec![Value::Integer(2)]]),
+            result => panic!("unexpected result {:?}", result),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_json() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let str = |s: &str| Value::String(s.to_string());
+        // 写入时校验 JSON
+        s.execute("create table t (id int, doc json);")?;
+        s.execute(
+            r#"insert into t values
+                (1, '{"name": "a", "tags": ["x", "y"], "price": 10, "meta": {"ok": true}}'),
+                (2, '{"name": "b", "tags": [], "price": 2.5, "meta": null}'),
+                (3, '[1, 2, 3]'),
+                (4, NULL);"#,
+        )?;
+        assert!(s.execute("insert into t values (5, '{\"a\": }');").is_err());
+        assert!(s.execute("insert into t values (5, 1);").is_err());
+        assert!(s.execute("update t set doc = 'x' where id = 1;").is_err());
+
+        assert_eq!(
+            s.execute(
+                "select id, doc ->> 'name', doc -> 'tags' -> 0, doc -> 'meta' ->> 'ok', doc ->> -1 from t order by id;"
+            )?,
+            ResultSet::Scan {
+                columns: vec![
+                    "id".to_string(),
+                    "doc ->> 'name'".to_string(),
+                    "doc -> 'tags' -> 0".to_string(),
+                    "doc -> 'meta' ->> 'ok'".to_string(),
+                    "doc ->> -1".to_string(),
+                ],
+                rows: vec![
+                    vec![
+                        Value::Integer(1),
+                        str("a"),
+                        Value::Json(Json::String("x".to_string())),
+                        str("true"),
+                        Value::Null,
+                    ],
+                    vec![Value::Integer(2), str("b"), Value::Null, Value::Null, Value::Null],
+                    vec![Value::Integer(3), Value::Null, Value::Null, Value::Null, str("3")],
+                    vec![Value::Integer(4), Value::Null, Value::Null, Value::Null, Value::Null],
+                ],
+            }
+        );
+        // 在 WHERE 中使用，->> 的结果是文本，可以转换成其他类型
+        match s.execute(
+            "select id, doc -> 'tags' from t where doc ->> 'name' = 'a' or cast(doc ->> 'price' as float) < 5 order by id;",
+        )? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![Value::Integer(1), Value::Json(Json::parse(r#"["x","y"]"#)?)],
+                    vec![Value::Integer(2), Value::Json(Json::Array(Vec::new()))],
+                ]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match s.execute("select cast(doc as string) from t where id = 2;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![str(
+                    r#"{"name":"b","tags":[],"price":2.5,"meta":null}"#
+                )]]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(s.execute("select doc -> true from t;").is_err());
+        assert!(s.execute("select id -> 'a' from t;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_operations() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t1 (id int, a int, b string);")?;
+        s.execute("create table t2 (id int, a float, b string);")?;
+        s.execute(
+            "insert into t1 values (1, 1, 'x'), (2, 2, 'y'), (3, 2, 'y'), (4, 3, 'z'), (5, NULL, NULL);",
+        )?;
+        s.execute(
+            "insert into t2 values (1, 2.0, 'y'), (2, 4.0, 'w'), (3, NULL, NULL), (4, 2.0, 'y');",
+        )?;
+        let rows = |s: &mut Session<KVEngine<MemoryEngine>>, sql: &str| match s.execute(sql) {
+            Ok(ResultSet::Scan { columns, rows }) => Ok((columns, rows)),
+            Ok(result) => panic!("unexpected result {:?}", result),
+            Err(e) => Err(e),
+        };
+        let float = Value::Float;
+        let str = |s: &str| Value::String(s.to_string());
+
+        // 整数和浮点数放在一起时整数转换成浮点数，NULL 和 NULL 相同
+        let (columns, union) = rows(&mut s, "select a, b from t1 union select a, b from t2;")?;
+        assert_eq!(columns, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(
+            union,
+            vec![
+                vec![float(1.0), str("x")],
+                vec![float(2.0), str("y")],
+                vec![float(3.0), str("z")],
+                vec![Value::Null, Value::Null],
+                vec![float(4.0), str("w")],
+            ]
+        );
+        let (_, union_all) = rows(&mut s, "select a from t1 union all select a from t2;")?;
+        assert_eq!(union_all.len(), 9);
+
+        for (sql, expected) in [
+            ("select a from t1 intersect select a from t2 order by a;", vec![Value::Null, float(2.0)]),
+            ("select a from t1 intersect all select a from t2 order by a;", vec![Value::Null, float(2.0), float(2.0)]),
+            ("select a from t1 except select a from t2 order by a desc;", vec![float(3.0), float(1.0)]),
+            ("select a from t2 except all select a from t1;", vec![float(4.0)]),
+            // INTERSECT 先计算，结果是 t1 中的所有值加上 4
+            (
+                "select a from t1 union select a from t2 intersect select a from t2 where a > 3 order by a;",
+                vec![Value::Null, float(1.0), float(2.0), float(3.0), float(4.0)],
+            ),
+            (
+                "select id from t1 where id in (select id from t2 except select 4) order by id;",
+                vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)],
+            ),
+            ("select x from (select id as x from t1 intersect select id from t2) s order by x desc;", vec![
+                Value::Integer(4),
+                Value::Integer(3),
+                Value::Integer(2),
+                Value::Integer(1),
+            ]),
+        ] {
+            let (_, result) = rows(&mut s, sql)?;
+            assert_eq!(result, expected.into_iter().map(|v| vec![v]).collect::<Vec<_>>(), "{}", sql);
+        }
+
+        // 列数和类型需要一致，排序只能使用结果中的列名
+        assert!(rows(&mut s, "select a, b from t1 union select a from t2;").is_err());
+        assert!(rows(&mut s, "select b from t1 union select a from t2;").is_err());
+        assert!(rows(
+            &mut s,
+            "select a from t1 union select a from t2 order by id;"
+        )
+        .is_err());
+        assert!(rows(
+            &mut s,
+            "select a from t1 as of version 1 union select a from t2;"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_views() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table users (id int, name text, age int);")?;
+        s.execute("create table orders (id int, user_id int, amount int);")?;
+        s.execute("insert into users values (1, 'a', 20), (2, 'b', 30), (3, 'c', 40);")?;
+        s.execute("insert into orders values (1, 1, 10), (2, 1, 5), (3, 3, 7);")?;
+        let rows = |s: &mut Session<KVEngine<MemoryEngine>>, sql: &str| match s.execute(sql) {
+            Ok(ResultSet::Scan { columns, rows }) => Ok((columns, rows)),
+            Ok(result) => panic!("unexpected result {:?}", result),
+            Err(e) => Err(e),
+        };
+        let int = Value::Integer;
+        let str = |s: &str| Value::String(s.to_string());
+
+        assert_eq!(
+            s.execute("create view adults as select id, name from users where age > 25;")?,
+            ResultSet::CreateView {
+                view_name: "adults".to_string()
+            }
+        );
+        s.execute(
+            "create view totals (user_id, total) as select user_id, sum(amount) from orders group by user_id;",
+        )?;
+        // 视图中的查询总是读取最新的数据
+        s.execute("insert into users values (4, 'd', 50);")?;
+        assert_eq!(
+            rows(&mut s, "select * from adults;")?,
+            (
+                vec!["id".to_string(), "name".to_string()],
+                vec![
+                    vec![int(2), str("b")],
+                    vec![int(3), str("c")],
+                    vec![int(4), str("d")]
+                ]
+            )
+        );
+        assert_eq!(
+            rows(&mut s, "select * from totals order by total desc;")?,
+            (
+                vec!["user_id".to_string(), "total".to_string()],
+                vec![vec![int(1), int(15)], vec![int(3), int(7)]]
+            )
+        );
+        // 视图可以连接、使用别名，也可以在其他视图和子查询中使用
+        assert_eq!(
+            rows(
+                &mut s,
+                "select a.name, t.total from adults a join totals t on a.id = t.user_id;"
+            )?
+            .1,
+            vec![vec![str("c"), int(7)]]
+        );
+        s.execute(
+            "create view rich as select name from adults where id in (select user_id from totals);",
+        )?;
+        assert_eq!(
+            rows(&mut s, "select name from rich;")?.1,
+            vec![vec![str("c")]]
+        );
+
+        // 名字不能和表或者其他视图相同，列名需要和查询结果对应，查询中的表需要存在
+        assert!(s.execute("create view users as select 1;").is_err());
+        assert!(s.execute("create table adults (a int);").is_err());
+        assert!(s.execute("alter table orders rename to adults;").is_err());
+        assert!(s.execute("create view adults as select 1;").is_err());
+        assert!(s
+            .execute("create view v (a) as select id, name from users;")
+            .is_err());
+        assert!(s
+            .execute("create view v (a, a) as select id, name from users;")
+            .is_err());
+        assert!(s
+            .execute("create view v as select * from missing;")
+            .is_err());
+        assert!(s
+            .execute("create view v as select * from users as of version 1;")
+            .is_err());
+        assert!(s.execute("insert into adults values (5, 'e');").is_err());
+
+        // 被其他视图引用时不能删除，先删除引用它的视图
+        assert_eq!(
+            s.execute("drop view adults;"),
+            Err(Error::Internal(
+                "cannot drop view adults, view rich depends on it".to_string()
+            ))
+        );
+        s.execute("drop view rich;")?;
+        assert_eq!(
+            s.execute("drop view adults;")?,
+            ResultSet::DropView {
+                view_name: "adults".to_string()
+            }
+        );
+        assert!(s.execute("select * from adults;").is_err());
+        assert!(s.execute("drop view adults;").is_err());
+        assert!(s.execute("drop view users;").is_err());
+        s.execute("create view adults as select name, id from users where age > 45;")?;
+        s.execute(
+            "create view rich as select name from adults where id in (select user_id from totals);",
+        )?;
+        assert_eq!(rows(&mut s, "select * from rich;")?.1, Vec::<Row>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepared_statements() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, price decimal(10, 2), uid uuid, doc json);")?;
+        let int = Value::Integer;
+        let str = |s: &str| Value::String(s.to_string());
+        let rows = |result: ResultSet| match result {
+            ResultSet::Scan { rows, .. } => rows,
+            result => panic!("unexpected result {:?}", result),
+        };
+
+        // 同一条语句使用不同的参数多次执行
+        let insert = s.prepare("insert into t values (?, ?, ?, ?, ?);")?;
+        assert_eq!(insert.parameters(), 5);
+        let price = Decimal::parse("1.50").unwrap();
+        let uid = Uuid::parse("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11").unwrap();
+        let doc = Json::parse(r#"{"a": [1, 2]}"#)?;
+        s.execute_prepared(
+            &insert,
+            &[
+                int(1),
+                str("a"),
+                Value::Decimal(price),
+                Value::Uuid(uid),
+                Value::Json(doc.clone()),
+            ],
+        )?;
+        // 参数的值不会作为 SQL 文本解析
+        let name = "x'); delete from t; --";
+        s.execute_prepared(
+            &insert,
+            &[int(2), str(name), Value::Null, Value::Null, Value::Null],
+        )?;
+
+        let select =
+            s.prepare("select id, name from t where id >= $1 and name <> $2 order by id;")?;
+        assert_eq!(
+            rows(s.execute_prepared(&select, &[int(1), str("b")])?),
+            vec![vec![int(1), str("a")], vec![int(2), str(name)]]
+        );
+        assert_eq!(
+            rows(s.execute_prepared(&select, &[int(2), str(name)])?),
+            Vec::<Row>::new()
+        );
+        let select = s.prepare("select price, uid, doc ->> 'a' from t where uid = ?;")?;
+        assert_eq!(
+            rows(s.execute_prepared(&select, &[Value::Uuid(uid)])?),
+            vec![vec![Value::Decimal(price), Value::Uuid(uid), str("[1,2]")]]
+        );
+
+        let update = s.prepare("update t set name = $2 where id = $1;")?;
+        s.execute_prepared(&update, &[int(2), str("b")])?;
+        let delete = s.prepare("delete from t where name = ?;")?;
+        s.execute_prepared(&delete, &[str("a")])?;
+        assert_eq!(
+            rows(s.execute("select id, name from t;")?),
+            vec![vec![int(2), str("b")]]
+        );
+
+        // 参数的数量需要和语句中的一致，没有绑定的参数不能直接执行
+        assert!(s.execute_prepared(&delete, &[]).is_err());
+        assert!(s.execute_prepared(&delete, &[str("a"), str("b")]).is_err());
+        assert!(s.execute("select * from t where id = ?;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quoted_identifiers() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute(r#"create table "My Table" ("select" int, "Name" text, name text);"#)?;
+        s.execute(r#"insert into `My Table` ("select", "Name", name) values (2, 'A', 'a'), (1, 'B', 'b');"#)?;
+        let int = Value::Integer;
+        let str = |s: &str| Value::String(s.to_string());
+
+        // 带引号的名字区分大小写，没有引号的名字转换成小写
+        match s.execute(
+            r#"select "select", `Name`, NAME, t."select" + 1 as "Sum" from "My Table" t where "select" > 0 order by "select";"#,
+        )? {
+            ResultSet::Scan { columns, rows } => {
+                assert_eq!(columns, vec!["select", "Name", "name", "Sum"]);
+                assert_eq!(
+                    rows,
+                    vec![
+                        vec![int(1), str("B"), str("b"), int(2)],
+                        vec![int(2), str("A"), str("a"), int(3)]
+                    ]
+                );
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        s.execute(r#"update "My Table" set "Name" = 'C' where "select" = 1;"#)?;
+        match s.execute(r#"select "Name" from "My Table" where "select" = 1;"#)? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![vec![str("C")]]),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        assert!(s.execute("select * from my table;").is_err());
+        assert!(s.execute(r#"select * from "my table";"#).is_err());
+        assert!(s
+            .execute(r#"select "name" from "My Table" where "NAME" = 'a';"#)
+            .is_err());
+        assert!(s.execute(r#"create table "a.b" (id int);"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_literals() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        match s.execute("select 1e3, 2.5E-3, .5, 0xFF, 0x10 + 1, -1e-1;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![vec![
+                    Value::Float(1000.0),
+                    Value::Float(0.0025),
+                    Value::Float(0.5),
+                    Value::Integer(255),
+                    Value::Integer(17),
+                    Value::Float(-0.1),
+                ]]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(s.execute("select 1e;").is_err());
+        assert!(s.execute("select 0xfffffffffffffffff;").is_err());
+
+        // 负号合并到整数常量中，最小的整数不会超出范围
+        match s.execute("select -9223372036854775808, - 5, -2 ^ 2, 1 - -1;")? {
+            ResultSet::Scan { columns, rows } => {
+                assert_eq!(columns[0], "-9223372036854775808");
+                assert_eq!(
+                    rows,
+                    vec![vec![
+                        Value::Integer(i64::MIN),
+                        Value::Integer(-5),
+                        Value::Integer(4),
+                        Value::Integer(2),
+                    ]]
+                );
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        // 超出范围的常量报错，浮点数不能是无穷大
+        for sql in [
+            "select 9223372036854775808;",
+            "select -9223372036854775809;",
+            "select 1e400;",
+            "select -1e400;",
+        ] {
+            assert!(
+                matches!(s.execute(sql), Err(Error::Parse(e)) if e.contains("out of range")),
+                "{}",
+                sql
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_secondary_indexes() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text, score int);")?;
+        s.execute("insert into t values (1, 'a', 10), (2, 'b', 20), (3, 'a', null);")?;
+        assert_eq!(
+            s.execute("create index idx_name on t (name);")?,
+            ResultSet::CreateIndex {
+                index_name: "idx_name".to_string(),
+                rows: 3,
+            }
+        );
+        assert_eq!(
+            s.execute("create index idx_score on t (score);")?,
+            ResultSet::CreateIndex {
+                index_name: "idx_score".to_string(),
+                rows: 2,
+            }
+        );
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ids = |s: &mut Session<_>, sql: &str| -> Result<Vec<i64>> {
+            Ok(rows(s, sql)?
+                .into_iter()
+                .map(|row| match row[0] {
+                    Value::Integer(i) => i,
+                    _ => unreachable!(),
+                })
+                .collect())
+        };
+        assert_eq!(
+            ids(&mut s, "select id from t where name = 'a';")?,
+            vec![1, 3]
+        );
+        assert_eq!(ids(&mut s, "select id from t where 20 = score;")?, vec![2]);
+        assert_eq!(
+            ids(&mut s, "select id from t where name = 'a' and score = 10;")?,
+            vec![1]
+        );
+        assert_eq!(
+            ids(&mut s, "select id from t where score = null;")?,
+            Vec::<i64>::new()
+        );
+
+        // 等值条件通过索引读取，过滤条件仍然保留
+        assert_eq!(
+            rows(&mut s, "explain verbose select id from t where name = 'b';")?,
+            vec![
+                vec![Value::String(
+                    "Projection (exprs: [id]) rows=10 cost=410.00".to_string()
+                )],
+                vec![Value::String(
+                    "  -> Filter (predicate: name = 'b') rows=10 cost=400.00".to_string()
+                )],
+                vec![Value::String(
+                    "    -> IndexScan (table: t, index: idx_name, key: [name = 'b'], \
+                     columns: [id, name, score]) rows=100 cost=300.00"
+                        .to_string()
+                )],
+            ]
+        );
+
+        // 插入、修改和删除行时维护索引
+        s.execute("insert into t values (4, 'c', 40);")?;
+        s.execute("update t set name = 'c' where id = 1;")?;
+        assert_eq!(ids(&mut s, "select id from t where name = 'a';")?, vec![3]);
+        assert_eq!(
+            ids(&mut s, "select id from t where name = 'c';")?,
+            vec![1, 4]
+        );
+        s.execute("update t set name = 'b' where name = 'c';")?;
+        assert_eq!(
+            ids(&mut s, "select id from t where name = 'b';")?,
+            vec![1, 2, 4]
+        );
+        s.execute("update t set id = 5 where id = 4;")?;
+        assert_eq!(
+            ids(&mut s, "select id from t where name = 'b';")?,
+            vec![1, 2, 5]
+        );
+        s.execute("update t set score = null where score = 10;")?;
+        assert_eq!(
+            ids(&mut s, "select id from t where score = 10;")?,
+            Vec::<i64>::new()
+        );
+        s.execute("delete from t where name = 'b' and id = 2;")?;
+        assert_eq!(
+            ids(&mut s, "select id from t where name = 'b';")?,
+            vec![1, 5]
+        );
+        assert_eq!(
+            ids(&mut s, "select id from t where score = 20;")?,
+            Vec::<i64>::new()
+        );
+
+        // 批量导入时同时写入索引，同一批中主键重复时不写入任何行
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("data.csv");
+        std::fs::write(&path, "6,d,60\n7,d,70\n6,e,60\n")?;
+        assert!(s
+            .execute(&format!("copy t from '{}';", path.display()))
+            .is_err());
+        assert_eq!(
+            ids(&mut s, "select id from t where name = 'd';")?,
+            Vec::<i64>::new()
+        );
+        std::fs::write(&path, "6,d,60\n7,e,70\n")?;
+        s.execute(&format!("copy t from '{}';", path.display()))?;
+        assert_eq!(ids(&mut s, "select id from t where name = 'd';")?, vec![6]);
+        assert_eq!(ids(&mut s, "select id from t where name = 'e';")?, vec![7]);
+        assert_eq!(ids(&mut s, "select id from t where score = 60;")?, vec![6]);
+
+        // 修改表名时索引一起移动
+        s.execute("alter table t rename to t2;")?;
+        assert_eq!(
+            ids(&mut s, "select id from t2 where name = 'b';")?,
+            vec![1, 5]
+        );
+        assert_eq!(
+            rows(&mut s, "show indexes from t2;")?,
+            vec![
+                vec![
+                    Value::String("primary".to_string()),
+                    Value::String("id".to_string()),
+                    Value::Boolean(true),
+                    Value::Integer(5),
+                    Value::Boolean(false),
+                ],
+                vec![
+                    Value::String("idx_name".to_string()),
+                    Value::String("name".to_string()),
+                    Value::Boolean(false),
+                    Value::Integer(5),
+                    Value::Boolean(true),
+                ],
+                vec![
+                    Value::String("idx_score".to_string()),
+                    Value::String("score".to_string()),
+                    Value::Boolean(false),
+                    Value::Integer(3),
+                    Value::Boolean(true),
+                ],
+            ]
+        );
+        assert_eq!(rows(&mut s, "check table t2;")?, Vec::<Row>::new());
+
+        // 索引中缺少的项在检查时报告，重建之后恢复
+        let txn = kvengine.begin()?;
+        let key = index_key(
+            &"t2".into(),
+            &"idx_name".into(),
+            &[&Value::String("b".to_string()), &Value::Integer(5)],
+        )?;
+        txn.txn.delete(key)?;
+        txn.commit()?;
+        assert_eq!(ids(&mut s, "select id from t2 where name = 'b';")?, vec![1]);
+        assert_eq!(
+            rows(&mut s, "check table t2;")?,
+            vec![vec![Value::String(
+                "index idx_name: missing entry for row Integer(5)".to_string()
+            )]]
+        );
+        s.execute("reindex index idx_name on t2;")?;
+        assert_eq!(rows(&mut s, "check table t2;")?, Vec::<Row>::new());
+        assert_eq!(
+            ids(&mut s, "select id from t2 where name = 'b';")?,
+            vec![1, 5]
+        );
+
+        // 删除索引之后扫描整个表
+        assert_eq!(
+            s.execute("drop index idx_name on t2;")?,
+            ResultSet::DropIndex {
+                index_name: "idx_name".to_string(),
+            }
+        );
+        assert_eq!(
+            ids(&mut s, "select id from t2 where name = 'b';")?,
+            vec![1, 5]
+        );
+        assert_eq!(rows(&mut s, "show indexes from t2;")?.len(), 2);
+        assert_eq!(rows(&mut s, "check table t2;")?, Vec::<Row>::new());
+
+        s.execute("create table tc (id int, name text) with (storage = 'columnar');")?;
+        for sql in [
+            "drop index idx_name on t2;",
+            "create index idx_score on t2 (name);",
+            "create index idx_score2 on t2 (score);",
+            "create index idx_id on t2 (id);",
+            "create index idx_x on t2 (missing);",
+            "create index idx_x on t3 (name);",
+            "create index idx_x on tc (name);",
+            "reindex index idx_name on t2;",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_composite_indexes() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, a int, b text, c float);")?;
+        s.execute(
+            "insert into t values (1, 1, 'x', 1.5), (2, 1, 'y', -2.0), (3, 2, 'x', 0.0), \
+             (4, -1, 'z', null), (5, 1, null, -0.0), (6, null, 'x', 3.0), (7, 10, 'w', 2.5);",
+        )?;
+        assert_eq!(
+            s.execute("create index idx_ab on t (a, b);")?,
+            ResultSet::CreateIndex {
+                index_name: "idx_ab".to_string(),
+                rows: 6,
+            }
+        );
+        s.execute("create index idx_c on t (c);")?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ids = |s: &mut Session<_>, sql: &str| -> Result<Vec<i64>> {
+            Ok(rows(s, sql)?
+                .into_iter()
+                .map(|row| match row[0] {
+                    Value::Integer(i) => i,
+                    _ => unreachable!(),
+                })
+                .collect())
+        };
+
+        // 使用索引的前缀，结果和扫描整个表相同
+        for (sql, expect) in [
+            ("select id from t where a = 1 order by id;", vec![1, 2, 5]),
+            ("select id from t where a = 1 and b = 'y';", vec![2]),
+            ("select id from t where b = 'x' and a = 2;", vec![3]),
+            (
+                "select id from t where a = 1 and b >= 'x' order by id;",
+                vec![1, 2],
+            ),
+            ("select id from t where a = 1 and b < 'y';", vec![1]),
+            ("select id from t where a > 1 order by id;", vec![3, 7]),
+            (
+                "select id from t where a >= -1 and a < 2 order by id;",
+                vec![1, 2, 4, 5],
+            ),
+            (
+                "select id from t where 2 > a order by id;",
+                vec![1, 2, 4, 5],
+            ),
+            ("select id from t where a <= 1 and a > 1;", vec![]),
+            ("select id from t where c < 1 order by id;", vec![2, 3, 5]),
+            ("select id from t where c = 0 order by id;", vec![3, 5]),
+            (
+                "select id from t where c > -1.0 and c <= 2.5 order by id;",
+                vec![1, 3, 5, 7],
+            ),
+        ] {
+            assert_eq!(ids(&mut s, sql)?, expect, "{}", sql);
+        }
+
+        // 只有第二列的条件时不能使用索引
+        let plan = |s: &mut Session<_>, sql: &str| -> Result<String> {
+            Ok(rows(s, &format!("explain {}", sql))?
+                .into_iter()
+                .map(|row| match &row[0] {
+                    Value::String(line) => line.clone(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"))
+        };
+        assert!(!plan(&mut s, "select id from t where b = 'x';")?.contains("IndexScan"));
+        assert!(plan(&mut s, "select id from t where a = 1 and b > 'x';")?
+            .contains("IndexScan (table: t, index: idx_ab, key: [a = 1, b > 'x']"));
+        assert!(plan(&mut s, "select id from t where a >= 1 and a < 3;")?
+            .contains("IndexScan (table: t, index: idx_ab, key: [a >= 1, a < 3]"));
+        // 等值条件多的索引优先，相同时使用先创建的索引
+        s.execute("create index idx_b on t (b);")?;
+        assert!(plan(&mut s, "select id from t where b = 'x' and a = 1;")?
+            .contains("index: idx_ab, key: [a = 1, b = 'x']"));
+        assert!(plan(&mut s, "select id from t where b = 'x' and a > 1;")?
+            .contains("index: idx_b, key: [b = 'x']"));
+        assert!(
+            plan(&mut s, "select id from t where a > 1;")?.contains("index: idx_ab, key: [a > 1]")
+        );
+
+        // 修改和删除行之后索引仍然有序
+        s.execute("update t set b = 'a' where id = 2;")?;
+        s.execute("delete from t where id = 1;")?;
+        s.execute("insert into t values (8, 1, 'v', null);")?;
+        assert_eq!(
+            ids(
+                &mut s,
+                "select id from t where a = 1 and b <= 'x' order by id;"
+            )?,
+            vec![2, 8]
+        );
+        assert_eq!(rows(&mut s, "check table t;")?, Vec::<Row>::new());
+        assert_eq!(
+            rows(&mut s, "show indexes from t;")?[1],
+            vec![
+                Value::String("idx_ab".to_string()),
+                Value::String("a, b".to_string()),
+                Value::Boolean(false),
+                Value::Integer(6),
+                Value::Boolean(true),
+            ]
+        );
+
+        for sql in [
+            "create index idx_ab2 on t (a, b);",
+            "create index idx_aa on t (a, a);",
+            "create index idx_x on t ();",
+            "create index idx_x on t (a, missing);",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        // 和已有索引的列顺序不同或者只是前缀时可以创建
+        s.execute("create index idx_ba on t (b, a);")?;
+        s.execute("create index idx_id_a on t (id, a);")?;
+        assert_eq!(rows(&mut s, "check table t;")?, Vec::<Row>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_columns() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int unique, email text unique, name text);")?;
+        s.execute("insert into t values (1, 'a@x', 'a'), (2, 'b@x', 'b'), (3, null, 'c');")?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let violation = |r: Result<ResultSet>| matches!(r, Err(Error::ConstraintViolation(_)));
+
+        assert_eq!(
+            s.execute("insert into t values (4, 'a@x', 'd');"),
+            Err(Error::ConstraintViolation(
+                "duplicate value String(\"a@x\") for UNIQUE column email of table t".to_string()
+            ))
+        );
+        // 失败的语句不写入任何行
+        assert!(violation(s.execute(
+            "insert into t values (4, 'd@x', 'd'), (5, 'd@x', 'e');"
+        )));
+        assert!(violation(
+            s.execute("update t set email = 'b@x' where id = 1;")
+        ));
+        assert!(violation(s.execute("update t set email = 'c@x';")));
+        assert!(violation(
+            s.execute("update t set id = 4, email = 'b@x' where id = 1;")
+        ));
+        assert_eq!(
+            rows(&mut s, "select count(*) from t;")?,
+            vec![vec![Value::Integer(3)]]
+        );
+
+        // NULL 不和任何值重复，修改成自己原来的值不是冲突
+        s.execute("insert into t values (4, null, 'd');")?;
+        s.execute("update t set email = 'a@x', name = 'aa' where id = 1;")?;
+        s.execute("update t set email = email, name = 'bb' where id = 2;")?;
+        s.execute("update t set id = 5 where id = 2;")?;
+        s.execute("update t set email = 'c@x' where id = 3;")?;
+        // 值被释放之后可以被其他行使用
+        s.execute("delete from t where id = 1;")?;
+        s.execute("insert into t values (6, 'a@x', 'f');")?;
+        assert_eq!(
+            rows(&mut s, "select id from t where email = 'a@x';")?,
+            vec![vec![Value::Integer(6)]]
+        );
+
+        // 同一个事务中写入的值也会冲突，回滚之后释放
+        let row = |id: i64, email: &str, name: &str| {
+            vec![
+                Value::Integer(id),
+                Value::String(email.to_string()),
+                Value::String(name.to_string()),
+            ]
+        };
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t".into(), row(7, "g@x", "g"))?;
+        assert!(matches!(
+            txn.create_row("t".into(), row(8, "g@x", "h")),
+            Err(Error::ConstraintViolation(_))
+        ));
+        txn.rollback()?;
+        s.execute("insert into t values (8, 'g@x', 'h');")?;
+
+        // 批量导入时和表中以及同一批中的行比较
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("data.csv");
+        std::fs::write(&path, "9,i@x,i\n10,i@x,j\n")?;
+        assert!(violation(
+            s.execute(&format!("copy t from '{}';", path.display()))
+        ));
+        std::fs::write(&path, "9,g@x,i\n")?;
+        assert!(violation(
+            s.execute(&format!("copy t from '{}';", path.display()))
+        ));
+        std::fs::write(&path, "9,j@x,i\n10,k@x,j\n10,l@x,j\n")?;
+        assert!(violation(
+            s.execute(&format!("copy t from '{}';", path.display()))
+        ));
+        std::fs::write(&path, "9,j@x,i\n10,l@x,j\n11,k@x,k\n")?;
+        s.execute(&format!("copy t from '{}';", path.display()))?;
+        assert_eq!(
+            rows(&mut s, "select id, email from t where id >= 8 order by id;")?,
+            vec![
+                vec![Value::Integer(8), Value::String("g@x".to_string())],
+                vec![Value::Integer(9), Value::String("j@x".to_string())],
+                vec![Value::Integer(10), Value::String("l@x".to_string())],
+                vec![Value::Integer(11), Value::String("k@x".to_string())],
+            ]
+        );
+
+        assert_eq!(
+            rows(&mut s, "show indexes from t;")?[1],
+            vec![
+                Value::String("t_email_key".to_string()),
+                Value::String("email".to_string()),
+                Value::Boolean(true),
+                Value::Integer(7),
+                Value::Boolean(true),
+            ]
+        );
+        assert_eq!(rows(&mut s, "check table t;")?, Vec::<Row>::new());
+
+        // 绕过约束写入的重复值在检查时报告
+        let txn = kvengine.begin()?;
+        let key = index_key(
+            &"t".into(),
+            &"t_email_key".into(),
+            &[&Value::String("l@x".to_string())],
+        )?;
+        txn.txn.set(key, bincode::serialize(&Value::Integer(6))?)?;
+        txn.txn.set(
+            bincode::serialize(&Key::Row("t".into(), Value::Integer(6)))?,
+            bincode::serialize(&row(6, "l@x", "f"))?,
+        )?;
+        txn.commit()?;
+        assert_eq!(
+            rows(&mut s, "check table t;")?,
+            vec![
+                vec![Value::String(
+                    "index t_email_key: duplicate value for rows Integer(6) and Integer(10)"
+                        .to_string()
+                )],
+                vec![Value::String(
+                    "index t_email_key: unexpected entry for row Integer(6)".to_string()
+                )]
+            ]
+        );
+
+        for sql in [
+            "drop index t_email_key on t;",
+            "alter table t add column phone text unique;",
+            "create table c (id int, email text unique) with (storage = 'columnar');",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_expressions() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let integer = |value: &Value| match value {
+            Value::Integer(i) => *i,
+            v => panic!("unexpected value {:?}", v),
+        };
+
+        // 常量表达式在建表时计算，now() 在每次写入时计算
+        s.execute(
+            "create table t (id int, n int default 2 * 3 + 1, s text default replace(upper('ab'), 'B', 'b'), \
+             created int not null default now(), expires int default now() + 3600, \
+             d decimal(4, 1) default 1 / 4.0, f float default -(1 + power(2, 3)));",
+        )?;
+        s.execute("insert into t (id) values (1);")?;
+        s.execute(
+            "insert into t values (2, default, default, default, default, default, default);",
+        )?;
+        s.execute("insert into t values (3);")?;
+        s.execute("insert into t (id, created) values (4, 100);")?;
+        let values = rows(
+            &mut s,
+            "select id, n, s, created, expires, d, f from t order by id;",
+        )?;
+        assert_eq!(values.len(), 4);
+        let now = now_secs();
+        for row in &values[..3] {
+            assert_eq!(row[1], Value::Integer(7));
+            assert_eq!(row[2], Value::String("Ab".to_string()));
+            assert!((now - 5..=now).contains(&integer(&row[3])), "{:?}", row);
+            assert_eq!(integer(&row[4]), integer(&row[3]) + 3600);
+            assert_eq!(row[5], Value::Decimal(Decimal::parse("0.3").unwrap()));
+            assert_eq!(row[6], Value::Float(-9.0));
+        }
+        assert_eq!(values[3][3], Value::Integer(100));
+
+        // 添加列时已有的每一行分别计算
+        s.execute("alter table t add column u uuid default gen_uuid();")?;
+        s.execute("insert into t (id) values (5);")?;
+        assert_eq!(
+            rows(
+                &mut s,
+                "select count(distinct u) from t where u is not null;"
+            )?,
+            vec![vec![Value::Integer(5)]]
+        );
+
+        // 写入时间作为 TTL 的时间列
+        s.execute(
+            "create table ttl (id int, at int default now()) \
+             with (ttl = '1 hours', ttl_column = 'at');",
+        )?;
+        s.execute("insert into ttl (id) values (1);")?;
+        assert_eq!(
+            rows(&mut s, "select id from ttl;")?,
+            vec![vec![Value::Integer(1)]]
+        );
+
+        for sql in [
+            "create table e (id int, a int default b);",
+            "create table e (id int, a int default gen_uuid());",
+            "create table e (id int, a int default now() + 'x');",
+            "create table e (id int, a int default 1 / 0);",
+            "create table e (id int, a int default 'x');",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_validation() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text not null, score float default 1, tag text);")?;
+        let error = |r: Result<ResultSet>| match r {
+            Err(Error::Internal(e)) => e,
+            r => panic!("unexpected result {:?}", r),
+        };
+
+        // 指定的列按照名字对应到表中的位置，没有给出的列使用默认值，整数转换成浮点数
+        s.execute("insert into t (tag, name, id) values ('x', 'a', 1);")?;
+        s.execute("insert into t values (2, 'b', 3), (3, 'c', default, 'y');")?;
+        match s.execute("select * from t order by id;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    vec![
+                        Value::Integer(1),
+                        Value::String("a".to_string()),
+                        Value::Float(1.0),
+                        Value::String("x".to_string()),
+                    ],
+                    vec![
+                        Value::Integer(2),
+                        Value::String("b".to_string()),
+                        Value::Float(3.0),
+                        Value::Null,
+                    ],
+                    vec![
+                        Value::Integer(3),
+                        Value::String("c".to_string()),
+                        Value::Float(1.0),
+                        Value::String("y".to_string()),
+                    ],
+                ]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        for (sql, expect) in [
+            (
+                "insert into t values (4, 'd', 1, 'z', 5);",
+                "table t has 4 columns, but INSERT has 5 values",
+            ),
+            (
+                "insert into t (id, name) values (4);",
+                "INSERT has 2 columns, but 1 values",
+            ),
+            (
+                "insert into t (id, name) values (4, 'd', 1);",
+                "table t has 2 columns, but INSERT has 3 values",
+            ),
+            (
+                "insert into t (id, nmae) values (4, 'd');",
+                "column nmae does not exist in table t",
+            ),
+            (
+                "insert into t (id, name, id) values (4, 'd', 5);",
+                "column id is specified more than once",
+            ),
+            (
+                "insert into t (id) values (4);",
+                "No default value for column name",
+            ),
+            (
+                "insert into t values (4, default);",
+                "No default value for column name",
+            ),
+            (
+                "insert into t values (4, null);",
+                "column name cannot be null",
+            ),
+            (
+                "insert into t values (4, 5);",
+                "column name type mismatch, expected STRING but got INTEGER",
+            ),
+            (
+                "insert into t values (4, 'd', 'high');",
+                "column score type mismatch, expected FLOAT but got STRING",
+            ),
+        ] {
+            assert_eq!(error(s.execute(sql)), expect, "{}", sql);
+        }
+        // 出错的语句不写入任何行
+        assert!(s
+            .execute("insert into t values (4, 'd'), (5, null);")
+            .is_err());
+        match s.execute("select count(*) from t;")? {
+            ResultSet::Scan { rows, .. } => assert_eq!(rows, vec![vec![Value::Integer(3)]]),
+            result => panic!("unexpected result {:?}", result),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_and_show_create_table() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let statements = |s: &mut Session<_>, table: &str| -> Result<Vec<String>> {
+            Ok(rows(s, &format!("show create table {};", table))?
+                .into_iter()
+                .map(|row| match &row[0] {
+                    Value::String(sql) => sql.clone(),
+                    v => panic!("unexpected value {:?}", v),
+                })
+                .collect())
+        };
+        let string = |s: &str| Value::String(s.to_string());
+
+        s.execute(
+            "create table \"User\" (id int, \"select\" text default 'x' unique, score float default 1.5, \
+             n int not null default -3, at int default now(), p decimal(5, 2) default 1.25, \
+             m text not null) with (ttl = '2 hours', ttl_column = 'at');",
+        )?;
+        s.execute("create index by_score on \"User\" (score, n);")?;
+        s.execute("create table c (id int, v text) with (storage = 'columnar', compression = 'dictionary');")?;
+        s.execute(
+            "create external table e (a int, b text) location 'data.csv' \
+             with (header = 'true', delimiter = ';');",
+        )?;
+
+        assert_eq!(
+            rows(&mut s, "describe \"User\";")?,
+            vec![
+                vec![
+                    string("id"),
+                    string("INTEGER"),
+                    Value::Boolean(false),
+                    Value::Null
+                ],
+                vec![
+                    string("select"),
+                    string("STRING"),
+                    Value::Boolean(true),
+                    string("'x'")
+                ],
+                vec![
+                    string("score"),
+                    string("FLOAT"),
+                    Value::Boolean(true),
+                    string("1.5")
+                ],
+                vec![
+                    string("n"),
+                    string("INTEGER"),
+                    Value::Boolean(false),
+                    string("-3")
+                ],
+                vec![
+                    string("at"),
+                    string("INTEGER"),
+                    Value::Boolean(true),
+                    string("now()")
+                ],
+                vec![
+                    string("p"),
+                    string("DECIMAL(5, 2)"),
+                    Value::Boolean(true),
+                    string("CAST('1.25' AS DECIMAL(38, 2))")
+                ],
+                vec![
+                    string("m"),
+                    string("STRING"),
+                    Value::Boolean(false),
+                    Value::Null
+                ],
+            ]
+        );
+        let user = statements(&mut s, "\"User\"")?;
+        assert_eq!(
+            user,
+            vec![
+                "CREATE TABLE \"User\" (id INTEGER, \"select\" STRING DEFAULT 'x' UNIQUE, \
+                 score FLOAT DEFAULT 1.5, n INTEGER NOT NULL DEFAULT -3, at INTEGER DEFAULT now(), \
+                 p DECIMAL(5, 2) DEFAULT CAST('1.25' AS DECIMAL(38, 2)), m STRING NOT NULL) \
+                 WITH (ttl = '2 hours', ttl_column = 'at');"
+                    .to_string(),
+                "CREATE INDEX by_score ON \"User\" (score, n);".to_string(),
+            ]
+        );
+        let c = statements(&mut s, "c")?;
+        assert_eq!(
+            c,
+            vec!["CREATE TABLE c (id INTEGER, v STRING) \
+                 WITH (storage = 'columnar', compression = 'dictionary');"
+                .to_string()]
+        );
+        let e = statements(&mut s, "e")?;
+        assert_eq!(
+            e,
+            vec![
+                "CREATE EXTERNAL TABLE e (a INTEGER, b STRING) LOCATION 'data.csv' \
+                 WITH (header = 'true', delimiter = ';');"
+                    .to_string()
+            ]
+        );
+        // 浮点数默认值输出之后重新解析仍然是浮点数
+        s.execute("create table f (id int, x float default 1e20, y float default 2.0);")?;
+        let f = statements(&mut s, "f")?;
+        assert_eq!(
+            f,
+            vec![
+                "CREATE TABLE f (id INTEGER, x FLOAT DEFAULT 1e20, y FLOAT DEFAULT 2.0);"
+                    .to_string()
+            ]
+        );
+
+        // 生成的语句在另一个数据库中执行，得到相同的表结构
+        let other = KVEngine::new(MemoryEngine::new());
+        let mut t = other.session()?;
+        for sql in user.iter().chain(&c).chain(&e).chain(&f) {
+            t.execute(sql)?;
+        }
+        for table in ["\"User\"", "c", "e", "f"] {
+            assert_eq!(statements(&mut t, table)?, statements(&mut s, table)?);
+            let describe = format!("describe {};", table);
+            assert_eq!(rows(&mut t, &describe)?, rows(&mut s, &describe)?);
+        }
+        let txn = kvengine.begin()?;
+        let copy = other.begin()?;
+        for table in ["User", "c", "e", "f"] {
+            assert_eq!(
+                copy.must_get_table(table.into())?,
+                txn.must_get_table(table.into())?
+            );
+        }
+
+        assert!(s.execute("describe missing;").is_err());
+        assert!(s.execute("show create table missing;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_transactions() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s1 = kvengine.session()?;
+        let mut s2 = kvengine.session()?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let ints = |values: &[i64]| {
+            values
+                .iter()
+                .map(|&i| vec![Value::Integer(i)])
+                .collect::<Vec<_>>()
+        };
+        s1.execute("set global query_cache_size = 10;")?;
+        s1.execute("create table t (a int, b int);")?;
+        s1.execute("insert into t values (1, 10);")?;
+        assert_eq!(rows(&mut s2, "select a from t;")?, ints(&[1]));
+
+        // 事务中的语句能看到之前的写入，提交之前其他 session 看不到
+        let version = match s1.execute("begin;")? {
+            ResultSet::Begin { version } => version,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert!(s1.in_transaction());
+        s1.execute("insert into t values (2, 20);")?;
+        s1.execute("update t set b = b + 1 where a = 1;")?;
+        assert_eq!(rows(&mut s1, "select a from t;")?, ints(&[1, 2]));
+        assert_eq!(rows(&mut s1, "select b from t;")?, ints(&[11, 20]));
+        assert_eq!(rows(&mut s2, "select a from t;")?, ints(&[1]));
+        assert_eq!(rows(&mut s2, "select b from t;")?, ints(&[10]));
+        // 同一行不能被两个事务同时修改
+        assert_eq!(
+            s2.execute("update t set b = 0 where a = 1;"),
+            Err(Error::WriteConflict)
+        );
+        assert!(s1.execute("begin;").is_err());
+        assert_eq!(s1.execute("commit;")?, ResultSet::Commit { version });
+        assert!(!s1.in_transaction());
+        assert_eq!(rows(&mut s2, "select b from t;")?, ints(&[11, 20]));
+
+        // ROLLBACK 撤销事务中所有的写入，包括 DDL
+        s1.execute("begin transaction;")?;
+        s1.execute("delete from t;")?;
+        s1.execute("create table t2 (a int);")?;
+        s1.execute("insert into t2 values (1);")?;
+        assert_eq!(rows(&mut s1, "select a from t2;")?, ints(&[1]));
+        assert!(matches!(
+            s1.execute("rollback;")?,
+            ResultSet::Rollback { .. }
+        ));
+        assert_eq!(rows(&mut s1, "select a from t;")?, ints(&[1, 2]));
+        assert!(s1.execute("select a from t2;").is_err());
+
+        // 语句出错时整个事务回滚，之后只能执行 COMMIT 或者 ROLLBACK
+        s1.execute("begin;")?;
+        s1.execute("insert into t values (3, 30);")?;
+        assert!(s1.execute("insert into t values ('x', 1);").is_err());
+        assert!(s1.in_transaction());
+        assert!(s1
+            .execute("select a from t;")
+            .is_err_and(|e| e.to_string().contains("is aborted")));
+        assert!(s1.execute("set statement_timeout = 10;").is_err());
+        assert!(s1
+            .execute("commit;")
+            .is_err_and(|e| e.to_string().contains("rolled back")));
+        assert!(!s1.in_transaction());
+        assert_eq!(rows(&mut s1, "select a from t;")?, ints(&[1, 2]));
+
+        s1.execute("begin;")?;
+        assert!(s1.execute("select * from other.t;").is_err());
+        assert!(matches!(
+            s1.execute("rollback;")?,
+            ResultSet::Rollback { .. }
+        ));
+        assert!(s1.execute("commit;").is_err());
+        assert!(s1.execute("rollback;").is_err());
+
+        // session 结束时回滚没有提交的事务，不会阻塞其他事务
+        let mut s3 = kvengine.session()?;
+        s3.execute("begin;")?;
+        s3.execute("update t set b = 0 where a = 2;")?;
+        drop(s3);
+        s2.execute("update t set b = 21 where a = 2;")?;
+        assert_eq!(rows(&mut s1, "select b from t;")?, ints(&[11, 21]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_commit_rollback() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (a int);")?;
+        let scan = |kvengine: &KVEngine<MemoryEngine>| -> Result<Vec<Row>> {
+            let txn = kvengine.begin()?;
+            let rows = txn.scan_table("t".into())?;
+            txn.commit()?;
+            Ok(rows)
+        };
+
+        // 回滚的写入对之后的事务不可见
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t".into(), vec![Value::Integer(1)])?;
+        assert_eq!(txn.scan_table("t".into())?, vec![vec![Value::Integer(1)]]);
+        txn.rollback()?;
+        assert_eq!(scan(&kvengine)?, Vec::<Row>::new());
+
+        s.execute("begin;")?;
+        s.execute("insert into t values (2);")?;
+        s.execute("rollback;")?;
+        assert_eq!(scan(&kvengine)?, Vec::<Row>::new());
+
+        // 提交之后才对其他事务可见
+        let mut txn = kvengine.begin()?;
+        txn.create_row("t".into(), vec![Value::Integer(3)])?;
+        assert_eq!(scan(&kvengine)?, Vec::<Row>::new());
+        txn.commit()?;
+        assert_eq!(scan(&kvengine)?, vec![vec![Value::Integer(3)]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_cache_volatile_functions() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("set global query_cache_size = 10;")?;
+        s.execute("create table t (id int, at int);")?;
+        s.execute("insert into t values (1, 0);")?;
+        let cache = kvengine.query_cache();
+
+        // 每次执行都重新计算 gen_uuid()，结果不写入缓存
+        for sql in [
+            "select id, gen_uuid() from t;",
+            "select id from t where gen_uuid() is not null;",
+            "select id from t order by gen_uuid();",
+            "select count(gen_uuid()) from t group by id having count(gen_uuid()) > 0;",
+        ] {
+            let first = s.execute(sql)?;
+            assert!(cache.get(sql)?.is_none(), "{}", sql);
+            if sql.starts_with("select id, gen_uuid()") {
+                assert_ne!(s.execute(sql)?, first);
+            }
+        }
+
+        // now() 在每次执行时计算，不会返回缓存中的时间
+        let sql = "select id, now() from t;";
+        s.execute(sql)?;
+        assert!(cache.get(sql)?.is_none());
+        let sql = "select id from t where at < now();";
+        s.execute(sql)?;
+        assert!(cache.get(sql)?.is_none());
+
+        // 没有调用这些函数的查询仍然使用缓存
+        let sql = "select id, upper('a') from t;";
+        let result = s.execute(sql)?;
+        assert_eq!(cache.get(sql)?, Some(result));
+        Ok(())
+    }
+
+    #[test]
+    fn test_primary_key_constraint() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        s.execute("create table t (id int, name text);")?;
+        s.execute("create table c (id int, name text) with (storage = 'columnar');")?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let row = |id: Value, name: &str| vec![id, Value::String(name.to_string())];
+
+        for table in ["t", "c"] {
+            s.execute(&format!("insert into {} values (1, 'a');", table))?;
+            // 主键重复时报错，不会覆盖原来的行
+            assert_eq!(
+                s.execute(&format!("insert into {} values (1, 'b');", table)),
+                Err(Error::ConstraintViolation(format!(
+                    "duplicate primary key Integer(1) in table {}",
+                    table
+                )))
+            );
+            assert_eq!(
+                s.execute(&format!("insert into {} values (null, 'b');", table)),
+                Err(Error::ConstraintViolation(format!(
+                    "primary key id of table {} cannot be null",
+                    table
+                )))
+            );
+            assert!(s
+                .execute(&format!("update {} set id = null where id = 1;", table))
+                .is_err());
+
+            // 批量写入时和表中以及同一批中的行比较，失败时不写入任何行
+            let mut txn = kvengine.begin()?;
+            for batch in [
+                vec![row(Value::Integer(2), "b"), row(Value::Integer(1), "c")],
+                vec![row(Value::Integer(2), "b"), row(Value::Integer(2), "c")],
+                vec![row(Value::Integer(2), "b"), row(Value::Null, "c")],
+            ] {
+                assert!(matches!(
+                    txn.insert_rows(table.into(), batch),
+                    Err(Error::ConstraintViolation(_))
+                ));
+            }
+            assert!(matches!(
+                txn.create_row(table.into(), row(Value::Integer(1), "c")),
+                Err(Error::ConstraintViolation(_))
+            ));
+            txn.insert_rows(table.into(), vec![row(Value::Integer(2), "b")])?;
+            // 同一个事务中写入的行也会冲突
+            assert!(matches!(
+                txn.create_row(table.into(), row(Value::Integer(2), "c")),
+                Err(Error::ConstraintViolation(_))
+            ));
+            txn.commit()?;
+
+            assert_eq!(
+                rows(&mut s, &format!("select * from {} order by id;", table))?,
+                vec![row(Value::Integer(1), "a"), row(Value::Integer(2), "b")]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_spatial_index() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s = kvengine.session()?;
+        let rows = |s: &mut Session<_>, sql: &str| match s.execute(sql)? {
+            ResultSet::Scan { rows, .. } => Ok(rows),
+            result => Err(Error::Internal(format!("unexpected result {:?}", result))),
+        };
+        let plan = |s: &mut Session<_>, sql: &str| -> Result<String> {
+            Ok(rows(s, &format!("explain {}", sql))?
+                .into_iter()
+                .map(|row| match &row[0] {
+                    Value::String(line) => line.clone(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"))
+        };
+        s.execute("create table t (id int, loc point, note text);")?;
+        s.execute("create table p (id int, loc point, note text);")?;
+        let points = "(1, st_point(0, 0), 'a'), (2, st_point(3, 4), 'b'), \
+            (3, st_point(-1.5, 0.5), 'c'), (4, st_point(10, 10), 'd'), \
+            (5, st_point(2.5, -2.5), 'e'), (6, null, 'f'), (7, st_point(-20, 3), 'g')";
+        s.execute(&format!("insert into t values {};", points))?;
+        s.execute(&format!("insert into p values {};", points))?;
+        s.execute("create spatial index t_loc on t (loc) with (grid_size = 2);")?;
+
+        // 和没有索引的表的结果相同
+        let queries = [
+            "select id from {} where st_dwithin(loc, st_point(0, 0), 3) order by id;",
+            "select id from {} where st_dwithin(st_point(1, 1), loc, 5) order by id;",
+            "select id from {} where st_within(loc, -2, -3, 3, 4) order by id;",
+            "select id from {} where st_within(loc, 0, 0, 0, 0) and note = 'a' order by id;",
+            "select id from {} where st_dwithin(loc, st_point(0, 0), -1) order by id;",
+            "select id from {} where st_within(loc, 5, 5, -5, -5) order by id;",
+        ];
+        for sql in queries {
+            let expect = rows(&mut s, &sql.replace("{}", "p"))?;
+            let sql = sql.replace("{}", "t");
+            assert_eq!(rows(&mut s, &sql)?, expect, "{}", sql);
+            assert!(plan(&mut s, &sql)?.contains("SpatialScan (table: t, index: t_loc"));
+        }
+        assert_eq!(
+            rows(&mut s, queries[0].replace("{}", "t").as_str())?,
+            vec![vec![Value::Integer(1)], vec![Value::Integer(3)]]
+        );
+        // 空间索引的每次访问都是扫描，范围是空的时候也会计数
+        assert_eq!(
+            rows(
+                &mut s,
+                "select scans, seeks from sys_indexes where name = 't_loc';"
+            )?,
+            vec![vec![Value::Integer(7), Value::Integer(0)]]
+        );
+        assert!(plan(
+            &mut s,
+            "select id from t where st_dwithin(loc, st_point(1, 2), 3);"
+        )?
+        .contains("SpatialScan (table: t, index: t_loc, area: (-2 -1, 4 5)"));
+        // 参数不是常量或者范围太大时扫描整个表
+        for sql in [
+            "select id from t where st_dwithin(loc, loc, 1);",
+            "select id from t where st_dwithin(loc, st_point(0, 0), id);",
+            "select id from t where st_within(loc, -1e9, 0, 1e9, 1);",
+            "select id from t where st_distance(loc, st_point(0, 0)) < 1;",
+        ] {
+            assert!(!plan(&mut s, sql)?.contains("SpatialScan"), "{}", sql);
+        }
+
+        // 修改和删除行之后维护索引项
+        s.execute("update t set loc = st_point(0.5, 0.5) where id = 4;")?;
+        s.execute("update t set loc = null where id = 1;")?;
+        s.execute("update t set loc = st_point(1, 1) where id = 6;")?;
+        s.execute("delete from t where id = 3;")?;
+        assert_eq!(
+            rows(
+                &mut s,
+                "select id from t where st_dwithin(loc, st_point(0, 0), 3) order by id;"
+            )?,
+            vec![vec![Value::Integer(4)], vec![Value::Integer(6)]]
+        );
+        assert_eq!(rows(&mut s, "check table t;")?, Vec::<Row>::new());
+        assert_eq!(
+            rows(&mut s, "show create table t;")?[1],
+            vec![Value::String(
+                "CREATE SPATIAL INDEX t_loc ON t (loc) WITH (grid_size = 2.0);".to_string()
+            )]
+        );
+        // 同一列上可以再建普通索引，普通索引的条件优先
+        s.execute("create index t_note on t (note);")?;
+        assert!(plan(
+            &mut s,
+            "select id from t where note = 'a' and st_within(loc, 0, 0, 1, 1);"
+        )?
+        .contains("IndexScan (table: t, index: t_note"));
+        assert!(s
+            .execute("create spatial index t_loc2 on t (loc);")
+            .is_err());
+
+        for sql in [
+            "create spatial index x on t (note);",
+            "create spatial index x on t (loc, note);",
+            "create spatial index x on p (loc) with (grid_size = 0);",
+            "create spatial index x on p (loc) with (grid_size = -1.5);",
+            "create spatial index x on p (loc) with (grid_size = 'a');",
+            "create spatial index x on p (loc) with (cells = 2);",
+            "create index x on p (loc) with (grid_size = 2);",
+        ] {
+            assert!(s.execute(sql).is_err(), "{}", sql);
+        }
+        // 默认的网格边长是 1
+        s.execute("create spatial index p_loc on p (loc);")?;
+        assert!(plan(&mut s, queries[2].replace("{}", "p").as_str())?
+            .contains("SpatialScan (table: p, index: p_loc, area: (-2 -3, 3 4)"));
+        assert_eq!(rows(&mut s, "check table p;")?, Vec::<Row>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_concurrent_writers() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s1 = kvengine.session()?;
+        let mut s2 = kvengine.session()?;
+        s1.execute("create table t (id int, email text unique, code int unique);")?;
+
+        // 两个事务写入相同的唯一值时写同一个索引项，后写入的事务冲突
+        s1.execute("begin;")?;
+        s2.execute("begin;")?;
+        s1.execute("insert into t values (1, 'x', 1);")?;
+        assert_eq!(
+            s2.execute("insert into t values (2, 'x', 2);"),
+            Err(Error::WriteConflict)
+        );
+        s2.execute("rollback;")?;
+        s2.execute("begin;")?;
+        assert_eq!(
+            s2.execute("insert into t values (3, 'y', 1);"),
+            Err(Error::WriteConflict)
+        );
+        s2.execute("rollback;")?;
+        s1.execute("commit;")?;
+
+        // 值不同或者有 NULL 的行互不影响
+        s1.execute("begin;")?;
+        s2.execute("begin;")?;
+        s1.execute("insert into t values (4, 'a', null), (5, null, 2);")?;
+        s2.execute("insert into t values (6, 'b', null), (7, null, 3);")?;
+        s1.execute("commit;")?;
+        s2.execute("commit;")?;
+
+        let result = s1.execute("check table t;")?;
+        assert_eq!(
+            result,
+            ResultSet::Scan {
+                columns: vec!["problem".to_string()],
+                rows: Vec::new()
+            }
+        );
+        assert!(matches!(
+            s1.execute("insert into t values (8, 'x', 9);"),
+            Err(Error::ConstraintViolation(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dictionary_concurrent_inserts() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        let mut s1 = kvengine.session()?;
+        let mut s2 = kvengine.session()?;
+        s1.execute("create table t (id int, name text) with (compression = 'dictionary');")?;
+        s1.execute(
+            "create table c (id int, name text) with (storage = 'columnar', compression = 'dictionary');",
+        )?;
+        s1.execute("insert into t values (1, 'a');")?;
+
+        // 两个事务写入不同的新字符串时不会冲突，编号也不会重复
+        for table in ["t", "c"] {
+            s1.execute("begin;")?;
+            s2.execute("begin;")?;
+            s1.execute(&format!("insert into {} values (2, 'x'), (3, 'y');", table))?;
+            s2.execute(&format!("insert into {} values (4, 'z'), (5, 'a');", table))?;
+            s1.execute("commit;")?;
+            s2.execute("commit;")?;
+        }
+        for table in ["t", "c"] {
+            match s1.execute(&format!("select name from {} where id > 1;", table))? {
+                ResultSet::Scan { rows, .. } => assert_eq!(
+                    rows,
+                    ["x", "y", "z", "a"]
+                        .iter()
+                        .map(|s| vec![Value::String(s.to_string())])
+                        .collect::<Vec<_>>()
+                ),
+                _ => unreachable!(),
+            }
+            match s1.execute(&format!("check table {};", table))? {
+                ResultSet::Scan { rows, .. } => assert!(rows.is_empty()),
+                _ => unreachable!(),
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,203 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    error::{Error, Result},
+    storage::mvcc::Instant,
+};
+
+// 等待锁时检查语句是否被 KILL 的间隔
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+// 应用自己使用的命名锁，由引擎管理，和表中的数据无关
+// 锁属于 session，同一个 session 可以重复获取同一个锁，需要释放相同的次数
+// 事务级的获取在事务结束时释放，session 结束时释放它持有的所有锁
+#[derive(Debug, Default)]
+pub struct AdvisoryLocks {
+    // 锁名 -> 持有锁的 session 和获取的次数
+    held: Mutex<HashMap<String, Holder>>,
+    // 锁被释放时通知等待的 session
+    released: Condvar,
+}
+
+#[derive(Debug)]
+struct Holder {
+    session: u64,
+    // session 级的获取次数，通过 release 释放
+    count: usize,
+    // 事务级的获取次数，通过 release_transaction 释放
+    transaction: usize,
+}
+
+impl AdvisoryLocks {
+    // 获取锁，timeout 为 None 时一直等待，超时返回 false
+    // transaction 为 true 时是事务级的获取，等待期间语句被 KILL 时返回 Cancelled
+    pub fn acquire(
+        &self,
+        session: u64,
+        name: &str,
+        timeout: Option<Duration>,
+        transaction: bool,
+        cancelled: &AtomicBool,
+    ) -> Result<bool> {
+        let mut deadline = None;
+        let mut held = self.held.lock()?;
+        loop {
+            let holder = held.entry(name.to_string()).or_insert(Holder {
+                session,
+                count: 0,
+                transaction: 0,
+            });
+            if holder.session == session {
+                match transaction {
+                    true => holder.transaction += 1,
+                    false => holder.count += 1,
+                }
+                return Ok(true);
+            }
+            // wasm32 下只有一个线程，等待期间不会有其他 session 释放锁
+            if cfg!(target_arch = "wasm32") {
+                return Ok(false);
+            }
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(Error::Cancelled);
+            }
+            let deadline = *deadline.get_or_insert_with(|| timeout.map(|t| Instant::now() + t));
+            let wait = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(false);
+                    }
+                    (deadline - now).min(CANCEL_CHECK_INTERVAL)
+                }
+                None => CANCEL_CHECK_INTERVAL,
+            };
+            held = self.released.wait_timeout(held, wait)?.0;
+        }
+    }
+
+    // 释放一次 session 级的获取，返回 None 表示锁没有被持有
+    // Some(false) 表示锁被其他 session 持有，或者当前 session 只在事务中获取了它
+    pub fn release(&self, session: u64, name: &str) -> Result<Option<bool>> {
+        let mut held = self.held.lock()?;
+        let released = match held.get_mut(name) {
+            None => return Ok(None),
+            Some(holder) if holder.session != session || holder.count == 0 => {
+                return Ok(Some(false))
+            }
+            Some(holder) => {
+                holder.count -= 1;
+                holder.count == 0 && holder.transaction == 0
+            }
+        };
+        if released {
+            held.remove(name);
+            self.released.notify_all();
+        }
+        Ok(Some(true))
+    }
+
+    // 事务结束时释放 session 在事务中获取的锁，返回释放的次数
+    pub fn release_transaction(&self, session: u64) -> Result<usize> {
+        self.release_where(session, |holder| std::mem::take(&mut holder.transaction))
+    }
+
+    // 释放 session 持有的所有锁，包括事务级的获取，返回释放的次数
+    pub fn release_all(&self, session: u64) -> Result<usize> {
+        self.release_where(session, |holder| {
+            std::mem::take(&mut holder.count) + std::mem::take(&mut holder.transaction)
+        })
+    }
+
+    // 对 session 持有的每个锁调用 release 减少获取次数，返回释放的总次数
+    fn release_where(&self, session: u64, release: impl Fn(&mut Holder) -> usize) -> Result<usize> {
+        let mut held = self.held.lock()?;
+        let mut released = 0;
+        held.retain(|_, holder| {
+            if holder.session == session {
+                released += release(holder);
+            }
+            holder.count + holder.transaction > 0
+        });
+        if released > 0 {
+            self.released.notify_all();
+        }
+        Ok(released)
+    }
+
+    // 锁是否没有被任何 session 持有
+    pub fn is_free(&self, name: &str) -> Result<bool> {
+        Ok(!self.held.lock()?.contains_key(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicBool, Arc},
+        thread,
+        time::Duration,
+    };
+
+    use crate::error::{Error, Result};
+
+    use super::AdvisoryLocks;
+
+    #[test]
+    fn test_advisory_locks() -> Result<()> {
+        let locks = Arc::new(AdvisoryLocks::default());
+        let cancelled = AtomicBool::new(false);
+        let timeout = Some(Duration::from_millis(20));
+
+        // 同一个 session 可以重复获取
+        assert!(locks.acquire(1, "a", timeout, false, &cancelled)?);
+        assert!(locks.acquire(1, "a", timeout, false, &cancelled)?);
+        assert!(!locks.acquire(2, "a", timeout, false, &cancelled)?);
+        assert!(!locks.is_free("a")?);
+        assert_eq!(locks.release(2, "a")?, Some(false));
+        assert_eq!(locks.release(1, "a")?, Some(true));
+        assert!(!locks.acquire(2, "a", timeout, false, &cancelled)?);
+        assert_eq!(locks.release(1, "a")?, Some(true));
+        assert!(locks.is_free("a")?);
+        assert_eq!(locks.release(1, "a")?, None);
+
+        // 锁被释放之后等待的 session 获取到锁
+        assert!(locks.acquire(1, "b", timeout, false, &cancelled)?);
+        assert!(locks.acquire(1, "c", timeout, false, &cancelled)?);
+        let waiter = {
+            let locks = locks.clone();
+            thread::spawn(move || locks.acquire(2, "b", None, false, &AtomicBool::new(false)))
+        };
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(locks.release_all(1)?, 2);
+        assert!(waiter.join().unwrap()?);
+        assert!(locks.is_free("c")?);
+
+        // 事务级的获取在事务结束时释放，不能通过 release 释放
+        assert!(locks.acquire(1, "d", timeout, true, &cancelled)?);
+        assert!(locks.acquire(1, "d", timeout, false, &cancelled)?);
+        assert!(locks.acquire(1, "e", timeout, true, &cancelled)?);
+        assert_eq!(locks.release(1, "e")?, Some(false));
+        assert_eq!(locks.release_transaction(1)?, 2);
+        assert!(locks.is_free("e")?);
+        assert!(!locks.acquire(2, "d", timeout, true, &cancelled)?);
+        assert_eq!(locks.release(1, "d")?, Some(true));
+        assert!(locks.is_free("d")?);
+        assert_eq!(locks.release_transaction(1)?, 0);
+
+        // 等待期间被取消
+        cancelled.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(
+            locks.acquire(1, "b", None, false, &cancelled),
+            Err(Error::Cancelled)
+        );
+        Ok(())
+    }
+}
@@ -1,18 +1,71 @@
-use crate::error::{Error, Result};
+use std::{
+    collections::HashMap,
+    ops::Bound,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
-use super::{executor::ResultSet, parser::Parser, plan::Plan, schema::Table, types::Row};
+use crate::{
+    error::{Error, Result},
+    storage::mvcc::{BackupOptions, BackupProgress, Instant, MvccOptions},
+};
 
-mod kv;
+use cache::QueryCache;
+use lock::AdvisoryLocks;
+
+use super::{
+    executor::ResultSet,
+    function::{self, TableFunction, LOCK_FUNCTIONS},
+    ident::Ident,
+    parser::{ast, Parser},
+    plan::Plan,
+    schema::{Column, Index, Table, View},
+    types::{FromRow, Row, Value},
+};
+
+pub mod cache;
+mod codec;
+pub mod csv;
+pub mod kv;
+pub mod lock;
 
 // 抽象的 SQL 引擎层定义，目前只有一个 KVEngine
 pub trait Engine: Clone {
-    type Transaction: Transaction;
+    type Transaction: Transaction + 'static;
 
     fn begin(&self) -> Result<Self::Transaction>;
 
+    // 开启只读的历史事务，查询 version 时刻的数据
+    fn begin_as_of(&self, version: u64) -> Result<Self::Transaction>;
+
+    // 这个引擎上所有的 session，用于 KILL 其他 session 的语句
+    fn sessions(&self) -> Arc<SessionRegistry>;
+
+    // 存储引擎的全局配置，SET GLOBAL 修改之后对新开启的事务生效
+    fn options(&self) -> Result<MvccOptions>;
+    fn set_options(&self, options: MvccOptions) -> Result<()>;
+
+    // 查询结果缓存，大小由 SET GLOBAL query_cache_size 设置
+    fn query_cache(&self) -> Arc<QueryCache>;
+
+    // 使用相同的存储引擎打开 path 中的另一个数据库，ATTACH DATABASE 时使用
+    fn open(&self, path: &str) -> Result<Self>;
+
     fn session(&self) -> Result<Session<Self>> {
+        let registry = self.sessions();
+        let (id, cancelled) = registry.register()?;
+        let variables = *registry.defaults.lock()?;
         Ok(Session {
             engine: self.clone(),
+            id,
+            registry,
+            cancelled,
+            variables,
+            attached: HashMap::new(),
+            transaction: None,
         })
     }
 }
@@ -20,22 +73,108 @@ pub trait Engine: Clone {
 // 抽象的事务信息，包含了 DDL 和 DML 操作
 // 底层可以接入普通的 KV 存储引擎，也可以接入分布式存储引擎
 pub trait Transaction {
+    // 事务的版本号
+    fn version(&self) -> u64;
     // 提交事务
     fn commit(&self) -> Result<()>;
     // 回滚事务
     fn rollback(&self) -> Result<()>;
+    // 强制中止另一个活跃的事务
+    fn abort_transaction(&self, version: u64) -> Result<()>;
+    // 设置当前语句的中断检查
+    fn set_interrupt(&mut self, _interrupt: Interrupt) {}
+    // 检查当前语句是否被取消或者超时，执行器在处理每一批数据之前调用
+    fn check_interrupt(&self) -> Result<()> {
+        Ok(())
+    }
+    // 记录执行器生成的行占用的内存，超过限制时报错
+    fn allocate_rows(&self, _rows: &[Row]) -> Result<()> {
+        Ok(())
+    }
+    // 将当前事务能看到的数据备份到 path，不阻塞其他事务
+    fn backup(&self, path: &str, options: BackupOptions) -> Result<BackupProgress>;
+    // 检查点，持久化数据并清理失效的日志
+    fn checkpoint(&self) -> Result<()>;
+    // 删除所有表中已经过期的行，返回删除的行数
+    fn purge_expired(&mut self) -> Result<usize>;
 
     // 创建行
-    fn create_row(&mut self, table_name: String, row: Row) -> Result<()>;
+    fn create_row(&mut self, table_name: Ident, row: Row) -> Result<()>;
+    // 批量创建行，用于导入大量数据，存储引擎可以合并成一次写入
+    fn insert_rows(&mut self, table_name: Ident, rows: Vec<Row>) -> Result<()> {
+        for row in rows {
+            self.create_row(table_name.clone(), row)?;
+        }
+        Ok(())
+    }
+    // 修改主键是 id 的行，新的行可以修改主键
+    fn update_row(&mut self, table_name: Ident, id: &Value, row: Row) -> Result<()>;
+    // 删除主键是 id 的行
+    fn delete_row(&mut self, table_name: Ident, id: &Value) -> Result<()>;
     // 扫描表
-    fn scan_table(&self, table_name: String) -> Result<Vec<Row>>;
+    fn scan_table(&self, table_name: Ident) -> Result<Vec<Row>>;
+    // 只扫描表中的部分列，没有读取的列填充为 NULL
+    fn scan_table_columns(&self, table_name: Ident, _columns: &[usize]) -> Result<Vec<Row>> {
+        self.scan_table(table_name)
+    }
+    // 使用二级索引读取行，索引的前几列等于 prefix 中的值，之后的一列在 range 范围中
+    // 返回的行按照索引的顺序排列
+    fn scan_index(
+        &self,
+        table_name: Ident,
+        index: &Ident,
+        prefix: &[Value],
+        range: (Bound<Value>, Bound<Value>),
+    ) -> Result<Vec<Row>>;
+    // 使用空间索引读取覆盖 area [xmin, ymin, xmax, ymax] 的网格中的行
+    // 返回的行中的点不一定在范围内，需要再按照条件过滤
+    fn scan_spatial_index(
+        &self,
+        table_name: Ident,
+        index: &Ident,
+        area: [f64; 4],
+    ) -> Result<Vec<Row>>;
 
     // DDL 相关操作
     fn create_table(&mut self, table: Table) -> Result<()>;
+    // 在表的最后添加一列，已有的行使用列的默认值填充
+    fn add_column(&mut self, table_name: Ident, column: Column) -> Result<()>;
+    // 修改表名，表的数据一起移动到新的表名下
+    fn rename_table(&mut self, table_name: Ident, new_name: Ident) -> Result<()>;
+    // 修改列名，列的数据不变
+    fn rename_column(&mut self, table_name: Ident, column: Ident, new_name: Ident) -> Result<()>;
+    // 创建视图，视图和表的名字不能相同
+    fn create_view(&mut self, view: View) -> Result<()>;
+    // 删除视图，视图不存在时报错
+    fn drop_view(&mut self, name: Ident) -> Result<()>;
+    // 获取视图信息，不存在时返回 None
+    fn get_view(&self, name: Ident) -> Result<Option<View>>;
+    // 创建二级索引，使用表中已有的行生成索引项，返回索引项的数量
+    fn create_index(&mut self, table_name: Ident, index: Index) -> Result<usize>;
+    // 删除二级索引和所有的索引项，索引不存在时报错
+    fn drop_index(&mut self, table_name: Ident, name: Ident) -> Result<()>;
+    // 收集表的统计信息，返回表中的行数
+    fn analyze_table(&mut self, table_name: Ident) -> Result<usize>;
+    // 检查表的数据是否完整，返回发现的所有问题，没有问题时返回空
+    fn check_table(&self, table_name: Ident) -> Result<Vec<String>>;
+    // 根据行数据重建表的索引，返回表中的行数
+    fn reindex_table(&mut self, table_name: Ident) -> Result<usize>;
+    // 最近一次 ANALYZE 时表中的行数，用于估算执行计划的代价，没有统计信息时返回 None
+    fn estimated_rows(&self, _table_name: Ident) -> Result<Option<usize>> {
+        Ok(None)
+    }
+    // 查询表的结果是否可以缓存，结果只会被写入这个表的事务改变时才可以缓存
+    fn is_cacheable(&self, _table_name: Ident) -> Result<bool> {
+        Ok(false)
+    }
+    // 获取 FROM 中使用的表函数，不存在时返回 None
+    fn table_function(&self, name: &str) -> Result<Option<Arc<dyn TableFunction>>> {
+        Ok(function::builtin_table_function(name))
+    }
     // 获取表信息
-    fn get_table(&self, table_name: String) -> Result<Option<Table>>;
+    fn get_table(&self, table_name: Ident) -> Result<Option<Table>>;
     // 获取表信息，不存在则报错
-    fn must_get_table(&self, table_name: String) -> Result<Table> {
+    fn must_get_table(&self, table_name: Ident) -> Result<Table> {
         self.get_table(table_name.clone())?
             .ok_or(Error::Internal(format!(
                 "table {} does not exist",
@@ -44,29 +183,698 @@ pub trait Transaction {
     }
 }
 
+// 表的行变更，row 为 None 表示这一行被删除
+#[derive(Debug, PartialEq)]
+pub struct RowChange {
+    // 提交变更的事务版本号
+    pub version: u64,
+    // 行的主键
+    pub key: Value,
+    pub row: Option<Row>,
+}
+
+// 单条语句的资源限制，None 表示不限制
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ResourceLimits {
+    // 从存储中读取的最大行数
+    pub max_rows_examined: Option<u64>,
+    // 返回的最大行数
+    pub max_result_rows: Option<u64>,
+    // 执行器生成的行占用的最大内存，单位是字节
+    pub max_memory: Option<u64>,
+}
+
+// 语句的中断检查，语句被 KILL、超时或者超过资源限制之后，执行器会在处理下一批数据之前停止
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+    limits: ResourceLimits,
+    examined: Arc<AtomicU64>,
+    memory: Arc<AtomicU64>,
+}
+
+impl Interrupt {
+    pub fn new(
+        cancelled: Arc<AtomicBool>,
+        timeout: Option<Duration>,
+        limits: ResourceLimits,
+    ) -> Self {
+        Self {
+            cancelled,
+            deadline: timeout.map(|t| Instant::now() + t),
+            limits,
+            ..Default::default()
+        }
+    }
+
+    // 记录从存储中读取的行，读取的行同样需要占用内存
+    pub fn examine(&self, rows: &[Row]) -> Result<()> {
+        let examined = self
+            .examined
+            .fetch_add(rows.len() as u64, Ordering::Relaxed)
+            + rows.len() as u64;
+        match self.limits.max_rows_examined {
+            Some(max) if examined > max => Err(Error::ResourceLimit(format!(
+                "statement examined more than {} rows",
+                max
+            ))),
+            _ => self.allocate(rows),
+        }
+    }
+
+    // 记录执行器生成的行占用的内存，只增加不减少，是内存占用的上限估计
+    pub fn allocate(&self, rows: &[Row]) -> Result<()> {
+        let size = rows
+            .iter()
+            .flatten()
+            .map(|v| {
+                std::mem::size_of::<Value>()
+                    + match v {
+                        Value::String(s) => s.len(),
+                        _ => 0,
+                    }
+            })
+            .sum::<usize>() as u64;
+        let memory = self.memory.fetch_add(size, Ordering::Relaxed) + size;
+        match self.limits.max_memory {
+            Some(max) if memory > max => Err(Error::ResourceLimit(format!(
+                "statement used more than {} bytes of memory",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+}
+
+// session 变量，全局的默认值由 SET GLOBAL 修改
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SessionVariables {
+    // 语句的最长执行时间，None 表示不限制
+    pub statement_timeout: Option<Duration>,
+    pub limits: ResourceLimits,
+}
+
+// 引擎上所有 session 的取消标记，新建 session 时使用的变量默认值，以及 session 持有的命名锁
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    next_id: AtomicU64,
+    sessions: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+    defaults: Mutex<SessionVariables>,
+    locks: AdvisoryLocks,
+}
+
+impl SessionRegistry {
+    fn register(&self) -> Result<(u64, Arc<AtomicBool>)> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.sessions.lock()?.insert(id, cancelled.clone());
+        Ok((id, cancelled))
+    }
+
+    // 取消 session 正在执行的语句
+    fn kill(&self, id: u64) -> Result<()> {
+        match self.sessions.lock()?.get(&id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            None => Err(Error::Internal(format!("session {} does not exist", id))),
+        }
+    }
+}
+
+// 查询结果写入缓存时使用的 key 和查询的表
+struct CacheSlot {
+    sql: String,
+    table_name: Ident,
+    // 查询开始之前表的写入次数
+    generation: u64,
+}
+
+// 批量执行时遇到错误的处理方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnError {
+    // 停止执行后面的语句
+    Abort,
+    // 继续执行后面的语句
+    Continue,
+}
+
+// 预处理的语句，只解析一次，之后可以使用不同的参数多次执行
+// 执行计划依赖执行时的表结构和统计信息，每次执行时替换参数之后重新构建
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedStatement {
+    stmt: ast::Statement,
+    parameters: usize,
+}
+
+impl PreparedStatement {
+    // 语句中参数的数量
+    pub fn parameters(&self) -> usize {
+        self.parameters
+    }
+}
+
 // 客户端 session 定义
 pub struct Session<E: Engine> {
     engine: E,
+    id: u64,
+    registry: Arc<SessionRegistry>,
+    // 当前语句的取消标记
+    cancelled: Arc<AtomicBool>,
+    variables: SessionVariables,
+    // 附加的数据库，别名 -> 引擎
+    attached: HashMap<String, E>,
+    // BEGIN 开启的事务，没有时每条语句自动提交
+    transaction: Option<SessionTransaction<E::Transaction>>,
+}
+
+// session 中 BEGIN 开启的事务
+enum SessionTransaction<T> {
+    Active(T),
+    // 事务中的语句执行出错，事务已经回滚，之后的语句报错，直到 COMMIT 或者 ROLLBACK，保存事务的版本号
+    Aborted(u64),
+}
+
+impl<E: Engine> Drop for Session<E> {
+    fn drop(&mut self) {
+        if let Some(SessionTransaction::Active(txn)) = self.transaction.take() {
+            let _ = txn.rollback();
+        }
+        if let Ok(mut sessions) = self.registry.sessions.lock() {
+            sessions.remove(&self.id);
+        }
+        let _ = self.registry.locks.release_all(self.id);
+    }
 }
 
 impl<E: Engine> Session<E> {
+    // session 的编号，KILL 语句需要使用
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    // 是否在 BEGIN 开启的事务中，事务出错回滚之后直到 COMMIT 或者 ROLLBACK 也是 true
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
     // 执行客户端 SQL 语句
+    // 开启查询缓存之后，普通查询的结果以 SQL 文本作为 key 缓存，写入查询的表之后失效
+    // BEGIN 开启的事务中的查询和调用了 now()、gen_uuid() 等结果不确定的函数的查询不使用缓存
     pub fn execute(&mut self, sql: &str) -> Result<ResultSet> {
-        match Parser::new(sql).parse()? {
-            stmt => {
-                let mut txn = self.engine.begin()?;
-                // 构建 plan，执行 SQL 语句
-                match Plan::build(stmt).execute(&mut txn) {
-                    Ok(result) => {
-                        txn.commit()?;
-                        Ok(result)
-                    }
-                    Err(err) => {
-                        txn.rollback()?;
-                        Err(err)
+        let stmt = Parser::new(sql).parse()?;
+        let cache = self.engine.query_cache();
+        match &stmt {
+            // 不缓存附加数据库中的表，缓存只记录一个表的写入次数，也不缓存连接查询和子查询
+            ast::Statement::Select {
+                table_name: Some(table_name),
+                subquery: None,
+                joins,
+                filter,
+                ..
+            } if !table_name.contains('.')
+                && joins.is_empty()
+                && !filter
+                    .as_ref()
+                    .is_some_and(ast::Expression::contains_subquery)
+                && self.transaction.is_none()
+                && !stmt.is_volatile()
+                && cache.capacity()? > 0 =>
+            {
+                if let Some(result) = cache.get(sql)? {
+                    return self.check_result_rows(result);
+                }
+                // 在开启事务之前读取表的写入次数
+                let entry = CacheSlot {
+                    sql: sql.to_string(),
+                    table_name: table_name.clone(),
+                    generation: cache.generation(table_name)?,
+                };
+                self.execute_transaction(stmt, Some(entry))
+            }
+            _ => self.execute_statement(stmt),
+        }
+    }
+
+    // 在一次调用中执行多条 SQL 语句，没有 BEGIN 时每条语句使用单独的事务，按顺序返回每条语句的结果
+    // 整批语句需要先全部解析成功，on_error 决定遇到执行错误之后是否继续执行后面的语句
+    pub fn execute_batch(
+        &mut self,
+        sql: &str,
+        on_error: OnError,
+    ) -> Result<Vec<Result<ResultSet>>> {
+        let mut results = Vec::new();
+        for stmt in Parser::new(sql).parse_batch()? {
+            let result = self.execute_statement(stmt);
+            let failed = result.is_err();
+            results.push(result);
+            if failed && on_error == OnError::Abort {
+                break;
+            }
+        }
+        Ok(results)
+    }
+
+    // 解析带有参数的语句，参数使用 ? 或者 $n 表示，通过 execute_prepared 传入参数的值执行
+    pub fn prepare(&self, sql: &str) -> Result<PreparedStatement> {
+        let mut parser = Parser::new(sql);
+        let stmt = parser.parse()?;
+        Ok(PreparedStatement {
+            stmt,
+            parameters: parser.parameters(),
+        })
+    }
+
+    // 使用参数执行预处理的语句，params[0] 是第一个参数
+    // 参数的值直接替换成语法树中的常量，不会作为 SQL 文本解析
+    pub fn execute_prepared(
+        &mut self,
+        stmt: &PreparedStatement,
+        params: &[Value],
+    ) -> Result<ResultSet> {
+        if params.len() != stmt.parameters {
+            return Err(Error::Internal(format!(
+                "prepared statement expects {} parameters, got {}",
+                stmt.parameters,
+                params.len()
+            )));
+        }
+        self.execute_statement(stmt.stmt.clone().bind_parameters(params)?)
+    }
+
+    // 执行已经构造好的语法树，例如通过 Query 构造的查询
+    pub fn execute_statement(&mut self, stmt: ast::Statement) -> Result<ResultSet> {
+        // 出错的事务中只能执行 COMMIT 和 ROLLBACK
+        if let Some(SessionTransaction::Aborted(version)) = self.transaction {
+            if !matches!(stmt, ast::Statement::Commit | ast::Statement::Rollback) {
+                return Err(Error::Internal(format!(
+                    "transaction {} is aborted, statements are ignored until COMMIT or ROLLBACK",
+                    version
+                )));
+            }
+        }
+        // session 相关的语句不需要开启事务
+        match stmt {
+            ast::Statement::Begin => return self.begin(),
+            ast::Statement::Commit => return self.commit(),
+            ast::Statement::Rollback => return self.rollback(),
+            ast::Statement::Set {
+                name,
+                value,
+                global,
+            } => return self.set_variable(name, value, global),
+            ast::Statement::ShowVariables { global } => return self.show_variables(global),
+            ast::Statement::Kill { session_id } => {
+                self.registry.kill(session_id)?;
+                return Ok(ResultSet::Kill { session_id });
+            }
+            ast::Statement::Attach { path, alias } => {
+                let engine = self.engine.open(&path)?;
+                self.attach(&alias, engine)?;
+                return Ok(ResultSet::Attach { alias });
+            }
+            ast::Statement::Detach { alias } => {
+                self.detach(&alias)?;
+                return Ok(ResultSet::Detach { alias });
+            }
+            _ => {}
+        }
+        let result = self
+            .resolve_lock_functions(stmt)
+            .and_then(|stmt| self.execute_transaction(stmt, None));
+        // 没有 BEGIN 时每条语句是一个事务，事务级的锁在语句结束时释放
+        if self.transaction.is_none() {
+            self.registry.locks.release_transaction(self.id)?;
+        }
+        result
+    }
+
+    // 命名锁属于 session，执行语句之前按照出现的顺序计算语句中的锁函数，结果作为常量替换到语句中
+    // 每条语句只计算一次，所以参数需要是常量
+    fn resolve_lock_functions(&self, mut stmt: ast::Statement) -> Result<ast::Statement> {
+        // 替换之后结果的列名仍然是原来的表达式，锁函数都是结果不确定的函数
+        if let ast::Statement::Select { select, .. } = &mut stmt {
+            for item in select {
+                if let ast::SelectItem::Expr(expr, alias @ None) = item {
+                    if expr.is_volatile() {
+                        *alias = Some(expr.to_string());
                     }
                 }
             }
         }
+        stmt.transform(&mut |expr| match expr {
+            ast::Expression::Function(name, args) if LOCK_FUNCTIONS.contains(&name.as_str()) => {
+                if !args.iter().all(ast::Expression::is_constant) {
+                    return Err(Error::Internal(format!(
+                        "arguments of function {} must be constants",
+                        name
+                    )));
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(&Vec::new()))
+                    .collect::<Result<_>>()?;
+                Ok(self.lock_function(&name, args)?.into())
+            }
+            expr => Ok(expr),
+        })
+    }
+
+    // 开启 session 的事务
+    fn begin(&mut self) -> Result<ResultSet> {
+        if let Some(SessionTransaction::Active(txn)) = &self.transaction {
+            return Err(Error::Internal(format!(
+                "transaction {} is already in progress",
+                txn.version()
+            )));
+        }
+        let txn = self.engine.begin()?;
+        let version = txn.version();
+        self.transaction = Some(SessionTransaction::Active(txn));
+        Ok(ResultSet::Begin { version })
+    }
+
+    // 提交 session 的事务，事务已经因为出错回滚时报错
+    fn commit(&mut self) -> Result<ResultSet> {
+        match self.transaction.take() {
+            Some(SessionTransaction::Active(txn)) => {
+                let version = txn.version();
+                let result = txn.commit();
+                self.registry.locks.release_transaction(self.id)?;
+                result.map(|_| ResultSet::Commit { version })
+            }
+            Some(SessionTransaction::Aborted(version)) => {
+                self.registry.locks.release_transaction(self.id)?;
+                Err(Error::Internal(format!(
+                    "transaction {} was rolled back because of an earlier error",
+                    version
+                )))
+            }
+            None => Err(Error::Internal("no transaction in progress".to_string())),
+        }
+    }
+
+    // 回滚 session 的事务
+    fn rollback(&mut self) -> Result<ResultSet> {
+        if self.transaction.is_some() {
+            self.registry.locks.release_transaction(self.id)?;
+        }
+        match self.transaction.take() {
+            Some(SessionTransaction::Active(txn)) => {
+                let version = txn.version();
+                txn.rollback()?;
+                Ok(ResultSet::Rollback { version })
+            }
+            Some(SessionTransaction::Aborted(version)) => Ok(ResultSet::Rollback { version }),
+            None => Err(Error::Internal("no transaction in progress".to_string())),
+        }
+    }
+
+    // 在 BEGIN 开启的事务中执行语句，出错时回滚整个事务
+    fn execute_in_transaction(&mut self, mut stmt: ast::Statement) -> Result<ResultSet> {
+        let Some(SessionTransaction::Active(txn)) = &mut self.transaction else {
+            return Err(Error::Internal("no transaction in progress".to_string()));
+        };
+        // 事务只属于当前数据库
+        let result = match stmt.table_name_mut() {
+            Some(name) => match name.split_once('.') {
+                Some(("main", table_name)) => {
+                    *name = table_name.into();
+                    Ok(())
+                }
+                Some((alias, _)) => Err(Error::Internal(format!(
+                    "cannot access attached database {} in a transaction",
+                    alias
+                ))),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        };
+        self.cancelled.store(false, Ordering::Relaxed);
+        txn.set_interrupt(Interrupt::new(
+            self.cancelled.clone(),
+            self.variables.statement_timeout,
+            self.variables.limits,
+        ));
+        let result = result
+            .and_then(|_| Plan::build(stmt, txn))
+            .and_then(|plan| plan.execute(txn))
+            .and_then(|result| self.check_result_rows(result));
+        if result.is_err() {
+            if let Some(SessionTransaction::Active(txn)) = self.transaction.take() {
+                let version = txn.version();
+                self.transaction = Some(SessionTransaction::Aborted(version));
+                txn.rollback()?;
+            }
+        }
+        result
+    }
+
+    // 在新的事务中执行语句，cache 不为空时将查询的结果写入缓存
+    // BEGIN 开启事务之后在 session 的事务中执行，AS OF 查询仍然使用单独的历史事务
+    fn execute_transaction(
+        &mut self,
+        mut stmt: ast::Statement,
+        cache: Option<CacheSlot>,
+    ) -> Result<ResultSet> {
+        let as_of = matches!(stmt, ast::Statement::Select { as_of: Some(_), .. });
+        if self.transaction.is_some() && !as_of {
+            return self.execute_in_transaction(stmt);
+        }
+        // 表名带有别名时在附加的数据库中执行
+        let engine = match stmt.table_name_mut() {
+            Some(name) => match name.split_once('.') {
+                Some((alias, table_name)) => {
+                    let engine = match alias {
+                        "main" => self.engine.clone(),
+                        alias => self.attached.get(alias).cloned().ok_or_else(|| {
+                            Error::Internal(format!("database {} is not attached", alias))
+                        })?,
+                    };
+                    *name = table_name.into();
+                    engine
+                }
+                None => self.engine.clone(),
+            },
+            None => self.engine.clone(),
+        };
+        // AS OF 查询使用只读的历史事务
+        let mut txn = match &stmt {
+            ast::Statement::Select {
+                as_of: Some(version),
+                ..
+            } => engine.begin_as_of(*version)?,
+            _ => engine.begin()?,
+        };
+        // 每条语句开始时清除之前的取消标记
+        self.cancelled.store(false, Ordering::Relaxed);
+        txn.set_interrupt(Interrupt::new(
+            self.cancelled.clone(),
+            self.variables.statement_timeout,
+            self.variables.limits,
+        ));
+        // 构建 plan，执行 SQL 语句
+        let result = Plan::build(stmt, &txn)
+            .and_then(|plan| plan.execute(&mut txn))
+            .and_then(|result| self.check_result_rows(result));
+        match result {
+            Ok(result) => {
+                let cache = match cache {
+                    Some(slot) if txn.is_cacheable(slot.table_name.clone())? => Some(slot),
+                    _ => None,
+                };
+                txn.commit()?;
+                if let Some(slot) = cache {
+                    self.engine.query_cache().insert(
+                        slot.sql,
+                        slot.table_name,
+                        slot.generation,
+                        result.clone(),
+                    )?;
+                }
+                Ok(result)
+            }
+            Err(err) => {
+                txn.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    // 以 alias 附加另一个数据库，之后可以使用 alias.table_name 访问其中的表
+    pub fn attach(&mut self, alias: &str, engine: E) -> Result<()> {
+        if alias == "main" || self.attached.contains_key(alias) {
+            return Err(Error::Internal(format!(
+                "database {} is already attached",
+                alias
+            )));
+        }
+        self.attached.insert(alias.to_string(), engine);
+        Ok(())
+    }
+
+    // 移除附加的数据库，数据库没有其他引用时会被关闭
+    pub fn detach(&mut self, alias: &str) -> Result<()> {
+        match self.attached.remove(alias) {
+            Some(_) => Ok(()),
+            None => Err(Error::Internal(format!(
+                "database {} is not attached",
+                alias
+            ))),
+        }
+    }
+
+    // 执行命名锁函数
+    // get_lock(name, timeout) 获取锁，timeout 是等待的秒数，负数表示一直等待，获取成功返回 1，超时返回 0
+    // get_xact_lock(name, timeout) 和 get_lock 相同，但是在当前事务结束时自动释放
+    // release_lock(name) 释放锁，成功返回 1，锁被其他 session 持有或者只在事务中获取时返回 0，锁没有被持有返回 NULL
+    // release_all_locks() 释放当前 session 持有的所有锁，包括事务中获取的，返回释放的次数
+    // is_free_lock(name) 锁没有被持有时返回 1，否则返回 0
+    fn lock_function(&self, name: &str, args: Vec<Value>) -> Result<Value> {
+        let locks = &self.registry.locks;
+        let flag = |b: bool| Value::Integer(b as i64);
+        Ok(match (name, &args[..]) {
+            ("get_lock" | "get_xact_lock", [Value::String(lock), Value::Integer(timeout)]) => {
+                let timeout = (*timeout >= 0).then(|| Duration::from_secs(*timeout as u64));
+                let transaction = name == "get_xact_lock";
+                self.cancelled.store(false, Ordering::Relaxed);
+                flag(locks.acquire(self.id, lock, timeout, transaction, &self.cancelled)?)
+            }
+            ("release_lock", [Value::String(lock)]) => match locks.release(self.id, lock)? {
+                Some(released) => flag(released),
+                None => Value::Null,
+            },
+            ("release_all_locks", []) => Value::Integer(locks.release_all(self.id)? as i64),
+            ("is_free_lock", [Value::String(lock)]) => flag(locks.is_free(lock)?),
+            (name, args) => {
+                return Err(Error::Internal(format!(
+                    "invalid arguments {:?} for function {}",
+                    args, name
+                )))
+            }
+        })
+    }
+
+    // 检查语句返回的行数是否超过限制
+    fn check_result_rows(&self, result: ResultSet) -> Result<ResultSet> {
+        match (&result, self.variables.limits.max_result_rows) {
+            (ResultSet::Scan { rows, .. }, Some(max)) if rows.len() as u64 > max => Err(
+                Error::ResourceLimit(format!("statement returned more than {} rows", max)),
+            ),
+            _ => Ok(result),
+        }
+    }
+
+    // 设置变量，global 为 true 时修改全局变量和新 session 的默认值
+    // 除了 prune_versions 之外的变量都是非负整数，0 表示不限制
+    fn set_variable(
+        &mut self,
+        name: String,
+        value: ast::Expression,
+        global: bool,
+    ) -> Result<ResultSet> {
+        let value = Value::from_expression(value)?;
+        let invalid = || Error::Internal(format!("invalid value {:?} for {}", value, name));
+        let limit = || match value {
+            Value::Integer(n) if n >= 0 => Ok((n > 0).then_some(n as u64)),
+            _ => Err(invalid()),
+        };
+        let millis = || limit().map(|l| l.map(Duration::from_millis));
+
+        // 持有锁，避免同时执行的 SET GLOBAL 互相覆盖
+        let mut defaults = self.registry.defaults.lock()?;
+        let mut variables = if global { *defaults } else { self.variables };
+        let mut options = self.engine.options()?;
+        let mut cache_size = None;
+        match name.as_str() {
+            // 单位是毫秒
+            "statement_timeout" => variables.statement_timeout = millis()?,
+            "max_rows_examined" => variables.limits.max_rows_examined = limit()?,
+            "max_result_rows" => variables.limits.max_result_rows = limit()?,
+            // 单位是字节
+            "max_memory" => variables.limits.max_memory = limit()?,
+            "lock_timeout" | "max_txn_age" | "prune_versions" | "query_cache_size" if !global => {
+                return Err(Error::Internal(format!(
+                    "variable {} can only be set with SET GLOBAL",
+                    name
+                )))
+            }
+            "lock_timeout" => options.lock_timeout = millis()?,
+            "max_txn_age" => options.max_txn_age = millis()?,
+            "prune_versions" => match value {
+                Value::Boolean(b) => options.prune_versions = b,
+                _ => return Err(invalid()),
+            },
+            "query_cache_size" => cache_size = Some(limit()?.unwrap_or(0) as usize),
+            name => return Err(Error::Internal(format!("unknown variable {}", name))),
+        }
+        if global {
+            *defaults = variables;
+            self.engine.set_options(options)?;
+            if let Some(size) = cache_size {
+                self.engine.query_cache().set_capacity(size)?;
+            }
+        } else {
+            self.variables = variables;
+        }
+        Ok(ResultSet::Set { name, value })
+    }
+
+    // 查看所有变量的值，按照变量名排序，global 为 true 时查看新 session 的默认值
+    fn show_variables(&self, global: bool) -> Result<ResultSet> {
+        let variables = if global {
+            *self.registry.defaults.lock()?
+        } else {
+            self.variables
+        };
+        let options = self.engine.options()?;
+        let millis = |d: Option<Duration>| Value::Integer(d.map_or(0, |d| d.as_millis() as i64));
+        let limit = |l: Option<u64>| Value::Integer(l.map_or(0, |l| l as i64));
+        let rows = [
+            ("lock_timeout", millis(options.lock_timeout)),
+            ("max_memory", limit(variables.limits.max_memory)),
+            ("max_result_rows", limit(variables.limits.max_result_rows)),
+            (
+                "max_rows_examined",
+                limit(variables.limits.max_rows_examined),
+            ),
+            ("max_txn_age", millis(options.max_txn_age)),
+            ("prune_versions", Value::Boolean(options.prune_versions)),
+            (
+                "query_cache_size",
+                Value::Integer(self.engine.query_cache().capacity()? as i64),
+            ),
+            ("statement_timeout", millis(variables.statement_timeout)),
+        ];
+        Ok(ResultSet::Scan {
+            columns: vec!["name".to_string(), "value".to_string()],
+            rows: rows
+                .into_iter()
+                .map(|(name, value)| vec![Value::String(name.to_string()), value])
+                .collect(),
+        })
+    }
+
+    // 执行查询语句，并将结果转换为指定的类型
+    pub fn query_as<T: FromRow>(&mut self, sql: &str) -> Result<Vec<T>> {
+        match self.execute(sql)? {
+            ResultSet::Scan { columns, rows } => rows
+                .into_iter()
+                .map(|row| T::from_row(&columns, row))
+                .collect(),
+            _ => Err(Error::Internal(format!("statement {} is not a query", sql))),
+        }
     }
 }
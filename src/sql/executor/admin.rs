@@ -0,0 +1,65 @@
+use crate::{error::Result, sql::engine::Transaction, storage::mvcc::BackupOptions};
+
+use super::{Executor, ResultSet};
+
+// 强制中止事务
+pub struct RollbackTransaction {
+    version: u64,
+}
+
+impl RollbackTransaction {
+    pub fn new(version: u64) -> Box<Self> {
+        Box::new(Self { version })
+    }
+}
+
+impl<T: Transaction> Executor<T> for RollbackTransaction {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.abort_transaction(self.version)?;
+        Ok(ResultSet::RollbackTransaction {
+            version: self.version,
+        })
+    }
+}
+
+// 检查点
+pub struct Checkpoint;
+
+impl Checkpoint {
+    pub fn new() -> Box<Self> {
+        Box::new(Self)
+    }
+}
+
+impl<T: Transaction> Executor<T> for Checkpoint {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        // 先删除过期的行，再持久化数据
+        txn.purge_expired()?;
+        txn.checkpoint()?;
+        Ok(ResultSet::Checkpoint)
+    }
+}
+
+// 在线备份
+pub struct Backup {
+    path: String,
+    options: BackupOptions,
+}
+
+impl Backup {
+    pub fn new(path: String, options: BackupOptions) -> Box<Self> {
+        Box::new(Self { path, options })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Backup {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let progress = txn.backup(&self.path, self.options)?;
+        Ok(ResultSet::Backup {
+            path: self.path,
+            version: progress.version,
+            keys: progress.keys,
+            bytes: progress.bytes,
+        })
+    }
+}
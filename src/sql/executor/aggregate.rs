@@ -0,0 +1,638 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    hash::{BuildHasher, RandomState},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+};
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        parser::{
+            ast::{self, Direction, Expression},
+            compile::CompiledExpression,
+        },
+        types::{decimal::Decimal, Row, Value},
+    },
+};
+
+use super::{query::compare_keys, Executor, ResultSet, INTERRUPT_BATCH};
+
+// 分组聚合，输出的每一行前面是分组表达式的值，后面是每个聚合函数的结果
+pub struct Aggregate<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    group_by: Vec<Expression>,
+    aggregates: Vec<ast::Aggregate>,
+}
+
+impl<T: Transaction> Aggregate<T> {
+    pub fn new(
+        source: Box<dyn Executor<T>>,
+        group_by: Vec<Expression>,
+        aggregates: Vec<ast::Aggregate>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            source,
+            group_by,
+            aggregates,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Aggregate<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let Self {
+            source,
+            group_by,
+            aggregates,
+        } = *self;
+        let new_group = || {
+            aggregates
+                .iter()
+                .map(AggregateState::new)
+                .collect::<Result<Vec<_>>>()
+        };
+        let rows = match source.execute(txn)? {
+            ResultSet::Scan { rows, .. } => rows,
+            _ => return Err(Error::Internal("unexpected result set".to_string())),
+        };
+
+        // 分组表达式，以及每个聚合函数的参数和排序表达式
+        let keys = group_by
+            .iter()
+            .map(CompiledExpression::new)
+            .collect::<Vec<_>>();
+        let inputs = aggregates
+            .iter()
+            .map(|agg| {
+                let args = agg.args.iter().map(CompiledExpression::new).collect();
+                let order_by = agg
+                    .order_by
+                    .iter()
+                    .map(|(e, _)| CompiledExpression::new(e))
+                    .collect();
+                (args, order_by)
+            })
+            .collect::<Vec<(Vec<_>, Vec<_>)>>();
+
+        // 分组按照第一次出现的顺序输出，分组的值序列化之后作为 key
+        let mut groups: Vec<(Row, Vec<AggregateState>)> = Vec::new();
+        let mut index = HashMap::new();
+        for (i, row) in rows.into_iter().enumerate() {
+            if i % INTERRUPT_BATCH == 0 {
+                txn.check_interrupt()?;
+            }
+            let values = keys
+                .iter()
+                .map(|e| e.evaluate(&row))
+                .collect::<Result<Row>>()?;
+            let key = bincode::serialize(&values)?;
+            let group = match index.get(&key) {
+                Some(group) => *group,
+                None => {
+                    groups.push((values, new_group()?));
+                    index.insert(key, groups.len() - 1);
+                    groups.len() - 1
+                }
+            };
+            for ((args, order_by), state) in inputs.iter().zip(&mut groups[group].1) {
+                state.add(args, order_by, &row)?;
+            }
+        }
+        // 没有 GROUP BY 时所有行是一个分组，即使没有行也返回一行
+        if groups.is_empty() && group_by.is_empty() {
+            groups.push((Vec::new(), new_group()?));
+        }
+
+        let rows = groups
+            .into_iter()
+            .map(|(mut row, states)| {
+                for state in states {
+                    row.push(state.finish()?);
+                }
+                Ok(row)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        txn.allocate_rows(&rows)?;
+        Ok(ResultSet::Scan {
+            columns: group_by
+                .iter()
+                .map(|e| e.to_string())
+                .chain(aggregates.iter().map(|a| a.to_string()))
+                .collect(),
+            rows,
+        })
+    }
+}
+
+// 一个分组中一个聚合函数的状态
+struct AggregateState {
+    accumulator: Accumulator,
+    // 有 ORDER BY 时先保存每一行的排序值和参数，排序之后再聚合
+    buffered: Option<Vec<(Row, Row)>>,
+    directions: Vec<Direction>,
+    // DISTINCT 时已经聚合过的参数
+    distinct: Option<DistinctSet>,
+}
+
+impl AggregateState {
+    fn new(agg: &ast::Aggregate) -> Result<Self> {
+        // 有 ORDER BY 时所有的行本来就保存在内存中排序，去重时不需要写入临时文件
+        let limit = if agg.order_by.is_empty() {
+            DISTINCT_MEMORY_LIMIT
+        } else {
+            usize::MAX
+        };
+        Ok(Self {
+            accumulator: Accumulator::new(&agg.name)?,
+            buffered: (!agg.order_by.is_empty()).then(Vec::new),
+            directions: agg.order_by.iter().map(|(_, d)| *d).collect(),
+            distinct: agg.distinct.then(|| DistinctSet::new(limit)),
+        })
+    }
+
+    fn add(
+        &mut self,
+        args: &[CompiledExpression],
+        order_by: &[CompiledExpression],
+        row: &Row,
+    ) -> Result<()> {
+        let mut args = args
+            .iter()
+            .map(|e| e.evaluate(row))
+            .collect::<Result<Row>>()?;
+        if let Some(distinct) = &mut self.distinct {
+            match distinct.insert(args)? {
+                Some(new) => args = new,
+                None => return Ok(()),
+            }
+        }
+        match &mut self.buffered {
+            Some(buffered) => {
+                let keys = order_by
+                    .iter()
+                    .map(|e| e.evaluate(row))
+                    .collect::<Result<Row>>()?;
+                buffered.push((keys, args));
+                Ok(())
+            }
+            None => self.accumulator.add(args),
+        }
+    }
+
+    fn finish(mut self) -> Result<Value> {
+        if let Some(mut buffered) = self.buffered.take() {
+            // 稳定排序，排序值相同的行保持原来的顺序
+            buffered.sort_by(|(l, _), (r, _)| compare_keys(l, r, &self.directions));
+            for (_, args) in buffered {
+                self.accumulator.add(args)?;
+            }
+        }
+        if let Some(distinct) = self.distinct.take() {
+            distinct.drain_spilled(|args| self.accumulator.add(args))?;
+        }
+        Ok(self.accumulator.finish())
+    }
+}
+
+// DISTINCT 聚合时一个分组在内存中最多保存的参数字节数，超过之后写入临时文件
+const DISTINCT_MEMORY_LIMIT: usize = 16 << 20;
+// 写入临时文件时按照哈希分成的文件数量，每个文件单独在内存中去重
+const DISTINCT_SPILL_PARTITIONS: usize = 16;
+
+// DISTINCT 聚合的参数去重，参数序列化之后保存在哈希表中
+// 超过内存限制之后，哈希表不再增长，不在哈希表中的参数按照哈希写入不同的临时文件，
+// 聚合结束时逐个读取临时文件去重，相同的参数一定在同一个文件中
+struct DistinctSet {
+    limit: usize,
+    seen: HashSet<Vec<u8>>,
+    bytes: usize,
+    hasher: RandomState,
+    spilled: Vec<BufWriter<File>>,
+}
+
+impl DistinctSet {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            seen: HashSet::new(),
+            bytes: 0,
+            hasher: RandomState::new(),
+            spilled: Vec::new(),
+        }
+    }
+
+    // 参数第一次出现时返回 Some，参数写入临时文件时返回 None，在 drain_spilled 中聚合
+    fn insert(&mut self, args: Row) -> Result<Option<Row>> {
+        let key = bincode::serialize(&args)?;
+        if self.seen.contains(&key) {
+            return Ok(None);
+        }
+        // wasm32 下没有文件系统，只在内存中去重
+        if self.spilled.is_empty()
+            && (self.bytes + key.len() <= self.limit || cfg!(target_arch = "wasm32"))
+        {
+            self.bytes += key.len();
+            self.seen.insert(key);
+            return Ok(Some(args));
+        }
+        if self.spilled.is_empty() {
+            for _ in 0..DISTINCT_SPILL_PARTITIONS {
+                self.spilled.push(BufWriter::new(tempfile::tempfile()?));
+            }
+        }
+        let partition = self.hasher.hash_one(&key) as usize % self.spilled.len();
+        let writer = &mut self.spilled[partition];
+        writer.write_all(&(key.len() as u32).to_le_bytes())?;
+        writer.write_all(&key)?;
+        Ok(None)
+    }
+
+    // 聚合写入临时文件中的参数，每个临时文件去重之后调用 f
+    fn drain_spilled(self, mut f: impl FnMut(Row) -> Result<()>) -> Result<()> {
+        for writer in self.spilled {
+            let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+            file.seek(SeekFrom::Start(0))?;
+            let mut reader = BufReader::new(file);
+            let mut seen = HashSet::new();
+            loop {
+                let mut len = [0u8; 4];
+                match reader.read_exact(&mut len) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e.into()),
+                }
+                let mut key = vec![0; u32::from_le_bytes(len) as usize];
+                reader.read_exact(&mut key)?;
+                if !seen.contains(&key) {
+                    f(bincode::deserialize(&key)?)?;
+                    seen.insert(key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// 聚合函数的累加器，除了 count(*) 之外都跳过第一个参数是 NULL 的行
+enum Accumulator {
+    Count(i64),
+    Sum(Option<Value>),
+    Min(Option<Value>),
+    Max(Option<Value>),
+    // 所有的值都是 DECIMAL 时精确计算，结果是 DECIMAL，否则使用浮点数
+    Avg {
+        sum: Option<Value>,
+        count: i64,
+    },
+    // group_concat 和 string_agg
+    Concat(Option<String>),
+    // 方差和标准差，使用 Welford 算法累加，population 表示总体方差，否则是样本方差
+    Variance {
+        count: i64,
+        mean: f64,
+        m2: f64,
+        population: bool,
+        stddev: bool,
+    },
+    // 精确的连续分位数，保存分组中所有的值，在相邻的两个值之间线性插值
+    Percentile {
+        values: Vec<f64>,
+        fraction: Option<f64>,
+    },
+    // 近似分位数，只使用固定大小的内存
+    ApproxPercentile {
+        sketch: QuantileSketch,
+        fraction: Option<f64>,
+    },
+}
+
+impl Accumulator {
+    fn new(name: &str) -> Result<Self> {
+        Ok(match name {
+            "count" => Self::Count(0),
+            "sum" => Self::Sum(None),
+            "min" => Self::Min(None),
+            "max" => Self::Max(None),
+            "avg" => Self::Avg {
+                sum: None,
+                count: 0,
+            },
+            "group_concat" | "string_agg" => Self::Concat(None),
+            "variance" | "var_samp" | "var_pop" | "stddev" | "stddev_samp" | "stddev_pop" => {
+                Self::Variance {
+                    count: 0,
+                    mean: 0.0,
+                    m2: 0.0,
+                    population: name.ends_with("_pop"),
+                    stddev: name.starts_with("stddev"),
+                }
+            }
+            "percentile_cont" => Self::Percentile {
+                values: Vec::new(),
+                fraction: None,
+            },
+            "approx_percentile" => Self::ApproxPercentile {
+                sketch: QuantileSketch::default(),
+                fraction: None,
+            },
+            name => {
+                return Err(Error::Internal(format!(
+                    "unknown aggregate function {}",
+                    name
+                )))
+            }
+        })
+    }
+
+    fn add(&mut self, args: Row) -> Result<()> {
+        // count(*) 没有参数，count(DISTINCT a, b) 跳过任意一个参数是 NULL 的行
+        if let Self::Count(count) = self {
+            if !args.contains(&Value::Null) {
+                *count += 1;
+            }
+            return Ok(());
+        }
+        let mut args = args.into_iter();
+        let value = match args.next() {
+            None | Some(Value::Null) => return Ok(()),
+            Some(value) => value,
+        };
+        match self {
+            Self::Count(_) => {}
+            Self::Sum(sum) => {
+                let result = match (sum.take(), value) {
+                    (None, v @ (Value::Integer(_) | Value::Float(_) | Value::Decimal(_))) => v,
+                    (Some(Value::Integer(l)), Value::Integer(r)) => Value::Integer(
+                        l.checked_add(r)
+                            .ok_or(Error::Internal("integer overflow".to_string()))?,
+                    ),
+                    (Some(Value::Integer(l)), Value::Float(r)) => Value::Float(l as f64 + r),
+                    (Some(Value::Float(l)), Value::Integer(r)) => Value::Float(l + r as f64),
+                    (Some(Value::Float(l)), Value::Float(r)) => Value::Float(l + r),
+                    // DECIMAL 的和是精确的，和浮点数相加时结果是浮点数
+                    (Some(Value::Decimal(l)), Value::Decimal(r)) => {
+                        Value::Decimal(l.checked_add(r)?)
+                    }
+                    (Some(Value::Decimal(l)), Value::Integer(r)) => {
+                        Value::Decimal(l.checked_add(r.into())?)
+                    }
+                    (Some(Value::Integer(l)), Value::Decimal(r)) => {
+                        Value::Decimal(Decimal::from(l).checked_add(r)?)
+                    }
+                    (Some(Value::Decimal(l)), Value::Float(r)) => Value::Float(l.to_f64() + r),
+                    (Some(Value::Float(l)), Value::Decimal(r)) => Value::Float(l + r.to_f64()),
+                    (_, v) => return Err(Error::Internal(format!("cannot sum {:?}", v))),
+                };
+                // 和算术运算一样，浮点数溢出时报错
+                if matches!(result, Value::Float(f) if !f.is_finite()) {
+                    return Err(Error::Internal("sum is not a finite number".to_string()));
+                }
+                *sum = Some(result);
+            }
+            Self::Min(min) => {
+                if min.as_ref().is_none_or(|m| value.sort_cmp(m).is_lt()) {
+                    *min = Some(value);
+                }
+            }
+            Self::Max(max) => {
+                if max.as_ref().is_none_or(|m| value.sort_cmp(m).is_gt()) {
+                    *max = Some(value);
+                }
+            }
+            Self::Avg { sum, count } => {
+                *sum = Some(match (sum.take(), value) {
+                    (None, v @ Value::Decimal(_)) => v,
+                    (Some(Value::Decimal(l)), Value::Decimal(r)) => {
+                        Value::Decimal(l.checked_add(r)?)
+                    }
+                    (s, v) => Value::Float(s.map_or(Ok(0.0), number)? + number(v)?),
+                });
+                *count += 1;
+            }
+            Self::Variance {
+                count, mean, m2, ..
+            } => {
+                let value = number(value)?;
+                *count += 1;
+                let delta = value - *mean;
+                *mean += delta / *count as f64;
+                *m2 += delta * (value - *mean);
+            }
+            Self::Percentile { values, fraction } => {
+                fraction.get_or_insert(percentile_fraction(args.next())?);
+                values.push(number(value)?);
+            }
+            Self::ApproxPercentile { sketch, fraction } => {
+                fraction.get_or_insert(percentile_fraction(args.next())?);
+                sketch.add(number(value)?);
+            }
+            Self::Concat(s) => {
+                let value = match value {
+                    Value::String(s) => s,
+                    Value::Boolean(b) => b.to_string(),
+                    Value::Integer(i) => i.to_string(),
+                    Value::Float(f) => f.to_string(),
+                    Value::Decimal(d) => d.to_string(),
+                    Value::Uuid(u) => u.to_string(),
+                    Value::Json(j) => j.to_string(),
+                    Value::Point(x, y) => format!("POINT({} {})", x, y),
+                    Value::Null => unreachable!(),
+                };
+                match s {
+                    None => *s = Some(value),
+                    Some(s) => {
+                        // 分隔符默认是逗号，NULL 表示不使用分隔符
+                        match args.next() {
+                            None => s.push(','),
+                            Some(Value::Null) => {}
+                            Some(Value::String(separator)) => s.push_str(&separator),
+                            Some(v) => {
+                                return Err(Error::Internal(format!("invalid separator {:?}", v)))
+                            }
+                        }
+                        s.push_str(&value);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 没有任何行时 count 返回 0，其他聚合函数返回 NULL
+    fn finish(self) -> Value {
+        match self {
+            Self::Count(count) => Value::Integer(count),
+            Self::Sum(v) | Self::Min(v) | Self::Max(v) => v.unwrap_or(Value::Null),
+            Self::Avg {
+                sum: Some(Value::Decimal(d)),
+                count,
+            } => d
+                .checked_div(count.into())
+                .map_or(Value::Float(d.to_f64() / count as f64), Value::Decimal),
+            Self::Avg {
+                sum: Some(Value::Float(f)),
+                count,
+            } => Value::Float(f / count as f64),
+            Self::Avg { .. } => Value::Null,
+            Self::Concat(s) => s.map_or(Value::Null, Value::String),
+            Self::Variance {
+                count,
+                m2,
+                population,
+                stddev,
+                ..
+            } => {
+                // 样本方差至少需要两个值
+                let n = if population { count } else { count - 1 };
+                if n <= 0 {
+                    return Value::Null;
+                }
+                let variance = m2 / n as f64;
+                Value::Float(if stddev { variance.sqrt() } else { variance })
+            }
+            Self::Percentile {
+                mut values,
+                fraction: Some(fraction),
+            } => {
+                values.sort_by(f64::total_cmp);
+                let position = fraction * (values.len() - 1) as f64;
+                let (lower, upper) = (position.floor(), position.ceil());
+                let (l, u) = (values[lower as usize], values[upper as usize]);
+                Value::Float(l + (u - l) * (position - lower))
+            }
+            Self::ApproxPercentile {
+                sketch,
+                fraction: Some(fraction),
+            } => sketch.quantile(fraction).map_or(Value::Null, Value::Float),
+            Self::Percentile { fraction: None, .. }
+            | Self::ApproxPercentile { fraction: None, .. } => Value::Null,
+        }
+    }
+}
+
+fn number(value: Value) -> Result<f64> {
+    match value {
+        Value::Integer(i) => Ok(i as f64),
+        Value::Float(f) => Ok(f),
+        Value::Decimal(d) => Ok(d.to_f64()),
+        v => Err(Error::Internal(format!("expected a number, got {:?}", v))),
+    }
+}
+
+// 分位数的第二个参数，需要在 0 到 1 之间
+fn percentile_fraction(value: Option<Value>) -> Result<f64> {
+    match value.map(number).transpose()? {
+        Some(f) if (0.0..=1.0).contains(&f) => Ok(f),
+        _ => Err(Error::Internal(
+            "percentile fraction must be between 0 and 1".to_string(),
+        )),
+    }
+}
+
+// 近似分位数使用的压缩采样，每一层最多保存 SKETCH_CAPACITY 个值
+// 第 i 层的每个值代表 2^i 个原始值，一层满了之后排序，隔一个保留一个放到下一层
+const SKETCH_CAPACITY: usize = 256;
+
+#[derive(Debug, Default)]
+struct QuantileSketch {
+    levels: Vec<Vec<f64>>,
+    // 压缩时交替保留奇数和偶数位置的值，避免误差总是偏向一边
+    offset: usize,
+}
+
+impl QuantileSketch {
+    fn add(&mut self, value: f64) {
+        if self.levels.is_empty() {
+            self.levels.push(Vec::new());
+        }
+        self.levels[0].push(value);
+        let mut level = 0;
+        while self.levels[level].len() >= SKETCH_CAPACITY {
+            let mut values = std::mem::take(&mut self.levels[level]);
+            values.sort_by(f64::total_cmp);
+            self.offset ^= 1;
+            if self.levels.len() == level + 1 {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level + 1].extend(values.into_iter().skip(self.offset).step_by(2));
+            level += 1;
+        }
+    }
+
+    fn quantile(&self, fraction: f64) -> Option<f64> {
+        let mut values = self
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(i, level)| level.iter().map(move |v| (*v, 1u64 << i)))
+            .collect::<Vec<_>>();
+        values.sort_by(|(l, _), (r, _)| l.total_cmp(r));
+        let total = values.iter().map(|(_, w)| w).sum::<u64>();
+        // 返回排在第 rank 个的值
+        let rank = ((fraction * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (value, weight) in &values {
+            seen += weight;
+            if seen >= rank {
+                return Some(*value);
+            }
+        }
+        values.last().map(|(v, _)| *v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Result, sql::types::Value};
+
+    use super::{DistinctSet, QuantileSketch, SKETCH_CAPACITY};
+
+    #[test]
+    fn test_quantile_sketch() {
+        let mut sketch = QuantileSketch::default();
+        assert_eq!(sketch.quantile(0.5), None);
+        // 乱序写入 0..n
+        let n = 100_000u64;
+        for i in 0..n {
+            sketch.add((i * 7919 % n) as f64);
+        }
+        // 使用的内存和数据量的对数成正比
+        let stored = sketch.levels.iter().map(|l| l.len()).sum::<usize>();
+        assert!(stored <= SKETCH_CAPACITY * 10);
+        for fraction in [0.0, 0.1, 0.5, 0.9, 1.0] {
+            let value = sketch.quantile(fraction).unwrap();
+            let expect = fraction * (n - 1) as f64;
+            assert!(
+                (value - expect).abs() <= n as f64 * 0.02,
+                "fraction {}: {} vs {}",
+                fraction,
+                value,
+                expect
+            );
+        }
+    }
+
+    #[test]
+    fn test_distinct_spill() -> Result<()> {
+        // 内存中只能保存很少的参数，其余的写入临时文件
+        let mut distinct = DistinctSet::new(64);
+        let mut values = Vec::new();
+        for i in 0..1000 {
+            let args = vec![Value::Integer(i % 300), Value::String("a".to_string())];
+            if let Some(args) = distinct.insert(args)? {
+                values.push(args[0].clone());
+            }
+        }
+        assert!(!distinct.spilled.is_empty());
+        assert!(values.len() < 300);
+        distinct.drain_spilled(|args| {
+            values.push(args[0].clone());
+            Ok(())
+        })?;
+        values.sort_by(|l, r| l.sort_cmp(r));
+        assert_eq!(values, (0..300).map(Value::Integer).collect::<Vec<_>>());
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+use std::cmp::Ordering;
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        parser::{
+            ast::{Expression, JoinType},
+            compile::CompiledExpression,
+        },
+        types::{Row, Value},
+    },
+};
+
+use super::{Executor, ResultSet, INTERRUPT_BATCH};
+
+// 嵌套循环连接，右边的行全部读取之后，和左边的每一行依次组合
+// 左外连接在左边的行之后输出没有匹配的行，右外连接在最后输出右边没有匹配的行
+pub struct NestedLoopJoin<T: Transaction> {
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    predicate: Option<Expression>,
+    kind: JoinType,
+}
+
+impl<T: Transaction> NestedLoopJoin<T> {
+    pub fn new(
+        left: Box<dyn Executor<T>>,
+        right: Box<dyn Executor<T>>,
+        predicate: Option<Expression>,
+        kind: JoinType,
+    ) -> Box<Self> {
+        Box::new(Self {
+            left,
+            right,
+            predicate,
+            kind,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for NestedLoopJoin<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let (mut output, left, right) = execute_sources(self.left, self.right, self.kind, txn)?;
+        let predicate = self.predicate.as_ref().map(CompiledExpression::new);
+        for l in &left {
+            let mut matched = false;
+            for (i, r) in right.iter().enumerate() {
+                matched |= output.combine(txn, l, i, r, predicate.as_ref())?;
+            }
+            if !matched {
+                output.unmatched_left(l);
+            }
+        }
+        for (i, r) in right.iter().enumerate() {
+            output.unmatched_right(i, r);
+        }
+        output.finish(txn)
+    }
+}
+
+// 归并连接，两边的行已经按照 key 升序排列，同时向后遍历两边，只组合 key 相等的行
+// 输出的行也按照 key 排序，key 是 NULL 的行不和任何行匹配
+pub struct MergeJoin<T: Transaction> {
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    keys: (Expression, Expression),
+    predicate: Expression,
+    kind: JoinType,
+}
+
+impl<T: Transaction> MergeJoin<T> {
+    pub fn new(
+        left: Box<dyn Executor<T>>,
+        right: Box<dyn Executor<T>>,
+        keys: (Expression, Expression),
+        predicate: Expression,
+        kind: JoinType,
+    ) -> Box<Self> {
+        Box::new(Self {
+            left,
+            right,
+            keys,
+            predicate,
+            kind,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for MergeJoin<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let (mut output, left, right) = execute_sources(self.left, self.right, self.kind, txn)?;
+        let predicate = CompiledExpression::new(&self.predicate);
+        let keys = |rows: &[Row], key: &Expression| {
+            let key = CompiledExpression::new(key);
+            rows.iter()
+                .map(|row| key.evaluate(row))
+                .collect::<Result<Vec<_>>>()
+        };
+        let (left_keys, right_keys) = (keys(&left, &self.keys.0)?, keys(&right, &self.keys.1)?);
+
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() {
+            // 左边 key 相同的一组行
+            let key = &left_keys[i];
+            let end = i + left_keys[i..]
+                .iter()
+                .take_while(|k| k.sort_cmp(key) == Ordering::Equal)
+                .count();
+            if *key == Value::Null {
+                left[i..end].iter().for_each(|l| output.unmatched_left(l));
+                i = end;
+                continue;
+            }
+            // 跳过右边 key 更小的行，包括 key 是 NULL 的行
+            while j < right.len() && right_keys[j].sort_cmp(key) == Ordering::Less {
+                output.unmatched_right(j, &right[j]);
+                j += 1;
+            }
+            // 右边 key 相同的一组行，和左边的这一组行两两组合
+            let group = j + right_keys[j..]
+                .iter()
+                .take_while(|k| k.sort_cmp(key) == Ordering::Equal)
+                .count();
+            for l in &left[i..end] {
+                let mut matched = false;
+                for (k, r) in right.iter().enumerate().take(group).skip(j) {
+                    matched |= output.combine(txn, l, k, r, Some(&predicate))?;
+                }
+                if !matched {
+                    output.unmatched_left(l);
+                }
+            }
+            for (k, r) in right.iter().enumerate().take(group).skip(j) {
+                output.unmatched_right(k, r);
+            }
+            i = end;
+            j = group;
+        }
+        for (k, r) in right.iter().enumerate().skip(j) {
+            output.unmatched_right(k, r);
+        }
+        output.finish(txn)
+    }
+}
+
+// 半连接，右边只执行一次，输出左边在右边有满足条件的行的行，反连接时输出没有的行
+// 有 key 时右边的行按照 key 排序，左边的每一行只和 key 相等的行比较，key 是 NULL 的行不和任何行匹配
+// NOT IN 的反连接是 null_aware，key 是 NULL 时和所有的行匹配
+pub struct SemiJoin<T: Transaction> {
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    keys: Option<(Expression, Expression)>,
+    predicate: Option<Expression>,
+    anti: bool,
+    null_aware: bool,
+}
+
+impl<T: Transaction> SemiJoin<T> {
+    pub fn new(
+        left: Box<dyn Executor<T>>,
+        right: Box<dyn Executor<T>>,
+        keys: Option<(Expression, Expression)>,
+        predicate: Option<Expression>,
+        anti: bool,
+        null_aware: bool,
+    ) -> Box<Self> {
+        Box::new(Self {
+            left,
+            right,
+            keys,
+            predicate,
+            anti,
+            null_aware,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for SemiJoin<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let (columns, left) = match self.left.execute(txn)? {
+            ResultSet::Scan { columns, rows } => (columns, rows),
+            _ => return Err(Error::Internal("unexpected result set".to_string())),
+        };
+        let right = match self.right.execute(txn)? {
+            ResultSet::Scan { rows, .. } => rows,
+            _ => return Err(Error::Internal("unexpected result set".to_string())),
+        };
+        let predicate = self.predicate.as_ref().map(CompiledExpression::new);
+        // 右边的行和 key，没有等值条件时 key 都是 NULL，每一行都需要比较
+        let keys = self
+            .keys
+            .as_ref()
+            .map(|(l, r)| (CompiledExpression::new(l), CompiledExpression::new(r)));
+        let mut right = right
+            .into_iter()
+            .map(|row| match &keys {
+                Some((_, key)) => Ok((key.evaluate(&row)?, row)),
+                None => Ok((Value::Null, row)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // NULL 排在最前面，null_aware 时每一行都需要和这些行比较
+        let mut nulls = 0;
+        if keys.is_some() {
+            right.sort_by(|(a, _), (b, _)| a.sort_cmp(b));
+            nulls = right.partition_point(|(key, _)| *key == Value::Null);
+        }
+
+        let mut rows = Vec::new();
+        let mut checked = 0usize;
+        for l in left {
+            // key 相等的行，以及 null_aware 时 key 是 NULL 的行
+            let (nulls, candidates) = match &keys {
+                Some((key, _)) => match (key.evaluate(&l)?, self.null_aware) {
+                    (Value::Null, true) => (&right[..0], &right[..]),
+                    (Value::Null, false) => (&right[..0], &right[..0]),
+                    (key, null_aware) => {
+                        let start = right.partition_point(|(k, _)| k.sort_cmp(&key).is_lt());
+                        let end = right.partition_point(|(k, _)| k.sort_cmp(&key).is_le());
+                        (
+                            &right[..if null_aware { nulls } else { 0 }],
+                            &right[start..end],
+                        )
+                    }
+                },
+                None => (&right[..0], &right[..]),
+            };
+            // 找到一行满足条件就不再继续比较，和 WHERE 一样，条件是 NULL 时不满足
+            let mut matched = false;
+            for (_, r) in nulls.iter().chain(candidates) {
+                checked += 1;
+                if checked.is_multiple_of(INTERRUPT_BATCH) {
+                    txn.check_interrupt()?;
+                }
+                let value = match &predicate {
+                    Some(predicate) => predicate.evaluate(&l.iter().chain(r).cloned().collect())?,
+                    None => Value::Boolean(true),
+                };
+                match value {
+                    Value::Boolean(true) => {
+                        matched = true;
+                        break;
+                    }
+                    Value::Boolean(false) | Value::Null => {}
+                    v => {
+                        return Err(Error::Internal(format!(
+                            "join predicate returned {:?}, expected boolean",
+                            v
+                        )))
+                    }
+                }
+            }
+            if matched != self.anti {
+                rows.push(l);
+            }
+        }
+        Ok(ResultSet::Scan { columns, rows })
+    }
+}
+
+// 执行连接的两边，返回两边的行和收集输出的结构
+fn execute_sources<T: Transaction>(
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    kind: JoinType,
+    txn: &mut T,
+) -> Result<(JoinOutput, Vec<Row>, Vec<Row>)> {
+    let (mut columns, left) = match left.execute(txn)? {
+        ResultSet::Scan { columns, rows } => (columns, rows),
+        _ => return Err(Error::Internal("unexpected result set".to_string())),
+    };
+    let left_width = columns.len();
+    let right = match right.execute(txn)? {
+        ResultSet::Scan {
+            columns: right_columns,
+            rows,
+        } => {
+            columns.extend(right_columns);
+            rows
+        }
+        _ => return Err(Error::Internal("unexpected result set".to_string())),
+    };
+    Ok((
+        JoinOutput::new(columns, left_width, right.len(), kind),
+        left,
+        right,
+    ))
+}
+
+// 收集连接输出的行，外连接时没有匹配的行和 NULL 组合
+struct JoinOutput {
+    // 左边的列和右边的列拼接起来
+    columns: Vec<String>,
+    rows: Vec<Row>,
+    left_width: usize,
+    right_width: usize,
+    keep_left: bool,
+    keep_right: bool,
+    // 右边的每一行是否已经匹配或者输出过
+    right_matched: Vec<bool>,
+    // 组合的数量可能很多，每一批检查一次中断和内存限制
+    checked: usize,
+    allocated: usize,
+}
+
+impl JoinOutput {
+    fn new(columns: Vec<String>, left_width: usize, right_rows: usize, kind: JoinType) -> Self {
+        Self {
+            right_width: columns.len() - left_width,
+            columns,
+            rows: Vec::new(),
+            left_width,
+            keep_left: matches!(kind, JoinType::Left | JoinType::Full),
+            keep_right: matches!(kind, JoinType::Right | JoinType::Full),
+            right_matched: vec![false; right_rows],
+            checked: 0,
+            allocated: 0,
+        }
+    }
+
+    // 组合左边的行和右边的第 i 行，满足连接条件时输出
+    fn combine<T: Transaction>(
+        &mut self,
+        txn: &T,
+        l: &Row,
+        i: usize,
+        r: &Row,
+        predicate: Option<&CompiledExpression>,
+    ) -> Result<bool> {
+        self.checked += 1;
+        if self.checked.is_multiple_of(INTERRUPT_BATCH) {
+            txn.check_interrupt()?;
+            txn.allocate_rows(&self.rows[self.allocated..])?;
+            self.allocated = self.rows.len();
+        }
+        let row = l.iter().chain(r).cloned().collect();
+        // 和 WHERE 一样，条件是 NULL 时不满足
+        match predicate.map(|p| p.evaluate(&row)).transpose()? {
+            None | Some(Value::Boolean(true)) => {
+                self.rows.push(row);
+                self.right_matched[i] = true;
+                Ok(true)
+            }
+            Some(Value::Boolean(false) | Value::Null) => Ok(false),
+            Some(v) => Err(Error::Internal(format!(
+                "join predicate returned {:?}, expected boolean",
+                v
+            ))),
+        }
+    }
+
+    // 左边没有匹配的行，左外连接时输出
+    fn unmatched_left(&mut self, l: &Row) {
+        if self.keep_left {
+            let nulls = std::iter::repeat_n(Value::Null, self.right_width);
+            self.rows.push(l.iter().cloned().chain(nulls).collect());
+        }
+    }
+
+    // 右边的第 i 行没有匹配的行时，右外连接输出一次
+    fn unmatched_right(&mut self, i: usize, r: &Row) {
+        if self.keep_right && !self.right_matched[i] {
+            let nulls = std::iter::repeat_n(Value::Null, self.left_width);
+            self.rows.push(nulls.chain(r.iter().cloned()).collect());
+            self.right_matched[i] = true;
+        }
+    }
+
+    fn finish<T: Transaction>(self, txn: &T) -> Result<ResultSet> {
+        txn.allocate_rows(&self.rows[self.allocated..])?;
+        Ok(ResultSet::Scan {
+            columns: self.columns,
+            rows: self.rows,
+        })
+    }
+}
@@ -1,43 +1,236 @@
-use super::{engine::Transaction, plan::Node, types::Row};
+use super::{
+    engine::Transaction,
+    plan::Node,
+    types::{Row, Value},
+};
 use crate::error::Result;
-use mutation::Insert;
-use query::Scan;
-use schema::CreateTable;
+use admin::{Backup, Checkpoint, RollbackTransaction};
+use aggregate::Aggregate;
+use join::{MergeJoin, NestedLoopJoin, SemiJoin};
+use mutation::{Copy, Delete, Insert, Update};
+use query::{
+    Distinct, Explain, Filter, IndexScan, Limit, Order, Projection, Scan, SetOperation,
+    SpatialScan, TableFunctionScan, Values,
+};
+use schema::{
+    AddColumn, Analyze, CheckTable, CreateIndex, CreateTable, CreateView, Describe, DropIndex,
+    DropView, Reindex, RenameColumn, RenameTable, ShowCreateTable, ShowIndexes,
+};
 
+mod admin;
+mod aggregate;
+mod join;
 mod mutation;
 mod query;
 mod schema;
 
+// 执行器每处理这么多行检查一次语句是否被取消或者超时
+const INTERRUPT_BATCH: usize = 1024;
+
 // 执行器定义
 pub trait Executor<T: Transaction> {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet>;
 }
 
-impl<T: Transaction> dyn Executor<T> {
+impl<T: Transaction + 'static> dyn Executor<T> {
     pub fn build(node: Node) -> Box<dyn Executor<T>> {
         match node {
             Node::CreateTable { schema } => CreateTable::new(schema),
+            Node::CreateView { view } => CreateView::new(view),
+            Node::DropView { name } => DropView::new(name),
+            Node::CreateIndex { table_name, index } => CreateIndex::new(table_name, index),
+            Node::DropIndex { table_name, name } => DropIndex::new(table_name, name),
+            Node::AddColumn { table_name, column } => AddColumn::new(table_name, column),
+            Node::RenameTable {
+                table_name,
+                new_name,
+            } => RenameTable::new(table_name, new_name),
+            Node::RenameColumn {
+                table_name,
+                column,
+                new_name,
+            } => RenameColumn::new(table_name, column, new_name),
             Node::Insert {
                 table_name,
                 columns,
                 values,
-            } => Insert::new(table_name, columns, values),
-            Node::Scan { table_name } => Scan::new(table_name),
+                source,
+            } => Insert::new(table_name, columns, values, source.map(|s| Self::build(*s))),
+            Node::Update {
+                table_name,
+                source,
+                columns,
+            } => Update::new(table_name, Self::build(*source), columns),
+            Node::Delete { table_name, source } => Delete::new(table_name, Self::build(*source)),
+            Node::Values { rows, columns } => Values::new(rows, columns),
+            Node::Scan {
+                table_name,
+                columns,
+            } => Scan::new(table_name, columns),
+            Node::IndexScan {
+                table_name,
+                index,
+                prefix,
+                range,
+            } => IndexScan::new(table_name, index, prefix, range),
+            Node::SpatialScan {
+                table_name,
+                index,
+                area,
+            } => SpatialScan::new(table_name, index, area),
+            Node::TableFunction {
+                name,
+                args,
+                columns,
+            } => TableFunctionScan::new(name, args, columns),
+            Node::RollbackTransaction { version } => RollbackTransaction::new(version),
+            Node::Checkpoint => Checkpoint::new(),
+            Node::ShowIndexes { table_name } => ShowIndexes::new(table_name),
+            Node::ShowCreateTable { table_name } => ShowCreateTable::new(table_name),
+            Node::Describe { table_name } => Describe::new(table_name),
+            Node::Analyze { table_name } => Analyze::new(table_name),
+            Node::CheckTable { table_name } => CheckTable::new(table_name),
+            Node::Reindex { table_name } => Reindex::new(table_name),
+            Node::Copy { table_name, source } => Copy::new(table_name, source),
+            Node::Backup { path, options } => Backup::new(path, options),
+            Node::NestedLoopJoin {
+                left,
+                right,
+                predicate,
+                kind,
+            } => NestedLoopJoin::new(Self::build(*left), Self::build(*right), predicate, kind),
+            Node::MergeJoin {
+                left,
+                right,
+                left_key,
+                right_key,
+                predicate,
+                kind,
+            } => MergeJoin::new(
+                Self::build(*left),
+                Self::build(*right),
+                (left_key, right_key),
+                predicate,
+                kind,
+            ),
+            Node::SemiJoin {
+                left,
+                right,
+                keys,
+                predicate,
+                anti,
+                null_aware,
+            } => SemiJoin::new(
+                Self::build(*left),
+                Self::build(*right),
+                keys,
+                predicate,
+                anti,
+                null_aware,
+            ),
+            Node::Filter { source, predicate } => Filter::new(Self::build(*source), predicate),
+            Node::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => Aggregate::new(Self::build(*source), group_by, aggregates),
+            Node::Order { source, order_by } => Order::new(Self::build(*source), order_by),
+            Node::Distinct { source } => Distinct::new(Self::build(*source)),
+            Node::SetOperation {
+                left,
+                right,
+                op,
+                all,
+            } => SetOperation::new(Self::build(*left), Self::build(*right), op, all),
+            Node::Limit { source, limit } => Limit::new(Self::build(*source), limit),
+            Node::Projection {
+                source,
+                exprs,
+                columns,
+            } => Projection::new(Self::build(*source), exprs, columns),
+            Node::Explain {
+                source,
+                format,
+                verbose,
+            } => Explain::new(*source, format, verbose),
         }
     }
 }
 
 // 执行结果集
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ResultSet {
     CreateTable {
         table_name: String,
     },
+    AlterTable {
+        table_name: String,
+    },
+    CreateView {
+        view_name: String,
+    },
+    DropView {
+        view_name: String,
+    },
+    CreateIndex {
+        index_name: String,
+        rows: usize,
+    },
+    DropIndex {
+        index_name: String,
+    },
     Insert {
         count: usize,
     },
+    Update {
+        count: usize,
+    },
+    Delete {
+        count: usize,
+    },
     Scan {
         columns: Vec<String>,
         rows: Vec<Row>,
     },
+    Begin {
+        version: u64,
+    },
+    Commit {
+        version: u64,
+    },
+    Rollback {
+        version: u64,
+    },
+    RollbackTransaction {
+        version: u64,
+    },
+    Checkpoint,
+    Set {
+        name: String,
+        value: Value,
+    },
+    Kill {
+        session_id: u64,
+    },
+    Attach {
+        alias: String,
+    },
+    Detach {
+        alias: String,
+    },
+    Analyze {
+        table_name: String,
+        rows: usize,
+    },
+    Reindex {
+        table_name: String,
+        rows: usize,
+    },
+    Backup {
+        path: String,
+        // 备份的快照版本
+        version: u64,
+        keys: u64,
+        bytes: u64,
+    },
 }
@@ -1,114 +1,284 @@
-use std::collections::HashMap;
-
 use crate::{
     error::{Error, Result},
     sql::{
-        engine::Transaction,
-        parser::ast::Expression,
-        schema::Table,
+        engine::{csv::ExternalScan, Transaction},
+        ident::Ident,
+        parser::{ast::Expression, compile::CompiledExpression},
+        schema::{Column, ExternalTable, Table},
         types::{Row, Value},
     },
 };
 
-use super::{Executor, ResultSet};
+use super::{Executor, ResultSet, INTERRUPT_BATCH};
+
+// 从 CSV 文件批量导入数据
+pub struct Copy {
+    table_name: Ident,
+    source: ExternalTable,
+}
+
+impl Copy {
+    pub fn new(table_name: Ident, source: ExternalTable) -> Box<Self> {
+        Box::new(Self { table_name, source })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Copy {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table = txn.must_get_table(self.table_name.clone())?;
+        let scan = ExternalScan::open(
+            &table,
+            &self.source,
+            format!("COPY into {}", self.table_name),
+        )?;
+        // 边读取边写入，每次只保存一批数据
+        let mut count = 0;
+        let mut rows = Vec::with_capacity(INTERRUPT_BATCH);
+        for row in scan {
+            rows.push(row?);
+            if rows.len() == INTERRUPT_BATCH {
+                txn.check_interrupt()?;
+                count += rows.len();
+                txn.insert_rows(self.table_name.clone(), std::mem::take(&mut rows))?;
+            }
+        }
+        if !rows.is_empty() {
+            count += rows.len();
+            txn.insert_rows(self.table_name.clone(), rows)?;
+        }
+        Ok(ResultSet::Insert { count })
+    }
+}
 
-pub struct Insert {
-    table_name: String,
+pub struct Insert<T: Transaction> {
+    table_name: Ident,
     columns: Vec<String>,
     values: Vec<Vec<Expression>>,
+    // INSERT ... SELECT 中的查询
+    source: Option<Box<dyn Executor<T>>>,
 }
 
-impl Insert {
+impl<T: Transaction> Insert<T> {
     pub fn new(
-        table_name: String,
+        table_name: Ident,
         columns: Vec<String>,
         values: Vec<Vec<Expression>>,
+        source: Option<Box<dyn Executor<T>>>,
     ) -> Box<Self> {
         Box::new(Self {
             table_name,
             columns,
             values,
+            source,
         })
     }
 }
 
-// 列对齐
-// tbl:
-// insert into tbl values(1, 2, 3);
-// a       b       c          d
-// 1       2       3      default 填充
-fn pad_row(table: &Table, row: &Row) -> Result<Row> {
-    let mut results = row.clone();
-    for column in table.columns.iter().skip(row.len()) {
-        if let Some(default) = &column.default {
-            results.push(default.clone());
-        } else {
+// INSERT 指定的列在表中的位置，没有指定列时按照表中列的顺序
+fn insert_columns(table: &Table, columns: &[String]) -> Result<Vec<usize>> {
+    if columns.is_empty() {
+        return Ok((0..table.columns.len()).collect());
+    }
+    let mut positions = Vec::with_capacity(columns.len());
+    for name in columns {
+        let Some(i) = table.columns.iter().position(|c| c.name == *name) else {
             return Err(Error::Internal(format!(
-                "No default value for column {}",
-                column.name
+                "column {} does not exist in table {}",
+                name, table.name
+            )));
+        };
+        if positions.contains(&i) {
+            return Err(Error::Internal(format!(
+                "column {} is specified more than once",
+                name
             )));
         }
+        positions.push(i);
     }
+    Ok(positions)
+}
 
-    Ok(results)
+// 列的默认值，没有默认值的列必须给出值
+fn default_value(column: &Column) -> Result<Value> {
+    column
+        .default_value()?
+        .ok_or_else(|| Error::Internal(format!("No default value for column {}", column.name)))
 }
 
-// tbl:
-// insert into tbl(d, c) values(1, 2);
-//    a          b       c          d
-// default   default     2          1
-fn make_row(table: &Table, columns: &Vec<String>, values: &Row) -> Result<Row> {
-    // 判断列数是否和value数一致
-    if columns.len() != values.len() {
-        return Err(Error::Internal(format!("columns and values num mismatch")));
+// 按照列的位置组成完整的一行，DEFAULT（为 None 的值）和没有给出值的列使用默认值
+// 值的类型和列不同时进行隐式转换，之后校验是否可以为 NULL 以及类型是否和列相同
+// tbl (a, b, c, d):
+// insert into tbl values (1, 2, 3);      ->  1        2        3   default
+// insert into tbl (d, c) values (1, 2);  ->  default  default  2   1
+fn build_row(table: &Table, positions: &[usize], row: Vec<Option<Value>>) -> Result<Row> {
+    let mut values = vec![None; table.columns.len()];
+    for (value, &i) in row.into_iter().zip(positions) {
+        let column = &table.columns[i];
+        values[i] = Some(match value {
+            None => default_value(column)?,
+            Some(value) => value.coerce(column.datatype)?,
+        });
     }
+    table
+        .columns
+        .iter()
+        .zip(values)
+        .map(|(column, value)| {
+            let value = match value {
+                Some(value) => value,
+                None => default_value(column)?,
+            };
+            column.validate_value(&value)?;
+            Ok(value)
+        })
+        .collect()
+}
 
-    let mut inputs = HashMap::new();
-    for (i, col_name) in columns.iter().enumerate() {
-        inputs.insert(col_name, values[i].clone());
-    }
+impl<T: Transaction> Executor<T> for Insert<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        // 查询的结果作为要插入的值，在写入之前读取全部的行
+        let rows = match self.source {
+            Some(source) => match source.execute(txn)? {
+                ResultSet::Scan { rows, .. } => rows
+                    .into_iter()
+                    .map(|row| row.into_iter().map(Some).collect())
+                    .collect(),
+                _ => return Err(Error::Internal("unexpected result set".to_string())),
+            },
+            None => self
+                .values
+                .into_iter()
+                .map(|exprs| {
+                    exprs
+                        .into_iter()
+                        .map(|expr| match expr {
+                            Expression::Default => Ok(None),
+                            expr => Value::from_expression(expr).map(Some),
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
 
-    let mut results = Vec::new();
-    for col in table.columns.iter() {
-        if let Some(value) = inputs.get(&col.name) {
-            results.push(value.clone());
-        } else if let Some(value) = &col.default {
-            results.push(value.clone());
-        } else {
-            return Err(Error::Internal(format!(
-                "No value given for the column {}",
-                col.name
-            )));
+        let mut count = 0;
+        // 先取出表信息
+        let table = txn.must_get_table(self.table_name.clone())?;
+        let positions = insert_columns(&table, &self.columns)?;
+        for (i, values) in rows.into_iter().enumerate() {
+            if i % INTERRUPT_BATCH == 0 {
+                txn.check_interrupt()?;
+            }
+            // 指定了列时值的个数需要和列相同，没有指定时可以省略最后的几列
+            if values.len() > positions.len() {
+                return Err(Error::Internal(format!(
+                    "table {} has {} columns, but INSERT has {} values",
+                    table.name,
+                    positions.len(),
+                    values.len()
+                )));
+            }
+            if !self.columns.is_empty() && values.len() < positions.len() {
+                return Err(Error::Internal(format!(
+                    "INSERT has {} columns, but {} values",
+                    positions.len(),
+                    values.len()
+                )));
+            }
+            let row = build_row(&table, &positions, values)?;
+            txn.create_row(self.table_name.clone(), row)?;
+            count += 1;
         }
+
+        Ok(ResultSet::Insert { count })
     }
+}
+
+// 值的类型和列的类型不同时进行隐式转换，例如整数插入到浮点数列
+fn coerce_row(table: &Table, row: Row) -> Result<Row> {
+    row.into_iter()
+        .enumerate()
+        .map(|(i, value)| match table.columns.get(i) {
+            Some(column) => value.coerce(column.datatype),
+            None => Ok(value),
+        })
+        .collect()
+}
+
+// 修改 source 返回的每一行，新的值根据修改之前的行计算
+pub struct Update<T: Transaction> {
+    table_name: Ident,
+    source: Box<dyn Executor<T>>,
+    columns: Vec<(usize, Expression)>,
+}
 
-    Ok(results)
+impl<T: Transaction> Update<T> {
+    pub fn new(
+        table_name: Ident,
+        source: Box<dyn Executor<T>>,
+        columns: Vec<(usize, Expression)>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            source,
+            columns,
+        })
+    }
 }
 
-impl<T: Transaction> Executor<T> for Insert {
+impl<T: Transaction> Executor<T> for Update<T> {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
-        let mut count = 0;
-        // 先取出表信息
+        let ResultSet::Scan { rows, .. } = self.source.execute(txn)? else {
+            return Err(Error::Internal("unexpected result set".to_string()));
+        };
         let table = txn.must_get_table(self.table_name.clone())?;
-        for exprs in self.values {
-            // 将表达式转换成 value
-            let row = exprs
-                .into_iter()
-                .map(|e| Value::from_expression(e))
-                .collect::<Vec<_>>();
-            // 如果没有指定插入的列
-            let insert_row = if self.columns.is_empty() {
-                pad_row(&table, &row)?
-            } else {
-                // 指定了插入的列，需要对 value 信息进行整理
-                make_row(&table, &self.columns, &row)?
-            };
-
-            // 插入数据
-            txn.create_row(self.table_name.clone(), insert_row)?;
+        let columns = self
+            .columns
+            .iter()
+            .map(|(i, expr)| (*i, CompiledExpression::new(expr)))
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        for (i, row) in rows.into_iter().enumerate() {
+            if i % INTERRUPT_BATCH == 0 {
+                txn.check_interrupt()?;
+            }
+            let mut new_row = row.clone();
+            for (column, expr) in &columns {
+                new_row[*column] = expr.evaluate(&row)?;
+            }
+            txn.update_row(
+                self.table_name.clone(),
+                &row[0],
+                coerce_row(&table, new_row)?,
+            )?;
             count += 1;
         }
+        Ok(ResultSet::Update { count })
+    }
+}
 
-        Ok(ResultSet::Insert { count })
+// 删除 source 返回的每一行
+pub struct Delete<T: Transaction> {
+    table_name: Ident,
+    source: Box<dyn Executor<T>>,
+}
+
+impl<T: Transaction> Delete<T> {
+    pub fn new(table_name: Ident, source: Box<dyn Executor<T>>) -> Box<Self> {
+        Box::new(Self { table_name, source })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Delete<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let ResultSet::Scan { rows, .. } = self.source.execute(txn)? else {
+            return Err(Error::Internal("unexpected result set".to_string()));
+        };
+        for (i, row) in rows.iter().enumerate() {
+            if i % INTERRUPT_BATCH == 0 {
+                txn.check_interrupt()?;
+            }
+            txn.delete_row(self.table_name.clone(), &row[0])?;
+        }
+        Ok(ResultSet::Delete { count: rows.len() })
     }
 }
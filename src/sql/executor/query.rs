@@ -1,24 +1,555 @@
-use crate::{error::Result, sql::engine::Transaction};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    ops::Bound,
+};
 
-use super::{Executor, ResultSet};
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        ident::Ident,
+        parser::{
+            ast::{Direction, ExplainFormat, Expression, SetOperator},
+            compile::CompiledExpression,
+        },
+        plan::{explain::ExplainNode, Node},
+        types::{DataType, Row, Value},
+    },
+};
+
+use super::{Executor, ResultSet, INTERRUPT_BATCH};
 
 pub struct Scan {
-    table_name: String,
+    table_name: Ident,
+    columns: Option<Vec<usize>>,
 }
 
 impl Scan {
-    pub fn new(table_name: String) -> Box<Self> {
-        Box::new(Self { table_name })
+    pub fn new(table_name: Ident, columns: Option<Vec<usize>>) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            columns,
+        })
     }
 }
 
 impl<T: Transaction> Executor<T> for Scan {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.check_interrupt()?;
         let table = txn.must_get_table(self.table_name.clone())?;
-        let rows = txn.scan_table(self.table_name.clone())?;
+        let rows = match &self.columns {
+            Some(columns) => txn.scan_table_columns(self.table_name.clone(), columns)?,
+            None => txn.scan_table(self.table_name.clone())?,
+        };
         Ok(ResultSet::Scan {
-            columns: table.columns.into_iter().map(|c| c.name.clone()).collect(),
+            columns: table.columns.into_iter().map(|c| c.name.into()).collect(),
             rows,
         })
     }
 }
+
+// 通过二级索引读取行，索引的前几列等于 prefix，之后的一列在 range 范围中
+pub struct IndexScan {
+    table_name: Ident,
+    index: Ident,
+    prefix: Vec<Value>,
+    range: (Bound<Value>, Bound<Value>),
+}
+
+impl IndexScan {
+    pub fn new(
+        table_name: Ident,
+        index: Ident,
+        prefix: Vec<Value>,
+        range: (Bound<Value>, Bound<Value>),
+    ) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            index,
+            prefix,
+            range,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for IndexScan {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.check_interrupt()?;
+        let table = txn.must_get_table(self.table_name.clone())?;
+        let rows = txn.scan_index(self.table_name, &self.index, &self.prefix, self.range)?;
+        Ok(ResultSet::Scan {
+            columns: table.columns.into_iter().map(|c| c.name.into()).collect(),
+            rows,
+        })
+    }
+}
+
+// 通过空间索引读取点可能在范围中的行
+pub struct SpatialScan {
+    table_name: Ident,
+    index: Ident,
+    area: [f64; 4],
+}
+
+impl SpatialScan {
+    pub fn new(table_name: Ident, index: Ident, area: [f64; 4]) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            index,
+            area,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for SpatialScan {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.check_interrupt()?;
+        let table = txn.must_get_table(self.table_name.clone())?;
+        let rows = txn.scan_spatial_index(self.table_name, &self.index, self.area)?;
+        Ok(ResultSet::Scan {
+            columns: table.columns.into_iter().map(|c| c.name.into()).collect(),
+            rows,
+        })
+    }
+}
+
+// 读取表函数生成的行
+pub struct TableFunctionScan {
+    name: String,
+    args: Vec<Value>,
+    columns: Vec<String>,
+}
+
+impl TableFunctionScan {
+    pub fn new(name: String, args: Vec<Value>, columns: Vec<String>) -> Box<Self> {
+        Box::new(Self {
+            name,
+            args,
+            columns,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for TableFunctionScan {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.check_interrupt()?;
+        let function = txn
+            .table_function(&self.name)?
+            .ok_or(Error::Internal(format!(
+                "table function {} does not exist",
+                self.name
+            )))?;
+        let mut rows = Vec::new();
+        for row in function.rows(self.args)? {
+            let row = row?;
+            if row.len() != self.columns.len() {
+                return Err(Error::Internal(format!(
+                    "table function {} returned a row with {} values, expected {}",
+                    self.name,
+                    row.len(),
+                    self.columns.len()
+                )));
+            }
+            rows.push(row);
+            // 表函数可能生成很多行，每一批检查一次中断和内存限制
+            if rows.len() % INTERRUPT_BATCH == 0 {
+                txn.check_interrupt()?;
+                txn.allocate_rows(&rows[rows.len() - INTERRUPT_BATCH..])?;
+            }
+        }
+        txn.allocate_rows(&rows[rows.len() - rows.len() % INTERRUPT_BATCH..])?;
+        Ok(ResultSet::Scan {
+            columns: self.columns,
+            rows,
+        })
+    }
+}
+
+// 常量行
+pub struct Values {
+    rows: Vec<Vec<Expression>>,
+    columns: Vec<String>,
+}
+
+impl Values {
+    pub fn new(rows: Vec<Vec<Expression>>, columns: Vec<String>) -> Box<Self> {
+        Box::new(Self { rows, columns })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Values {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let rows = self
+            .rows
+            .into_iter()
+            .map(|exprs| exprs.into_iter().map(Value::from_expression).collect())
+            .collect::<Result<Vec<_>>>()?;
+        txn.allocate_rows(&rows)?;
+        Ok(ResultSet::Scan {
+            columns: self.columns,
+            rows,
+        })
+    }
+}
+
+// 过滤，只保留条件为 true 的行
+pub struct Filter<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    predicate: Expression,
+}
+
+impl<T: Transaction> Filter<T> {
+    pub fn new(source: Box<dyn Executor<T>>, predicate: Expression) -> Box<Self> {
+        Box::new(Self { source, predicate })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Filter<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan { columns, rows } => {
+                let predicate = CompiledExpression::new(&self.predicate);
+                let mut new_rows = Vec::new();
+                for (i, row) in rows.into_iter().enumerate() {
+                    if i % INTERRUPT_BATCH == 0 {
+                        txn.check_interrupt()?;
+                    }
+                    match predicate.evaluate(&row)? {
+                        Value::Boolean(true) => new_rows.push(row),
+                        // NULL 当作 false 处理
+                        Value::Boolean(false) | Value::Null => {}
+                        v => {
+                            return Err(Error::Internal(format!(
+                                "filter predicate returned {:?}, expected boolean",
+                                v
+                            )))
+                        }
+                    }
+                }
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows: new_rows,
+                })
+            }
+            _ => Err(Error::Internal("unexpected result set".to_string())),
+        }
+    }
+}
+
+// 排序，先计算每一行的排序值，再稳定排序
+pub struct Order<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    order_by: Vec<(Expression, Direction)>,
+}
+
+impl<T: Transaction> Order<T> {
+    pub fn new(source: Box<dyn Executor<T>>, order_by: Vec<(Expression, Direction)>) -> Box<Self> {
+        Box::new(Self { source, order_by })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Order<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan { columns, rows } => {
+                let exprs = self
+                    .order_by
+                    .iter()
+                    .map(|(e, _)| CompiledExpression::new(e))
+                    .collect::<Vec<_>>();
+                let directions = self.order_by.iter().map(|(_, d)| *d).collect::<Vec<_>>();
+                let mut keyed = Vec::with_capacity(rows.len());
+                for (i, row) in rows.into_iter().enumerate() {
+                    if i % INTERRUPT_BATCH == 0 {
+                        txn.check_interrupt()?;
+                    }
+                    let keys = exprs
+                        .iter()
+                        .map(|e| e.evaluate(&row))
+                        .collect::<Result<Row>>()?;
+                    keyed.push((keys, row));
+                }
+                keyed.sort_by(|(l, _), (r, _)| compare_keys(l, r, &directions));
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows: keyed.into_iter().map(|(_, row)| row).collect(),
+                })
+            }
+            _ => Err(Error::Internal("unexpected result set".to_string())),
+        }
+    }
+}
+
+// 按照排序方向依次比较排序值，使用 Value::sort_cmp 的全序，NULL 最小
+pub(super) fn compare_keys(l: &[Value], r: &[Value], directions: &[Direction]) -> Ordering {
+    l.iter()
+        .zip(r)
+        .zip(directions)
+        .map(|((l, r), direction)| match direction {
+            Direction::Asc => l.sort_cmp(r),
+            Direction::Desc => r.sort_cmp(l),
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+// 去重，每一行序列化之后放到集合中，相同的行只保留第一次出现的
+pub struct Distinct<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+}
+
+impl<T: Transaction> Distinct<T> {
+    pub fn new(source: Box<dyn Executor<T>>) -> Box<Self> {
+        Box::new(Self { source })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Distinct<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan { columns, rows } => {
+                let mut seen = HashSet::new();
+                let mut distinct = Vec::new();
+                for (i, row) in rows.into_iter().enumerate() {
+                    if i % INTERRUPT_BATCH == 0 {
+                        txn.check_interrupt()?;
+                    }
+                    if seen.insert(bincode::serialize(&row)?) {
+                        distinct.push(row);
+                    }
+                }
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows: distinct,
+                })
+            }
+            _ => Err(Error::Internal("unexpected result set".to_string())),
+        }
+    }
+}
+
+// 集合运算，两边的结果都读取到内存中，按照整行的编码判断是否相同
+pub struct SetOperation<T: Transaction> {
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    op: SetOperator,
+    all: bool,
+}
+
+impl<T: Transaction> SetOperation<T> {
+    pub fn new(
+        left: Box<dyn Executor<T>>,
+        right: Box<dyn Executor<T>>,
+        op: SetOperator,
+        all: bool,
+    ) -> Box<Self> {
+        Box::new(Self {
+            left,
+            right,
+            op,
+            all,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for SetOperation<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let (columns, mut left) = match self.left.execute(txn)? {
+            ResultSet::Scan { columns, rows } => (columns, rows),
+            _ => return Err(Error::Internal("unexpected result set".to_string())),
+        };
+        let mut right = match self.right.execute(txn)? {
+            ResultSet::Scan { rows, .. } => rows,
+            _ => return Err(Error::Internal("unexpected result set".to_string())),
+        };
+        unify_column_types(self.op, &columns, &mut left, &mut right)?;
+
+        let mut rows = Vec::new();
+        let mut seen = HashSet::new();
+        match self.op {
+            SetOperator::Union => {
+                for (i, row) in left.into_iter().chain(right).enumerate() {
+                    if i % INTERRUPT_BATCH == 0 {
+                        txn.check_interrupt()?;
+                    }
+                    if self.all || seen.insert(bincode::serialize(&row)?) {
+                        rows.push(row);
+                    }
+                }
+            }
+            // 右边每一行出现的次数，ALL 时左边的每一行消耗一次
+            SetOperator::Intersect | SetOperator::Except => {
+                let mut counts = HashMap::new();
+                for (i, row) in right.iter().enumerate() {
+                    if i % INTERRUPT_BATCH == 0 {
+                        txn.check_interrupt()?;
+                    }
+                    *counts.entry(bincode::serialize(row)?).or_insert(0usize) += 1;
+                }
+                for (i, row) in left.into_iter().enumerate() {
+                    if i % INTERRUPT_BATCH == 0 {
+                        txn.check_interrupt()?;
+                    }
+                    let key = bincode::serialize(&row)?;
+                    let count = counts.get_mut(&key);
+                    let matched = count.as_ref().is_some_and(|c| **c > 0);
+                    let keep = (self.op == SetOperator::Intersect) == matched
+                        && (self.all || seen.insert(key));
+                    if let (Some(count), true) = (count, self.all && matched) {
+                        *count -= 1;
+                    }
+                    if keep {
+                        rows.push(row);
+                    }
+                }
+            }
+        }
+        Ok(ResultSet::Scan { columns, rows })
+    }
+}
+
+// 检查两边每一列的类型是否一致，按照每一边第一个不是 NULL 的值判断
+// 整数和浮点数可以一起运算，整数转换成浮点数之后再比较
+fn unify_column_types(
+    op: SetOperator,
+    columns: &[String],
+    left: &mut [Row],
+    right: &mut [Row],
+) -> Result<()> {
+    let datatype = |rows: &[Row], i: usize| {
+        rows.iter()
+            .find_map(|row| row.get(i).and_then(Value::datatype))
+    };
+    for (i, column) in columns.iter().enumerate() {
+        match (datatype(left, i), datatype(right, i)) {
+            (Some(l), Some(r)) if l == r => {}
+            (
+                Some(DataType::Integer | DataType::Float),
+                Some(DataType::Integer | DataType::Float),
+            ) => {
+                for row in left.iter_mut().chain(right.iter_mut()) {
+                    if let Some(Value::Integer(v)) = row.get(i) {
+                        row[i] = Value::Float(*v as f64);
+                    }
+                }
+            }
+            (Some(l), Some(r)) => {
+                return Err(Error::Internal(format!(
+                    "{} column {} has mismatched types {} and {}",
+                    op, column, l, r
+                )))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// 限制返回的行数
+pub struct Limit<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    limit: usize,
+}
+
+impl<T: Transaction> Limit<T> {
+    pub fn new(source: Box<dyn Executor<T>>, limit: usize) -> Box<Self> {
+        Box::new(Self { source, limit })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Limit<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan { columns, rows } => Ok(ResultSet::Scan {
+                columns,
+                rows: rows.into_iter().take(self.limit).collect(),
+            }),
+            _ => Err(Error::Internal("unexpected result set".to_string())),
+        }
+    }
+}
+
+// 投影，计算 select 列表
+pub struct Projection<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    exprs: Vec<Expression>,
+    columns: Vec<String>,
+}
+
+impl<T: Transaction> Projection<T> {
+    pub fn new(
+        source: Box<dyn Executor<T>>,
+        exprs: Vec<Expression>,
+        columns: Vec<String>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            source,
+            exprs,
+            columns,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Projection<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan { rows, .. } => {
+                let exprs = self
+                    .exprs
+                    .iter()
+                    .map(CompiledExpression::new)
+                    .collect::<Vec<_>>();
+                let mut new_rows = Vec::new();
+                for (i, row) in rows.into_iter().enumerate() {
+                    if i % INTERRUPT_BATCH == 0 {
+                        txn.check_interrupt()?;
+                    }
+                    new_rows.push(
+                        exprs
+                            .iter()
+                            .map(|e| e.evaluate(&row))
+                            .collect::<Result<Vec<_>>>()?,
+                    );
+                }
+                txn.allocate_rows(&new_rows)?;
+                Ok(ResultSet::Scan {
+                    columns: self.columns,
+                    rows: new_rows,
+                })
+            }
+            _ => Err(Error::Internal("unexpected result set".to_string())),
+        }
+    }
+}
+
+// 输出执行计划，每行一列，JSON 格式只有一行
+pub struct Explain {
+    source: Node,
+    format: ExplainFormat,
+    verbose: bool,
+}
+
+impl Explain {
+    pub fn new(source: Node, format: ExplainFormat, verbose: bool) -> Box<Self> {
+        Box::new(Self {
+            source,
+            format,
+            verbose,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Explain {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let plan = ExplainNode::build(&self.source, txn, self.verbose)?;
+        let lines = match self.format {
+            ExplainFormat::Text => plan.to_text(),
+            ExplainFormat::Json => vec![plan.to_json()],
+        };
+        Ok(ResultSet::Scan {
+            columns: vec!["QUERY PLAN".to_string()],
+            rows: lines.into_iter().map(|l| vec![Value::String(l)]).collect(),
+        })
+    }
+}
@@ -1,6 +1,12 @@
 use crate::{
     error::Result,
-    sql::{engine::Transaction, schema::Table},
+    sql::{
+        engine::Transaction,
+        ident::Ident,
+        parser::{ast::Expression, quote_ident},
+        schema::{Column, Compression, Index, StorageLayout, Table, View},
+        types::Value,
+    },
 };
 
 use super::{Executor, ResultSet};
@@ -20,6 +26,450 @@ impl<T: Transaction> Executor<T> for CreateTable {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
         let table_name = self.schema.name.clone();
         txn.create_table(self.schema)?;
-        Ok(ResultSet::CreateTable { table_name })
+        Ok(ResultSet::CreateTable {
+            table_name: table_name.into(),
+        })
     }
 }
+
+// 创建视图
+pub struct CreateView {
+    view: View,
+}
+
+impl CreateView {
+    pub fn new(view: View) -> Box<Self> {
+        Box::new(Self { view })
+    }
+}
+
+impl<T: Transaction> Executor<T> for CreateView {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let view_name = self.view.name.clone();
+        txn.create_view(self.view)?;
+        Ok(ResultSet::CreateView {
+            view_name: view_name.into(),
+        })
+    }
+}
+
+// 删除视图
+pub struct DropView {
+    name: Ident,
+}
+
+impl DropView {
+    pub fn new(name: Ident) -> Box<Self> {
+        Box::new(Self { name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for DropView {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.drop_view(self.name.clone())?;
+        Ok(ResultSet::DropView {
+            view_name: self.name.into(),
+        })
+    }
+}
+
+// 在表的最后添加一列，已有的行使用默认值填充
+pub struct AddColumn {
+    table_name: Ident,
+    column: Column,
+}
+
+impl AddColumn {
+    pub fn new(table_name: Ident, column: Column) -> Box<Self> {
+        Box::new(Self { table_name, column })
+    }
+}
+
+impl<T: Transaction> Executor<T> for AddColumn {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.add_column(self.table_name.clone(), self.column)?;
+        Ok(ResultSet::AlterTable {
+            table_name: self.table_name.into(),
+        })
+    }
+}
+
+// 修改表名，返回新的表名
+pub struct RenameTable {
+    table_name: Ident,
+    new_name: Ident,
+}
+
+impl RenameTable {
+    pub fn new(table_name: Ident, new_name: Ident) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            new_name,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for RenameTable {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.rename_table(self.table_name, self.new_name.clone())?;
+        Ok(ResultSet::AlterTable {
+            table_name: self.new_name.into(),
+        })
+    }
+}
+
+// 修改列名
+pub struct RenameColumn {
+    table_name: Ident,
+    column: Ident,
+    new_name: Ident,
+}
+
+impl RenameColumn {
+    pub fn new(table_name: Ident, column: Ident, new_name: Ident) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            column,
+            new_name,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for RenameColumn {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.rename_column(self.table_name.clone(), self.column, self.new_name)?;
+        Ok(ResultSet::AlterTable {
+            table_name: self.table_name.into(),
+        })
+    }
+}
+
+// 收集表的统计信息
+pub struct Analyze {
+    table_name: Ident,
+}
+
+impl Analyze {
+    pub fn new(table_name: Ident) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Analyze {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let rows = txn.analyze_table(self.table_name.clone())?;
+        Ok(ResultSet::Analyze {
+            table_name: self.table_name.into(),
+            rows,
+        })
+    }
+}
+
+// 检查表的数据是否完整，每个问题作为一行返回
+pub struct CheckTable {
+    table_name: Ident,
+}
+
+impl CheckTable {
+    pub fn new(table_name: Ident) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for CheckTable {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let rows = txn
+            .check_table(self.table_name)?
+            .into_iter()
+            .map(|problem| vec![Value::String(problem)])
+            .collect();
+        Ok(ResultSet::Scan {
+            columns: vec!["problem".to_string()],
+            rows,
+        })
+    }
+}
+
+// 重建表的索引
+pub struct Reindex {
+    table_name: Ident,
+}
+
+impl Reindex {
+    pub fn new(table_name: Ident) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Reindex {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let rows = txn.reindex_table(self.table_name.clone())?;
+        Ok(ResultSet::Reindex {
+            table_name: self.table_name.into(),
+            rows,
+        })
+    }
+}
+
+// 创建二级索引，返回写入的索引项数量
+pub struct CreateIndex {
+    table_name: Ident,
+    index: Index,
+}
+
+impl CreateIndex {
+    pub fn new(table_name: Ident, index: Index) -> Box<Self> {
+        Box::new(Self { table_name, index })
+    }
+}
+
+impl<T: Transaction> Executor<T> for CreateIndex {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let index_name = self.index.name.clone();
+        let rows = txn.create_index(self.table_name, self.index)?;
+        Ok(ResultSet::CreateIndex {
+            index_name: index_name.into(),
+            rows,
+        })
+    }
+}
+
+// 删除二级索引
+pub struct DropIndex {
+    table_name: Ident,
+    name: Ident,
+}
+
+impl DropIndex {
+    pub fn new(table_name: Ident, name: Ident) -> Box<Self> {
+        Box::new(Self { table_name, name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for DropIndex {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.drop_index(self.table_name, self.name.clone())?;
+        Ok(ResultSet::DropIndex {
+            index_name: self.name.into(),
+        })
+    }
+}
+
+// 查看表的索引
+// 第一列作为主键的隐式索引，数据按照主键保存，查询还不会使用它
+// 之后是 CREATE INDEX 和 UNIQUE 列创建的二级索引，前几列等于常量以及之后一列的范围条件会使用它，第一列是 NULL 的行不在索引中
+pub struct ShowIndexes {
+    table_name: Ident,
+}
+
+impl ShowIndexes {
+    pub fn new(table_name: Ident) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for ShowIndexes {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table = txn.must_get_table(self.table_name.clone())?;
+        // 主键索引的条目数就是表中的行数，列存的表只需要读取主键列
+        let size = txn.scan_table_columns(self.table_name.clone(), &[0])?.len();
+        let mut rows = table
+            .columns
+            .first()
+            .map(|column| {
+                vec![
+                    Value::String("primary".to_string()),
+                    Value::String(column.name.to_string()),
+                    Value::Boolean(true),
+                    Value::Integer(size as i64),
+                    Value::Boolean(false),
+                ]
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+        for index in &table.indexes {
+            let first = index.columns[0];
+            let size = txn
+                .scan_table_columns(self.table_name.clone(), &[0, first])?
+                .iter()
+                .filter(|row| row[first] != Value::Null)
+                .count();
+            let columns = index
+                .columns
+                .iter()
+                .map(|&i| table.columns[i].name.to_string())
+                .collect::<Vec<_>>();
+            rows.push(vec![
+                Value::String(index.name.to_string()),
+                Value::String(columns.join(", ")),
+                Value::Boolean(index.unique),
+                Value::Integer(size as i64),
+                Value::Boolean(true),
+            ]);
+        }
+        Ok(ResultSet::Scan {
+            columns: ["name", "columns", "unique", "size", "usable"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            rows,
+        })
+    }
+}
+
+// 查看表的建表语句，从保存的表结构生成，第一行是 CREATE TABLE，之后每个 CREATE INDEX 创建的索引一行
+// UNIQUE 列的索引写成列的约束，和默认值相同的表选项不写在语句中
+pub struct ShowCreateTable {
+    table_name: Ident,
+}
+
+impl ShowCreateTable {
+    pub fn new(table_name: Ident) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for ShowCreateTable {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table = txn.must_get_table(self.table_name.clone())?;
+        let name = quote_ident(&table.name);
+        let columns = table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let mut sql = format!("{} {}", quote_ident(&column.name), column.datatype);
+                if !column.nullable {
+                    sql.push_str(" NOT NULL");
+                }
+                if let Some(default) = column_default(column) {
+                    sql.push_str(&format!(" DEFAULT {}", default));
+                }
+                if table
+                    .indexes
+                    .iter()
+                    .any(|index| index.unique && index.columns == [i])
+                {
+                    sql.push_str(" UNIQUE");
+                }
+                sql
+            })
+            .collect::<Vec<_>>();
+
+        let mut sql = match table.external {
+            Some(_) => format!("CREATE EXTERNAL TABLE {} ({})", name, columns.join(", ")),
+            None => format!("CREATE TABLE {} ({})", name, columns.join(", ")),
+        };
+        let mut options = Vec::new();
+        if let Some(external) = &table.external {
+            sql.push_str(&format!(" LOCATION '{}'", external.location));
+            if external.header {
+                options.push("header = 'true'".to_string());
+            }
+            if external.delimiter != ',' {
+                options.push(format!("delimiter = '{}'", external.delimiter));
+            }
+        }
+        if table.storage == StorageLayout::Columnar {
+            options.push("storage = 'columnar'".to_string());
+        }
+        if table.compression == Compression::Dictionary {
+            options.push("compression = 'dictionary'".to_string());
+        }
+        if let Some(ttl) = &table.ttl {
+            options.push(format!("ttl = '{}'", format_duration(ttl.seconds)));
+            options.push(format!("ttl_column = '{}'", table.columns[ttl.column].name));
+        }
+        if !options.is_empty() {
+            sql.push_str(&format!(" WITH ({})", options.join(", ")));
+        }
+        sql.push(';');
+
+        let mut rows = vec![vec![Value::String(sql)]];
+        for index in table.indexes.iter().filter(|index| !index.unique) {
+            let columns = index
+                .columns
+                .iter()
+                .map(|&i| quote_ident(&table.columns[i].name))
+                .collect::<Vec<_>>();
+            rows.push(vec![Value::String(match index.grid {
+                Some(size) => format!(
+                    "CREATE SPATIAL INDEX {} ON {} ({}) WITH (grid_size = {:?});",
+                    quote_ident(&index.name),
+                    name,
+                    columns.join(", "),
+                    size
+                ),
+                None => format!(
+                    "CREATE INDEX {} ON {} ({});",
+                    quote_ident(&index.name),
+                    name,
+                    columns.join(", ")
+                ),
+            })]);
+        }
+        Ok(ResultSet::Scan {
+            columns: vec!["statement".to_string()],
+            rows,
+        })
+    }
+}
+
+// 查看表的列，默认值是 SQL 表达式的文本，没有默认值或者默认值是 NULL 时是 NULL
+pub struct Describe {
+    table_name: Ident,
+}
+
+impl Describe {
+    pub fn new(table_name: Ident) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Describe {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table = txn.must_get_table(self.table_name.clone())?;
+        let rows = table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                vec![
+                    Value::String(column.name.to_string()),
+                    Value::String(column.datatype.to_string()),
+                    // 第一列是主键，不能为 NULL
+                    Value::Boolean(column.nullable && i != 0),
+                    column_default(column).map_or(Value::Null, Value::String),
+                ]
+            })
+            .collect();
+        Ok(ResultSet::Scan {
+            columns: ["column", "type", "nullable", "default"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            rows,
+        })
+    }
+}
+
+// 列的默认值在 SQL 中的写法，每次写入时计算的默认值是保存的表达式文本
+fn column_default(column: &Column) -> Option<String> {
+    match (&column.default_expr, &column.default) {
+        (Some(expr), _) => Some(expr.clone()),
+        (None, Some(value)) if *value != Value::Null => {
+            Some(Expression::from(value.clone()).to_string())
+        }
+        _ => None,
+    }
+}
+
+// 过期时间写成能整除的最大单位，例如 '7 days'
+fn format_duration(seconds: u64) -> String {
+    [("days", 24 * 60 * 60), ("hours", 60 * 60), ("minutes", 60)]
+        .into_iter()
+        .find(|(_, unit)| seconds.is_multiple_of(*unit))
+        .map_or(format!("{} seconds", seconds), |(name, unit)| {
+            format!("{} {}", seconds / unit, name)
+        })
+}
@@ -0,0 +1,781 @@
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        parser::ast,
+        types::{uuid::Uuid, Row, Value},
+    },
+    storage::mvcc::now_millis,
+};
+
+use Volatility::{Immutable, Volatile};
+
+// 调用内置的标量函数，参数已经计算成值
+pub fn call(name: &str, args: Vec<Value>) -> Result<Value> {
+    if LOCK_FUNCTIONS.contains(&name) {
+        return Err(Error::Internal(format!(
+            "lock function {} cannot be used in defaults, constraints or views",
+            name
+        )));
+    }
+    let Some((_, arity, _, f)) = SCALAR_FUNCTIONS.iter().find(|(n, ..)| *n == name) else {
+        return Err(Error::Internal(format!("unknown function {}", name)));
+    };
+    if !arity.contains(&args.len()) {
+        let expected = match (arity.start(), arity.end()) {
+            (start, end) if start == end => start.to_string(),
+            (start, end) => format!("{} to {}", start, end),
+        };
+        return Err(Error::Internal(format!(
+            "function {} expects {} arguments, got {}",
+            name,
+            expected,
+            args.len()
+        )));
+    }
+    // 参数是 NULL 时结果是 NULL
+    if args.contains(&Value::Null) {
+        return Ok(Value::Null);
+    }
+    f(name, &args)
+}
+
+// 标量函数的计算，参数的个数已经检查过，并且不包含 NULL
+type ScalarFunction = fn(&str, &[Value]) -> Result<Value>;
+
+// 函数的结果是否只由参数决定
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Volatility {
+    // 参数相同时结果相同，参数都是常量时可以在编译时计算，查询的结果可以缓存
+    Immutable,
+    // 每次调用的结果都可能不同，例如 now() 和 gen_uuid()
+    Volatile,
+}
+
+// 内置的标量函数、允许的参数个数和结果是否只由参数决定，添加函数时在这里注册
+const SCALAR_FUNCTIONS: &[(&str, RangeInclusive<usize>, Volatility, ScalarFunction)] = &[
+    // 空间函数
+    ("st_point", 2..=2, Immutable, |name, args| {
+        Ok(Value::Point(float(name, &args[0])?, float(name, &args[1])?))
+    }),
+    ("st_x", 1..=1, Immutable, |name, args| {
+        Ok(Value::Float(point(name, &args[0])?.0))
+    }),
+    ("st_y", 1..=1, Immutable, |name, args| {
+        Ok(Value::Float(point(name, &args[0])?.1))
+    }),
+    ("st_distance", 2..=2, Immutable, |name, args| {
+        Ok(Value::Float(distance(
+            point(name, &args[0])?,
+            point(name, &args[1])?,
+        )))
+    }),
+    ("st_dwithin", 3..=3, Immutable, |name, args| {
+        Ok(Value::Boolean(
+            distance(point(name, &args[0])?, point(name, &args[1])?) <= float(name, &args[2])?,
+        ))
+    }),
+    ("st_within", 5..=5, Immutable, |name, args| {
+        let (x, y) = point(name, &args[0])?;
+        let [xmin, ymin, xmax, ymax] = [
+            float(name, &args[1])?,
+            float(name, &args[2])?,
+            float(name, &args[3])?,
+            float(name, &args[4])?,
+        ];
+        Ok(Value::Boolean(
+            xmin <= x && x <= xmax && ymin <= y && y <= ymax,
+        ))
+    }),
+    // 字符串函数，长度和位置都按照字符计算
+    ("upper", 1..=1, Immutable, |name, args| {
+        Ok(Value::String(string(name, &args[0])?.to_uppercase()))
+    }),
+    ("lower", 1..=1, Immutable, |name, args| {
+        Ok(Value::String(string(name, &args[0])?.to_lowercase()))
+    }),
+    ("length", 1..=1, Immutable, |name, args| {
+        Ok(Value::Integer(
+            string(name, &args[0])?.chars().count() as i64
+        ))
+    }),
+    ("substr", 2..=3, Immutable, substr),
+    ("trim", 1..=2, Immutable, |name, args| {
+        let s = string(name, &args[0])?;
+        Ok(Value::String(match args.get(1) {
+            Some(chars) => {
+                let chars = string(name, chars)?;
+                s.trim_matches(|c| chars.contains(c)).to_string()
+            }
+            None => s.trim().to_string(),
+        }))
+    }),
+    ("replace", 3..=3, Immutable, |name, args| {
+        let (s, from, to) = (
+            string(name, &args[0])?,
+            string(name, &args[1])?,
+            string(name, &args[2])?,
+        );
+        // 空字符串不替换，否则会在每个字符之间插入 to
+        Ok(Value::String(match from.is_empty() {
+            true => s.to_string(),
+            false => s.replace(from, to),
+        }))
+    }),
+    // 数值函数，整数的结果超出范围时报错
+    ("abs", 1..=1, Immutable, |name, args| match &args[0] {
+        Value::Integer(i) => Ok(Value::Integer(i.checked_abs().ok_or(overflow())?)),
+        Value::Decimal(d) => Ok(Value::Decimal(d.abs())),
+        v => Ok(Value::Float(float(name, v)?.abs())),
+    }),
+    ("round", 1..=2, Immutable, round),
+    ("ceil", 1..=1, Immutable, |name, args| match &args[0] {
+        Value::Integer(i) => Ok(Value::Integer(*i)),
+        Value::Decimal(d) => Ok(Value::Decimal(d.ceil()?)),
+        v => Ok(Value::Float(float(name, v)?.ceil())),
+    }),
+    ("floor", 1..=1, Immutable, |name, args| match &args[0] {
+        Value::Integer(i) => Ok(Value::Integer(*i)),
+        Value::Decimal(d) => Ok(Value::Decimal(d.floor()?)),
+        v => Ok(Value::Float(float(name, v)?.floor())),
+    }),
+    // mod(a, b) 和 a % b 相同，power(a, b) 和 a ^ b 相同
+    ("mod", 2..=2, Immutable, |_, args| {
+        ast::remainder(args[0].clone(), args[1].clone())
+    }),
+    ("power", 2..=2, Immutable, |_, args| {
+        ast::exponentiate(args[0].clone(), args[1].clone())
+    }),
+    ("sqrt", 1..=1, Immutable, |name, args| {
+        match float(name, &args[0])? {
+            f if f < 0.0 => Err(Error::Internal(format!(
+                "cannot take square root of negative number {}",
+                f
+            ))),
+            f => Ok(Value::Float(f.sqrt())),
+        }
+    }),
+    // 随机生成第 4 版的 UUID
+    ("gen_uuid", 0..=0, Volatile, |_, _| {
+        Ok(Value::Uuid(Uuid::new_v4()))
+    }),
+    // 当前的 unix 时间戳（秒），和 TTL 的时间列使用相同的单位
+    ("now", 0..=0, Volatile, |_, _| {
+        Ok(Value::Integer((now_millis() / 1000) as i64))
+    }),
+];
+
+// 需要使用 session 状态的命名锁函数，由 session 在执行语句之前计算，结果替换成常量
+pub const LOCK_FUNCTIONS: [&str; 5] = [
+    "get_lock",
+    "get_xact_lock",
+    "release_lock",
+    "release_all_locks",
+    "is_free_lock",
+];
+
+// 每次调用的结果都可能不同的函数，参数都是常量时也不能在编译时计算，使用它的查询不使用查询缓存
+pub fn is_volatile(name: &str) -> bool {
+    LOCK_FUNCTIONS.contains(&name)
+        || SCALAR_FUNCTIONS
+            .iter()
+            .any(|(n, _, volatility, _)| *n == name && *volatility == Volatile)
+}
+
+// round(x [, digits])，四舍五入到小数点后 digits 位，digits 默认是 0，负数时舍入到整数的十位、百位等
+// 结果的类型和 x 相同
+fn round(name: &str, args: &[Value]) -> Result<Value> {
+    let digits = match args.get(1) {
+        Some(digits) => integer(name, digits)?,
+        None => 0,
+    };
+    match &args[0] {
+        Value::Integer(i) if digits >= 0 => Ok(Value::Integer(*i)),
+        Value::Integer(i) => {
+            // 使用 i128 计算，不会溢出，10^38 以上的位数结果都是 0
+            let unit = 10i128.pow(digits.unsigned_abs().min(38) as u32);
+            let i = *i as i128;
+            let rounded = match i >= 0 {
+                true => (i + unit / 2) / unit * unit,
+                false => (i - unit / 2) / unit * unit,
+            };
+            Ok(Value::Integer(rounded.try_into().map_err(|_| overflow())?))
+        }
+        Value::Decimal(d) => Ok(Value::Decimal(d.round(digits)?)),
+        v => {
+            let f = float(name, v)?;
+            let scale = 10f64.powi(digits.clamp(-308, 308) as i32);
+            // 位数很多时乘上 scale 会溢出，这时已经没有可以舍入的小数位
+            Ok(Value::Float(match (f * scale).is_finite() {
+                true => (f * scale).round() / scale,
+                false => f,
+            }))
+        }
+    }
+}
+
+// substr(s, start [, length])，start 从 1 开始，start 小于 1 时前面不存在的位置也计入 length
+fn substr(name: &str, args: &[Value]) -> Result<Value> {
+    let s = string(name, &args[0])?;
+    let start = integer(name, &args[1])?;
+    let end = match args.get(2) {
+        Some(length) => match integer(name, length)? {
+            length if length < 0 => {
+                return Err(Error::Internal(format!(
+                    "negative length {} for function {}",
+                    length, name
+                )))
+            }
+            length => start.saturating_add(length),
+        },
+        None => i64::MAX,
+    };
+    let skip = start.max(1) - 1;
+    let take = end.saturating_sub(start.max(1)).max(0);
+    Ok(Value::String(
+        s.chars()
+            .skip(skip.try_into().unwrap_or(usize::MAX))
+            .take(take.try_into().unwrap_or(usize::MAX))
+            .collect(),
+    ))
+}
+
+pub fn is_aggregate(name: &str) -> bool {
+    aggregate_arity(name, false).is_some()
+}
+
+// 聚合函数允许的参数个数，不是聚合函数时返回 None
+// 聚合的计算在 executor/aggregate.rs 中
+pub fn aggregate_arity(name: &str, distinct: bool) -> Option<RangeInclusive<usize>> {
+    Some(match name {
+        // count(DISTINCT a, b) 统计不同的参数组合
+        "count" if distinct => 1..=usize::MAX,
+        // count(*) 没有参数
+        "count" => 0..=1,
+        "sum" | "min" | "max" | "avg" => 1..=1,
+        // group_concat(expr [, separator])，默认使用逗号分隔
+        "group_concat" => 1..=2,
+        "string_agg" => 2..=2,
+        "variance" | "var_samp" | "var_pop" | "stddev" | "stddev_samp" | "stddev_pop" => 1..=1,
+        // percentile_cont(expr, fraction)，fraction 在 0 到 1 之间
+        "percentile_cont" | "approx_percentile" => 2..=2,
+        _ => return None,
+    })
+}
+
+// 表函数，在 FROM 中像表一样使用，例如 SELECT * FROM generate_series(1, 10)
+// 参数是常量表达式，生成执行计划时计算成值
+pub trait TableFunction: Send + Sync {
+    // 结果中的列名，规划查询时调用
+    fn columns(&self, args: &[Value]) -> Result<Vec<String>>;
+
+    // 生成结果的行，每一行的列数需要和 columns 一致
+    fn rows(&self, args: Vec<Value>) -> Result<Box<dyn Iterator<Item = Result<Row>> + '_>>;
+}
+
+// 引擎上注册的表函数，同一个引擎上的 session 共享，内置的表函数不需要注册
+#[derive(Default)]
+pub struct TableFunctions {
+    registered: Mutex<HashMap<String, Arc<dyn TableFunction>>>,
+}
+
+impl TableFunctions {
+    // 注册表函数，函数名和 SQL 中的标识符一样不区分大小写
+    pub fn register(&self, name: &str, function: Arc<dyn TableFunction>) -> Result<()> {
+        let name = name.to_lowercase();
+        let mut registered = self.registered.lock()?;
+        if registered.contains_key(&name) || builtin_table_function(&name).is_some() {
+            return Err(Error::Internal(format!(
+                "table function {} already exists",
+                name
+            )));
+        }
+        registered.insert(name, function);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<Arc<dyn TableFunction>>> {
+        Ok(match self.registered.lock()?.get(name) {
+            Some(function) => Some(function.clone()),
+            None => builtin_table_function(name),
+        })
+    }
+}
+
+// 内置的表函数
+pub fn builtin_table_function(name: &str) -> Option<Arc<dyn TableFunction>> {
+    match name {
+        "generate_series" => Some(Arc::new(GenerateSeries)),
+        _ => None,
+    }
+}
+
+// generate_series(start, stop [, step])，生成从 start 到 stop 的整数，包含 stop
+// step 默认是 1，可以是负数，任意参数是 NULL 时没有结果
+struct GenerateSeries;
+
+impl TableFunction for GenerateSeries {
+    fn columns(&self, args: &[Value]) -> Result<Vec<String>> {
+        if !(2..=3).contains(&args.len()) {
+            return Err(Error::Internal(format!(
+                "function generate_series expects 2 or 3 arguments, got {}",
+                args.len()
+            )));
+        }
+        if let Some(v) = args
+            .iter()
+            .find(|v| !matches!(v, Value::Integer(_) | Value::Null))
+        {
+            return Err(invalid_argument("generate_series", v));
+        }
+        if args.get(2) == Some(&Value::Integer(0)) {
+            return Err(Error::Internal(
+                "step of generate_series cannot be zero".to_string(),
+            ));
+        }
+        Ok(vec!["generate_series".to_string()])
+    }
+
+    fn rows(&self, args: Vec<Value>) -> Result<Box<dyn Iterator<Item = Result<Row>> + '_>> {
+        let args = args.iter().map(|v| match v {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        });
+        let (start, stop, step) = match args.collect::<Vec<_>>()[..] {
+            [Some(start), Some(stop)] => (start, stop, 1),
+            [Some(start), Some(stop), Some(step)] if step != 0 => (start, stop, step),
+            _ => return Ok(Box::new(std::iter::empty())),
+        };
+        // 使用 checked_add 避免接近 i64 边界时溢出
+        let series = std::iter::successors(Some(start), move |i| i.checked_add(step))
+            .take_while(move |i| if step > 0 { *i <= stop } else { *i >= stop });
+        Ok(Box::new(series.map(|i| Ok(vec![Value::Integer(i)]))))
+    }
+}
+
+// 保存在 REGEXP 运算中的正则表达式编译结果，表达式属于一个语句，
+// 模式不变时语句中的每一行都使用同一个编译结果
+#[derive(Debug, Default)]
+pub struct PatternCache {
+    #[cfg(feature = "regex")]
+    compiled: Mutex<Option<(String, regex::Regex)>>,
+}
+
+// 复制表达式时不复制缓存，第一次匹配时重新编译
+impl Clone for PatternCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+// 缓存不影响表达式是否相等
+impl PartialEq for PatternCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl PatternCache {
+    // text 中是否有和 pattern 匹配的部分
+    #[cfg(feature = "regex")]
+    pub fn is_match(&self, text: &str, pattern: &str) -> Result<bool> {
+        let mut compiled = self.compiled.lock()?;
+        let regex = match &mut *compiled {
+            Some((p, regex)) if p == pattern => regex,
+            compiled => {
+                let regex = regex::Regex::new(pattern).map_err(|e| {
+                    Error::Internal(format!("invalid regular expression {}: {}", pattern, e))
+                })?;
+                &compiled.insert((pattern.to_string(), regex)).1
+            }
+        };
+        Ok(regex.is_match(text))
+    }
+
+    #[cfg(not(feature = "regex"))]
+    pub fn is_match(&self, _text: &str, _pattern: &str) -> Result<bool> {
+        Err(Error::Internal(
+            "REGEXP requires the regex feature".to_string(),
+        ))
+    }
+}
+
+// LIKE 的模式匹配整个字符串，% 匹配任意个字符，_ 匹配一个字符，escape 之后的字符按照原样匹配
+pub fn like(text: &str, pattern: &str, escape: Option<char>) -> Result<bool> {
+    // 模式中的一项，None 表示 %，Some(None) 表示 _
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(c) => Some(Some(c)),
+                None => {
+                    return Err(Error::Internal(format!(
+                        "LIKE pattern {} must not end with the escape character",
+                        pattern
+                    )))
+                }
+            },
+            '%' => None,
+            '_' => Some(None),
+            c => Some(Some(c)),
+        });
+    }
+    let text = text.chars().collect::<Vec<_>>();
+    // 遇到 % 时记录位置，后面不匹配时让这个 % 多匹配一个字符再继续
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(None) => {
+                p += 1;
+                backtrack = Some((p, t));
+                continue;
+            }
+            Some(Some(None)) => {
+                (t, p) = (t + 1, p + 1);
+                continue;
+            }
+            Some(Some(Some(c))) if *c == text[t] => {
+                (t, p) = (t + 1, p + 1);
+                continue;
+            }
+            _ => {}
+        }
+        match backtrack {
+            Some((bp, bt)) => {
+                (t, p) = (bt + 1, bp);
+                backtrack = Some((bp, bt + 1));
+            }
+            None => return Ok(false),
+        }
+    }
+    Ok(tokens[p..].iter().all(Option::is_none))
+}
+
+fn distance((x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> f64 {
+    (x1 - x2).hypot(y1 - y2)
+}
+
+// 整数和 DECIMAL 参数转换为浮点数
+fn float(name: &str, value: &Value) -> Result<f64> {
+    match value {
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Float(f) => Ok(*f),
+        Value::Decimal(d) => Ok(d.to_f64()),
+        v => Err(invalid_argument(name, v)),
+    }
+}
+
+fn integer(name: &str, value: &Value) -> Result<i64> {
+    match value {
+        Value::Integer(i) => Ok(*i),
+        v => Err(invalid_argument(name, v)),
+    }
+}
+
+fn string<'a>(name: &str, value: &'a Value) -> Result<&'a str> {
+    match value {
+        Value::String(s) => Ok(s),
+        v => Err(invalid_argument(name, v)),
+    }
+}
+
+fn point(name: &str, value: &Value) -> Result<(f64, f64)> {
+    match value {
+        Value::Point(x, y) => Ok((*x, *y)),
+        v => Err(invalid_argument(name, v)),
+    }
+}
+
+fn overflow() -> Error {
+    Error::Internal("integer overflow".to_string())
+}
+
+fn invalid_argument(name: &str, value: &Value) -> Error {
+    Error::Internal(format!(
+        "invalid argument {:?} for function {}",
+        value, name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{call, TableFunction, TableFunctions};
+    use crate::{
+        error::Result,
+        sql::types::{decimal::Decimal, Row, Value},
+    };
+
+    #[test]
+    fn test_spatial_functions() -> Result<()> {
+        let p = call("st_point", vec![Value::Integer(0), Value::Float(0.5)])?;
+        assert_eq!(p, Value::Point(0.0, 0.5));
+        let q = Value::Point(3.0, 4.5);
+        assert_eq!(call("st_x", vec![q.clone()])?, Value::Float(3.0));
+        assert_eq!(call("st_y", vec![q.clone()])?, Value::Float(4.5));
+        assert_eq!(
+            call("st_distance", vec![p.clone(), q.clone()])?,
+            Value::Float(5.0)
+        );
+        assert_eq!(
+            call("st_dwithin", vec![p.clone(), q.clone(), Value::Integer(5)])?,
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            call("st_dwithin", vec![p.clone(), q.clone(), Value::Float(4.9)])?,
+            Value::Boolean(false)
+        );
+        let rect = |p: &Value| {
+            let mut args = vec![p.clone()];
+            args.extend([0, 0, 3, 3].map(Value::Integer));
+            call("st_within", args)
+        };
+        assert_eq!(rect(&p)?, Value::Boolean(true));
+        assert_eq!(rect(&q)?, Value::Boolean(false));
+
+        // NULL 参数
+        assert_eq!(
+            call("st_distance", vec![p.clone(), Value::Null])?,
+            Value::Null
+        );
+        // 参数错误
+        assert!(call("st_x", vec![Value::Integer(1)]).is_err());
+        assert!(call("st_point", vec![Value::Integer(1)]).is_err());
+        assert!(call("st_area", vec![p]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_functions() -> Result<()> {
+        let s = |s: &str| Value::String(s.to_string());
+        for (name, args, expected) in [
+            ("upper", vec![s("aBc中")], s("ABC中")),
+            ("lower", vec![s("aBc")], s("abc")),
+            ("length", vec![s("中文ab")], Value::Integer(4)),
+            ("length", vec![s("")], Value::Integer(0)),
+            ("substr", vec![s("hello"), Value::Integer(2)], s("ello")),
+            (
+                "substr",
+                vec![s("hello"), Value::Integer(2), Value::Integer(3)],
+                s("ell"),
+            ),
+            (
+                "substr",
+                vec![s("hello"), Value::Integer(0), Value::Integer(2)],
+                s("h"),
+            ),
+            (
+                "substr",
+                vec![s("hello"), Value::Integer(-5), Value::Integer(2)],
+                s(""),
+            ),
+            ("substr", vec![s("hello"), Value::Integer(9)], s("")),
+            (
+                "substr",
+                vec![s("中文字符"), Value::Integer(2), Value::Integer(2)],
+                s("文字"),
+            ),
+            ("trim", vec![s("  a b  ")], s("a b")),
+            ("trim", vec![s("xxaxyx"), s("xy")], s("a")),
+            ("replace", vec![s("a-b-c"), s("-"), s("+")], s("a+b+c")),
+            ("replace", vec![s("abc"), s(""), s("x")], s("abc")),
+            ("upper", vec![Value::Null], Value::Null),
+            ("substr", vec![s("abc"), Value::Null], Value::Null),
+        ] {
+            assert_eq!(call(name, args)?, expected, "{}", name);
+        }
+
+        // 参数的类型和个数不对
+        assert!(call("upper", vec![Value::Integer(1)]).is_err());
+        assert!(call("substr", vec![s("abc"), Value::Float(1.0)]).is_err());
+        assert!(call(
+            "substr",
+            vec![s("abc"), Value::Integer(1), Value::Integer(-1)]
+        )
+        .is_err());
+        assert!(call("length", vec![]).is_err());
+        assert!(call("trim", vec![s("a"), s("b"), s("c")]).is_err());
+        assert!(call("unknown", vec![]).is_err());
+        assert!(matches!(call("gen_uuid", vec![])?, Value::Uuid(_)));
+        assert!(call("gen_uuid", vec![Value::Integer(1)]).is_err());
+        assert!(matches!(call("now", vec![])?, Value::Integer(t) if t > 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_functions() -> Result<()> {
+        let (i, f) = (Value::Integer, Value::Float);
+        let d = |s: &str| Value::Decimal(Decimal::parse(s).unwrap());
+        for (name, args, expected) in [
+            ("abs", vec![i(-3)], i(3)),
+            ("abs", vec![f(-1.5)], f(1.5)),
+            ("round", vec![f(2.5)], f(3.0)),
+            ("round", vec![f(-2.5)], f(-3.0)),
+            ("round", vec![f(1.2345), i(2)], f(1.23)),
+            ("round", vec![f(1234.5), i(-2)], f(1200.0)),
+            ("round", vec![f(1.5), i(400)], f(1.5)),
+            ("round", vec![i(7)], i(7)),
+            ("round", vec![i(1250), i(-2)], i(1300)),
+            ("round", vec![i(-1250), i(-2)], i(-1300)),
+            ("round", vec![i(1249), i(-2)], i(1200)),
+            ("round", vec![i(i64::MAX), i(-40)], i(0)),
+            ("ceil", vec![f(1.2)], f(2.0)),
+            ("ceil", vec![f(-1.2)], f(-1.0)),
+            ("ceil", vec![i(5)], i(5)),
+            ("floor", vec![f(-1.2)], f(-2.0)),
+            ("floor", vec![i(5)], i(5)),
+            ("mod", vec![i(7), i(3)], i(1)),
+            ("mod", vec![i(-7), i(3)], i(-1)),
+            ("mod", vec![f(7.5), i(2)], f(1.5)),
+            ("power", vec![i(2), i(10)], i(1024)),
+            ("power", vec![i(2), i(-1)], f(0.5)),
+            ("power", vec![f(4.0), f(0.5)], f(2.0)),
+            ("sqrt", vec![i(16)], f(4.0)),
+            ("sqrt", vec![f(2.25)], f(1.5)),
+            ("sqrt", vec![Value::Null], Value::Null),
+            ("abs", vec![d("-1.50")], d("1.50")),
+            ("round", vec![d("2.345"), i(2)], d("2.35")),
+            ("round", vec![d("-2.345"), i(2)], d("-2.35")),
+            ("round", vec![d("2.5")], d("3")),
+            ("round", vec![d("1250.5"), i(-2)], d("1300")),
+            ("round", vec![d("1.5"), i(4)], d("1.5")),
+            ("ceil", vec![d("-1.5")], d("-1")),
+            ("ceil", vec![d("1.01")], d("2")),
+            ("floor", vec![d("-1.5")], d("-2")),
+            ("mod", vec![d("7.5"), i(2)], d("1.5")),
+            ("power", vec![d("1.5"), i(2)], d("2.25")),
+            ("power", vec![d("4"), f(0.5)], f(2.0)),
+            ("sqrt", vec![d("2.25")], f(1.5)),
+        ] {
+            assert_eq!(call(name, args.clone())?, expected, "{} {:?}", name, args);
+        }
+
+        // 整数溢出、除以 0 和参数的类型不对
+        for (name, args) in [
+            ("abs", vec![i(i64::MIN)]),
+            ("round", vec![i(i64::MAX), i(-1)]),
+            ("mod", vec![i(1), i(0)]),
+            ("power", vec![i(10), i(30)]),
+            ("sqrt", vec![i(-1)]),
+            ("abs", vec![Value::String("1".to_string())]),
+            ("round", vec![f(1.0), f(1.0)]),
+            ("round", vec![d(&"9".repeat(38)), i(-38)]),
+            ("mod", vec![d("1.5"), d("0")]),
+        ] {
+            assert!(call(name, args.clone()).is_err(), "{} {:?}", name, args);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_like() -> Result<()> {
+        for (text, pattern, expected) in [
+            ("abc", "abc", true),
+            ("abc", "ab", false),
+            ("abc", "a%", true),
+            ("abc", "%c", true),
+            ("abc", "%b%", true),
+            ("abc", "a_c", true),
+            ("abc", "a__c", false),
+            ("abc", "%", true),
+            ("", "%", true),
+            ("", "_", false),
+            ("aXbXc", "a%b%c", true),
+            ("aXbXd", "a%b%c", false),
+            ("mississippi", "%iss%ppi", true),
+            ("中文字符", "中_字%", true),
+            ("ABC", "abc", false),
+        ] {
+            assert_eq!(
+                super::like(text, pattern, None)?,
+                expected,
+                "{} {}",
+                text,
+                pattern
+            );
+        }
+        // 转义之后 % 和 _ 按照原样匹配
+        assert!(super::like("100%", "100!%", Some('!'))?);
+        assert!(!super::like("1000", "100!%", Some('!'))?);
+        assert!(super::like("a_b", "a\\_b", Some('\\'))?);
+        assert!(!super::like("axb", "a\\_b", Some('\\'))?);
+        assert!(super::like("a!b", "a!!b", Some('!'))?);
+        assert!(super::like("a%", "a!", Some('!')).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_pattern_cache() -> Result<()> {
+        let cache = super::PatternCache::default();
+        assert!(cache.is_match("abc", "^a.c$")?);
+        assert!(!cache.is_match("abd", "^a.c$")?);
+        // 模式改变之后重新编译
+        assert!(cache.is_match("abd", "d$")?);
+        assert!(cache.is_match("x(", "(").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_series() -> Result<()> {
+        let functions = TableFunctions::default();
+        let series = functions.get("generate_series")?.unwrap();
+        let rows = |args: Vec<i64>| -> Result<Vec<i64>> {
+            let args = args.into_iter().map(Value::Integer).collect::<Vec<_>>();
+            series.columns(&args)?;
+            series
+                .rows(args)?
+                .map(|row| match row?[..] {
+                    [Value::Integer(i)] => Ok(i),
+                    ref row => panic!("unexpected row {:?}", row),
+                })
+                .collect()
+        };
+        assert_eq!(rows(vec![1, 3])?, vec![1, 2, 3]);
+        assert_eq!(rows(vec![1, 6, 2])?, vec![1, 3, 5]);
+        assert_eq!(rows(vec![3, 1, -1])?, vec![3, 2, 1]);
+        assert_eq!(rows(vec![3, 1])?, Vec::<i64>::new());
+        assert_eq!(rows(vec![i64::MAX - 1, i64::MAX, 2])?, vec![i64::MAX - 1]);
+        assert!(rows(vec![1, 3, 0]).is_err());
+        assert!(rows(vec![1]).is_err());
+        assert!(series
+            .columns(&[Value::Integer(1), Value::Float(2.0)])
+            .is_err());
+        assert_eq!(
+            series.rows(vec![Value::Integer(1), Value::Null])?.count(),
+            0
+        );
+
+        // 注册自定义的表函数
+        struct Letters;
+        impl TableFunction for Letters {
+            fn columns(&self, _: &[Value]) -> Result<Vec<String>> {
+                Ok(vec!["letter".to_string()])
+            }
+
+            fn rows(&self, _: Vec<Value>) -> Result<Box<dyn Iterator<Item = Result<Row>> + '_>> {
+                Ok(Box::new(
+                    ["a", "b"]
+                        .map(|s| Ok(vec![Value::String(s.to_string())]))
+                        .into_iter(),
+                ))
+            }
+        }
+        functions.register("Letters", Arc::new(Letters))?;
+        assert_eq!(functions.get("letters")?.unwrap().rows(vec![])?.count(), 2);
+        assert!(functions.register("letters", Arc::new(Letters)).is_err());
+        assert!(functions
+            .register("generate_series", Arc::new(Letters))
+            .is_err());
+        assert!(functions.get("numbers")?.is_none());
+        Ok(())
+    }
+}
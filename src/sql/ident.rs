@@ -0,0 +1,135 @@
+use std::{borrow::Borrow, fmt::Display, ops::Deref, sync::Arc};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// 表名、列名等标识符，多个地方共享同一份字符串，clone 时不需要重新分配内存
+// 序列化的格式和 String 一致，保存在存储中的表信息和 key 的编码不受影响
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Ident(Arc<str>);
+
+impl Ident {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Ident {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Ident {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Ident {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Ident {
+    fn from(s: &str) -> Self {
+        Self(s.into())
+    }
+}
+
+impl From<String> for Ident {
+    fn from(s: String) -> Self {
+        Self(s.into())
+    }
+}
+
+impl From<&String> for Ident {
+    fn from(s: &String) -> Self {
+        Self(s.as_str().into())
+    }
+}
+
+impl From<Ident> for String {
+    fn from(ident: Ident) -> Self {
+        ident.0.to_string()
+    }
+}
+
+impl PartialEq<str> for Ident {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Ident {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Ident {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<Ident> for String {
+    fn eq(&self, other: &Ident) -> bool {
+        **self == *other.0
+    }
+}
+
+impl std::fmt::Debug for Ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl Display for Ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Ident {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ident {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(String::deserialize(deserializer)?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::error::Result;
+
+    use super::Ident;
+
+    #[test]
+    fn test_ident() -> Result<()> {
+        let ident = Ident::from("users");
+        let cloned = ident.clone();
+        assert!(std::ptr::eq(ident.as_str(), cloned.as_str()));
+        assert_eq!(ident, "users");
+        assert_eq!(ident, "users".to_string());
+        assert_eq!(format!("{} {:?}", ident, ident), "users \"users\"");
+
+        // 和 String 的编码一致
+        let encoded = bincode::serialize(&ident)?;
+        assert_eq!(encoded, bincode::serialize(&"users".to_string())?);
+        assert_eq!(bincode::deserialize::<Ident>(&encoded)?, ident);
+
+        // 可以直接使用 &str 查找
+        let map = HashMap::from([(ident, 1)]);
+        assert_eq!(map.get("users"), Some(&1));
+        Ok(())
+    }
+}
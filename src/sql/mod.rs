@@ -1,5 +1,8 @@
+pub mod builder;
 pub mod engine;
 pub mod executor;
+pub mod function;
+pub mod ident;
 pub mod parser;
 pub mod plan;
 pub mod schema;
@@ -1,35 +1,1468 @@
-use crate::sql::types::DataType;
+use std::fmt::Display;
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        function::{self, PatternCache},
+        ident::Ident,
+        types::{
+            decimal::{Decimal, MAX_PRECISION},
+            json::Json,
+            uuid::Uuid,
+            DataType, Row, Value,
+        },
+    },
+};
 
 // Abstract Syntax Tree 抽象语法树定义
-#[derive(Debug, PartialEq)]
+// 每条语句只解析一次，Select 的字段比其他语句多也不需要放到堆上
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Statement {
     CreateTable {
-        name: String,
+        name: Ident,
         columns: Vec<Column>,
+        // WITH 中指定的表选项
+        options: Vec<(String, Expression)>,
+        // 外部表的数据文件路径，CREATE EXTERNAL TABLE ... LOCATION 'path'
+        location: Option<String>,
     },
     Insert {
-        table_name: String,
+        table_name: Ident,
         columns: Option<Vec<String>>,
         values: Vec<Vec<Expression>>,
+        // INSERT ... SELECT 中的查询，这时 values 为空
+        query: Option<Box<Statement>>,
+    },
+    Values {
+        rows: Vec<Vec<Expression>>,
+    },
+    // 修改满足条件的行，columns 是列名和新的值
+    Update {
+        table_name: Ident,
+        columns: Vec<(String, Expression)>,
+        filter: Option<Expression>,
+    },
+    // 修改表结构
+    AlterTable {
+        table_name: Ident,
+        operation: AlterTableOperation,
+    },
+    // 创建视图，query 是解析之后的查询，text 是保存的查询语句
+    CreateView {
+        name: Ident,
+        columns: Vec<String>,
+        query: Box<Statement>,
+        text: String,
+    },
+    DropView {
+        name: Ident,
+    },
+    // 在表的一列或者多列上创建二级索引，spatial 表示在 POINT 列上创建空间索引
+    CreateIndex {
+        name: Ident,
+        table_name: Ident,
+        columns: Vec<String>,
+        spatial: bool,
+        // WITH 中指定的索引选项
+        options: Vec<(String, Expression)>,
+    },
+    DropIndex {
+        name: Ident,
+        table_name: Ident,
+    },
+    // 删除满足条件的行
+    Delete {
+        table_name: Ident,
+        filter: Option<Expression>,
     },
     Select {
-        table_name: String,
+        select: Vec<SelectItem>,
+        // SELECT DISTINCT 去掉结果中重复的行
+        distinct: bool,
+        // 没有 FROM 时为 None，select 列表中只能是常量表达式
+        table_name: Option<Ident>,
+        // FROM 中是表函数时为函数的参数，table_name 是函数名
+        table_args: Option<Vec<Expression>>,
+        // FROM 中是子查询或者 VALUES 时为子查询的语句，table_name 是子查询的别名
+        subquery: Option<Box<Statement>>,
+        // FROM 中表的别名，有别名时只能使用别名引用表中的列
+        alias: Option<Ident>,
+        // FROM 中和第一个表连接的其他表，按照出现的顺序连接
+        joins: Vec<Join>,
+        // 过滤条件
+        filter: Option<Expression>,
+        // 分组的表达式，为空并且 select 列表中有聚合函数时所有行是一个分组
+        group_by: Vec<Expression>,
+        // 分组之后的过滤条件，可以使用聚合函数
+        having: Option<Expression>,
+        // 排序的表达式和方向，在分组和聚合之后、LIMIT 之前排序
+        order_by: Vec<(Expression, Direction)>,
+        // 返回的最大行数
+        limit: Option<u64>,
+        // 历史查询的版本号
+        as_of: Option<u64>,
+    },
+    // 两个查询的集合运算，all 表示保留重复的行，结果的列名是左边查询的列名
+    SetOperation {
+        op: SetOperator,
+        all: bool,
+        left: Box<Statement>,
+        right: Box<Statement>,
+        // 对整个结果排序，只能使用结果中的列名
+        order_by: Vec<(Expression, Direction)>,
+    },
+    // 在 session 中开启事务，之后的语句都在这个事务中执行
+    Begin,
+    // 提交 session 中的事务
+    Commit,
+    // 回滚 session 中的事务
+    Rollback,
+    // 强制中止指定版本的活跃事务
+    RollbackTransaction {
+        version: u64,
+    },
+    // 检查点，持久化数据并清理失效的日志
+    Checkpoint,
+    // 查看表的索引
+    ShowIndexes {
+        table_name: Ident,
+    },
+    // 查看表的建表语句
+    ShowCreateTable {
+        table_name: Ident,
+    },
+    // 查看表的列
+    Describe {
+        table_name: Ident,
+    },
+    // 收集表的统计信息
+    Analyze {
+        table_name: Ident,
+    },
+    // 检查表的数据是否完整
+    CheckTable {
+        table_name: Ident,
+    },
+    // 重建表的索引，index 为 None 表示重建所有索引
+    Reindex {
+        table_name: Ident,
+        index: Option<String>,
+    },
+    // 设置 session 变量，global 表示设置全局变量
+    Set {
+        name: String,
+        value: Expression,
+        global: bool,
+    },
+    // 查看变量的值
+    ShowVariables {
+        global: bool,
+    },
+    // 取消 session 正在执行的语句
+    Kill {
+        session_id: u64,
+    },
+    // 在当前 session 中附加另一个数据库
+    Attach {
+        path: String,
+        alias: String,
     },
+    // 移除附加的数据库
+    Detach {
+        alias: String,
+    },
+    // 从 CSV 文件批量导入数据
+    Copy {
+        table_name: Ident,
+        path: String,
+        options: Vec<(String, Expression)>,
+    },
+    // 在线备份数据库到指定的文件
+    Backup {
+        path: String,
+        // 增量备份的起始版本
+        since: Option<u64>,
+        options: Vec<(String, Expression)>,
+    },
+    // 查看语句的执行计划
+    Explain {
+        statement: Box<Statement>,
+        format: ExplainFormat,
+        // 输出估算的行数和代价等信息
+        verbose: bool,
+    },
+}
+
+// EXPLAIN 的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExplainFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Select 列表中的一项
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    // 通配符 * 或者 t.*
+    Wildcard(Option<String>),
+    // 表达式和别名，expr [ AS ] alias
+    Expr(Expression, Option<String>),
+}
+
+// FROM 中连接的表，JOIN table_name [ [ AS ] alias ] ON predicate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub kind: JoinType,
+    pub table_name: Ident,
+    // 表函数的参数
+    pub table_args: Option<Vec<Expression>>,
+    // 子查询，table_name 是子查询的别名
+    pub subquery: Option<Box<Statement>>,
+    pub alias: Option<Ident>,
+    // 连接条件，可以引用前面的表和这个表中的列
+    pub predicate: Expression,
+}
+
+// 连接的类型，外连接时没有匹配的行和 NULL 组合
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinType {
+    Inner,
+    // 保留左边所有的行
+    Left,
+    // 保留右边所有的行
+    Right,
+    // 保留两边所有的行
+    Full,
+}
+
+// 集合运算的类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOperator {
+    // 两边的行合并在一起
+    Union,
+    // 两边都有的行
+    Intersect,
+    // 在左边但是不在右边的行
+    Except,
+}
+
+impl Display for SetOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Union => "UNION",
+            Self::Intersect => "INTERSECT",
+            Self::Except => "EXCEPT",
+        })
+    }
 }
 
 // 列定义
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Column {
-    pub name: String,
+    pub name: Ident,
     pub datatype: DataType,
     pub nullable: Option<bool>,
     pub default: Option<Expression>,
+    // DEFAULT 之后的表达式的原始文本，每次写入时计算的默认值保存它
+    pub default_text: Option<String>,
+    pub unique: bool,
 }
 
-// 表达式定义，目前只有常量
-#[derive(Debug, PartialEq)]
+// ALTER TABLE 对表结构的修改
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlterTableOperation {
+    // 在表的最后添加一列
+    AddColumn { column: Column },
+    RenameTable { new_name: Ident },
+    RenameColumn { column: Ident, new_name: Ident },
+}
+
+// 表达式定义
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
+    // 常量
     Consts(Consts),
+    // 列名，可以带上表名，例如 t.a
+    Field(Option<String>, String),
+    // 计划阶段将列名解析成的列下标
+    Column(usize),
+    // Insert 中的 DEFAULT，使用列的默认值
+    Default,
+    // 运算表达式
+    Operation(Operation),
+    // 函数调用，函数名和参数
+    Function(String, Vec<Expression>),
+    // 聚合函数
+    Aggregate(Aggregate),
+    // EXISTS 子查询，计划阶段改写成半连接或者反连接
+    Exists(Box<Statement>),
+    // expr IN (SELECT ...)，和 EXISTS 一样改写成半连接
+    InSubquery(Box<Expression>, Box<Statement>),
+    // 预处理语句中的参数，从 0 开始的下标，执行之前替换成参数的值
+    Parameter(usize),
+}
+
+// 聚合函数调用，例如 string_agg(name, ',' ORDER BY id DESC)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub name: String,
+    // 相同的参数只聚合一次
+    pub distinct: bool,
+    // count(*) 没有参数
+    pub args: Vec<Expression>,
+    // 聚合之前按照这些表达式对分组中的行排序
+    pub order_by: Vec<(Expression, Direction)>,
+}
+
+// 排序方向
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+// 运算符定义
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    // 逻辑运算
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+
+    // 比较运算
+    Equal(Box<Expression>, Box<Expression>),
+    NotEqual(Box<Expression>, Box<Expression>),
+    GreaterThan(Box<Expression>, Box<Expression>),
+    GreaterThanOrEqual(Box<Expression>, Box<Expression>),
+    LessThan(Box<Expression>, Box<Expression>),
+    LessThanOrEqual(Box<Expression>, Box<Expression>),
+    // 正则匹配，第三个字段缓存编译之后的正则表达式
+    Regexp(Box<Expression>, Box<Expression>, PatternCache),
+    // expr IN (expr, ...)
+    In(Box<Expression>, Vec<Expression>),
+    // expr IS NULL，IS NOT NULL 是 NOT (expr IS NULL)
+    IsNull(Box<Expression>),
+    // expr LIKE pattern [ ESCAPE escape ]
+    Like(Box<Expression>, Box<Expression>, Option<Box<Expression>>),
+
+    // 算术运算
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+    Remainder(Box<Expression>, Box<Expression>),
+    Exponentiate(Box<Expression>, Box<Expression>),
+    Negate(Box<Expression>),
+    Identity(Box<Expression>),
+    // CAST(expr AS type)
+    Cast(Box<Expression>, DataType),
+
+    // JSON 运算，json -> key 返回 JSON，json ->> key 返回文本，key 是对象的键或者数组的下标
+    JsonGet(Box<Expression>, Box<Expression>),
+    JsonGetText(Box<Expression>, Box<Expression>),
+}
+
+impl Operation {
+    // 运算的所有操作数
+    fn operands(&self) -> Vec<&Expression> {
+        match self {
+            Self::Not(e)
+            | Self::IsNull(e)
+            | Self::Negate(e)
+            | Self::Identity(e)
+            | Self::Cast(e, _) => vec![e],
+            Self::And(l, r)
+            | Self::Or(l, r)
+            | Self::Equal(l, r)
+            | Self::NotEqual(l, r)
+            | Self::GreaterThan(l, r)
+            | Self::GreaterThanOrEqual(l, r)
+            | Self::LessThan(l, r)
+            | Self::LessThanOrEqual(l, r)
+            | Self::Add(l, r)
+            | Self::Subtract(l, r)
+            | Self::Multiply(l, r)
+            | Self::Divide(l, r)
+            | Self::Remainder(l, r)
+            | Self::Exponentiate(l, r)
+            | Self::JsonGet(l, r)
+            | Self::JsonGetText(l, r)
+            | Self::Regexp(l, r, _) => vec![l, r],
+            Self::In(e, list) => std::iter::once(&**e).chain(list).collect(),
+            Self::Like(e, pattern, escape) => std::iter::once(&**e)
+                .chain(Some(&**pattern))
+                .chain(escape.as_deref())
+                .collect(),
+        }
+    }
+
+    // 使用 f 转换运算的每个操作数，例如在计划阶段解析列名
+    pub fn try_map<F: FnMut(Expression) -> Result<Expression>>(self, mut f: F) -> Result<Self> {
+        let mut map = |e: Box<Expression>| f(*e).map(Box::new);
+        Ok(match self {
+            Self::And(l, r) => Self::And(map(l)?, map(r)?),
+            Self::Or(l, r) => Self::Or(map(l)?, map(r)?),
+            Self::Not(e) => Self::Not(map(e)?),
+            Self::IsNull(e) => Self::IsNull(map(e)?),
+            Self::Equal(l, r) => Self::Equal(map(l)?, map(r)?),
+            Self::NotEqual(l, r) => Self::NotEqual(map(l)?, map(r)?),
+            Self::GreaterThan(l, r) => Self::GreaterThan(map(l)?, map(r)?),
+            Self::GreaterThanOrEqual(l, r) => Self::GreaterThanOrEqual(map(l)?, map(r)?),
+            Self::LessThan(l, r) => Self::LessThan(map(l)?, map(r)?),
+            Self::LessThanOrEqual(l, r) => Self::LessThanOrEqual(map(l)?, map(r)?),
+            Self::Regexp(l, r, cache) => Self::Regexp(map(l)?, map(r)?, cache),
+            Self::In(e, list) => Self::In(
+                map(e)?,
+                list.into_iter().map(&mut f).collect::<Result<_>>()?,
+            ),
+            Self::Like(e, pattern, escape) => {
+                Self::Like(map(e)?, map(pattern)?, escape.map(&mut map).transpose()?)
+            }
+            Self::Add(l, r) => Self::Add(map(l)?, map(r)?),
+            Self::Subtract(l, r) => Self::Subtract(map(l)?, map(r)?),
+            Self::Multiply(l, r) => Self::Multiply(map(l)?, map(r)?),
+            Self::Divide(l, r) => Self::Divide(map(l)?, map(r)?),
+            Self::Remainder(l, r) => Self::Remainder(map(l)?, map(r)?),
+            Self::Exponentiate(l, r) => Self::Exponentiate(map(l)?, map(r)?),
+            Self::Negate(e) => Self::Negate(map(e)?),
+            Self::Identity(e) => Self::Identity(map(e)?),
+            Self::Cast(e, datatype) => Self::Cast(map(e)?, datatype),
+            Self::JsonGet(l, r) => Self::JsonGet(map(l)?, map(r)?),
+            Self::JsonGetText(l, r) => Self::JsonGetText(map(l)?, map(r)?),
+        })
+    }
+
+    fn evaluate(&self, row: &Row) -> Result<Value> {
+        use std::cmp::Ordering;
+        match self {
+            Self::And(l, r) => and(l.evaluate(row)?, r.evaluate(row)?),
+            Self::Or(l, r) => or(l.evaluate(row)?, r.evaluate(row)?),
+            Self::Not(e) => not(e.evaluate(row)?),
+            Self::IsNull(e) => is_null(e.evaluate(row)?),
+            Self::Equal(l, r) => compare(&l.evaluate(row)?, &r.evaluate(row)?, Ordering::is_eq),
+            Self::NotEqual(l, r) => compare(&l.evaluate(row)?, &r.evaluate(row)?, Ordering::is_ne),
+            Self::GreaterThan(l, r) => {
+                compare(&l.evaluate(row)?, &r.evaluate(row)?, Ordering::is_gt)
+            }
+            Self::GreaterThanOrEqual(l, r) => {
+                compare(&l.evaluate(row)?, &r.evaluate(row)?, Ordering::is_ge)
+            }
+            Self::LessThan(l, r) => compare(&l.evaluate(row)?, &r.evaluate(row)?, Ordering::is_lt),
+            Self::LessThanOrEqual(l, r) => {
+                compare(&l.evaluate(row)?, &r.evaluate(row)?, Ordering::is_le)
+            }
+            Self::Regexp(l, r, cache) => regexp(cache, &l.evaluate(row)?, &r.evaluate(row)?),
+            Self::In(e, list) => in_list(
+                &e.evaluate(row)?,
+                list.iter().map(|item| item.evaluate(row)),
+            ),
+            Self::Like(e, pattern, escape) => like(
+                &e.evaluate(row)?,
+                &pattern.evaluate(row)?,
+                &escape
+                    .as_ref()
+                    .map_or(Ok(Value::Null), |e| e.evaluate(row))?,
+            ),
+            Self::Add(l, r) => evaluate_arithmetic(l, r, row, add),
+            Self::Subtract(l, r) => evaluate_arithmetic(l, r, row, subtract),
+            Self::Multiply(l, r) => evaluate_arithmetic(l, r, row, multiply),
+            Self::Divide(l, r) => evaluate_arithmetic(l, r, row, divide),
+            Self::Remainder(l, r) => evaluate_arithmetic(l, r, row, remainder),
+            Self::Exponentiate(l, r) => exponentiate(l.evaluate(row)?, r.evaluate(row)?),
+            Self::Negate(e) => negate(e.evaluate(row)?),
+            Self::Identity(e) => identity(e.evaluate(row)?),
+            Self::Cast(e, datatype) => e.evaluate(row)?.cast(*datatype),
+            Self::JsonGet(l, r) => json_get(l.evaluate(row)?, r.evaluate(row)?),
+            Self::JsonGetText(l, r) => json_get_text(l.evaluate(row)?, r.evaluate(row)?),
+        }
+    }
+
+    // 运算符的优先级，用于输出时判断是否需要加上括号
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Or(..) => 1,
+            Self::And(..) => 2,
+            Self::Not(_) => 3,
+            Self::Equal(..)
+            | Self::NotEqual(..)
+            | Self::GreaterThan(..)
+            | Self::GreaterThanOrEqual(..)
+            | Self::LessThan(..)
+            | Self::LessThanOrEqual(..)
+            | Self::Regexp(..)
+            | Self::In(..)
+            | Self::IsNull(_)
+            | Self::Like(..) => 4,
+            Self::Add(..) | Self::Subtract(..) => 5,
+            Self::Multiply(..) | Self::Divide(..) | Self::Remainder(..) => 6,
+            Self::Exponentiate(..) => 7,
+            Self::Negate(_) | Self::Identity(_) => 8,
+            Self::JsonGet(..) | Self::JsonGetText(..) => 9,
+            // 自带括号，操作数不需要再加上括号
+            Self::Cast(..) => 10,
+        }
+    }
+}
+
+// 运算符的计算，操作数已经求值，解释执行和编译之后的表达式共用
+// 逻辑运算使用三值逻辑，NULL 表示未知
+pub(super) fn and(l: Value, r: Value) -> Result<Value> {
+    Ok(match (l, r) {
+        (Value::Boolean(false), _) | (_, Value::Boolean(false)) => Value::Boolean(false),
+        (Value::Boolean(true), Value::Boolean(true)) => Value::Boolean(true),
+        (Value::Boolean(_) | Value::Null, Value::Boolean(_) | Value::Null) => Value::Null,
+        (l, r) => return Err(invalid_operands("AND", l, r)),
+    })
+}
+
+pub(super) fn or(l: Value, r: Value) -> Result<Value> {
+    Ok(match (l, r) {
+        (Value::Boolean(true), _) | (_, Value::Boolean(true)) => Value::Boolean(true),
+        (Value::Boolean(false), Value::Boolean(false)) => Value::Boolean(false),
+        (Value::Boolean(_) | Value::Null, Value::Boolean(_) | Value::Null) => Value::Null,
+        (l, r) => return Err(invalid_operands("OR", l, r)),
+    })
+}
+
+pub(super) fn not(v: Value) -> Result<Value> {
+    match v {
+        Value::Boolean(b) => Ok(Value::Boolean(!b)),
+        Value::Null => Ok(Value::Null),
+        v => Err(Error::Internal(format!("cannot apply NOT to {:?}", v))),
+    }
+}
+
+// 只有 IS NULL 的结果不会是 NULL
+pub(super) fn is_null(v: Value) -> Result<Value> {
+    Ok(Value::Boolean(v == Value::Null))
+}
+
+pub(super) fn regexp(cache: &PatternCache, l: &Value, r: &Value) -> Result<Value> {
+    match (l, r) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (Value::String(s), Value::String(p)) => Ok(Value::Boolean(cache.is_match(s, p)?)),
+        (l, r) => Err(invalid_operands("REGEXP", l.clone(), r.clone())),
+    }
+}
+
+// 没有 ESCAPE 时 escape 是 NULL，除此之外有 NULL 时结果为 NULL
+pub(super) fn like(v: &Value, pattern: &Value, escape: &Value) -> Result<Value> {
+    let escape = match escape {
+        Value::Null => None,
+        Value::String(s) if s.chars().count() == 1 => s.chars().next(),
+        v => {
+            return Err(Error::Internal(format!(
+                "ESCAPE must be a single character, got {:?}",
+                v
+            )))
+        }
+    };
+    match (v, pattern) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (Value::String(s), Value::String(p)) => Ok(Value::Boolean(function::like(s, p, escape)?)),
+        (v, p) => Err(invalid_operands("LIKE", v.clone(), p.clone())),
+    }
+}
+
+// 和列表中的值依次比较，有相等的值时为 true，否则有比较的结果是 NULL 时为 NULL
+pub(super) fn in_list(v: &Value, list: impl Iterator<Item = Result<Value>>) -> Result<Value> {
+    let mut result = Value::Boolean(false);
+    for item in list {
+        match compare(v, &item?, std::cmp::Ordering::is_eq)? {
+            Value::Boolean(true) => return Ok(Value::Boolean(true)),
+            Value::Null => result = Value::Null,
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+pub(super) fn add(l: Value, r: Value) -> Result<Value> {
+    arithmetic(
+        "+",
+        l,
+        r,
+        i64::checked_add,
+        |l, r| l + r,
+        Decimal::checked_add,
+    )
+}
+
+pub(super) fn subtract(l: Value, r: Value) -> Result<Value> {
+    arithmetic(
+        "-",
+        l,
+        r,
+        i64::checked_sub,
+        |l, r| l - r,
+        Decimal::checked_sub,
+    )
+}
+
+pub(super) fn multiply(l: Value, r: Value) -> Result<Value> {
+    arithmetic(
+        "*",
+        l,
+        r,
+        i64::checked_mul,
+        |l, r| l * r,
+        Decimal::checked_mul,
+    )
+}
+
+pub(super) fn divide(l: Value, r: Value) -> Result<Value> {
+    match (l, r) {
+        (Value::Integer(_), Value::Integer(0)) => {
+            Err(Error::Internal("division by zero".to_string()))
+        }
+        (l, r) => arithmetic(
+            "/",
+            l,
+            r,
+            i64::checked_div,
+            |l, r| l / r,
+            Decimal::checked_div,
+        ),
+    }
+}
+
+pub(crate) fn remainder(l: Value, r: Value) -> Result<Value> {
+    match (l, r) {
+        (Value::Integer(_), Value::Integer(0)) => {
+            Err(Error::Internal("division by zero".to_string()))
+        }
+        (l, r) => arithmetic(
+            "%",
+            l,
+            r,
+            i64::checked_rem,
+            |l, r| l % r,
+            Decimal::checked_rem,
+        ),
+    }
+}
+
+// 结果是无穷大或者 NaN 时报错，例如 0 的负数次方、负数的小数次方和溢出的浮点数
+pub(crate) fn exponentiate(l: Value, r: Value) -> Result<Value> {
+    match power(l.clone(), r.clone())? {
+        Value::Float(f) if !f.is_finite() => Err(Error::Internal(format!(
+            "result of {:?} ^ {:?} is not a finite number",
+            l, r
+        ))),
+        result => Ok(result),
+    }
+}
+
+fn power(l: Value, r: Value) -> Result<Value> {
+    match (l, r) {
+        // 负数的指数使用浮点数计算
+        (Value::Integer(l), Value::Integer(r)) if r < 0 => {
+            Ok(Value::Float((l as f64).powf(r as f64)))
+        }
+        // DECIMAL 的非负整数次方是精确的，其他情况转换成浮点数计算
+        (l @ Value::Decimal(_), r) | (l, r @ Value::Decimal(_))
+            if !matches!(r, Value::Integer(0..) | Value::Null)
+                && !matches!(r, Value::Decimal(d) if d.scale() == 0 && d.mantissa() >= 0) =>
+        {
+            let float = |v: Value| match v {
+                Value::Decimal(d) => Value::Float(d.to_f64()),
+                v => v,
+            };
+            power(float(l), float(r))
+        }
+        (l, r) => arithmetic(
+            "^",
+            l,
+            r,
+            |l, r| u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+            f64::powf,
+            |l, r| l.checked_pow(u32::try_from(r.mantissa()).unwrap_or(u32::MAX)),
+        ),
+    }
+}
+
+pub(super) fn negate(v: Value) -> Result<Value> {
+    match v {
+        Value::Integer(i) => Ok(Value::Integer(
+            i.checked_neg()
+                .ok_or(Error::Internal("integer overflow".to_string()))?,
+        )),
+        Value::Float(f) => Ok(Value::Float(-f)),
+        Value::Decimal(d) => Ok(Value::Decimal(d.neg())),
+        Value::Null => Ok(Value::Null),
+        v => Err(Error::Internal(format!("cannot negate {:?}", v))),
+    }
+}
+
+pub(super) fn identity(v: Value) -> Result<Value> {
+    match v {
+        v @ (Value::Integer(_) | Value::Float(_) | Value::Decimal(_) | Value::Null) => Ok(v),
+        v => Err(Error::Internal(format!("cannot apply + to {:?}", v))),
+    }
+}
+
+// json -> key，key 是字符串时获取对象的字段，是整数时获取数组的元素，负数从末尾开始数
+// 字段或者元素不存在时返回 NULL
+pub(super) fn json_get(l: Value, r: Value) -> Result<Value> {
+    Ok(json_field("->", &l, &r)?.map_or(Value::Null, |j| Value::Json(j.clone())))
+}
+
+// json ->> key，结果是字段的文本，字符串不带引号，JSON 的 null 返回 NULL
+pub(super) fn json_get_text(l: Value, r: Value) -> Result<Value> {
+    Ok(json_field("->>", &l, &r)?
+        .and_then(Json::to_text)
+        .map_or(Value::Null, Value::String))
+}
+
+fn json_field<'a>(op: &str, l: &'a Value, r: &Value) -> Result<Option<&'a Json>> {
+    match (l, r) {
+        (Value::Null, _) | (_, Value::Null) => Ok(None),
+        (Value::Json(j), Value::String(key)) => Ok(j.get(key)),
+        (Value::Json(j), Value::Integer(i)) => Ok(j.index(*i)),
+        (l, r) => Err(invalid_operands(op, l.clone(), r.clone())),
+    }
+}
+
+// 比较两个值，任意一个值是 NULL 时结果是 NULL，整数和浮点数可以互相比较
+pub(super) fn compare(l: &Value, r: &Value, f: fn(std::cmp::Ordering) -> bool) -> Result<Value> {
+    let ordering = match (l, r) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
+        (Value::Boolean(l), Value::Boolean(r)) => l.partial_cmp(r),
+        (Value::Integer(l), Value::Integer(r)) => l.partial_cmp(r),
+        (Value::Decimal(l), Value::Decimal(r)) => Some(l.cmp_numeric(r)),
+        (Value::Decimal(l), Value::Integer(r)) => Some(l.cmp_numeric(&Decimal::from(*r))),
+        (Value::Integer(l), Value::Decimal(r)) => Some(Decimal::from(*l).cmp_numeric(r)),
+        (Value::Decimal(l), Value::Float(r)) => l.to_f64().partial_cmp(r),
+        (Value::Float(l), Value::Decimal(r)) => l.partial_cmp(&r.to_f64()),
+        (Value::Integer(l), Value::Float(r)) => (*l as f64).partial_cmp(r),
+        (Value::Float(l), Value::Integer(r)) => l.partial_cmp(&(*r as f64)),
+        (Value::Float(l), Value::Float(r)) => l.partial_cmp(r),
+        (Value::String(l), Value::String(r)) => l.partial_cmp(r),
+        (Value::Point(lx, ly), Value::Point(rx, ry)) => (lx, ly).partial_cmp(&(rx, ry)),
+        (Value::Uuid(l), Value::Uuid(r)) => l.partial_cmp(r),
+        // 字符串常量和 UUID 比较时解析成 UUID，例如 id = 'a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11'
+        (Value::Uuid(l), Value::String(r)) => l.partial_cmp(&parse_uuid(r)?),
+        (Value::String(l), Value::Uuid(r)) => parse_uuid(l)?.partial_cmp(r),
+        (Value::Json(l), Value::Json(r)) => l.to_string().partial_cmp(&r.to_string()),
+        _ => {
+            return Err(Error::Internal(format!(
+                "cannot compare {:?} and {:?}",
+                l, r
+            )))
+        }
+    };
+    // NaN 和任何值比较的结果都是 false
+    Ok(Value::Boolean(ordering.is_some_and(f)))
+}
+
+fn parse_uuid(s: &str) -> Result<Uuid> {
+    Uuid::parse(s.trim()).ok_or(Error::Internal(format!("invalid UUID {}", s)))
+}
+
+fn evaluate_arithmetic(
+    l: &Expression,
+    r: &Expression,
+    row: &Row,
+    op: fn(Value, Value) -> Result<Value>,
+) -> Result<Value> {
+    let (lv, rv) = decimal_operands(
+        l.evaluate(row)?,
+        r.evaluate(row)?,
+        || decimal_literal(l),
+        || decimal_literal(r),
+    );
+    op(lv, rv)
+}
+
+// 浮点数常量按照 SQL 中的写法转换成 DECIMAL，不是浮点数常量或者超出 DECIMAL 的范围时返回 None
+pub(super) fn decimal_literal(e: &Expression) -> Option<Value> {
+    match e {
+        Expression::Consts(Consts::Float(f)) => Decimal::from_f64(*f).map(Value::Decimal),
+        _ => None,
+    }
+}
+
+// DECIMAL 和浮点数常量计算时使用常量转换成的 DECIMAL，例如 price + 0.1 的结果是精确的 DECIMAL
+// 浮点数的列和表达式仍然按照浮点数计算
+pub(super) fn decimal_operands(
+    l: Value,
+    r: Value,
+    l_literal: impl FnOnce() -> Option<Value>,
+    r_literal: impl FnOnce() -> Option<Value>,
+) -> (Value, Value) {
+    match (l, r) {
+        (l @ Value::Decimal(_), Value::Float(f)) => (l, r_literal().unwrap_or(Value::Float(f))),
+        (Value::Float(f), r @ Value::Decimal(_)) => (l_literal().unwrap_or(Value::Float(f)), r),
+        (l, r) => (l, r),
+    }
+}
+
+// 算术运算，整数运算溢出时返回错误，整数和浮点数运算的结果是浮点数
+fn arithmetic(
+    op: &str,
+    l: Value,
+    r: Value,
+    int: impl Fn(i64, i64) -> Option<i64>,
+    float: impl Fn(f64, f64) -> f64,
+    decimal: impl Fn(Decimal, Decimal) -> Result<Decimal>,
+) -> Result<Value> {
+    // 浮点数的结果是无穷大或者 NaN 时报错，例如溢出和 0.0 / 0
+    let float = |l: f64, r: f64| match float(l, r) {
+        f if f.is_finite() => Ok(f),
+        _ => Err(Error::Internal(format!(
+            "result of {:?} {} {:?} is not a finite number",
+            l, op, r
+        ))),
+    };
+    Ok(match (l, r) {
+        (Value::Null, _) | (_, Value::Null) => Value::Null,
+        (Value::Integer(l), Value::Integer(r)) => {
+            Value::Integer(int(l, r).ok_or(Error::Internal("integer overflow".to_string()))?)
+        }
+        (Value::Integer(l), Value::Float(r)) => Value::Float(float(l as f64, r)?),
+        (Value::Float(l), Value::Integer(r)) => Value::Float(float(l, r as f64)?),
+        (Value::Float(l), Value::Float(r)) => Value::Float(float(l, r)?),
+        // DECIMAL 和整数的计算结果是 DECIMAL，和浮点数的计算结果是浮点数
+        (Value::Decimal(l), Value::Decimal(r)) => Value::Decimal(decimal(l, r)?),
+        (Value::Decimal(l), Value::Integer(r)) => Value::Decimal(decimal(l, r.into())?),
+        (Value::Integer(l), Value::Decimal(r)) => Value::Decimal(decimal(l.into(), r)?),
+        (Value::Decimal(l), Value::Float(r)) => Value::Float(float(l.to_f64(), r)?),
+        (Value::Float(l), Value::Decimal(r)) => Value::Float(float(l, r.to_f64())?),
+        (l, r) => return Err(invalid_operands(op, l, r)),
+    })
+}
+
+fn invalid_operands(op: &str, l: Value, r: Value) -> Error {
+    Error::Internal(format!("cannot apply {} to {:?} and {:?}", op, l, r))
+}
+
+impl Statement {
+    // 查询中直接使用的表达式，不包括 FROM 中的子查询里的表达式
+    fn query_exprs(&self) -> Vec<&Expression> {
+        match self {
+            Self::Select {
+                select,
+                table_args,
+                joins,
+                filter,
+                group_by,
+                having,
+                order_by,
+                ..
+            } => {
+                let mut exprs = select
+                    .iter()
+                    .filter_map(|item| match item {
+                        SelectItem::Expr(expr, _) => Some(expr),
+                        SelectItem::Wildcard(_) => None,
+                    })
+                    .chain(table_args.iter().flatten())
+                    .chain(filter)
+                    .chain(group_by)
+                    .chain(having)
+                    .chain(order_by.iter().map(|(e, _)| e))
+                    .collect::<Vec<_>>();
+                for join in joins {
+                    exprs.push(&join.predicate);
+                    exprs.extend(join.table_args.iter().flatten());
+                }
+                exprs
+            }
+            Self::SetOperation { order_by, .. } => order_by.iter().map(|(e, _)| e).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // FROM 和 JOIN 中的子查询，以及集合运算两边的查询
+    fn source_queries(&self) -> Vec<&Statement> {
+        match self {
+            Self::Select {
+                subquery, joins, ..
+            } => subquery
+                .iter()
+                .chain(joins.iter().filter_map(|j| j.subquery.as_ref()))
+                .map(|s| s.as_ref())
+                .collect(),
+            Self::SetOperation { left, right, .. } => vec![left.as_ref(), right.as_ref()],
+            _ => Vec::new(),
+        }
+    }
+
+    // 查询中是否调用了结果不确定的函数，这样的查询每次执行的结果都可能不同，不能使用查询缓存
+    pub fn is_volatile(&self) -> bool {
+        self.query_exprs().into_iter().any(Expression::is_volatile)
+            || self.source_queries().into_iter().any(Self::is_volatile)
+    }
+
+    // 查询中是否读取了这个表或者视图，包括子查询中的，用于修改表结构时检查依赖的视图
+    pub fn references_table(&self, name: &str) -> bool {
+        let direct = match self {
+            Self::Select {
+                table_name: Some(table_name),
+                table_args: None,
+                subquery: None,
+                ..
+            } => table_name == name,
+            _ => false,
+        };
+        let joined = match self {
+            Self::Select { joins, .. } => joins
+                .iter()
+                .any(|j| j.table_args.is_none() && j.subquery.is_none() && j.table_name == name),
+            _ => false,
+        };
+        direct
+            || joined
+            || self
+                .query_exprs()
+                .into_iter()
+                .any(|e| e.references_table(name))
+            || self
+                .source_queries()
+                .into_iter()
+                .any(|s| s.references_table(name))
+    }
+
+    // 查询中是否使用了这个列名，不区分列属于哪个表，包括子查询中的
+    pub fn references_column(&self, name: &str) -> bool {
+        self.query_exprs()
+            .into_iter()
+            .any(|e| e.references_column(name))
+            || self
+                .source_queries()
+                .into_iter()
+                .any(|s| s.references_column(name))
+    }
+
+    // 使用参数的值替换语句中的参数，params[0] 是第一个参数
+    // 只替换查询和修改数据的语句中的表达式，其他语句中的参数在计算时报错
+    pub fn bind_parameters(self, params: &[Value]) -> Result<Self> {
+        self.transform(&mut |e| e.bind_parameter(params))
+    }
+
+    // 从下往上替换查询和修改数据的语句中的每个表达式，包括子查询中的表达式
+    pub fn transform(self, f: &mut dyn FnMut(Expression) -> Result<Expression>) -> Result<Self> {
+        fn all(
+            exprs: Vec<Expression>,
+            f: &mut dyn FnMut(Expression) -> Result<Expression>,
+        ) -> Result<Vec<Expression>> {
+            exprs.into_iter().map(|e| e.transform(f)).collect()
+        }
+        fn order(
+            order_by: Vec<(Expression, Direction)>,
+            f: &mut dyn FnMut(Expression) -> Result<Expression>,
+        ) -> Result<Vec<(Expression, Direction)>> {
+            order_by
+                .into_iter()
+                .map(|(e, direction)| Ok((e.transform(f)?, direction)))
+                .collect()
+        }
+        fn query(
+            stmt: Box<Statement>,
+            f: &mut dyn FnMut(Expression) -> Result<Expression>,
+        ) -> Result<Box<Statement>> {
+            stmt.transform(f).map(Box::new)
+        }
+        Ok(match self {
+            Self::Insert {
+                table_name,
+                columns,
+                values,
+                query: q,
+            } => Self::Insert {
+                table_name,
+                columns,
+                values: values
+                    .into_iter()
+                    .map(|row| all(row, f))
+                    .collect::<Result<_>>()?,
+                query: q.map(|s| query(s, f)).transpose()?,
+            },
+            Self::Values { rows } => Self::Values {
+                rows: rows
+                    .into_iter()
+                    .map(|row| all(row, f))
+                    .collect::<Result<_>>()?,
+            },
+            Self::Update {
+                table_name,
+                columns,
+                filter,
+            } => Self::Update {
+                table_name,
+                columns: columns
+                    .into_iter()
+                    .map(|(name, e)| Ok((name, e.transform(f)?)))
+                    .collect::<Result<_>>()?,
+                filter: filter.map(|e| e.transform(f)).transpose()?,
+            },
+            Self::Delete { table_name, filter } => Self::Delete {
+                table_name,
+                filter: filter.map(|e| e.transform(f)).transpose()?,
+            },
+            Self::Select {
+                select,
+                distinct,
+                table_name,
+                table_args,
+                subquery,
+                alias,
+                joins,
+                filter,
+                group_by,
+                having,
+                order_by,
+                limit,
+                as_of,
+            } => Self::Select {
+                select: select
+                    .into_iter()
+                    .map(|item| match item {
+                        SelectItem::Expr(e, alias) => Ok(SelectItem::Expr(e.transform(f)?, alias)),
+                        item => Ok(item),
+                    })
+                    .collect::<Result<_>>()?,
+                distinct,
+                table_name,
+                table_args: table_args.map(|args| all(args, f)).transpose()?,
+                subquery: subquery.map(|s| query(s, f)).transpose()?,
+                alias,
+                joins: joins
+                    .into_iter()
+                    .map(|join| {
+                        Ok(Join {
+                            table_args: join.table_args.map(|args| all(args, f)).transpose()?,
+                            subquery: join.subquery.map(|s| query(s, f)).transpose()?,
+                            predicate: join.predicate.transform(f)?,
+                            ..join
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+                filter: filter.map(|e| e.transform(f)).transpose()?,
+                group_by: all(group_by, f)?,
+                having: having.map(|e| e.transform(f)).transpose()?,
+                order_by: order(order_by, f)?,
+                limit,
+                as_of,
+            },
+            Self::SetOperation {
+                op,
+                all,
+                left,
+                right,
+                order_by,
+            } => Self::SetOperation {
+                op,
+                all,
+                left: query(left, f)?,
+                right: query(right, f)?,
+                order_by: order(order_by, f)?,
+            },
+            Self::Explain {
+                statement,
+                format,
+                verbose,
+            } => Self::Explain {
+                statement: query(statement, f)?,
+                format,
+                verbose,
+            },
+            stmt => stmt,
+        })
+    }
+
+    // 语句访问的表名，表名可以带上附加数据库的别名，例如 other.users
+    pub fn table_name_mut(&mut self) -> Option<&mut Ident> {
+        match self {
+            Self::CreateTable { name, .. } => Some(name),
+            Self::Insert { table_name, .. }
+            | Self::Update { table_name, .. }
+            | Self::Delete { table_name, .. }
+            | Self::AlterTable { table_name, .. }
+            | Self::ShowIndexes { table_name }
+            | Self::ShowCreateTable { table_name }
+            | Self::Describe { table_name }
+            | Self::Analyze { table_name }
+            | Self::CheckTable { table_name }
+            | Self::Copy { table_name, .. }
+            | Self::CreateIndex { table_name, .. }
+            | Self::DropIndex { table_name, .. }
+            | Self::Reindex { table_name, .. } => Some(table_name),
+            Self::Select { table_name, .. } => table_name.as_mut(),
+            Self::Explain { statement, .. } => statement.table_name_mut(),
+            _ => None,
+        }
+    }
+}
+
+impl Expression {
+    // 根据一行数据计算表达式的值
+    pub fn evaluate(&self, row: &Row) -> Result<Value> {
+        Ok(match self {
+            Self::Consts(Consts::Null) => Value::Null,
+            Self::Consts(Consts::Boolean(b)) => Value::Boolean(*b),
+            Self::Consts(Consts::Integer(i)) => Value::Integer(*i),
+            Self::Consts(Consts::Float(f)) => Value::Float(*f),
+            Self::Consts(Consts::String(s)) => Value::String(s.clone()),
+            Self::Column(i) => row
+                .get(*i)
+                .cloned()
+                .ok_or(Error::Internal(format!("column index {} out of bound", i)))?,
+            Self::Field(..) => {
+                return Err(Error::Internal(format!("unresolved column {}", self)));
+            }
+            Self::Default => {
+                return Err(Error::Internal(
+                    "DEFAULT is only allowed in INSERT values".to_string(),
+                ));
+            }
+            Self::Operation(op) => op.evaluate(row)?,
+            Self::Function(name, args) => function::call(
+                name,
+                args.iter()
+                    .map(|a| a.evaluate(row))
+                    .collect::<Result<_>>()?,
+            )?,
+            Self::Aggregate(agg) => {
+                return Err(Error::Internal(format!(
+                    "aggregate function {} is not allowed here",
+                    agg.name
+                )));
+            }
+            Self::Exists(_) | Self::InSubquery(..) => {
+                return Err(Error::Internal("subquery is not allowed here".to_string()));
+            }
+            Self::Parameter(_) => {
+                return Err(Error::Internal(format!("parameter {} is not bound", self)));
+            }
+        })
+    }
+}
+
+impl Expression {
+    // 参数替换成绑定的值，其他表达式不变
+    fn bind_parameter(self, params: &[Value]) -> Result<Self> {
+        match self {
+            Self::Parameter(i) => match params.get(i) {
+                Some(value) => Ok(value.clone().into()),
+                None => Err(Error::Internal(format!(
+                    "parameter ${} is not bound",
+                    i + 1
+                ))),
+            },
+            expr => Ok(expr),
+        }
+    }
+
+    // 先替换子表达式和子查询中的表达式，再使用 f 替换这个表达式
+    pub fn transform(self, f: &mut dyn FnMut(Expression) -> Result<Expression>) -> Result<Self> {
+        let expr = match self {
+            Self::Operation(op) => Self::Operation(op.try_map(|e| e.transform(f))?),
+            Self::Function(name, args) => Self::Function(
+                name,
+                args.into_iter()
+                    .map(|e| e.transform(f))
+                    .collect::<Result<_>>()?,
+            ),
+            Self::Aggregate(agg) => Self::Aggregate(Aggregate {
+                name: agg.name,
+                distinct: agg.distinct,
+                args: agg
+                    .args
+                    .into_iter()
+                    .map(|e| e.transform(f))
+                    .collect::<Result<_>>()?,
+                order_by: agg
+                    .order_by
+                    .into_iter()
+                    .map(|(e, direction)| Ok((e.transform(f)?, direction)))
+                    .collect::<Result<_>>()?,
+            }),
+            Self::Exists(stmt) => Self::Exists(Box::new(stmt.transform(f)?)),
+            Self::InSubquery(e, stmt) => {
+                Self::InSubquery(Box::new(e.transform(f)?), Box::new(stmt.transform(f)?))
+            }
+            expr => expr,
+        };
+        f(expr)
+    }
+
+    // 收集表达式中引用的列下标
+    pub fn collect_columns(&self, columns: &mut Vec<usize>) {
+        match self {
+            Self::Column(i) => columns.push(*i),
+            Self::Operation(op) => op
+                .operands()
+                .into_iter()
+                .for_each(|e| e.collect_columns(columns)),
+            Self::Function(_, args) => args.iter().for_each(|e| e.collect_columns(columns)),
+            Self::Aggregate(agg) => agg.exprs().for_each(|e| e.collect_columns(columns)),
+            Self::InSubquery(e, _) => e.collect_columns(columns),
+            Self::Consts(_)
+            | Self::Field(..)
+            | Self::Default
+            | Self::Exists(_)
+            | Self::Parameter(_) => {}
+        }
+    }
+
+    // 表达式中是否有聚合函数
+    pub fn contains_aggregate(&self) -> bool {
+        match self {
+            Self::Aggregate(_) => true,
+            Self::Operation(op) => op.operands().into_iter().any(Self::contains_aggregate),
+            Self::Function(_, args) => args.iter().any(Self::contains_aggregate),
+            Self::InSubquery(e, _) => e.contains_aggregate(),
+            Self::Consts(_)
+            | Self::Field(..)
+            | Self::Column(_)
+            | Self::Default
+            | Self::Exists(_)
+            | Self::Parameter(_) => false,
+        }
+    }
+
+    // 表达式中是否有子查询
+    pub fn contains_subquery(&self) -> bool {
+        match self {
+            Self::Exists(_) | Self::InSubquery(..) => true,
+            Self::Operation(op) => op.operands().into_iter().any(Self::contains_subquery),
+            Self::Function(_, args) => args.iter().any(Self::contains_subquery),
+            Self::Aggregate(agg) => agg.exprs().any(Self::contains_subquery),
+            Self::Consts(_)
+            | Self::Field(..)
+            | Self::Column(_)
+            | Self::Default
+            | Self::Parameter(_) => false,
+        }
+    }
+
+    // 表达式是否是常量，即不引用任何列
+    pub fn is_constant(&self) -> bool {
+        match self {
+            Self::Consts(_) | Self::Parameter(_) => true,
+            Self::Operation(op) => op.operands().into_iter().all(Self::is_constant),
+            Self::Function(_, args) => args.iter().all(Self::is_constant),
+            Self::Field(..)
+            | Self::Column(_)
+            | Self::Default
+            | Self::Aggregate(_)
+            | Self::Exists(_)
+            | Self::InSubquery(..) => false,
+        }
+    }
+
+    // 是否调用了结果不确定的函数，例如 gen_uuid()，包括聚合函数的参数和子查询中的表达式
+    pub fn is_volatile(&self) -> bool {
+        match self {
+            Self::Operation(op) => op.operands().into_iter().any(Self::is_volatile),
+            Self::Function(name, args) => {
+                function::is_volatile(name) || args.iter().any(Self::is_volatile)
+            }
+            Self::Aggregate(agg) => agg.exprs().any(Self::is_volatile),
+            Self::Exists(stmt) => stmt.is_volatile(),
+            Self::InSubquery(expr, stmt) => expr.is_volatile() || stmt.is_volatile(),
+            Self::Consts(_)
+            | Self::Field(..)
+            | Self::Column(_)
+            | Self::Default
+            | Self::Parameter(_) => false,
+        }
+    }
+
+    // 表达式的子查询中是否读取了这个表或者视图
+    pub fn references_table(&self, name: &str) -> bool {
+        match self {
+            Self::Operation(op) => op.operands().into_iter().any(|e| e.references_table(name)),
+            Self::Function(_, args) => args.iter().any(|e| e.references_table(name)),
+            Self::Aggregate(agg) => agg.exprs().any(|e| e.references_table(name)),
+            Self::Exists(stmt) => stmt.references_table(name),
+            Self::InSubquery(expr, stmt) => {
+                expr.references_table(name) || stmt.references_table(name)
+            }
+            Self::Consts(_)
+            | Self::Field(..)
+            | Self::Column(_)
+            | Self::Default
+            | Self::Parameter(_) => false,
+        }
+    }
+
+    // 表达式中是否使用了这个列名，包括子查询中的
+    pub fn references_column(&self, name: &str) -> bool {
+        match self {
+            Self::Field(_, column) => column == name,
+            Self::Operation(op) => op.operands().into_iter().any(|e| e.references_column(name)),
+            Self::Function(_, args) => args.iter().any(|e| e.references_column(name)),
+            Self::Aggregate(agg) => agg.exprs().any(|e| e.references_column(name)),
+            Self::Exists(stmt) => stmt.references_column(name),
+            Self::InSubquery(expr, stmt) => {
+                expr.references_column(name) || stmt.references_column(name)
+            }
+            Self::Consts(_) | Self::Column(_) | Self::Default | Self::Parameter(_) => false,
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Consts(c) => write!(f, "{}", c),
+            Self::Field(Some(table), name) => write!(f, "{}.{}", table, name),
+            Self::Field(None, name) => write!(f, "{}", name),
+            Self::Column(i) => write!(f, "#{}", i),
+            Self::Default => write!(f, "DEFAULT"),
+            Self::Operation(op) => write!(f, "{}", op),
+            Self::Function(name, args) => {
+                let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Self::Aggregate(agg) => write!(f, "{}", agg),
+            Self::Exists(_) => write!(f, "EXISTS (...)"),
+            Self::InSubquery(e, _) => write!(f, "{} IN (...)", e),
+            Self::Parameter(i) => write!(f, "${}", i + 1),
+        }
+    }
+}
+
+impl Aggregate {
+    // 参数和排序使用的所有表达式
+    pub fn exprs(&self) -> impl Iterator<Item = &Expression> {
+        self.args.iter().chain(self.order_by.iter().map(|(e, _)| e))
+    }
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args = self.args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let args = match (args.is_empty(), self.distinct) {
+            (true, _) => "*".to_string(),
+            (false, true) => format!("DISTINCT {}", args.join(", ")),
+            (false, false) => args.join(", "),
+        };
+        let order_by = self
+            .order_by
+            .iter()
+            .map(|(e, direction)| match direction {
+                Direction::Asc => e.to_string(),
+                Direction::Desc => format!("{} DESC", e),
+            })
+            .collect::<Vec<_>>();
+        if order_by.is_empty() {
+            write!(f, "{}({})", self.name, args)
+        } else {
+            write!(
+                f,
+                "{}({} ORDER BY {})",
+                self.name,
+                args,
+                order_by.join(", ")
+            )
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 操作数的优先级低于当前运算符时需要加上括号，优先级相同时根据结合方向判断
+        let operand = |e: &Expression, wrap_equal: bool| match e {
+            Expression::Operation(op)
+                if op.precedence() < self.precedence()
+                    || (op.precedence() == self.precedence() && wrap_equal) =>
+            {
+                format!("({})", e)
+            }
+            e => e.to_string(),
+        };
+        // 指数运算是右结合的
+        let exponentiate = matches!(self, Self::Exponentiate(..));
+        match self {
+            Self::Not(e) => write!(f, "NOT {}", operand(e, true)),
+            Self::Negate(e) => write!(f, "-{}", operand(e, true)),
+            Self::Identity(e) => write!(f, "+{}", operand(e, true)),
+            Self::IsNull(e) => write!(f, "{} IS NULL", operand(e, false)),
+            Self::Cast(e, datatype) => write!(f, "CAST({} AS {})", e, datatype),
+            Self::Regexp(l, r, _) => write!(f, "{} REGEXP {}", operand(l, false), operand(r, true)),
+            Self::In(e, list) => {
+                let list = list.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "{} IN ({})", operand(e, false), list.join(", "))
+            }
+            Self::Like(e, pattern, escape) => {
+                write!(f, "{} LIKE {}", operand(e, false), operand(pattern, true))?;
+                match escape {
+                    Some(escape) => write!(f, " ESCAPE {}", operand(escape, true)),
+                    None => Ok(()),
+                }
+            }
+            Self::And(l, r)
+            | Self::Or(l, r)
+            | Self::Equal(l, r)
+            | Self::NotEqual(l, r)
+            | Self::GreaterThan(l, r)
+            | Self::GreaterThanOrEqual(l, r)
+            | Self::LessThan(l, r)
+            | Self::LessThanOrEqual(l, r)
+            | Self::Add(l, r)
+            | Self::Subtract(l, r)
+            | Self::Multiply(l, r)
+            | Self::Divide(l, r)
+            | Self::Remainder(l, r)
+            | Self::Exponentiate(l, r)
+            | Self::JsonGet(l, r)
+            | Self::JsonGetText(l, r) => {
+                let op = match self {
+                    Self::And(..) => "AND",
+                    Self::Or(..) => "OR",
+                    Self::Equal(..) => "=",
+                    Self::NotEqual(..) => "!=",
+                    Self::GreaterThan(..) => ">",
+                    Self::GreaterThanOrEqual(..) => ">=",
+                    Self::LessThan(..) => "<",
+                    Self::LessThanOrEqual(..) => "<=",
+                    Self::Add(..) => "+",
+                    Self::Subtract(..) => "-",
+                    Self::Multiply(..) => "*",
+                    Self::Divide(..) => "/",
+                    Self::Remainder(..) => "%",
+                    Self::JsonGet(..) => "->",
+                    Self::JsonGetText(..) => "->>",
+                    _ => "^",
+                };
+                write!(
+                    f,
+                    "{} {} {}",
+                    operand(l, exponentiate),
+                    op,
+                    operand(r, !exponentiate)
+                )
+            }
+        }
+    }
+}
+
+// 参数的值转换成常量表达式，没有对应常量的类型使用字符串转换
+impl From<Value> for Expression {
+    fn from(value: Value) -> Self {
+        let cast = |s: String, datatype| {
+            Self::Operation(Operation::Cast(
+                Box::new(Consts::String(s).into()),
+                datatype,
+            ))
+        };
+        match value {
+            Value::Null => Consts::Null.into(),
+            Value::Boolean(b) => Consts::Boolean(b).into(),
+            Value::Integer(i) => Consts::Integer(i).into(),
+            Value::Float(f) => Consts::Float(f).into(),
+            Value::String(s) => Consts::String(s).into(),
+            Value::Point(x, y) => cast(format!("POINT({} {})", x, y), DataType::Point),
+            Value::Decimal(d) => cast(d.to_string(), DataType::Decimal(MAX_PRECISION, d.scale())),
+            Value::Uuid(u) => cast(u.to_string(), DataType::Uuid),
+            Value::Json(j) => cast(j.to_string(), DataType::Json),
+        }
+    }
 }
 
 impl From<Consts> for Expression {
@@ -38,7 +1471,7 @@ impl From<Consts> for Expression {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Consts {
     Null,
     Boolean(bool),
@@ -46,3 +1479,16 @@ pub enum Consts {
     Float(f64),
     String(String),
 }
+
+impl Display for Consts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Consts::Null => write!(f, "NULL"),
+            Consts::Boolean(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+            Consts::Integer(i) => write!(f, "{}", i),
+            // 使用 Debug 格式，总是带有小数点或者指数，重新解析之后仍然是浮点数
+            Consts::Float(v) => write!(f, "{:?}", v),
+            Consts::String(s) => write!(f, "'{}'", s),
+        }
+    }
+}
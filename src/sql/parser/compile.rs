@@ -0,0 +1,291 @@
+use std::{borrow::Cow, cmp::Ordering};
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        function::{self, PatternCache},
+        types::{Row, Value},
+    },
+};
+
+use super::ast::{self, Expression, Operation};
+
+// 编译之后的表达式，每条语句只编译一次，计算每一行时不需要再遍历和匹配表达式树
+// 列已经在计划阶段解析成下标，常量子表达式在编译时计算出结果
+pub struct CompiledExpression(Node);
+
+type Closure = Box<dyn Fn(&Row) -> Result<Value>>;
+
+enum Node {
+    Const(Value),
+    Column(usize),
+    // 计算时一定会返回的错误，例如没有解析的列名
+    Error(Error),
+    Closure(Closure),
+}
+
+impl CompiledExpression {
+    pub fn new(expr: &Expression) -> Self {
+        // 常量计算出错时保留到计算每一行时再报错，和解释执行的行为一致，没有行时不会报错
+        if expr.is_constant() && !expr.is_volatile() {
+            if let Ok(value) = expr.evaluate(&Vec::new()) {
+                return Self(Node::Const(value));
+            }
+        }
+        Self(match expr {
+            Expression::Column(i) => Node::Column(*i),
+            Expression::Consts(_)
+            | Expression::Field(..)
+            | Expression::Default
+            | Expression::Aggregate(_)
+            | Expression::Exists(_)
+            | Expression::InSubquery(..)
+            | Expression::Parameter(_) => match expr.evaluate(&Vec::new()) {
+                Ok(value) => Node::Const(value),
+                Err(e) => Node::Error(e),
+            },
+            Expression::Operation(op) => Self::operation(op),
+            Expression::Function(name, args) => {
+                let name = name.clone();
+                let args = args.iter().map(Self::new).collect::<Vec<_>>();
+                Node::Closure(Box::new(move |row| {
+                    function::call(
+                        &name,
+                        args.iter()
+                            .map(|a| a.evaluate(row))
+                            .collect::<Result<_>>()?,
+                    )
+                }))
+            }
+        })
+    }
+
+    // 根据一行数据计算表达式的值
+    pub fn evaluate(&self, row: &Row) -> Result<Value> {
+        self.borrow(row).map(Cow::into_owned)
+    }
+
+    // 常量和列直接借用，不需要复制，例如比较字符串时
+    fn borrow<'a>(&'a self, row: &'a Row) -> Result<Cow<'a, Value>> {
+        match &self.0 {
+            Node::Const(value) => Ok(Cow::Borrowed(value)),
+            Node::Column(i) => row
+                .get(*i)
+                .map(Cow::Borrowed)
+                .ok_or(Error::Internal(format!("column index {} out of bound", i))),
+            Node::Error(e) => Err(e.clone()),
+            Node::Closure(f) => f(row).map(Cow::Owned),
+        }
+    }
+
+    fn operation(op: &Operation) -> Node {
+        match op {
+            Operation::And(l, r) => Self::binary(l, r, ast::and),
+            Operation::Or(l, r) => Self::binary(l, r, ast::or),
+            Operation::Not(e) => Self::unary(e, ast::not),
+            Operation::IsNull(e) => Self::unary(e, ast::is_null),
+            Operation::Equal(l, r) => Self::compare(l, r, Ordering::is_eq),
+            Operation::NotEqual(l, r) => Self::compare(l, r, Ordering::is_ne),
+            Operation::GreaterThan(l, r) => Self::compare(l, r, Ordering::is_gt),
+            Operation::GreaterThanOrEqual(l, r) => Self::compare(l, r, Ordering::is_ge),
+            Operation::LessThan(l, r) => Self::compare(l, r, Ordering::is_lt),
+            Operation::LessThanOrEqual(l, r) => Self::compare(l, r, Ordering::is_le),
+            Operation::Regexp(l, r, _) => {
+                let (l, r) = (Self::new(l), Self::new(r));
+                let cache = PatternCache::default();
+                Node::Closure(Box::new(move |row| {
+                    let (l, r) = (l.borrow(row)?, r.borrow(row)?);
+                    ast::regexp(&cache, &l, &r)
+                }))
+            }
+            Operation::In(e, list) => {
+                let e = Self::new(e);
+                let list = list.iter().map(Self::new).collect::<Vec<_>>();
+                Node::Closure(Box::new(move |row| {
+                    let v = e.borrow(row)?;
+                    ast::in_list(&v, list.iter().map(|item| item.evaluate(row)))
+                }))
+            }
+            Operation::Like(e, pattern, escape) => {
+                let (e, pattern) = (Self::new(e), Self::new(pattern));
+                let escape = escape.as_deref().map(Self::new);
+                Node::Closure(Box::new(move |row| {
+                    let escape = match &escape {
+                        Some(escape) => escape.borrow(row)?,
+                        None => Cow::Owned(Value::Null),
+                    };
+                    let (e, pattern) = (e.borrow(row)?, pattern.borrow(row)?);
+                    ast::like(&e, &pattern, &escape)
+                }))
+            }
+            Operation::Add(l, r) => Self::arithmetic(l, r, ast::add),
+            Operation::Subtract(l, r) => Self::arithmetic(l, r, ast::subtract),
+            Operation::Multiply(l, r) => Self::arithmetic(l, r, ast::multiply),
+            Operation::Divide(l, r) => Self::arithmetic(l, r, ast::divide),
+            Operation::Remainder(l, r) => Self::arithmetic(l, r, ast::remainder),
+            Operation::Exponentiate(l, r) => Self::binary(l, r, ast::exponentiate),
+            Operation::Negate(e) => Self::unary(e, ast::negate),
+            Operation::Identity(e) => Self::unary(e, ast::identity),
+            Operation::JsonGet(l, r) => Self::binary(l, r, ast::json_get),
+            Operation::JsonGetText(l, r) => Self::binary(l, r, ast::json_get_text),
+            Operation::Cast(e, datatype) => {
+                let (e, datatype) = (Self::new(e), *datatype);
+                Node::Closure(Box::new(move |row| e.evaluate(row)?.cast(datatype)))
+            }
+        }
+    }
+
+    fn unary(e: &Expression, f: fn(Value) -> Result<Value>) -> Node {
+        let e = Self::new(e);
+        Node::Closure(Box::new(move |row| f(e.evaluate(row)?)))
+    }
+
+    fn binary(l: &Expression, r: &Expression, f: fn(Value, Value) -> Result<Value>) -> Node {
+        let (l, r) = (Self::new(l), Self::new(r));
+        Node::Closure(Box::new(move |row| f(l.evaluate(row)?, r.evaluate(row)?)))
+    }
+
+    // 浮点数常量在编译时转换成 DECIMAL，和 DECIMAL 计算时使用
+    fn arithmetic(l: &Expression, r: &Expression, f: fn(Value, Value) -> Result<Value>) -> Node {
+        let (l_literal, r_literal) = (ast::decimal_literal(l), ast::decimal_literal(r));
+        let (l, r) = (Self::new(l), Self::new(r));
+        Node::Closure(Box::new(move |row| {
+            let (l, r) = ast::decimal_operands(
+                l.evaluate(row)?,
+                r.evaluate(row)?,
+                || l_literal.clone(),
+                || r_literal.clone(),
+            );
+            f(l, r)
+        }))
+    }
+
+    fn compare(l: &Expression, r: &Expression, f: fn(Ordering) -> bool) -> Node {
+        let (l, r) = (Self::new(l), Self::new(r));
+        Node::Closure(Box::new(move |row| {
+            let (l, r) = (l.borrow(row)?, r.borrow(row)?);
+            ast::compare(&l, &r, f)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::{Error, Result},
+        sql::{
+            parser::{
+                ast::{Expression, Statement},
+                Parser,
+            },
+            types::Value,
+        },
+    };
+
+    use super::{CompiledExpression, Node};
+
+    // 解析 select 列表中的表达式，列名 a、b、c 依次解析成下标 0、1、2
+    fn parse(expr: &str) -> Result<Expression> {
+        fn resolve(expr: Expression) -> Result<Expression> {
+            match expr {
+                Expression::Field(None, name) => Ok(match name.as_str() {
+                    "a" => Expression::Column(0),
+                    "b" => Expression::Column(1),
+                    "c" => Expression::Column(2),
+                    _ => Expression::Field(None, name),
+                }),
+                Expression::Operation(op) => Ok(Expression::Operation(op.try_map(resolve)?)),
+                Expression::Function(name, args) => Ok(Expression::Function(
+                    name,
+                    args.into_iter().map(resolve).collect::<Result<_>>()?,
+                )),
+                expr => Ok(expr),
+            }
+        }
+        match Parser::new(&format!("values ({});", expr)).parse()? {
+            Statement::Values { mut rows } => resolve(rows.remove(0).remove(0)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_compiled_expression() -> Result<()> {
+        let row = vec![
+            Value::Integer(3),
+            Value::String("abc".to_string()),
+            Value::Null,
+        ];
+        for expr in [
+            "a + 1 * 2",
+            "-a ^ 2 - a / 2 % 2",
+            "a > 2 AND b = 'abc'",
+            "b <> 'x' OR c = 1",
+            "NOT c",
+            "st_x(st_point(a, 1.5)) + 1",
+            "a / 0",
+            "1 / 0 + a",
+            "b + 1",
+            "d",
+            "+b",
+            "9223372036854775807 + a",
+            "a IN (1, 3)",
+            "a IN (1, c)",
+            "a IN (1, 2)",
+            "NOT c IN (1, 2)",
+            "b IN (1)",
+            "c IS NULL",
+            "NOT a + 1 IS NULL",
+            "b IS NULL = c IS NULL",
+            "CAST(a AS STRING)",
+            "CAST(b AS INTEGER)",
+            "CAST(CAST(a AS FLOAT) / 2 AS INTEGER) + 1",
+            "CAST(c AS BOOLEAN)",
+            "b LIKE 'a%'",
+            "b LIKE '_b_'",
+            "NOT b LIKE 'x%'",
+            "b LIKE c",
+            "a LIKE 'a'",
+            "b LIKE 'a!%' ESCAPE '!'",
+            "b LIKE 'a%' ESCAPE 'xy'",
+            "CAST(a AS DECIMAL(5, 2)) * 1.5",
+            "CAST(a AS DECIMAL(5, 2)) / 7 + a",
+            "0.1 - CAST(a AS DECIMAL(5, 2)) % 1e300",
+            "CAST(a AS DECIMAL(5, 2)) > 2.99",
+            "-CAST(b AS DECIMAL(5, 2))",
+            "CAST('{\"a\": [1, \"x\"]}' AS JSON) -> 'a' ->> -1",
+            "CAST('[1]' AS JSON) -> a",
+            "CAST('[1]' AS JSON) -> c",
+            "b -> 'a'",
+        ] {
+            let expr = parse(expr)?;
+            assert_eq!(
+                CompiledExpression::new(&expr).evaluate(&row),
+                expr.evaluate(&row),
+                "{}",
+                expr
+            );
+        }
+
+        // 常量子表达式在编译时计算
+        let expr = parse("1 + 2 * 3")?;
+        assert!(matches!(
+            CompiledExpression::new(&expr).0,
+            Node::Const(Value::Integer(7))
+        ));
+        // gen_uuid() 每次计算的结果都不同
+        let expr = parse("gen_uuid()")?;
+        let compiled = CompiledExpression::new(&expr);
+        assert!(matches!(compiled.0, Node::Closure(_)));
+        assert_ne!(compiled.evaluate(&row)?, compiled.evaluate(&row)?);
+        // 出错的常量保留到计算时
+        let expr = parse("1 / 0")?;
+        assert_eq!(
+            CompiledExpression::new(&expr).evaluate(&row),
+            Err(Error::Internal("division by zero".to_string()))
+        );
+        assert!(CompiledExpression::new(&Expression::Column(5))
+            .evaluate(&row)
+            .is_err());
+        Ok(())
+    }
+}
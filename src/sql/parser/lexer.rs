@@ -28,6 +28,34 @@ pub enum Token {
     Minus,
     // 斜杠 /
     Slash,
+    // 点 .
+    Period,
+    // 等号 =
+    Equal,
+    // 不等号 !=
+    NotEqual,
+    // 不等号 <>
+    LessOrGreaterThan,
+    // 小于号 <
+    LessThan,
+    // 小于等于 <=
+    LessThanOrEqual,
+    // 大于号 >
+    GreaterThan,
+    // 大于等于 >=
+    GreaterThanOrEqual,
+    // 百分号 %
+    Percent,
+    // 脱字符 ^
+    Caret,
+    // 感叹号 !
+    Exclamation,
+    // 箭头 ->，获取 JSON 的字段
+    Arrow,
+    // 双箭头 ->>，获取 JSON 字段的文本
+    DoubleArrow,
+    // 预处理语句中的参数 ? 或者 $n
+    Parameter(String),
 }
 
 impl Display for Token {
@@ -37,6 +65,7 @@ impl Display for Token {
             Token::Ident(ident) => ident,
             Token::String(v) => v,
             Token::Number(n) => n,
+            Token::Parameter(p) => p,
             Token::OpenParen => "(",
             Token::CloseParen => ")",
             Token::Comma => ",",
@@ -45,6 +74,19 @@ impl Display for Token {
             Token::Plus => "+",
             Token::Minus => "-",
             Token::Slash => "/",
+            Token::Period => ".",
+            Token::Equal => "=",
+            Token::NotEqual => "!=",
+            Token::LessOrGreaterThan => "<>",
+            Token::LessThan => "<",
+            Token::LessThanOrEqual => "<=",
+            Token::GreaterThan => ">",
+            Token::GreaterThanOrEqual => ">=",
+            Token::Percent => "%",
+            Token::Caret => "^",
+            Token::Exclamation => "!",
+            Token::Arrow => "->",
+            Token::DoubleArrow => "->>",
         })
     }
 }
@@ -62,6 +104,11 @@ pub enum Keyword {
     Varchar,
     Float,
     Double,
+    Decimal,
+    Numeric,
+    Uuid,
+    Point,
+    Geometry,
     Select,
     From,
     Insert,
@@ -74,6 +121,78 @@ pub enum Keyword {
     Null,
     Primary,
     Key,
+    Unique,
+    As,
+    Of,
+    Version,
+    With,
+    Rollback,
+    Transaction,
+    Begin,
+    Commit,
+    Backup,
+    To,
+    Since,
+    Checkpoint,
+    Show,
+    Indexes,
+    Describe,
+    Analyze,
+    Set,
+    Kill,
+    And,
+    Or,
+    Global,
+    Variables,
+    Check,
+    Reindex,
+    Index,
+    Spatial,
+    On,
+    Attach,
+    Detach,
+    Database,
+    Regexp,
+    Group,
+    Having,
+    Join,
+    Exists,
+    In,
+    Between,
+    Like,
+    Cast,
+    Is,
+    Escape,
+    Inner,
+    Left,
+    Right,
+    Full,
+    Outer,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Distinct,
+    Union,
+    Intersect,
+    Except,
+    All,
+    External,
+    Location,
+    Copy,
+    Explain,
+    Format,
+    Verbose,
+    Json,
+    Where,
+    Update,
+    Delete,
+    Alter,
+    Add,
+    Column,
+    Rename,
+    View,
+    Drop,
 }
 
 impl Keyword {
@@ -90,6 +209,11 @@ impl Keyword {
             "VARCHAR" => Keyword::Varchar,
             "FLOAT" => Keyword::Float,
             "DOUBLE" => Keyword::Double,
+            "DECIMAL" => Keyword::Decimal,
+            "NUMERIC" => Keyword::Numeric,
+            "UUID" => Keyword::Uuid,
+            "POINT" => Keyword::Point,
+            "GEOMETRY" => Keyword::Geometry,
             "SELECT" => Keyword::Select,
             "FROM" => Keyword::From,
             "INSERT" => Keyword::Insert,
@@ -102,6 +226,78 @@ impl Keyword {
             "NULL" => Keyword::Null,
             "PRIMARY" => Keyword::Primary,
             "KEY" => Keyword::Key,
+            "UNIQUE" => Keyword::Unique,
+            "AS" => Keyword::As,
+            "OF" => Keyword::Of,
+            "VERSION" => Keyword::Version,
+            "WITH" => Keyword::With,
+            "ROLLBACK" => Keyword::Rollback,
+            "BEGIN" => Keyword::Begin,
+            "COMMIT" => Keyword::Commit,
+            "TRANSACTION" => Keyword::Transaction,
+            "BACKUP" => Keyword::Backup,
+            "TO" => Keyword::To,
+            "SINCE" => Keyword::Since,
+            "CHECKPOINT" => Keyword::Checkpoint,
+            "SHOW" => Keyword::Show,
+            "INDEXES" => Keyword::Indexes,
+            "DESCRIBE" => Keyword::Describe,
+            "ANALYZE" => Keyword::Analyze,
+            "SET" => Keyword::Set,
+            "KILL" => Keyword::Kill,
+            "AND" => Keyword::And,
+            "OR" => Keyword::Or,
+            "GLOBAL" => Keyword::Global,
+            "VARIABLES" => Keyword::Variables,
+            "CHECK" => Keyword::Check,
+            "REINDEX" => Keyword::Reindex,
+            "INDEX" => Keyword::Index,
+            "SPATIAL" => Keyword::Spatial,
+            "ON" => Keyword::On,
+            "ATTACH" => Keyword::Attach,
+            "DETACH" => Keyword::Detach,
+            "DATABASE" => Keyword::Database,
+            "REGEXP" => Keyword::Regexp,
+            "GROUP" => Keyword::Group,
+            "HAVING" => Keyword::Having,
+            "JOIN" => Keyword::Join,
+            "EXISTS" => Keyword::Exists,
+            "IN" => Keyword::In,
+            "BETWEEN" => Keyword::Between,
+            "LIKE" => Keyword::Like,
+            "CAST" => Keyword::Cast,
+            "IS" => Keyword::Is,
+            "ESCAPE" => Keyword::Escape,
+            "INNER" => Keyword::Inner,
+            "LEFT" => Keyword::Left,
+            "RIGHT" => Keyword::Right,
+            "FULL" => Keyword::Full,
+            "OUTER" => Keyword::Outer,
+            "ORDER" => Keyword::Order,
+            "BY" => Keyword::By,
+            "ASC" => Keyword::Asc,
+            "DESC" => Keyword::Desc,
+            "DISTINCT" => Keyword::Distinct,
+            "UNION" => Keyword::Union,
+            "INTERSECT" => Keyword::Intersect,
+            "EXCEPT" => Keyword::Except,
+            "ALL" => Keyword::All,
+            "EXTERNAL" => Keyword::External,
+            "LOCATION" => Keyword::Location,
+            "COPY" => Keyword::Copy,
+            "EXPLAIN" => Keyword::Explain,
+            "FORMAT" => Keyword::Format,
+            "VERBOSE" => Keyword::Verbose,
+            "JSON" => Keyword::Json,
+            "WHERE" => Keyword::Where,
+            "UPDATE" => Keyword::Update,
+            "DELETE" => Keyword::Delete,
+            "ALTER" => Keyword::Alter,
+            "ADD" => Keyword::Add,
+            "COLUMN" => Keyword::Column,
+            "RENAME" => Keyword::Rename,
+            "VIEW" => Keyword::View,
+            "DROP" => Keyword::Drop,
             _ => return None,
         })
     }
@@ -119,6 +315,11 @@ impl Keyword {
             Keyword::Varchar => "VARCHAR",
             Keyword::Float => "FLOAT",
             Keyword::Double => "DOUBLE",
+            Keyword::Decimal => "DECIMAL",
+            Keyword::Numeric => "NUMERIC",
+            Keyword::Uuid => "UUID",
+            Keyword::Point => "POINT",
+            Keyword::Geometry => "GEOMETRY",
             Keyword::Select => "SELECT",
             Keyword::From => "FROM",
             Keyword::Insert => "INSERT",
@@ -131,6 +332,78 @@ impl Keyword {
             Keyword::Null => "NULL",
             Keyword::Primary => "PRIMARY",
             Keyword::Key => "KEY",
+            Keyword::Unique => "UNIQUE",
+            Keyword::As => "AS",
+            Keyword::Of => "OF",
+            Keyword::Version => "VERSION",
+            Keyword::With => "WITH",
+            Keyword::Rollback => "ROLLBACK",
+            Keyword::Begin => "BEGIN",
+            Keyword::Commit => "COMMIT",
+            Keyword::Transaction => "TRANSACTION",
+            Keyword::Backup => "BACKUP",
+            Keyword::To => "TO",
+            Keyword::Since => "SINCE",
+            Keyword::Checkpoint => "CHECKPOINT",
+            Keyword::Show => "SHOW",
+            Keyword::Indexes => "INDEXES",
+            Keyword::Describe => "DESCRIBE",
+            Keyword::Analyze => "ANALYZE",
+            Keyword::Set => "SET",
+            Keyword::Kill => "KILL",
+            Keyword::And => "AND",
+            Keyword::Or => "OR",
+            Keyword::Global => "GLOBAL",
+            Keyword::Variables => "VARIABLES",
+            Keyword::Check => "CHECK",
+            Keyword::Reindex => "REINDEX",
+            Keyword::Index => "INDEX",
+            Keyword::Spatial => "SPATIAL",
+            Keyword::On => "ON",
+            Keyword::Attach => "ATTACH",
+            Keyword::Detach => "DETACH",
+            Keyword::Database => "DATABASE",
+            Keyword::Regexp => "REGEXP",
+            Keyword::Group => "GROUP",
+            Keyword::Having => "HAVING",
+            Keyword::Join => "JOIN",
+            Keyword::Exists => "EXISTS",
+            Keyword::In => "IN",
+            Keyword::Between => "BETWEEN",
+            Keyword::Like => "LIKE",
+            Keyword::Cast => "CAST",
+            Keyword::Is => "IS",
+            Keyword::Escape => "ESCAPE",
+            Keyword::Inner => "INNER",
+            Keyword::Left => "LEFT",
+            Keyword::Right => "RIGHT",
+            Keyword::Full => "FULL",
+            Keyword::Outer => "OUTER",
+            Keyword::Order => "ORDER",
+            Keyword::By => "BY",
+            Keyword::Asc => "ASC",
+            Keyword::Desc => "DESC",
+            Keyword::Distinct => "DISTINCT",
+            Keyword::Union => "UNION",
+            Keyword::Intersect => "INTERSECT",
+            Keyword::Except => "EXCEPT",
+            Keyword::All => "ALL",
+            Keyword::External => "EXTERNAL",
+            Keyword::Location => "LOCATION",
+            Keyword::Copy => "COPY",
+            Keyword::Explain => "EXPLAIN",
+            Keyword::Format => "FORMAT",
+            Keyword::Verbose => "VERBOSE",
+            Keyword::Json => "JSON",
+            Keyword::Where => "WHERE",
+            Keyword::Update => "UPDATE",
+            Keyword::Delete => "DELETE",
+            Keyword::Alter => "ALTER",
+            Keyword::Add => "ADD",
+            Keyword::Column => "COLUMN",
+            Keyword::Rename => "RENAME",
+            Keyword::View => "VIEW",
+            Keyword::Drop => "DROP",
         }
     }
 }
@@ -143,6 +416,15 @@ impl Display for Keyword {
 
 // 词法分析 Lexer 定义
 // 目前支持的 SQL 语法
+//
+// 关键字和没有引号的表名、列名等不区分大小写，名字会转换成小写
+// 使用双引号或者反引号括起来的名字保留原来的大小写，可以包含空格或者使用关键字，例如 "select"、`My Table`
+// 引号中连续的两个引号表示引号本身，表名中不能包含 .
+//
+// 数字可以写成整数 1、浮点数 1.5 或者 .5、科学计数法 1e10 或者 2.5E-3，以及十六进制整数 0xFF
+// 带小数点或者指数的数字是浮点数，十六进制整数不能超过 64 位有符号整数的范围，之后不能紧跟着字母或者下划线，例如 0x1g
+// 整数超出 64 位有符号整数的范围或者浮点数溢出成无穷大时报错，负号和之后的整数一起计算范围
+// 浮点数的运算和 sum 的结果是无穷大或者 NaN 时报错，字符串也不能转换成 inf 或者 NaN
 
 // 1. Create Table
 // -------------------------------------
@@ -156,20 +438,368 @@ impl Display for Keyword {
 //     - FLOAT(DOUBLE)
 //     - INTEGER(INT)
 //     - STRING(TEXT, VARCHAR)
+//     - POINT(GEOMETRY): 平面上的点，使用 st_point(x, y) 构造
+//     - DECIMAL(NUMERIC) [ ( precision [, scale] ) ]: 定点小数，默认是 DECIMAL(10, 0)，precision 最大是 38
+//       写入时按照 scale 四舍五入，整数部分超出范围时报错
+//     - UUID: 16 字节的 UUID，可以写成 'a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11' 格式的字符串，使用 gen_uuid() 生成
+//     - JSON: JSON 文档，写入的字符串必须是合法的 JSON
 //
 //    where column_constraint is:
-//    [ NOT NULL | NULL | DEFAULT expr ]
+//    [ NOT NULL | NULL | DEFAULT expr | UNIQUE ]
+//    DEFAULT 的 expr 是不引用列的表达式，结果的类型需要和列相同，不能使用参数，一般在建表时计算一次
+//    使用 now() 和 gen_uuid() 等每次结果都不同的函数时，每次写入没有指定这一列的行时重新计算
+//    UNIQUE 的列不能有值相同的两行，NULL 不和任何值重复，写入重复的值时报 constraint violation 错误
+//    约束通过名字是 table_column_key 的唯一索引检查，只能用于行存的表，第一列是主键，已经是唯一的
+//    TTL 过期但还没有被清理的行仍然占用它的值
+//
+//    [ WITH ( option_name [ = ] value [, ...] ) ]
+//    where option is:
+//     - storage = 'row' | 'columnar'
+//     - compression = 'none' | 'dictionary'
+//     - ttl = 'n seconds | minutes | hours | days'，行的过期时间，需要同时指定 ttl_column
+//     - ttl_column = 'column_name'，保存写入时间的整数列，单位是秒
+//
+// CREATE EXTERNAL TABLE table_name ( ... ) LOCATION 'file.csv'
+//    [ WITH ( option_name [ = ] value [, ...] ) ]
+//    数据保存在 CSV 文件中，查询时读取，表是只读的
+//    where option is:
+//     - header = true | false，第一行是否是列名，也可以写成字符串 'true' | 'false'，默认是 false
+//     - delimiter = 'c'，字段的分隔符，默认是逗号
 //
 // 2. Insert Into
 // -------------------------------------
 // INSERT INTO table_name
 // [ ( column_name [, ...] ) ]
-// values ( expr [, ...] );
-// 3. Select * From
+// { values ( { expr | DEFAULT } [, ...] ) [, ...] | DEFAULT VALUES | query };
+//    query 是 SELECT 查询，查询结果的每一行按照 values 中的一行写入，不能使用 AS OF
+//    值的类型和列不同时，整数自动转换成浮点数，其他类型需要使用 CAST 转换，UPDATE 和列的默认值也一样
+//    指定列时列名需要存在并且不能重复，值的个数和列相同，没有指定的列使用默认值
+//    没有指定列时值按照表中列的顺序对应，可以省略最后的几列，省略的列使用默认值
+//    没有默认值的列必须给出值，NOT NULL 的列不能是 NULL，有一行出错时整条语句不写入任何行
+//
+// 3. Values
+// -------------------------------------
+// VALUES ( expr [, ...] ) [, ...];
+//    结果的列名是 column1、column2 ...，也可以在 FROM 和 JOIN 中作为子查询使用
+//
+// 4. Select * From
+// -------------------------------------
+// SELECT [ DISTINCT ] [ * | table_name.* | expr [ [ AS ] alias ] ] [, ...]
+// FROM from_item [ join_type JOIN from_item ON expr [ ... ] ] [ AS OF VERSION version ]
+// [ WHERE expr ]
+// [ GROUP BY expr [, ...] ]
+// [ HAVING expr ]
+// [ ORDER BY expr [ ASC | DESC ] [, ...] ];
+//
+//    from_item 是 { table_name | table_function ( [ expr [, ...] ] ) } [ [ AS ] alias ]
+//    或者子查询 ( { SELECT ... | VALUES ... } ) [ AS ] alias，子查询必须有别名，列名是子查询结果中的列名
+//
+//    WHERE 只保留条件为 TRUE 的行，条件是 NULL 或者 FALSE 的行被过滤掉
+//
+//    JOIN 按照出现的顺序连接，只保留 ON 条件为 TRUE 的组合，ON 中可以引用前面的表和连接的表中的列
+//    join_type 是 [ INNER ] 或者 { LEFT | RIGHT | FULL } [ OUTER ]，外连接时没有匹配的行和 NULL 组合
+//    多个表中有相同的列名时需要带上表名，SELECT * 的列名是 table_name.column_name
+//
+//    DISTINCT 在排序之后去掉重复的行，每一行保留第一次出现的位置，NULL 和 NULL 相同
+//
+//    别名是结果中的列名，没有别名时列名是列的名字或者表达式，ORDER BY 中可以使用别名
+//    FROM 中的表可以指定别名，之后只能使用别名引用表中的列，例如 SELECT u.id FROM users u
+//
+//    ORDER BY 默认升序，在分组和聚合之后排序，有聚合时和 select 列表一样只能使用分组的列和聚合函数
+//    NULL 比其他值都小，升序时排在最前面，降序时排在最后面；整数和浮点数按照数值比较
+//    排序值相同的行保持原来的顺序
+//
+//    表函数的参数是常量表达式，结果和表一样使用，通过 KVEngine::register_table_function 注册
+//     - generate_series(start, stop [, step])，从 start 到 stop 的整数，列名是 generate_series
+//
+//    有 GROUP BY 或者聚合函数时，select 列表中聚合函数之外的列必须出现在 GROUP BY 中
+//    HAVING 在分组和聚合之后过滤分组，和 select 列表一样只能使用分组的列和聚合函数，没有 GROUP BY 时所有行是一个分组
+//    聚合函数跳过参数是 NULL 的行，可以在参数后面指定聚合之前的顺序：
+//    aggregate_function( { * | [ DISTINCT ] expr [, ...] } [ ORDER BY expr [ ASC | DESC ] [, ...] ] )
+//    DISTINCT 表示相同的参数只聚合一次
+//     - count(*)、count(expr)、count(DISTINCT expr [, ...])，多个参数时统计不同的组合，跳过有 NULL 的组合
+//     - sum(expr)、avg(expr)、min(expr)、max(expr)
+//     - group_concat(expr [, separator])，使用分隔符连接字符串，默认是逗号
+//     - string_agg(expr, separator)
+//     - variance(expr)、var_samp(expr)、var_pop(expr)，样本方差和总体方差，variance 是样本方差
+//     - stddev(expr)、stddev_samp(expr)、stddev_pop(expr)，标准差
+//     - percentile_cont(expr, fraction)，连续分位数，在相邻的两个值之间线性插值
+//     - approx_percentile(expr, fraction)，近似分位数，只使用固定大小的内存
+//
+// query { UNION | INTERSECT | EXCEPT } [ ALL ] query [ ... ] [ ORDER BY expr [ ASC | DESC ] [, ...] ];
+//    集合运算，两边查询的列数需要相同，结果的列名是第一个查询的列名
+//    每一列的类型需要相同或者都是数值，整数和浮点数一起出现时整数转换成浮点数，NULL 和任何类型兼容
+//    UNION 合并两边的行，INTERSECT 保留两边都有的行，EXCEPT 保留在左边但是不在右边的行
+//    没有 ALL 时去掉结果中重复的行，保留第一次出现的位置；ALL 时按照行出现的次数计算
+//    INTERSECT 的优先级比 UNION 和 EXCEPT 高，相同优先级时从左到右计算
+//    ORDER BY 只能出现在最后一个查询之后，对整个结果排序，只能使用结果中的列名
+//
+// SELECT expr [ [ AS ] alias ] [, ...] [ WHERE expr ];
+//    没有 FROM 时只计算常量表达式，返回一行，WHERE 条件不成立时不返回行
+//
+//    命名锁函数，命名锁属于 session，session 结束时自动释放
+//    每条语句执行之前计算一次，参数需要是常量，不能在默认值、约束和视图中使用：
+//     - get_lock('name', timeout)，等待 timeout 秒，负数表示一直等待，获取成功返回 1，超时返回 0
+//     - get_xact_lock('name', timeout)，和 get_lock 相同，在事务结束时释放，没有 BEGIN 时在语句结束时释放
+//     - release_lock('name')，释放成功返回 1，锁被其他 session 持有或者只在事务中获取时返回 0，锁没有被持有返回 NULL
+//     - release_all_locks()，释放当前 session 持有的所有锁，返回释放的次数
+//     - is_free_lock('name')，锁没有被持有时返回 1，否则返回 0
+//
+//    空间函数，参数是 NULL 时返回 NULL：
+//     - st_point(x, y)，构造一个点
+//     - st_x(p)、st_y(p)，点的坐标
+//     - st_distance(p, q)，两点之间的距离
+//     - st_dwithin(p, q, d)，两点之间的距离是否不超过 d
+//     - st_within(p, xmin, ymin, xmax, ymax)，点是否在矩形范围内，包括边界
+//
+//    字符串函数，参数是 NULL 时返回 NULL，长度和位置按照字符计算：
+//     - upper(s)、lower(s)，转换成大写或者小写
+//     - length(s)，字符个数
+//     - substr(s, start [, length])，从第 start 个字符开始的子串，start 从 1 开始
+//     - trim(s [, chars])，去掉两端的空白，或者去掉两端在 chars 中的字符
+//     - replace(s, from, to)，把所有的 from 替换成 to
+//
+//    数值函数，参数是 NULL 时返回 NULL，整数的结果超出范围时报错：
+//     - abs(x)，绝对值
+//     - round(x [, digits])，四舍五入到小数点后 digits 位，digits 是负数时舍入到十位、百位等
+//     - ceil(x)、floor(x)，向上或者向下取整，整数的结果不变
+//     - mod(a, b)、power(a, b)，和 a % b、a ^ b 相同，乘方的结果是无穷大或者 NaN 时报错，例如 power(0, -1)
+//     - sqrt(x)，平方根，结果是浮点数，x 不能是负数
+//     除了 sqrt 和 power 的负数指数，整数参数的结果是整数，有浮点数参数时结果是浮点数
+//
+//    gen_uuid()：随机生成第 4 版的 UUID，每一行的结果都不同
+//    now()：当前的 unix 时间戳（秒），整数，可以作为 TTL 时间列的默认值
+//
+//    CAST(expr AS type)：
+//     转换成另一种类型，NULL 转换之后还是 NULL，不能转换时报错
+//     布尔值和整数、整数和浮点数之间可以互相转换，浮点数转换成整数时四舍五入
+//     所有类型都可以和字符串互相转换，字符串两端的空白被忽略，布尔值是 true 和 false，点是 POINT(x y)
+//     整数、浮点数和 DECIMAL 之间可以互相转换，转换成 DECIMAL 时按照 scale 四舍五入
+//     DECIMAL 和整数运算的结果是精确的 DECIMAL，和浮点数运算的结果是浮点数
+//     浮点数常量按照写法转换成 DECIMAL 计算，例如 price + 0.1 的结果是 DECIMAL，DECIMAL 的 avg 也是 DECIMAL
+//
+//    json -> key 和 json ->> key：
+//     获取 JSON 对象的字段或者数组的元素，key 是字符串时是对象的键，是整数时是数组的下标，负数从末尾开始数
+//     -> 的结果是 JSON，->> 的结果是文本，字符串不带引号，不存在的字段和 JSON 的 null 返回 NULL
+//     可以连续使用，例如 doc -> 'items' -> 0 ->> 'name'
+//
+//    expr REGEXP 'pattern' 或者 regexp_matches(expr, 'pattern')：
+//     字符串中有和正则表达式匹配的部分时返回 true，需要开启 regex feature
+//
+//    expr [ NOT ] IN ( expr [, ...] )：
+//     和列表中的某个值相等时为 true，否则列表中有 NULL 或者 expr 是 NULL 时为 NULL
+//
+//    expr [ NOT ] BETWEEN low AND high：
+//     等价于 expr >= low AND expr <= high，包括两端的值，low 大于 high 时为 false
+//
+//    expr IS [ NOT ] NULL：
+//     expr 是 NULL 时 IS NULL 为 true，否则为 false，结果不会是 NULL，expr = NULL 的结果总是 NULL
+//
+//    expr [ NOT ] LIKE 'pattern' [ ESCAPE 'c' ]：
+//     整个字符串和模式匹配时为 true，% 匹配任意个字符，_ 匹配一个字符，区分大小写
+//     ESCAPE 指定一个转义字符，转义字符之后的 %、_ 或者转义字符本身按照原样匹配
+//
+//    [ NOT ] EXISTS ( SELECT ... ) 和 expr [ NOT ] IN ( SELECT ... )：
+//     只能出现在 WHERE 中，和其他条件使用 AND 连接，EXISTS 在子查询有结果时为 true
+//     IN 的子查询只能返回一列，NULL 的处理和值列表相同，NOT IN 在子查询的结果中有 NULL 时不返回行
+//     子查询的 WHERE 中可以引用外层查询的列，列名优先在子查询的表中查找
+//     有分组或者聚合函数的子查询不能引用外层查询的列
+//     子查询只执行一次，IN 的值或者外层的列等于子查询的列的条件用来查找匹配的行
+//
+//    参数 ? 或者 $n：
+//     通过 Session::prepare 解析语句，Session::execute_prepared 传入参数的值执行，参数的值不会作为 SQL 文本解析
+//     ? 按照出现的顺序编号，$n 是第 n 个参数，从 1 开始，可以重复使用，同一条语句中不能混用两种写法
+//     参数可以出现在表达式中，不能出现在 CREATE VIEW 中，执行时参数的数量需要和语句中的一致
+//
+// 5. Transaction
+// -------------------------------------
+// BEGIN [ TRANSACTION ];
+// COMMIT [ TRANSACTION ];
+// ROLLBACK;
+//    没有 BEGIN 时每条语句在单独的事务中执行，成功之后自动提交
+//    BEGIN 在 session 中开启事务，之后的语句都在这个事务中执行，能看到事务中之前的写入，COMMIT 或者 ROLLBACK 结束事务
+//    事务中有语句执行出错时整个事务回滚，之后的语句报错，直到执行 ROLLBACK 或者 COMMIT，这时 COMMIT 也会报错
+//    事务中不能访问附加数据库中的表，AS OF 查询仍然使用单独的历史事务，查询不使用查询缓存
+//    session 结束时还没有提交的事务会被回滚
+//
+// ROLLBACK TRANSACTION version;
+//    强制中止另一个 session 中指定版本的活跃事务
+//
+// 6. Backup
+// -------------------------------------
+// BACKUP TO 'path' [ SINCE VERSION version ] [ WITH ( option_name [ = ] value [, ...] ) ];
+//    where option is:
+//     - batch_size = integer
+//     - rate_limit = integer，每秒写入的最大字节数
+//
+// 7. Checkpoint
+// -------------------------------------
+// CHECKPOINT;
+//
+// 8. Show
+// -------------------------------------
+// SHOW INDEXES FROM table_name;
+// SHOW [ GLOBAL ] VARIABLES;
+// SHOW CREATE TABLE table_name;
+//    从保存的表结构生成建表语句，每一行是一条语句，之后是 CREATE INDEX 创建的索引
+//    UNIQUE 列的索引写成列的约束，没有指定的选项使用默认值，不写在语句中
+// DESCRIBE table_name;
+//    表的每一列的名字、类型、是否可以为 NULL 和默认值，默认值是 SQL 表达式的文本，没有默认值或者默认值是 NULL 时是 NULL
+//
+// 9. Analyze
+// -------------------------------------
+// ANALYZE table_name;
+//
+// 10. Set
+// -------------------------------------
+// SET [ GLOBAL ] variable_name = value;
+//    where variable is:
+//     - statement_timeout = integer，语句执行的最长毫秒数
+//     - max_rows_examined = integer，语句从存储中读取的最大行数
+//     - max_result_rows = integer，语句返回的最大行数
+//     - max_memory = integer，语句执行时生成的行占用的最大字节数
+//    以上变量设置为 0 表示不限制，SET GLOBAL 修改的是之后新建的 session 的默认值
+//
+//    以下全局变量只能使用 SET GLOBAL 修改，对之后开启的事务生效：
+//     - lock_timeout = integer，写冲突时等待对方事务结束的最长毫秒数，0 表示不等待
+//     - max_txn_age = integer，事务的最长存活毫秒数，0 表示不限制
+//     - prune_versions = true | false，是否清理旧版本数据，清理过的版本不能再用于 AS OF 查询
+//     - query_cache_size = integer，最多缓存的查询结果数量，0 表示不缓存
+//
+// 11. Kill
+// -------------------------------------
+// KILL session_id;
+//
+// 12. Check
+// -------------------------------------
+// CHECK TABLE table_name;
+//    检查表的数据能否解码、是否满足表的定义、主键和行数据是否一致、二级索引和行数据是否一致以及底层的版本数据是否完整
+//    返回发现的所有问题，没有问题时返回空
+//
+// 13. Reindex
+// -------------------------------------
+// REINDEX TABLE table_name;
+// REINDEX INDEX index_name ON table_name;
+//    根据表中的行数据重新生成索引，包括第一列作为主键的隐式索引 primary 和 CREATE INDEX 创建的二级索引
+//    指定任何一个索引时都会重建表的所有索引
+//
+// 14. Attach / Detach
+// -------------------------------------
+// ATTACH DATABASE 'path' AS alias;
+// DETACH DATABASE alias;
+//    在当前 session 中打开另一个数据库，表名使用 alias.table_name 访问附加的数据库，main 表示当前数据库
+//    每条语句只能访问一个数据库，事务只在这个数据库中生效
+//
+// 15. Copy
+// -------------------------------------
+// COPY table_name FROM 'file.csv' [ WITH ( option_name [ = ] value [, ...] ) ];
+//    将 CSV 文件中的数据批量导入到表中，文件的每一行对应表的一行，选项和外部表相同
+//    导入的数据合并成批量写入，遇到写冲突时和 INSERT 一样按照 lock_timeout 等待
+//
+// 16. Explain
+// -------------------------------------
+// EXPLAIN [ VERBOSE ] statement;
+// EXPLAIN ( option [, ...] ) statement;
+//    where option is:
+//     - FORMAT TEXT | JSON，默认为 TEXT，每行输出计划树的一个节点，JSON 格式只输出一行
+//     - VERBOSE [ TRUE | FALSE ]，输出估算的行数和代价、使用的索引等信息
+//    只生成执行计划，不执行语句，行数优先使用 ANALYZE 收集的统计信息
+//
+// 17. Update
+// -------------------------------------
+// UPDATE table_name SET column_name = expr [, ...] [ WHERE expr ];
+//    表达式中的列是修改之前的值，修改主键时新的主键不能和其他行重复
+//
+// 18. Delete
+// -------------------------------------
+// DELETE FROM table_name [ WHERE expr ];
+//    没有 WHERE 时删除表中的所有行
+//
+// 19. Alter Table
+// -------------------------------------
+// ALTER TABLE table_name ADD [ COLUMN ] column_name data_type [ column_constraint [...] ];
+// ALTER TABLE table_name RENAME TO new_table_name;
+// ALTER TABLE table_name RENAME [ COLUMN ] column_name TO new_column_name;
+//    新的列添加在最后，类型和约束与 CREATE TABLE 相同
+//    表中已有的行使用列的默认值填充，默认值是 now() 和 gen_uuid() 等函数时每一行分别计算
+//    没有默认值的 NOT NULL 列只能添加到空表中
+//    修改表名时表的数据一起移动到新的表名下，在同一个事务中完成，其他事务看到的是修改之前或者之后的表
+//
+// CREATE VIEW view_name [ ( column_name [, ...] ) ] AS query;
+// DROP VIEW view_name;
+//    视图保存定义它的查询语句，在 FROM 中和表一样使用，查询时展开成子查询，总是读取表中最新的数据
+//    列名默认是查询结果的列名，指定列名时数量需要和查询结果的列数相同
+//    创建时检查查询中的表和列是否存在，视图和表的名字不能相同，视图不能修改数据，AS OF 不能用在视图中
+//    被其他视图引用的视图不能删除，需要先删除引用它的视图
+//
+// CREATE INDEX index_name ON table_name ( column_name [, ...] );
+// DROP INDEX index_name ON table_name;
+//    在一列或者多列上创建二级索引，只能用于行存的表，列相同并且顺序相同的索引最多有一个，只有主键一列时已经有隐式的索引
+//    索引按照列的顺序保序编码，WHERE 中和 AND 连接的条件可以使用索引的前缀：前几列是 column_name = 常量，
+//    之后的一列可以是 < | <= | > | >= 常量 的范围条件，通过索引读取匹配的行，不需要扫描整个表
+//    第一列是 NULL 的行不在索引中，常量的类型需要和列的类型相同，否则仍然扫描整个表，JSON 列只能用于等值条件
+//    写入、修改和删除行时同时维护索引
+//
+// 表达式 expr 支持的运算符，按照优先级从低到高排列
 // -------------------------------------
-// SELECT * FROM table_name;
+//     - OR
+//     - AND
+//     - NOT expr
+//     - = | != | <> | < | <= | > | >=
+//     - + | -
+//     - * | / | %
+//     - ^，右结合
+//     - +expr | -expr
+//    可以使用括号 ( expr ) 改变运算的顺序
+#[derive(Clone)]
 pub struct Lexer<'a> {
     iter: Peekable<Chars<'a>>,
+    // 已经读取的字节数
+    offset: usize,
+}
+
+// 可以预读一个 Token，并且知道下一个 Token 在 SQL 中的起始位置，用于截取视图定义中的查询语句
+#[derive(Clone)]
+pub struct TokenStream<'a> {
+    lexer: Lexer<'a>,
+    // 预读的 Token 和扫描它之前的位置
+    peeked: Option<(usize, Option<Result<Token>>)>,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(lexer: Lexer<'a>) -> Self {
+        Self {
+            lexer,
+            peeked: None,
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<&Result<Token>> {
+        if self.peeked.is_none() {
+            let offset = self.lexer.offset;
+            self.peeked = Some((offset, self.lexer.next()));
+        }
+        self.peeked.as_ref().and_then(|(_, token)| token.as_ref())
+    }
+
+    // 还没有读取的部分的起始位置，位置之后可能还有空白字符
+    pub fn offset(&self) -> usize {
+        match &self.peeked {
+            Some((offset, _)) => *offset,
+            None => self.lexer.offset,
+        }
+    }
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peeked.take() {
+            Some((_, token)) => token,
+            None => self.lexer.next(),
+        }
+    }
 }
 
 // 自定义迭代器，返回 Token
@@ -178,10 +808,7 @@ impl<'a> Iterator for Lexer<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.scan() {
-            Ok(Some(token)) => {
-                println!("next function got a token: {:?}", token);
-                Some(Ok(token))
-            },
+            Ok(Some(token)) => Some(Ok(token)),
             Ok(None) => self
                 .iter
                 .peek()
@@ -195,20 +822,17 @@ impl<'a> Lexer<'a> {
     pub fn new(sql_text: &'a str) -> Self {
         Self {
             iter: sql_text.chars().peekable(),
+            offset: 0,
         }
     }
 
-    pub fn debug(&self) {
-        let mut iter_clone = self.iter.clone(); // 克隆 iter
-        println!("#######Debugging Lexer iter:");
-
-        // 遍历克隆的迭代器，输出字符，但不影响原始迭代器
-        while let Some(c) = iter_clone.next() {
-            print!("{}", c);
-        }
-        println!("#######Debugging over@@@@@@@@@@@@@@@");
+    // 读取下一个字符，同时记录读取的位置
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.iter.next()?;
+        self.offset += c.len_utf8();
+        Some(c)
     }
-    
+
     // 消除空白字符
     // eg. selct *       from        t;
     fn erase_whitespace(&mut self) {
@@ -221,7 +845,7 @@ impl<'a> Lexer<'a> {
         // 结构出一个&char的类型，不然就会返回一个None。
         self.iter.peek().filter(|&c| predicate(*c))?;
         // 如果符合这个predicate的话，就需要把迭代器往下移动，并且这个next方法返回的是Option.
-        self.iter.next()
+        self.next_char()
     }
 
     // 判断当前字符是否满足条件，如果是的话就跳转到下一个字符
@@ -237,22 +861,26 @@ impl<'a> Lexer<'a> {
     // 只有是 Token 类型，才跳转到下一个，并返回 Token
     fn next_if_token<F: Fn(char) -> Option<Token>>(&mut self, predicate: F) -> Option<Token> {
         let token = self.iter.peek().and_then(|c| predicate(*c))?;
-        self.iter.next();
+        self.next_char();
         Some(token)
     }
 
     // 扫描拿到下一个 Token
     fn scan(&mut self) -> Result<Option<Token>> {
-        println!("begin scan function");
-      //  self.debug();
         // 消除字符串中的空白字符部分
         self.erase_whitespace();
         // 根据第一个字符判断
-        match self.iter.peek() {
-            Some('\'') => self.scan_string(), // 扫描字符串
-            Some(c) if c.is_ascii_digit() => Ok(self.scan_number()), // 扫描数字
+        match self.iter.peek().copied() {
+            Some('\'') => self.scan_string(),                      // 扫描字符串
+            Some('"' | '`') => self.scan_quoted_ident().map(Some), // 扫描带引号的 Ident
+            Some(c) if c.is_ascii_digit() => self.scan_number(),   // 扫描数字
+            // 小数点开头的浮点数，例如 .5
+            Some('.') if self.iter.clone().nth(1).is_some_and(|c| c.is_ascii_digit()) => {
+                self.scan_number()
+            }
             Some(c) if c.is_alphabetic() => Ok(self.scan_ident()), // 扫描 Ident 类型
-            Some(_) => Ok(self.scan_symbol()), // 扫描符号
+            Some('?' | '$') => self.scan_parameter().map(Some),    // 扫描参数
+            Some(_) => Ok(self.scan_symbol()),                     // 扫描符号
             None => Ok(None),
         }
     }
@@ -266,10 +894,10 @@ impl<'a> Lexer<'a> {
 
         let mut val = String::new();
         loop {
-            match self.iter.next() {
+            match self.next_char() {
                 Some('\'') => break,
                 Some(c) => val.push(c),
-                None => return Err(Error::Parse(format!("[Lexer] Unexpected end of string"))),
+                None => return Err(Error::Parse("[Lexer] Unexpected end of string".to_string())),
             }
         }
 
@@ -277,9 +905,30 @@ impl<'a> Lexer<'a> {
     }
 
     // 扫描数字
-    fn scan_number(&mut self) -> Option<Token> {
-        // 先扫描一部分
-        let mut num = self.next_while(|c| c.is_ascii_digit())?;
+    fn scan_number(&mut self) -> Result<Option<Token>> {
+        // 先扫描一部分，小数点开头时整数部分为空
+        let mut num = self.next_while(|c| c.is_ascii_digit()).unwrap_or_default();
+
+        // 0x 开头的十六进制整数，转换成十进制的文本
+        if num == "0" && self.next_if(|c| c == 'x' || c == 'X').is_some() {
+            let hex = self
+                .next_while(|c| c.is_ascii_hexdigit())
+                .unwrap_or_default();
+            // 后面紧跟着名字中的字符时不是合法的数字，例如 0x1g
+            if let Some(rest) = self.next_while(|c| c.is_alphanumeric() || c == '_') {
+                return Err(Error::Parse(format!(
+                    "[Lexer] Invalid hex number 0x{}{}",
+                    hex, rest
+                )));
+            }
+            return match i64::from_str_radix(&hex, 16) {
+                Ok(n) => Ok(Some(Token::Number(n.to_string()))),
+                Err(_) => Err(Error::Parse(format!(
+                    "[Lexer] Invalid hex number 0x{}",
+                    hex
+                ))),
+            };
+        }
 
         // 如果中间有小数点，说明是浮点数
         if let Some(sep) = self.next_if(|c| c == '.') {
@@ -290,7 +939,17 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Some(Token::Number(num))
+        // 科学计数法，例如 1e10、2.5E-3，指数部分不能为空
+        if let Some(e) = self.next_if(|c| c == 'e' || c == 'E') {
+            num.push(e);
+            num.extend(self.next_if(|c| c == '+' || c == '-'));
+            match self.next_while(|c| c.is_ascii_digit()) {
+                Some(exp) => num.push_str(&exp),
+                None => return Err(Error::Parse(format!("[Lexer] Invalid number {}", num))),
+            }
+        }
+
+        Ok(Some(Token::Number(num)))
     }
 
     // 扫描 Ident 类型，例如表名、列名等，也有可能是关键字，true / false
@@ -298,14 +957,57 @@ impl<'a> Lexer<'a> {
         let mut value = self.next_if(|c| c.is_alphabetic())?.to_string();
         while let Some(c) = self.next_if(|c| c.is_alphanumeric() || c == '_') {
             value.push(c);
-        } 
+        }
 
         Some(Keyword::from_str(&value).map_or(Token::Ident(value.to_lowercase()), Token::Keyword))
     }
 
+    // 扫描双引号或者反引号中的 Ident，保留原来的大小写，不会被识别成关键字
+    // 连续的两个引号表示引号本身，例如 "a""b" 是 a"b
+    fn scan_quoted_ident(&mut self) -> Result<Token> {
+        let Some(quote) = self.next_if(|c| c == '"' || c == '`') else {
+            return Err(Error::Parse(
+                "[Lexer] Expected quoted identifier".to_string(),
+            ));
+        };
+        let mut value = String::new();
+        loop {
+            match self.next_char() {
+                Some(c) if c == quote => match self.next_if(|c| c == quote) {
+                    Some(c) => value.push(c),
+                    None => break,
+                },
+                Some(c) => value.push(c),
+                None => {
+                    return Err(Error::Parse(
+                        "[Lexer] Unexpected end of quoted identifier".to_string(),
+                    ))
+                }
+            }
+        }
+        if value.is_empty() {
+            return Err(Error::Parse("[Lexer] Empty quoted identifier".to_string()));
+        }
+        Ok(Token::Ident(value))
+    }
+
+    // 扫描参数 ? 或者 $n，n 是从 1 开始的参数编号
+    fn scan_parameter(&mut self) -> Result<Token> {
+        if self.next_if(|c| c == '?').is_some() {
+            return Ok(Token::Parameter("?".to_string()));
+        }
+        self.next_if(|c| c == '$');
+        match self.next_while(|c| c.is_ascii_digit()) {
+            Some(n) => Ok(Token::Parameter(format!("${}", n))),
+            None => Err(Error::Parse(
+                "[Lexer] Expected parameter number after $".to_string(),
+            )),
+        }
+    }
+
     // 扫描符号
     fn scan_symbol(&mut self) -> Option<Token> {
-        self.next_if_token(|c| match c {
+        let token = self.next_if_token(|c| match c {
             '*' => Some(Token::Asterisk),
             '(' => Some(Token::OpenParen),
             ')' => Some(Token::CloseParen),
@@ -314,7 +1016,28 @@ impl<'a> Lexer<'a> {
             '+' => Some(Token::Plus),
             '-' => Some(Token::Minus),
             '/' => Some(Token::Slash),
+            '.' => Some(Token::Period),
+            '=' => Some(Token::Equal),
+            '%' => Some(Token::Percent),
+            '^' => Some(Token::Caret),
+            '!' => Some(Token::Exclamation),
+            '<' => Some(Token::LessThan),
+            '>' => Some(Token::GreaterThan),
             _ => None,
+        })?;
+        // 两个字符组成的符号
+        Some(match token {
+            Token::Exclamation if self.next_if(|c| c == '=').is_some() => Token::NotEqual,
+            Token::LessThan if self.next_if(|c| c == '=').is_some() => Token::LessThanOrEqual,
+            Token::LessThan if self.next_if(|c| c == '>').is_some() => Token::LessOrGreaterThan,
+            Token::GreaterThan if self.next_if(|c| c == '=').is_some() => Token::GreaterThanOrEqual,
+            Token::Minus if self.next_if(|c| c == '>').is_some() => {
+                match self.next_if(|c| c == '>') {
+                    Some(_) => Token::DoubleArrow,
+                    None => Token::Arrow,
+                }
+            }
+            token => token,
         })
     }
 }
@@ -383,7 +1106,7 @@ mod tests {
         .collect::<Result<Vec<_>>>()?;
 
         println!("解析出的第二个token：{:?}", tokens2);
-        assert!(tokens2.len() > 0);
+        assert!(!tokens2.is_empty());
 
         Ok(())
     }
@@ -465,6 +1188,189 @@ mod tests {
                 Token::Semicolon,
             ]
         );
+
+        let tokens2 = Lexer::new("select tbl.*, tbl.a from tbl;")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            tokens2,
+            vec![
+                Token::Keyword(Keyword::Select),
+                Token::Ident("tbl".to_string()),
+                Token::Period,
+                Token::Asterisk,
+                Token::Comma,
+                Token::Ident("tbl".to_string()),
+                Token::Period,
+                Token::Ident("a".to_string()),
+                Token::Keyword(Keyword::From),
+                Token::Ident("tbl".to_string()),
+                Token::Semicolon,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_operators() -> Result<()> {
+        let tokens = Lexer::new("a!=b<>c<=d>=e<f>g%h^i = !j and or")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        let ident = |s: &str| Token::Ident(s.to_string());
+        assert_eq!(
+            tokens,
+            vec![
+                ident("a"),
+                Token::NotEqual,
+                ident("b"),
+                Token::LessOrGreaterThan,
+                ident("c"),
+                Token::LessThanOrEqual,
+                ident("d"),
+                Token::GreaterThanOrEqual,
+                ident("e"),
+                Token::LessThan,
+                ident("f"),
+                Token::GreaterThan,
+                ident("g"),
+                Token::Percent,
+                ident("h"),
+                Token::Caret,
+                ident("i"),
+                Token::Equal,
+                Token::Exclamation,
+                ident("j"),
+                Token::Keyword(Keyword::And),
+                Token::Keyword(Keyword::Or),
+            ]
+        );
+
+        let tokens = Lexer::new("a->b->>c - >d-1")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                ident("a"),
+                Token::Arrow,
+                ident("b"),
+                Token::DoubleArrow,
+                ident("c"),
+                Token::Minus,
+                Token::GreaterThan,
+                ident("d"),
+                Token::Minus,
+                Token::Number("1".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_parameters() -> Result<()> {
+        let tokens = Lexer::new("a = ? and b in ($1,$12)")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        let parameter = |s: &str| Token::Parameter(s.to_string());
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("a".to_string()),
+                Token::Equal,
+                parameter("?"),
+                Token::Keyword(Keyword::And),
+                Token::Ident("b".to_string()),
+                Token::Keyword(Keyword::In),
+                Token::OpenParen,
+                parameter("$1"),
+                Token::Comma,
+                parameter("$12"),
+                Token::CloseParen,
+            ]
+        );
+        assert!(Lexer::new("a = $").collect::<Result<Vec<_>>>().is_err());
+        assert!(Lexer::new("a = $b").collect::<Result<Vec<_>>>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_numbers() -> Result<()> {
+        let tokens = Lexer::new("1e10, 2.5E-3, 3e+2, .5, 1., 0xFF, 0X1a, 0, t.a")
+            .collect::<Result<Vec<_>>>()?;
+        let number = |s: &str| Token::Number(s.to_string());
+        assert_eq!(
+            tokens,
+            vec![
+                number("1e10"),
+                Token::Comma,
+                number("2.5E-3"),
+                Token::Comma,
+                number("3e+2"),
+                Token::Comma,
+                number(".5"),
+                Token::Comma,
+                number("1."),
+                Token::Comma,
+                number("255"),
+                Token::Comma,
+                number("26"),
+                Token::Comma,
+                number("0"),
+                Token::Comma,
+                Token::Ident("t".to_string()),
+                Token::Period,
+                Token::Ident("a".to_string()),
+            ]
+        );
+        for sql in [
+            "1e",
+            "1e+",
+            "2.5E-",
+            "0x",
+            "0xG",
+            "0x1g",
+            "0xff_1",
+            "0x8000000000000000",
+        ] {
+            assert!(
+                Lexer::new(sql).collect::<Result<Vec<_>>>().is_err(),
+                "{}",
+                sql
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_quoted_ident() -> Result<()> {
+        let tokens = Lexer::new(r#"select "Select", `My Table`.a, "a""b", `a``b` from "t""#)
+            .collect::<Result<Vec<_>>>()?;
+        let ident = |s: &str| Token::Ident(s.to_string());
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Keyword(Keyword::Select),
+                ident("Select"),
+                Token::Comma,
+                ident("My Table"),
+                Token::Period,
+                ident("a"),
+                Token::Comma,
+                ident("a\"b"),
+                Token::Comma,
+                ident("a`b"),
+                Token::Keyword(Keyword::From),
+                ident("t"),
+            ]
+        );
+        for sql in [r#"select "a"#, "select `a", r#"select """#, "select ``"] {
+            assert!(
+                Lexer::new(sql).collect::<Result<Vec<_>>>().is_err(),
+                "{}",
+                sql
+            );
+        }
         Ok(())
     }
 }
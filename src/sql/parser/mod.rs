@@ -1,27 +1,51 @@
-use std::iter::Peekable;
-
 use ast::Column;
-use lexer::{Keyword, Lexer, Token};
+use lexer::{Keyword, Lexer, Token, TokenStream};
 
 use crate::error::{Error, Result};
 
-use super::types::DataType;
+use super::{
+    function,
+    ident::Ident,
+    types::{decimal::MAX_PRECISION, DataType},
+};
 
 pub mod ast;
+pub mod compile;
 mod lexer;
 
 // 解析器定义
 pub struct Parser<'a> {
-    lexer: Peekable<Lexer<'a>>,
+    input: &'a str,
+    lexer: TokenStream<'a>,
+    // 语句中参数的数量，是最大的参数编号
+    parameters: usize,
+    // 参数使用 $n 编号，同一条语句中不能同时使用 ? 和 $n
+    numbered: Option<bool>,
+}
+
+// FROM 中的一项，子查询时 table_name 是子查询的别名
+struct FromItem {
+    table_name: Ident,
+    table_args: Option<Vec<ast::Expression>>,
+    subquery: Option<Box<ast::Statement>>,
+    alias: Option<Ident>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
         Parser {
-            lexer: Lexer::new(input).peekable(),
+            input,
+            lexer: TokenStream::new(Lexer::new(input)),
+            parameters: 0,
+            numbered: None,
         }
     }
 
+    // 已经解析的语句中参数的数量
+    pub fn parameters(&self) -> usize {
+        self.parameters
+    }
+
     // 解析，获取到抽象语法树
     pub fn parse(&mut self) -> Result<ast::Statement> {
         let stmt = self.parse_statement()?;
@@ -34,14 +58,94 @@ impl<'a> Parser<'a> {
         Ok(stmt)
     }
 
+    // 解析用分号分隔的多条 sql 语句，每条语句的最后都需要有分号
+    pub fn parse_batch(&mut self) -> Result<Vec<ast::Statement>> {
+        let mut stmts = Vec::new();
+        while self.peek()?.is_some() {
+            stmts.push(self.parse_statement()?);
+            self.next_expect(Token::Semicolon)?;
+        }
+        Ok(stmts)
+    }
+
+    // 解析单独的一个表达式，例如表结构中保存的默认值，表达式之后不能有其他的符号
+    pub fn parse_standalone_expression(&mut self) -> Result<ast::Expression> {
+        let expr = self.parse_expression()?;
+        if let Some(token) = self.peek()? {
+            return Err(Error::Parse(format!("[Parser] Unexpected token {}", token)));
+        }
+        Ok(expr)
+    }
+
     fn parse_statement(&mut self) -> Result<ast::Statement> {
         // 查看第一个 Token 类型
         match self.peek()? {
-            Some(Token::Keyword(Keyword::Create)) => self.parse_ddl(),
-            Some(Token::Keyword(Keyword::Select)) => self.parse_select(),
+            Some(Token::Keyword(Keyword::Create))
+            | Some(Token::Keyword(Keyword::Alter))
+            | Some(Token::Keyword(Keyword::Drop)) => self.parse_ddl(),
+            Some(Token::Keyword(Keyword::Select)) => self.parse_query(),
             Some(Token::Keyword(Keyword::Insert)) => self.parse_insert(),
+            Some(Token::Keyword(Keyword::Update)) => self.parse_update(),
+            Some(Token::Keyword(Keyword::Delete)) => {
+                self.next()?;
+                self.next_expect(Token::Keyword(Keyword::From))?;
+                Ok(ast::Statement::Delete {
+                    table_name: self.parse_table_name()?,
+                    filter: self.parse_where()?,
+                })
+            }
+            Some(Token::Keyword(Keyword::Values)) => self.parse_values_statement(),
+            Some(Token::Keyword(Keyword::Rollback)) => self.parse_rollback(),
+            Some(Token::Keyword(Keyword::Begin)) => {
+                self.next()?;
+                self.next_if_token(Token::Keyword(Keyword::Transaction));
+                Ok(ast::Statement::Begin)
+            }
+            Some(Token::Keyword(Keyword::Commit)) => {
+                self.next()?;
+                self.next_if_token(Token::Keyword(Keyword::Transaction));
+                Ok(ast::Statement::Commit)
+            }
+            Some(Token::Keyword(Keyword::Backup)) => self.parse_backup(),
+            Some(Token::Keyword(Keyword::Copy)) => self.parse_copy(),
+            Some(Token::Keyword(Keyword::Explain)) => self.parse_explain(),
+            Some(Token::Keyword(Keyword::Show)) => self.parse_show(),
+            Some(Token::Keyword(Keyword::Describe)) => {
+                self.next()?;
+                Ok(ast::Statement::Describe {
+                    table_name: self.parse_table_name()?,
+                })
+            }
+            Some(Token::Keyword(Keyword::Set)) => self.parse_set(),
+            Some(Token::Keyword(Keyword::Kill)) => self.parse_kill(),
+            Some(Token::Keyword(Keyword::Reindex)) => self.parse_reindex(),
+            Some(Token::Keyword(Keyword::Attach)) => self.parse_attach(),
+            Some(Token::Keyword(Keyword::Detach)) => {
+                self.next()?;
+                self.next_expect(Token::Keyword(Keyword::Database))?;
+                Ok(ast::Statement::Detach {
+                    alias: self.next_ident()?,
+                })
+            }
+            Some(Token::Keyword(Keyword::Analyze)) => {
+                self.next()?;
+                Ok(ast::Statement::Analyze {
+                    table_name: self.parse_table_name()?,
+                })
+            }
+            Some(Token::Keyword(Keyword::Checkpoint)) => {
+                self.next()?;
+                Ok(ast::Statement::Checkpoint)
+            }
+            Some(Token::Keyword(Keyword::Check)) => {
+                self.next()?;
+                self.next_expect(Token::Keyword(Keyword::Table))?;
+                Ok(ast::Statement::CheckTable {
+                    table_name: self.parse_table_name()?,
+                })
+            }
             Some(t) => Err(Error::Parse(format!("[Parser] Unexpected token {}", t))),
-            None => Err(Error::Parse(format!("[Parser] Unexpected end of input"))),
+            None => Err(Error::Parse("[Parser] Unexpected end of input".to_string())),
         }
     }
 
@@ -49,22 +153,622 @@ impl<'a> Parser<'a> {
     fn parse_ddl(&mut self) -> Result<ast::Statement> {
         match self.next()? {
             Token::Keyword(Keyword::Create) => match self.next()? {
-                Token::Keyword(Keyword::Table) => self.parse_ddl_create_table(),
+                Token::Keyword(Keyword::Table) => self.parse_ddl_create_table(false),
+                Token::Keyword(Keyword::External) => {
+                    self.next_expect(Token::Keyword(Keyword::Table))?;
+                    self.parse_ddl_create_table(true)
+                }
+                Token::Keyword(Keyword::View) => self.parse_ddl_create_view(),
+                Token::Keyword(Keyword::Index) => self.parse_ddl_create_index(false),
+                Token::Keyword(Keyword::Spatial) => {
+                    self.next_expect(Token::Keyword(Keyword::Index))?;
+                    self.parse_ddl_create_index(true)
+                }
+                token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
+            },
+            Token::Keyword(Keyword::Drop) => match self.next()? {
+                Token::Keyword(Keyword::View) => Ok(ast::Statement::DropView {
+                    name: self.next_ident()?.into(),
+                }),
+                // DROP INDEX index_name ON table_name
+                Token::Keyword(Keyword::Index) => {
+                    let name = self.next_ident()?.into();
+                    self.next_expect(Token::Keyword(Keyword::On))?;
+                    Ok(ast::Statement::DropIndex {
+                        name,
+                        table_name: self.parse_table_name()?,
+                    })
+                }
                 token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
             },
+            Token::Keyword(Keyword::Alter) => {
+                self.next_expect(Token::Keyword(Keyword::Table))?;
+                self.parse_ddl_alter_table()
+            }
             token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
         }
     }
 
+    // CREATE [ SPATIAL ] INDEX index_name ON table_name ( column_name [, ...] ) [ WITH ( option = value [, ...] ) ]
+    fn parse_ddl_create_index(&mut self, spatial: bool) -> Result<ast::Statement> {
+        let name = self.next_ident()?.into();
+        self.next_expect(Token::Keyword(Keyword::On))?;
+        let table_name = self.parse_table_name()?;
+        self.next_expect(Token::OpenParen)?;
+        let mut columns = Vec::new();
+        loop {
+            columns.push(self.next_ident()?);
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+        self.next_expect(Token::CloseParen)?;
+        Ok(ast::Statement::CreateIndex {
+            name,
+            table_name,
+            columns,
+            spatial,
+            options: self.parse_with_options()?,
+        })
+    }
+
+    // CREATE VIEW view_name [ ( column_name [, ...] ) ] AS query，保存 AS 之后的查询语句
+    fn parse_ddl_create_view(&mut self) -> Result<ast::Statement> {
+        let name = self.next_ident()?.into();
+        let mut columns = Vec::new();
+        if self.next_if_token(Token::OpenParen).is_some() {
+            loop {
+                columns.push(self.next_ident()?);
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
+                }
+            }
+            self.next_expect(Token::CloseParen)?;
+        }
+        self.next_expect(Token::Keyword(Keyword::As))?;
+        let start = self.lexer.offset();
+        let parameters = self.parameters;
+        let query = self.parse_query()?;
+        if self.parameters != parameters {
+            return Err(Error::Parse(
+                "[Parser] Parameters are not allowed in a view".to_string(),
+            ));
+        }
+        let text = self.input[start..self.lexer.offset()].trim().to_string();
+        Ok(ast::Statement::CreateView {
+            name,
+            columns,
+            query: Box::new(query),
+            text,
+        })
+    }
+
+    // 查询和集合运算 query { UNION | INTERSECT | EXCEPT } [ ALL ] query ...
+    // INTERSECT 的优先级比 UNION 和 EXCEPT 高，相同优先级时从左到右计算
+    // ORDER BY 只能出现在最后，有集合运算时对整个结果排序
+    fn parse_query(&mut self) -> Result<ast::Statement> {
+        let mut query = self.parse_intersect()?;
+        loop {
+            let op = match self.peek()? {
+                Some(Token::Keyword(Keyword::Union)) => ast::SetOperator::Union,
+                Some(Token::Keyword(Keyword::Except)) => ast::SetOperator::Except,
+                _ => break,
+            };
+            self.next()?;
+            let all = self.next_if_token(Token::Keyword(Keyword::All)).is_some();
+            query = ast::Statement::SetOperation {
+                op,
+                all,
+                left: Box::new(query),
+                right: Box::new(self.parse_intersect()?),
+                order_by: vec![],
+            };
+        }
+        match &mut query {
+            // 没有 FROM 的查询不能排序
+            ast::Statement::Select {
+                table_name: None, ..
+            } => {}
+            ast::Statement::Select { order_by, .. }
+            | ast::Statement::SetOperation { order_by, .. } => *order_by = self.parse_order_by()?,
+            _ => {}
+        }
+        Ok(query)
+    }
+
+    fn parse_intersect(&mut self) -> Result<ast::Statement> {
+        let mut query = self.parse_select()?;
+        while self
+            .next_if_token(Token::Keyword(Keyword::Intersect))
+            .is_some()
+        {
+            let all = self.next_if_token(Token::Keyword(Keyword::All)).is_some();
+            query = ast::Statement::SetOperation {
+                op: ast::SetOperator::Intersect,
+                all,
+                left: Box::new(query),
+                right: Box::new(self.parse_select()?),
+                order_by: vec![],
+            };
+        }
+        Ok(query)
+    }
+
     // 解析 Select 语句
     fn parse_select(&mut self) -> Result<ast::Statement> {
         self.next_expect(Token::Keyword(Keyword::Select))?;
-        self.next_expect(Token::Asterisk)?;
+        let distinct = self
+            .next_if_token(Token::Keyword(Keyword::Distinct))
+            .is_some();
+
+        // select 列表
+        let mut select = Vec::new();
+        loop {
+            select.push(self.parse_select_item()?);
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+        // 表名，没有 FROM 时只计算 select 列表中的表达式
+        if self.next_if_token(Token::Keyword(Keyword::From)).is_none() {
+            return Ok(ast::Statement::Select {
+                select,
+                distinct,
+                table_name: None,
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: self.parse_where()?,
+                group_by: vec![],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: None,
+            });
+        }
+        let FromItem {
+            table_name,
+            table_args,
+            subquery,
+            alias,
+        } = self.parse_from_item()?;
+        let table_name = Some(table_name);
+
+        // 连接 [ INNER | { LEFT | RIGHT | FULL } [ OUTER ] ] JOIN from_item ON expr
+        let mut joins = Vec::new();
+        while let Some(kind) = self.parse_join_type()? {
+            let FromItem {
+                table_name,
+                table_args,
+                subquery,
+                alias,
+            } = self.parse_from_item()?;
+            self.next_expect(Token::Keyword(Keyword::On))?;
+            joins.push(ast::Join {
+                kind,
+                table_name,
+                table_args,
+                subquery,
+                alias,
+                predicate: self.parse_expression()?,
+            });
+        }
+
+        // 历史查询 AS OF VERSION n
+        let as_of = if self.next_if_token(Token::Keyword(Keyword::As)).is_some() {
+            self.next_expect(Token::Keyword(Keyword::Of))?;
+            self.next_expect(Token::Keyword(Keyword::Version))?;
+            match self.next()? {
+                Token::Number(n) => Some(n.parse()?),
+                token => {
+                    return Err(Error::Parse(format!(
+                        "[Parser] Expected version number, got token {}",
+                        token
+                    )))
+                }
+            }
+        } else {
+            None
+        };
+
+        let filter = self.parse_where()?;
+
+        // 分组 GROUP BY expr [, ...]
+        let mut group_by = Vec::new();
+        if self.next_if_token(Token::Keyword(Keyword::Group)).is_some() {
+            self.next_expect(Token::Keyword(Keyword::By))?;
+            loop {
+                group_by.push(self.parse_expression()?);
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+        // 分组之后的过滤条件 HAVING expr
+        let having = match self.next_if_token(Token::Keyword(Keyword::Having)) {
+            Some(_) => Some(self.parse_expression()?),
+            None => None,
+        };
+
+        Ok(ast::Statement::Select {
+            select,
+            distinct,
+            table_name,
+            table_args,
+            subquery,
+            alias,
+            joins,
+            filter,
+            group_by,
+            having,
+            order_by: vec![],
+            limit: None,
+            as_of,
+        })
+    }
+
+    // FROM 中的表、表函数或者子查询，以及别名
+    fn parse_from_item(&mut self) -> Result<FromItem> {
+        // 子查询 FROM (SELECT ...) [AS] alias，子查询必须有别名，别名作为表名使用
+        // 子查询也可以是 (VALUES (...), ...)，列名是 column1、column2 ...
+        if self.next_if_token(Token::OpenParen).is_some() {
+            let subquery = match self.peek()? {
+                Some(Token::Keyword(Keyword::Values)) => self.parse_values_statement()?,
+                _ => self.parse_query()?,
+            };
+            self.next_expect(Token::CloseParen)?;
+            self.next_if_token(Token::Keyword(Keyword::As));
+            let alias = match self.next()? {
+                Token::Ident(alias) => alias,
+                token => {
+                    return Err(Error::Parse(format!(
+                        "[Parser] Expected subquery alias, got token {}",
+                        token
+                    )))
+                }
+            };
+            return Ok(FromItem {
+                table_name: alias.into(),
+                table_args: None,
+                subquery: Some(Box::new(subquery)),
+                alias: None,
+            });
+        }
+        let table_name = self.parse_table_name()?;
+        // 表函数 FROM f(args)
+        let table_args = match self.next_if_token(Token::OpenParen) {
+            Some(_) => Some(self.parse_function_args()?),
+            None => None,
+        };
+        // 表的别名，AS 之后是 OF 时是历史查询
+        let mut lookahead = self.lexer.clone();
+        let alias = match (lookahead.next(), lookahead.next()) {
+            (Some(Ok(Token::Keyword(Keyword::As))), Some(Ok(Token::Ident(alias)))) => {
+                self.lexer = lookahead;
+                Some(alias.into())
+            }
+            (Some(Ok(Token::Ident(alias))), _) => {
+                self.next()?;
+                Some(alias.into())
+            }
+            _ => None,
+        };
+        Ok(FromItem {
+            table_name,
+            table_args,
+            subquery: None,
+            alias,
+        })
+    }
+
+    // 连接的类型，后面不是连接时返回 None
+    fn parse_join_type(&mut self) -> Result<Option<ast::JoinType>> {
+        let kind = match self.peek()? {
+            Some(Token::Keyword(Keyword::Join)) => {
+                self.next()?;
+                return Ok(Some(ast::JoinType::Inner));
+            }
+            Some(Token::Keyword(Keyword::Inner)) => ast::JoinType::Inner,
+            Some(Token::Keyword(Keyword::Left)) => ast::JoinType::Left,
+            Some(Token::Keyword(Keyword::Right)) => ast::JoinType::Right,
+            Some(Token::Keyword(Keyword::Full)) => ast::JoinType::Full,
+            _ => return Ok(None),
+        };
+        self.next()?;
+        if kind != ast::JoinType::Inner {
+            self.next_if_token(Token::Keyword(Keyword::Outer));
+        }
+        self.next_expect(Token::Keyword(Keyword::Join))?;
+        Ok(Some(kind))
+    }
+
+    // 过滤条件 WHERE expr
+    fn parse_where(&mut self) -> Result<Option<ast::Expression>> {
+        match self.next_if_token(Token::Keyword(Keyword::Where)) {
+            Some(_) => Ok(Some(self.parse_expression()?)),
+            None => Ok(None),
+        }
+    }
+
+    // 解析 select 列表中的一项
+    fn parse_select_item(&mut self) -> Result<ast::SelectItem> {
+        if self.next_if_token(Token::Asterisk).is_some() {
+            return Ok(ast::SelectItem::Wildcard(None));
+        }
+
+        // t.* 需要向前看三个 Token
+        let mut lookahead = self.lexer.clone();
+        if let (Some(Ok(Token::Ident(table))), Some(Ok(Token::Period)), Some(Ok(Token::Asterisk))) =
+            (lookahead.next(), lookahead.next(), lookahead.next())
+        {
+            self.lexer = lookahead;
+            return Ok(ast::SelectItem::Wildcard(Some(table)));
+        }
+
+        let expr = self.parse_expression()?;
+        // 别名前面的 AS 可以省略
+        let alias = if self.next_if_token(Token::Keyword(Keyword::As)).is_some() {
+            Some(self.next_ident()?)
+        } else {
+            match self.next_if(|t| matches!(t, Token::Ident(_))) {
+                Some(Token::Ident(alias)) => Some(alias),
+                _ => None,
+            }
+        };
+        Ok(ast::SelectItem::Expr(expr, alias))
+    }
+
+    // 解析 Rollback Transaction 语句
+    fn parse_rollback(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Rollback))?;
+        // 没有 TRANSACTION 时回滚 session 中的事务
+        if self
+            .next_if_token(Token::Keyword(Keyword::Transaction))
+            .is_none()
+        {
+            return Ok(ast::Statement::Rollback);
+        }
+        match self.next()? {
+            Token::Number(n) => Ok(ast::Statement::RollbackTransaction {
+                version: n.parse()?,
+            }),
+            token => Err(Error::Parse(format!(
+                "[Parser] Expected transaction version, got token {}",
+                token
+            ))),
+        }
+    }
+
+    // 解析 Set 语句
+    fn parse_set(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Set))?;
+        let global = self
+            .next_if_token(Token::Keyword(Keyword::Global))
+            .is_some();
+        let name = self.next_ident()?;
+        self.next_expect(Token::Equal)?;
+        Ok(ast::Statement::Set {
+            name,
+            value: self.parse_expression()?,
+            global,
+        })
+    }
+
+    // 解析 Kill 语句
+    fn parse_kill(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Kill))?;
+        match self.next()? {
+            Token::Number(n) => Ok(ast::Statement::Kill {
+                session_id: n.parse()?,
+            }),
+            token => Err(Error::Parse(format!(
+                "[Parser] Expected session id, got token {}",
+                token
+            ))),
+        }
+    }
+
+    // 解析 Attach 语句
+    fn parse_attach(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Attach))?;
+        self.next_expect(Token::Keyword(Keyword::Database))?;
+        let path = match self.next()? {
+            Token::String(path) => path,
+            token => {
+                return Err(Error::Parse(format!(
+                    "[Parser] Expected database path, got token {}",
+                    token
+                )))
+            }
+        };
+        self.next_expect(Token::Keyword(Keyword::As))?;
+        Ok(ast::Statement::Attach {
+            path,
+            alias: self.next_ident()?,
+        })
+    }
+
+    // 解析 Reindex 语句
+    fn parse_reindex(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Reindex))?;
+        match self.next()? {
+            Token::Keyword(Keyword::Table) => Ok(ast::Statement::Reindex {
+                table_name: self.parse_table_name()?,
+                index: None,
+            }),
+            Token::Keyword(Keyword::Index) => {
+                // 主键索引的名字 primary 是关键字
+                let index = match self.next_if_token(Token::Keyword(Keyword::Primary)) {
+                    Some(_) => "primary".to_string(),
+                    None => self.next_ident()?,
+                };
+                self.next_expect(Token::Keyword(Keyword::On))?;
+                Ok(ast::Statement::Reindex {
+                    table_name: self.parse_table_name()?,
+                    index: Some(index),
+                })
+            }
+            token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
+        }
+    }
+
+    // 解析 Show Indexes、Show Create Table 和 Show Variables 语句
+    fn parse_show(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Show))?;
+        match self.next()? {
+            Token::Keyword(Keyword::Indexes) => {
+                self.next_expect(Token::Keyword(Keyword::From))?;
+                Ok(ast::Statement::ShowIndexes {
+                    table_name: self.parse_table_name()?,
+                })
+            }
+            Token::Keyword(Keyword::Create) => {
+                self.next_expect(Token::Keyword(Keyword::Table))?;
+                Ok(ast::Statement::ShowCreateTable {
+                    table_name: self.parse_table_name()?,
+                })
+            }
+            Token::Keyword(Keyword::Variables) => {
+                Ok(ast::Statement::ShowVariables { global: false })
+            }
+            Token::Keyword(Keyword::Global) => {
+                self.next_expect(Token::Keyword(Keyword::Variables))?;
+                Ok(ast::Statement::ShowVariables { global: true })
+            }
+            token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
+        }
+    }
+
+    // 解析 Backup 语句
+    fn parse_backup(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Backup))?;
+        self.next_expect(Token::Keyword(Keyword::To))?;
+        let path = match self.next()? {
+            Token::String(path) => path,
+            token => {
+                return Err(Error::Parse(format!(
+                    "[Parser] Expected backup path, got token {}",
+                    token
+                )))
+            }
+        };
+        // 增量备份 SINCE VERSION n
+        let since = if self.next_if_token(Token::Keyword(Keyword::Since)).is_some() {
+            self.next_expect(Token::Keyword(Keyword::Version))?;
+            match self.next()? {
+                Token::Number(n) => Some(n.parse()?),
+                token => {
+                    return Err(Error::Parse(format!(
+                        "[Parser] Expected version number, got token {}",
+                        token
+                    )))
+                }
+            }
+        } else {
+            None
+        };
+        Ok(ast::Statement::Backup {
+            path,
+            since,
+            options: self.parse_with_options()?,
+        })
+    }
+
+    // 解析 Copy 语句
+    fn parse_copy(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Copy))?;
+        let table_name = self.parse_table_name()?;
         self.next_expect(Token::Keyword(Keyword::From))?;
+        let path = match self.next()? {
+            Token::String(path) => path,
+            token => {
+                return Err(Error::Parse(format!(
+                    "[Parser] Expected file path, got token {}",
+                    token
+                )))
+            }
+        };
+        Ok(ast::Statement::Copy {
+            table_name,
+            path,
+            options: self.parse_with_options()?,
+        })
+    }
 
-        // 表名
-        let table_name = self.next_ident()?;
-        Ok(ast::Statement::Select { table_name })
+    // 解析 Explain 语句
+    fn parse_explain(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Explain))?;
+        let mut format = ast::ExplainFormat::default();
+        let mut verbose = self
+            .next_if_token(Token::Keyword(Keyword::Verbose))
+            .is_some();
+        if !verbose && self.next_if_token(Token::OpenParen).is_some() {
+            loop {
+                match self.next()? {
+                    Token::Keyword(Keyword::Format) => {
+                        format = match self.next()? {
+                            Token::Keyword(Keyword::Text) => ast::ExplainFormat::Text,
+                            Token::Keyword(Keyword::Json) => ast::ExplainFormat::Json,
+                            token => {
+                                return Err(Error::Parse(format!(
+                                    "[Parser] Expected explain format, got token {}",
+                                    token
+                                )))
+                            }
+                        }
+                    }
+                    Token::Keyword(Keyword::Verbose) => {
+                        verbose = match self.next_if_keyword() {
+                            Some(Token::Keyword(Keyword::True)) | None => true,
+                            Some(Token::Keyword(Keyword::False)) => false,
+                            Some(token) => {
+                                return Err(Error::Parse(format!(
+                                    "[Parser] Unexpected token {}",
+                                    token
+                                )))
+                            }
+                        }
+                    }
+                    token => {
+                        return Err(Error::Parse(format!(
+                            "[Parser] Expected explain option, got token {}",
+                            token
+                        )))
+                    }
+                }
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
+                }
+            }
+            self.next_expect(Token::CloseParen)?;
+        }
+        Ok(ast::Statement::Explain {
+            statement: Box::new(self.parse_statement()?),
+            format,
+            verbose,
+        })
+    }
+
+    // 解析 Update 语句
+    fn parse_update(&mut self) -> Result<ast::Statement> {
+        self.next_expect(Token::Keyword(Keyword::Update))?;
+        let table_name = self.parse_table_name()?;
+        self.next_expect(Token::Keyword(Keyword::Set))?;
+        let mut columns = Vec::new();
+        loop {
+            let name = self.next_ident()?;
+            self.next_expect(Token::Equal)?;
+            columns.push((name, self.parse_expression()?));
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+        Ok(ast::Statement::Update {
+            table_name,
+            columns,
+            filter: self.parse_where()?,
+        })
     }
 
     // 解析 Insert 语句
@@ -73,7 +777,7 @@ impl<'a> Parser<'a> {
         self.next_expect(Token::Keyword(Keyword::Into))?;
 
         // 表名
-        let table_name = self.next_ident()?;
+        let table_name = self.parse_table_name()?;
 
         // 查看是否给指定的列进行 insert
         let columns = if self.next_if_token(Token::OpenParen).is_some() {
@@ -94,14 +798,64 @@ impl<'a> Parser<'a> {
         };
 
         // 解析 value 信息
-        self.next_expect(Token::Keyword(Keyword::Values))?;
         // insert into tbl(a, b, c) values (1, 2, 3),(4, 5, 6);
+        // insert into tbl default values;
+        // insert into tbl select ...;
+        if let Some(Token::Keyword(Keyword::Select)) = self.peek()? {
+            return Ok(ast::Statement::Insert {
+                table_name,
+                columns,
+                values: vec![],
+                query: Some(Box::new(self.parse_query()?)),
+            });
+        }
+        let values = if self
+            .next_if_token(Token::Keyword(Keyword::Default))
+            .is_some()
+        {
+            if columns.is_some() {
+                return Err(Error::Parse(
+                    "[Parser] DEFAULT VALUES does not accept a column list".to_string(),
+                ));
+            }
+            self.next_expect(Token::Keyword(Keyword::Values))?;
+            vec![vec![]]
+        } else {
+            self.parse_values()?
+        };
+
+        Ok(ast::Statement::Insert {
+            table_name,
+            columns,
+            values,
+            query: None,
+        })
+    }
+
+    // 解析 Values 语句
+    fn parse_values_statement(&mut self) -> Result<ast::Statement> {
+        Ok(ast::Statement::Values {
+            rows: self.parse_values()?,
+        })
+    }
+
+    // 解析 values (1, 2, 3),(4, 5, 6)
+    fn parse_values(&mut self) -> Result<Vec<Vec<ast::Expression>>> {
+        self.next_expect(Token::Keyword(Keyword::Values))?;
         let mut values = Vec::new();
         loop {
             self.next_expect(Token::OpenParen)?;
             let mut exprs = Vec::new();
             loop {
-                exprs.push(self.parse_expression()?);
+                // DEFAULT 表示使用列的默认值
+                if self
+                    .next_if_token(Token::Keyword(Keyword::Default))
+                    .is_some()
+                {
+                    exprs.push(ast::Expression::Default);
+                } else {
+                    exprs.push(self.parse_expression()?);
+                }
                 match self.next()? {
                     Token::CloseParen => break,
                     Token::Comma => {}
@@ -115,18 +869,13 @@ impl<'a> Parser<'a> {
                 break;
             }
         }
-
-        Ok(ast::Statement::Insert {
-            table_name,
-            columns,
-            values,
-        })
+        Ok(values)
     }
 
     // 解析 Create Table 语句
-    fn parse_ddl_create_table(&mut self) -> Result<ast::Statement> {
+    fn parse_ddl_create_table(&mut self, external: bool) -> Result<ast::Statement> {
         // 期望是 Table 名
-        let table_name = self.next_ident()?;
+        let table_name = self.parse_table_name()?;
         // 表名之后应该是括号
         self.next_expect(Token::OpenParen)?;
 
@@ -141,65 +890,382 @@ impl<'a> Parser<'a> {
         }
 
         self.next_expect(Token::CloseParen)?;
+
+        // 外部表需要指定数据文件
+        let location = if external {
+            self.next_expect(Token::Keyword(Keyword::Location))?;
+            match self.next()? {
+                Token::String(path) => Some(path),
+                token => {
+                    return Err(Error::Parse(format!(
+                        "[Parser] Expected file path, got token {}",
+                        token
+                    )))
+                }
+            }
+        } else {
+            None
+        };
+
         Ok(ast::Statement::CreateTable {
             name: table_name,
             columns,
+            options: self.parse_with_options()?,
+            location,
         })
     }
 
-    // 解析列信息
-    fn parse_ddl_column(&mut self) -> Result<ast::Column> {
-        let mut column = Column {
-            name: self.next_ident()?,
-            datatype: match self.next()? {
-                Token::Keyword(Keyword::Int) | Token::Keyword(Keyword::Integer) => {
-                    DataType::Integer
+    // 解析 Alter Table 语句
+    fn parse_ddl_alter_table(&mut self) -> Result<ast::Statement> {
+        let table_name = self.parse_table_name()?;
+        let operation = match self.next()? {
+            Token::Keyword(Keyword::Add) => {
+                self.next_if_token(Token::Keyword(Keyword::Column));
+                ast::AlterTableOperation::AddColumn {
+                    column: self.parse_ddl_column()?,
                 }
-                Token::Keyword(Keyword::Bool) | Token::Keyword(Keyword::Boolean) => {
-                    DataType::Boolean
+            }
+            Token::Keyword(Keyword::Rename) => {
+                if self.next_if_token(Token::Keyword(Keyword::To)).is_some() {
+                    ast::AlterTableOperation::RenameTable {
+                        new_name: self.next_ident()?.into(),
+                    }
+                } else {
+                    self.next_if_token(Token::Keyword(Keyword::Column));
+                    let column = self.next_ident()?.into();
+                    self.next_expect(Token::Keyword(Keyword::To))?;
+                    ast::AlterTableOperation::RenameColumn {
+                        column,
+                        new_name: self.next_ident()?.into(),
+                    }
                 }
-                Token::Keyword(Keyword::Float) | Token::Keyword(Keyword::Double) => DataType::Float,
-                Token::Keyword(Keyword::String)
-                | Token::Keyword(Keyword::Text)
-                | Token::Keyword(Keyword::Varchar) => DataType::String,
-                token => return Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
-            },
-            nullable: None,
-            default: None,
+            }
+            token => return Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
         };
+        Ok(ast::Statement::AlterTable {
+            table_name,
+            operation,
+        })
+    }
 
-        // 解析列的默认值，以及是否可以为空
-        while let Some(Token::Keyword(keyword)) = self.next_if_keyword() {
-            match keyword {
-                Keyword::Null => column.nullable = Some(true),
-                Keyword::Not => {
-                    self.next_expect(Token::Keyword(Keyword::Null))?;
-                    column.nullable = Some(false);
+    // 解析选项 WITH (name [ = ] value, ...)
+    fn parse_with_options(&mut self) -> Result<Vec<(String, ast::Expression)>> {
+        let mut options = Vec::new();
+        if self.next_if_token(Token::Keyword(Keyword::With)).is_some() {
+            self.next_expect(Token::OpenParen)?;
+            loop {
+                // 选项名和值之间的等号可以省略，例如 WITH (header true)
+                let name = self.next_ident()?;
+                self.next_if_token(Token::Equal);
+                options.push((name, self.parse_expression()?));
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
                 }
-                Keyword::Default => column.default = Some(self.parse_expression()?),
-                k => return Err(Error::Parse(format!("[Parser] Unexpected keyword {}", k))),
             }
+            self.next_expect(Token::CloseParen)?;
         }
-
-        Ok(column)
+        Ok(options)
     }
 
-    // 解析表达式
-    fn parse_expression(&mut self) -> Result<ast::Expression> {
+    // 列的类型，也用于 CAST
+    fn parse_datatype(&mut self) -> Result<DataType> {
         Ok(match self.next()? {
-            Token::Number(n) => {
-                if n.chars().all(|c| c.is_ascii_digit()) {
-                    // 整数
-                    ast::Consts::Integer(n.parse()?).into()
-                } else {
-                    // 浮点数
-                    ast::Consts::Float(n.parse()?).into()
+            Token::Keyword(Keyword::Int) | Token::Keyword(Keyword::Integer) => DataType::Integer,
+            Token::Keyword(Keyword::Bool) | Token::Keyword(Keyword::Boolean) => DataType::Boolean,
+            Token::Keyword(Keyword::Float) | Token::Keyword(Keyword::Double) => DataType::Float,
+            Token::Keyword(Keyword::String)
+            | Token::Keyword(Keyword::Text)
+            | Token::Keyword(Keyword::Varchar) => DataType::String,
+            Token::Keyword(Keyword::Point) | Token::Keyword(Keyword::Geometry) => DataType::Point,
+            Token::Keyword(Keyword::Uuid) => DataType::Uuid,
+            Token::Keyword(Keyword::Json) => DataType::Json,
+            Token::Keyword(Keyword::Decimal) | Token::Keyword(Keyword::Numeric) => {
+                self.parse_decimal()?
+            }
+            token => return Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
+        })
+    }
+
+    // 解析 DECIMAL(p, s) 的精度和小数位数，省略时默认为 DECIMAL(10, 0)
+    fn parse_decimal(&mut self) -> Result<DataType> {
+        let (mut precision, mut scale) = (10, 0);
+        if self.next_if_token(Token::OpenParen).is_some() {
+            precision = self.next_u8()?;
+            if self.next_if_token(Token::Comma).is_some() {
+                scale = self.next_u8()?;
+            }
+            self.next_expect(Token::CloseParen)?;
+        }
+        if precision == 0 || precision > MAX_PRECISION {
+            return Err(Error::Parse(format!(
+                "[Parser] DECIMAL precision must be between 1 and {}, got {}",
+                MAX_PRECISION, precision
+            )));
+        }
+        if scale > precision {
+            return Err(Error::Parse(format!(
+                "[Parser] DECIMAL scale {} exceeds precision {}",
+                scale, precision
+            )));
+        }
+        Ok(DataType::Decimal(precision, scale))
+    }
+
+    // 解析列信息
+    fn parse_ddl_column(&mut self) -> Result<ast::Column> {
+        let mut column = Column {
+            name: self.next_ident()?.into(),
+            datatype: self.parse_datatype()?,
+            nullable: None,
+            default: None,
+            default_text: None,
+            unique: false,
+        };
+
+        // 解析列的默认值，是否可以为空，以及是否唯一
+        while let Some(Token::Keyword(keyword)) = self.next_if_keyword() {
+            match keyword {
+                Keyword::Null => column.nullable = Some(true),
+                Keyword::Not => {
+                    self.next_expect(Token::Keyword(Keyword::Null))?;
+                    column.nullable = Some(false);
+                }
+                Keyword::Default => {
+                    let start = self.lexer.offset();
+                    let parameters = self.parameters;
+                    column.default = Some(self.parse_expression()?);
+                    if self.parameters != parameters {
+                        return Err(Error::Parse(
+                            "[Parser] Parameters are not allowed in a DEFAULT expression"
+                                .to_string(),
+                        ));
+                    }
+                    let text = self.input[start..self.lexer.offset()].trim();
+                    column.default_text = Some(text.to_string());
+                }
+                Keyword::Unique => column.unique = true,
+                k => return Err(Error::Parse(format!("[Parser] Unexpected keyword {}", k))),
+            }
+        }
+
+        Ok(column)
+    }
+
+    // 解析表达式
+    fn parse_expression(&mut self) -> Result<ast::Expression> {
+        self.parse_expression_at(0)
+    }
+
+    // 使用优先级爬升（Pratt）算法解析表达式，只处理优先级不低于 min_precedence 的中缀运算符
+    fn parse_expression_at(&mut self, min_precedence: u8) -> Result<ast::Expression> {
+        // 前缀运算符，操作数中只能包含优先级不低于它的运算符
+        let mut lhs = match self.peek()?.as_ref().and_then(PrefixOperator::from_token) {
+            Some(op) => {
+                self.next()?;
+                match (&op, self.peek()?) {
+                    // 负号直接合并到整数中，-9223372036854775808 才不会超出范围
+                    (PrefixOperator::Minus, Some(Token::Number(n)))
+                        if n.chars().all(|c| c.is_ascii_digit()) =>
+                    {
+                        self.next()?;
+                        parse_number(&format!("-{}", n))?.into()
+                    }
+                    _ => op.build(self.parse_expression_at(op.precedence())?),
+                }
+            }
+            None => self.parse_expression_atom()?,
+        };
+
+        // 中缀运算符
+        loop {
+            // expr [ NOT ] IN ( ... )、expr [ NOT ] BETWEEN low AND high、expr [ NOT ] LIKE pattern
+            // 和 expr IS [ NOT ] NULL，和比较运算的优先级相同
+            if PREDICATE_PRECEDENCE >= min_precedence {
+                let operator = self
+                    .next_if_negated(Keyword::In)
+                    .map(|negated| (Keyword::In, negated))
+                    .or_else(|| {
+                        self.next_if_negated(Keyword::Between)
+                            .map(|negated| (Keyword::Between, negated))
+                    })
+                    .or_else(|| {
+                        self.next_if_negated(Keyword::Like)
+                            .map(|negated| (Keyword::Like, negated))
+                    })
+                    .or_else(|| {
+                        self.next_if_token(Token::Keyword(Keyword::Is)).map(|_| {
+                            let negated = self.next_if_token(Token::Keyword(Keyword::Not));
+                            (Keyword::Is, negated.is_some())
+                        })
+                    });
+                if let Some((keyword, negated)) = operator {
+                    let predicate = match keyword {
+                        Keyword::In => self.parse_in(lhs)?,
+                        Keyword::Like => self.parse_like(lhs)?,
+                        Keyword::Is => {
+                            self.next_expect(Token::Keyword(Keyword::Null))?;
+                            ast::Expression::Operation(ast::Operation::IsNull(Box::new(lhs)))
+                        }
+                        _ => self.parse_between(lhs)?,
+                    };
+                    lhs = match negated {
+                        true => {
+                            ast::Expression::Operation(ast::Operation::Not(Box::new(predicate)))
+                        }
+                        false => predicate,
+                    };
+                    continue;
+                }
+            }
+            let Some(op) = self
+                .peek()?
+                .as_ref()
+                .and_then(InfixOperator::from_token)
+                .filter(|op| op.precedence() >= min_precedence)
+            else {
+                break;
+            };
+            self.next()?;
+            // 左结合的运算符，右侧只能包含优先级更高的运算符
+            let precedence = match op {
+                InfixOperator::Exponentiate => op.precedence(),
+                _ => op.precedence() + 1,
+            };
+            let rhs = self.parse_expression_at(precedence)?;
+            lhs = op.build(lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    // 关键字或者 NOT 和关键字，例如 IN 和 NOT IN，返回是否有 NOT
+    fn next_if_negated(&mut self, keyword: Keyword) -> Option<bool> {
+        let mut lookahead = self.lexer.clone();
+        match (lookahead.next(), lookahead.next()) {
+            (Some(Ok(Token::Keyword(k))), _) if k == keyword => {
+                self.lexer.next();
+                Some(false)
+            }
+            (Some(Ok(Token::Keyword(Keyword::Not))), Some(Ok(Token::Keyword(k))))
+                if k == keyword =>
+            {
+                self.lexer = lookahead;
+                Some(true)
+            }
+            _ => None,
+        }
+    }
+
+    // BETWEEN low AND high 改写成 expr >= low AND expr <= high
+    // low 和 high 中只能包含优先级更高的运算符，其中的 AND 是 BETWEEN 的一部分
+    fn parse_between(&mut self, expr: ast::Expression) -> Result<ast::Expression> {
+        let low = self.parse_expression_at(PREDICATE_PRECEDENCE + 1)?;
+        self.next_expect(Token::Keyword(Keyword::And))?;
+        let high = self.parse_expression_at(PREDICATE_PRECEDENCE + 1)?;
+        Ok(ast::Expression::Operation(ast::Operation::And(
+            Box::new(ast::Expression::Operation(
+                ast::Operation::GreaterThanOrEqual(Box::new(expr.clone()), Box::new(low)),
+            )),
+            Box::new(ast::Expression::Operation(ast::Operation::LessThanOrEqual(
+                Box::new(expr),
+                Box::new(high),
+            ))),
+        )))
+    }
+
+    // LIKE 之后的模式和可选的 ESCAPE 转义字符
+    fn parse_like(&mut self, expr: ast::Expression) -> Result<ast::Expression> {
+        let pattern = self.parse_expression_at(PREDICATE_PRECEDENCE + 1)?;
+        let escape = match self.next_if_token(Token::Keyword(Keyword::Escape)) {
+            Some(_) => Some(Box::new(
+                self.parse_expression_at(PREDICATE_PRECEDENCE + 1)?,
+            )),
+            None => None,
+        };
+        Ok(ast::Expression::Operation(ast::Operation::Like(
+            Box::new(expr),
+            Box::new(pattern),
+            escape,
+        )))
+    }
+
+    // IN 之后的值列表 (expr [, ...]) 或者子查询 (SELECT ...)
+    fn parse_in(&mut self, expr: ast::Expression) -> Result<ast::Expression> {
+        self.next_expect(Token::OpenParen)?;
+        let expr = Box::new(expr);
+        let result = if self.peek()? == Some(Token::Keyword(Keyword::Select)) {
+            ast::Expression::InSubquery(expr, Box::new(self.parse_query()?))
+        } else {
+            let mut list = Vec::new();
+            loop {
+                list.push(self.parse_expression()?);
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
                 }
             }
+            ast::Expression::Operation(ast::Operation::In(expr, list))
+        };
+        self.next_expect(Token::CloseParen)?;
+        Ok(result)
+    }
+
+    // 解析表达式中不包含运算符的部分
+    fn parse_expression_atom(&mut self) -> Result<ast::Expression> {
+        Ok(match self.next()? {
+            Token::Number(n) => parse_number(&n)?.into(),
             Token::String(s) => ast::Consts::String(s).into(),
+            Token::Parameter(p) => self.parse_parameter(&p)?,
             Token::Keyword(Keyword::True) => ast::Consts::Boolean(true).into(),
             Token::Keyword(Keyword::False) => ast::Consts::Boolean(false).into(),
             Token::Keyword(Keyword::Null) => ast::Consts::Null.into(),
+            // 函数调用 f(a, b)
+            Token::Ident(ident) if self.next_if_token(Token::OpenParen).is_some() => {
+                if function::is_aggregate(&ident) {
+                    return self.parse_aggregate(ident);
+                }
+                let args = self.parse_function_args()?;
+                // regexp_matches(s, p) 等价于 s REGEXP p
+                if ident == "regexp_matches" {
+                    let [l, r] = <[_; 2]>::try_from(args).map_err(|_| {
+                        Error::Parse("[Parser] regexp_matches expects 2 arguments".to_string())
+                    })?;
+                    return Ok(ast::Expression::Operation(ast::Operation::Regexp(
+                        Box::new(l),
+                        Box::new(r),
+                        Default::default(),
+                    )));
+                }
+                ast::Expression::Function(ident, args)
+            }
+            // 列名，可能带有表名 t.a
+            Token::Ident(ident) => {
+                if self.next_if_token(Token::Period).is_some() {
+                    ast::Expression::Field(Some(ident), self.next_ident()?)
+                } else {
+                    ast::Expression::Field(None, ident)
+                }
+            }
+            // CAST(expr AS type)
+            Token::Keyword(Keyword::Cast) => {
+                self.next_expect(Token::OpenParen)?;
+                let expr = self.parse_expression()?;
+                self.next_expect(Token::Keyword(Keyword::As))?;
+                let datatype = self.parse_datatype()?;
+                self.next_expect(Token::CloseParen)?;
+                ast::Expression::Operation(ast::Operation::Cast(Box::new(expr), datatype))
+            }
+            // EXISTS (SELECT ...)
+            Token::Keyword(Keyword::Exists) => {
+                self.next_expect(Token::OpenParen)?;
+                let subquery = self.parse_query()?;
+                self.next_expect(Token::CloseParen)?;
+                ast::Expression::Exists(Box::new(subquery))
+            }
+            // 括号中的表达式
+            Token::OpenParen => {
+                let expr = self.parse_expression()?;
+                self.next_expect(Token::CloseParen)?;
+                expr
+            }
             t => {
                 return Err(Error::Parse(format!(
                     "[Parser] Unexpected expression token {}",
@@ -209,6 +1275,53 @@ impl<'a> Parser<'a> {
         })
     }
 
+    // 解析聚合函数的参数，左括号已经读取
+    // name( { * | [ DISTINCT ] expr [, ...] } [ ORDER BY expr [ ASC | DESC ] [, ...] ] )
+    fn parse_aggregate(&mut self, name: String) -> Result<ast::Expression> {
+        let distinct = self
+            .next_if_token(Token::Keyword(Keyword::Distinct))
+            .is_some();
+        let mut args = Vec::new();
+        if distinct || name != "count" || self.next_if_token(Token::Asterisk).is_none() {
+            loop {
+                args.push(self.parse_expression()?);
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+        let order_by = self.parse_order_by()?;
+        self.next_expect(Token::CloseParen)?;
+        Ok(ast::Expression::Aggregate(ast::Aggregate {
+            name,
+            distinct,
+            args,
+            order_by,
+        }))
+    }
+
+    // 排序 ORDER BY expr [ ASC | DESC ] [, ...]，没有 ORDER BY 时返回空
+    fn parse_order_by(&mut self) -> Result<Vec<(ast::Expression, ast::Direction)>> {
+        let mut order_by = Vec::new();
+        if self.next_if_token(Token::Keyword(Keyword::Order)).is_some() {
+            self.next_expect(Token::Keyword(Keyword::By))?;
+            loop {
+                let expr = self.parse_expression()?;
+                let direction = if self.next_if_token(Token::Keyword(Keyword::Desc)).is_some() {
+                    ast::Direction::Desc
+                } else {
+                    self.next_if_token(Token::Keyword(Keyword::Asc));
+                    ast::Direction::Asc
+                };
+                order_by.push((expr, direction));
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+        Ok(order_by)
+    }
+
     fn peek(&mut self) -> Result<Option<Token>> {
         self.lexer.peek().cloned().transpose()
     }
@@ -216,7 +1329,7 @@ impl<'a> Parser<'a> {
     fn next(&mut self) -> Result<Token> {
         self.lexer
             .next()
-            .unwrap_or_else(|| Err(Error::Parse(format!("[Parser] Unexpected end of input"))))
+            .unwrap_or_else(|| Err(Error::Parse("[Parser] Unexpected end of input".to_string())))
     }
 
     fn next_ident(&mut self) -> Result<String> {
@@ -229,6 +1342,68 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // 解析函数的参数列表，左括号已经被读取
+    fn parse_function_args(&mut self) -> Result<Vec<ast::Expression>> {
+        let mut args = Vec::new();
+        if self.next_if_token(Token::CloseParen).is_none() {
+            loop {
+                args.push(self.parse_expression()?);
+                if self.next_if_token(Token::Comma).is_none() {
+                    break;
+                }
+            }
+            self.next_expect(Token::CloseParen)?;
+        }
+        Ok(args)
+    }
+
+    // ? 按照出现的顺序编号，$n 直接指定编号，编号从 1 开始
+    fn parse_parameter(&mut self, p: &str) -> Result<ast::Expression> {
+        let numbered = p != "?";
+        if self.numbered.is_some_and(|n| n != numbered) {
+            return Err(Error::Parse(
+                "[Parser] Cannot mix ? and $n parameters".to_string(),
+            ));
+        }
+        self.numbered = Some(numbered);
+        let index = match numbered {
+            true => match p[1..].parse::<usize>() {
+                Ok(n) if n > 0 => n - 1,
+                _ => return Err(Error::Parse(format!("[Parser] Invalid parameter {}", p))),
+            },
+            false => self.parameters,
+        };
+        self.parameters = self.parameters.max(index + 1);
+        Ok(ast::Expression::Parameter(index))
+    }
+
+    // 表名，可以带上附加数据库的别名 alias.table_name
+    // 带引号的名字中不能有 .，否则无法和附加数据库的别名区分
+    fn parse_table_name(&mut self) -> Result<Ident> {
+        let mut name = self.next_ident()?;
+        let mut periods = 0;
+        if self.next_if_token(Token::Period).is_some() {
+            name = format!("{}.{}", name, self.next_ident()?);
+            periods = 1;
+        }
+        if name.matches('.').count() > periods {
+            return Err(Error::Parse(format!(
+                "[Parser] Invalid table name {}",
+                name
+            )));
+        }
+        Ok(name.into())
+    }
+
+    fn next_u8(&mut self) -> Result<u8> {
+        match self.next()? {
+            Token::Number(n) => n
+                .parse()
+                .map_err(|_| Error::Parse(format!("[Parser] Invalid number {}", n))),
+            token => Err(Error::Parse(format!("[Parser] Unexpected token {}", token))),
+        }
+    }
+
     fn next_expect(&mut self, expect: Token) -> Result<()> {
         let token = self.next()?;
         if token != expect {
@@ -256,11 +1431,172 @@ impl<'a> Parser<'a> {
     }
 }
 
+// 生成 SQL 中的名字，不是小写或者是关键字的名字需要加上双引号，解析之后得到原来的名字
+pub fn quote_ident(name: &str) -> String {
+    let plain = name.chars().next().is_some_and(|c| c.is_alphabetic())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && name.to_lowercase() == name
+        && Keyword::from_str(name).is_none();
+    match plain {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+// 数字常量，只有数字时是整数，否则是浮点数，超出范围时报错
+fn parse_number(n: &str) -> Result<ast::Consts> {
+    let out_of_range = || Error::Parse(format!("[Parser] Number {} is out of range", n));
+    if n.trim_start_matches('-')
+        .chars()
+        .all(|c| c.is_ascii_digit())
+    {
+        return n
+            .parse()
+            .map(ast::Consts::Integer)
+            .map_err(|_| out_of_range());
+    }
+    match n.parse::<f64>()? {
+        f if f.is_finite() => Ok(ast::Consts::Float(f)),
+        _ => Err(out_of_range()),
+    }
+}
+
+// 前缀运算符
+enum PrefixOperator {
+    Not,
+    Minus,
+    Plus,
+}
+
+impl PrefixOperator {
+    fn from_token(token: &Token) -> Option<Self> {
+        Some(match token {
+            Token::Keyword(Keyword::Not) => Self::Not,
+            Token::Minus => Self::Minus,
+            Token::Plus => Self::Plus,
+            _ => return None,
+        })
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Not => 3,
+            Self::Minus | Self::Plus => 8,
+        }
+    }
+
+    fn build(&self, expr: ast::Expression) -> ast::Expression {
+        let expr = Box::new(expr);
+        ast::Expression::Operation(match self {
+            Self::Not => ast::Operation::Not(expr),
+            Self::Minus => ast::Operation::Negate(expr),
+            Self::Plus => ast::Operation::Identity(expr),
+        })
+    }
+}
+
+// IN、BETWEEN、LIKE 和 IS NULL 的优先级，和比较运算相同
+const PREDICATE_PRECEDENCE: u8 = 4;
+
+// 中缀运算符
+enum InfixOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    Regexp,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Exponentiate,
+    JsonGet,
+    JsonGetText,
+}
+
+impl InfixOperator {
+    fn from_token(token: &Token) -> Option<Self> {
+        Some(match token {
+            Token::Keyword(Keyword::Or) => Self::Or,
+            Token::Keyword(Keyword::And) => Self::And,
+            Token::Equal => Self::Equal,
+            Token::NotEqual | Token::LessOrGreaterThan => Self::NotEqual,
+            Token::GreaterThan => Self::GreaterThan,
+            Token::GreaterThanOrEqual => Self::GreaterThanOrEqual,
+            Token::LessThan => Self::LessThan,
+            Token::LessThanOrEqual => Self::LessThanOrEqual,
+            Token::Keyword(Keyword::Regexp) => Self::Regexp,
+            Token::Plus => Self::Add,
+            Token::Minus => Self::Subtract,
+            Token::Asterisk => Self::Multiply,
+            Token::Slash => Self::Divide,
+            Token::Percent => Self::Remainder,
+            Token::Caret => Self::Exponentiate,
+            Token::Arrow => Self::JsonGet,
+            Token::DoubleArrow => Self::JsonGetText,
+            _ => return None,
+        })
+    }
+
+    // 运算符的优先级，数值越大优先级越高
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Equal
+            | Self::NotEqual
+            | Self::GreaterThan
+            | Self::GreaterThanOrEqual
+            | Self::LessThan
+            | Self::LessThanOrEqual
+            | Self::Regexp => 4,
+            Self::Add | Self::Subtract => 5,
+            Self::Multiply | Self::Divide | Self::Remainder => 6,
+            Self::Exponentiate => 7,
+            Self::JsonGet | Self::JsonGetText => 9,
+        }
+    }
+
+    fn build(&self, lhs: ast::Expression, rhs: ast::Expression) -> ast::Expression {
+        let (l, r) = (Box::new(lhs), Box::new(rhs));
+        ast::Expression::Operation(match self {
+            Self::Or => ast::Operation::Or(l, r),
+            Self::And => ast::Operation::And(l, r),
+            Self::Equal => ast::Operation::Equal(l, r),
+            Self::NotEqual => ast::Operation::NotEqual(l, r),
+            Self::GreaterThan => ast::Operation::GreaterThan(l, r),
+            Self::GreaterThanOrEqual => ast::Operation::GreaterThanOrEqual(l, r),
+            Self::LessThan => ast::Operation::LessThan(l, r),
+            Self::LessThanOrEqual => ast::Operation::LessThanOrEqual(l, r),
+            Self::Regexp => ast::Operation::Regexp(l, r, Default::default()),
+            Self::Add => ast::Operation::Add(l, r),
+            Self::Subtract => ast::Operation::Subtract(l, r),
+            Self::Multiply => ast::Operation::Multiply(l, r),
+            Self::Divide => ast::Operation::Divide(l, r),
+            Self::Remainder => ast::Operation::Remainder(l, r),
+            Self::Exponentiate => ast::Operation::Exponentiate(l, r),
+            Self::JsonGet => ast::Operation::JsonGet(l, r),
+            Self::JsonGetText => ast::Operation::JsonGetText(l, r),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{error::Result, sql::parser::ast};
+    use crate::{
+        error::Result,
+        sql::{
+            parser::ast,
+            types::{DataType, Value},
+        },
+    };
 
-    use super::Parser;
+    use super::{quote_ident, Parser};
 
     #[test]
     fn test_parser_create_table() -> Result<()> {
@@ -296,6 +1632,111 @@ mod tests {
 
         let stmt3 = Parser::new(sql3).parse();
         assert!(stmt3.is_err());
+
+        let sql4 = "create table tbl1 (a int) with (storage = 'columnar');";
+        let stmt4 = Parser::new(sql4).parse()?;
+        assert_eq!(
+            stmt4,
+            ast::Statement::CreateTable {
+                name: "tbl1".into(),
+                columns: vec![ast::Column {
+                    name: "a".into(),
+                    datatype: DataType::Integer,
+                    nullable: None,
+                    default: None,
+                    default_text: None,
+                    unique: false,
+                }],
+                options: vec![(
+                    "storage".to_string(),
+                    ast::Consts::String("columnar".to_string()).into()
+                )],
+                location: None,
+            }
+        );
+        assert!(Parser::new("create table tbl1 (a int) with ();")
+            .parse()
+            .is_err());
+        assert!(Parser::new("create table tbl1 (a int) with (storage);")
+            .parse()
+            .is_err());
+
+        let sql5 = "create external table tbl1 (a int) location 'data.csv' with (header = 'true');";
+        let stmt5 = Parser::new(sql5).parse()?;
+        assert_eq!(
+            stmt5,
+            ast::Statement::CreateTable {
+                name: "tbl1".into(),
+                columns: vec![ast::Column {
+                    name: "a".into(),
+                    datatype: DataType::Integer,
+                    nullable: None,
+                    default: None,
+                    default_text: None,
+                    unique: false,
+                }],
+                options: vec![(
+                    "header".to_string(),
+                    ast::Consts::String("true".to_string()).into()
+                )],
+                location: Some("data.csv".to_string()),
+            }
+        );
+        // 外部表需要指定文件路径
+        assert!(Parser::new("create external table tbl1 (a int);")
+            .parse()
+            .is_err());
+        assert!(
+            Parser::new("create external table tbl1 (a int) location data;")
+                .parse()
+                .is_err()
+        );
+
+        // UNIQUE 可以和其他列约束以任意顺序出现
+        match Parser::new("create table tbl1 (a int, b text unique not null, c int default 1);")
+            .parse()?
+        {
+            ast::Statement::CreateTable { columns, .. } => {
+                assert_eq!(
+                    columns.iter().map(|c| c.unique).collect::<Vec<_>>(),
+                    vec![false, true, false]
+                );
+                assert_eq!(columns[1].nullable, Some(false));
+            }
+            stmt => panic!("unexpected statement {:?}", stmt),
+        }
+
+        // DECIMAL 默认是 DECIMAL(10, 0)，以及 UUID
+        let datatypes = |sql: &str| -> Result<Vec<DataType>> {
+            match Parser::new(sql).parse()? {
+                ast::Statement::CreateTable { columns, .. } => {
+                    Ok(columns.into_iter().map(|c| c.datatype).collect())
+                }
+                stmt => panic!("unexpected statement {:?}", stmt),
+            }
+        };
+        assert_eq!(
+            datatypes(
+                "create table t (a decimal, b decimal(5), c numeric(12, 2), d decimal(38, 38), e uuid);"
+            )?,
+            vec![
+                DataType::Decimal(10, 0),
+                DataType::Decimal(5, 0),
+                DataType::Decimal(12, 2),
+                DataType::Decimal(38, 38),
+                DataType::Uuid,
+            ]
+        );
+        for sql in [
+            "create table t (a decimal(0));",
+            "create table t (a decimal(39, 2));",
+            "create table t (a decimal(5, 6));",
+            "create table t (a decimal(5,));",
+            "create table t (a decimal(1.5));",
+            "create table t (a decimal(300));",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
         Ok(())
     }
 
@@ -306,7 +1747,7 @@ mod tests {
         assert_eq!(
             stmt1,
             ast::Statement::Insert {
-                table_name: "tbl1".to_string(),
+                table_name: "tbl1".into(),
                 columns: None,
                 values: vec![vec![
                     ast::Consts::Integer(1).into(),
@@ -315,6 +1756,7 @@ mod tests {
                     ast::Consts::String("a".to_string()).into(),
                     ast::Consts::Boolean(true).into(),
                 ]],
+                query: None,
             }
         );
 
@@ -323,7 +1765,7 @@ mod tests {
         assert_eq!(
             stmt2,
             ast::Statement::Insert {
-                table_name: "tbl2".to_string(),
+                table_name: "tbl2".into(),
                 columns: Some(vec!["c1".to_string(), "c2".to_string(), "c3".to_string()]),
                 values: vec![
                     vec![
@@ -337,9 +1779,61 @@ mod tests {
                         ast::Consts::Boolean(false).into(),
                     ],
                 ],
+                query: None,
+            }
+        );
+
+        let sql3 = "insert into tbl3 values (1, default), (default, 2);";
+        let stmt3 = Parser::new(sql3).parse()?;
+        assert_eq!(
+            stmt3,
+            ast::Statement::Insert {
+                table_name: "tbl3".into(),
+                columns: None,
+                values: vec![
+                    vec![ast::Consts::Integer(1).into(), ast::Expression::Default],
+                    vec![ast::Expression::Default, ast::Consts::Integer(2).into()],
+                ],
+                query: None,
             }
         );
 
+        let sql4 = "insert into tbl4 default values;";
+        let stmt4 = Parser::new(sql4).parse()?;
+        assert_eq!(
+            stmt4,
+            ast::Statement::Insert {
+                table_name: "tbl4".into(),
+                columns: None,
+                values: vec![vec![]],
+                query: None,
+            }
+        );
+        assert!(Parser::new("insert into tbl4 (a) default values;")
+            .parse()
+            .is_err());
+
+        let sql5 = "insert into tbl5 (a) select * from (values (1), (2)) v;";
+        match Parser::new(sql5).parse()? {
+            ast::Statement::Insert {
+                columns,
+                values,
+                query: Some(query),
+                ..
+            } => {
+                assert_eq!(columns, Some(vec!["a".to_string()]));
+                assert!(values.is_empty());
+                assert!(matches!(
+                    *query,
+                    ast::Statement::Select {
+                        subquery: Some(_),
+                        ..
+                    }
+                ));
+            }
+            stmt => panic!("unexpected statement {:?}", stmt),
+        }
+
         Ok(())
     }
 
@@ -350,9 +1844,1291 @@ mod tests {
         assert_eq!(
             stmt,
             ast::Statement::Select {
-                table_name: "tbl1".to_string()
+                select: vec![ast::SelectItem::Wildcard(None)],
+                distinct: false,
+                table_name: Some("tbl1".into()),
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: None,
+                group_by: vec![],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: None,
+            }
+        );
+
+        let sql = "select * from tbl1 as of version 10;";
+        let stmt = Parser::new(sql).parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::Select {
+                select: vec![ast::SelectItem::Wildcard(None)],
+                distinct: false,
+                table_name: Some("tbl1".into()),
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: None,
+                group_by: vec![],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: Some(10),
+            }
+        );
+
+        let sql = "select tbl1.*, b, tbl1.a, 1, * from tbl1;";
+        let stmt = Parser::new(sql).parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::Select {
+                select: vec![
+                    ast::SelectItem::Wildcard(Some("tbl1".to_string())),
+                    ast::SelectItem::Expr(ast::Expression::Field(None, "b".to_string()), None),
+                    ast::SelectItem::Expr(
+                        ast::Expression::Field(Some("tbl1".to_string()), "a".to_string()),
+                        None
+                    ),
+                    ast::SelectItem::Expr(ast::Consts::Integer(1).into(), None),
+                    ast::SelectItem::Wildcard(None),
+                ],
+                distinct: false,
+                table_name: Some("tbl1".into()),
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: None,
+                group_by: vec![],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: None,
+            }
+        );
+        assert!(Parser::new("select from tbl1;").parse().is_err());
+        assert!(Parser::new("select tbl1. from tbl1;").parse().is_err());
+
+        assert!(Parser::new("select * from tbl1 as of 10;").parse().is_err());
+        assert!(Parser::new("select * from tbl1 as of version 1.5;")
+            .parse()
+            .is_err());
+
+        let sql = "select * from generate_series(1, 10 + 1) group by a;";
+        let stmt = Parser::new(sql).parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::Select {
+                select: vec![ast::SelectItem::Wildcard(None)],
+                distinct: false,
+                table_name: Some("generate_series".into()),
+                table_args: Some(vec![
+                    ast::Consts::Integer(1).into(),
+                    ast::Expression::Operation(ast::Operation::Add(
+                        Box::new(ast::Consts::Integer(10).into()),
+                        Box::new(ast::Consts::Integer(1).into()),
+                    )),
+                ]),
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: None,
+                group_by: vec![ast::Expression::Field(None, "a".to_string())],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: None,
+            }
+        );
+        let Ok(ast::Statement::Select { table_args, .. }) =
+            Parser::new("select * from f();").parse()
+        else {
+            panic!("expected select");
+        };
+        assert_eq!(table_args, Some(vec![]));
+        assert!(Parser::new("select * from f(1;").parse().is_err());
+
+        let sql = "select a from tbl1 as of version 3 where a = 1 and b > 2 group by a;";
+        let stmt = Parser::new(sql).parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::Select {
+                select: vec![ast::SelectItem::Expr(
+                    ast::Expression::Field(None, "a".to_string()),
+                    None
+                )],
+                distinct: false,
+                table_name: Some("tbl1".into()),
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: Some(ast::Expression::Operation(ast::Operation::And(
+                    Box::new(ast::Expression::Operation(ast::Operation::Equal(
+                        Box::new(ast::Expression::Field(None, "a".to_string())),
+                        Box::new(ast::Consts::Integer(1).into()),
+                    ))),
+                    Box::new(ast::Expression::Operation(ast::Operation::GreaterThan(
+                        Box::new(ast::Expression::Field(None, "b".to_string())),
+                        Box::new(ast::Consts::Integer(2).into()),
+                    ))),
+                ))),
+                group_by: vec![ast::Expression::Field(None, "a".to_string())],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: Some(3),
             }
         );
+        assert!(Parser::new("select * from tbl1 where;").parse().is_err());
+        assert!(Parser::new("select * from tbl1 group by a where a = 1;")
+            .parse()
+            .is_err());
+
+        let sql = "select a from tbl1 group by a having count(*) > 1;";
+        let Ok(ast::Statement::Select { having, .. }) = Parser::new(sql).parse() else {
+            panic!("expected select");
+        };
+        assert_eq!(
+            having.map(|e| e.to_string()),
+            Some("count(*) > 1".to_string())
+        );
+        assert!(
+            Parser::new("select a from tbl1 having count(*) > 1 group by a;")
+                .parse()
+                .is_err()
+        );
+
+        let Ok(ast::Statement::Select {
+            distinct, select, ..
+        }) = Parser::new("select distinct a, count(distinct b) from tbl1;").parse()
+        else {
+            panic!("expected select");
+        };
+        assert!(distinct);
+        assert_eq!(select.len(), 2);
+
+        let sql = "select * from a x join b on x.id = b.aid inner join c as y on y.id = b.cid;";
+        let Ok(ast::Statement::Select { alias, joins, .. }) = Parser::new(sql).parse() else {
+            panic!("expected select");
+        };
+        assert_eq!(alias, Some("x".into()));
+        assert_eq!(
+            joins
+                .iter()
+                .map(|j| (
+                    j.table_name.to_string(),
+                    j.alias.clone(),
+                    j.predicate.to_string()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("b".to_string(), None, "x.id = b.aid".to_string()),
+                (
+                    "c".to_string(),
+                    Some("y".into()),
+                    "y.id = b.cid".to_string()
+                ),
+            ]
+        );
+        let sql =
+            "select * from a left join b on true right outer join c on true full join d on true;";
+        let Ok(ast::Statement::Select { joins, .. }) = Parser::new(sql).parse() else {
+            panic!("expected select");
+        };
+        assert_eq!(
+            joins.iter().map(|j| j.kind).collect::<Vec<_>>(),
+            vec![
+                ast::JoinType::Left,
+                ast::JoinType::Right,
+                ast::JoinType::Full
+            ]
+        );
+        assert!(Parser::new("select * from a join b;").parse().is_err());
+        assert!(Parser::new("select * from a inner outer join b on true;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("select * from a left b on true;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("select * from a inner b on a.id = b.id;")
+            .parse()
+            .is_err());
+
+        // FROM 中的子查询，别名作为表名
+        let sql = "select * from (select a from tbl1 where a > 1) as s join (select * from tbl2) t on s.a = t.a;";
+        let Ok(ast::Statement::Select {
+            table_name,
+            subquery: Some(subquery),
+            alias: None,
+            joins,
+            ..
+        }) = Parser::new(sql).parse()
+        else {
+            panic!("expected select with subquery");
+        };
+        assert_eq!(table_name, Some("s".into()));
+        assert!(matches!(
+            *subquery,
+            ast::Statement::Select { table_name: Some(ref t), filter: Some(_), .. } if t == "tbl1"
+        ));
+        assert_eq!(joins[0].table_name, "t");
+        assert!(joins[0].subquery.is_some());
+        assert!(Parser::new("select * from (select a from tbl1);")
+            .parse()
+            .is_err());
+        assert!(Parser::new("select * from (select a from tbl1 s;")
+            .parse()
+            .is_err());
+
+        // EXISTS 子查询
+        let sql =
+            "select * from a where not exists (select * from b where b.id = a.id) and a.x > 1;";
+        let Ok(ast::Statement::Select {
+            filter: Some(ast::Expression::Operation(ast::Operation::And(l, r))),
+            ..
+        }) = Parser::new(sql).parse()
+        else {
+            panic!("expected select with filter");
+        };
+        assert!(matches!(
+            *l,
+            ast::Expression::Operation(ast::Operation::Not(ref e))
+                if matches!(**e, ast::Expression::Exists(_))
+        ));
+        assert_eq!(r.to_string(), "a.x > 1");
+        assert!(Parser::new("select * from a where exists b;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("select * from a where exists (values (1));")
+            .parse()
+            .is_err());
+
+        // IN 的值列表和子查询，NOT IN 等价于 NOT (expr IN ...)
+        let filter = |sql: &str| match Parser::new(sql).parse() {
+            Ok(ast::Statement::Select { filter, .. }) => Ok(filter.map(|f| f.to_string())),
+            Ok(stmt) => panic!("expected select, got {:?}", stmt),
+            Err(e) => Err(e),
+        };
+        assert_eq!(
+            filter("select * from a where x + 1 in (1, y, 2 * 3) and y not in (null);")?,
+            Some("x + 1 IN (1, y, 2 * 3) AND NOT y IN (NULL)".to_string())
+        );
+        assert_eq!(
+            filter("select * from a where not x in (1) = false;")?,
+            Some("NOT x IN (1) = FALSE".to_string())
+        );
+        let Ok(ast::Statement::Select {
+            filter: Some(ast::Expression::InSubquery(expr, subquery)),
+            ..
+        }) = Parser::new("select * from a where a.x in (select y from b);").parse()
+        else {
+            panic!("expected IN subquery");
+        };
+        assert_eq!(expr.to_string(), "a.x");
+        assert!(matches!(*subquery, ast::Statement::Select { .. }));
+        // BETWEEN 改写成范围比较，其中的 AND 属于 BETWEEN
+        assert_eq!(
+            filter("select * from a where x between 1 and y + 1 and y not between -1 and 2 * 3;")?,
+            Some("x >= 1 AND x <= y + 1 AND NOT (y >= -1 AND y <= 2 * 3)".to_string())
+        );
+        assert_eq!(
+            filter("select * from a where x between 1 and 2 = true;")?,
+            Some("(x >= 1 AND x <= 2) = TRUE".to_string())
+        );
+        assert!(filter("select * from a where x between 1;").is_err());
+        assert!(filter("select * from a where x between 1 or 2;").is_err());
+        // LIKE 的模式和 ESCAPE 中只能包含优先级更高的运算符
+        assert_eq!(
+            filter("select * from a where x like 'a%' and y not like b escape c;")?,
+            Some("x LIKE 'a%' AND NOT y LIKE b ESCAPE c".to_string())
+        );
+        assert_eq!(
+            filter("select * from a where x like 'a' = true;")?,
+            Some("x LIKE 'a' = TRUE".to_string())
+        );
+        assert!(filter("select * from a where x like;").is_err());
+        // IS NOT NULL 等价于 NOT (expr IS NULL)
+        assert_eq!(
+            filter("select * from a where x + 1 is null and y is not null or x is null = false;")?,
+            Some("x + 1 IS NULL AND NOT y IS NULL OR x IS NULL = FALSE".to_string())
+        );
+        assert!(filter("select * from a where x is 1;").is_err());
+        assert!(filter("select * from a where x is not;").is_err());
+        assert!(filter("select * from a where x not is null;").is_err());
+        // CAST 的类型和建表时列的类型写法相同
+        assert_eq!(
+            filter("select * from a where cast(x + 1 as text) = '2' and -cast(y as double) < 1;")?,
+            Some("CAST(x + 1 AS STRING) = '2' AND -CAST(y AS FLOAT) < 1".to_string())
+        );
+        assert!(filter("select * from a where cast(x) = 1;").is_err());
+        assert!(filter("select * from a where cast(x as date) = 1;").is_err());
+        assert!(filter("select * from a where cast x as int = 1;").is_err());
+        assert!(filter("select * from a where x like 'a' escape;").is_err());
+        assert!(filter("select * from a where x in ();").is_err());
+        assert!(filter("select * from a where x in 1;").is_err());
+        assert!(filter("select * from a where x not (1);").is_err());
+        // JSON 运算符是左结合的，优先级高于其他的二元运算符
+        assert_eq!(
+            filter("select * from a where doc->'a'->>0 = 'x' and -doc -> 'n' ^ 2 > 1;")?,
+            Some("doc -> 'a' ->> 0 = 'x' AND -doc -> 'n' ^ 2 > 1".to_string())
+        );
+        assert_eq!(
+            filter("select * from a where doc ->> (k + 1) is null;")?,
+            Some("doc ->> (k + 1) IS NULL".to_string())
+        );
+        assert!(filter("select * from a where doc - > 'a';").is_err());
+        assert!(filter("select * from a where doc ->;").is_err());
+
+        let sql = "select a, b from tbl1 where a > 1 order by b desc, a + 1, a asc;";
+        let Ok(ast::Statement::Select { order_by, .. }) = Parser::new(sql).parse() else {
+            panic!("expected select");
+        };
+        let field = |name: &str| ast::Expression::Field(None, name.to_string());
+        assert_eq!(
+            order_by,
+            vec![
+                (field("b"), ast::Direction::Desc),
+                (
+                    ast::Expression::Operation(ast::Operation::Add(
+                        Box::new(field("a")),
+                        Box::new(ast::Consts::Integer(1).into()),
+                    )),
+                    ast::Direction::Asc
+                ),
+                (field("a"), ast::Direction::Asc),
+            ]
+        );
+        let sql = "select a as x, b y, a + 1 from tbl1;";
+        let Ok(ast::Statement::Select { select, .. }) = Parser::new(sql).parse() else {
+            panic!("expected select");
+        };
+        assert_eq!(
+            select,
+            vec![
+                ast::SelectItem::Expr(field("a"), Some("x".to_string())),
+                ast::SelectItem::Expr(field("b"), Some("y".to_string())),
+                ast::SelectItem::Expr(
+                    ast::Expression::Operation(ast::Operation::Add(
+                        Box::new(field("a")),
+                        Box::new(ast::Consts::Integer(1).into()),
+                    )),
+                    None
+                ),
+            ]
+        );
+        let aliases = |sql: &str| match Parser::new(sql).parse() {
+            Ok(ast::Statement::Select { alias, as_of, .. }) => Ok((alias, as_of)),
+            Ok(stmt) => panic!("expected select, got {:?}", stmt),
+            Err(e) => Err(e),
+        };
+        assert_eq!(
+            aliases("select u.a from tbl1 u;")?,
+            (Some("u".into()), None)
+        );
+        assert_eq!(
+            aliases("select * from tbl1 as u as of version 3;")?,
+            (Some("u".into()), Some(3))
+        );
+        assert_eq!(
+            aliases("select * from tbl1 as of version 3;")?,
+            (None, Some(3))
+        );
+        assert_eq!(
+            aliases("select * from generate_series(1, 3) g;")?,
+            (Some("g".into()), None)
+        );
+        assert!(aliases("select * from tbl1 as;").is_err());
+        assert!(aliases("select * from tbl1 u v;").is_err());
+
+        assert!(Parser::new("select a as from tbl1;").parse().is_err());
+        assert!(Parser::new("select a x y from tbl1;").parse().is_err());
+        assert!(Parser::new("select * from tbl1 order by;").parse().is_err());
+        assert!(Parser::new("select * from tbl1 order a;").parse().is_err());
+        assert!(Parser::new("select * from tbl1 order by a group by a;")
+            .parse()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_aggregate() -> Result<()> {
+        let field = |name: &str| ast::Expression::Field(None, name.to_string());
+        assert_eq!(
+            Parser::new("select a, count(*), string_agg(b, ',' order by c desc, d asc) from tbl1 group by a;")
+                .parse()?,
+            ast::Statement::Select {
+                select: vec![
+                    ast::SelectItem::Expr(field("a"), None),
+                    ast::SelectItem::Expr(ast::Expression::Aggregate(ast::Aggregate {
+                        name: "count".to_string(),
+                        distinct: false,
+                        args: vec![],
+                        order_by: vec![],
+                    }), None),
+                    ast::SelectItem::Expr(ast::Expression::Aggregate(ast::Aggregate {
+                        name: "string_agg".to_string(),
+                        distinct: false,
+                        args: vec![field("b"), ast::Consts::String(",".to_string()).into()],
+                        order_by: vec![
+                            (field("c"), ast::Direction::Desc),
+                            (field("d"), ast::Direction::Asc),
+                        ],
+                    }), None),
+                ],
+                distinct: false,
+                table_name: Some("tbl1".into()),
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: None,
+                group_by: vec![field("a")],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: None,
+            }
+        );
+        let stmt =
+            Parser::new("select sum(a) + 1, group_concat(b order by a) from tbl1;").parse()?;
+        let ast::Statement::Select { select, .. } = stmt else {
+            panic!("expected select statement");
+        };
+        let exprs = select
+            .iter()
+            .map(|item| match item {
+                ast::SelectItem::Expr(e, _) => e.to_string(),
+                ast::SelectItem::Wildcard(_) => "*".to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(exprs, vec!["sum(a) + 1", "group_concat(b ORDER BY a)"]);
+
+        for sql in [
+            "select count() from tbl1;",
+            "select sum(*) from tbl1;",
+            "select count(*) from tbl1 group a;",
+            "select count(*) from tbl1 group by;",
+            "select string_agg(a, ',' order a) from tbl1;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_set_operation() -> Result<()> {
+        // 用表名表示每个查询，括号表示运算的顺序
+        fn shape(stmt: &ast::Statement) -> String {
+            match stmt {
+                ast::Statement::Select { table_name, .. } => {
+                    table_name.as_deref().unwrap_or("_").to_string()
+                }
+                ast::Statement::SetOperation {
+                    op,
+                    all,
+                    left,
+                    right,
+                    ..
+                } => format!(
+                    "({} {}{} {})",
+                    shape(left),
+                    op,
+                    if *all { " ALL" } else { "" },
+                    shape(right)
+                ),
+                stmt => panic!("unexpected statement {:?}", stmt),
+            }
+        }
+        for (sql, expected) in [
+            ("select a from t1 union select a from t2;", "(t1 UNION t2)"),
+            (
+                "select a from t1 union all select a from t2 except select a from t3;",
+                "((t1 UNION ALL t2) EXCEPT t3)",
+            ),
+            (
+                "select a from t1 union select a from t2 intersect all select a from t3;",
+                "(t1 UNION (t2 INTERSECT ALL t3))",
+            ),
+            (
+                "select 1 intersect select a from t1 intersect select a from t2;",
+                "((_ INTERSECT t1) INTERSECT t2)",
+            ),
+            (
+                "select * from (select a from t1 except select a from t2) s;",
+                "s",
+            ),
+        ] {
+            assert_eq!(shape(&Parser::new(sql).parse()?), expected, "{}", sql);
+        }
+
+        // ORDER BY 属于整个结果，最后一个查询中没有排序
+        let field = |name: &str| ast::Expression::Field(None, name.to_string());
+        let ast::Statement::SetOperation {
+            right, order_by, ..
+        } = Parser::new("select a from t1 union select b from t2 order by a desc;").parse()?
+        else {
+            panic!("expected set operation");
+        };
+        assert_eq!(order_by, vec![(field("a"), ast::Direction::Desc)]);
+        assert!(
+            matches!(*right, ast::Statement::Select { ref order_by, .. } if order_by.is_empty())
+        );
+
+        let ast::Statement::Select {
+            filter: Some(ast::Expression::InSubquery(_, subquery)),
+            ..
+        } = Parser::new("select * from t1 where a in (select a from t2 union select a from t3);")
+            .parse()?
+        else {
+            panic!("expected IN subquery");
+        };
+        assert_eq!(shape(&subquery), "(t2 UNION t3)");
+
+        for sql in [
+            "select a from t1 order by a union select a from t2;",
+            "select a from t1 union;",
+            "select a from t1 union all all select a from t2;",
+            "select a from t1 union values (1);",
+            "select a from t1 intersect distinct select a from t2;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_rollback_transaction() -> Result<()> {
+        assert_eq!(
+            Parser::new("rollback transaction 12;").parse()?,
+            ast::Statement::RollbackTransaction { version: 12 }
+        );
+        assert!(Parser::new("rollback transaction;").parse().is_err());
+        assert!(Parser::new("rollback transaction 1.5;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_transaction_control() -> Result<()> {
+        for (sql, stmt) in [
+            ("begin;", ast::Statement::Begin),
+            ("BEGIN TRANSACTION;", ast::Statement::Begin),
+            ("commit;", ast::Statement::Commit),
+            ("commit transaction;", ast::Statement::Commit),
+            ("rollback;", ast::Statement::Rollback),
+        ] {
+            assert_eq!(Parser::new(sql).parse()?, stmt);
+        }
+        assert!(Parser::new("begin work;").parse().is_err());
+        assert!(Parser::new("commit 1;").parse().is_err());
+        assert!(Parser::new("rollback 1;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_show_indexes() -> Result<()> {
+        assert_eq!(
+            Parser::new("show indexes from tbl1;").parse()?,
+            ast::Statement::ShowIndexes {
+                table_name: "tbl1".into()
+            }
+        );
+        assert!(Parser::new("show indexes tbl1;").parse().is_err());
+        assert_eq!(
+            Parser::new("show create table tbl1;").parse()?,
+            ast::Statement::ShowCreateTable {
+                table_name: "tbl1".into()
+            }
+        );
+        assert!(Parser::new("show create tbl1;").parse().is_err());
+        assert_eq!(
+            Parser::new("DESCRIBE \"Tbl1\";").parse()?,
+            ast::Statement::Describe {
+                table_name: "Tbl1".into()
+            }
+        );
+        assert!(Parser::new("describe;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_ident() -> Result<()> {
+        for (name, quoted) in [
+            ("users", "users"),
+            ("user_1", "user_1"),
+            ("Users", "\"Users\""),
+            ("select", "\"select\""),
+            ("my table", "\"my table\""),
+            ("a\"b", "\"a\"\"b\""),
+            ("1a", "\"1a\""),
+        ] {
+            assert_eq!(quote_ident(name), quoted);
+            assert_eq!(
+                Parser::new(&format!("describe {};", quoted)).parse()?,
+                ast::Statement::Describe {
+                    table_name: name.into()
+                }
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_set_kill() -> Result<()> {
+        assert_eq!(
+            Parser::new("set statement_timeout = 100;").parse()?,
+            ast::Statement::Set {
+                name: "statement_timeout".to_string(),
+                value: ast::Consts::Integer(100).into(),
+                global: false,
+            }
+        );
+        assert_eq!(
+            Parser::new("set global prune_versions = true;").parse()?,
+            ast::Statement::Set {
+                name: "prune_versions".to_string(),
+                value: ast::Consts::Boolean(true).into(),
+                global: true,
+            }
+        );
+        assert_eq!(
+            Parser::new("show variables;").parse()?,
+            ast::Statement::ShowVariables { global: false }
+        );
+        assert_eq!(
+            Parser::new("show global variables;").parse()?,
+            ast::Statement::ShowVariables { global: true }
+        );
+        assert!(Parser::new("show global;").parse().is_err());
+        assert_eq!(
+            Parser::new("kill 3;").parse()?,
+            ast::Statement::Kill { session_id: 3 }
+        );
+        assert!(Parser::new("set statement_timeout 100;").parse().is_err());
+        assert!(Parser::new("kill a;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_expression() -> Result<()> {
+        let parse = |expr: &str| -> Result<ast::Expression> {
+            match Parser::new(&format!("values ({});", expr)).parse()? {
+                ast::Statement::Values { mut rows } => Ok(rows.remove(0).remove(0)),
+                _ => unreachable!(),
+            }
+        };
+        let evaluate = |expr: &str| parse(expr)?.evaluate(&Vec::new());
+
+        // 没有 FROM 的查询
+        assert_eq!(
+            Parser::new("select 1, f('a');").parse()?,
+            ast::Statement::Select {
+                select: vec![
+                    ast::SelectItem::Expr(ast::Consts::Integer(1).into(), None),
+                    ast::SelectItem::Expr(
+                        ast::Expression::Function(
+                            "f".to_string(),
+                            vec![ast::Consts::String("a".to_string()).into()],
+                        ),
+                        None
+                    ),
+                ],
+                distinct: false,
+                table_name: None,
+                table_args: None,
+                subquery: None,
+                alias: None,
+                joins: vec![],
+                filter: None,
+                group_by: vec![],
+                having: None,
+                order_by: vec![],
+                limit: None,
+                as_of: None,
+            }
+        );
+        assert!(Parser::new("select f(1;").parse().is_err());
+        assert!(Parser::new("select f(1,);").parse().is_err());
+
+        // 运算符的优先级和结合方向
+        for (expr, expect) in [
+            ("1 + 2 * 3 - 4 / 5 % 6", "1 + 2 * 3 - 4 / 5 % 6"),
+            ("(1 + 2) * 3", "(1 + 2) * 3"),
+            ("1 - (2 - 3)", "1 - (2 - 3)"),
+            ("1 - 2 - 3", "1 - 2 - 3"),
+            ("2 ^ 3 ^ 2", "2 ^ 3 ^ 2"),
+            ("(2 ^ 3) ^ 2", "(2 ^ 3) ^ 2"),
+            ("-a ^ 2", "-a ^ 2"),
+            ("-(a + b)", "-(a + b)"),
+            (
+                "not a = 1 and b <> 2 or c >= 3",
+                "NOT a = 1 AND b != 2 OR c >= 3",
+            ),
+            ("not (a or b)", "NOT (a OR b)"),
+            ("a < 1 = (b <= t.c)", "a < 1 = (b <= t.c)"),
+            ("f()", "f()"),
+            ("f(a, 1 + 2) * -g(t.b)", "f(a, 1 + 2) * -g(t.b)"),
+            (
+                "a regexp 'x' and not regexp_matches(b, '^' + c)",
+                "a REGEXP 'x' AND NOT b REGEXP '^' + c",
+            ),
+        ] {
+            assert_eq!(parse(expr)?.to_string(), expect);
+        }
+        assert_eq!(
+            parse("-1 * 2 > 3")?,
+            ast::Expression::Operation(ast::Operation::GreaterThan(
+                Box::new(ast::Expression::Operation(ast::Operation::Multiply(
+                    Box::new(ast::Consts::Integer(-1).into()),
+                    Box::new(ast::Consts::Integer(2).into()),
+                ))),
+                Box::new(ast::Consts::Integer(3).into()),
+            ))
+        );
+
+        // 表达式求值
+        for (expr, expect) in [
+            ("1 + 2 * 3 - 4 / 3 % 2", Value::Integer(6)),
+            ("2 ^ 3 ^ 2", Value::Integer(512)),
+            ("-2 ^ 2", Value::Integer(4)),
+            ("2 ^ -1", Value::Float(0.5)),
+            ("7 % 4 + 1.5", Value::Float(4.5)),
+            ("+3 - -3", Value::Integer(6)),
+            ("1 != 1.0", Value::Boolean(false)),
+            ("'a' < 'b' and 2 >= 2 and not 1 > 2", Value::Boolean(true)),
+            ("null = 1 or true", Value::Boolean(true)),
+            ("null = 1 and true", Value::Null),
+            ("null + 1", Value::Null),
+        ] {
+            assert_eq!(evaluate(expr)?, expect, "{}", expr);
+        }
+        for expr in [
+            "1 / 0",
+            "1 % 0",
+            "9223372036854775807 + 1",
+            "1 + 'a'",
+            "not 1",
+        ] {
+            assert!(evaluate(expr).is_err(), "{}", expr);
+        }
+        assert!(parse("regexp_matches('a')").is_err());
+        #[cfg(feature = "regex")]
+        {
+            for (expr, expect) in [
+                ("'abc' regexp 'b'", Value::Boolean(true)),
+                ("regexp_matches('abc', '^b')", Value::Boolean(false)),
+                ("null regexp 'a'", Value::Null),
+            ] {
+                assert_eq!(evaluate(expr)?, expect, "{}", expr);
+            }
+            assert!(evaluate("1 regexp 'a'").is_err());
+            assert!(evaluate("'a' regexp '('").is_err());
+        }
+        for expr in ["1 +", "(1 + 2", "1 ! 2", "1 == 2"] {
+            assert!(parse(expr).is_err(), "{}", expr);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_batch() -> Result<()> {
+        assert_eq!(
+            Parser::new("checkpoint; kill 3;\n analyze tbl1;").parse_batch()?,
+            vec![
+                ast::Statement::Checkpoint,
+                ast::Statement::Kill { session_id: 3 },
+                ast::Statement::Analyze {
+                    table_name: "tbl1".into(),
+                },
+            ]
+        );
+        assert!(Parser::new("").parse_batch()?.is_empty());
+        assert!(Parser::new("checkpoint; kill 3").parse_batch().is_err());
+        assert!(Parser::new("checkpoint kill 3;").parse_batch().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_analyze() -> Result<()> {
+        assert_eq!(
+            Parser::new("analyze tbl1;").parse()?,
+            ast::Statement::Analyze {
+                table_name: "tbl1".into()
+            }
+        );
+        assert!(Parser::new("analyze;").parse().is_err());
+
+        assert_eq!(
+            Parser::new("check table tbl1;").parse()?,
+            ast::Statement::CheckTable {
+                table_name: "tbl1".into()
+            }
+        );
+        assert!(Parser::new("check tbl1;").parse().is_err());
+
+        assert_eq!(
+            Parser::new("reindex table tbl1;").parse()?,
+            ast::Statement::Reindex {
+                table_name: "tbl1".into(),
+                index: None,
+            }
+        );
+        assert_eq!(
+            Parser::new("reindex index primary on tbl1;").parse()?,
+            ast::Statement::Reindex {
+                table_name: "tbl1".into(),
+                index: Some("primary".to_string()),
+            }
+        );
+        assert!(Parser::new("reindex tbl1;").parse().is_err());
+
+        // 附加的数据库
+        assert_eq!(
+            Parser::new("attach database '/tmp/db' as other;").parse()?,
+            ast::Statement::Attach {
+                path: "/tmp/db".to_string(),
+                alias: "other".to_string(),
+            }
+        );
+        assert_eq!(
+            Parser::new("detach database other;").parse()?,
+            ast::Statement::Detach {
+                alias: "other".to_string(),
+            }
+        );
+        assert_eq!(
+            Parser::new("check table other.tbl1;").parse()?,
+            ast::Statement::CheckTable {
+                table_name: "other.tbl1".into()
+            }
+        );
+        assert!(Parser::new("attach database other;").parse().is_err());
+        assert!(Parser::new("attach '/tmp/db' as other;").parse().is_err());
+        assert!(Parser::new("check table other.;").parse().is_err());
+        assert!(Parser::new("reindex index primary tbl1;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_backup() -> Result<()> {
+        assert_eq!(
+            Parser::new("backup to '/tmp/backup';").parse()?,
+            ast::Statement::Backup {
+                path: "/tmp/backup".to_string(),
+                since: None,
+                options: vec![],
+            }
+        );
+        assert_eq!(
+            Parser::new("backup to 'b' with (rate_limit = 1024);").parse()?,
+            ast::Statement::Backup {
+                path: "b".to_string(),
+                since: None,
+                options: vec![("rate_limit".to_string(), ast::Consts::Integer(1024).into())],
+            }
+        );
+        assert_eq!(
+            Parser::new("backup to 'b' since version 10;").parse()?,
+            ast::Statement::Backup {
+                path: "b".to_string(),
+                since: Some(10),
+                options: vec![],
+            }
+        );
+        assert!(Parser::new("backup to 'b' since 10;").parse().is_err());
+        assert_eq!(
+            Parser::new("checkpoint;").parse()?,
+            ast::Statement::Checkpoint
+        );
+        assert!(Parser::new("backup to b;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_copy() -> Result<()> {
+        assert_eq!(
+            Parser::new("copy tbl1 from '/tmp/data.csv';").parse()?,
+            ast::Statement::Copy {
+                table_name: "tbl1".into(),
+                path: "/tmp/data.csv".to_string(),
+                options: vec![],
+            }
+        );
+        assert_eq!(
+            Parser::new("COPY other.tbl1 FROM 'a.csv' WITH (header = 'true');").parse()?,
+            ast::Statement::Copy {
+                table_name: "other.tbl1".into(),
+                path: "a.csv".to_string(),
+                options: vec![(
+                    "header".to_string(),
+                    ast::Consts::String("true".to_string()).into()
+                )],
+            }
+        );
+        assert_eq!(
+            Parser::new("copy tbl1 from 'a.csv' with (header true, delimiter = ';');").parse()?,
+            ast::Statement::Copy {
+                table_name: "tbl1".into(),
+                path: "a.csv".to_string(),
+                options: vec![
+                    ("header".to_string(), ast::Consts::Boolean(true).into()),
+                    (
+                        "delimiter".to_string(),
+                        ast::Consts::String(";".to_string()).into()
+                    ),
+                ],
+            }
+        );
+        assert!(Parser::new("copy tbl1 'a.csv';").parse().is_err());
+        assert!(Parser::new("copy tbl1 from a;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_update() -> Result<()> {
+        let field = |name: &str| ast::Expression::Field(None, name.to_string());
+        assert_eq!(
+            Parser::new("update tbl1 set a = 1;").parse()?,
+            ast::Statement::Update {
+                table_name: "tbl1".into(),
+                columns: vec![("a".to_string(), ast::Consts::Integer(1).into())],
+                filter: None,
+            }
+        );
+        assert_eq!(
+            Parser::new("UPDATE other.tbl1 SET a = a + 1, b = 'x' WHERE c = true;").parse()?,
+            ast::Statement::Update {
+                table_name: "other.tbl1".into(),
+                columns: vec![
+                    (
+                        "a".to_string(),
+                        ast::Expression::Operation(ast::Operation::Add(
+                            Box::new(field("a")),
+                            Box::new(ast::Consts::Integer(1).into()),
+                        ))
+                    ),
+                    ("b".to_string(), ast::Consts::String("x".to_string()).into()),
+                ],
+                filter: Some(ast::Expression::Operation(ast::Operation::Equal(
+                    Box::new(field("c")),
+                    Box::new(ast::Consts::Boolean(true).into()),
+                ))),
+            }
+        );
+        assert!(Parser::new("update tbl1;").parse().is_err());
+        assert!(Parser::new("update tbl1 set a;").parse().is_err());
+        assert!(Parser::new("update tbl1 set a = 1,;").parse().is_err());
+        assert!(Parser::new("update tbl1 set a = 1 where;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_delete() -> Result<()> {
+        assert_eq!(
+            Parser::new("delete from tbl1;").parse()?,
+            ast::Statement::Delete {
+                table_name: "tbl1".into(),
+                filter: None,
+            }
+        );
+        assert_eq!(
+            Parser::new("DELETE FROM other.tbl1 WHERE a > 1;").parse()?,
+            ast::Statement::Delete {
+                table_name: "other.tbl1".into(),
+                filter: Some(ast::Expression::Operation(ast::Operation::GreaterThan(
+                    Box::new(ast::Expression::Field(None, "a".to_string())),
+                    Box::new(ast::Consts::Integer(1).into()),
+                ))),
+            }
+        );
+        assert!(Parser::new("delete tbl1;").parse().is_err());
+        assert!(Parser::new("delete from tbl1 where;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_alter_table() -> Result<()> {
+        let stmt = Parser::new("alter table tbl1 add column c int not null default 1;").parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::AlterTable {
+                table_name: "tbl1".into(),
+                operation: ast::AlterTableOperation::AddColumn {
+                    column: ast::Column {
+                        name: "c".into(),
+                        datatype: DataType::Integer,
+                        nullable: Some(false),
+                        default: Some(ast::Consts::Integer(1).into()),
+                        default_text: Some("1".to_string()),
+                        unique: false,
+                    },
+                },
+            }
+        );
+        // COLUMN 可以省略
+        assert_eq!(
+            Parser::new("ALTER TABLE tbl1 ADD c INT NOT NULL DEFAULT 1;").parse()?,
+            stmt
+        );
+
+        assert_eq!(
+            Parser::new("alter table other.tbl1 rename to tbl2;").parse()?,
+            ast::Statement::AlterTable {
+                table_name: "other.tbl1".into(),
+                operation: ast::AlterTableOperation::RenameTable {
+                    new_name: "tbl2".into()
+                },
+            }
+        );
+        let stmt = Parser::new("alter table tbl1 rename column a to b;").parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::AlterTable {
+                table_name: "tbl1".into(),
+                operation: ast::AlterTableOperation::RenameColumn {
+                    column: "a".into(),
+                    new_name: "b".into()
+                },
+            }
+        );
+        assert_eq!(
+            Parser::new("ALTER TABLE tbl1 RENAME a TO b;").parse()?,
+            stmt
+        );
+
+        assert!(Parser::new("alter table tbl1 add column c;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("alter table tbl1 rename to;").parse().is_err());
+        assert!(Parser::new("alter table tbl1 rename to other.tbl2;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("alter table tbl1 rename column a b;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("alter table tbl1 drop column c;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("alter tbl1 add column c int;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_create_view() -> Result<()> {
+        // 保存的查询语句是 AS 之后、分号之前的原始文本
+        let sql = "create view v (x, y) as\n  select a, 'é;' from tbl1 where a > 1 order by a  ;";
+        let Ok(ast::Statement::CreateView {
+            name,
+            columns,
+            query,
+            text,
+        }) = Parser::new(sql).parse()
+        else {
+            panic!("expected create view");
+        };
+        assert_eq!(name.as_str(), "v");
+        assert_eq!(columns, vec!["x".to_string(), "y".to_string()]);
+        assert_eq!(text, "select a, 'é;' from tbl1 where a > 1 order by a");
+        assert_eq!(Parser::new(&format!("{};", text)).parse()?, *query);
+
+        let stmts =
+            Parser::new("create view v as select a from t1 union select a from t2; drop view v;")
+                .parse_batch()?;
+        assert!(matches!(
+            &stmts[0],
+            ast::Statement::CreateView { columns, text, .. }
+                if columns.is_empty() && text == "select a from t1 union select a from t2"
+        ));
+        assert_eq!(stmts[1], ast::Statement::DropView { name: "v".into() });
+
+        for sql in [
+            "create view v;",
+            "create view v as;",
+            "create view v () as select 1;",
+            "create view v as values (1);",
+            "create view v select a from t1;",
+            "drop view;",
+            "drop table t1;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_create_index() -> Result<()> {
+        let stmts = Parser::new(
+            "create index idx_b on tbl1 (b); create index idx_cb on tbl1 (c, b); \
+             create spatial index idx_loc on tbl1 (loc) with (grid_size = 10); \
+             drop index idx_b on tbl1;",
+        )
+        .parse_batch()?;
+        assert_eq!(
+            stmts,
+            vec![
+                ast::Statement::CreateIndex {
+                    name: "idx_b".into(),
+                    table_name: "tbl1".into(),
+                    columns: vec!["b".to_string()],
+                    spatial: false,
+                    options: Vec::new(),
+                },
+                ast::Statement::CreateIndex {
+                    name: "idx_cb".into(),
+                    table_name: "tbl1".into(),
+                    columns: vec!["c".to_string(), "b".to_string()],
+                    spatial: false,
+                    options: Vec::new(),
+                },
+                ast::Statement::CreateIndex {
+                    name: "idx_loc".into(),
+                    table_name: "tbl1".into(),
+                    columns: vec!["loc".to_string()],
+                    spatial: true,
+                    options: vec![(
+                        "grid_size".to_string(),
+                        ast::Expression::Consts(ast::Consts::Integer(10))
+                    )],
+                },
+                ast::Statement::DropIndex {
+                    name: "idx_b".into(),
+                    table_name: "tbl1".into(),
+                },
+            ]
+        );
+
+        for sql in [
+            "create index idx_b on tbl1;",
+            "create index idx_b on tbl1 ();",
+            "create index idx_b on tbl1 (b,);",
+            "create index on tbl1 (b);",
+            "create index idx_b tbl1 (b);",
+            "create spatial idx_b on tbl1 (b);",
+            "create spatial index idx_b on tbl1 (b) with grid_size = 1;",
+            "drop index idx_b;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_parameters() -> Result<()> {
+        let mut parser = Parser::new("select * from tbl1 where a = ? and b > ?;");
+        let stmt = parser.parse()?;
+        assert_eq!(parser.parameters(), 2);
+        let ast::Statement::Select {
+            filter: Some(filter),
+            ..
+        } = stmt
+        else {
+            panic!("expected select");
+        };
+        assert_eq!(filter.to_string(), "a = $1 AND b > $2");
+
+        // $n 可以重复使用，参数的数量是最大的编号
+        let mut parser = Parser::new("update tbl1 set a = $2 where b = $1 or c = $2;");
+        parser.parse()?;
+        assert_eq!(parser.parameters(), 2);
+
+        let mut parser = Parser::new("select 1;");
+        parser.parse()?;
+        assert_eq!(parser.parameters(), 0);
+
+        for sql in [
+            "select ? + $1;",
+            "select $1 + ?;",
+            "select $0;",
+            "select $;",
+            "select $a;",
+            "create view v as select * from tbl1 where a = ?;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{}", sql);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_explain() -> Result<()> {
+        let explain = |format, verbose| ast::Statement::Explain {
+            statement: Box::new(ast::Statement::Analyze {
+                table_name: "tbl1".into(),
+            }),
+            format,
+            verbose,
+        };
+        assert_eq!(
+            Parser::new("explain analyze tbl1;").parse()?,
+            explain(ast::ExplainFormat::Text, false)
+        );
+        assert_eq!(
+            Parser::new("EXPLAIN VERBOSE analyze tbl1;").parse()?,
+            explain(ast::ExplainFormat::Text, true)
+        );
+        assert_eq!(
+            Parser::new("explain (format json) analyze tbl1;").parse()?,
+            explain(ast::ExplainFormat::Json, false)
+        );
+        assert_eq!(
+            Parser::new("explain (verbose, format json) analyze tbl1;").parse()?,
+            explain(ast::ExplainFormat::Json, true)
+        );
+        assert_eq!(
+            Parser::new("explain (format json, verbose false, format text) analyze tbl1;")
+                .parse()?,
+            explain(ast::ExplainFormat::Text, false)
+        );
+        assert!(Parser::new("explain;").parse().is_err());
+        assert!(Parser::new("explain () analyze tbl1;").parse().is_err());
+        assert!(Parser::new("explain (format xml) analyze tbl1;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("explain (verbose 1) analyze tbl1;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("explain (format json analyze tbl1;")
+            .parse()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_values() -> Result<()> {
+        let sql = "values (1, 'a'), (2, 'b');";
+        let stmt = Parser::new(sql).parse()?;
+        assert_eq!(
+            stmt,
+            ast::Statement::Values {
+                rows: vec![
+                    vec![
+                        ast::Consts::Integer(1).into(),
+                        ast::Consts::String("a".to_string()).into(),
+                    ],
+                    vec![
+                        ast::Consts::Integer(2).into(),
+                        ast::Consts::String("b".to_string()).into(),
+                    ],
+                ],
+            }
+        );
+
+        assert!(Parser::new("values;").parse().is_err());
+        assert!(Parser::new("values (1, 2),;").parse().is_err());
+
+        // FROM 和 JOIN 中的 VALUES 需要有别名
+        for sql in [
+            "select * from (values (1, 'a')) v;",
+            "select * from (values (1, 'a')) as v join t on v.column1 = t.a;",
+        ] {
+            Parser::new(sql).parse()?;
+        }
+        assert!(Parser::new("select * from (values (1, 'a'));")
+            .parse()
+            .is_err());
         Ok(())
     }
 }
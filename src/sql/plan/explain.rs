@@ -0,0 +1,805 @@
+use std::{fmt::Write, ops::Bound};
+
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        ident::Ident,
+        parser::ast::{self, Expression, Operation},
+        schema::StorageLayout,
+        types::Value,
+    },
+};
+
+use super::Node;
+
+// 没有统计信息的表、外部表和表函数估算的行数
+const DEFAULT_ROWS: f64 = 1000.0;
+// 等值条件和其他条件估算的选择率
+const EQUAL_SELECTIVITY: f64 = 0.1;
+const DEFAULT_SELECTIVITY: f64 = 1.0 / 3.0;
+// 分组之后估算的行数占输入行数的比例
+const GROUP_RATIO: f64 = 0.1;
+
+// EXPLAIN 输出的计划树节点，文本和 JSON 格式使用相同的结构
+pub struct ExplainNode {
+    name: &'static str,
+    // 节点的属性，按照添加的顺序输出
+    properties: Vec<(&'static str, Property)>,
+    // 估算的输出行数和代价，代价是读取和计算的值的数量，只在 VERBOSE 时输出
+    estimate: Option<(f64, f64)>,
+    verbose: bool,
+    children: Vec<ExplainNode>,
+    // 节点输出的列名，父节点的表达式中引用的列使用列名显示
+    columns: Vec<String>,
+}
+
+enum Property {
+    String(String),
+    Integer(u64),
+    List(Vec<String>),
+    Null,
+}
+
+impl ExplainNode {
+    // 生成执行计划的说明，verbose 时包括存储方式、使用的索引等信息
+    pub fn build<T: Transaction>(node: &Node, txn: &T, verbose: bool) -> Result<Self> {
+        let mut explain = Self {
+            name: "",
+            properties: Vec::new(),
+            estimate: None,
+            verbose,
+            children: Vec::new(),
+            columns: Vec::new(),
+        };
+        let table = |name: &Ident| ("table", Property::String(name.to_string()));
+        explain.name = match node {
+            Node::Scan {
+                table_name,
+                columns,
+            } => {
+                let schema = txn.must_get_table(table_name.clone())?;
+                explain.columns = schema.columns.iter().map(|c| c.name.to_string()).collect();
+                let read = match columns {
+                    Some(columns) => columns.clone(),
+                    None => (0..schema.columns.len()).collect(),
+                };
+                explain.properties.push(table(table_name));
+                explain.properties.push((
+                    "columns",
+                    Property::List(
+                        read.iter()
+                            .filter_map(|&i| explain.columns.get(i).cloned())
+                            .collect(),
+                    ),
+                ));
+                if verbose {
+                    let storage = match (&schema.external, schema.storage) {
+                        (Some(_), _) => "external",
+                        (None, StorageLayout::Row) => "row",
+                        (None, StorageLayout::Columnar) => "columnar",
+                    };
+                    explain
+                        .properties
+                        .push(("storage", Property::String(storage.to_string())));
+                    // 扫描总是读取整个表，使用二级索引时是 IndexScan 节点
+                    explain.properties.push(("index", Property::Null));
+                }
+                let rows = txn
+                    .estimated_rows(table_name.clone())?
+                    .map_or(DEFAULT_ROWS, |rows| rows as f64);
+                explain.estimate = Some((rows, rows * read.len() as f64));
+                "Scan"
+            }
+            Node::IndexScan {
+                table_name,
+                index,
+                prefix,
+                range,
+            } => {
+                let schema = txn.must_get_table(table_name.clone())?;
+                explain.columns = schema.columns.iter().map(|c| c.name.to_string()).collect();
+                let columns = schema
+                    .indexes
+                    .iter()
+                    .find(|i| i.name == *index)
+                    .map(|i| i.columns.clone())
+                    .unwrap_or_default();
+                let name = |i: usize| {
+                    columns
+                        .get(i)
+                        .and_then(|&c| explain.columns.get(c))
+                        .cloned()
+                        .unwrap_or_default()
+                };
+                // 前几列的等值条件和之后的一列的范围条件
+                let mut key = prefix
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| format!("{} = {}", name(i), display_value(v)))
+                    .collect::<Vec<_>>();
+                let column = name(prefix.len());
+                match &range.0 {
+                    Bound::Included(v) => key.push(format!("{} >= {}", column, display_value(v))),
+                    Bound::Excluded(v) => key.push(format!("{} > {}", column, display_value(v))),
+                    Bound::Unbounded => {}
+                }
+                match &range.1 {
+                    Bound::Included(v) => key.push(format!("{} <= {}", column, display_value(v))),
+                    Bound::Excluded(v) => key.push(format!("{} < {}", column, display_value(v))),
+                    Bound::Unbounded => {}
+                }
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("index", Property::String(index.to_string())));
+                explain.properties.push(("key", Property::List(key)));
+                explain
+                    .properties
+                    .push(("columns", Property::List(explain.columns.clone())));
+                // 只读取索引中匹配的行，每个等值条件和范围条件按照各自的选择率估算
+                let mut rows = txn
+                    .estimated_rows(table_name.clone())?
+                    .map_or(DEFAULT_ROWS, |rows| rows as f64)
+                    * EQUAL_SELECTIVITY.powi(prefix.len() as i32);
+                if *range != (Bound::Unbounded, Bound::Unbounded) {
+                    rows *= DEFAULT_SELECTIVITY;
+                }
+                explain.estimate = Some((rows, rows * explain.columns.len() as f64));
+                "IndexScan"
+            }
+            Node::SpatialScan {
+                table_name,
+                index,
+                area: [xmin, ymin, xmax, ymax],
+            } => {
+                let schema = txn.must_get_table(table_name.clone())?;
+                explain.columns = schema.columns.iter().map(|c| c.name.to_string()).collect();
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("index", Property::String(index.to_string())));
+                explain.properties.push((
+                    "area",
+                    Property::String(format!("({} {}, {} {})", xmin, ymin, xmax, ymax)),
+                ));
+                explain
+                    .properties
+                    .push(("columns", Property::List(explain.columns.clone())));
+                // 只读取范围覆盖的网格中的点，按照范围条件的选择率估算
+                let rows = txn
+                    .estimated_rows(table_name.clone())?
+                    .map_or(DEFAULT_ROWS, |rows| rows as f64)
+                    * DEFAULT_SELECTIVITY;
+                explain.estimate = Some((rows, rows * explain.columns.len() as f64));
+                "SpatialScan"
+            }
+            Node::TableFunction {
+                name,
+                args,
+                columns,
+            } => {
+                explain.columns = columns.clone();
+                explain
+                    .properties
+                    .push(("function", Property::String(name.clone())));
+                explain.properties.push((
+                    "args",
+                    Property::List(args.iter().map(display_value).collect()),
+                ));
+                explain.estimate = Some((DEFAULT_ROWS, DEFAULT_ROWS));
+                "TableFunction"
+            }
+            Node::Values { rows, columns } => {
+                explain.columns = columns.clone();
+                explain
+                    .properties
+                    .push(("values", Property::Integer(rows.len() as u64)));
+                let rows = rows.len() as f64;
+                explain.estimate = Some((rows, rows * columns.len() as f64));
+                "Values"
+            }
+            Node::NestedLoopJoin {
+                left,
+                right,
+                predicate,
+                kind,
+            } => {
+                let (left, right) = (
+                    Self::build(left, txn, verbose)?,
+                    Self::build(right, txn, verbose)?,
+                );
+                explain.join(left, right, predicate.as_ref(), *kind, |rows, _| rows);
+                "NestedLoopJoin"
+            }
+            Node::MergeJoin {
+                left,
+                right,
+                left_key,
+                right_key,
+                predicate,
+                kind,
+            } => {
+                let (left, right) = (
+                    Self::build(left, txn, verbose)?,
+                    Self::build(right, txn, verbose)?,
+                );
+                let key = format!("{} = {}", left.display(left_key), right.display(right_key));
+                // 只组合 key 相等的行，每一边只需要遍历一次
+                explain.join(left, right, Some(predicate), *kind, |_, (l, r)| l + r);
+                explain.properties.insert(1, ("key", Property::String(key)));
+                "MergeJoin"
+            }
+            Node::SemiJoin {
+                left,
+                right,
+                keys,
+                predicate,
+                anti,
+                null_aware,
+            } => {
+                let (left, right) = (
+                    Self::build(left, txn, verbose)?,
+                    Self::build(right, txn, verbose)?,
+                );
+                let name = match (anti, null_aware) {
+                    (false, _) => "semi",
+                    (true, false) => "anti",
+                    (true, true) => "null-aware anti",
+                };
+                explain
+                    .properties
+                    .push(("type", Property::String(name.to_string())));
+                if let Some((l, r)) = keys {
+                    let key = format!("{} = {}", left.display(l), right.display(r));
+                    explain.properties.push(("key", Property::String(key)));
+                }
+                // 条件引用两边的列，输出的只有左边的列
+                explain.columns = left.columns.iter().chain(&right.columns).cloned().collect();
+                if let Some(predicate) = predicate {
+                    explain
+                        .properties
+                        .push(("predicate", Property::String(explain.display(predicate))));
+                }
+                explain.columns = left.columns.clone();
+                // 有 key 时右边排序之后每一行二分查找，否则每一行和右边所有的行比较
+                explain.estimate = left.estimate.zip(right.estimate).map(
+                    |((left_rows, left_cost), (right_rows, right_cost))| {
+                        let compare = match keys {
+                            Some(_) => (left_rows + right_rows) * right_rows.max(2.0).log2(),
+                            None => left_rows * right_rows,
+                        };
+                        let output =
+                            (left_rows * DEFAULT_SELECTIVITY).clamp(left_rows.min(1.0), left_rows);
+                        (output, left_cost + right_cost + compare)
+                    },
+                );
+                explain.children.push(left);
+                explain.children.push(right);
+                "SemiJoin"
+            }
+            Node::Filter { source, predicate } => {
+                let source = Self::build(source, txn, verbose)?;
+                explain
+                    .properties
+                    .push(("predicate", Property::String(source.display(predicate))));
+                explain.columns = source.columns.clone();
+                // 和分组一样，输入不为空时至少估算一行
+                explain.estimate = source.estimate.map(|(rows, cost)| {
+                    let output = (rows * selectivity(predicate)).clamp(rows.min(1.0), rows);
+                    (output, cost + rows)
+                });
+                explain.children.push(source);
+                "Filter"
+            }
+            Node::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => {
+                let source = Self::build(source, txn, verbose)?;
+                let group_by = group_by
+                    .iter()
+                    .map(|e| source.display(e))
+                    .collect::<Vec<_>>();
+                let aggregates = aggregates
+                    .iter()
+                    .map(|agg| source.display(&Expression::Aggregate(agg.clone())))
+                    .collect::<Vec<_>>();
+                explain.columns = group_by.iter().chain(&aggregates).cloned().collect();
+                explain.estimate = source.estimate.map(|(rows, cost)| {
+                    let output = if group_by.is_empty() {
+                        1.0
+                    } else {
+                        (rows * GROUP_RATIO).max(1.0)
+                    };
+                    (output, cost + rows)
+                });
+                explain
+                    .properties
+                    .push(("group_by", Property::List(group_by)));
+                explain
+                    .properties
+                    .push(("aggregates", Property::List(aggregates)));
+                explain.children.push(source);
+                "Aggregate"
+            }
+            Node::Order { source, order_by } => {
+                let source = Self::build(source, txn, verbose)?;
+                explain.properties.push((
+                    "order_by",
+                    Property::List(
+                        order_by
+                            .iter()
+                            .map(|(e, direction)| match direction {
+                                ast::Direction::Asc => source.display(e),
+                                ast::Direction::Desc => format!("{} DESC", source.display(e)),
+                            })
+                            .collect(),
+                    ),
+                ));
+                explain.columns = source.columns.clone();
+                // 排序的代价按照 n log n 估算
+                explain.estimate = source
+                    .estimate
+                    .map(|(rows, cost)| (rows, cost + rows * rows.max(2.0).log2()));
+                explain.children.push(source);
+                "Order"
+            }
+            Node::Distinct { source } => {
+                let source = Self::build(source, txn, verbose)?;
+                explain.columns = source.columns.clone();
+                // 去重之后的行数未知，按照最多的行数估算
+                explain.estimate = source.estimate.map(|(rows, cost)| (rows, cost + rows));
+                explain.children.push(source);
+                "Distinct"
+            }
+            Node::SetOperation {
+                left,
+                right,
+                op,
+                all,
+            } => {
+                let (left, right) = (
+                    Self::build(left, txn, verbose)?,
+                    Self::build(right, txn, verbose)?,
+                );
+                explain.properties.push((
+                    "op",
+                    Property::String(match all {
+                        true => format!("{} ALL", op),
+                        false => op.to_string(),
+                    }),
+                ));
+                explain.columns = left.columns.clone();
+                // 两边各遍历一次，UNION 最多输出两边的行数，INTERSECT 和 EXCEPT 最多输出左边的行数
+                explain.estimate = left.estimate.zip(right.estimate).map(
+                    |((left_rows, left_cost), (right_rows, right_cost))| {
+                        let output = match op {
+                            ast::SetOperator::Union => left_rows + right_rows,
+                            _ => left_rows,
+                        };
+                        (output, left_cost + right_cost + left_rows + right_rows)
+                    },
+                );
+                explain.children.push(left);
+                explain.children.push(right);
+                "SetOperation"
+            }
+            Node::Limit { source, limit } => {
+                let source = Self::build(source, txn, verbose)?;
+                explain
+                    .properties
+                    .push(("limit", Property::Integer(*limit as u64)));
+                explain.columns = source.columns.clone();
+                explain.estimate = source
+                    .estimate
+                    .map(|(rows, cost)| (rows.min(*limit as f64), cost));
+                explain.children.push(source);
+                "Limit"
+            }
+            Node::Projection {
+                source,
+                exprs,
+                columns,
+            } => {
+                let source = Self::build(source, txn, verbose)?;
+                explain.properties.push((
+                    "exprs",
+                    Property::List(exprs.iter().map(|e| source.display(e)).collect()),
+                ));
+                explain.columns = columns.clone();
+                explain.estimate = source
+                    .estimate
+                    .map(|(rows, cost)| (rows, cost + rows * exprs.len() as f64));
+                explain.children.push(source);
+                "Projection"
+            }
+            Node::Insert {
+                table_name,
+                columns,
+                values,
+                source,
+            } => {
+                explain.properties.push(table(table_name));
+                if !columns.is_empty() {
+                    explain
+                        .properties
+                        .push(("columns", Property::List(columns.clone())));
+                }
+                match source {
+                    Some(source) => {
+                        let source = Self::build(source, txn, verbose)?;
+                        explain.estimate = source.estimate.map(|(rows, cost)| (rows, cost + rows));
+                        explain.children.push(source);
+                    }
+                    None => {
+                        explain
+                            .properties
+                            .push(("values", Property::Integer(values.len() as u64)));
+                        let rows = values.len() as f64;
+                        explain.estimate = Some((rows, rows));
+                    }
+                }
+                "Insert"
+            }
+            Node::Update {
+                table_name,
+                source,
+                columns,
+            } => {
+                let source = Self::build(source, txn, verbose)?;
+                explain.properties.push(table(table_name));
+                explain.properties.push((
+                    "set",
+                    Property::List(
+                        columns
+                            .iter()
+                            .map(|(i, expr)| {
+                                let name = source.columns.get(*i).cloned().unwrap_or_default();
+                                format!("{} = {}", name, source.display(expr))
+                            })
+                            .collect(),
+                    ),
+                ));
+                explain.estimate = source.estimate.map(|(rows, cost)| (rows, cost + rows));
+                explain.children.push(source);
+                "Update"
+            }
+            Node::Delete { table_name, source } => {
+                let source = Self::build(source, txn, verbose)?;
+                explain.properties.push(table(table_name));
+                explain.estimate = source.estimate.map(|(rows, cost)| (rows, cost + rows));
+                explain.children.push(source);
+                "Delete"
+            }
+            Node::Copy { table_name, source } => {
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("path", Property::String(source.location.clone())));
+                "Copy"
+            }
+            Node::CreateTable { schema } => {
+                explain.properties.push(table(&schema.name));
+                "CreateTable"
+            }
+            Node::CreateView { view } => {
+                explain
+                    .properties
+                    .push(("view", Property::String(view.name.to_string())));
+                "CreateView"
+            }
+            Node::DropView { name } => {
+                explain
+                    .properties
+                    .push(("view", Property::String(name.to_string())));
+                "DropView"
+            }
+            Node::CreateIndex { table_name, index } => {
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("index", Property::String(index.name.to_string())));
+                "CreateIndex"
+            }
+            Node::DropIndex { table_name, name } => {
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("index", Property::String(name.to_string())));
+                "DropIndex"
+            }
+            Node::AddColumn { table_name, column } => {
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("column", Property::String(column.name.to_string())));
+                "AddColumn"
+            }
+            Node::RenameTable {
+                table_name,
+                new_name,
+            } => {
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("new_name", Property::String(new_name.to_string())));
+                "RenameTable"
+            }
+            Node::RenameColumn {
+                table_name,
+                column,
+                new_name,
+            } => {
+                explain.properties.push(table(table_name));
+                explain
+                    .properties
+                    .push(("column", Property::String(column.to_string())));
+                explain
+                    .properties
+                    .push(("new_name", Property::String(new_name.to_string())));
+                "RenameColumn"
+            }
+            Node::ShowIndexes { table_name } => {
+                explain.properties.push(table(table_name));
+                "ShowIndexes"
+            }
+            Node::ShowCreateTable { table_name } => {
+                explain.properties.push(table(table_name));
+                "ShowCreateTable"
+            }
+            Node::Describe { table_name } => {
+                explain.properties.push(table(table_name));
+                "Describe"
+            }
+            Node::Analyze { table_name } => {
+                explain.properties.push(table(table_name));
+                "Analyze"
+            }
+            Node::CheckTable { table_name } => {
+                explain.properties.push(table(table_name));
+                "CheckTable"
+            }
+            Node::Reindex { table_name } => {
+                explain.properties.push(table(table_name));
+                "Reindex"
+            }
+            Node::RollbackTransaction { version } => {
+                explain
+                    .properties
+                    .push(("version", Property::Integer(*version)));
+                "RollbackTransaction"
+            }
+            Node::Checkpoint => "Checkpoint",
+            Node::Backup { path, .. } => {
+                explain
+                    .properties
+                    .push(("path", Property::String(path.clone())));
+                "Backup"
+            }
+            Node::Explain { .. } => {
+                return Err(Error::Internal("EXPLAIN cannot be nested".to_string()))
+            }
+        };
+        Ok(explain)
+    }
+
+    // 连接节点的属性和估算，cost 根据组合的数量和两边的行数计算比较的次数
+    fn join(
+        &mut self,
+        left: ExplainNode,
+        right: ExplainNode,
+        predicate: Option<&Expression>,
+        kind: ast::JoinType,
+        cost: fn(f64, (f64, f64)) -> f64,
+    ) {
+        self.columns = left.columns.iter().chain(&right.columns).cloned().collect();
+        let name = match kind {
+            ast::JoinType::Inner => "inner",
+            ast::JoinType::Left => "left",
+            ast::JoinType::Right => "right",
+            ast::JoinType::Full => "full",
+        };
+        self.properties
+            .push(("type", Property::String(name.to_string())));
+        if let Some(predicate) = predicate {
+            self.properties
+                .push(("predicate", Property::String(self.display(predicate))));
+        }
+        self.estimate = left.estimate.zip(right.estimate).map(
+            |((left_rows, left_cost), (right_rows, right_cost))| {
+                let rows = left_rows * right_rows;
+                let output =
+                    predicate.map_or(rows, |p| (rows * selectivity(p)).clamp(rows.min(1.0), rows));
+                // 外连接至少输出保留的一边的所有行
+                let output = match kind {
+                    ast::JoinType::Inner => output,
+                    ast::JoinType::Left => output.max(left_rows),
+                    ast::JoinType::Right => output.max(right_rows),
+                    ast::JoinType::Full => output.max(left_rows + right_rows),
+                };
+                (
+                    output,
+                    left_cost + right_cost + cost(rows, (left_rows, right_rows)),
+                )
+            },
+        );
+        self.children.push(left);
+        self.children.push(right);
+    }
+
+    // 将表达式中的列下标替换成这个节点输出的列名
+    fn display(&self, expr: &Expression) -> String {
+        fn rename(expr: Expression, columns: &[String]) -> Result<Expression> {
+            Ok(match expr {
+                Expression::Column(i) if i < columns.len() => {
+                    Expression::Field(None, columns[i].clone())
+                }
+                Expression::Operation(op) => {
+                    Expression::Operation(op.try_map(|e| rename(e, columns))?)
+                }
+                Expression::Function(name, args) => Expression::Function(
+                    name,
+                    args.into_iter()
+                        .map(|e| rename(e, columns))
+                        .collect::<Result<_>>()?,
+                ),
+                Expression::Aggregate(agg) => Expression::Aggregate(ast::Aggregate {
+                    args: agg
+                        .args
+                        .into_iter()
+                        .map(|e| rename(e, columns))
+                        .collect::<Result<_>>()?,
+                    order_by: agg
+                        .order_by
+                        .into_iter()
+                        .map(|(e, direction)| Ok((rename(e, columns)?, direction)))
+                        .collect::<Result<_>>()?,
+                    ..agg
+                }),
+                expr => expr,
+            })
+        }
+        // 改写列名不会出错
+        rename(expr.clone(), &self.columns).map_or_else(|_| expr.to_string(), |e| e.to_string())
+    }
+
+    // 文本格式，每个节点一行，子节点缩进显示
+    pub fn to_text(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.write_text(0, &mut lines);
+        lines
+    }
+
+    fn write_text(&self, depth: usize, lines: &mut Vec<String>) {
+        let mut line = match depth {
+            0 => self.name.to_string(),
+            depth => format!("{}-> {}", "  ".repeat(depth), self.name),
+        };
+        if !self.properties.is_empty() {
+            let properties = self
+                .properties
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        Property::String(s) => s.clone(),
+                        Property::Integer(i) => i.to_string(),
+                        Property::List(items) => format!("[{}]", items.join(", ")),
+                        Property::Null => "none".to_string(),
+                    };
+                    format!("{}: {}", name, value)
+                })
+                .collect::<Vec<_>>();
+            let _ = write!(line, " ({})", properties.join(", "));
+        }
+        if let Some((rows, cost)) = self.estimate.filter(|_| self.verbose) {
+            let _ = write!(line, " rows={} cost={:.2}", rows.round(), cost);
+        }
+        lines.push(line);
+        for child in &self.children {
+            child.write_text(depth + 1, lines);
+        }
+    }
+
+    // JSON 格式，每个节点是一个对象，子节点在 children 中
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(&mut json);
+        json
+    }
+
+    fn write_json(&self, json: &mut String) {
+        json.push_str("{\"node\":");
+        write_json_string(json, self.name);
+        for (name, value) in &self.properties {
+            json.push(',');
+            write_json_string(json, name);
+            json.push(':');
+            match value {
+                Property::String(s) => write_json_string(json, s),
+                Property::Integer(i) => {
+                    let _ = write!(json, "{}", i);
+                }
+                Property::List(items) => {
+                    json.push('[');
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            json.push(',');
+                        }
+                        write_json_string(json, item);
+                    }
+                    json.push(']');
+                }
+                Property::Null => json.push_str("null"),
+            }
+        }
+        if let Some((rows, cost)) = self.estimate.filter(|_| self.verbose) {
+            let _ = write!(
+                json,
+                ",\"rows\":{},\"cost\":{}",
+                rows.round(),
+                (cost * 100.0).round() / 100.0
+            );
+        }
+        if !self.children.is_empty() {
+            json.push_str(",\"children\":[");
+            for (i, child) in self.children.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                child.write_json(json);
+            }
+            json.push(']');
+        }
+        json.push('}');
+    }
+}
+
+// 估算满足过滤条件的行所占的比例
+fn selectivity(predicate: &Expression) -> f64 {
+    match predicate {
+        Expression::Operation(Operation::And(l, r)) => selectivity(l) * selectivity(r),
+        Expression::Operation(Operation::Or(l, r)) => {
+            let (l, r) = (selectivity(l), selectivity(r));
+            l + r - l * r
+        }
+        Expression::Operation(Operation::Not(e)) => 1.0 - selectivity(e),
+        Expression::Operation(Operation::Equal(..)) => EQUAL_SELECTIVITY,
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+// 表函数的参数，和 SQL 中常量的写法一致
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Boolean(true) => "TRUE".to_string(),
+        Value::Boolean(false) => "FALSE".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Decimal(d) => format!("CAST('{}' AS DECIMAL({}, {}))", d, d.precision(), d.scale()),
+        Value::String(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Point(x, y) => format!("POINT({} {})", x, y),
+        Value::Uuid(u) => format!("CAST('{}' AS UUID)", u),
+        Value::Json(j) => format!("CAST('{}' AS JSON)", j.to_string().replace('\'', "''")),
+    }
+}
+
+fn write_json_string(json: &mut String, s: &str) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
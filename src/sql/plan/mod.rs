@@ -1,14 +1,19 @@
+use std::ops::Bound;
+
 use planner::Planner;
 
-use crate::error::Result;
+use crate::{error::Result, storage::mvcc::BackupOptions};
 
 use super::{
     engine::Transaction,
     executor::{Executor, ResultSet},
+    ident::Ident,
     parser::ast::{self, Expression},
-    schema::Table,
+    schema::{Column, ExternalTable, Index, Table, View},
+    types::Value,
 };
 
+pub mod explain;
 mod planner;
 
 // 执行节点
@@ -19,16 +24,238 @@ pub enum Node {
         schema: Table,
     },
 
-    // 插入数据
+    // 在表的最后添加一列
+    AddColumn {
+        table_name: Ident,
+        column: Column,
+    },
+
+    // 修改表名
+    RenameTable {
+        table_name: Ident,
+        new_name: Ident,
+    },
+
+    // 修改列名
+    RenameColumn {
+        table_name: Ident,
+        column: Ident,
+        new_name: Ident,
+    },
+
+    // 创建视图
+    CreateView {
+        view: View,
+    },
+
+    // 删除视图
+    DropView {
+        name: Ident,
+    },
+
+    // 创建二级索引
+    CreateIndex {
+        table_name: Ident,
+        index: Index,
+    },
+
+    // 删除二级索引
+    DropIndex {
+        table_name: Ident,
+        name: Ident,
+    },
+
+    // 插入数据，source 是 INSERT ... SELECT 中的查询，这时 values 为空
     Insert {
-        table_name: String,
+        table_name: Ident,
         columns: Vec<String>,
         values: Vec<Vec<Expression>>,
+        source: Option<Box<Node>>,
+    },
+
+    // 修改 source 返回的行，columns 是列的下标和新的值
+    Update {
+        table_name: Ident,
+        source: Box<Node>,
+        columns: Vec<(usize, Expression)>,
+    },
+
+    // 删除 source 返回的行
+    Delete {
+        table_name: Ident,
+        source: Box<Node>,
+    },
+
+    // 常量行
+    Values {
+        rows: Vec<Vec<Expression>>,
+        columns: Vec<String>,
     },
 
     // 扫描节点
     Scan {
-        table_name: String,
+        table_name: Ident,
+        // 需要读取的列，None 表示读取所有列
+        columns: Option<Vec<usize>>,
+    },
+
+    // 通过二级索引读取行，索引的前几列等于 prefix 中的值，之后的一列在 range 范围中
+    // 输出的列和 Scan 读取所有列时相同
+    IndexScan {
+        table_name: Ident,
+        index: Ident,
+        prefix: Vec<Value>,
+        range: (Bound<Value>, Bound<Value>),
+    },
+
+    // 通过空间索引读取点可能在 area [xmin, ymin, xmax, ymax] 范围中的行，还需要按照条件过滤
+    // 输出的列和 Scan 读取所有列时相同
+    SpatialScan {
+        table_name: Ident,
+        index: Ident,
+        area: [f64; 4],
+    },
+
+    // 表函数，参数已经计算成值
+    TableFunction {
+        name: String,
+        args: Vec<Value>,
+        columns: Vec<String>,
+    },
+
+    // 嵌套循环连接，输出的每一行是左边的行和右边的行拼接起来，只保留条件为 true 的组合
+    // 外连接时没有匹配的行和 NULL 组合
+    NestedLoopJoin {
+        left: Box<Node>,
+        right: Box<Node>,
+        predicate: Option<Expression>,
+        kind: ast::JoinType,
+    },
+
+    // 归并连接，两边的行已经按照连接的 key 升序排列，key 相等的行再判断连接条件
+    // left_key 引用左边的列，right_key 引用右边的列
+    MergeJoin {
+        left: Box<Node>,
+        right: Box<Node>,
+        left_key: Expression,
+        right_key: Expression,
+        predicate: Expression,
+        kind: ast::JoinType,
+    },
+
+    // 半连接，只输出左边的行，保留在右边有满足条件的行的行，anti 时保留没有的行
+    // predicate 引用左边的行和右边的行拼接起来的列，右边的行还需要 keys 两边的值相等
+    // null_aware 是 NOT IN 的反连接，key 是 NULL 时结果未知，也当作匹配
+    SemiJoin {
+        left: Box<Node>,
+        right: Box<Node>,
+        keys: Option<(Expression, Expression)>,
+        predicate: Option<Expression>,
+        anti: bool,
+        null_aware: bool,
+    },
+
+    // 过滤节点，只保留满足条件的行
+    Filter {
+        source: Box<Node>,
+        predicate: Expression,
+    },
+
+    // 分组聚合，输出分组的值和聚合函数的结果
+    Aggregate {
+        source: Box<Node>,
+        group_by: Vec<Expression>,
+        aggregates: Vec<ast::Aggregate>,
+    },
+
+    // 按照表达式排序，排序值相同的行保持原来的顺序
+    Order {
+        source: Box<Node>,
+        order_by: Vec<(Expression, ast::Direction)>,
+    },
+
+    // 去掉重复的行，保留每一行第一次出现的位置
+    Distinct {
+        source: Box<Node>,
+    },
+
+    // 集合运算，两边的列数相同，输出的列名是左边的列名
+    // all 时按照出现的次数计算，否则结果中没有重复的行
+    SetOperation {
+        left: Box<Node>,
+        right: Box<Node>,
+        op: ast::SetOperator,
+        all: bool,
+    },
+
+    // 限制返回的行数
+    Limit {
+        source: Box<Node>,
+        limit: usize,
+    },
+
+    // 强制中止事务
+    RollbackTransaction {
+        version: u64,
+    },
+
+    // 检查点
+    Checkpoint,
+
+    // 查看表的索引
+    ShowIndexes {
+        table_name: Ident,
+    },
+
+    // 查看表的建表语句
+    ShowCreateTable {
+        table_name: Ident,
+    },
+
+    // 查看表的列
+    Describe {
+        table_name: Ident,
+    },
+
+    // 收集表的统计信息
+    Analyze {
+        table_name: Ident,
+    },
+
+    // 检查表的数据是否完整
+    CheckTable {
+        table_name: Ident,
+    },
+
+    // 重建表的索引
+    Reindex {
+        table_name: Ident,
+    },
+
+    // 从 CSV 文件批量导入数据
+    Copy {
+        table_name: Ident,
+        source: ExternalTable,
+    },
+
+    // 备份数据库
+    Backup {
+        path: String,
+        options: BackupOptions,
+    },
+
+    // 投影节点，计算 select 列表中的表达式
+    Projection {
+        source: Box<Node>,
+        exprs: Vec<Expression>,
+        columns: Vec<String>,
+    },
+
+    // 输出执行计划，不执行 source
+    Explain {
+        source: Box<Node>,
+        format: ast::ExplainFormat,
+        verbose: bool,
     },
 }
 
@@ -37,11 +264,11 @@ pub enum Node {
 pub struct Plan(pub Node);
 
 impl Plan {
-    pub fn build(stmt: ast::Statement) -> Self {
-        Planner::new().build(stmt)
+    pub fn build<T: Transaction>(stmt: ast::Statement, txn: &T) -> Result<Self> {
+        Planner::new(txn).build(stmt)
     }
 
-    pub fn execute<T: Transaction>(self, txn: &mut T) -> Result<ResultSet> {
+    pub fn execute<T: Transaction + 'static>(self, txn: &mut T) -> Result<ResultSet> {
         <dyn Executor<T>>::build(self.0).execute(txn)
     }
 }
@@ -51,16 +278,19 @@ mod tests {
     use crate::{
         error::Result,
         sql::{
+            engine::{kv::KVEngine, Engine},
             parser::{
                 ast::{self, Expression},
                 Parser,
             },
             plan::{Node, Plan},
         },
+        storage::memory::MemoryEngine,
     };
 
     #[test]
     fn test_plan_create_table() -> Result<()> {
+        let txn = KVEngine::new(MemoryEngine::new()).begin()?;
         let sql1 = "
         create table tbl1 (
             a int default 100,
@@ -70,7 +300,7 @@ mod tests {
         );
         ";
         let stmt1 = Parser::new(sql1).parse()?;
-        let p1 = Plan::build(stmt1);
+        let p1 = Plan::build(stmt1, &txn)?;
 
         let sql2 = "
         create            table tbl1 (
@@ -81,7 +311,7 @@ mod tests {
         );
         ";
         let stmt2 = Parser::new(sql2).parse()?;
-        let p2 = Plan::build(stmt2);
+        let p2 = Plan::build(stmt2, &txn)?;
         assert_eq!(p1, p2);
 
         Ok(())
@@ -89,13 +319,14 @@ mod tests {
 
     #[test]
     fn test_plan_insert() -> Result<()> {
+        let txn = KVEngine::new(MemoryEngine::new()).begin()?;
         let sql1 = "insert into tbl1 values (1, 2, 3, 'a', true);";
         let stmt1 = Parser::new(sql1).parse()?;
-        let p1 = Plan::build(stmt1);
+        let p1 = Plan::build(stmt1, &txn)?;
         assert_eq!(
             p1,
             Plan(Node::Insert {
-                table_name: "tbl1".to_string(),
+                table_name: "tbl1".into(),
                 columns: vec![],
                 values: vec![vec![
                     Expression::Consts(ast::Consts::Integer(1)),
@@ -104,16 +335,17 @@ mod tests {
                     Expression::Consts(ast::Consts::String("a".to_string())),
                     Expression::Consts(ast::Consts::Boolean(true)),
                 ]],
+                source: None,
             })
         );
 
         let sql2 = "insert into tbl2 (c1, c2, c3) values (3, 'a', true),(4, 'b', false);";
         let stmt2 = Parser::new(sql2).parse()?;
-        let p2 = Plan::build(stmt2);
+        let p2 = Plan::build(stmt2, &txn)?;
         assert_eq!(
             p2,
             Plan(Node::Insert {
-                table_name: "tbl2".to_string(),
+                table_name: "tbl2".into(),
                 columns: vec!["c1".to_string(), "c2".to_string(), "c3".to_string()],
                 values: vec![
                     vec![
@@ -127,6 +359,7 @@ mod tests {
                         Expression::Consts(ast::Consts::Boolean(false)),
                     ],
                 ],
+                source: None,
             })
         );
 
@@ -135,16 +368,139 @@ mod tests {
 
     #[test]
     fn test_plan_select() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        kvengine
+            .session()?
+            .execute("create table tbl1 (a int, b text, c float);")?;
+        let txn = kvengine.begin()?;
+
         let sql = "select * from tbl1;";
         let stmt = Parser::new(sql).parse()?;
-        let p = Plan::build(stmt);
+        let p = Plan::build(stmt, &txn)?;
         assert_eq!(
             p,
             Plan(Node::Scan {
-                table_name: "tbl1".to_string(),
+                table_name: "tbl1".into(),
+                columns: None,
+            })
+        );
+
+        let sql = "select c, tbl1.*, tbl1.a from tbl1;";
+        let stmt = Parser::new(sql).parse()?;
+        let p = Plan::build(stmt, &txn)?;
+        assert_eq!(
+            p,
+            Plan(Node::Projection {
+                source: Box::new(Node::Scan {
+                    table_name: "tbl1".into(),
+                    columns: None,
+                }),
+                exprs: vec![
+                    Expression::Column(2),
+                    Expression::Column(0),
+                    Expression::Column(1),
+                    Expression::Column(2),
+                    Expression::Column(0),
+                ],
+                columns: vec![
+                    "c".to_string(),
+                    "a".to_string(),
+                    "b".to_string(),
+                    "c".to_string(),
+                    "a".to_string(),
+                ],
             })
         );
 
+        for sql in [
+            "select * from tbl2;",
+            "select d from tbl1;",
+            "select tbl2.a from tbl1;",
+            "select tbl2.* from tbl1;",
+        ] {
+            let stmt = Parser::new(sql).parse()?;
+            assert!(Plan::build(stmt, &txn).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_values() -> Result<()> {
+        let txn = KVEngine::new(MemoryEngine::new()).begin()?;
+        let stmt = Parser::new("values (1, 'a'), (2, 'b');").parse()?;
+        let p = Plan::build(stmt, &txn)?;
+        assert_eq!(
+            p,
+            Plan(Node::Values {
+                rows: vec![
+                    vec![
+                        Expression::Consts(ast::Consts::Integer(1)),
+                        Expression::Consts(ast::Consts::String("a".to_string())),
+                    ],
+                    vec![
+                        Expression::Consts(ast::Consts::Integer(2)),
+                        Expression::Consts(ast::Consts::String("b".to_string())),
+                    ],
+                ],
+                columns: vec!["column1".to_string(), "column2".to_string()],
+            })
+        );
+
+        // 每一行的列数需要一致
+        let stmt = Parser::new("values (1, 'a'), (2);").parse()?;
+        assert!(Plan::build(stmt, &txn).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_columnar_scan() -> Result<()> {
+        let kvengine = KVEngine::new(MemoryEngine::new());
+        kvengine
+            .session()?
+            .execute("create table tbl1 (a int, b text, c float) with (storage = 'columnar');")?;
+        let txn = kvengine.begin()?;
+
+        // 只读取 select 列表和过滤条件中用到的列
+        let stmt = ast::Statement::Select {
+            select: vec![ast::SelectItem::Expr(
+                Expression::Field(None, "c".to_string()),
+                None,
+            )],
+            distinct: false,
+            table_name: Some("tbl1".into()),
+            table_args: None,
+            subquery: None,
+            alias: None,
+            joins: vec![],
+            filter: Some(Expression::Operation(ast::Operation::Equal(
+                Box::new(Expression::Field(None, "b".to_string())),
+                Box::new(ast::Consts::String("x".to_string()).into()),
+            ))),
+            group_by: vec![],
+            having: None,
+            order_by: vec![],
+            limit: None,
+            as_of: None,
+        };
+        let p = Plan::build(stmt, &txn)?;
+        assert_eq!(
+            p,
+            Plan(Node::Projection {
+                source: Box::new(Node::Filter {
+                    source: Box::new(Node::Scan {
+                        table_name: "tbl1".into(),
+                        columns: Some(vec![1, 2]),
+                    }),
+                    predicate: Expression::Operation(ast::Operation::Equal(
+                        Box::new(Expression::Column(1)),
+                        Box::new(ast::Consts::String("x".to_string()).into()),
+                    )),
+                }),
+                exprs: vec![Expression::Column(2)],
+                columns: vec!["c".to_string()],
+            })
+        );
         Ok(())
     }
 }
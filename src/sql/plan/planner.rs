@@ -1,57 +1,1846 @@
-use crate::sql::{
-    parser::ast,
-    schema::{self, Table},
-    types::Value,
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        function,
+        ident::Ident,
+        parser::{
+            ast::{self, Expression},
+            Parser,
+        },
+        schema::{self, Compression, ExternalTable, StorageLayout, Table, Ttl, View},
+        types::{DataType, Value},
+    },
+    storage::mvcc::BackupOptions,
 };
+use std::{collections::HashMap, ops::Bound};
 
 use super::{Node, Plan};
 
-pub struct Planner;
+pub struct Planner<'a, T: Transaction> {
+    // 用于在计划阶段读取表的元数据
+    txn: &'a T,
+}
 
-impl Planner {
-    pub fn new() -> Self {
-        Self {}
+impl<'a, T: Transaction> Planner<'a, T> {
+    pub fn new(txn: &'a T) -> Self {
+        Self { txn }
     }
 
-    pub fn build(&mut self, stmt: ast::Statement) -> Plan {
-        Plan(self.build_statment(stmt))
+    pub fn build(&mut self, stmt: ast::Statement) -> Result<Plan> {
+        Ok(Plan(self.build_statment(stmt)?))
     }
 
-    fn build_statment(&self, stmt: ast::Statement) -> Node {
-        match stmt {
-            ast::Statement::CreateTable { name, columns } => Node::CreateTable {
-                schema: Table {
+    fn build_statment(&self, stmt: ast::Statement) -> Result<Node> {
+        Ok(match stmt {
+            ast::Statement::CreateTable {
+                name,
+                columns,
+                options,
+                location,
+            } => {
+                let unique = columns.iter().map(|c| c.unique).collect::<Vec<_>>();
+                let mut table = Table {
                     name,
                     columns: columns
                         .into_iter()
-                        .map(|c| {
-                            let nullable = c.nullable.unwrap_or(true);
-                            let default = match c.default {
-                                Some(expr) => Some(Value::from_expression(expr)),
-                                None if nullable => Some(Value::Null),
-                                None => None,
-                            };
-
-                            schema::Column {
-                                name: c.name,
-                                datatype: c.datatype,
-                                nullable,
-                                default,
-                            }
-                        })
-                        .collect(),
+                        .map(Self::build_column)
+                        .collect::<Result<_>>()?,
+                    storage: StorageLayout::default(),
+                    compression: Compression::default(),
+                    ttl: None,
+                    external: location.map(|location| ExternalTable {
+                        location,
+                        header: false,
+                        delimiter: ',',
+                    }),
+                    indexes: Vec::new(),
+                };
+                Self::apply_table_options(&mut table, options)?;
+                // 每个 UNIQUE 列创建一个唯一索引，第一列是主键，本身就是唯一的
+                for i in (1..unique.len()).filter(|&i| unique[i]) {
+                    if table.storage == StorageLayout::Columnar || table.external.is_some() {
+                        return Err(Error::Internal(format!(
+                            "UNIQUE column {} is only supported in row storage tables",
+                            table.columns[i].name
+                        )));
+                    }
+                    table.indexes.push(schema::Index {
+                        name: format!("{}_{}_key", table.name, table.columns[i].name).into(),
+                        columns: vec![i],
+                        unique: true,
+                        grid: None,
+                    });
+                }
+                Node::CreateTable { schema: table }
+            }
+            ast::Statement::AlterTable {
+                table_name,
+                operation,
+            } => match operation {
+                // 已有的行都使用默认值，不能满足 UNIQUE 约束
+                ast::AlterTableOperation::AddColumn { column } if column.unique => {
+                    return Err(Error::Internal(format!(
+                        "cannot add UNIQUE column {} to table {}",
+                        column.name, table_name
+                    )))
+                }
+                ast::AlterTableOperation::AddColumn { column } => Node::AddColumn {
+                    table_name,
+                    column: Self::build_column(column)?,
+                },
+                ast::AlterTableOperation::RenameTable { new_name } => Node::RenameTable {
+                    table_name,
+                    new_name,
+                },
+                ast::AlterTableOperation::RenameColumn { column, new_name } => Node::RenameColumn {
+                    table_name,
+                    column,
+                    new_name,
                 },
             },
+            ast::Statement::CreateView {
+                name,
+                columns,
+                query,
+                text,
+            } => {
+                if let ast::Statement::Select { as_of: Some(_), .. } = *query {
+                    return Err(Error::Internal(
+                        "AS OF is not allowed in a view".to_string(),
+                    ));
+                }
+                // 创建时构建一次查询，检查其中的表和列是否存在
+                let outputs = self.output_columns(&self.build_statment(*query)?)?;
+                let columns = match columns.is_empty() {
+                    true => outputs,
+                    false if columns.len() == outputs.len() => columns,
+                    false => {
+                        return Err(Error::Internal(format!(
+                            "view {} has {} columns but its query returns {}",
+                            name,
+                            columns.len(),
+                            outputs.len()
+                        )))
+                    }
+                };
+                if let Some(column) = columns
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, c)| columns[..i].contains(c).then_some(c))
+                {
+                    return Err(Error::Internal(format!(
+                        "duplicate column {} in view {}",
+                        column, name
+                    )));
+                }
+                Node::CreateView {
+                    view: View {
+                        name,
+                        columns,
+                        query: text,
+                    },
+                }
+            }
+            ast::Statement::DropView { name } => Node::DropView { name },
+            ast::Statement::CreateIndex {
+                name,
+                table_name,
+                columns,
+                spatial,
+                options,
+            } => {
+                let table = self.txn.must_get_table(table_name.clone())?;
+                let scope = Scope::from_table(&table);
+                let mut indexed = Vec::new();
+                for column in &columns {
+                    let column = scope.resolve(None, column)?;
+                    if indexed.contains(&column) {
+                        return Err(Error::Internal(format!(
+                            "duplicate column {} in index {}",
+                            table.columns[column].name, name
+                        )));
+                    }
+                    indexed.push(column);
+                }
+                let grid = match spatial {
+                    true => Some(Self::spatial_grid_size(&table, &name, &indexed, options)?),
+                    false => {
+                        if let Some((option, _)) = options.first() {
+                            return Err(Error::Internal(format!(
+                                "unknown index option {}",
+                                option
+                            )));
+                        }
+                        // 只有主键一列时和主键的隐式索引相同
+                        if indexed == [0] {
+                            return Err(Error::Internal(format!(
+                                "column {} of table {} is the primary key",
+                                table.columns[0].name, table_name
+                            )));
+                        }
+                        None
+                    }
+                };
+                Node::CreateIndex {
+                    table_name,
+                    index: schema::Index {
+                        name,
+                        columns: indexed,
+                        unique: false,
+                        grid,
+                    },
+                }
+            }
+            ast::Statement::DropIndex { name, table_name } => Node::DropIndex { table_name, name },
             ast::Statement::Insert {
                 table_name,
                 columns,
                 values,
-            } => Node::Insert {
+                query,
+            } => {
+                // 查询和 INSERT 在同一个事务中执行，不能单独指定历史版本
+                if let Some(ast::Statement::Select { as_of: Some(_), .. }) = query.as_deref() {
+                    return Err(Error::Internal(
+                        "AS OF is not allowed in INSERT ... SELECT".to_string(),
+                    ));
+                }
+                Node::Insert {
+                    table_name,
+                    columns: columns.unwrap_or_default(),
+                    values,
+                    source: query
+                        .map(|q| self.build_statment(*q).map(Box::new))
+                        .transpose()?,
+                }
+            }
+            ast::Statement::Update {
                 table_name,
-                columns: columns.unwrap_or_default(),
-                values,
+                columns,
+                filter,
+            } => {
+                let table = self.txn.must_get_table(table_name.clone())?;
+                let scope = Scope::from_table(&table);
+                let mut assignments = Vec::new();
+                for (name, expr) in columns {
+                    let i = scope.resolve(None, &name)?;
+                    if assignments.iter().any(|(c, _)| *c == i) {
+                        return Err(Error::Internal(format!(
+                            "column {} is assigned more than once",
+                            name
+                        )));
+                    }
+                    let expr = scope.resolve_expression(expr)?;
+                    if expr.contains_aggregate() {
+                        return Err(Error::Internal(
+                            "aggregate functions are not allowed in UPDATE".to_string(),
+                        ));
+                    }
+                    assignments.push((i, expr));
+                }
+                // 需要读取整行，修改之后整行写回
+                let mut source = Node::Scan {
+                    table_name: table_name.clone(),
+                    columns: None,
+                };
+                if let Some(predicate) = filter {
+                    let predicate = scope.resolve_expression(predicate)?;
+                    source =
+                        self.build_index_scan(source, 0, &split_conjunction(predicate.clone()))?;
+                    source = Node::Filter {
+                        source: Box::new(source),
+                        predicate,
+                    };
+                }
+                Node::Update {
+                    table_name,
+                    source: Box::new(source),
+                    columns: assignments,
+                }
+            }
+            ast::Statement::Delete { table_name, filter } => {
+                let table = self.txn.must_get_table(table_name.clone())?;
+                let mut source = Node::Scan {
+                    table_name: table_name.clone(),
+                    columns: None,
+                };
+                if let Some(predicate) = filter {
+                    let predicate = Scope::from_table(&table).resolve_expression(predicate)?;
+                    source =
+                        self.build_index_scan(source, 0, &split_conjunction(predicate.clone()))?;
+                    source = Node::Filter {
+                        source: Box::new(source),
+                        predicate,
+                    };
+                }
+                Node::Delete {
+                    table_name,
+                    source: Box::new(source),
+                }
+            }
+            ast::Statement::Values { rows } => {
+                let width = rows.first().map_or(0, |r| r.len());
+                if rows.iter().any(|r| r.len() != width) {
+                    return Err(Error::Internal(
+                        "all VALUES rows must have the same number of columns".to_string(),
+                    ));
+                }
+                Node::Values {
+                    rows,
+                    columns: (1..=width).map(|i| format!("column{}", i)).collect(),
+                }
+            }
+            ast::Statement::RollbackTransaction { version } => {
+                Node::RollbackTransaction { version }
+            }
+            ast::Statement::Checkpoint => Node::Checkpoint,
+            ast::Statement::ShowIndexes { table_name } => Node::ShowIndexes { table_name },
+            ast::Statement::ShowCreateTable { table_name } => Node::ShowCreateTable { table_name },
+            ast::Statement::Describe { table_name } => Node::Describe { table_name },
+            ast::Statement::Analyze { table_name } => Node::Analyze { table_name },
+            ast::Statement::CheckTable { table_name } => Node::CheckTable { table_name },
+            ast::Statement::Reindex { table_name, index } => {
+                // 主键的隐式索引和二级索引都和行一起重建
+                if let Some(index) = index.filter(|i| i != "primary") {
+                    let table = self.txn.must_get_table(table_name.clone())?;
+                    if !table.indexes.iter().any(|i| i.name == index) {
+                        return Err(Error::Internal(format!(
+                            "index {} does not exist on table {}",
+                            index, table_name
+                        )));
+                    }
+                }
+                Node::Reindex { table_name }
+            }
+            // session 语句由 Session 直接执行，不需要生成执行计划
+            ast::Statement::Set { .. }
+            | ast::Statement::ShowVariables { .. }
+            | ast::Statement::Attach { .. }
+            | ast::Statement::Detach { .. }
+            | ast::Statement::Kill { .. }
+            | ast::Statement::Begin
+            | ast::Statement::Commit
+            | ast::Statement::Rollback => {
+                return Err(Error::Internal(
+                    "session statement cannot be planned".to_string(),
+                ))
+            }
+            ast::Statement::Explain { statement, .. }
+                if matches!(*statement, ast::Statement::Explain { .. }) =>
+            {
+                return Err(Error::Internal("EXPLAIN cannot be nested".to_string()))
+            }
+            ast::Statement::Explain {
+                statement,
+                format,
+                verbose,
+            } => Node::Explain {
+                source: Box::new(self.build_statment(*statement)?),
+                format,
+                verbose,
+            },
+            ast::Statement::Copy {
+                table_name,
+                path,
+                options,
+            } => {
+                let mut source = ExternalTable {
+                    location: path,
+                    header: false,
+                    delimiter: ',',
+                };
+                for (name, value) in options {
+                    let value = Value::from_expression(value)?;
+                    if !Self::apply_csv_option(&mut source, &name, &value)? {
+                        return Err(Error::Internal(format!("unknown copy option {}", name)));
+                    }
+                }
+                Node::Copy { table_name, source }
+            }
+            ast::Statement::Backup {
+                path,
+                since,
+                options,
+            } => Node::Backup {
+                path,
+                options: BackupOptions {
+                    since,
+                    ..Self::build_backup_options(options)?
+                },
+            },
+            ast::Statement::SetOperation {
+                op,
+                all,
+                left,
+                right,
+                order_by,
+            } => {
+                for stmt in [&left, &right] {
+                    if let ast::Statement::Select { as_of: Some(_), .. } = **stmt {
+                        return Err(Error::Internal(format!("AS OF is not allowed in {}", op)));
+                    }
+                }
+                let (left, right) = (self.build_statment(*left)?, self.build_statment(*right)?);
+                let columns = self.output_columns(&left)?;
+                let width = self.output_columns(&right)?.len();
+                if columns.len() != width {
+                    return Err(Error::Internal(format!(
+                        "each {} query must have the same number of columns, got {} and {}",
+                        op,
+                        columns.len(),
+                        width
+                    )));
+                }
+                let mut node = Node::SetOperation {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    op,
+                    all,
+                };
+                // 排序只能使用结果中的列名
+                if !order_by.is_empty() {
+                    let scope = Scope {
+                        columns: columns
+                            .into_iter()
+                            .map(|c| (Ident::default(), c.into()))
+                            .collect(),
+                    };
+                    node = Node::Order {
+                        source: Box::new(node),
+                        order_by: order_by
+                            .into_iter()
+                            .map(|(e, direction)| Ok((scope.resolve_expression(e)?, direction)))
+                            .collect::<Result<_>>()?,
+                    };
+                }
+                node
+            }
+            // 没有 FROM 的查询只返回一行
+            ast::Statement::Select {
+                select,
+                table_name: None,
+                filter,
+                ..
+            } => {
+                let mut exprs = Vec::new();
+                let mut columns = Vec::new();
+                for item in select {
+                    match item {
+                        ast::SelectItem::Expr(expr, alias) if expr.is_constant() => {
+                            columns.push(alias.unwrap_or_else(|| expr.to_string()));
+                            exprs.push(expr);
+                        }
+                        ast::SelectItem::Expr(expr, _) => {
+                            return Err(Error::Internal(format!(
+                                "expression {} requires a FROM clause",
+                                expr
+                            )))
+                        }
+                        ast::SelectItem::Wildcard(_) => {
+                            return Err(Error::Internal(
+                                "SELECT * requires a FROM clause".to_string(),
+                            ))
+                        }
+                    }
+                }
+                let node = Node::Values {
+                    columns,
+                    rows: vec![exprs],
+                };
+                // 条件不成立时不返回行
+                match filter {
+                    Some(predicate) if predicate.is_constant() => Node::Filter {
+                        source: Box::new(node),
+                        predicate,
+                    },
+                    Some(predicate) => {
+                        return Err(Error::Internal(format!(
+                            "expression {} requires a FROM clause",
+                            predicate
+                        )))
+                    }
+                    None => node,
+                }
+            }
+            ast::Statement::Select {
+                select,
+                distinct,
+                table_name: Some(table_name),
+                table_args,
+                subquery,
+                alias,
+                joins,
+                filter,
+                group_by,
+                having,
+                order_by,
+                limit,
+                ..
+            } => {
+                let joined = !joins.is_empty();
+                let FromClause {
+                    scope,
+                    sources,
+                    join_predicates,
+                } = self.build_from(table_name, table_args, subquery, alias, joins)?;
+                // WHERE 中 AND 连接的 EXISTS 和 IN 子查询改写成半连接，在其他过滤条件之后执行
+                let mut predicates = Vec::new();
+                let mut semi_joins = Vec::new();
+                for predicate in filter.map(split_conjunction).unwrap_or_default() {
+                    let (stmt, key, anti) = match predicate {
+                        Expression::Exists(stmt) => (stmt, None, false),
+                        Expression::InSubquery(key, stmt) => (stmt, Some(*key), false),
+                        Expression::Operation(ast::Operation::Not(e))
+                            if matches!(*e, Expression::Exists(_) | Expression::InSubquery(..)) =>
+                        {
+                            match *e {
+                                Expression::Exists(stmt) => (stmt, None, true),
+                                Expression::InSubquery(key, stmt) => (stmt, Some(*key), true),
+                                _ => unreachable!(),
+                            }
+                        }
+                        predicate => {
+                            predicates.push(scope.resolve_expression(predicate)?);
+                            continue;
+                        }
+                    };
+                    semi_joins.push(self.build_semi_join(&scope, *stmt, key, anti)?);
+                }
+                // 有等值条件的表通过二级索引读取，过滤条件仍然在连接之后判断
+                let sources = sources
+                    .into_iter()
+                    .map(|(source, offset, width, columnar)| {
+                        let source = self.build_index_scan(source, offset, &predicates)?;
+                        Ok((source, offset, width, columnar))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let predicate = join_conjunction(predicates);
+                let group_by = group_by
+                    .into_iter()
+                    .map(|e| scope.resolve_expression(e))
+                    .collect::<Result<Vec<_>>>()?;
+                if group_by.iter().any(Expression::contains_aggregate) {
+                    return Err(Error::Internal(
+                        "aggregate functions are not allowed in GROUP BY".to_string(),
+                    ));
+                }
+                // 有 GROUP BY、HAVING 或者聚合函数时，select 列表和排序在聚合之后计算
+                let aggregate = !group_by.is_empty()
+                    || having.is_some()
+                    || select.iter().any(
+                        |item| matches!(item, ast::SelectItem::Expr(e, _) if e.contains_aggregate()),
+                    )
+                    || order_by.iter().any(|(e, _)| e.contains_aggregate());
+                let mut aggregates = Vec::new();
+
+                // 别名和对应的 select 列表中的表达式的下标
+                let mut aliases = Vec::new();
+                // select * 不需要投影，连接的结果需要使用带表名的列名
+                let wildcard = select == vec![ast::SelectItem::Wildcard(None)];
+                let projection = if wildcard && !aggregate && !joined {
+                    None
+                } else {
+                    let mut exprs = Vec::new();
+                    let mut columns = Vec::new();
+                    for item in select {
+                        match item {
+                            ast::SelectItem::Wildcard(_) if aggregate => {
+                                return Err(Error::Internal(
+                                    "SELECT * cannot be used with GROUP BY or aggregate functions"
+                                        .to_string(),
+                                ))
+                            }
+                            // 通配符按照表中列的顺序展开
+                            ast::SelectItem::Wildcard(table) => {
+                                for i in scope.expand(table.as_deref())? {
+                                    let (table, column) = &scope.columns[i];
+                                    exprs.push(Expression::Column(i));
+                                    columns.push(match joined {
+                                        true => format!("{}.{}", table, column),
+                                        false => column.to_string(),
+                                    });
+                                }
+                            }
+                            ast::SelectItem::Expr(expr, alias) => {
+                                columns.push(match (alias, &expr) {
+                                    (Some(alias), _) => {
+                                        aliases.push((alias.clone(), exprs.len()));
+                                        alias
+                                    }
+                                    (None, Expression::Field(_, name)) => name.clone(),
+                                    (None, expr) => expr.to_string(),
+                                });
+                                let expr = scope.resolve_expression(expr)?;
+                                exprs.push(if aggregate {
+                                    scope.resolve_aggregate(expr, &group_by, &mut aggregates)?
+                                } else {
+                                    expr
+                                });
+                            }
+                        }
+                    }
+                    Some((exprs, columns))
+                };
+
+                let mut order = Vec::new();
+                for (expr, direction) in order_by {
+                    // 排序中的列名优先使用 select 列表中的别名，直接使用别名对应的表达式
+                    if let (Expression::Field(None, name), Some((exprs, _))) = (&expr, &projection)
+                    {
+                        if let Some((_, i)) = aliases.iter().find(|(alias, _)| alias == name) {
+                            order.push((exprs[*i].clone(), direction));
+                            continue;
+                        }
+                    }
+                    let expr = scope.resolve_expression(expr)?;
+                    order.push(if aggregate {
+                        (
+                            scope.resolve_aggregate(expr, &group_by, &mut aggregates)?,
+                            direction,
+                        )
+                    } else {
+                        (expr, direction)
+                    });
+                }
+
+                // 分组之后的过滤条件和 select 列表一样引用聚合的结果
+                let having = having
+                    .map(|e| {
+                        let expr = scope.resolve_expression(e)?;
+                        scope.resolve_aggregate(expr, &group_by, &mut aggregates)
+                    })
+                    .transpose()?;
+
+                // 列存的表只需要读取用到的列，聚合之后的 select 列表引用的是聚合的结果
+                let scan_columns = match &projection {
+                    Some((exprs, _)) if sources.iter().any(|(.., columnar)| *columnar) => {
+                        let mut used = Vec::new();
+                        let exprs = if aggregate {
+                            group_by
+                                .iter()
+                                .chain(aggregates.iter().flat_map(ast::Aggregate::exprs))
+                                .collect::<Vec<_>>()
+                        } else {
+                            exprs.iter().chain(order.iter().map(|(e, _)| e)).collect()
+                        };
+                        predicate
+                            .iter()
+                            .chain(join_predicates.iter().map(|(p, _)| p))
+                            .chain(semi_joins.iter().flat_map(|j| {
+                                j.predicate.iter().chain(j.keys.iter().map(|(key, _)| key))
+                            }))
+                            .chain(exprs)
+                            .for_each(|e| e.collect_columns(&mut used));
+                        used.sort();
+                        used.dedup();
+                        Some(used)
+                    }
+                    _ => None,
+                };
+
+                // 每个列存的表只读取自己用到的列，下标是在表中的位置
+                let sources = sources
+                    .into_iter()
+                    .map(|(mut source, offset, width, columnar)| {
+                        if let (Node::Scan { columns, .. }, Some(used), true) =
+                            (&mut source, &scan_columns, columnar)
+                        {
+                            *columns = Some(
+                                used.iter()
+                                    .filter(|i| (offset..offset + width).contains(i))
+                                    .map(|i| i - offset)
+                                    .collect(),
+                            );
+                        }
+                        (source, offset)
+                    })
+                    .collect::<Vec<_>>();
+                let mut node = self.join_sources(sources, join_predicates)?;
+
+                // 过滤条件
+                if let Some(predicate) = predicate {
+                    node = Node::Filter {
+                        source: Box::new(node),
+                        predicate,
+                    };
+                }
+                let width = scope.columns.len();
+                for join in semi_joins {
+                    node = build_semi_join(node, join, width);
+                }
+
+                if aggregate {
+                    node = Node::Aggregate {
+                        source: Box::new(node),
+                        group_by,
+                        aggregates,
+                    };
+                }
+
+                if let Some(predicate) = having {
+                    node = Node::Filter {
+                        source: Box::new(node),
+                        predicate,
+                    };
+                }
+
+                if !order.is_empty() {
+                    node = Node::Order {
+                        source: Box::new(node),
+                        order_by: order,
+                    };
+                }
+
+                // limit 在过滤、聚合和排序之后、投影之前，DISTINCT 时在去重之后
+                if let (Some(limit), false) = (limit, distinct) {
+                    node = Node::Limit {
+                        source: Box::new(node),
+                        limit: limit as usize,
+                    };
+                }
+
+                if let Some((exprs, columns)) = projection {
+                    node = Node::Projection {
+                        source: Box::new(node),
+                        exprs,
+                        columns,
+                    };
+                }
+
+                // 按照投影之后的整行去重
+                if distinct {
+                    node = Node::Distinct {
+                        source: Box::new(node),
+                    };
+                    if let Some(limit) = limit {
+                        node = Node::Limit {
+                            source: Box::new(node),
+                            limit: limit as usize,
+                        };
+                    }
+                }
+                node
+            }
+        })
+    }
+
+    // FROM 中的第一个表和连接的表，连接条件只能引用前面的表和连接的表中的列
+    fn build_from(
+        &self,
+        table_name: Ident,
+        table_args: Option<Vec<Expression>>,
+        subquery: Option<Box<ast::Statement>>,
+        alias: Option<Ident>,
+        joins: Vec<ast::Join>,
+    ) -> Result<FromClause> {
+        let (mut scope, source, columnar) =
+            self.build_from_item(table_name, table_args, subquery, alias)?;
+        let mut sources = vec![(source, 0, scope.columns.len(), columnar)];
+        let mut join_predicates = Vec::new();
+        for join in joins {
+            let (right, source, columnar) =
+                self.build_from_item(join.table_name, join.table_args, join.subquery, join.alias)?;
+            sources.push((source, scope.columns.len(), right.columns.len(), columnar));
+            scope.columns.extend(right.columns);
+            let predicate = scope.resolve_expression(join.predicate)?;
+            if predicate.contains_aggregate() {
+                return Err(Error::Internal(
+                    "aggregate functions are not allowed in JOIN conditions".to_string(),
+                ));
+            }
+            join_predicates.push((predicate, join.kind));
+        }
+        Ok(FromClause {
+            scope,
+            sources,
+            join_predicates,
+        })
+    }
+
+    // EXISTS 和 IN 子查询，返回半连接右边读取数据的节点和连接的条件，key 是 IN 左边的表达式
+    // 子查询中可以引用外层查询的列，WHERE 中引用外层查询的条件作为半连接的条件，其他条件在子查询中过滤
+    // 有分组或者聚合函数的子查询不能引用外层查询的列，整个子查询作为右边
+    fn build_semi_join(
+        &self,
+        outer: &Scope,
+        stmt: ast::Statement,
+        key: Option<Expression>,
+        anti: bool,
+    ) -> Result<SubqueryJoin> {
+        let width = outer.columns.len();
+        let key = key.map(|e| outer.resolve_expression(e)).transpose()?;
+        // NOT IN 在子查询的结果或者左边的值中有 NULL 时结果未知，不输出这一行
+        let null_aware = anti && key.is_some();
+        let (right, keys, predicate) = match stmt {
+            ast::Statement::Select { as_of: Some(_), .. } => {
+                return Err(Error::Internal(
+                    "AS OF is not allowed in a subquery".to_string(),
+                ))
+            }
+            ast::Statement::Select {
+                select,
+                table_name: Some(table_name),
+                table_args,
+                subquery,
+                alias,
+                joins,
+                filter,
+                group_by,
+                having: None,
+                limit: None,
+                ..
+            } if group_by.is_empty()
+                && !select.iter().any(
+                    |item| matches!(item, ast::SelectItem::Expr(e, _) if e.contains_aggregate()),
+                ) =>
+            {
+                let FromClause {
+                    scope,
+                    sources,
+                    join_predicates,
+                } = self.build_from(table_name, table_args, subquery, alias, joins)?;
+                // EXISTS 的 select 列表不影响结果，只检查其中的列是否存在
+                let mut outputs = Vec::new();
+                for item in select {
+                    match item {
+                        ast::SelectItem::Wildcard(table) => outputs.extend(
+                            scope
+                                .expand(table.as_deref())?
+                                .into_iter()
+                                .map(|i| Expression::Column(width + i)),
+                        ),
+                        ast::SelectItem::Expr(expr, _) => {
+                            outputs.push(scope.resolve_correlated(outer, expr)?)
+                        }
+                    }
+                }
+                let mut filters = Vec::new();
+                let mut predicates = Vec::new();
+                for expr in filter.map(split_conjunction).unwrap_or_default() {
+                    let predicate = scope.resolve_correlated(outer, expr)?;
+                    match references_outer(&predicate, width) {
+                        true => predicates.push(predicate),
+                        false => filters.push(shift_columns(predicate, width)),
+                    }
+                }
+                // IN 的值只引用子查询的列时作为 key，否则和左边的值比较的条件作为连接条件
+                let mut keys = None;
+                if let Some(key) = key {
+                    let output = single_column(outputs)?;
+                    if !references_outer(&output, width) {
+                        keys = Some((key, shift_columns(output, width)));
+                    } else if null_aware {
+                        return Err(Error::Internal(
+                            "NOT IN subquery cannot reference outer columns in the select list"
+                                .to_string(),
+                        ));
+                    } else {
+                        predicates.push(Expression::Operation(ast::Operation::Equal(
+                            Box::new(key),
+                            Box::new(output),
+                        )));
+                    }
+                }
+                let sources = sources
+                    .into_iter()
+                    .map(|(source, offset, ..)| (source, offset))
+                    .collect();
+                let mut node = self.join_sources(sources, join_predicates)?;
+                if let Some(predicate) = join_conjunction(filters) {
+                    node = Node::Filter {
+                        source: Box::new(node),
+                        predicate,
+                    };
+                }
+                (node, keys, join_conjunction(predicates))
+            }
+            stmt => {
+                let node = self.build_statment(stmt)?;
+                let keys = match key {
+                    Some(key) => {
+                        single_column(self.output_columns(&node)?)?;
+                        Some((key, Expression::Column(0)))
+                    }
+                    None => None,
+                };
+                (node, keys, None)
+            }
+        };
+        Ok(SubqueryJoin {
+            right,
+            keys,
+            predicate,
+            anti,
+            null_aware,
+        })
+    }
+
+    // FROM 中的表、表函数或者子查询，返回其中的列和读取数据的节点，以及是否是列存的表
+    fn build_from_item(
+        &self,
+        table_name: Ident,
+        args: Option<Vec<Expression>>,
+        subquery: Option<Box<ast::Statement>>,
+        alias: Option<Ident>,
+    ) -> Result<(Scope, Node, bool)> {
+        // 表函数的结果没有保存在表中，列名由函数根据参数决定
+        let (mut scope, node, columnar) = match (args, subquery) {
+            // 子查询和外层查询在同一个事务中执行，不能单独指定历史版本
+            (_, Some(stmt)) => {
+                if let ast::Statement::Select { as_of: Some(_), .. } = *stmt {
+                    return Err(Error::Internal(
+                        "AS OF is not allowed in a subquery".to_string(),
+                    ));
+                }
+                let node = self.build_statment(*stmt)?;
+                let scope = Scope {
+                    columns: self
+                        .output_columns(&node)?
+                        .into_iter()
+                        .map(|c| (table_name.clone(), c.into()))
+                        .collect(),
+                };
+                (scope, node, false)
+            }
+            (Some(args), None) => {
+                let (args, columns) = self.build_table_function(&table_name, args)?;
+                let scope = Scope {
+                    columns: columns
+                        .iter()
+                        .map(|c| (table_name.clone(), c.into()))
+                        .collect(),
+                };
+                let node = Node::TableFunction {
+                    name: table_name.into(),
+                    args,
+                    columns,
+                };
+                (scope, node, false)
+            }
+            (None, None) if self.txn.get_table(table_name.clone())?.is_none() => {
+                let node = self.build_view(&table_name)?;
+                let scope = Scope {
+                    columns: self
+                        .output_columns(&node)?
+                        .into_iter()
+                        .map(|c| (table_name.clone(), c.into()))
+                        .collect(),
+                };
+                (scope, node, false)
+            }
+            (None, None) => {
+                let table = self.txn.must_get_table(table_name.clone())?;
+                let columnar = table.storage == StorageLayout::Columnar;
+                let node = Node::Scan {
+                    table_name,
+                    columns: None,
+                };
+                (Scope::from_table(&table), node, columnar)
+            }
+        };
+        // 有别名时表名不能再用来引用列
+        if let Some(alias) = alias {
+            for (table, _) in &mut scope.columns {
+                *table = alias.clone();
+            }
+        }
+        Ok((scope, node, columnar))
+    }
+
+    // 重新解析视图的查询语句，展开成子查询，结果的列名换成视图的列名，视图也不存在时报错
+    fn build_view(&self, name: &Ident) -> Result<Node> {
+        let view = self
+            .txn
+            .get_view(name.clone())?
+            .ok_or(Error::Internal(format!("table {} does not exist", name)))?;
+        let stmt = Parser::new(&format!("{};", view.query)).parse()?;
+        let node = self.build_statment(stmt)?;
+        let outputs = self.output_columns(&node)?;
+        if outputs.len() != view.columns.len() {
+            return Err(Error::Internal(format!(
+                "view {} has {} columns but its query returns {}",
+                name,
+                view.columns.len(),
+                outputs.len()
+            )));
+        }
+        Ok(match outputs == view.columns {
+            true => node,
+            false => Node::Projection {
+                source: Box::new(node),
+                exprs: (0..outputs.len()).map(Expression::Column).collect(),
+                columns: view.columns,
             },
-            ast::Statement::Select { table_name } => Node::Scan { table_name },
+        })
+    }
+
+    // 查询节点输出的列名，子查询的列名作为外层查询中可以引用的列
+    fn output_columns(&self, node: &Node) -> Result<Vec<String>> {
+        match node {
+            Node::Projection { columns, .. }
+            | Node::Values { columns, .. }
+            | Node::TableFunction { columns, .. } => Ok(columns.clone()),
+            Node::Scan { table_name, .. }
+            | Node::IndexScan { table_name, .. }
+            | Node::SpatialScan { table_name, .. } => Ok(self
+                .txn
+                .must_get_table(table_name.clone())?
+                .columns
+                .iter()
+                .map(|c| c.name.to_string())
+                .collect()),
+            Node::Filter { source, .. }
+            | Node::Order { source, .. }
+            | Node::Limit { source, .. }
+            | Node::Distinct { source }
+            | Node::SemiJoin { left: source, .. }
+            | Node::SetOperation { left: source, .. } => self.output_columns(source),
+            node => Err(Error::Internal(format!(
+                "unexpected subquery plan {:?}",
+                node
+            ))),
+        }
+    }
+
+    // 过滤条件中有索引的前几列等于常量的条件，或者之后的一列和常量比较的条件时，通过索引读取行
+    // 使用等值条件最多的索引，相同时优先使用还有范围条件的索引
+    // 没有可以使用的普通索引时，st_dwithin 和 st_within 的条件通过空间索引读取范围内的网格中的点
+    // offset 是表在连接之后的行中的起始位置，只替换读取所有列的行存表的扫描
+    fn build_index_scan(
+        &self,
+        node: Node,
+        offset: usize,
+        predicates: &[Expression],
+    ) -> Result<Node> {
+        let Node::Scan {
+            table_name,
+            columns: None,
+        } = &node
+        else {
+            return Ok(node);
+        };
+        let table = self.txn.must_get_table(table_name.clone())?;
+        if table.indexes.is_empty() {
+            return Ok(node);
+        }
+        let width = table.columns.len();
+        // 每一列等于的常量，以及大于和小于的常量，一列有多个条件时使用第一个
+        let mut equal = HashMap::new();
+        let mut lower = HashMap::new();
+        let mut upper = HashMap::new();
+        for predicate in predicates {
+            let Some((column, op, value)) = compare_constant(predicate, offset, width) else {
+                continue;
+            };
+            // 类型不同的值需要按照比较的规则转换，不能直接查找索引
+            // 计算出错时不使用索引，由过滤条件报错
+            let Ok(value) = value.evaluate(&Vec::new()) else {
+                continue;
+            };
+            let datatype = table.columns[column].datatype;
+            if !datatype.matches(&value) {
+                continue;
+            }
+            // JSON 按照文本比较大小，和索引中的顺序不同
+            if op != Comparison::Equal && datatype == DataType::Json {
+                continue;
+            }
+            let (map, bound) = match op {
+                Comparison::Equal => {
+                    equal.entry(column).or_insert(value);
+                    continue;
+                }
+                Comparison::Greater => (&mut lower, Bound::Excluded(value)),
+                Comparison::GreaterOrEqual => (&mut lower, Bound::Included(value)),
+                Comparison::Less => (&mut upper, Bound::Excluded(value)),
+                Comparison::LessOrEqual => (&mut upper, Bound::Included(value)),
+            };
+            map.entry(column).or_insert(bound);
+        }
+
+        let mut best: Option<((usize, bool), Node)> = None;
+        for index in table.indexes.iter().filter(|i| i.grid.is_none()) {
+            let prefix = index
+                .columns
+                .iter()
+                .map_while(|c| equal.get(c).cloned())
+                .collect::<Vec<_>>();
+            let range = match index.columns.get(prefix.len()) {
+                Some(c) => (
+                    lower.get(c).cloned().unwrap_or(Bound::Unbounded),
+                    upper.get(c).cloned().unwrap_or(Bound::Unbounded),
+                ),
+                None => (Bound::Unbounded, Bound::Unbounded),
+            };
+            let score = (prefix.len(), range != (Bound::Unbounded, Bound::Unbounded));
+            if score == (0, false) || best.as_ref().is_some_and(|(best, _)| *best >= score) {
+                continue;
+            }
+            let scan = Node::IndexScan {
+                table_name: table_name.clone(),
+                index: index.name.clone(),
+                prefix,
+                range,
+            };
+            best = Some((score, scan));
+        }
+        if let Some((_, scan)) = best {
+            return Ok(scan);
+        }
+
+        for predicate in predicates {
+            let Some((column, area)) = spatial_area(predicate, offset, width) else {
+                continue;
+            };
+            let Some(index) = table
+                .indexes
+                .iter()
+                .find(|i| i.grid.is_some() && i.columns == [column])
+            else {
+                continue;
+            };
+            // 范围覆盖的网格列数太多时，逐列读取索引不如直接扫描整个表
+            let (Some((x0, _)), Some((x1, _))) = (
+                index.grid_cell(area[0], area[1]),
+                index.grid_cell(area[2], area[3]),
+            ) else {
+                continue;
+            };
+            if !area.iter().all(|v| v.is_finite()) || x1.saturating_sub(x0) >= MAX_GRID_COLUMNS {
+                continue;
+            }
+            return Ok(Node::SpatialScan {
+                table_name: table_name.clone(),
+                index: index.name.clone(),
+                area,
+            });
+        }
+        Ok(node)
+    }
+
+    // 计算表函数的参数并获取结果的列名，参数需要是常量表达式
+    fn build_table_function(
+        &self,
+        name: &str,
+        args: Vec<Expression>,
+    ) -> Result<(Vec<Value>, Vec<String>)> {
+        let function = self
+            .txn
+            .table_function(name)?
+            .ok_or(Error::Internal(format!(
+                "table function {} does not exist",
+                name
+            )))?;
+        let args = args
+            .into_iter()
+            .map(|arg| {
+                if !arg.is_constant() {
+                    return Err(Error::Internal(format!(
+                        "argument {} of table function {} must be a constant",
+                        arg, name
+                    )));
+                }
+                arg.evaluate(&Vec::new())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let columns = function.columns(&args)?;
+        Ok((args, columns))
+    }
+
+    // 解析 CSV 文件格式的选项，外部表和 COPY 共用，不是文件格式的选项时返回 false
+    // header 可以是布尔值或者 'true'、'false'
+    fn apply_csv_option(external: &mut ExternalTable, name: &str, value: &Value) -> Result<bool> {
+        match (name, value) {
+            ("header", Value::Boolean(b)) => external.header = *b,
+            ("header", Value::String(v)) if v == "true" || v == "false" => {
+                external.header = v == "true"
+            }
+            ("header", v) => {
+                return Err(Error::Internal(format!(
+                    "invalid value {:?} for option {}",
+                    v, name
+                )));
+            }
+            ("delimiter", v) => {
+                let Value::String(v) = v else {
+                    return Err(Error::Internal(format!("invalid delimiter {:?}", v)));
+                };
+                let mut chars = v.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if !matches!(c, '"' | '\r' | '\n') => external.delimiter = c,
+                    _ => return Err(Error::Internal(format!("invalid delimiter {:?}", v))),
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // 列定义，没有指定时列可以为 NULL，可以为 NULL 的列默认值是 NULL
+    // 默认值是常量表达式，在建表时计算一次，使用 now() 和 gen_uuid() 等函数时保存表达式，每次写入时计算
+    fn build_column(c: ast::Column) -> Result<schema::Column> {
+        let nullable = c.nullable.unwrap_or(true);
+        let mut default_expr = None;
+        let default = match c.default {
+            Some(expr) => {
+                let volatile = expr.is_volatile();
+                // 表达式的默认值也先计算一次，检查表达式中没有引用列并且结果的类型和列相同
+                let value = Value::from_expression(expr)?.coerce(c.datatype)?;
+                if value != Value::Null && !c.datatype.matches(&value) {
+                    return Err(Error::Internal(format!(
+                        "default value of column {} type mismatch",
+                        c.name
+                    )));
+                }
+                match volatile {
+                    true => {
+                        default_expr = c.default_text;
+                        None
+                    }
+                    false => Some(value),
+                }
+            }
+            None if nullable => Some(Value::Null),
+            None => None,
+        };
+
+        Ok(schema::Column {
+            name: c.name,
+            datatype: c.datatype,
+            nullable,
+            default,
+            default_expr,
+        })
+    }
+
+    // 解析 WITH 中的表选项
+    // 空间索引只能建在一个 POINT 列上，grid_size 是网格的边长，默认是 1
+    fn spatial_grid_size(
+        table: &Table,
+        name: &Ident,
+        columns: &[usize],
+        options: Vec<(String, Expression)>,
+    ) -> Result<f64> {
+        if columns.len() != 1 || table.columns[columns[0]].datatype != DataType::Point {
+            return Err(Error::Internal(format!(
+                "spatial index {} must be on a single POINT column",
+                name
+            )));
+        }
+        let mut size = 1.0;
+        for (option, value) in options {
+            if option != "grid_size" {
+                return Err(Error::Internal(format!("unknown index option {}", option)));
+            }
+            size = match Value::from_expression(value)? {
+                Value::Integer(i) => i as f64,
+                Value::Float(f) => f,
+                v => {
+                    return Err(Error::Internal(format!(
+                        "invalid value {:?} for index option {}",
+                        v, option
+                    )))
+                }
+            };
+        }
+        if !(size.is_finite() && size > 0.0) {
+            return Err(Error::Internal(format!(
+                "grid_size of spatial index {} must be a positive number",
+                name
+            )));
+        }
+        Ok(size)
+    }
+
+    fn apply_table_options(table: &mut Table, options: Vec<(String, Expression)>) -> Result<()> {
+        let (mut ttl, mut ttl_column) = (None, None);
+        for (name, value) in options {
+            let value = Value::from_expression(value)?;
+            // 外部表的文件格式
+            if name == "header" || name == "delimiter" {
+                match &mut table.external {
+                    Some(external) => {
+                        Self::apply_csv_option(external, &name, &value)?;
+                        continue;
+                    }
+                    None => {
+                        return Err(Error::Internal(format!(
+                            "table option {} is only supported for external tables",
+                            name
+                        )));
+                    }
+                }
+            }
+            let value = match value {
+                Value::String(s) => s,
+                v => {
+                    return Err(Error::Internal(format!(
+                        "invalid value {:?} for table option {}",
+                        v, name
+                    )))
+                }
+            };
+            match (name.as_str(), value.as_str()) {
+                ("storage", "row") => table.storage = StorageLayout::Row,
+                ("storage", "columnar") => table.storage = StorageLayout::Columnar,
+                ("compression", "none") => table.compression = Compression::None,
+                ("compression", "dictionary") => table.compression = Compression::Dictionary,
+                ("ttl", v) => ttl = Some(Self::parse_duration(v)?),
+                ("ttl_column", v) => ttl_column = Some(v.to_string()),
+                ("storage" | "compression", v) => {
+                    return Err(Error::Internal(format!(
+                        "invalid value {} for table option {}",
+                        v, name
+                    )));
+                }
+                (name, _) => {
+                    return Err(Error::Internal(format!("unknown table option {}", name)));
+                }
+            }
+        }
+
+        // 过期时间需要指定保存写入时间的整数列
+        table.ttl = match (ttl, ttl_column) {
+            (Some(seconds), Some(name)) => {
+                let column = table
+                    .columns
+                    .iter()
+                    .position(|c| c.name == name)
+                    .ok_or(Error::Internal(format!("column {} does not exist", name)))?;
+                if table.columns[column].datatype != DataType::Integer {
+                    return Err(Error::Internal(format!(
+                        "ttl column {} must be an integer",
+                        name
+                    )));
+                }
+                Some(Ttl { column, seconds })
+            }
+            (None, None) => None,
+            _ => {
+                return Err(Error::Internal(
+                    "table options ttl and ttl_column must be set together".to_string(),
+                ))
+            }
+        };
+
+        // 外部表的数据不在存储引擎中，存储方式、压缩和过期时间都不适用
+        if table.external.is_some()
+            && (table.storage != StorageLayout::Row
+                || table.compression != Compression::None
+                || table.ttl.is_some())
+        {
+            return Err(Error::Internal(format!(
+                "external table {} only supports table options header and delimiter",
+                table.name
+            )));
+        }
+        Ok(())
+    }
+
+    // 解析过期时间，例如 '7 days'，返回秒数
+    fn parse_duration(s: &str) -> Result<u64> {
+        let invalid = || Error::Internal(format!("invalid duration {}", s));
+        let (n, unit) = s.trim().split_once(' ').ok_or_else(invalid)?;
+        let n: u64 = n.parse().map_err(|_| invalid())?;
+        let unit = match unit.trim() {
+            "second" | "seconds" => 1,
+            "minute" | "minutes" => 60,
+            "hour" | "hours" => 60 * 60,
+            "day" | "days" => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        match n.checked_mul(unit) {
+            Some(seconds) if seconds > 0 => Ok(seconds),
+            _ => Err(invalid()),
+        }
+    }
+
+    // 解析 WITH 中的备份选项
+    fn build_backup_options(options: Vec<(String, Expression)>) -> Result<BackupOptions> {
+        let mut backup = BackupOptions::default();
+        for (name, value) in options {
+            let value = match Value::from_expression(value)? {
+                Value::Integer(i) if i > 0 => i as u64,
+                v => {
+                    return Err(Error::Internal(format!(
+                        "invalid value {:?} for backup option {}",
+                        v, name
+                    )))
+                }
+            };
+            match name.as_str() {
+                "batch_size" => backup.batch_size = value as usize,
+                "rate_limit" => backup.rate_limit = Some(value),
+                name => {
+                    return Err(Error::Internal(format!("unknown backup option {}", name)));
+                }
+            }
+        }
+        Ok(backup)
+    }
+
+    // 依次连接 FROM 中的每个表，offset 是每个表在连接之后的行中的起始位置
+    fn join_sources(
+        &self,
+        mut sources: Vec<(Node, usize)>,
+        join_predicates: Vec<(Expression, ast::JoinType)>,
+    ) -> Result<Node> {
+        let (mut node, _) = sources.remove(0);
+        for ((right, offset), (predicate, kind)) in sources.into_iter().zip(join_predicates) {
+            node = self.build_join(node, right, offset, predicate, kind)?;
+        }
+        Ok(node)
+    }
+
+    // 连接条件中有两边的列相等的条件，并且两边都已经按照 key 排序时使用归并连接，否则使用嵌套循环连接
+    // 按照主键读取的行不是按照主键的值排序的，只有二级索引读取的行和排序之后的行是有序的
+    // left_width 是左边的列数，右边的列在连接之后的下标从 left_width 开始
+    fn build_join(
+        &self,
+        left: Node,
+        right: Node,
+        left_width: usize,
+        predicate: Expression,
+        kind: ast::JoinType,
+    ) -> Result<Node> {
+        if let Some((l, r)) = equi_join_keys(&predicate, left_width) {
+            if self.sorted_by(&left, l)? && self.sorted_by(&right, r)? {
+                return Ok(Node::MergeJoin {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    left_key: Expression::Column(l),
+                    right_key: Expression::Column(r),
+                    predicate,
+                    kind,
+                });
+            }
+        }
+        Ok(Node::NestedLoopJoin {
+            left: Box::new(left),
+            right: Box::new(right),
+            predicate: Some(predicate),
+            kind,
+        })
+    }
+
+    // 节点输出的行是否已经按照第 i 列升序排列
+    fn sorted_by(&self, node: &Node, i: usize) -> Result<bool> {
+        Ok(match node {
+            Node::Order { order_by, .. } => {
+                order_by.first() == Some(&(Expression::Column(i), ast::Direction::Asc))
+            }
+            Node::Filter { source, .. } | Node::SemiJoin { left: source, .. } => {
+                self.sorted_by(source, i)?
+            }
+            // 二级索引按照列的值排序，前几列等于常量，之后的一列按照顺序读取
+            Node::IndexScan {
+                table_name,
+                index,
+                prefix,
+                ..
+            } => self
+                .txn
+                .must_get_table(table_name.clone())?
+                .indexes
+                .iter()
+                .find(|idx| idx.name == *index)
+                .is_some_and(|idx| idx.columns.iter().take(prefix.len() + 1).any(|&c| c == i)),
+            // 内连接和左外连接按照左边的 key 的顺序输出，右外连接没有匹配的行左边的 key 是 NULL
+            Node::MergeJoin {
+                left_key: Expression::Column(k),
+                kind: ast::JoinType::Inner | ast::JoinType::Left,
+                ..
+            } => *k == i,
+            _ => false,
+        })
+    }
+}
+
+// WHERE 中的 EXISTS 或者 IN 子查询改写成的半连接
+struct SubqueryJoin {
+    right: Node,
+    // 左边的值和子查询的值，分别引用左边和右边的行
+    keys: Option<(Expression, Expression)>,
+    // 引用左边的行和右边的行拼接起来的列
+    predicate: Option<Expression>,
+    anti: bool,
+    null_aware: bool,
+}
+
+// FROM 中的表和连接条件
+struct FromClause {
+    scope: Scope,
+    // FROM 中的每个表，以及在连接之后的行中的起始位置、列数和是否是列存的表
+    sources: Vec<(Node, usize, usize, bool)>,
+    // 每个连接的表的条件和类型
+    join_predicates: Vec<(Expression, ast::JoinType)>,
+}
+
+// 表达式中可以引用的列
+struct Scope {
+    // 表名和列名
+    columns: Vec<(Ident, Ident)>,
+}
+
+impl Scope {
+    fn from_table(table: &Table) -> Self {
+        Self {
+            columns: table
+                .columns
+                .iter()
+                .map(|c| (table.name.clone(), c.name.clone()))
+                .collect(),
+        }
+    }
+
+    // 获取 t.* 或者 * 对应的所有列
+    fn expand(&self, table: Option<&str>) -> Result<Vec<usize>> {
+        let indexes = (0..self.columns.len())
+            .filter(|i| table.is_none_or(|t| self.columns[*i].0 == t))
+            .collect::<Vec<_>>();
+        if indexes.is_empty() {
+            if let Some(t) = table {
+                return Err(Error::Internal(format!("table {} does not exist", t)));
+            }
+        }
+        Ok(indexes)
+    }
+
+    // 获取列名对应的下标
+    fn resolve(&self, table: Option<&str>, name: &str) -> Result<usize> {
+        let mut indexes = (0..self.columns.len()).filter(|i| {
+            let (t, n) = &self.columns[*i];
+            n == name && table.is_none_or(|table| t == table)
+        });
+        let field = match table {
+            Some(t) => format!("{}.{}", t, name),
+            None => name.to_string(),
+        };
+        match (indexes.next(), indexes.next()) {
+            (Some(i), None) => Ok(i),
+            (Some(_), Some(_)) => Err(Error::Internal(format!("column {} is ambiguous", field))),
+            (None, _) => Err(Error::Internal(format!("column {} does not exist", field))),
+        }
+    }
+
+    // 解析子查询中的表达式，列名优先在子查询的表中查找，找不到时引用外层查询的列
+    // 结果中外层查询的列在前，子查询的列排在外层查询的列之后
+    fn resolve_correlated(&self, outer: &Scope, expr: Expression) -> Result<Expression> {
+        Ok(match expr {
+            Expression::Field(table, name) => {
+                let inner = self
+                    .columns
+                    .iter()
+                    .any(|(t, n)| *n == *name && table.as_deref().is_none_or(|table| t == table));
+                Expression::Column(match inner {
+                    true => outer.columns.len() + self.resolve(table.as_deref(), &name)?,
+                    false => outer.resolve(table.as_deref(), &name)?,
+                })
+            }
+            Expression::Operation(op) => {
+                Expression::Operation(op.try_map(|e| self.resolve_correlated(outer, e))?)
+            }
+            Expression::Function(name, args) => Expression::Function(
+                name,
+                args.into_iter()
+                    .map(|e| self.resolve_correlated(outer, e))
+                    .collect::<Result<_>>()?,
+            ),
+            Expression::Aggregate(agg) => {
+                return Err(Error::Internal(format!(
+                    "aggregate function {} is not allowed in WHERE",
+                    agg
+                )))
+            }
+            Expression::Exists(_) | Expression::InSubquery(..) => {
+                return Err(Error::Internal(
+                    "nested subqueries are not supported".to_string(),
+                ))
+            }
+            expr => expr,
+        })
+    }
+
+    // 将表达式中的列名解析为列的下标
+    fn resolve_expression(&self, expr: Expression) -> Result<Expression> {
+        Ok(match expr {
+            Expression::Field(table, name) => {
+                Expression::Column(self.resolve(table.as_deref(), &name)?)
+            }
+            Expression::Operation(op) => {
+                Expression::Operation(op.try_map(|e| self.resolve_expression(e))?)
+            }
+            Expression::Function(name, args) => Expression::Function(
+                name,
+                args.into_iter()
+                    .map(|e| self.resolve_expression(e))
+                    .collect::<Result<_>>()?,
+            ),
+            Expression::Aggregate(agg) => Expression::Aggregate(ast::Aggregate {
+                name: agg.name,
+                distinct: agg.distinct,
+                args: agg
+                    .args
+                    .into_iter()
+                    .map(|e| self.resolve_expression(e))
+                    .collect::<Result<_>>()?,
+                order_by: agg
+                    .order_by
+                    .into_iter()
+                    .map(|(e, direction)| Ok((self.resolve_expression(e)?, direction)))
+                    .collect::<Result<_>>()?,
+            }),
+            Expression::Exists(_) | Expression::InSubquery(..) => {
+                return Err(Error::Internal(
+                    "subqueries are only allowed in WHERE conditions combined with AND".to_string(),
+                ))
+            }
+            expr => expr,
+        })
+    }
+
+    // 将 select 列表中的表达式改写为引用聚合节点的输出，前面是分组的值，后面是聚合函数的结果
+    // 聚合函数之外引用的列必须出现在 GROUP BY 中
+    fn resolve_aggregate(
+        &self,
+        expr: Expression,
+        group_by: &[Expression],
+        aggregates: &mut Vec<ast::Aggregate>,
+    ) -> Result<Expression> {
+        if let Some(i) = group_by.iter().position(|e| e == &expr) {
+            return Ok(Expression::Column(i));
+        }
+        Ok(match expr {
+            Expression::Aggregate(agg) => {
+                if agg.exprs().any(Expression::contains_aggregate) {
+                    return Err(Error::Internal(format!(
+                        "aggregate function calls cannot be nested in {}",
+                        agg
+                    )));
+                }
+                if !function::aggregate_arity(&agg.name, agg.distinct)
+                    .is_some_and(|arity| arity.contains(&agg.args.len()))
+                {
+                    return Err(Error::Internal(format!(
+                        "invalid number of arguments for {}",
+                        agg
+                    )));
+                }
+                // 相同的聚合函数只计算一次
+                let i = match aggregates.iter().position(|a| a == &agg) {
+                    Some(i) => i,
+                    None => {
+                        aggregates.push(agg);
+                        aggregates.len() - 1
+                    }
+                };
+                Expression::Column(group_by.len() + i)
+            }
+            Expression::Column(i) => {
+                return Err(Error::Internal(format!(
+                    "column {} must appear in GROUP BY or be used in an aggregate function",
+                    self.columns[i].1
+                )))
+            }
+            Expression::Operation(op) => Expression::Operation(
+                op.try_map(|e| self.resolve_aggregate(e, group_by, aggregates))?,
+            ),
+            Expression::Function(name, args) => Expression::Function(
+                name,
+                args.into_iter()
+                    .map(|e| self.resolve_aggregate(e, group_by, aggregates))
+                    .collect::<Result<_>>()?,
+            ),
+            expr => expr,
+        })
+    }
+}
+
+// 子查询的半连接，EXISTS 有等值条件时按照两边的列查找匹配的行
+fn build_semi_join(left: Node, join: SubqueryJoin, left_width: usize) -> Node {
+    let keys = join.keys.or_else(|| {
+        join.predicate
+            .as_ref()
+            .and_then(|p| equi_join_keys(p, left_width))
+            .map(|(l, r)| (Expression::Column(l), Expression::Column(r)))
+    });
+    Node::SemiJoin {
+        left: Box::new(left),
+        right: Box::new(join.right),
+        keys,
+        predicate: join.predicate,
+        anti: join.anti,
+        null_aware: join.null_aware,
+    }
+}
+
+// 子查询的条件是否引用外层查询的列，外层查询的列在前 width 列
+fn references_outer(expr: &Expression, width: usize) -> bool {
+    let mut columns = Vec::new();
+    expr.collect_columns(&mut columns);
+    columns.iter().any(|i| *i < width)
+}
+
+// 只引用子查询的列的表达式，改写成引用子查询的行中的列
+fn shift_columns(expr: Expression, width: usize) -> Expression {
+    match expr {
+        Expression::Column(i) => Expression::Column(i - width),
+        Expression::Operation(op) => Expression::Operation(
+            op.try_map(|e| Ok(shift_columns(e, width)))
+                .expect("shifting columns cannot fail"),
+        ),
+        Expression::Function(name, args) => Expression::Function(
+            name,
+            args.into_iter().map(|e| shift_columns(e, width)).collect(),
+        ),
+        expr => expr,
+    }
+}
+
+// IN 的子查询只能返回一列
+fn single_column<E>(mut outputs: Vec<E>) -> Result<E> {
+    match outputs.len() {
+        1 => Ok(outputs.remove(0)),
+        _ => Err(Error::Internal(
+            "subquery in IN must return exactly one column".to_string(),
+        )),
+    }
+}
+
+// 将 AND 连接的条件拆分成多个条件
+fn split_conjunction(expr: Expression) -> Vec<Expression> {
+    match expr {
+        Expression::Operation(ast::Operation::And(l, r)) => {
+            let mut exprs = split_conjunction(*l);
+            exprs.extend(split_conjunction(*r));
+            exprs
+        }
+        expr => vec![expr],
+    }
+}
+
+// 使用 AND 连接多个条件，没有条件时返回 None
+fn join_conjunction(exprs: Vec<Expression>) -> Option<Expression> {
+    exprs
+        .into_iter()
+        .reduce(|l, r| Expression::Operation(ast::Operation::And(Box::new(l), Box::new(r))))
+}
+
+// 从 AND 连接的条件中找出左边的列等于右边的列的条件，返回两边的列在各自的行中的下标
+fn equi_join_keys(predicate: &Expression, left_width: usize) -> Option<(usize, usize)> {
+    match predicate {
+        Expression::Operation(ast::Operation::Equal(l, r)) => match (&**l, &**r) {
+            (Expression::Column(l), Expression::Column(r))
+                if *l < left_width && *r >= left_width =>
+            {
+                Some((*l, *r - left_width))
+            }
+            (Expression::Column(l), Expression::Column(r))
+                if *r < left_width && *l >= left_width =>
+            {
+                Some((*r, *l - left_width))
+            }
+            _ => None,
+        },
+        Expression::Operation(ast::Operation::And(l, r)) => {
+            equi_join_keys(l, left_width).or_else(|| equi_join_keys(r, left_width))
+        }
+        _ => None,
+    }
+}
+
+// 列和常量比较的运算符，常量在左边时交换方向
+#[derive(Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    fn flip(self) -> Self {
+        match self {
+            Self::Equal => Self::Equal,
+            Self::Greater => Self::Less,
+            Self::GreaterOrEqual => Self::LessOrEqual,
+            Self::Less => Self::Greater,
+            Self::LessOrEqual => Self::GreaterOrEqual,
+        }
+    }
+}
+
+// 空间索引一次最多读取的网格列数
+const MAX_GRID_COLUMNS: i64 = 1024;
+
+// st_dwithin(列, 点, 距离) 和 st_within(列, xmin, ymin, xmax, ymax) 中满足条件的点所在的范围
+// 返回列的下标和范围 [xmin, ymin, xmax, ymax]，参数需要是常量
+fn spatial_area(expr: &Expression, offset: usize, width: usize) -> Option<(usize, [f64; 4])> {
+    let Expression::Function(name, args) = expr else {
+        return None;
+    };
+    let column = |e: &Expression| match e {
+        Expression::Column(i) if (offset..offset + width).contains(i) => Some(i - offset),
+        _ => None,
+    };
+    let constant = |e: &Expression| match e.is_constant() && !e.is_volatile() {
+        true => e.evaluate(&Vec::new()).ok(),
+        false => None,
+    };
+    let number = |e: &Expression| match constant(e)? {
+        Value::Integer(i) => Some(i as f64),
+        Value::Float(f) => Some(f),
+        _ => None,
+    };
+    match (name.as_str(), &args[..]) {
+        ("st_dwithin", [a, b, distance]) => {
+            let (column, point) = match (column(a), column(b)) {
+                (Some(c), None) => (c, b),
+                (None, Some(c)) => (c, a),
+                _ => return None,
+            };
+            let Value::Point(x, y) = constant(point)? else {
+                return None;
+            };
+            let d = number(distance)?;
+            Some((column, [x - d, y - d, x + d, y + d]))
+        }
+        ("st_within", [c, xmin, ymin, xmax, ymax]) => Some((
+            column(c)?,
+            [number(xmin)?, number(ymin)?, number(xmax)?, number(ymax)?],
+        )),
+        _ => None,
+    }
+}
+
+// 表的一列和常量比较的条件，返回列在表中的下标、运算符和常量表达式
+// offset 和 width 是表在连接之后的行中的起始位置和列数
+fn compare_constant(
+    expr: &Expression,
+    offset: usize,
+    width: usize,
+) -> Option<(usize, Comparison, &Expression)> {
+    let (l, r, op) = match expr {
+        Expression::Operation(ast::Operation::Equal(l, r)) => (l, r, Comparison::Equal),
+        Expression::Operation(ast::Operation::GreaterThan(l, r)) => (l, r, Comparison::Greater),
+        Expression::Operation(ast::Operation::GreaterThanOrEqual(l, r)) => {
+            (l, r, Comparison::GreaterOrEqual)
+        }
+        Expression::Operation(ast::Operation::LessThan(l, r)) => (l, r, Comparison::Less),
+        Expression::Operation(ast::Operation::LessThanOrEqual(l, r)) => {
+            (l, r, Comparison::LessOrEqual)
+        }
+        _ => return None,
+    };
+    let constant = |e: &Expression| e.is_constant() && !e.is_volatile();
+    match (&**l, &**r) {
+        (Expression::Column(i), e) if (offset..offset + width).contains(i) && constant(e) => {
+            Some((i - offset, op, e))
+        }
+        (e, Expression::Column(i)) if (offset..offset + width).contains(i) && constant(e) => {
+            Some((i - offset, op.flip(), e))
         }
+        _ => None,
     }
 }
@@ -1,17 +1,159 @@
 use serde::{Deserialize, Serialize};
 
-use super::types::{DataType, Value};
+use crate::error::{Error, Result};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+use super::{
+    ident::Ident,
+    parser::Parser,
+    types::{DataType, Row, Value},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Table {
-    pub name: String,
+    pub name: Ident,
     pub columns: Vec<Column>,
+    pub storage: StorageLayout,
+    pub compression: Compression,
+    pub ttl: Option<Ttl>,
+    // 外部表的数据保存在 CSV 文件中，不在存储引擎中
+    pub external: Option<ExternalTable>,
+    // 二级索引，第一列作为主键的隐式索引 primary 不在其中
+    pub indexes: Vec<Index>,
+}
+
+impl Table {
+    // 校验行的有效性，列数需要和表一致，每一列的类型和是否可以为 NULL 需要满足定义
+    pub fn validate_row(&self, row: &Row) -> Result<()> {
+        if row.len() != self.columns.len() {
+            return Err(Error::Internal(format!(
+                "table {} has {} columns, but row has {} values",
+                self.name,
+                self.columns.len(),
+                row.len()
+            )));
+        }
+        for (value, col) in row.iter().zip(&self.columns) {
+            col.validate_value(value)?;
+        }
+        Ok(())
+    }
+}
+
+// 二级索引，保存一列或者多列的值到主键的映射，按照列的值排序
+// 前几列等于常量的查询只需要读取匹配的行，之后的一列还可以按照范围读取
+// 列相同的索引只能有一个，第一列是 NULL 的行不在索引中
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    pub name: Ident,
+    // 索引的列的下标，按照索引中的顺序
+    pub columns: Vec<usize>,
+    // 列的 UNIQUE 约束创建的索引，写入时检查没有其他行的值相同
+    pub unique: bool,
+    // 空间索引的网格边长，POINT 列上的空间索引按照点所在的网格保存，不按照列的值排序
+    // None 是普通的索引
+    pub grid: Option<f64>,
+}
+
+impl Index {
+    // 点所在的网格的坐标，不是空间索引时返回 None
+    pub fn grid_cell(&self, x: f64, y: f64) -> Option<(i64, i64)> {
+        self.grid
+            .map(|size| ((x / size).floor() as i64, (y / size).floor() as i64))
+    }
+}
+
+// 行的过期时间，时间列中保存的是 unix 时间戳（秒）
+// 过期的行读取时会被过滤，CHECKPOINT 时删除
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Ttl {
+    // 时间列的下标
+    pub column: usize,
+    pub seconds: u64,
+}
+
+impl Ttl {
+    // 时间列的值是否已经过期，now 是当前的 unix 时间戳，时间列为 NULL 的行不会过期
+    pub fn is_expired(&self, value: &Value, now: i64) -> bool {
+        matches!(value, Value::Integer(t) if t.saturating_add(self.seconds as i64) <= now)
+    }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+// 外部表的数据文件，查询时读取并解析，表是只读的
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExternalTable {
+    // 文件路径，相对路径相对于进程的工作目录
+    pub location: String,
+    // 第一行是否是列名
+    pub header: bool,
+    // 字段的分隔符
+    pub delimiter: char,
+}
+
+// 表数据的存储方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StorageLayout {
+    // 按行存储，一行数据保存在一个 key 中
+    #[default]
+    Row,
+    // 按列存储，每一列的数据分别保存，只读取部分列时更快
+    Columnar,
+}
+
+// 表数据的压缩方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Compression {
+    // 不压缩，直接使用 bincode 编码
+    #[default]
+    None,
+    // 字符串使用字典编码，整数使用变长编码
+    Dictionary,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Column {
-    pub name: String,
+    pub name: Ident,
     pub datatype: DataType,
     pub nullable: bool,
     pub default: Option<Value>,
+    // 每次写入时计算的默认值，例如 now() 和 gen_uuid()，保存 DEFAULT 之后的表达式文本，这时 default 是 None
+    pub default_expr: Option<String>,
+}
+
+impl Column {
+    // 校验写入这一列的值，NOT NULL 的列不能是 NULL，其他值的类型需要和列的类型相同
+    pub fn validate_value(&self, value: &Value) -> Result<()> {
+        match value.datatype() {
+            None if self.nullable => Ok(()),
+            None => Err(Error::Internal(format!(
+                "column {} cannot be null",
+                self.name
+            ))),
+            Some(datatype) if !self.datatype.matches(value) => Err(Error::Internal(format!(
+                "column {} type mismatch, expected {} but got {}",
+                self.name, self.datatype, datatype
+            ))),
+            Some(_) => Ok(()),
+        }
+    }
+
+    // 写入时使用的默认值，没有默认值时返回 None，表达式每次调用时重新解析和计算
+    pub fn default_value(&self) -> Result<Option<Value>> {
+        match &self.default_expr {
+            Some(text) => {
+                let expr = Parser::new(text).parse_standalone_expression()?;
+                Ok(Some(Value::from_expression(expr)?.coerce(self.datatype)?))
+            }
+            None => Ok(self.default.clone()),
+        }
+    }
+}
+
+// 视图，保存定义视图的查询语句，查询时重新解析并展开成子查询
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    pub name: Ident,
+    // 视图的列名，创建时确定，查询结果的列数需要和列名的数量相同
+    pub columns: Vec<String>,
+    // AS 之后的查询语句，不包括最后的分号
+    pub query: String,
 }
@@ -0,0 +1,371 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+// 有效数字和小数位数的上限，38 位十进制数可以用 i128 表示
+pub const MAX_PRECISION: u8 = 38;
+
+// 十进制定点数，值是 mantissa / 10^scale，计算的结果是精确的
+// 小数位数不同的值不相等，例如 1.5 和 1.50，比较大小时使用 cmp_numeric
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u8,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, scale: u8) -> Result<Self> {
+        let decimal = Self { mantissa, scale };
+        if scale > MAX_PRECISION || decimal.precision() > MAX_PRECISION {
+            return Err(overflow());
+        }
+        Ok(decimal)
+    }
+
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    // 有效数字的位数，0 是 1 位，不小于小数位数，例如 0.05 是 DECIMAL(2, 2)
+    pub fn precision(&self) -> u8 {
+        let mut digits = 1;
+        let mut m = self.mantissa.unsigned_abs();
+        while m >= 10 {
+            m /= 10;
+            digits += 1;
+        }
+        digits.max(self.scale)
+    }
+
+    // 解析十进制的字符串，例如 -12.50，小数位数和字符串中的一致
+    pub fn parse(s: &str) -> Option<Self> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+        if int.is_empty() && frac.is_empty()
+            || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let digits = int.trim_start_matches('0').len() + frac.len();
+        if digits > MAX_PRECISION as usize || frac.len() > MAX_PRECISION as usize {
+            return None;
+        }
+        let mut mantissa = format!("{}{}", int, frac).parse::<i128>().unwrap_or(0);
+        if negative {
+            mantissa = -mantissa;
+        }
+        Self::new(mantissa, frac.len() as u8).ok()
+    }
+
+    // 浮点数按照最短的十进制表示转换，和 SQL 中写的常量一致，例如 0.1 转换成 0.1
+    pub fn from_f64(f: f64) -> Option<Self> {
+        f.is_finite().then(|| Self::parse(&f.to_string())).flatten()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    // 四舍五入成整数，超出 i64 的范围时报错
+    pub fn to_i64(&self) -> Result<i64> {
+        i64::try_from(self.rescale(0)?.mantissa).map_err(|_| overflow())
+    }
+
+    // 调整小数位数，减少位数时四舍五入
+    pub fn rescale(&self, scale: u8) -> Result<Self> {
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Ok(*self),
+            Ordering::Greater => {
+                let mantissa = pow10(scale - self.scale)
+                    .and_then(|p| self.mantissa.checked_mul(p))
+                    .ok_or_else(overflow)?;
+                Self::new(mantissa, scale)
+            }
+            Ordering::Less => {
+                let p = pow10(self.scale - scale).ok_or_else(overflow)?;
+                Self::new(round_div(self.mantissa, p), scale)
+            }
+        }
+    }
+
+    // 转换成 DECIMAL(precision, scale) 列的值，整数部分的位数超过限制时报错
+    pub fn fit(&self, precision: u8, scale: u8) -> Result<Self> {
+        let decimal = self.rescale(scale)?;
+        if decimal.precision() > precision {
+            return Err(Error::Internal(format!(
+                "value {} out of range for DECIMAL({}, {})",
+                self, precision, scale
+            )));
+        }
+        Ok(decimal)
+    }
+
+    // 四舍五入到小数点后 digits 位，digits 是负数时舍入到十位、百位等，结果的小数位数不超过 digits
+    pub fn round(&self, digits: i64) -> Result<Self> {
+        match digits {
+            d if d >= self.scale as i64 => Ok(*self),
+            d if d >= 0 => self.rescale(d as u8),
+            d => {
+                let mantissa = self.rescale(0)?.mantissa;
+                let unit = pow10(d.unsigned_abs().min(MAX_PRECISION as u64) as u8).unwrap_or(1);
+                Self::new(round_div(mantissa, unit) * unit, 0)
+            }
+        }
+    }
+
+    // 向下和向上取整，结果没有小数位
+    pub fn floor(&self) -> Result<Self> {
+        let p = pow10(self.scale).ok_or_else(overflow)?;
+        Self::new(self.mantissa.div_euclid(p), 0)
+    }
+
+    pub fn ceil(&self) -> Result<Self> {
+        let p = pow10(self.scale).ok_or_else(overflow)?;
+        Self::new(-(-self.mantissa).div_euclid(p), 0)
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            mantissa: self.mantissa.abs(),
+            scale: self.scale,
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        Self {
+            mantissa: -self.mantissa,
+            scale: self.scale,
+        }
+    }
+
+    // 加、减和取余的结果的小数位数是两个操作数中较大的
+    pub fn checked_add(self, other: Self) -> Result<Self> {
+        let (l, r, scale) = Self::align(self, other)?;
+        Self::new(l.checked_add(r).ok_or_else(overflow)?, scale)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self> {
+        let (l, r, scale) = Self::align(self, other)?;
+        Self::new(l.checked_sub(r).ok_or_else(overflow)?, scale)
+    }
+
+    pub fn checked_rem(self, other: Self) -> Result<Self> {
+        let (l, r, scale) = Self::align(self, other)?;
+        if r == 0 {
+            return Err(Error::Internal("division by zero".to_string()));
+        }
+        Self::new(l % r, scale)
+    }
+
+    // 乘法的小数位数是两个操作数的和，超过上限时四舍五入
+    pub fn checked_mul(self, other: Self) -> Result<Self> {
+        let mantissa = self
+            .mantissa
+            .checked_mul(other.mantissa)
+            .ok_or_else(overflow)?;
+        let scale = self.scale + other.scale;
+        match scale.checked_sub(MAX_PRECISION).filter(|d| *d > 0) {
+            Some(d) => Self::new(
+                round_div(mantissa, pow10(d).ok_or_else(overflow)?),
+                MAX_PRECISION,
+            ),
+            None => Self::new(mantissa, scale),
+        }
+    }
+
+    // 除法的结果比操作数多保留 DIVISION_SCALE 位小数，整数部分太长时减少小数位数
+    pub fn checked_div(self, other: Self) -> Result<Self> {
+        if other.mantissa == 0 {
+            return Err(Error::Internal("division by zero".to_string()));
+        }
+        let scale = (self.scale.max(other.scale) + DIVISION_SCALE).min(MAX_PRECISION);
+        // 结果是 self.mantissa * 10^(scale - self.scale + other.scale) / other.mantissa
+        for scale in (0..=scale).rev() {
+            let shift = scale as i32 - self.scale as i32 + other.scale as i32;
+            let (dividend, divisor) = match u8::try_from(shift) {
+                Ok(shift) => match pow10(shift).and_then(|p| self.mantissa.checked_mul(p)) {
+                    Some(dividend) => (dividend, other.mantissa),
+                    None => continue,
+                },
+                Err(_) => match pow10(shift.unsigned_abs() as u8)
+                    .and_then(|p| other.mantissa.checked_mul(p))
+                {
+                    Some(divisor) => (self.mantissa, divisor),
+                    None => return Self::new(0, scale),
+                },
+            };
+            if let Ok(decimal) = Self::new(round_div(dividend, divisor), scale) {
+                return Ok(decimal);
+            }
+        }
+        Err(overflow())
+    }
+
+    // 非负整数次方，使用平方的方法计算
+    pub fn checked_pow(self, mut exp: u32) -> Result<Self> {
+        let (mut base, mut result) = (self, Self::from(1));
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.checked_mul(base)?;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Ok(result)
+    }
+
+    // 按照数值比较大小，1.5 和 1.50 相等
+    pub fn cmp_numeric(&self, other: &Self) -> Ordering {
+        // 分别比较整数部分和小数部分，避免对齐小数位数时溢出
+        let split = |d: &Self| {
+            let p = pow10(d.scale).unwrap_or(1);
+            let scale = self.scale.max(other.scale);
+            let frac = (d.mantissa % p) * pow10(scale - d.scale).unwrap_or(1);
+            (d.mantissa / p, frac)
+        };
+        split(self).cmp(&split(other))
+    }
+
+    // 对齐两个数的小数位数
+    fn align(l: Self, r: Self) -> Result<(i128, i128, u8)> {
+        let scale = l.scale.max(r.scale);
+        Ok((
+            l.rescale(scale)?.mantissa,
+            r.rescale(scale)?.mantissa,
+            scale,
+        ))
+    }
+}
+
+// 除法结果额外保留的小数位数
+const DIVISION_SCALE: u8 = 6;
+
+impl From<i64> for Decimal {
+    fn from(i: i64) -> Self {
+        Self {
+            mantissa: i as i128,
+            scale: 0,
+        }
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, int, frac)
+    }
+}
+
+fn pow10(exp: u8) -> Option<i128> {
+    10i128.checked_pow(exp as u32)
+}
+
+// 整数除法，四舍五入，远离 0 的方向
+fn round_div(dividend: i128, divisor: i128) -> i128 {
+    let (q, r) = (dividend / divisor, dividend % divisor);
+    if r.unsigned_abs() >= divisor.unsigned_abs() - r.unsigned_abs() {
+        if (dividend < 0) == (divisor < 0) {
+            q + 1
+        } else {
+            q - 1
+        }
+    } else {
+        q
+    }
+}
+
+fn overflow() -> Error {
+    Error::Internal("decimal overflow".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::Decimal;
+    use crate::error::Result;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in ["0", "1.50", "-0.05", "123456.789", "0.000", "-12"] {
+            assert_eq!(d(s).to_string(), s);
+        }
+        assert_eq!(d("+.5").to_string(), "0.5");
+        assert_eq!(d("007.10").to_string(), "7.10");
+        assert_eq!(d("1.50").scale(), 2);
+        assert_eq!(d("-0.05").precision(), 2);
+        assert_eq!(d("-12.5").precision(), 3);
+        for s in ["", ".", "1e5", "1.2.3", "--1", "abc", &"9".repeat(39)] {
+            assert_eq!(Decimal::parse(s), None, "{}", s);
+        }
+        assert_eq!(Decimal::from_f64(0.1), Some(d("0.1")));
+        assert_eq!(Decimal::from_f64(f64::NAN), None);
+        assert_eq!(d("2.25").to_f64(), 2.25);
+    }
+
+    #[test]
+    fn test_arithmetic() -> Result<()> {
+        assert_eq!(d("0.1").checked_add(d("0.2"))?, d("0.3"));
+        assert_eq!(d("1.5").checked_sub(d("2.25"))?, d("-0.75"));
+        assert_eq!(d("1.5").checked_mul(d("-0.25"))?, d("-0.375"));
+        assert_eq!(d("10").checked_div(d("4"))?, d("2.500000"));
+        assert_eq!(d("1").checked_div(d("3"))?, d("0.333333"));
+        assert_eq!(d("2").checked_div(d("3"))?, d("0.666667"));
+        assert_eq!(d("-7.5").checked_rem(d("2"))?, d("-1.5"));
+        assert!(d("1").checked_div(d("0")).is_err());
+        assert!(d("1").checked_rem(d("0.00")).is_err());
+        let max = d(&"9".repeat(38));
+        assert!(max.checked_add(d("1")).is_err());
+        assert!(max.checked_mul(d("10")).is_err());
+        // 整数部分很长时除法减少小数位数
+        assert_eq!(max.checked_div(d("1"))?, max);
+
+        assert_eq!(d("1.005").rescale(2)?, d("1.01"));
+        assert_eq!(d("-1.005").rescale(2)?, d("-1.01"));
+        assert_eq!(d("1.5").rescale(3)?, d("1.500"));
+        assert_eq!(d("123.456").round(-1)?, d("120"));
+        assert_eq!(d("123.456").round(1)?, d("123.5"));
+        assert_eq!(d("-1.5").floor()?, d("-2"));
+        assert_eq!(d("-1.5").ceil()?, d("-1"));
+        assert_eq!(d("2.5").to_i64()?, 3);
+        assert_eq!(d("12.345").fit(5, 2)?, d("12.35"));
+        assert!(d("1234.5").fit(5, 2).is_err());
+        assert_eq!(d("0.05").fit(2, 2)?, d("0.05"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cmp_numeric() {
+        for (l, r, expected) in [
+            ("1.5", "1.50", Ordering::Equal),
+            ("-1.5", "-1.2", Ordering::Less),
+            ("-0.5", "0.2", Ordering::Less),
+            ("10", "9.99", Ordering::Greater),
+            ("0.001", "0", Ordering::Greater),
+        ] {
+            assert_eq!(d(l).cmp_numeric(&d(r)), expected, "{} {}", l, r);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::{fmt::Display, iter::Peekable, str::Chars};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+// 嵌套的层数限制，避免解析时栈溢出
+const MAX_DEPTH: usize = 128;
+
+// JSON 文档，写入时解析和校验，保存解析之后的结构，读取字段时不需要重新解析
+// 数字保存原始的文本，不会丢失精度；对象中重复的键只保留最后一个值，键的顺序和写入时一致
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Json {
+    Null,
+    Boolean(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: s.chars().peekable(),
+        };
+        let json = parser.parse_value(0)?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            Some(c) => Err(invalid(format!("unexpected character {:?}", c))),
+            None => Ok(json),
+        }
+    }
+
+    // 对象中键对应的值，不是对象时返回 None
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    // 数组中的元素，负数表示从末尾开始数，不是数组时返回 None
+    pub fn index(&self, i: i64) -> Option<&Json> {
+        match self {
+            Self::Array(items) => {
+                let i = match i < 0 {
+                    true => items.len().checked_sub(i.unsigned_abs() as usize)?,
+                    false => i as usize,
+                };
+                items.get(i)
+            }
+            _ => None,
+        }
+    }
+
+    // ->> 返回的文本，字符串不带引号，JSON 的 null 返回 None
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            Self::Null => None,
+            Self::String(s) => Some(s.clone()),
+            json => Some(json.to_string()),
+        }
+    }
+}
+
+// 输出紧凑的 JSON 文本
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Number(n) => f.write_str(n),
+            Self::String(s) => write_string(f, s),
+            Self::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+fn invalid(msg: String) -> Error {
+    Error::Internal(format!("invalid JSON: {}", msg))
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn parse_value(&mut self, depth: usize) -> Result<Json> {
+        if depth > MAX_DEPTH {
+            return Err(invalid(format!("nesting exceeds {} levels", MAX_DEPTH)));
+        }
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => self.parse_object(depth),
+            Some('[') => self.parse_array(depth),
+            Some('"') => Ok(Json::String(self.parse_string()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(_) => {
+                let word = self.next_while(|c| c.is_ascii_alphabetic());
+                match word.as_str() {
+                    "null" => Ok(Json::Null),
+                    "true" => Ok(Json::Boolean(true)),
+                    "false" => Ok(Json::Boolean(false)),
+                    _ => Err(invalid(format!(
+                        "unexpected token {:?}",
+                        match word.is_empty() {
+                            true => self.chars.next().map(String::from).unwrap_or_default(),
+                            false => word,
+                        }
+                    ))),
+                }
+            }
+            None => Err(invalid("unexpected end of input".to_string())),
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Json> {
+        self.expect('{')?;
+        let mut fields: Vec<(String, Json)> = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value(depth + 1)?;
+            match fields.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => *v = value,
+                None => fields.push((key, value)),
+            }
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(fields)),
+                c => return Err(unexpected(c)),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Json> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.parse_value(depth + 1)?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(items)),
+                c => return Err(unexpected(c)),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => s.push(self.parse_unicode_escape()?),
+                    c => return Err(unexpected(c)),
+                },
+                Some(c) if (c as u32) < 0x20 => return Err(unexpected(Some(c))),
+                Some(c) => s.push(c),
+                None => return Err(unexpected(None)),
+            }
+        }
+    }
+
+    // \uXXXX，UTF-16 的代理对由两个转义组成
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let high = self.parse_hex4()?;
+        let code = match high {
+            0xd800..=0xdbff => {
+                self.expect('\\')?;
+                self.expect('u')?;
+                match self.parse_hex4()? {
+                    low @ 0xdc00..=0xdfff => 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00),
+                    low => return Err(invalid(format!("invalid surrogate pair {:x}", low))),
+                }
+            }
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| invalid(format!("invalid unicode escape {:x}", code)))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let hex = (0..4).filter_map(|_| self.chars.next()).collect::<String>();
+        match hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => Ok(u32::from_str_radix(&hex, 16).unwrap_or_default()),
+            false => Err(invalid(format!("invalid unicode escape {}", hex))),
+        }
+    }
+
+    // -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn parse_number(&mut self) -> Result<Json> {
+        let mut n = String::new();
+        n.extend(self.chars.next_if_eq(&'-'));
+        let int = self.next_while(|c| c.is_ascii_digit());
+        if int.is_empty() || int.len() > 1 && int.starts_with('0') {
+            return Err(invalid(format!("invalid number {}{}", n, int)));
+        }
+        n.push_str(&int);
+        if let Some(dot) = self.chars.next_if_eq(&'.') {
+            let frac = self.next_while(|c| c.is_ascii_digit());
+            if frac.is_empty() {
+                return Err(invalid(format!("invalid number {}.", n)));
+            }
+            n.push(dot);
+            n.push_str(&frac);
+        }
+        if let Some(e) = self.chars.next_if(|c| matches!(c, 'e' | 'E')) {
+            n.push(e);
+            n.extend(self.chars.next_if(|c| matches!(c, '+' | '-')));
+            let exp = self.next_while(|c| c.is_ascii_digit());
+            if exp.is_empty() {
+                return Err(invalid(format!("invalid number {}", n)));
+            }
+            n.push_str(&exp);
+        }
+        Ok(Json::Number(n))
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.next_while(|c| matches!(c, ' ' | '\t' | '\n' | '\r'));
+    }
+
+    fn next_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.chars.next_if(|c| predicate(*c)) {
+            s.push(c);
+        }
+        s
+    }
+
+    fn expect(&mut self, expect: char) -> Result<()> {
+        match self.chars.next() {
+            Some(c) if c == expect => Ok(()),
+            c => Err(unexpected(c)),
+        }
+    }
+}
+
+fn unexpected(c: Option<char>) -> Error {
+    match c {
+        Some(c) => invalid(format!("unexpected character {:?}", c)),
+        None => invalid("unexpected end of input".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+    use crate::error::Result;
+
+    #[test]
+    fn test_parse_and_display() -> Result<()> {
+        for (input, expected) in [
+            ("null", "null"),
+            (" true ", "true"),
+            ("-12.50e+3", "-12.50e+3"),
+            ("12345678901234567890", "12345678901234567890"),
+            (r#""a\"b\\c\n\u00e9\ud83d\ude00""#, "\"a\\\"b\\\\c\\né😀\""),
+            ("[1, [], {}, \"x\"]", "[1,[],{},\"x\"]"),
+            (
+                r#"{ "a": 1, "b": {"c": [true, null]}, "a": 2 }"#,
+                r#"{"a":2,"b":{"c":[true,null]}}"#,
+            ),
+        ] {
+            assert_eq!(Json::parse(input)?.to_string(), expected, "{}", input);
+        }
+
+        for input in [
+            "",
+            "nul",
+            "[1,]",
+            "{\"a\" 1}",
+            "{a: 1}",
+            "01",
+            "1.",
+            "-",
+            "1e",
+            "\"abc",
+            "\"\\x\"",
+            "\"\\ud83d\"",
+            "[1] 2",
+            "'a'",
+            &"[".repeat(200),
+        ] {
+            assert!(Json::parse(input).is_err(), "{}", input);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_access() -> Result<()> {
+        let json = Json::parse(r#"{"a": [1, "x", null], "b": {"c": "d"}}"#)?;
+        let a = json.get("a").unwrap();
+        assert_eq!(a.index(0), Some(&Json::Number("1".to_string())));
+        assert_eq!(a.index(-2), Some(&Json::String("x".to_string())));
+        assert_eq!(a.index(3), None);
+        assert_eq!(a.index(-4), None);
+        assert_eq!(a.get("a"), None);
+        assert_eq!(json.index(0), None);
+        assert_eq!(a.index(1).and_then(Json::to_text), Some("x".to_string()));
+        assert_eq!(a.index(2).and_then(Json::to_text), None);
+        assert_eq!(
+            json.get("b").and_then(Json::to_text),
+            Some(r#"{"c":"d"}"#.to_string())
+        );
+        Ok(())
+    }
+}
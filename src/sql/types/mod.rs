@@ -1,13 +1,58 @@
+pub mod decimal;
+pub mod json;
+pub mod uuid;
+
+use std::{cmp::Ordering, fmt::Display};
+
 use serde::{Deserialize, Serialize};
 
-use super::parser::ast::{Consts, Expression};
+use crate::error::{Error, Result};
+
+use super::parser::ast::Expression;
+use decimal::Decimal;
+use json::Json;
+use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum DataType {
     Boolean,
     Integer,
     Float,
     String,
+    Point,
+    // DECIMAL(precision, scale)，precision 是有效数字的位数，scale 是小数位数
+    Decimal(u8, u8),
+    Uuid,
+    Json,
+}
+
+impl DataType {
+    // 值是否可以保存在这个类型的列中，DECIMAL 的值需要和列有相同的小数位数，并且没有超过有效数字的位数
+    pub fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::Decimal(precision, scale), Value::Decimal(d)) => {
+                d.scale() == *scale && d.precision() <= *precision
+            }
+            (dt, v) => v.datatype() == Some(*dt),
+        }
+    }
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Boolean => "BOOLEAN",
+            Self::Integer => "INTEGER",
+            Self::Float => "FLOAT",
+            Self::String => "STRING",
+            Self::Point => "POINT",
+            Self::Decimal(precision, scale) => {
+                return write!(f, "DECIMAL({}, {})", precision, scale)
+            }
+            Self::Uuid => "UUID",
+            Self::Json => "JSON",
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -17,17 +62,23 @@ pub enum Value {
     Integer(i64),
     Float(f64),
     String(String),
+    // 平面上的点 (x, y)
+    Point(f64, f64),
+    Decimal(Decimal),
+    Uuid(Uuid),
+    Json(Json),
 }
 
 impl Value {
-    pub fn from_expression(expr: Expression) -> Self {
-        match expr {
-            Expression::Consts(Consts::Null) => Self::Null,
-            Expression::Consts(Consts::Boolean(b)) => Self::Boolean(b),
-            Expression::Consts(Consts::Integer(i)) => Self::Integer(i),
-            Expression::Consts(Consts::Float(f)) => Self::Float(f),
-            Expression::Consts(Consts::String(s)) => Self::String(s),
+    // 常量表达式转换为 Value，表达式中不能引用列
+    pub fn from_expression(expr: Expression) -> Result<Self> {
+        if !expr.is_constant() {
+            return Err(Error::Internal(format!(
+                "expression {} is not a constant",
+                expr
+            )));
         }
+        expr.evaluate(&Vec::new())
     }
 
     pub fn datatype(&self) -> Option<DataType> {
@@ -37,8 +88,418 @@ impl Value {
             Self::Integer(_) => Some(DataType::Integer),
             Self::Float(_) => Some(DataType::Float),
             Self::String(_) => Some(DataType::String),
+            Self::Point(..) => Some(DataType::Point),
+            Self::Decimal(d) => Some(DataType::Decimal(d.precision(), d.scale())),
+            Self::Uuid(_) => Some(DataType::Uuid),
+            Self::Json(_) => Some(DataType::Json),
+        }
+    }
+
+    // 类型转换，CAST(expr AS type) 使用，NULL 转换成任何类型都是 NULL
+    // 可以转换的类型如下，隐式表示插入和更新时也会自动转换，x 表示不能转换
+    //
+    //   from \ to   BOOLEAN   INTEGER   FLOAT   STRING   POINT   DECIMAL   UUID   JSON
+    //   BOOLEAN     -         显式      x       显式     x       x         x      x
+    //   INTEGER     显式      -         隐式    显式     x       隐式      x      x
+    //   FLOAT       x         显式      -       显式     x       隐式      x      x
+    //   STRING      显式      显式      显式    -        显式    显式      隐式   隐式
+    //   POINT       x         x         x       显式     -       x         x      x
+    //   DECIMAL     x         显式      显式    显式     x       隐式      x      x
+    //   UUID        x         x         x       显式     x       x         -      x
+    //   JSON        x         x         x       显式     x       x         x      -
+    //
+    // 布尔值转换成整数是 1 和 0，整数不是 0 时转换成 true，浮点数和 DECIMAL 转换成整数时四舍五入
+    // 转换成 DECIMAL 时四舍五入到列的小数位数，整数部分的位数超过限制时报错
+    // 浮点数按照最短的十进制表示转换成 DECIMAL，和 SQL 中写的常量一致
+    // 字符串转换时忽略两端的空白，布尔值是 true 和 false，不区分大小写，点的格式是 POINT(x y)
+    // UUID 的格式是 8-4-4-4-12 个十六进制数字，也可以省略连字符，JSON 转换成字符串时输出紧凑的文本
+    pub fn cast(self, datatype: DataType) -> Result<Value> {
+        let invalid = |v: &Value| Error::Internal(format!("cannot cast {:?} to {}", v, datatype));
+        Ok(match (self, datatype) {
+            (Self::Null, _) => Self::Null,
+            (v, dt) if dt.matches(&v) => v,
+            (Self::Boolean(b), DataType::Integer) => Self::Integer(b as i64),
+            (Self::Integer(i), DataType::Boolean) => Self::Boolean(i != 0),
+            (Self::Integer(i), DataType::Float) => Self::Float(i as f64),
+            // i64::MAX 转换成浮点数之后是 2^63，超出了整数的范围
+            (Self::Float(f), DataType::Integer) => match f.round() {
+                f if f >= i64::MIN as f64 && f < i64::MAX as f64 => Self::Integer(f as i64),
+                _ => return Err(invalid(&Self::Float(f))),
+            },
+            (Self::Boolean(b), DataType::String) => Self::String(b.to_string()),
+            (Self::Integer(i), DataType::String) => Self::String(i.to_string()),
+            (Self::Float(f), DataType::String) => Self::String(f.to_string()),
+            (Self::Point(x, y), DataType::String) => Self::String(format!("POINT({} {})", x, y)),
+            (Self::Integer(i), DataType::Decimal(p, s)) => {
+                Self::Decimal(Decimal::from(i).fit(p, s)?)
+            }
+            (Self::Float(f), DataType::Decimal(p, s)) => match Decimal::from_f64(f) {
+                Some(d) => Self::Decimal(d.fit(p, s)?),
+                None => return Err(invalid(&Self::Float(f))),
+            },
+            (Self::Decimal(d), DataType::Decimal(p, s)) => Self::Decimal(d.fit(p, s)?),
+            (Self::Decimal(d), DataType::Integer) => Self::Integer(d.to_i64()?),
+            (Self::Decimal(d), DataType::Float) => Self::Float(d.to_f64()),
+            (Self::Decimal(d), DataType::String) => Self::String(d.to_string()),
+            (Self::Uuid(u), DataType::String) => Self::String(u.to_string()),
+            (Self::Json(j), DataType::String) => Self::String(j.to_string()),
+            // 不是合法的 JSON 时返回具体的解析错误
+            (Self::String(s), DataType::Json) => Self::Json(Json::parse(&s)?),
+            // DECIMAL 解析之后还需要调整小数位数
+            (Self::String(s), dt) => match parse_value(&s, dt) {
+                Some(v) => return v.cast(dt),
+                None => return Err(invalid(&Self::String(s))),
+            },
+            (v, _) => return Err(invalid(&v)),
+        })
+    }
+
+    // 隐式转换，插入和更新时把值转换成列的类型，包括整数转换成浮点数，数值转换成 DECIMAL，以及字符串转换成 UUID 和 JSON
+    // 其他情况返回原来的值，由校验行的时候报错
+    pub fn coerce(self, datatype: DataType) -> Result<Value> {
+        match (self, datatype) {
+            (Self::Integer(i), DataType::Float) => Ok(Self::Float(i as f64)),
+            (v @ (Self::Integer(_) | Self::Float(_) | Self::Decimal(_)), DataType::Decimal(..)) => {
+                v.cast(datatype)
+            }
+            (v @ Self::String(_), DataType::Uuid | DataType::Json) => v.cast(datatype),
+            (v, _) => Ok(v),
+        }
+    }
+
+    // 排序使用的全序，NULL 最小，整数和浮点数按照数值比较，不同类型之间按照类型的顺序比较
+    pub fn sort_cmp(&self, other: &Self) -> Ordering {
+        let rank = |v: &Self| match v {
+            Self::Null => 0,
+            Self::Boolean(_) => 1,
+            Self::Integer(_) | Self::Float(_) | Self::Decimal(_) => 2,
+            Self::String(_) => 3,
+            Self::Point(..) => 4,
+            Self::Uuid(_) => 5,
+            Self::Json(_) => 6,
+        };
+        match (self, other) {
+            (Self::Boolean(l), Self::Boolean(r)) => l.cmp(r),
+            (Self::Integer(l), Self::Integer(r)) => l.cmp(r),
+            (Self::Integer(l), Self::Float(r)) => (*l as f64).total_cmp(r),
+            (Self::Float(l), Self::Integer(r)) => l.total_cmp(&(*r as f64)),
+            (Self::Float(l), Self::Float(r)) => l.total_cmp(r),
+            (Self::Decimal(l), Self::Decimal(r)) => l.cmp_numeric(r),
+            (Self::Decimal(l), Self::Integer(r)) => l.cmp_numeric(&Decimal::from(*r)),
+            (Self::Integer(l), Self::Decimal(r)) => Decimal::from(*l).cmp_numeric(r),
+            (Self::Decimal(l), Self::Float(r)) => l.to_f64().total_cmp(r),
+            (Self::Float(l), Self::Decimal(r)) => l.total_cmp(&r.to_f64()),
+            (Self::String(l), Self::String(r)) => l.cmp(r),
+            (Self::Point(lx, ly), Self::Point(rx, ry)) => lx.total_cmp(rx).then(ly.total_cmp(ry)),
+            (Self::Uuid(l), Self::Uuid(r)) => l.cmp(r),
+            (Self::Json(l), Self::Json(r)) => l.to_string().cmp(&r.to_string()),
+            (l, r) => rank(l).cmp(&rank(r)),
         }
     }
 }
 
+// 字符串转换成其他类型的值，也用于解析 CSV 中的字段
+fn parse_value(s: &str, datatype: DataType) -> Option<Value> {
+    let s = s.trim();
+    Some(match datatype {
+        DataType::Boolean => match s.to_lowercase().as_str() {
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            _ => return None,
+        },
+        DataType::Integer => Value::Integer(s.parse().ok()?),
+        // inf 和 NaN 不是合法的浮点数
+        DataType::Float => Value::Float(s.parse().ok().filter(|f: &f64| f.is_finite())?),
+        DataType::String => Value::String(s.to_string()),
+        DataType::Decimal(..) => Value::Decimal(Decimal::parse(s)?),
+        DataType::Uuid => Value::Uuid(Uuid::parse(s)?),
+        DataType::Json => Value::Json(Json::parse(s).ok()?),
+        // 和 C 接口中的文本格式一致，POINT(x y)
+        DataType::Point => {
+            let inner = s
+                .get(..6)
+                .filter(|p| p.eq_ignore_ascii_case("point("))
+                .and_then(|_| s[6..].strip_suffix(')'))?;
+            let mut coords = inner.split_whitespace().map(|v| v.parse::<f64>());
+            match (coords.next(), coords.next(), coords.next()) {
+                (Some(Ok(x)), Some(Ok(y)), None) => Value::Point(x, y),
+                _ => return None,
+            }
+        }
+    })
+}
+
 pub type Row = Vec<Value>;
+
+// 将 Value 转换为 Rust 类型
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            v => Err(Error::Internal(format!("cannot convert {:?} to bool", v))),
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Integer(i) => Ok(i),
+            v => Err(Error::Internal(format!("cannot convert {:?} to i64", v))),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Float(f) => Ok(f),
+            Value::Integer(i) => Ok(i as f64),
+            v => Err(Error::Internal(format!("cannot convert {:?} to f64", v))),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::String(s) => Ok(s),
+            v => Err(Error::Internal(format!("cannot convert {:?} to String", v))),
+        }
+    }
+}
+
+// NULL 转换为 None
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            v => Ok(Some(T::from_value(v)?)),
+        }
+    }
+}
+
+// 将查询结果中的一行数据转换为用户自定义的类型
+pub trait FromRow: Sized {
+    fn from_row(columns: &[String], row: Row) -> Result<Self>;
+}
+
+// 按照列名获取一行数据中的值
+pub fn get_column<T: FromValue>(columns: &[String], row: &Row, name: &str) -> Result<T> {
+    let value = columns
+        .iter()
+        .position(|c| c == name)
+        .and_then(|i| row.get(i))
+        .ok_or(Error::Internal(format!("column {} does not exist", name)))?;
+    T::from_value(value.clone())
+}
+
+// 元组按照列的位置进行转换
+macro_rules! impl_from_row_for_tuple {
+    ($len:expr, $($t:ident),+) => {
+        impl<$($t: FromValue),+> FromRow for ($($t,)+) {
+            fn from_row(_columns: &[String], row: Row) -> Result<Self> {
+                if row.len() != $len {
+                    return Err(Error::Internal(format!(
+                        "expected {} columns, got {}",
+                        $len,
+                        row.len()
+                    )));
+                }
+                let mut iter = row.into_iter();
+                Ok(($($t::from_value(iter.next().unwrap_or(Value::Null))?,)+))
+            }
+        }
+    };
+}
+
+impl_from_row_for_tuple!(1, A);
+impl_from_row_for_tuple!(2, A, B);
+impl_from_row_for_tuple!(3, A, B, C);
+impl_from_row_for_tuple!(4, A, B, C, D);
+impl_from_row_for_tuple!(5, A, B, C, D, E);
+impl_from_row_for_tuple!(6, A, B, C, D, E, F);
+
+// 为结构体实现 FromRow，字段按照同名的列进行转换
+// struct User { id: i64, name: String }
+// impl_from_row!(User { id, name });
+#[macro_export]
+macro_rules! impl_from_row {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::sql::types::FromRow for $ty {
+            fn from_row(
+                columns: &[String],
+                row: $crate::sql::types::Row,
+            ) -> $crate::error::Result<Self> {
+                Ok(Self {
+                    $($field: $crate::sql::types::get_column(columns, &row, stringify!($field))?,)*
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataType, Decimal, FromRow, Json, Uuid, Value};
+    use crate::error::Result;
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: i64,
+        name: String,
+        score: Option<f64>,
+    }
+
+    crate::impl_from_row!(User { id, name, score });
+
+    #[test]
+    fn test_from_row() -> Result<()> {
+        let columns = vec!["name".to_string(), "id".to_string(), "score".to_string()];
+        let row = vec![
+            Value::String("a".to_string()),
+            Value::Integer(1),
+            Value::Null,
+        ];
+        assert_eq!(
+            User::from_row(&columns, row.clone())?,
+            User {
+                id: 1,
+                name: "a".to_string(),
+                score: None,
+            }
+        );
+        assert_eq!(
+            <(String, i64, Option<f64>)>::from_row(&columns, row.clone())?,
+            ("a".to_string(), 1, None)
+        );
+
+        // 类型不匹配
+        assert!(<(i64, i64, Value)>::from_row(&columns, row.clone()).is_err());
+        // 列数不匹配
+        assert!(<(String, i64)>::from_row(&columns, row).is_err());
+        // 列不存在
+        assert!(User::from_row(&columns[..2], vec![]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cast() -> Result<()> {
+        let s = |s: &str| Value::String(s.to_string());
+        let d = |s: &str| Value::Decimal(Decimal::parse(s).unwrap());
+        let u = |s: &str| Value::Uuid(Uuid::parse(s).unwrap());
+        let j = |s: &str| Value::Json(Json::parse(s).unwrap());
+        for (value, datatype, expected) in [
+            (Value::Null, DataType::Integer, Value::Null),
+            (Value::Boolean(true), DataType::Integer, Value::Integer(1)),
+            (Value::Integer(0), DataType::Boolean, Value::Boolean(false)),
+            (Value::Integer(-2), DataType::Boolean, Value::Boolean(true)),
+            (Value::Integer(3), DataType::Float, Value::Float(3.0)),
+            (Value::Float(2.5), DataType::Integer, Value::Integer(3)),
+            (Value::Float(-2.4), DataType::Integer, Value::Integer(-2)),
+            (Value::Boolean(false), DataType::String, s("false")),
+            (Value::Integer(-7), DataType::String, s("-7")),
+            (Value::Float(1.5), DataType::String, s("1.5")),
+            (
+                Value::Point(1.0, -2.5),
+                DataType::String,
+                s("POINT(1 -2.5)"),
+            ),
+            (s(" 42 "), DataType::Integer, Value::Integer(42)),
+            (s("1e3"), DataType::Float, Value::Float(1000.0)),
+            (s("True"), DataType::Boolean, Value::Boolean(true)),
+            (s("point(1 2)"), DataType::Point, Value::Point(1.0, 2.0)),
+            (s(" a "), DataType::String, s(" a ")),
+            (Value::Integer(3), DataType::Decimal(5, 2), d("3.00")),
+            (Value::Float(1.005), DataType::Decimal(5, 2), d("1.01")),
+            (d("-1.25"), DataType::Decimal(5, 1), d("-1.3")),
+            (d("1.5"), DataType::Decimal(5, 2), d("1.50")),
+            (d("2.5"), DataType::Integer, Value::Integer(3)),
+            (d("-0.25"), DataType::Float, Value::Float(-0.25)),
+            (d("1.50"), DataType::String, s("1.50")),
+            (s(" 1.5 "), DataType::Decimal(5, 2), d("1.50")),
+            (
+                s(" A0EEBC99-9C0B-4EF8-BB6D-6BB9BD380A11 "),
+                DataType::Uuid,
+                u("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"),
+            ),
+            (
+                u("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"),
+                DataType::String,
+                s("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"),
+            ),
+            (
+                s(r#" {"a": [1, 2.50]} "#),
+                DataType::Json,
+                j(r#"{"a":[1,2.50]}"#),
+            ),
+            (j(r#"{ "a" : "b" }"#), DataType::String, s(r#"{"a":"b"}"#)),
+        ] {
+            assert_eq!(value.clone().cast(datatype)?, expected, "{:?}", value);
+        }
+
+        for (value, datatype) in [
+            (s("1.5"), DataType::Integer),
+            (s("abc"), DataType::Float),
+            (s("nan"), DataType::Float),
+            (s("-inf"), DataType::Float),
+            (s("1e400"), DataType::Float),
+            (s("yes"), DataType::Boolean),
+            (Value::Float(1e19), DataType::Integer),
+            (Value::Float(f64::NAN), DataType::Integer),
+            (Value::Boolean(true), DataType::Float),
+            (Value::Float(1.0), DataType::Boolean),
+            (Value::Integer(1), DataType::Point),
+            (Value::Point(1.0, 2.0), DataType::Integer),
+            (Value::Integer(1000), DataType::Decimal(5, 2)),
+            (Value::Float(f64::INFINITY), DataType::Decimal(5, 2)),
+            (s("1.2.3"), DataType::Decimal(5, 2)),
+            (Value::Boolean(true), DataType::Decimal(5, 2)),
+            (d(&"9".repeat(38)), DataType::Integer),
+            (s("a0eebc99"), DataType::Uuid),
+            (Value::Integer(1), DataType::Uuid),
+            (s("{a: 1}"), DataType::Json),
+            (Value::Integer(1), DataType::Json),
+            (j("1"), DataType::Integer),
+            (u("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"), DataType::Integer),
+        ] {
+            assert!(value.clone().cast(datatype).is_err(), "{:?}", value);
+        }
+
+        // 隐式转换只包括整数转换成浮点数、数值转换成 DECIMAL 和字符串转换成 UUID、JSON，其他的值保持不变
+        assert_eq!(
+            Value::Integer(1).coerce(DataType::Float)?,
+            Value::Float(1.0)
+        );
+        assert_eq!(
+            Value::Float(1.0).coerce(DataType::Integer)?,
+            Value::Float(1.0)
+        );
+        assert_eq!(s("1").coerce(DataType::Integer)?, s("1"));
+        assert_eq!(Value::Null.coerce(DataType::Float)?, Value::Null);
+        assert_eq!(
+            Value::Float(0.125).coerce(DataType::Decimal(3, 2))?,
+            d("0.13")
+        );
+        assert_eq!(s("1").coerce(DataType::Decimal(3, 2))?, s("1"));
+        assert!(Value::Integer(10).coerce(DataType::Decimal(3, 2)).is_err());
+        assert_eq!(
+            s("a0eebc999c0b4ef8bb6d6bb9bd380a11").coerce(DataType::Uuid)?,
+            u("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11")
+        );
+        assert!(s("x").coerce(DataType::Uuid).is_err());
+        assert_eq!(s("[1, 2]").coerce(DataType::Json)?, j("[1,2]"));
+        assert!(s("[1, 2").coerce(DataType::Json).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+// 16 字节的 UUID，按照大端顺序保存，字节的顺序和 UUID 的大小顺序一致
+// 作为主键时编码成固定的 16 字节，比保存成 36 个字符的字符串更短
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Uuid([u8; 16]);
+
+impl Uuid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    // 随机生成第 4 版的 UUID，第 6 个字节的高 4 位是版本号，第 8 个字节的高 2 位是变体 10
+    pub fn new_v4() -> Self {
+        let mut bytes = fastrand::u128(..).to_be_bytes();
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Self(bytes)
+    }
+
+    // 解析 8-4-4-4-12 格式的字符串，也可以省略连字符，不区分大小写
+    pub fn parse(s: &str) -> Option<Self> {
+        let hex = match s.len() {
+            36 => {
+                let groups = s.split('-').collect::<Vec<_>>();
+                if groups.iter().map(|g| g.len()).ne([8, 4, 4, 4, 12]) {
+                    return None;
+                }
+                groups.concat()
+            }
+            32 => s.to_string(),
+            _ => return None,
+        };
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        u128::from_str_radix(&hex, 16)
+            .ok()
+            .map(|v| Self(v.to_be_bytes()))
+    }
+}
+
+impl Display for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Uuid;
+
+    #[test]
+    fn test_parse_and_display() {
+        let s = "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11";
+        let uuid = Uuid::parse(s).unwrap();
+        assert_eq!(uuid.as_bytes()[..4], [0xa0, 0xee, 0xbc, 0x99]);
+        assert_eq!(uuid.to_string(), s);
+        assert_eq!(Uuid::parse("A0EEBC999C0B4EF8BB6D6BB9BD380A11"), Some(uuid));
+        for s in [
+            "",
+            "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a1",
+            "a0eebc999-c0b-4ef8-bb6d-6bb9bd380a11",
+            "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a1g",
+            "+0eebc999c0b4ef8bb6d6bb9bd380a11",
+            "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11-",
+        ] {
+            assert_eq!(Uuid::parse(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_new_v4() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert_ne!(a, b);
+        for uuid in [a, b] {
+            let s = uuid.to_string();
+            assert_eq!(&s[14..15], "4");
+            assert!("89ab".contains(&s[19..20]), "{}", s);
+            assert_eq!(Uuid::parse(&s), Some(uuid));
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        // 字节的顺序和字符串的顺序一致
+        let mut uuids = [
+            "ffffffff-0000-0000-0000-000000000000",
+            "00000000-0000-0000-0000-000000000001",
+            "00000001-0000-0000-0000-000000000000",
+            "00000000-0001-0000-0000-000000000000",
+        ]
+        .map(|s| Uuid::parse(s).unwrap());
+        uuids.sort();
+        assert_eq!(
+            uuids.map(|u| u.to_string()),
+            [
+                "00000000-0000-0000-0000-000000000001",
+                "00000000-0001-0000-0000-000000000000",
+                "00000001-0000-0000-0000-000000000000",
+                "ffffffff-0000-0000-0000-000000000000",
+            ]
+        );
+    }
+}
@@ -2,10 +2,11 @@ use std::{
     collections::{btree_map, BTreeMap},
     fs::{File, OpenOptions},
     io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     vec,
 };
 
+use bytes::Bytes;
 use fs4::FileExt;
 
 use crate::error::Result;
@@ -22,11 +23,28 @@ pub struct DiskEngine {
 impl DiskEngine {
     pub fn new(file_path: PathBuf) -> Result<Self> {
         let mut log = Log::new(file_path)?;
-        // 从 log 中去恢复的 keydir
-        let keydir = log.build_keydir()?;
+        // 上次正常关闭时保存了 keydir，可以跳过扫描日志，否则从 log 中去恢复 keydir
+        let keydir = match log.load_hint()? {
+            Some(keydir) => keydir,
+            None => log.build_keydir()?,
+        };
         Ok(Self { keydir, log })
     }
 
+    // 正常关闭时保存 keydir 的文件路径
+    pub fn hint_path(file_path: &Path) -> PathBuf {
+        file_path.with_extension("hint")
+    }
+
+    // 关闭存储引擎，持久化日志并保存 keydir，标记为正常关闭
+    fn close(&mut self) -> Result<()> {
+        self.log.file.sync_all()?;
+        let size = self.log.file.metadata()?.len();
+        let hint = bincode::serialize(&(size, &self.keydir))?;
+        std::fs::write(Self::hint_path(&self.log.file_path), hint)?;
+        Ok(())
+    }
+
     pub fn new_compact(file_path: PathBuf) -> Result<Self> {
         let mut eng = Self::new(file_path)?;
         eng.compact()?;
@@ -45,7 +63,7 @@ impl DiskEngine {
         for (key, (offset, val_size)) in self.keydir.iter() {
             // 读取 value
             let value = self.log.read_value(*offset, *val_size)?;
-            let (new_offset, new_size) = new_log.write_entry(key, Some(&value))?;
+            let (new_offset, new_size) = new_log.write_entry(key, Some(&value[..]))?;
 
             new_keydir.insert(
                 key.clone(),
@@ -53,7 +71,8 @@ impl DiskEngine {
             );
         }
 
-        // 将临时文件更改为正式文件
+        // 将临时文件更改为正式文件，之前需要保证数据已经写入磁盘
+        new_log.file.sync_all()?;
         std::fs::rename(&new_log.file_path, &self.log.file_path)?;
 
         new_log.file_path = self.log.file_path.clone();
@@ -67,9 +86,14 @@ impl DiskEngine {
 impl super::engine::Engine for DiskEngine {
     type EngineIterator<'a> = DiskEngineIterator<'a>;
 
-    fn set(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+    fn open(path: PathBuf) -> Result<Self> {
+        Self::new(path)
+    }
+
+    fn set(&mut self, key: Vec<u8>, value: impl Into<Bytes>) -> Result<()> {
+        let value = value.into();
         // 先写日志
-        let (offset, size) = self.log.write_entry(&key, Some(&value))?;
+        let (offset, size) = self.log.write_entry(&key, Some(&value[..]))?;
         // 更新内存索引
         // 100----------------|-----150
         //                   130
@@ -80,7 +104,25 @@ impl super::engine::Engine for DiskEngine {
         Ok(())
     }
 
-    fn get(&mut self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
+    // 所有的数据在一次写入中追加到日志末尾
+    fn write_batch(&mut self, batch: Vec<(Vec<u8>, Option<Bytes>)>) -> Result<()> {
+        let positions = self.log.write_entries(&batch)?;
+        for ((key, value), (offset, size)) in batch.into_iter().zip(positions) {
+            match value {
+                Some(value) => {
+                    let val_size = value.len() as u32;
+                    self.keydir
+                        .insert(key, (offset + size as u64 - val_size as u64, val_size));
+                }
+                None => {
+                    self.keydir.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, key: Vec<u8>) -> Result<Option<Bytes>> {
         match self.keydir.get(&key) {
             Some((offset, val_size)) => {
                 let val = self.log.read_value(*offset, *val_size)?;
@@ -90,12 +132,37 @@ impl super::engine::Engine for DiskEngine {
         }
     }
 
+    // 按照数据在日志中的位置顺序读取，减少随机读
+    fn get_many(&mut self, keys: Vec<Vec<u8>>) -> Result<Vec<Option<Bytes>>> {
+        let mut positions = keys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, key)| self.keydir.get(key).map(|&pos| (pos, i)))
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        let mut values = vec![None; keys.len()];
+        for ((offset, val_size), i) in positions {
+            values[i] = Some(self.log.read_value(offset, val_size)?);
+        }
+        Ok(values)
+    }
+
     fn delete(&mut self, key: Vec<u8>) -> Result<()> {
         self.log.write_entry(&key, None)?;
         self.keydir.remove(&key);
         Ok(())
     }
 
+    fn flush(&mut self) -> Result<()> {
+        self.log.file.sync_all()?;
+        Ok(())
+    }
+
+    // 重写日志，日志中只保留当前有效的数据
+    fn checkpoint(&mut self) -> Result<()> {
+        self.compact()
+    }
+
     fn scan(&mut self, range: impl std::ops::RangeBounds<Vec<u8>>) -> Self::EngineIterator<'_> {
         DiskEngineIterator {
             inner: self.keydir.range(range),
@@ -104,6 +171,13 @@ impl super::engine::Engine for DiskEngine {
     }
 }
 
+impl Drop for DiskEngine {
+    fn drop(&mut self) {
+        // 关闭失败时下次打开会重新扫描日志，不影响数据的正确性
+        let _ = self.close();
+    }
+}
+
 pub struct DiskEngineIterator<'a> {
     inner: btree_map::Range<'a, Vec<u8>, (u64, u32)>,
     log: &'a mut Log,
@@ -120,7 +194,7 @@ impl<'a> DiskEngineIterator<'a> {
 impl<'a> super::engine::EngineIterator for DiskEngineIterator<'a> {}
 
 impl<'a> Iterator for DiskEngineIterator<'a> {
-    type Item = Result<(Vec<u8>, Vec<u8>)>;
+    type Item = Result<(Vec<u8>, Bytes)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next().map(|item| self.map(item))
@@ -143,13 +217,14 @@ impl Log {
         // 如果目录不存在的话则创建
         if let Some(dir) = file_path.parent() {
             if !dir.exists() {
-                std::fs::create_dir_all(&dir)?;
+                std::fs::create_dir_all(dir)?;
             }
         }
 
         // 打开文件
         let file = OpenOptions::new()
             .create(true)
+            .truncate(false)
             .read(true)
             .write(true)
             .open(&file_path)?;
@@ -160,6 +235,23 @@ impl Log {
         Ok(Self { file, file_path })
     }
 
+    // 读取正常关闭时保存的 keydir，读取之后删除文件，之后的写入会让它失效
+    // 日志的大小和保存时不一致，说明关闭之后日志被修改过，需要重新扫描
+    fn load_hint(&mut self) -> Result<Option<KeyDir>> {
+        let hint_path = DiskEngine::hint_path(&self.file_path);
+        let hint = match std::fs::read(&hint_path) {
+            Ok(hint) => hint,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        std::fs::remove_file(&hint_path)?;
+        let file_size = self.file.metadata()?.len();
+        Ok(match bincode::deserialize::<(u64, KeyDir)>(&hint) {
+            Ok((size, keydir)) if size == file_size => Some(keydir),
+            _ => None,
+        })
+    }
+
     // 遍历数据文件，构建内存索引
     fn build_keydir(&mut self) -> Result<KeyDir> {
         let mut keydir = KeyDir::new();
@@ -172,7 +264,14 @@ impl Log {
                 break;
             }
 
-            let (key, val_size) = Self::read_entry(&mut buf_reader, offset)?;
+            // 掉电时最后一个写入可能只有一部分落盘，丢弃这个不完整的写入
+            let (key, val_size) = match Self::read_entry(&mut buf_reader, offset, file_size)? {
+                Some(entry) => entry,
+                None => {
+                    self.file.set_len(offset)?;
+                    break;
+                }
+            };
             let key_size = key.len() as u32;
             if val_size == -1 {
                 keydir.remove(&key);
@@ -195,7 +294,7 @@ impl Log {
     // +-------------+-------------+----------------+----------------+
     // | key len(4)    val len(4)     key(varint)       val(varint)  |
     // +-------------+-------------+----------------+----------------+
-    fn write_entry(&mut self, key: &Vec<u8>, value: Option<&Vec<u8>>) -> Result<(u64, u32)> {
+    fn write_entry(&mut self, key: &[u8], value: Option<&[u8]>) -> Result<(u64, u32)> {
         // 首先将文件偏移移动到文件末尾
         let offset = self.file.seek(SeekFrom::End(0))?;
         let key_size = key.len() as u32;
@@ -206,7 +305,7 @@ impl Log {
         let mut writer = BufWriter::with_capacity(total_size as usize, &self.file);
         writer.write_all(&key_size.to_be_bytes())?;
         writer.write_all(&value.map_or(-1, |v| v.len() as i32).to_be_bytes())?;
-        writer.write_all(&key)?;
+        writer.write_all(key)?;
         if let Some(v) = value {
             writer.write_all(v)?;
         }
@@ -215,14 +314,44 @@ impl Log {
         Ok((offset, total_size))
     }
 
-    fn read_value(&mut self, offset: u64, val_size: u32) -> Result<Vec<u8>> {
+    // 连续写入多条日志，只需要一次 seek 和一次刷新，返回每一条日志的位置和大小
+    fn write_entries(&mut self, entries: &[(Vec<u8>, Option<Bytes>)]) -> Result<Vec<(u64, u32)>> {
+        let mut offset = self.file.seek(SeekFrom::End(0))?;
+        let mut positions = Vec::with_capacity(entries.len());
+        let mut writer = BufWriter::new(&self.file);
+        for (key, value) in entries {
+            let key_size = key.len() as u32;
+            let total_size =
+                key_size + value.as_ref().map_or(0, |v| v.len() as u32) + LOG_HEADER_SIZE;
+            writer.write_all(&key_size.to_be_bytes())?;
+            writer.write_all(&value.as_ref().map_or(-1, |v| v.len() as i32).to_be_bytes())?;
+            writer.write_all(key)?;
+            if let Some(v) = value {
+                writer.write_all(v)?;
+            }
+            positions.push((offset, total_size));
+            offset += total_size as u64;
+        }
+        writer.flush()?;
+        Ok(positions)
+    }
+
+    fn read_value(&mut self, offset: u64, val_size: u32) -> Result<Bytes> {
         self.file.seek(SeekFrom::Start(offset))?;
         let mut buf = vec![0; val_size as usize];
         self.file.read_exact(&mut buf)?;
-        Ok(buf)
+        Ok(buf.into())
     }
 
-    fn read_entry(buf_reader: &mut BufReader<&File>, offset: u64) -> Result<(Vec<u8>, i32)> {
+    // 读取 offset 处的 key 和 value size，日志在这个写入的中间结束时返回 None
+    fn read_entry(
+        buf_reader: &mut BufReader<&File>,
+        offset: u64,
+        file_size: u64,
+    ) -> Result<Option<(Vec<u8>, i32)>> {
+        if offset + LOG_HEADER_SIZE as u64 > file_size {
+            return Ok(None);
+        }
         buf_reader.seek(SeekFrom::Start(offset))?;
         let mut len_buf = [0; 4];
 
@@ -234,11 +363,16 @@ impl Log {
         buf_reader.read_exact(&mut len_buf)?;
         let val_size = i32::from_be_bytes(len_buf);
 
+        let entry_size = LOG_HEADER_SIZE as u64 + key_size as u64 + val_size.max(0) as u64;
+        if offset + entry_size > file_size {
+            return Ok(None);
+        }
+
         // 读取 key
         let mut key = vec![0; key_size as usize];
         buf_reader.read_exact(&mut key)?;
 
-        Ok((key, val_size))
+        Ok(Some((key, val_size)))
     }
 }
 
@@ -248,6 +382,7 @@ mod tets {
         error::Result,
         storage::{disk::DiskEngine, engine::Engine},
     };
+    use bytes::Bytes;
     use std::path::PathBuf;
 
     #[test]
@@ -272,9 +407,9 @@ mod tets {
         assert_eq!(
             v,
             vec![
-                (b"aa".to_vec(), b"value3".to_vec()),
-                (b"bb".to_vec(), b"value5".to_vec()),
-                (b"key3".to_vec(), b"value".to_vec()),
+                (b"aa".to_vec(), Bytes::from_static(b"value3")),
+                (b"bb".to_vec(), Bytes::from_static(b"value5")),
+                (b"key3".to_vec(), Bytes::from_static(b"value")),
             ]
         );
         drop(eng);
@@ -285,9 +420,9 @@ mod tets {
         assert_eq!(
             v2,
             vec![
-                (b"aa".to_vec(), b"value3".to_vec()),
-                (b"bb".to_vec(), b"value5".to_vec()),
-                (b"key3".to_vec(), b"value".to_vec()),
+                (b"aa".to_vec(), Bytes::from_static(b"value3")),
+                (b"bb".to_vec(), Bytes::from_static(b"value5")),
+                (b"key3".to_vec(), Bytes::from_static(b"value")),
             ]
         );
         drop(eng2);
@@ -296,4 +431,115 @@ mod tets {
 
         Ok(())
     }
+
+    #[test]
+    fn test_disk_engine_checkpoint() -> Result<()> {
+        let path = tempfile::tempdir()?.keep().join("sqldb-log");
+        let mut eng = DiskEngine::new(path.clone())?;
+        for i in 0..10 {
+            eng.set(b"key1".to_vec(), format!("value{}", i).into_bytes())?;
+        }
+        eng.set(b"key2".to_vec(), b"value".to_vec())?;
+        eng.delete(b"key2".to_vec())?;
+        let size = std::fs::metadata(&path)?.len();
+
+        // 检查点之后日志中只剩下有效的数据
+        eng.checkpoint()?;
+        assert!(std::fs::metadata(&path)?.len() < size);
+        eng.set(b"key3".to_vec(), b"value".to_vec())?;
+        drop(eng);
+
+        let mut eng = DiskEngine::new(path.clone())?;
+        assert_eq!(
+            eng.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![
+                (b"key1".to_vec(), Bytes::from_static(b"value9")),
+                (b"key3".to_vec(), Bytes::from_static(b"value")),
+            ]
+        );
+        drop(eng);
+        std::fs::remove_dir_all(path.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_disk_engine_hint() -> Result<()> {
+        let path = tempfile::tempdir()?.keep().join("sqldb-log");
+        let hint_path = DiskEngine::hint_path(&path);
+        let mut eng = DiskEngine::new(path.clone())?;
+        eng.set(b"key1".to_vec(), b"value1".to_vec())?;
+        eng.set(b"key2".to_vec(), b"value2".to_vec())?;
+        eng.delete(b"key1".to_vec())?;
+        drop(eng);
+
+        // 正常关闭时保存了 keydir，打开时使用之后删除
+        assert!(hint_path.exists());
+        let stale = std::fs::read(&hint_path)?;
+        let mut eng = DiskEngine::new(path.clone())?;
+        assert!(!hint_path.exists());
+        assert_eq!(
+            eng.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![(b"key2".to_vec(), Bytes::from_static(b"value2"))]
+        );
+        eng.set(b"key3".to_vec(), b"value3".to_vec())?;
+        drop(eng);
+
+        // 和日志不一致的 keydir 会被忽略，重新扫描日志
+        std::fs::write(&hint_path, stale)?;
+        let mut eng = DiskEngine::new(path.clone())?;
+        assert_eq!(
+            eng.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![
+                (b"key2".to_vec(), Bytes::from_static(b"value2")),
+                (b"key3".to_vec(), Bytes::from_static(b"value3")),
+            ]
+        );
+        drop(eng);
+        std::fs::remove_dir_all(path.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_disk_engine_torn_write() -> Result<()> {
+        let dir = tempfile::tempdir()?.keep();
+        let path = dir.join("sqldb-log");
+        let mut eng = DiskEngine::new(path.clone())?;
+        // 每次写入之后的数据
+        let mut states = vec![vec![]];
+        for (key, value) in [
+            (b"key1", Some(b"value1")),
+            (b"key2", Some(b"value2")),
+            (b"key1", None),
+            (b"key2", Some(b"value3")),
+        ] {
+            match value {
+                Some(value) => eng.set(key.to_vec(), value.to_vec())?,
+                None => eng.delete(key.to_vec())?,
+            }
+            states.push(eng.scan(..).collect::<Result<Vec<_>>>()?);
+        }
+        drop(eng);
+        let data = std::fs::read(&path)?;
+
+        // 掉电时最后一个写入可能只有一部分落盘，打开时丢弃不完整的写入
+        for len in 0..=data.len() {
+            let path = dir.join(format!("sqldb-log-{}", len));
+            std::fs::write(&path, &data[..len])?;
+            let mut eng = DiskEngine::new(path.clone())?;
+            let state = eng.scan(..).collect::<Result<Vec<_>>>()?;
+            assert!(states.contains(&state), "truncated to {}: {:?}", len, state);
+
+            // 之后的写入不受不完整写入的影响
+            eng.set(b"key3".to_vec(), b"value".to_vec())?;
+            drop(eng);
+            let mut eng = DiskEngine::new(path.clone())?;
+            assert_eq!(
+                eng.get(b"key3".to_vec())?,
+                Some(Bytes::from_static(b"value"))
+            );
+            assert_eq!(eng.scan(..).count(), state.len() + 1);
+        }
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
 }
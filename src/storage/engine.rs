@@ -1,6 +1,11 @@
-use std::ops::{Bound, RangeBounds};
+use std::{
+    ops::{Bound, RangeBounds},
+    path::PathBuf,
+};
 
-use crate::error::Result;
+use bytes::Bytes;
+
+use crate::error::{Error, Result};
 
 // 抽象存储引擎接口定义，接入不同的存储引擎，目前支持内存和简单的磁盘 KV 存储
 pub trait Engine {
@@ -8,34 +13,82 @@ pub trait Engine {
     where
         Self: 'a;
 
-    // 设置 key/value
-    fn set(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()>;
+    // 打开 path 中保存的数据，ATTACH DATABASE 时使用，不支持文件的存储引擎返回错误
+    fn open(path: PathBuf) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(Error::Internal(format!(
+            "storage engine cannot open {}",
+            path.display()
+        )))
+    }
+
+    // 设置 key/value，value 使用可以低成本 clone 的 Bytes，避免读写时复制数据
+    fn set(&mut self, key: Vec<u8>, value: impl Into<Bytes>) -> Result<()>;
 
     // 获取 key 对应的数据
-    fn get(&mut self, key: Vec<u8>) -> Result<Option<Vec<u8>>>;
+    fn get(&mut self, key: Vec<u8>) -> Result<Option<Bytes>>;
+
+    // 按顺序获取一批 key 对应的数据，返回的结果和 keys 一一对应
+    fn get_many(&mut self, keys: Vec<Vec<u8>>) -> Result<Vec<Option<Bytes>>> {
+        keys.into_iter().map(|key| self.get(key)).collect()
+    }
 
     // 删除 key 对应的数据，如果 key 不存在的话则忽略
     fn delete(&mut self, key: Vec<u8>) -> Result<()>;
 
+    // 按顺序写入一批数据，value 为 None 表示删除
+    // 存储引擎可以合并成一次写入，崩溃时和逐个写入一样，只有前面的一部分会生效
+    fn write_batch(&mut self, batch: Vec<(Vec<u8>, Option<Bytes>)>) -> Result<()> {
+        for (key, value) in batch {
+            match value {
+                Some(value) => self.set(key, value)?,
+                None => self.delete(key)?,
+            }
+        }
+        Ok(())
+    }
+
+    // 将写入的数据持久化
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    // 检查点，将数据持久化并清理已经失效的日志，缩短恢复时间
+    fn checkpoint(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     // 扫描
     fn scan(&mut self, range: impl RangeBounds<Vec<u8>>) -> Self::EngineIterator<'_>;
 
     // 前缀扫描
     fn scan_prefix(&mut self, prefix: Vec<u8>) -> Self::EngineIterator<'_> {
-        // start: aaaa
-        // end:   aaab
-        let start = Bound::Included(prefix.clone());
-        let mut bound_prefix = prefix.clone();
-        if let Some(last) = bound_prefix.iter_mut().last() {
-            *last += 1;
+        let end = match prefix_end(&prefix) {
+            Some(end) => Bound::Excluded(end),
+            None => Bound::Unbounded,
         };
-        let end = Bound::Excluded(bound_prefix);
+        self.scan((Bound::Included(prefix), end))
+    }
+}
 
-        self.scan((start, end))
+// 以 prefix 开头的 key 的上界，不包括上界本身
+// start: aaaa
+// end:   aaab
+// 末尾为 0xff 的字节不能加一，需要去掉之后再对前一个字节加一
+// 例如 aa 0xff 的结束位置是 ab，全部为 0xff 时没有上界，返回 None
+pub fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while end.last() == Some(&0xff) {
+        end.pop();
     }
+    let last = end.last_mut()?;
+    *last += 1;
+    Some(end)
 }
 
-pub trait EngineIterator: DoubleEndedIterator<Item = Result<(Vec<u8>, Vec<u8>)>> {}
+pub trait EngineIterator: DoubleEndedIterator<Item = Result<(Vec<u8>, Bytes)>> {}
 
 #[cfg(test)]
 mod tests {
@@ -44,6 +97,7 @@ mod tests {
         error::Result,
         storage::{disk::DiskEngine, memory::MemoryEngine},
     };
+    use bytes::Bytes;
     use std::{ops::Bound, path::PathBuf};
 
     // 测试点读的情况
@@ -53,11 +107,17 @@ mod tests {
 
         // 获取一个存在的 key
         eng.set(b"aa".to_vec(), vec![1, 2, 3, 4])?;
-        assert_eq!(eng.get(b"aa".to_vec())?, Some(vec![1, 2, 3, 4]));
+        assert_eq!(
+            eng.get(b"aa".to_vec())?,
+            Some(Bytes::from(vec![1, 2, 3, 4]))
+        );
 
         // 重复 put，将会覆盖前一个值
         eng.set(b"aa".to_vec(), vec![5, 6, 7, 8])?;
-        assert_eq!(eng.get(b"aa".to_vec())?, Some(vec![5, 6, 7, 8]));
+        assert_eq!(
+            eng.get(b"aa".to_vec())?,
+            Some(Bytes::from(vec![5, 6, 7, 8]))
+        );
 
         // 删除之后再读取
         eng.delete(b"aa".to_vec())?;
@@ -66,10 +126,13 @@ mod tests {
         // key、value 为空的情况
         assert_eq!(eng.get(b"".to_vec())?, None);
         eng.set(b"".to_vec(), vec![])?;
-        assert_eq!(eng.get(b"".to_vec())?, Some(vec![]));
+        assert_eq!(eng.get(b"".to_vec())?, Some(Bytes::from(vec![])));
 
         eng.set(b"cc".to_vec(), vec![5, 6, 7, 8])?;
-        assert_eq!(eng.get(b"cc".to_vec())?, Some(vec![5, 6, 7, 8]));
+        assert_eq!(
+            eng.get(b"cc".to_vec())?,
+            Some(Bytes::from(vec![5, 6, 7, 8]))
+        );
         Ok(())
     }
 
@@ -127,11 +190,62 @@ mod tests {
         Ok(())
     }
 
+    // 测试批量读取
+    fn test_get_many(mut eng: impl Engine) -> Result<()> {
+        eng.set(b"c".to_vec(), b"value1".to_vec())?;
+        eng.set(b"a".to_vec(), b"value2".to_vec())?;
+        eng.set(b"b".to_vec(), b"value3".to_vec())?;
+        eng.set(b"a".to_vec(), b"value4".to_vec())?;
+        assert_eq!(
+            eng.get_many(vec![
+                b"a".to_vec(),
+                b"d".to_vec(),
+                b"c".to_vec(),
+                b"a".to_vec(),
+            ])?,
+            vec![
+                Some(Bytes::from_static(b"value4")),
+                None,
+                Some(Bytes::from_static(b"value1")),
+                Some(Bytes::from_static(b"value4")),
+            ]
+        );
+        assert_eq!(eng.get_many(vec![])?, vec![]);
+        Ok(())
+    }
+
+    // 测试批量写入
+    fn test_write_batch(mut eng: impl Engine) -> Result<()> {
+        eng.set(b"a".to_vec(), b"value1".to_vec())?;
+        eng.set(b"b".to_vec(), b"value2".to_vec())?;
+        eng.write_batch(vec![
+            (b"a".to_vec(), None),
+            (b"c".to_vec(), Some(Bytes::from_static(b"value3"))),
+            (b"b".to_vec(), Some(Bytes::from_static(b"value4"))),
+            // 同一个 key 按照顺序生效
+            (b"c".to_vec(), Some(Bytes::from_static(b"value5"))),
+            (b"d".to_vec(), Some(Bytes::new())),
+        ])?;
+        eng.write_batch(vec![])?;
+        let entries = eng.scan(..).collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            entries,
+            vec![
+                (b"b".to_vec(), Bytes::from_static(b"value4")),
+                (b"c".to_vec(), Bytes::from_static(b"value5")),
+                (b"d".to_vec(), Bytes::new()),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_memory() -> Result<()> {
         test_point_opt(MemoryEngine::new())?;
         test_scan(MemoryEngine::new())?;
         test_scan_prefix(MemoryEngine::new())?;
+        test_write_batch(MemoryEngine::new())?;
+        test_get_many(MemoryEngine::new())?;
         Ok(())
     }
 
@@ -145,6 +259,17 @@ mod tests {
 
         test_scan_prefix(DiskEngine::new(PathBuf::from("/tmp/sqldb3/db.log"))?)?;
         std::fs::remove_dir_all(PathBuf::from("/tmp/sqldb3"))?;
+
+        let dir = tempfile::tempdir()?;
+        test_get_many(DiskEngine::new(dir.path().join("get.log"))?)?;
+
+        // 批量写入之后重新打开，从日志中恢复
+        let path = dir.path().join("db.log");
+        test_write_batch(DiskEngine::new(path.clone())?)?;
+        let mut eng = DiskEngine::new(path)?;
+        assert_eq!(eng.get(b"a".to_vec())?, None);
+        assert_eq!(eng.get(b"c".to_vec())?, Some(Bytes::from_static(b"value5")));
+        assert_eq!(eng.scan(..).count(), 3);
         Ok(())
     }
 }
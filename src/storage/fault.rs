@@ -0,0 +1,123 @@
+use std::{
+    ops::RangeBounds,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+
+use crate::error::Result;
+
+use super::{engine::Engine, memory::MemoryEngine};
+
+// 测试使用的存储引擎，记录写入到底层存储引擎的所有操作
+// 掉电时最后一次持久化之后的写入可能只有一部分落盘，恢复测试通过重放前 n 个写入
+// 得到在任意位置崩溃之后磁盘上的状态，验证重新打开之后数据仍然一致
+pub struct FaultEngine<E: Engine> {
+    inner: E,
+    log: WriteLog,
+}
+
+// 写入操作，value 为 None 表示删除
+#[derive(Debug, Clone)]
+pub struct Write {
+    pub key: Vec<u8>,
+    pub value: Option<Bytes>,
+}
+
+// 记录的写入操作，可以在 FaultEngine 被移动到 Mvcc 中之后继续读取
+#[derive(Debug, Clone, Default)]
+pub struct WriteLog {
+    writes: Arc<Mutex<Vec<Write>>>,
+    // 每次持久化时已经写入的操作数量
+    flushes: Arc<Mutex<Vec<usize>>>,
+}
+
+impl<E: Engine> FaultEngine<E> {
+    pub fn new(inner: E) -> Self {
+        Self {
+            inner,
+            log: WriteLog::default(),
+        }
+    }
+
+    pub fn log(&self) -> WriteLog {
+        self.log.clone()
+    }
+}
+
+impl WriteLog {
+    // 记录的写入操作数量
+    pub fn count(&self) -> usize {
+        self.writes.lock().unwrap().len()
+    }
+
+    // 最后一次持久化时的写入操作数量，在这之前的写入掉电之后不会丢失
+    pub fn flushed(&self) -> usize {
+        self.flushes.lock().unwrap().last().copied().unwrap_or(0)
+    }
+
+    // 模拟在第 n 个写入之后崩溃，返回只包含前 n 个写入的存储引擎
+    pub fn replay(&self, n: usize) -> Result<MemoryEngine> {
+        let mut engine = MemoryEngine::new();
+        for write in self.writes.lock().unwrap().iter().take(n) {
+            match &write.value {
+                Some(value) => engine.set(write.key.clone(), value.clone())?,
+                None => engine.delete(write.key.clone())?,
+            }
+        }
+        Ok(engine)
+    }
+}
+
+impl<E: Engine> Engine for FaultEngine<E> {
+    type EngineIterator<'a>
+        = E::EngineIterator<'a>
+    where
+        E: 'a;
+
+    fn set(&mut self, key: Vec<u8>, value: impl Into<Bytes>) -> Result<()> {
+        let value = value.into();
+        self.inner.set(key.clone(), value.clone())?;
+        self.log.writes.lock().unwrap().push(Write {
+            key,
+            value: Some(value),
+        });
+        Ok(())
+    }
+
+    fn get(&mut self, key: Vec<u8>) -> Result<Option<Bytes>> {
+        self.inner.get(key)
+    }
+
+    fn get_many(&mut self, keys: Vec<Vec<u8>>) -> Result<Vec<Option<Bytes>>> {
+        self.inner.get_many(keys)
+    }
+
+    fn delete(&mut self, key: Vec<u8>) -> Result<()> {
+        self.inner.delete(key.clone())?;
+        self.log
+            .writes
+            .lock()
+            .unwrap()
+            .push(Write { key, value: None });
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        let len = self.log.count();
+        self.log.flushes.lock().unwrap().push(len);
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> Result<()> {
+        self.inner.checkpoint()?;
+        let len = self.log.count();
+        self.log.flushes.lock().unwrap().push(len);
+        Ok(())
+    }
+
+    fn scan(&mut self, range: impl RangeBounds<Vec<u8>>) -> Self::EngineIterator<'_> {
+        self.inner.scan(range)
+    }
+}
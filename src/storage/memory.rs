@@ -1,10 +1,18 @@
 use std::collections::{btree_map, BTreeMap};
 
+use bytes::Bytes;
+
 use crate::error::Result;
 
 // 内存存储引擎定义
 pub struct MemoryEngine {
-    data: BTreeMap<Vec<u8>, Vec<u8>>,
+    data: BTreeMap<Vec<u8>, Bytes>,
+}
+
+impl Default for MemoryEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryEngine {
@@ -18,12 +26,12 @@ impl MemoryEngine {
 impl super::engine::Engine for MemoryEngine {
     type EngineIterator<'a> = MemoryEngineIterator<'a>;
 
-    fn set(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
-        self.data.insert(key, value);
+    fn set(&mut self, key: Vec<u8>, value: impl Into<Bytes>) -> Result<()> {
+        self.data.insert(key, value.into());
         Ok(())
     }
 
-    fn get(&mut self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
+    fn get(&mut self, key: Vec<u8>) -> Result<Option<Bytes>> {
         let value = self.data.get(&key).cloned();
         Ok(value)
     }
@@ -42,20 +50,21 @@ impl super::engine::Engine for MemoryEngine {
 
 // 内存存储引擎迭代器
 pub struct MemoryEngineIterator<'a> {
-    inner: btree_map::Range<'a, Vec<u8>, Vec<u8>>,
+    inner: btree_map::Range<'a, Vec<u8>, Bytes>,
 }
 
 impl<'a> super::engine::EngineIterator for MemoryEngineIterator<'a> {}
 
 impl<'a> MemoryEngineIterator<'a> {
-    fn map(item: (&Vec<u8>, &Vec<u8>)) -> <Self as Iterator>::Item {
+    // value 的 clone 只增加引用计数
+    fn map(item: (&Vec<u8>, &Bytes)) -> <Self as Iterator>::Item {
         let (k, v) = item;
         Ok((k.clone(), v.clone()))
     }
 }
 
 impl<'a> Iterator for MemoryEngineIterator<'a> {
-    type Item = Result<(Vec<u8>, Vec<u8>)>;
+    type Item = Result<(Vec<u8>, Bytes)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next().map(Self::map)
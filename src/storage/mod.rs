@@ -1,5 +1,8 @@
+#[cfg(not(target_arch = "wasm32"))]
 pub mod disk;
 pub mod engine;
+#[cfg(test)]
+pub mod fault;
 pub mod keycode;
 pub mod memory;
 pub mod mvcc;
@@ -1,15 +1,20 @@
 use std::{
-    collections::{BTreeMap, HashSet},
-    sync::{Arc, Mutex, MutexGuard},
-    u64,
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Bound,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::Duration,
 };
 
+use bytes::{BufMut, Bytes, BytesMut};
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Error, Result};
 
 use super::{
-    engine::Engine,
+    engine::{prefix_end, Engine},
     keycode::{deserialize_key, serialize_key},
 };
 
@@ -17,31 +22,335 @@ pub type Version = u64;
 
 pub struct Mvcc<E: Engine> {
     engine: Arc<Mutex<E>>,
+    options: Arc<Mutex<MvccOptions>>,
+    locks: Arc<LockTable>,
+    // 存在的快照和历史事务需要的最小版本 -> 数量，清理旧版本时需要保留它们能看到的版本
+    snapshots: Arc<Mutex<BTreeMap<Version, usize>>>,
+}
+
+// Mvcc 的配置
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MvccOptions {
+    // 读取时清理所有事务都不再需要的旧版本数据
+    // 清理之后 AS OF 历史查询和数据变更订阅无法再读取到这些版本
+    pub prune_versions: bool,
+    // 事务的最长存活时间，开启新事务时会中止超时的事务
+    pub max_txn_age: Option<Duration>,
+    // 写入和未提交的活跃事务冲突时，最多等待这么久让对方结束
+    // None 表示不等待，直接返回 WriteConflict
+    pub lock_timeout: Option<Duration>,
+}
+
+// 备份的配置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackupOptions {
+    // 每次持有存储引擎的锁时最多读取的 key 数量
+    pub batch_size: usize,
+    // 每秒最多写入的字节数，None 表示不限速
+    pub rate_limit: Option<u64>,
+    // 增量备份，只备份这个版本之后提交的数据，一般是上一次备份的快照版本
+    pub since: Option<Version>,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 1024,
+            rate_limit: None,
+            since: None,
+        }
+    }
+}
+
+// 备份的进度
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BackupProgress {
+    // 备份的快照版本，这个版本之前提交的数据都已经备份
+    pub version: Version,
+    // 已经写入的 key 数量和字节数
+    pub keys: u64,
+    pub bytes: u64,
+}
+
+// 等待写冲突的事务之间共享的状态
+#[derive(Default)]
+struct LockTable {
+    // 等待关系，key 事务正在等待 value 事务结束，用于检测死锁
+    waits_for: Mutex<HashMap<Version, Version>>,
+    // 事务提交或者回滚时通知等待的事务，和存储引擎的锁配合使用
+    released: Condvar,
 }
 
 impl<E: Engine> Clone for Mvcc<E> {
     fn clone(&self) -> Self {
         Self {
             engine: self.engine.clone(),
+            options: self.options.clone(),
+            locks: self.locks.clone(),
+            snapshots: self.snapshots.clone(),
         }
     }
 }
 
 impl<E: Engine> Mvcc<E> {
     pub fn new(eng: E) -> Self {
+        Self::new_with_options(eng, MvccOptions::default())
+    }
+
+    pub fn new_with_options(eng: E, options: MvccOptions) -> Self {
         Self {
             engine: Arc::new(Mutex::new(eng)),
+            options: Arc::new(Mutex::new(options)),
+            locks: Arc::new(LockTable::default()),
+            snapshots: Arc::default(),
         }
     }
 
     pub fn begin(&self) -> Result<MvccTransaction<E>> {
-        MvccTransaction::begin(self.engine.clone())
+        let options = *self.options.lock()?;
+        MvccTransaction::begin(
+            self.engine.clone(),
+            options,
+            self.locks.clone(),
+            self.snapshots.clone(),
+        )
+    }
+
+    // 获取当前已提交数据的只读快照，可以看到在此之前提交的所有事务的写入
+    // 快照不分配版本号，也不写入活跃事务列表，开销比开启事务小，可以克隆之后在多个线程中同时读取
+    // 快照存在期间，它能读取到的旧版本不会被清理
+    pub fn snapshot(&self) -> Result<MvccSnapshot<E>> {
+        let (state, guard) = self.committed_view()?;
+        Ok(MvccSnapshot {
+            engine: self.engine.clone(),
+            view: Arc::new(SnapshotView {
+                state,
+                _guard: guard,
+            }),
+        })
+    }
+
+    // 开启一个只读事务，和 snapshot 一样读取当前已提交的数据，不分配版本号，也不写入活跃事务列表
+    // 用于只需要读取的内部操作，写入时返回 ReadOnly，提交和回滚不需要做任何事情
+    pub fn begin_read_only(&self) -> Result<MvccTransaction<E>> {
+        let (state, guard) = self.committed_view()?;
+        Ok(MvccTransaction {
+            engine: self.engine.clone(),
+            state,
+            options: MvccOptions::default(),
+            locks: self.locks.clone(),
+            snapshots: self.snapshots.clone(),
+            prune_below: None,
+            horizon: AtomicU64::new(0),
+            _guard: Some(guard),
+        })
+    }
+
+    // 当前已提交数据的只读视图，登记之后它能读取到的旧版本不会被清理
+    fn committed_view(&self) -> Result<(TransactionState, SnapshotGuard)> {
+        let mut engine = self.engine.lock()?;
+        let version = match engine.get(MvccKey::NextVersion.encode()?)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => 1,
+        };
+        let active_versions = MvccTransaction::scan_active(&mut engine)?;
+        // 在持有存储引擎的锁时登记，之后开启的事务一定能看到这个视图
+        let guard = SnapshotGuard::register(&self.snapshots, version, &active_versions)?;
+        Ok((
+            TransactionState {
+                version,
+                active_versions,
+                read_only: true,
+            },
+            guard,
+        ))
+    }
+
+    pub fn options(&self) -> Result<MvccOptions> {
+        Ok(*self.options.lock()?)
+    }
+
+    // 修改配置，只对之后开启的事务生效
+    pub fn set_options(&self, options: MvccOptions) -> Result<()> {
+        *self.options.lock()? = options;
+        Ok(())
+    }
+
+    // 中止超过 max_age 的活跃事务，返回被中止的事务版本号
+    pub fn abort_expired(&self, max_age: Duration) -> Result<Vec<Version>> {
+        let expired = MvccTransaction::abort_expired(&mut self.engine.lock()?, max_age)?;
+        self.locks.released.notify_all();
+        Ok(expired)
+    }
+
+    // 关闭前调用，回滚所有活跃的事务并持久化数据，返回被回滚的事务版本号
+    // 还没有结束的事务之后再写入或者提交会返回 Aborted
+    pub fn shutdown(&self) -> Result<Vec<Version>> {
+        let mut engine = self.engine.lock()?;
+        let mut active = MvccTransaction::scan_active(&mut engine)?
+            .into_iter()
+            .collect::<Vec<_>>();
+        active.sort();
+        for version in active.iter() {
+            MvccTransaction::rollback_version(&mut engine, *version)?;
+        }
+        engine.flush()?;
+        drop(engine);
+        self.locks.released.notify_all();
+        Ok(active)
+    }
+
+    // 开启一个只读的历史事务，读取到的是 version 对应的事务开启时的数据
+    // 历史事务存在期间，它能读取到的旧版本不会被清理
+    pub fn begin_as_of(&self, version: Version) -> Result<MvccTransaction<E>> {
+        MvccTransaction::begin_as_of(
+            self.engine.clone(),
+            version,
+            self.locks.clone(),
+            self.snapshots.clone(),
+        )
+    }
+
+    // 获取 prefix 下从 from 版本开始已经提交的数据变更，按照版本号排序
+    // 同时返回下一次获取变更的起始版本号，在此版本之前的事务都已经结束了
+    pub fn changes(&self, prefix: Vec<u8>, from: Version) -> Result<(Vec<Change>, Version)> {
+        let mut engine = self.engine.lock()?;
+        let next_version = match engine.get(MvccKey::NextVersion.encode()?)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => 1,
+        };
+        // 最小的活跃事务之前的版本都已经提交或者回滚了
+        let watermark = MvccTransaction::scan_active(&mut engine)?
+            .into_iter()
+            .min()
+            .unwrap_or(next_version);
+
+        let mut enc_prefix = MvccKeyPrefix::Version(prefix).encode()?;
+        enc_prefix.truncate(enc_prefix.len() - 2);
+
+        let mut changes = Vec::new();
+        let mut iter = engine.scan_prefix(enc_prefix);
+        while let Some((key, value)) = iter.next().transpose()? {
+            match MvccKey::decode(key.clone())? {
+                MvccKey::Version(raw_key, version) => {
+                    if version >= from && version < watermark {
+                        changes.push(Change {
+                            version,
+                            key: raw_key,
+                            value: decode_value(value)?,
+                        });
+                    }
+                }
+                _ => {
+                    return Err(Error::Internal(format!(
+                        "unexpected key: {:?}",
+                        String::from_utf8(key)
+                    )))
+                }
+            }
+        }
+        changes.sort_by(|a, b| a.version.cmp(&b.version).then_with(|| a.key.cmp(&b.key)));
+
+        Ok((changes, watermark.max(from)))
+    }
+}
+
+// 只读的数据快照，克隆之后共享同一个读取视图
+pub struct MvccSnapshot<E: Engine> {
+    engine: Arc<Mutex<E>>,
+    view: Arc<SnapshotView>,
+}
+
+struct SnapshotView {
+    state: TransactionState,
+    // 最后一个克隆释放时取消登记
+    _guard: SnapshotGuard,
+}
+
+// 登记不在活跃事务列表中的读取视图需要的最小版本，释放时从 snapshots 中删除
+struct SnapshotGuard {
+    lowest: Version,
+    snapshots: Arc<Mutex<BTreeMap<Version, usize>>>,
+}
+
+impl SnapshotGuard {
+    // 需要在持有存储引擎的锁时登记，清理旧版本时也持有这个锁，因此一定能看到登记的视图
+    fn register(
+        snapshots: &Arc<Mutex<BTreeMap<Version, usize>>>,
+        version: Version,
+        active_versions: &HashSet<Version>,
+    ) -> Result<Self> {
+        let lowest = active_versions.iter().copied().fold(version, Version::min);
+        *snapshots.lock()?.entry(lowest).or_default() += 1;
+        Ok(Self {
+            lowest,
+            snapshots: snapshots.clone(),
+        })
+    }
+}
+
+impl Drop for SnapshotGuard {
+    fn drop(&mut self) {
+        if let Ok(mut snapshots) = self.snapshots.lock() {
+            if let Some(count) = snapshots.get_mut(&self.lowest) {
+                *count -= 1;
+                if *count == 0 {
+                    snapshots.remove(&self.lowest);
+                }
+            }
+        }
+    }
+}
+
+impl<E: Engine> Clone for MvccSnapshot<E> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            view: self.view.clone(),
+        }
+    }
+}
+
+impl<E: Engine> MvccSnapshot<E> {
+    // 快照的版本号，小于这个版本的事务中创建快照时已经提交的写入都可见
+    pub fn version(&self) -> Version {
+        self.view.state.version
+    }
+
+    pub fn get(&self, key: Vec<u8>) -> Result<Option<Bytes>> {
+        self.view.state.get(&mut *self.engine.lock()?, key, None)
+    }
+
+    pub fn get_many(&self, keys: Vec<Vec<u8>>) -> Result<Vec<Option<Bytes>>> {
+        self.view
+            .state
+            .get_many(&mut *self.engine.lock()?, keys, None)
+    }
+
+    pub fn scan_prefix(&self, prefix: Vec<u8>) -> Result<Vec<ScanResult>> {
+        self.view
+            .state
+            .scan_prefix(&mut *self.engine.lock()?, prefix)
+    }
+
+    pub fn scan(&self, start: Vec<u8>, end: Option<Vec<u8>>) -> Result<Vec<ScanResult>> {
+        self.view.state.scan(&mut *self.engine.lock()?, start, end)
     }
 }
 
 pub struct MvccTransaction<E: Engine> {
     engine: Arc<Mutex<E>>,
     state: TransactionState,
+    options: MvccOptions,
+    locks: Arc<LockTable>,
+    snapshots: Arc<Mutex<BTreeMap<Version, usize>>>,
+    // 小于这个版本号的数据对所有活跃的事务都可见，None 表示不清理旧版本
+    // 还需要和读取时登记的快照和历史事务比较，见 prune_below()
+    prune_below: Option<Version>,
+    // 当前事务已经记录过的清理上限，见 record_horizon()
+    horizon: AtomicU64,
+    // 历史事务不在活跃事务列表中，通过登记避免它需要的版本被清理
+    _guard: Option<SnapshotGuard>,
 }
 
 // 事务状态
@@ -50,15 +359,146 @@ pub struct TransactionState {
     pub version: Version,
     // 当前活跃事务版本列表
     pub active_versions: HashSet<Version>,
+    // 是否是只读的历史事务
+    pub read_only: bool,
 }
 
 impl TransactionState {
     fn is_visible(&self, version: Version) -> bool {
         if self.active_versions.contains(&version) {
-            return false;
+            false
+        } else if self.read_only {
+            // 只读事务不会写入数据，只能看到这个版本之前提交的数据
+            version < self.version
         } else {
-            return version <= self.version;
+            version <= self.version
+        }
+    }
+
+    // 读取 key 对这个视图可见的最新版本，同时清理 prune_below 之前不再需要的旧版本
+    fn get<E: Engine>(
+        &self,
+        engine: &mut E,
+        key: Vec<u8>,
+        prune_below: Option<Version>,
+    ) -> Result<Option<Bytes>> {
+        // version: 9
+        // 扫描的 version 的范围应该是 0-9，从当前版本开始反向查找
+        let from = MvccKey::Version(key.clone(), 0).encode()?;
+        let to = MvccKey::Version(key.clone(), self.version).encode()?;
+        let mut iter = engine.scan(from..=to).rev();
+        // 从最新的版本开始读取，找到一个最新的可见的版本
+        let mut found = None;
+        while let Some((key, value)) = iter.next().transpose()? {
+            match MvccKey::decode(key.clone())? {
+                MvccKey::Version(_, version) => {
+                    if self.is_visible(version) {
+                        found = Some((version, decode_value(value)?));
+                        break;
+                    }
+                }
+                _ => {
+                    return Err(Error::Internal(format!(
+                        "unexpected key: {:?}",
+                        String::from_utf8(key)
+                    )))
+                }
+            }
+        }
+        let Some((version, value)) = found else {
+            return Ok(None);
+        };
+
+        // 可见的版本对所有事务都可见时，更旧的版本已经不会再被读取，可以直接删除
+        // 如果这个版本是删除标记，那么它本身也可以删除
+        if prune_below.is_some_and(|v| version < v) {
+            let mut dead = iter
+                .map(|item| item.map(|(key, _)| key))
+                .collect::<Result<Vec<_>>>()?;
+            if value.is_none() {
+                dead.push(MvccKey::Version(key.clone(), version).encode()?);
+            }
+            for key in dead {
+                engine.delete(key)?;
+            }
+        }
+        Ok(value)
+    }
+
+    // 在同一次加锁中读取一批 key 的可见版本
+    fn get_many<E: Engine>(
+        &self,
+        engine: &mut E,
+        keys: Vec<Vec<u8>>,
+        prune_below: Option<Version>,
+    ) -> Result<Vec<Option<Bytes>>> {
+        keys.into_iter()
+            .map(|key| self.get(engine, key, prune_below))
+            .collect()
+    }
+
+    // 扫描 prefix 下对这个视图可见的所有 key，每个 key 只返回最新的可见版本，跳过删除标记
+    fn scan_prefix<E: Engine>(&self, eng: &mut E, prefix: Vec<u8>) -> Result<Vec<ScanResult>> {
+        let mut enc_prefix = MvccKeyPrefix::Version(prefix).encode()?;
+        // 原始值           编码后
+        // 97 98 99     -> 97 98 99 0 0
+        // 前缀原始值        前缀编码后
+        // 97 98        -> 97 98 0 0         -> 97 98
+        // 去掉最后的 [0, 0] 后缀
+        enc_prefix.truncate(enc_prefix.len() - 2);
+
+        self.collect_visible(eng.scan_prefix(enc_prefix))
+    }
+
+    // 扫描 [start, end) 范围内对这个视图可见的所有 key，end 为 None 时没有上界
+    // 编码之后的 key 保持原始 key 的顺序，原始 key 等于 end 的所有版本都在版本 0 之后
+    fn scan<E: Engine>(
+        &self,
+        eng: &mut E,
+        start: Vec<u8>,
+        end: Option<Vec<u8>>,
+    ) -> Result<Vec<ScanResult>> {
+        let start = Bound::Included(MvccKey::Version(start, 0).encode()?);
+        let end = match end {
+            Some(end) => Bound::Excluded(MvccKey::Version(end, 0).encode()?),
+            // 第一个字节是 Version 的编号，扫描到所有 Version key 的结尾
+            None => match prefix_end(&MvccKeyPrefix::Version(Vec::new()).encode()?[..1]) {
+                Some(end) => Bound::Excluded(end),
+                None => Bound::Unbounded,
+            },
+        };
+        self.collect_visible(eng.scan((start, end)))
+    }
+
+    // 每个 key 只保留最新的可见版本，跳过删除标记
+    fn collect_visible(
+        &self,
+        mut iter: impl Iterator<Item = Result<(Vec<u8>, Bytes)>>,
+    ) -> Result<Vec<ScanResult>> {
+        let mut results = BTreeMap::new();
+        while let Some((key, value)) = iter.next().transpose()? {
+            match MvccKey::decode(key.clone())? {
+                MvccKey::Version(raw_key, version) => {
+                    if self.is_visible(version) {
+                        match decode_value(value)? {
+                            Some(raw_value) => results.insert(raw_key, raw_value),
+                            None => results.remove(&raw_key),
+                        };
+                    }
+                }
+                _ => {
+                    return Err(Error::Internal(format!(
+                        "Unexepected key {:?}",
+                        String::from_utf8(key)
+                    )))
+                }
+            }
         }
+
+        Ok(results
+            .into_iter()
+            .map(|(key, value)| ScanResult { key, value })
+            .collect())
     }
 }
 
@@ -68,11 +508,17 @@ pub enum MvccKey {
     TxnAcvtive(Version),
     TxnWrite(Version, #[serde(with = "serde_bytes")] Vec<u8>),
     Version(#[serde(with = "serde_bytes")] Vec<u8>, Version),
+    TxnActiveSnapshot(Version),
+    // 增量备份的起始版本，只保存在增量备份中
+    BackupSince,
+    // 清理旧版本用过的最大的版本上限，小于它的版本可能已经被清理，不能再用于历史查询
+    PruneHorizon,
 }
 
 // NextVersion 0
 // TxnAcvtive 1-100 1-101 1-102
 // Version key1-101 key2-101
+// TxnActiveSnapshot 4-101 记录事务 101 开启时的活跃事务列表，用于历史查询
 
 impl MvccKey {
     pub fn encode(&self) -> Result<Vec<u8>> {
@@ -90,6 +536,7 @@ pub enum MvccKeyPrefix {
     TxnAcvtive,
     TxnWrite(Version),
     Version(#[serde(with = "serde_bytes")] Vec<u8>),
+    TxnActiveSnapshot,
 }
 
 impl MvccKeyPrefix {
@@ -100,7 +547,12 @@ impl MvccKeyPrefix {
 
 impl<E: Engine> MvccTransaction<E> {
     // 开启事务
-    pub fn begin(eng: Arc<Mutex<E>>) -> Result<Self> {
+    fn begin(
+        eng: Arc<Mutex<E>>,
+        options: MvccOptions,
+        locks: Arc<LockTable>,
+        snapshots: Arc<Mutex<BTreeMap<Version, usize>>>,
+    ) -> Result<Self> {
         // 获取存储引擎
         let mut engine = eng.lock()?;
         // 获取最新的版本号
@@ -114,54 +566,227 @@ impl<E: Engine> MvccTransaction<E> {
             bincode::serialize(&(next_version + 1))?,
         )?;
 
+        // 中止运行时间过长的事务，避免活跃事务列表和旧版本数据无限增长
+        if let Some(max_age) = options.max_txn_age {
+            if !Self::abort_expired(&mut engine, max_age)?.is_empty() {
+                locks.released.notify_all();
+            }
+        }
+
         // 获取当前活跃的事务列表
         let active_versions = Self::scan_active(&mut engine)?;
 
-        // 当前事务加入到活跃事务列表中
-        engine.set(MvccKey::TxnAcvtive(next_version).encode()?, vec![])?;
+        // 保存活跃事务列表的快照，历史查询的时候需要用来判断可见性
+        if !active_versions.is_empty() {
+            engine.set(
+                MvccKey::TxnActiveSnapshot(next_version).encode()?,
+                bincode::serialize(&active_versions)?,
+            )?;
+        }
+
+        // 当前事务加入到活跃事务列表中，记录事务的开启时间
+        engine.set(
+            MvccKey::TxnAcvtive(next_version).encode()?,
+            bincode::serialize(&now_millis())?,
+        )?;
+
+        // 活跃事务开启时仍然活跃的事务，它们写入的版本对这些活跃事务不可见
+        // 因此取所有活跃事务及其快照中的最小版本号，比它小的已提交版本对所有事务可见
+        let prune_below = if options.prune_versions {
+            let mut lowest = next_version;
+            for version in active_versions.iter() {
+                lowest = lowest.min(*version);
+                if let Some(value) = engine.get(MvccKey::TxnActiveSnapshot(*version).encode()?)? {
+                    let snapshot: HashSet<Version> = bincode::deserialize(&value)?;
+                    lowest = snapshot.into_iter().fold(lowest, Version::min);
+                }
+            }
+            Some(lowest)
+        } else {
+            None
+        };
 
         Ok(Self {
             engine: eng.clone(),
             state: TransactionState {
                 version: next_version,
                 active_versions,
+                read_only: false,
+            },
+            options,
+            locks,
+            snapshots,
+            prune_below,
+            horizon: AtomicU64::new(0),
+            _guard: None,
+        })
+    }
+
+    // 开启只读的历史事务
+    // 不分配新的版本号，也不加入活跃事务列表，可见性和 version 开启时保持一致
+    fn begin_as_of(
+        eng: Arc<Mutex<E>>,
+        version: Version,
+        locks: Arc<LockTable>,
+        snapshots: Arc<Mutex<BTreeMap<Version, usize>>>,
+    ) -> Result<Self> {
+        let mut engine = eng.lock()?;
+        let next_version: Version = match engine.get(MvccKey::NextVersion.encode()?)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => 1,
+        };
+        if version == 0 || version >= next_version {
+            return Err(Error::Internal(format!(
+                "version {} does not exist",
+                version
+            )));
+        }
+        // 清理过的版本读取到的数据不完整
+        let horizon: Version = match engine.get(MvccKey::PruneHorizon.encode()?)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => 0,
+        };
+        if version < horizon {
+            return Err(Error::Internal(format!(
+                "version {} has been pruned, the oldest version for AS OF is {}",
+                version, horizon
+            )));
+        }
+
+        // 读取 version 开启时的活跃事务列表
+        let active_versions = match engine.get(MvccKey::TxnActiveSnapshot(version).encode()?)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => HashSet::new(),
+        };
+        let guard = SnapshotGuard::register(&snapshots, version, &active_versions)?;
+
+        Ok(Self {
+            engine: eng.clone(),
+            state: TransactionState {
+                version,
+                active_versions,
+                read_only: true,
             },
+            options: MvccOptions::default(),
+            locks,
+            snapshots,
+            prune_below: None,
+            horizon: AtomicU64::new(0),
+            _guard: Some(guard),
         })
     }
 
+    // 获取当前事务的版本号
+    pub fn version(&self) -> Version {
+        self.state.version
+    }
+
     // 提交事务
     pub fn commit(&self) -> Result<()> {
+        // 只读事务没有写入任何数据
+        if self.state.read_only {
+            return Ok(());
+        }
         // 获取存储引擎
         let mut engine = self.engine.lock()?;
+        // 事务已经被强制中止，写入的数据都已经回滚
+        if !Self::is_active(&mut engine, self.state.version)? {
+            return Err(Error::Aborted);
+        }
+
+        // 从活跃事务列表中删除，之后写入的数据对其他事务可见，事务提交完成
+        // 需要在删除 TxnWrite 之前，否则中途崩溃之后回滚只能删除一部分写入的数据
+        let mut batch = vec![(MvccKey::TxnAcvtive(self.state.version).encode()?, None)];
 
-        let mut delete_keys = Vec::new();
         // 找到这个当前事务的 TxnWrite 信息
         let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnWrite(self.state.version).encode()?);
         while let Some((key, _)) = iter.next().transpose()? {
-            delete_keys.push(key);
+            batch.push((key, None));
         }
         drop(iter);
 
-        for key in delete_keys.into_iter() {
-            engine.delete(key)?;
-        }
-
-        // 从活跃事务列表中删除
-        engine.delete(MvccKey::TxnAcvtive(self.state.version).encode()?)
+        // 一次写入，批量写入按照顺序生效，仍然先删除 TxnActive
+        engine.write_batch(batch)?;
+        self.locks.released.notify_all();
+        Ok(())
     }
 
     // 回滚事务
     pub fn rollback(&self) -> Result<()> {
+        if self.state.read_only {
+            return Ok(());
+        }
         // 获取存储引擎
         let mut engine = self.engine.lock()?;
+        Self::rollback_version(&mut engine, self.state.version)?;
+        self.locks.released.notify_all();
+        Ok(())
+    }
+
+    // 强制中止另一个活跃的事务，回滚它写入的数据
+    pub fn abort(&self, version: Version) -> Result<()> {
+        if version == self.state.version {
+            return Err(Error::Internal(
+                "cannot abort the current transaction".to_string(),
+            ));
+        }
+        let mut engine = self.engine.lock()?;
+        if !Self::is_active(&mut engine, version)? {
+            return Err(Error::Internal(format!(
+                "transaction {} is not active",
+                version
+            )));
+        }
+        Self::rollback_version(&mut engine, version)?;
+        self.locks.released.notify_all();
+        Ok(())
+    }
+
+    // 中止开启时间超过 max_age 的活跃事务
+    fn abort_expired(engine: &mut MutexGuard<E>, max_age: Duration) -> Result<Vec<Version>> {
+        let deadline = now_millis().saturating_sub(max_age.as_millis() as u64);
+        let mut expired = Vec::new();
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnAcvtive.encode()?);
+        while let Some((key, value)) = iter.next().transpose()? {
+            // 没有记录开启时间的事务不会被中止
+            if value.is_empty() {
+                continue;
+            }
+            let start: u64 = bincode::deserialize(&value)?;
+            match MvccKey::decode(key.clone())? {
+                MvccKey::TxnAcvtive(version) if start < deadline => expired.push(version),
+                MvccKey::TxnAcvtive(_) => {}
+                _ => {
+                    return Err(Error::Internal(format!(
+                        "unexpected key: {:?}",
+                        String::from_utf8(key)
+                    )))
+                }
+            }
+        }
+        drop(iter);
+
+        for version in expired.iter() {
+            Self::rollback_version(engine, *version)?;
+        }
+        Ok(expired)
+    }
+
+    fn is_active(engine: &mut MutexGuard<E>, version: Version) -> Result<bool> {
+        Ok(engine
+            .get(MvccKey::TxnAcvtive(version).encode()?)?
+            .is_some())
+    }
 
+    // 删除事务写入的数据，并将事务从活跃事务列表中删除
+    fn rollback_version(engine: &mut MutexGuard<E>, version: Version) -> Result<()> {
         let mut delete_keys = Vec::new();
-        // 找到这个当前事务的 TxnWrite 信息
-        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnWrite(self.state.version).encode()?);
+        // 找到这个事务的 TxnWrite 信息
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnWrite(version).encode()?);
         while let Some((key, _)) = iter.next().transpose()? {
             match MvccKey::decode(key.clone())? {
                 MvccKey::TxnWrite(_, raw_key) => {
-                    delete_keys.push(MvccKey::Version(raw_key, self.state.version).encode()?);
+                    delete_keys.push(MvccKey::Version(raw_key, version).encode()?);
                 }
                 _ => {
                     return Err(Error::Internal(format!(
@@ -179,34 +804,102 @@ impl<E: Engine> MvccTransaction<E> {
         }
 
         // 从活跃事务列表中删除
-        engine.delete(MvccKey::TxnAcvtive(self.state.version).encode()?)
+        engine.delete(MvccKey::TxnAcvtive(version).encode()?)
     }
 
-    pub fn set(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
-        self.write_inner(key, Some(value))
+    pub fn set(&self, key: Vec<u8>, value: impl Into<Bytes>) -> Result<()> {
+        self.write_inner(key, Some(value.into()))
     }
 
     pub fn delete(&self, key: Vec<u8>) -> Result<()> {
         self.write_inner(key, None)
     }
 
-    pub fn get(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
-        // 获取存储引擎
+    pub fn get(&self, key: Vec<u8>) -> Result<Option<Bytes>> {
         let mut engine = self.engine.lock()?;
+        let prune_below = self.prune_below(&mut engine)?;
+        self.state.get(&mut *engine, key, prune_below)
+    }
 
-        // version: 9
-        // 扫描的 version 的范围应该是 0-8
-        let from = MvccKey::Version(key.clone(), 0).encode()?;
-        let to = MvccKey::Version(key.clone(), self.state.version).encode()?;
-        let mut iter = engine.scan(from..=to).rev();
-        // 从最新的版本开始读取，找到一个最新的可见的版本
-        while let Some((key, value)) = iter.next().transpose()? {
-            match MvccKey::decode(key.clone())? {
-                MvccKey::Version(_, version) => {
-                    if self.state.is_visible(version) {
-                        return Ok(bincode::deserialize(&value)?);
+    // 一次加锁读取多个 key，结果和 keys 一一对应，避免每个 key 都要获取一次锁
+    pub fn get_many(&self, keys: Vec<Vec<u8>>) -> Result<Vec<Option<Bytes>>> {
+        let mut engine = self.engine.lock()?;
+        let prune_below = self.prune_below(&mut engine)?;
+        self.state.get_many(&mut *engine, keys, prune_below)
+    }
+
+    // 可以清理的版本上限，需要在持有存储引擎的锁时调用
+    // 历史事务读取的是过去的数据，可能在当前事务开启之后才登记，所以每次清理前都要重新检查
+    fn prune_below(&self, engine: &mut MutexGuard<E>) -> Result<Option<Version>> {
+        let Some(prune_below) = self.prune_below else {
+            return Ok(None);
+        };
+        let prune_below = match self.snapshots.lock()?.keys().next() {
+            Some(lowest) => prune_below.min(*lowest),
+            None => prune_below,
+        };
+        self.record_horizon(engine, prune_below)?;
+        Ok(Some(prune_below))
+    }
+
+    // 清理之前记录清理的版本上限，之后 AS OF 不能再读取更早的版本
+    // 更早的版本开启时的活跃事务快照只有历史查询会用到，同时删除
+    fn record_horizon(&self, engine: &mut MutexGuard<E>, horizon: Version) -> Result<()> {
+        if horizon <= self.horizon.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let stored: Version = match engine.get(MvccKey::PruneHorizon.encode()?)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => 0,
+        };
+        if horizon > stored {
+            engine.set(
+                MvccKey::PruneHorizon.encode()?,
+                bincode::serialize(&horizon)?,
+            )?;
+            let mut dead = Vec::new();
+            let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnActiveSnapshot.encode()?);
+            while let Some((key, _)) = iter.next().transpose()? {
+                match MvccKey::decode(key.clone())? {
+                    MvccKey::TxnActiveSnapshot(version) if version < horizon => dead.push(key),
+                    MvccKey::TxnActiveSnapshot(_) => break,
+                    _ => {
+                        return Err(Error::Internal(format!(
+                            "unexpected key: {:?}",
+                            String::from_utf8(key)
+                        )))
                     }
                 }
+            }
+            drop(iter);
+            for key in dead {
+                engine.delete(key)?;
+            }
+        }
+        self.horizon.store(horizon.max(stored), Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn scan_prefix(&self, prefix: Vec<u8>) -> Result<Vec<ScanResult>> {
+        self.state.scan_prefix(&mut *self.engine.lock()?, prefix)
+    }
+
+    // 扫描 [start, end) 范围内的 key，按照 key 的顺序返回
+    pub fn scan(&self, start: Vec<u8>, end: Option<Vec<u8>>) -> Result<Vec<ScanResult>> {
+        self.state.scan(&mut *self.engine.lock()?, start, end)
+    }
+
+    // 获取 key 保存的所有版本，按照版本号从小到大排列，用于排查 MVCC 和旧版本清理的问题
+    // 不考虑可见性，包括其他事务还没有提交的版本
+    pub fn history(&self, key: Vec<u8>) -> Result<Vec<KeyVersion>> {
+        let mut engine = self.engine.lock()?;
+        let from = MvccKey::Version(key.clone(), 0).encode()?;
+        let to = MvccKey::Version(key, u64::MAX).encode()?;
+        let mut versions = Vec::new();
+        let mut iter = engine.scan(from..=to);
+        while let Some((key, value)) = iter.next().transpose()? {
+            match MvccKey::decode(key.clone())? {
+                MvccKey::Version(_, version) => versions.push((version, decode_value(value)?)),
                 _ => {
                     return Err(Error::Internal(format!(
                         "unexpected key: {:?}",
@@ -215,57 +908,357 @@ impl<E: Engine> MvccTransaction<E> {
                 }
             }
         }
-        Ok(None)
+        drop(iter);
+
+        versions
+            .into_iter()
+            .map(|(version, value)| {
+                Ok(KeyVersion {
+                    version,
+                    value,
+                    committed: !Self::is_active(&mut engine, version)?,
+                })
+            })
+            .collect()
     }
 
-    pub fn scan_prefix(&self, prefix: Vec<u8>) -> Result<Vec<ScanResult>> {
-        let mut eng = self.engine.lock()?;
+    // 统计 prefix 下保存的版本数量，包括删除标记和当前事务不可见的版本
+    pub fn version_count(&self, prefix: Vec<u8>) -> Result<u64> {
+        let mut engine = self.engine.lock()?;
         let mut enc_prefix = MvccKeyPrefix::Version(prefix).encode()?;
-        // 原始值           编码后
-        // 97 98 99     -> 97 98 99 0 0
-        // 前缀原始值        前缀编码后
-        // 97 98        -> 97 98 0 0         -> 97 98
-        // 去掉最后的 [0, 0] 后缀
         enc_prefix.truncate(enc_prefix.len() - 2);
+        let mut count = 0;
+        let mut iter = engine.scan_prefix(enc_prefix);
+        while iter.next().transpose()?.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
 
-        let mut iter = eng.scan_prefix(enc_prefix);
-        let mut results = BTreeMap::new();
+    // 检查 prefix 下保存的所有版本，返回发现的所有问题，不会因为第一个问题就停止
+    // 版本号不能超过下一个可分配的版本号，版本数据需要能够解码
+    // 未提交的版本需要有对应的写入记录，否则事务回滚时不会被清理
+    pub fn verify(&self, prefix: Vec<u8>) -> Result<Vec<String>> {
+        let mut engine = self.engine.lock()?;
+        let next_version: Version = match engine.get(MvccKey::NextVersion.encode()?)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => 1,
+        };
+        let active = Self::scan_active(&mut engine)?;
+
+        let mut enc_prefix = MvccKeyPrefix::Version(prefix).encode()?;
+        enc_prefix.truncate(enc_prefix.len() - 2);
+
+        let mut problems = Vec::new();
+        let mut uncommitted = Vec::new();
+        let mut iter = engine.scan_prefix(enc_prefix);
         while let Some((key, value)) = iter.next().transpose()? {
-            match MvccKey::decode(key.clone())? {
-                MvccKey::Version(raw_key, version) => {
-                    if self.state.is_visible(version) {
-                        match bincode::deserialize(&value)? {
-                            Some(raw_value) => results.insert(raw_key, raw_value),
-                            None => results.remove(&raw_key),
-                        };
-                    }
+            let (raw_key, version) = match MvccKey::decode(key.clone()) {
+                Ok(MvccKey::Version(raw_key, version)) => (raw_key, version),
+                Ok(k) => {
+                    problems.push(format!("unexpected key {:?}", k));
+                    continue;
                 }
-                _ => {
+                Err(err) => {
+                    problems.push(format!("invalid version key {:?}: {}", key, err));
+                    continue;
+                }
+            };
+            if version >= next_version {
+                problems.push(format!(
+                    "version {} of key {:?} is not less than next version {}",
+                    version, raw_key, next_version
+                ));
+            }
+            if let Err(err) = decode_value(value) {
+                problems.push(format!("version {} of key {:?}: {}", version, raw_key, err));
+            }
+            if active.contains(&version) {
+                uncommitted.push((raw_key, version));
+            }
+        }
+        drop(iter);
+
+        for (raw_key, version) in uncommitted {
+            let write = MvccKey::TxnWrite(version, raw_key.clone()).encode()?;
+            if engine.get(write)?.is_none() {
+                problems.push(format!(
+                    "uncommitted version {} of key {:?} has no write record",
+                    version, raw_key
+                ));
+            }
+        }
+        Ok(problems)
+    }
+
+    // 持久化存储引擎中的数据，并清理失效的日志
+    pub fn checkpoint(&self) -> Result<()> {
+        self.engine.lock()?.checkpoint()
+    }
+
+    // 将当前事务开启前已经提交的数据备份到 dst 中，备份可以直接作为存储引擎打开
+    // 数据分批读取，每读取一批就释放存储引擎的锁，不会阻塞其他事务的读写
+    // 当前事务是活跃的，需要备份的版本不会被清理
+    pub fn backup<D: Engine>(
+        &self,
+        dst: &mut D,
+        options: BackupOptions,
+        mut progress: impl FnMut(&BackupProgress),
+    ) -> Result<BackupProgress> {
+        if options.batch_size == 0 {
+            return Err(Error::Internal(
+                "backup batch size must be positive".to_string(),
+            ));
+        }
+        // wasm32 下不能让线程等待
+        if cfg!(target_arch = "wasm32") && options.rate_limit.is_some() {
+            return Err(Error::Internal(
+                "backup rate_limit is not supported on wasm32".to_string(),
+            ));
+        }
+        if dst.scan_prefix(Vec::new()).next().is_some() {
+            return Err(Error::Internal("backup target is not empty".to_string()));
+        }
+
+        // 增量备份还需要包含 since 开启时仍然活跃的事务，它们在上一次备份之后才提交
+        let since_active: HashSet<Version> = match options.since {
+            Some(since) => {
+                if since == 0 || since >= self.state.version {
                     return Err(Error::Internal(format!(
-                        "Unexepected key {:?}",
-                        String::from_utf8(key)
-                    )))
+                        "invalid incremental backup version {}",
+                        since
+                    )));
+                }
+                // 清理旧版本时可能会删除上一次备份之后写入的删除标记
+                if self.options.prune_versions {
+                    return Err(Error::Internal(
+                        "incremental backup is not supported when pruning versions".to_string(),
+                    ));
+                }
+                let mut engine = self.engine.lock()?;
+                match engine.get(MvccKey::TxnActiveSnapshot(since).encode()?)? {
+                    Some(value) => bincode::deserialize(&value)?,
+                    None => HashSet::new(),
+                }
+            }
+            None => HashSet::new(),
+        };
+        let is_new = |version: Version| {
+            options
+                .since
+                .is_none_or(|since| version >= since || since_active.contains(&version))
+        };
+
+        let mut version_prefix = MvccKeyPrefix::Version(Vec::new()).encode()?;
+        version_prefix.truncate(version_prefix.len() - 2);
+        let start = options.rate_limit.map(|_| Instant::now());
+        let mut stats = BackupProgress {
+            version: self.state.version,
+            ..Default::default()
+        };
+        for prefix in [version_prefix, MvccKeyPrefix::TxnActiveSnapshot.encode()?] {
+            let mut from = Bound::Included(prefix.clone());
+            loop {
+                let batch = {
+                    let mut engine = self.engine.lock()?;
+                    let mut iter = engine.scan((from.clone(), Bound::Unbounded));
+                    let mut batch = Vec::with_capacity(options.batch_size);
+                    while batch.len() < options.batch_size {
+                        match iter.next().transpose()? {
+                            Some((key, value)) if key.starts_with(&prefix) => {
+                                batch.push((key, value))
+                            }
+                            _ => break,
+                        }
+                    }
+                    batch
+                };
+                let Some((last, _)) = batch.last() else {
+                    break;
+                };
+                from = Bound::Excluded(last.clone());
+                let done = batch.len() < options.batch_size;
+
+                for (key, value) in batch {
+                    let keep = match MvccKey::decode(key.clone())? {
+                        // 只备份当前事务开启前已经提交的版本
+                        MvccKey::Version(_, version) => {
+                            version < self.state.version
+                                && !self.state.active_versions.contains(&version)
+                                && is_new(version)
+                        }
+                        MvccKey::TxnActiveSnapshot(version) => {
+                            version < self.state.version
+                                && options.since.is_none_or(|since| version >= since)
+                        }
+                        _ => {
+                            return Err(Error::Internal(format!(
+                                "unexpected key: {:?}",
+                                String::from_utf8(key)
+                            )))
+                        }
+                    };
+                    if keep {
+                        stats.keys += 1;
+                        stats.bytes += (key.len() + value.len()) as u64;
+                        dst.set(key, value)?;
+                    }
+                }
+                progress(&stats);
+
+                // 限速，写入速度超过限制时等待
+                if let (Some(rate), Some(start)) = (options.rate_limit, start) {
+                    let expected = Duration::from_secs_f64(stats.bytes as f64 / rate.max(1) as f64);
+                    if let Some(wait) = expected.checked_sub(start.elapsed()) {
+                        std::thread::sleep(wait);
+                    }
+                }
+                if done {
+                    break;
                 }
             }
         }
 
-        Ok(results
-            .into_iter()
-            .map(|(key, value)| ScanResult { key, value })
-            .collect())
+        // 恢复之后从当前事务的版本号开始分配新的版本
+        dst.set(
+            MvccKey::NextVersion.encode()?,
+            bincode::serialize(&self.state.version)?,
+        )?;
+        if let Some(since) = options.since {
+            dst.set(MvccKey::BackupSince.encode()?, bincode::serialize(&since)?)?;
+        }
+        // 恢复之后同样不能读取已经清理的版本
+        let horizon = self.engine.lock()?.get(MvccKey::PruneHorizon.encode()?)?;
+        if let Some(horizon) = horizon {
+            dst.set(MvccKey::PruneHorizon.encode()?, horizon)?;
+        }
+        Ok(stats)
     }
 
     // 更新/删除数据
-    fn write_inner(&self, key: Vec<u8>, value: Option<Vec<u8>>) -> Result<()> {
+    fn write_inner(&self, key: Vec<u8>, value: Option<Bytes>) -> Result<()> {
+        if self.state.read_only {
+            return Err(Error::ReadOnly);
+        }
         // 获取存储引擎
         let mut engine = self.engine.lock()?;
+        if !Self::is_active(&mut engine, self.state.version)? {
+            return Err(Error::Aborted);
+        }
+        let deadline = self.options.lock_timeout.map(|t| Instant::now() + t);
+        let (mut engine, _) = self.wait_for_write(engine, &key, deadline)?;
+
+        // 记录这个 version 写入了哪些 key，用于回滚事务
+        engine.set(
+            MvccKey::TxnWrite(self.state.version, key.clone()).encode()?,
+            Bytes::new(),
+        )?;
+
+        // 写入实际的 key value 数据
+        engine.set(
+            MvccKey::Version(key.clone(), self.state.version).encode()?,
+            encode_value(value),
+        )?;
+        Ok(())
+    }
+
+    // 等待写入 key 的其他未提交事务结束，返回存储引擎和 key 最新的版本号
+    // 冲突的版本已经提交、deadline 为 None 或者等待超时时返回 WriteConflict
+    // 对方事务直接或者间接地在等待当前事务时返回 Deadlock
+    fn wait_for_write<'a>(
+        &'a self,
+        mut engine: MutexGuard<'a, E>,
+        key: &[u8],
+        deadline: Option<Instant>,
+    ) -> Result<(MutexGuard<'a, E>, Option<Version>)> {
+        loop {
+            let latest = self.latest_version(&mut engine, key)?;
+            let Some(version) = latest.filter(|version| !self.state.is_visible(*version)) else {
+                return Ok((engine, latest));
+            };
+            // 没有开启等待，直接返回冲突，wasm32 下只有一个线程，等待期间对方事务不会结束
+            let Some(deadline) = deadline.filter(|_| !cfg!(target_arch = "wasm32")) else {
+                return Err(Error::WriteConflict);
+            };
+            // 冲突的版本已经提交了，等待也无法解决冲突
+            if !Self::is_active(&mut engine, version)? {
+                return Err(Error::WriteConflict);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::WriteConflict);
+            }
+
+            // 对方事务未提交，等待它提交或者回滚后再重新检测冲突
+            // 如果对方事务直接或者间接地在等待当前事务，那么就是死锁
+            {
+                let mut waits_for = self.locks.waits_for.lock()?;
+                let mut holder = version;
+                let mut visited = HashSet::new();
+                while let Some(next) = waits_for.get(&holder) {
+                    if *next == self.state.version {
+                        return Err(Error::Deadlock);
+                    }
+                    if !visited.insert(*next) {
+                        break;
+                    }
+                    holder = *next;
+                }
+                waits_for.insert(self.state.version, version);
+            }
+            let (guard, _) = self.locks.released.wait_timeout(engine, deadline - now)?;
+            engine = guard;
+            self.locks.waits_for.lock()?.remove(&self.state.version);
+            // 等待期间当前事务可能已经被回滚
+            if !Self::is_active(&mut engine, self.state.version)? {
+                return Err(Error::Aborted);
+            }
+        }
+    }
+
+    // 批量更新/删除数据，只获取一次存储引擎，并且合并成一次写入
+    // 遇到冲突时和 set 一样按照 lock_timeout 等待，整批写入共用一个等待的截止时间
+    // 当前事务已经写入过的 key 不需要重复记录 TxnWrite
+    pub fn write_batch(&self, writes: Vec<(Vec<u8>, Option<Bytes>)>) -> Result<()> {
+        if self.state.read_only {
+            return Err(Error::ReadOnly);
+        }
+        let mut engine = self.engine.lock()?;
+        if !Self::is_active(&mut engine, self.state.version)? {
+            return Err(Error::Aborted);
+        }
+        let deadline = self.options.lock_timeout.map(|t| Instant::now() + t);
+        let mut written = HashSet::new();
+        let mut batch = Vec::with_capacity(writes.len() * 2);
+        for (key, value) in writes {
+            if !written.contains(&key) {
+                let (guard, latest) = self.wait_for_write(engine, &key, deadline)?;
+                engine = guard;
+                match latest {
+                    Some(version) if version == self.state.version => {}
+                    // 记录这个 version 写入了哪些 key，在实际的数据之前写入，用于回滚事务
+                    _ => batch.push((
+                        MvccKey::TxnWrite(self.state.version, key.clone()).encode()?,
+                        Some(Bytes::new()),
+                    )),
+                }
+                written.insert(key.clone());
+            }
+            batch.push((
+                MvccKey::Version(key, self.state.version).encode()?,
+                Some(encode_value(value)),
+            ));
+        }
+        engine.write_batch(batch)
+    }
 
-        // 检测冲突
+    // 返回 key 在最早的活跃事务之后的最新版本号
+    fn latest_version(&self, engine: &mut MutexGuard<E>, key: &[u8]) -> Result<Option<Version>> {
         //  3 4 5
         //  6
         //  key1-3 key2-4 key3-5
         let from = MvccKey::Version(
-            key.clone(),
+            key.to_vec(),
             self.state
                 .active_versions
                 .iter()
@@ -274,7 +1267,7 @@ impl<E: Engine> MvccTransaction<E> {
                 .unwrap_or(self.state.version + 1),
         )
         .encode()?;
-        let to = MvccKey::Version(key.clone(), u64::MAX).encode()?;
+        let to = MvccKey::Version(key.to_vec(), u64::MAX).encode()?;
         //  当前活跃事务列表 3 4 5
         //  当前事务 6
         // 只需要判断最后一个版本号
@@ -283,12 +1276,7 @@ impl<E: Engine> MvccTransaction<E> {
         // 3. 如果是当前活跃事务修改了这个 key，比如 4，那么事务 5 就不可能修改这个 key
         if let Some((k, _)) = engine.scan(from..=to).last().transpose()? {
             match MvccKey::decode(k.clone())? {
-                MvccKey::Version(_, version) => {
-                    // 检测这个 version 是否是可见的
-                    if !self.state.is_visible(version) {
-                        return Err(Error::WriteConflict);
-                    }
-                }
+                MvccKey::Version(_, version) => return Ok(Some(version)),
                 _ => {
                     return Err(Error::Internal(format!(
                         "unexpected key: {:?}",
@@ -297,19 +1285,7 @@ impl<E: Engine> MvccTransaction<E> {
                 }
             }
         }
-
-        // 记录这个 version 写入了哪些 key，用于回滚事务
-        engine.set(
-            MvccKey::TxnWrite(self.state.version, key.clone()).encode()?,
-            vec![],
-        )?;
-
-        // 写入实际的 key value 数据
-        engine.set(
-            MvccKey::Version(key.clone(), self.state.version).encode()?,
-            bincode::serialize(&value)?,
-        )?;
-        Ok(())
+        Ok(None)
     }
 
     // 扫描获取当前活跃事务列表
@@ -333,20 +1309,182 @@ impl<E: Engine> MvccTransaction<E> {
     }
 }
 
+// 将增量备份合并到基础备份中，合并之后可以继续合并下一个增量备份
+// 增量备份的起始版本不能晚于基础备份的快照版本，否则中间的数据会丢失
+pub fn restore_backup<D: Engine, S: Engine>(base: &mut D, incremental: &mut S) -> Result<()> {
+    let base_version: Version = match base.get(MvccKey::NextVersion.encode()?)? {
+        Some(value) => bincode::deserialize(&value)?,
+        None => return Err(Error::Internal("base backup is empty".to_string())),
+    };
+    let since: Version = match incremental.get(MvccKey::BackupSince.encode()?)? {
+        Some(value) => bincode::deserialize(&value)?,
+        None => return Err(Error::Internal("not an incremental backup".to_string())),
+    };
+    if since > base_version {
+        return Err(Error::Internal(format!(
+            "incremental backup starts at version {}, but base backup ends at version {}",
+            since, base_version
+        )));
+    }
+    let version: Version = match incremental.get(MvccKey::NextVersion.encode()?)? {
+        Some(value) => bincode::deserialize(&value)?,
+        None => return Err(Error::Internal("incremental backup is empty".to_string())),
+    };
+
+    let mut iter = incremental.scan_prefix(Vec::new());
+    while let Some((key, value)) = iter.next().transpose()? {
+        match MvccKey::decode(key.clone())? {
+            MvccKey::Version(..) | MvccKey::TxnActiveSnapshot(_) => base.set(key, value)?,
+            MvccKey::NextVersion | MvccKey::BackupSince | MvccKey::PruneHorizon => {}
+            _ => {
+                return Err(Error::Internal(format!(
+                    "unexpected key: {:?}",
+                    String::from_utf8(key)
+                )))
+            }
+        }
+    }
+    base.set(
+        MvccKey::NextVersion.encode()?,
+        bincode::serialize(&base_version.max(version))?,
+    )
+}
+
+// 当前时间的毫秒数，wasm32 下没有系统时间，使用 JS 的 Date.now()
+pub(crate) fn now_millis() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    return js_sys::Date::now() as u64;
+    #[cfg(not(target_arch = "wasm32"))]
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+// 计算超时使用的时钟，wasm32 下 std::time::Instant::now() 会 panic，使用 Date.now() 实现需要的部分
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use wasm_clock::Instant;
+
+#[cfg(target_arch = "wasm32")]
+mod wasm_clock {
+    use std::{
+        ops::{Add, Sub},
+        time::Duration,
+    };
+
+    // 从 unix 纪元开始的时间，Date.now() 不是单调的，时钟回拨时按照没有经过时间处理
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub(crate) struct Instant(Duration);
+
+    impl Instant {
+        pub(crate) fn now() -> Self {
+            Self(Duration::from_millis(super::now_millis()))
+        }
+
+        pub(crate) fn elapsed(&self) -> Duration {
+            Self::now() - *self
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Self;
+
+        fn add(self, d: Duration) -> Self {
+            Self(self.0 + d)
+        }
+    }
+
+    impl Sub for Instant {
+        type Output = Duration;
+
+        fn sub(self, other: Self) -> Duration {
+            self.0.saturating_sub(other.0)
+        }
+    }
+}
+
+// 版本数据的编码和 bincode 序列化 Option<Vec<u8>> 相同，已有的日志文件不需要转换
+// 删除是一个字节 0，写入是一个字节 1、8 字节小端的长度，之后是原始的 value
+// 解码时直接引用原始的 Bytes，不需要复制 value
+const VALUE_DELETED: u8 = 0;
+const VALUE_PRESENT: u8 = 1;
+const VALUE_HEADER_LEN: usize = 9;
+
+fn encode_value(value: Option<Bytes>) -> Bytes {
+    match value {
+        None => Bytes::from_static(&[VALUE_DELETED]),
+        Some(value) => {
+            let mut buf = BytesMut::with_capacity(value.len() + VALUE_HEADER_LEN);
+            buf.put_u8(VALUE_PRESENT);
+            buf.put_u64_le(value.len() as u64);
+            buf.put(value);
+            buf.freeze()
+        }
+    }
+}
+
+fn decode_value(value: Bytes) -> Result<Option<Bytes>> {
+    match value.first() {
+        Some(&VALUE_DELETED) if value.len() == 1 => Ok(None),
+        Some(&VALUE_PRESENT) if value.len() >= VALUE_HEADER_LEN => {
+            let mut len = [0; 8];
+            len.copy_from_slice(&value[1..VALUE_HEADER_LEN]);
+            match u64::from_le_bytes(len) == (value.len() - VALUE_HEADER_LEN) as u64 {
+                true => Ok(Some(value.slice(VALUE_HEADER_LEN..))),
+                false => Err(Error::Internal(format!(
+                    "invalid version value {:?}",
+                    value
+                ))),
+            }
+        }
+        _ => Err(Error::Internal(format!(
+            "invalid version value {:?}",
+            value
+        ))),
+    }
+}
+
+// key 保存的一个版本
+#[derive(Debug, PartialEq)]
+pub struct KeyVersion {
+    // 写入这个版本的事务版本号
+    pub version: Version,
+    // None 表示删除标记
+    pub value: Option<Bytes>,
+    // 写入这个版本的事务是否已经提交
+    pub committed: bool,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ScanResult {
     pub key: Vec<u8>,
-    pub value: Vec<u8>,
+    pub value: Bytes,
+}
+
+// 已提交的数据变更，value 为 None 表示删除
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub version: Version,
+    pub key: Vec<u8>,
+    pub value: Option<Bytes>,
 }
 
 #[cfg(test)]
 mod tests {
+    use bytes::Bytes;
+
+    use std::time::Duration;
+
     use crate::{
-        error::Result,
-        storage::{disk::DiskEngine, engine::Engine, memory::MemoryEngine},
+        error::{Error, Result},
+        storage::{disk::DiskEngine, engine::Engine, fault::FaultEngine, memory::MemoryEngine},
     };
 
-    use super::Mvcc;
+    use super::{
+        encode_value, restore_backup, BackupOptions, KeyVersion, Mvcc, MvccKey, MvccKeyPrefix,
+        MvccOptions, ScanResult,
+    };
 
     // 1. Get
     fn get(eng: impl Engine) -> Result<()> {
@@ -360,8 +1498,14 @@ mod tests {
         tx.commit()?;
 
         let tx1 = mvcc.begin()?;
-        assert_eq!(tx1.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
-        assert_eq!(tx1.get(b"key2".to_vec())?, Some(b"val3".to_vec()));
+        assert_eq!(
+            tx1.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
+        assert_eq!(
+            tx1.get(b"key2".to_vec())?,
+            Some(Bytes::from_static(b"val3"))
+        );
         assert_eq!(tx1.get(b"key3".to_vec())?, None);
 
         Ok(())
@@ -371,7 +1515,7 @@ mod tests {
     fn test_get() -> Result<()> {
         get(MemoryEngine::new())?;
 
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         get(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -397,9 +1541,18 @@ mod tests {
         tx3.delete(b"key3".to_vec())?;
         tx3.commit()?;
 
-        assert_eq!(tx2.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
-        assert_eq!(tx2.get(b"key2".to_vec())?, Some(b"val3".to_vec()));
-        assert_eq!(tx2.get(b"key3".to_vec())?, Some(b"val4".to_vec()));
+        assert_eq!(
+            tx2.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
+        assert_eq!(
+            tx2.get(b"key2".to_vec())?,
+            Some(Bytes::from_static(b"val3"))
+        );
+        assert_eq!(
+            tx2.get(b"key3".to_vec())?,
+            Some(Bytes::from_static(b"val4"))
+        );
 
         Ok(())
     }
@@ -407,7 +1560,7 @@ mod tests {
     fn test_get_isolation() -> Result<()> {
         get_isolation(MemoryEngine::new())?;
 
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         get_isolation(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -432,11 +1585,11 @@ mod tests {
             vec![
                 super::ScanResult {
                     key: b"aabb".to_vec(),
-                    value: b"val1".to_vec()
+                    value: Bytes::from_static(b"val1")
                 },
                 super::ScanResult {
                     key: b"aaca".to_vec(),
-                    value: b"val5".to_vec()
+                    value: Bytes::from_static(b"val5")
                 },
             ]
         );
@@ -447,19 +1600,19 @@ mod tests {
             vec![
                 super::ScanResult {
                     key: b"aabb".to_vec(),
-                    value: b"val1".to_vec()
+                    value: Bytes::from_static(b"val1")
                 },
                 super::ScanResult {
                     key: b"aaca".to_vec(),
-                    value: b"val5".to_vec()
+                    value: Bytes::from_static(b"val5")
                 },
                 super::ScanResult {
                     key: b"abcc".to_vec(),
-                    value: b"val2".to_vec()
+                    value: Bytes::from_static(b"val2")
                 },
                 super::ScanResult {
                     key: b"acca".to_vec(),
-                    value: b"val4".to_vec()
+                    value: Bytes::from_static(b"val4")
                 },
             ]
         );
@@ -469,17 +1622,32 @@ mod tests {
             iter3,
             vec![super::ScanResult {
                 key: b"bcca".to_vec(),
-                value: b"val6".to_vec()
+                value: Bytes::from_static(b"val6")
             },]
         );
 
+        // 范围扫描包括 start，不包括 end
+        tx1.delete(b"acca".to_vec())?;
+        let keys = |results: Vec<super::ScanResult>| {
+            results.into_iter().map(|r| r.key).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(tx1.scan(b"aaca".to_vec(), Some(b"bbaa".to_vec()))?),
+            vec![b"aaca".to_vec(), b"abcc".to_vec()]
+        );
+        assert_eq!(
+            keys(tx1.scan(b"bb".to_vec(), None)?),
+            vec![b"bbaa".to_vec(), b"bcca".to_vec()]
+        );
+        assert!(tx1.scan(b"b".to_vec(), Some(b"b".to_vec()))?.is_empty());
+
         Ok(())
     }
 
     #[test]
     fn test_scan_prefix() -> Result<()> {
         scan_prefix(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         scan_prefix(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -513,11 +1681,11 @@ mod tests {
             vec![
                 super::ScanResult {
                     key: b"aabb".to_vec(),
-                    value: b"val1".to_vec()
+                    value: Bytes::from_static(b"val1")
                 },
                 super::ScanResult {
                     key: b"aaca".to_vec(),
-                    value: b"val5".to_vec()
+                    value: Bytes::from_static(b"val5")
                 },
             ]
         );
@@ -528,19 +1696,19 @@ mod tests {
             vec![
                 super::ScanResult {
                     key: b"aabb".to_vec(),
-                    value: b"val1".to_vec()
+                    value: Bytes::from_static(b"val1")
                 },
                 super::ScanResult {
                     key: b"aaca".to_vec(),
-                    value: b"val5".to_vec()
+                    value: Bytes::from_static(b"val5")
                 },
                 super::ScanResult {
                     key: b"abcc".to_vec(),
-                    value: b"val2".to_vec()
+                    value: Bytes::from_static(b"val2")
                 },
                 super::ScanResult {
                     key: b"acca".to_vec(),
-                    value: b"val4".to_vec()
+                    value: Bytes::from_static(b"val4")
                 },
             ]
         );
@@ -550,7 +1718,7 @@ mod tests {
             iter3,
             vec![super::ScanResult {
                 key: b"bcca".to_vec(),
-                value: b"val6".to_vec()
+                value: Bytes::from_static(b"val6")
             },]
         );
 
@@ -560,7 +1728,7 @@ mod tests {
     #[test]
     fn test_scan_isolation() -> Result<()> {
         scan_isolation(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         scan_isolation(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -591,17 +1759,29 @@ mod tests {
         tx2.commit()?;
 
         let tx = mvcc.begin()?;
-        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1-1".to_vec()));
-        assert_eq!(tx.get(b"key2".to_vec())?, Some(b"val3-2".to_vec()));
-        assert_eq!(tx.get(b"key3".to_vec())?, Some(b"val4-1".to_vec()));
-        assert_eq!(tx.get(b"key4".to_vec())?, Some(b"val5-1".to_vec()));
+        assert_eq!(
+            tx.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1-1"))
+        );
+        assert_eq!(
+            tx.get(b"key2".to_vec())?,
+            Some(Bytes::from_static(b"val3-2"))
+        );
+        assert_eq!(
+            tx.get(b"key3".to_vec())?,
+            Some(Bytes::from_static(b"val4-1"))
+        );
+        assert_eq!(
+            tx.get(b"key4".to_vec())?,
+            Some(Bytes::from_static(b"val5-1"))
+        );
         Ok(())
     }
 
     #[test]
     fn test_set() -> Result<()> {
         set(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         set(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -645,7 +1825,7 @@ mod tests {
     #[test]
     fn test_set_conflict() -> Result<()> {
         set_conflict(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         set_conflict(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -672,11 +1852,11 @@ mod tests {
             vec![
                 super::ScanResult {
                     key: b"key1".to_vec(),
-                    value: b"val1".to_vec()
+                    value: Bytes::from_static(b"val1")
                 },
                 super::ScanResult {
                     key: b"key3".to_vec(),
-                    value: b"val3-1".to_vec()
+                    value: Bytes::from_static(b"val3-1")
                 },
             ]
         );
@@ -686,7 +1866,7 @@ mod tests {
     #[test]
     fn test_delete() -> Result<()> {
         delete(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         delete(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -720,7 +1900,7 @@ mod tests {
     #[test]
     fn test_delete_conflict() -> Result<()> {
         delete_conflict(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         delete_conflict(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -739,7 +1919,10 @@ mod tests {
         let tx2 = mvcc.begin()?;
 
         tx2.set(b"key1".to_vec(), b"val1-1".to_vec())?;
-        assert_eq!(tx1.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        assert_eq!(
+            tx1.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
 
         Ok(())
     }
@@ -747,7 +1930,7 @@ mod tests {
     #[test]
     fn test_dirty_read() -> Result<()> {
         dirty_read(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         dirty_read(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -766,9 +1949,15 @@ mod tests {
         let tx2 = mvcc.begin()?;
 
         tx2.set(b"key1".to_vec(), b"val1-1".to_vec())?;
-        assert_eq!(tx1.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        assert_eq!(
+            tx1.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
         tx2.commit()?;
-        assert_eq!(tx1.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        assert_eq!(
+            tx1.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
 
         Ok(())
     }
@@ -776,7 +1965,7 @@ mod tests {
     #[test]
     fn test_unrepeatable_read() -> Result<()> {
         unrepeatable_read(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         unrepeatable_read(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -800,15 +1989,15 @@ mod tests {
             vec![
                 super::ScanResult {
                     key: b"key1".to_vec(),
-                    value: b"val1".to_vec()
+                    value: Bytes::from_static(b"val1")
                 },
                 super::ScanResult {
                     key: b"key2".to_vec(),
-                    value: b"val2".to_vec()
+                    value: Bytes::from_static(b"val2")
                 },
                 super::ScanResult {
                     key: b"key3".to_vec(),
-                    value: b"val3".to_vec()
+                    value: Bytes::from_static(b"val3")
                 },
             ]
         );
@@ -823,15 +2012,15 @@ mod tests {
             vec![
                 super::ScanResult {
                     key: b"key1".to_vec(),
-                    value: b"val1".to_vec()
+                    value: Bytes::from_static(b"val1")
                 },
                 super::ScanResult {
                     key: b"key2".to_vec(),
-                    value: b"val2".to_vec()
+                    value: Bytes::from_static(b"val2")
                 },
                 super::ScanResult {
                     key: b"key3".to_vec(),
-                    value: b"val3".to_vec()
+                    value: Bytes::from_static(b"val3")
                 },
             ]
         );
@@ -841,7 +2030,7 @@ mod tests {
     #[test]
     fn test_phantom_read() -> Result<()> {
         phantom_read(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         phantom_read(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
@@ -863,9 +2052,18 @@ mod tests {
         tx1.rollback()?;
 
         let tx2 = mvcc.begin()?;
-        assert_eq!(tx2.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
-        assert_eq!(tx2.get(b"key2".to_vec())?, Some(b"val2".to_vec()));
-        assert_eq!(tx2.get(b"key3".to_vec())?, Some(b"val3".to_vec()));
+        assert_eq!(
+            tx2.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
+        assert_eq!(
+            tx2.get(b"key2".to_vec())?,
+            Some(Bytes::from_static(b"val2"))
+        );
+        assert_eq!(
+            tx2.get(b"key3".to_vec())?,
+            Some(Bytes::from_static(b"val3"))
+        );
 
         Ok(())
     }
@@ -873,9 +2071,1077 @@ mod tests {
     #[test]
     fn test_rollback() -> Result<()> {
         rollback(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
         rollback(DiskEngine::new(p.clone())?)?;
         std::fs::remove_dir_all(p.parent().unwrap())?;
         Ok(())
     }
+
+    // 13. as of
+    fn as_of(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.commit()?;
+
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1-1".to_vec())?;
+
+        let tx2 = mvcc.begin()?;
+        tx2.delete(b"key2".to_vec())?;
+        tx2.set(b"key3".to_vec(), b"val3".to_vec())?;
+        tx2.commit()?;
+        tx1.commit()?;
+
+        // 事务 tx2 开启的时候，tx1 还没有提交
+        let snapshot = mvcc.begin_as_of(tx2.version())?;
+        assert_eq!(
+            snapshot.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
+        assert_eq!(
+            snapshot.get(b"key2".to_vec())?,
+            Some(Bytes::from_static(b"val2"))
+        );
+        assert_eq!(snapshot.get(b"key3".to_vec())?, None);
+
+        let latest = mvcc.begin()?;
+        let snapshot = mvcc.begin_as_of(latest.version())?;
+        assert_eq!(
+            snapshot.scan_prefix(b"key".to_vec())?,
+            vec![
+                super::ScanResult {
+                    key: b"key1".to_vec(),
+                    value: Bytes::from_static(b"val1-1")
+                },
+                super::ScanResult {
+                    key: b"key3".to_vec(),
+                    value: Bytes::from_static(b"val3")
+                },
+            ]
+        );
+
+        // 历史事务只读
+        assert_eq!(
+            snapshot.set(b"key1".to_vec(), b"val1-2".to_vec()),
+            Err(super::Error::ReadOnly)
+        );
+        // 不存在的版本
+        assert!(mvcc.begin_as_of(latest.version() + 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_of() -> Result<()> {
+        as_of(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        as_of(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    // 14. changes
+    fn changes(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"other".to_vec(), b"val".to_vec())?;
+        tx.commit()?;
+
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key2".to_vec(), b"val2".to_vec())?;
+
+        let tx2 = mvcc.begin()?;
+        tx2.delete(b"key1".to_vec())?;
+        tx2.commit()?;
+
+        // tx1 还没有提交，它之后的变更也不能返回
+        let (changes, next) = mvcc.changes(b"key".to_vec(), 0)?;
+        assert_eq!(
+            changes,
+            vec![super::Change {
+                version: tx.version(),
+                key: b"key1".to_vec(),
+                value: Some(Bytes::from_static(b"val1")),
+            }]
+        );
+        assert_eq!(next, tx1.version());
+
+        tx1.commit()?;
+        let (changes, _) = mvcc.changes(b"key".to_vec(), next)?;
+        assert_eq!(
+            changes,
+            vec![
+                super::Change {
+                    version: tx1.version(),
+                    key: b"key2".to_vec(),
+                    value: Some(Bytes::from_static(b"val2")),
+                },
+                super::Change {
+                    version: tx2.version(),
+                    key: b"key1".to_vec(),
+                    value: None,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes() -> Result<()> {
+        changes(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        changes(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_encoding() -> Result<()> {
+        let raw = Bytes::from(vec![7; 1024]);
+        let encoded = super::encode_value(Some(raw.clone()));
+        let decoded = super::decode_value(encoded.clone())?.unwrap();
+        assert_eq!(decoded, raw);
+        // 解码后的 value 直接引用编码后的数据，没有复制
+        assert_eq!(decoded.as_ptr(), encoded[9..].as_ptr());
+
+        assert_eq!(super::decode_value(super::encode_value(None))?, None);
+        assert!(super::decode_value(Bytes::new()).is_err());
+        assert!(super::decode_value(Bytes::from_static(&[1, 5, 0])).is_err());
+        assert!(super::decode_value(encoded.slice(..100)).is_err());
+
+        // 和 bincode 序列化 Option<Vec<u8>> 的格式相同
+        for value in [None, Some(vec![]), Some(b"value".to_vec())] {
+            let encoded = bincode::serialize(&value)?;
+            assert_eq!(super::encode_value(value.clone().map(Bytes::from)), encoded);
+            assert_eq!(
+                super::decode_value(Bytes::from(encoded))?,
+                value.map(Bytes::from)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_encoding_existing_log() -> Result<()> {
+        // 使用 bincode 写入版本数据的日志文件，重新打开之后读取到原来的 value
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        {
+            let mut eng = DiskEngine::new(p.clone())?;
+            eng.set(MvccKey::NextVersion.encode()?, bincode::serialize(&3u64)?)?;
+            for (key, version, value) in [
+                (b"key1", 1, Some(b"val1".to_vec())),
+                (b"key2", 1, Some(b"val2".to_vec())),
+                (b"key2", 2, None),
+            ] {
+                eng.set(
+                    MvccKey::Version(key.to_vec(), version).encode()?,
+                    bincode::serialize(&value)?,
+                )?;
+            }
+        }
+        let mvcc = Mvcc::new(DiskEngine::new(p.clone())?);
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from_static(b"val1")));
+        assert_eq!(tx.get(b"key2".to_vec())?, None);
+        assert_eq!(
+            tx.scan_prefix(b"key".to_vec())?,
+            vec![ScanResult {
+                key: b"key1".to_vec(),
+                value: Bytes::from_static(b"val1"),
+            }]
+        );
+        tx.commit()?;
+        drop(mvcc);
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    // 15. 旧版本清理
+    fn prune_versions(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new_with_options(
+            eng,
+            MvccOptions {
+                prune_versions: true,
+                ..Default::default()
+            },
+        );
+        // key 的所有版本数量
+        let versions = |key: &[u8]| -> Result<usize> {
+            let mut prefix = MvccKeyPrefix::Version(key.to_vec()).encode()?;
+            prefix.truncate(prefix.len() - 2);
+            Ok(mvcc.engine.lock()?.scan_prefix(prefix).count())
+        };
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val1".to_vec())?;
+        tx.commit()?;
+
+        // 旧的活跃事务需要读取旧版本，不能被清理
+        let old = mvcc.begin()?;
+        for val in [b"val2", b"val3"] {
+            let tx = mvcc.begin()?;
+            tx.set(b"key1".to_vec(), val.to_vec())?;
+            tx.commit()?;
+        }
+        let tx = mvcc.begin()?;
+        tx.delete(b"key2".to_vec())?;
+        tx.commit()?;
+
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from_static(b"val3")));
+        assert_eq!(tx.get(b"key2".to_vec())?, None);
+        tx.commit()?;
+        assert_eq!(versions(b"key1")?, 3);
+        assert_eq!(versions(b"key2")?, 2);
+        assert_eq!(
+            old.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
+        assert_eq!(
+            old.get(b"key2".to_vec())?,
+            Some(Bytes::from_static(b"val1"))
+        );
+        old.commit()?;
+
+        // 没有活跃事务之后，只保留最新的版本，删除标记也会被清理
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from_static(b"val3")));
+        assert_eq!(tx.get(b"key2".to_vec())?, None);
+        tx.commit()?;
+        assert_eq!(versions(b"key1")?, 1);
+        assert_eq!(versions(b"key2")?, 0);
+
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from_static(b"val3")));
+        assert_eq!(
+            tx.scan_prefix(b"key".to_vec())?,
+            vec![super::ScanResult {
+                key: b"key1".to_vec(),
+                value: Bytes::from_static(b"val3"),
+            }]
+        );
+        tx.commit()?;
+
+        // 清理过的版本不能再用于历史查询，这些版本的活跃事务快照也被删除
+        assert_eq!(
+            mvcc.begin_as_of(3).err(),
+            Some(Error::Internal(
+                "version 3 has been pruned, the oldest version for AS OF is 8".to_string()
+            ))
+        );
+        let snapshots = MvccKeyPrefix::TxnActiveSnapshot.encode()?;
+        assert_eq!(mvcc.engine.lock()?.scan_prefix(snapshots).count(), 0);
+        assert!(mvcc.begin_as_of(7).is_err());
+        let tx = mvcc.begin_as_of(8)?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from_static(b"val3")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_versions() -> Result<()> {
+        prune_versions(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        prune_versions(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    // 清理旧版本时保留历史事务需要的版本
+    fn prune_versions_as_of(eng: impl Engine + Send + 'static) -> Result<()> {
+        let mvcc = Mvcc::new_with_options(
+            eng,
+            MvccOptions {
+                prune_versions: true,
+                ..Default::default()
+            },
+        );
+        let versions = |key: &[u8]| -> Result<usize> {
+            let mut prefix = MvccKeyPrefix::Version(key.to_vec()).encode()?;
+            prefix.truncate(prefix.len() - 2);
+            Ok(mvcc.engine.lock()?.scan_prefix(prefix).count())
+        };
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val1".to_vec())?;
+        tx.commit()?;
+        let tx = mvcc.begin()?;
+        let as_of = tx.version();
+        tx.commit()?;
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val2".to_vec())?;
+        tx.delete(b"key2".to_vec())?;
+        tx.commit()?;
+
+        // 历史事务在清理的事务开启之后才开始，清理时仍然需要保留它读取的版本
+        let pruner = mvcc.begin()?;
+        let reader = mvcc.begin_as_of(as_of)?;
+        let handle = {
+            let mvcc = mvcc.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let reader = mvcc.begin_as_of(as_of)?;
+                for _ in 0..100 {
+                    assert_eq!(
+                        reader.get(b"key1".to_vec())?,
+                        Some(Bytes::from_static(b"val1"))
+                    );
+                    assert_eq!(
+                        reader.get(b"key2".to_vec())?,
+                        Some(Bytes::from_static(b"val1"))
+                    );
+                }
+                Ok(())
+            })
+        };
+        for _ in 0..100 {
+            assert_eq!(
+                pruner.get(b"key1".to_vec())?,
+                Some(Bytes::from_static(b"val2"))
+            );
+            assert_eq!(pruner.get(b"key2".to_vec())?, None);
+        }
+        handle.join().unwrap()?;
+        assert_eq!(versions(b"key1")?, 2);
+        assert_eq!(versions(b"key2")?, 2);
+        assert_eq!(
+            reader.scan_prefix(b"key".to_vec())?,
+            vec![
+                super::ScanResult {
+                    key: b"key1".to_vec(),
+                    value: Bytes::from_static(b"val1"),
+                },
+                super::ScanResult {
+                    key: b"key2".to_vec(),
+                    value: Bytes::from_static(b"val1"),
+                },
+            ]
+        );
+
+        // 历史事务结束之后，旧版本可以被清理
+        drop(reader);
+        assert_eq!(
+            pruner.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val2"))
+        );
+        assert_eq!(pruner.get(b"key2".to_vec())?, None);
+        pruner.commit()?;
+        assert_eq!(versions(b"key1")?, 1);
+        assert_eq!(versions(b"key2")?, 0);
+        assert!(mvcc.snapshots.lock()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_versions_as_of() -> Result<()> {
+        prune_versions_as_of(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        prune_versions_as_of(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    // 16. 强制中止事务
+    fn abort(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1".to_vec())?;
+        let tx2 = mvcc.begin()?;
+        tx2.set(b"key2".to_vec(), b"val2".to_vec())?;
+
+        let tx3 = mvcc.begin()?;
+        tx3.abort(tx1.version())?;
+        assert!(tx3.abort(tx1.version()).is_err());
+        assert!(tx3.abort(tx3.version()).is_err());
+        tx3.commit()?;
+
+        // 被中止的事务不能再写入和提交
+        assert_eq!(
+            tx1.set(b"key1".to_vec(), b"val1".to_vec()),
+            Err(Error::Aborted)
+        );
+        assert_eq!(tx1.commit(), Err(Error::Aborted));
+        tx1.rollback()?;
+
+        // 超时的事务被中止
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(mvcc.abort_expired(Duration::ZERO)?, vec![tx2.version()]);
+        assert_eq!(tx2.commit(), Err(Error::Aborted));
+        assert!(mvcc.abort_expired(Duration::ZERO)?.is_empty());
+
+        let tx4 = mvcc.begin()?;
+        assert_eq!(tx4.get(b"key1".to_vec())?, None);
+        assert_eq!(tx4.get(b"key2".to_vec())?, None);
+        tx4.commit()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abort() -> Result<()> {
+        abort(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        abort(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown() -> Result<()> {
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        let mvcc = Mvcc::new(DiskEngine::new(p.clone())?);
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx1.commit()?;
+        let tx2 = mvcc.begin()?;
+        tx2.set(b"key1".to_vec(), b"val2".to_vec())?;
+        tx2.set(b"key2".to_vec(), b"val2".to_vec())?;
+
+        // 关闭时回滚还没有结束的事务
+        assert_eq!(mvcc.shutdown()?, vec![tx2.version()]);
+        assert_eq!(tx2.commit(), Err(Error::Aborted));
+        drop((tx1, tx2, mvcc));
+
+        // 重新打开之后没有遗留的活跃事务，写入不会冲突
+        let mvcc = Mvcc::new(DiskEngine::new(p.clone())?);
+        let tx = mvcc.begin()?;
+        assert_eq!(
+            tx.scan_prefix(b"key".to_vec())?,
+            vec![ScanResult {
+                key: b"key1".to_vec(),
+                value: Bytes::from_static(b"val1"),
+            }]
+        );
+        tx.set(b"key1".to_vec(), b"val3".to_vec())?;
+        tx.commit()?;
+        drop(mvcc);
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_crash_recovery() -> Result<()> {
+        let engine = FaultEngine::new(MemoryEngine::new());
+        let log = engine.log();
+        let mvcc = Mvcc::new(engine);
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx1.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx1.commit()?;
+        let tx2 = mvcc.begin()?;
+        tx2.set(b"key1".to_vec(), b"val3".to_vec())?;
+        tx2.delete(b"key2".to_vec())?;
+        tx2.set(b"key3".to_vec(), b"val3".to_vec())?;
+        tx2.commit()?;
+        let tx3 = mvcc.begin()?;
+        tx3.set(b"key1".to_vec(), b"val4".to_vec())?;
+        tx3.rollback()?;
+        let tx4 = mvcc.begin()?;
+        tx4.set(b"key2".to_vec(), b"val4".to_vec())?;
+
+        // 在任意一个写入之后崩溃，恢复之后只能看到完整提交的事务
+        let committed = [
+            vec![],
+            vec![
+                (b"key1".to_vec(), b"val1".to_vec()),
+                (b"key2".to_vec(), b"val2".to_vec()),
+            ],
+            vec![
+                (b"key1".to_vec(), b"val3".to_vec()),
+                (b"key3".to_vec(), b"val3".to_vec()),
+            ],
+        ];
+        for n in 0..=log.count() {
+            let mvcc = Mvcc::new(log.replay(n)?);
+            mvcc.shutdown()?;
+            let tx = mvcc.begin()?;
+            let state = tx
+                .scan_prefix(b"key".to_vec())?
+                .into_iter()
+                .map(|r| (r.key, r.value.to_vec()))
+                .collect::<Vec<_>>();
+            assert!(
+                committed.contains(&state),
+                "crash after {} writes: {:?}",
+                n,
+                state
+            );
+            if n == log.count() {
+                assert_eq!(state, committed[2]);
+            }
+            // 恢复之后可以正常写入
+            tx.set(b"key2".to_vec(), b"val5".to_vec())?;
+            tx.commit()?;
+        }
+
+        // 关闭时持久化之后，之前的写入都不会丢失
+        mvcc.shutdown()?;
+        assert_eq!(log.flushed(), log.count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_txn_age() -> Result<()> {
+        // 开启事务时根据配置中止超时的事务
+        let mvcc = Mvcc::new_with_options(
+            MemoryEngine::new(),
+            MvccOptions {
+                max_txn_age: Some(Duration::from_millis(1)),
+                ..Default::default()
+            },
+        );
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1".to_vec())?;
+        std::thread::sleep(Duration::from_millis(5));
+
+        let tx2 = mvcc.begin()?;
+        assert_eq!(tx2.get(b"key1".to_vec())?, None);
+        tx2.set(b"key1".to_vec(), b"val2".to_vec())?;
+        tx2.commit()?;
+        assert_eq!(tx1.commit(), Err(Error::Aborted));
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_timeout() -> Result<()> {
+        let mvcc = Mvcc::new_with_options(
+            MemoryEngine::new(),
+            MvccOptions {
+                lock_timeout: Some(Duration::from_secs(5)),
+                ..Default::default()
+            },
+        );
+
+        // 对方事务回滚之后，等待的写入可以成功
+        let tx1 = mvcc.begin()?;
+        let tx2 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1".to_vec())?;
+        std::thread::scope(|s| {
+            let handle = s.spawn(|| tx2.set(b"key1".to_vec(), b"val2".to_vec()));
+            std::thread::sleep(Duration::from_millis(20));
+            tx1.rollback()?;
+            handle.join().unwrap()
+        })?;
+        tx2.commit()?;
+
+        // 对方事务提交之后，等待的写入仍然冲突
+        let tx3 = mvcc.begin()?;
+        let tx4 = mvcc.begin()?;
+        tx3.set(b"key1".to_vec(), b"val3".to_vec())?;
+        let result = std::thread::scope(|s| {
+            let handle = s.spawn(|| tx4.set(b"key1".to_vec(), b"val4".to_vec()));
+            std::thread::sleep(Duration::from_millis(20));
+            tx3.commit()?;
+            handle.join().unwrap()
+        });
+        assert_eq!(result, Err(Error::WriteConflict));
+        tx4.rollback()?;
+
+        // 两个事务互相等待
+        let tx5 = mvcc.begin()?;
+        let tx6 = mvcc.begin()?;
+        tx5.set(b"key1".to_vec(), b"val5".to_vec())?;
+        tx6.set(b"key2".to_vec(), b"val6".to_vec())?;
+        let result = std::thread::scope(|s| {
+            let handle = s.spawn(|| tx5.set(b"key2".to_vec(), b"val5".to_vec()));
+            std::thread::sleep(Duration::from_millis(20));
+            let result = tx6.set(b"key1".to_vec(), b"val6".to_vec());
+            tx6.rollback()?;
+            handle.join().unwrap()?;
+            result
+        });
+        assert_eq!(result, Err(Error::Deadlock));
+        tx5.commit()?;
+
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from("val5")));
+        assert_eq!(tx.get(b"key2".to_vec())?, Some(Bytes::from("val5")));
+        tx.commit()?;
+
+        // 批量写入和 set 一样等待冲突的事务，也会检测死锁
+        let batch = |key: &[u8], value: &'static [u8]| {
+            vec![
+                (b"key3".to_vec(), Some(Bytes::from_static(value))),
+                (key.to_vec(), Some(Bytes::from_static(value))),
+            ]
+        };
+        let tx7 = mvcc.begin()?;
+        let tx8 = mvcc.begin()?;
+        tx7.set(b"key1".to_vec(), b"val7".to_vec())?;
+        std::thread::scope(|s| {
+            let handle = s.spawn(|| tx8.write_batch(batch(b"key1", b"val8")));
+            std::thread::sleep(Duration::from_millis(20));
+            tx7.rollback()?;
+            handle.join().unwrap()
+        })?;
+        let tx9 = mvcc.begin()?;
+        tx9.set(b"key2".to_vec(), b"val9".to_vec())?;
+        let result = std::thread::scope(|s| {
+            let handle = s.spawn(|| tx9.write_batch(batch(b"key1", b"val9")));
+            std::thread::sleep(Duration::from_millis(20));
+            let result = tx8.write_batch(batch(b"key2", b"val8"));
+            tx8.rollback()?;
+            handle.join().unwrap()?;
+            result
+        });
+        assert_eq!(result, Err(Error::Deadlock));
+        tx9.commit()?;
+
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from("val9")));
+        assert_eq!(tx.get(b"key3".to_vec())?, Some(Bytes::from("val9")));
+        tx.commit()?;
+
+        // 等待超时
+        let mvcc = Mvcc::new_with_options(
+            MemoryEngine::new(),
+            MvccOptions {
+                lock_timeout: Some(Duration::from_millis(10)),
+                ..Default::default()
+            },
+        );
+        let tx1 = mvcc.begin()?;
+        let tx2 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1".to_vec())?;
+        assert_eq!(
+            tx2.set(b"key1".to_vec(), b"val2".to_vec()),
+            Err(Error::WriteConflict)
+        );
+        assert_eq!(
+            tx2.write_batch(vec![(b"key1".to_vec(), None)]),
+            Err(Error::WriteConflict)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.set(b"key3".to_vec(), b"val3".to_vec())?;
+        tx.commit()?;
+        let tx = mvcc.begin()?;
+        tx.delete(b"key3".to_vec())?;
+        tx.commit()?;
+
+        // 未提交的事务和备份开始之后的写入都不会被备份
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"uncommitted".to_vec())?;
+        let backup = mvcc.begin()?;
+        let tx2 = mvcc.begin()?;
+        tx2.set(b"key2".to_vec(), b"later".to_vec())?;
+        tx2.commit()?;
+
+        let mut dst = MemoryEngine::new();
+        let mut batches = 0;
+        let stats = backup.backup(
+            &mut dst,
+            BackupOptions {
+                batch_size: 2,
+                ..Default::default()
+            },
+            |_| batches += 1,
+        )?;
+        backup.commit()?;
+        tx1.commit()?;
+        assert_eq!(stats.version, backup.version());
+        assert_eq!(stats.keys, 4);
+        assert!(batches > 1);
+
+        // 备份的数据可以直接打开
+        let restored = Mvcc::new(dst);
+        let tx = restored.begin()?;
+        assert_eq!(tx.version(), backup.version());
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from("val1")));
+        assert_eq!(tx.get(b"key2".to_vec())?, Some(Bytes::from("val2")));
+        assert_eq!(tx.get(b"key3".to_vec())?, None);
+        tx.commit()?;
+        let tx = restored.begin_as_of(2)?;
+        assert_eq!(tx.get(b"key3".to_vec())?, Some(Bytes::from("val3")));
+
+        // 备份的目标需要是空的
+        let mut dst = MemoryEngine::new();
+        dst.set(b"key".to_vec(), b"value".to_vec())?;
+        assert!(mvcc
+            .begin()?
+            .backup(&mut dst, BackupOptions::default(), |_| {})
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_backup() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.commit()?;
+
+        // 基础备份时 tx1 还没有提交
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key3".to_vec(), b"val3".to_vec())?;
+        let backup = mvcc.begin()?;
+        let mut base = MemoryEngine::new();
+        backup.backup(&mut base, BackupOptions::default(), |_| {})?;
+        backup.commit()?;
+        tx1.commit()?;
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"new1".to_vec())?;
+        tx.delete(b"key2".to_vec())?;
+        tx.commit()?;
+
+        // 增量备份包含基础备份之后提交的数据，不包含基础备份已有的数据
+        let mut incremental = MemoryEngine::new();
+        let tx = mvcc.begin()?;
+        let stats = tx.backup(
+            &mut incremental,
+            BackupOptions {
+                since: Some(backup.version()),
+                ..Default::default()
+            },
+            |_| {},
+        )?;
+        tx.commit()?;
+        assert_eq!(stats.keys, 4);
+
+        // 增量备份不能单独恢复，也不能跳过中间的版本
+        assert!(restore_backup(&mut MemoryEngine::new(), &mut incremental).is_err());
+        assert!(restore_backup(&mut incremental, &mut base).is_err());
+
+        restore_backup(&mut base, &mut incremental)?;
+        let restored = Mvcc::new(base);
+        let tx = restored.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(Bytes::from("new1")));
+        assert_eq!(tx.get(b"key2".to_vec())?, None);
+        assert_eq!(tx.get(b"key3".to_vec())?, Some(Bytes::from("val3")));
+        tx.commit()?;
+
+        let versions = mvcc.begin()?;
+        assert!(versions
+            .backup(
+                &mut MemoryEngine::new(),
+                BackupOptions {
+                    since: Some(versions.version()),
+                    ..Default::default()
+                },
+                |_| {},
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.commit()?;
+        let tx = mvcc.begin()?;
+        tx.delete(b"key1".to_vec())?;
+        tx.commit()?;
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val3".to_vec())?;
+
+        let version = |version, value: Option<&'static str>, committed| KeyVersion {
+            version,
+            value: value.map(Bytes::from),
+            committed,
+        };
+        assert_eq!(
+            mvcc.begin()?.history(b"key1".to_vec())?,
+            vec![
+                version(1, Some("val1"), true),
+                version(2, None, true),
+                version(3, Some("val3"), false),
+            ]
+        );
+        assert_eq!(
+            mvcc.begin()?.history(b"key2".to_vec())?,
+            vec![version(1, Some("val2"), true)]
+        );
+        assert!(mvcc.begin()?.history(b"key".to_vec())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let mvcc = Mvcc::new(MemoryEngine::new());
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.delete(b"key2".to_vec())?;
+        tx.commit()?;
+        let tx = mvcc.begin()?;
+        tx.set(b"key3".to_vec(), b"val3".to_vec())?;
+        assert!(mvcc.begin()?.verify(b"key".to_vec())?.is_empty());
+
+        // 直接修改存储引擎中的数据，模拟损坏的版本
+        {
+            let mut engine = mvcc.engine.lock()?;
+            engine.set(MvccKey::Version(b"key1".to_vec(), 1).encode()?, vec![9])?;
+            engine.set(
+                MvccKey::Version(b"key4".to_vec(), 100).encode()?,
+                encode_value(None),
+            )?;
+            engine.delete(MvccKey::TxnWrite(2, b"key3".to_vec()).encode()?)?;
+        }
+        let problems = mvcc.begin()?.verify(b"key".to_vec())?;
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("invalid version value"));
+        assert!(problems[1].contains("not less than next version"));
+        assert!(problems[2].contains("has no write record"));
+
+        // 其他前缀下的版本不受影响
+        assert!(mvcc.begin()?.verify(b"key2".to_vec())?.is_empty());
+        Ok(())
+    }
+
+    // 17. 快照
+    fn snapshot(eng: impl Engine + Send + 'static) -> Result<()> {
+        let mvcc = Mvcc::new_with_options(
+            eng,
+            MvccOptions {
+                prune_versions: true,
+                ..Default::default()
+            },
+        );
+        let versions = |key: &[u8]| -> Result<usize> {
+            let mut prefix = MvccKeyPrefix::Version(key.to_vec()).encode()?;
+            prefix.truncate(prefix.len() - 2);
+            Ok(mvcc.engine.lock()?.scan_prefix(prefix).count())
+        };
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.commit()?;
+
+        // 创建快照时还没有提交的事务对快照不可见
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1-1".to_vec())?;
+        let snapshot = mvcc.snapshot()?;
+        assert_eq!(snapshot.version(), tx1.version() + 1);
+        tx1.commit()?;
+        let tx2 = mvcc.begin()?;
+        tx2.delete(b"key2".to_vec())?;
+        tx2.set(b"key3".to_vec(), b"val3".to_vec())?;
+        tx2.commit()?;
+
+        // 快照不会加入活跃事务列表，也不会分配版本号
+        assert!(super::MvccTransaction::scan_active(&mut mvcc.engine.lock()?)?.is_empty());
+        assert_eq!(mvcc.begin()?.version(), tx2.version() + 1);
+
+        let expected = vec![
+            super::ScanResult {
+                key: b"key1".to_vec(),
+                value: Bytes::from_static(b"val1"),
+            },
+            super::ScanResult {
+                key: b"key2".to_vec(),
+                value: Bytes::from_static(b"val2"),
+            },
+        ];
+        // 多个线程同时读取同一个快照
+        let readers = (0..4)
+            .map(|_| {
+                let snapshot = snapshot.clone();
+                std::thread::spawn(move || snapshot.scan_prefix(b"key".to_vec()))
+            })
+            .collect::<Vec<_>>();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap()?, expected);
+        }
+        assert_eq!(snapshot.get(b"key3".to_vec())?, None);
+
+        // 快照存在期间，新的事务不会清理快照能读取到的版本
+        let tx = mvcc.begin()?;
+        assert_eq!(
+            tx.get(b"key1".to_vec())?,
+            Some(Bytes::from_static(b"val1-1"))
+        );
+        assert_eq!(tx.get(b"key2".to_vec())?, None);
+        tx.commit()?;
+        assert_eq!(versions(b"key1")?, 2);
+        assert_eq!(versions(b"key2")?, 2);
+        assert_eq!(snapshot.scan_prefix(b"key".to_vec())?, expected);
+
+        // 所有的克隆都释放之后，旧版本可以被清理
+        drop(snapshot);
+        let tx = mvcc.begin()?;
+        tx.get(b"key1".to_vec())?;
+        tx.get(b"key2".to_vec())?;
+        tx.commit()?;
+        assert_eq!(versions(b"key1")?, 1);
+        assert_eq!(versions(b"key2")?, 0);
+        assert!(mvcc.snapshots.lock()?.is_empty());
+
+        // 只读事务和快照一样读取已提交的数据并且登记视图，不能写入
+        {
+            let reader = mvcc.begin_read_only()?;
+            assert_eq!(
+                reader.get(b"key3".to_vec())?,
+                Some(Bytes::from_static(b"val3"))
+            );
+            assert_eq!(
+                reader.set(b"key3".to_vec(), b"val3-1".to_vec()),
+                Err(Error::ReadOnly)
+            );
+            assert_eq!(mvcc.snapshots.lock()?.len(), 1);
+            reader.commit()?;
+        }
+        assert!(mvcc.snapshots.lock()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> Result<()> {
+        snapshot(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        snapshot(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    // 18. 批量写入
+    fn write_batch(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.commit()?;
+
+        let tx1 = mvcc.begin()?;
+        let tx2 = mvcc.begin()?;
+        tx1.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx1.write_batch(vec![
+            (b"key1".to_vec(), None),
+            (b"key2".to_vec(), Some(Bytes::from_static(b"val2-1"))),
+            (b"key3".to_vec(), Some(Bytes::from_static(b"val3"))),
+            (b"key3".to_vec(), Some(Bytes::from_static(b"val3-1"))),
+        ])?;
+        // 每个 key 只记录一次 TxnWrite
+        let txn_writes = |version| -> Result<usize> {
+            let mut engine = mvcc.engine.lock()?;
+            let prefix = MvccKeyPrefix::TxnWrite(version).encode()?;
+            Ok(engine.scan_prefix(prefix).count())
+        };
+        assert_eq!(txn_writes(tx1.version())?, 3);
+
+        // 没有设置 lock_timeout 时和未提交的事务冲突不等待，整个批量写入都不生效
+        assert_eq!(
+            tx2.write_batch(vec![
+                (b"key4".to_vec(), Some(Bytes::from_static(b"val4"))),
+                (b"key1".to_vec(), Some(Bytes::from_static(b"val1-2"))),
+            ]),
+            Err(Error::WriteConflict)
+        );
+        assert_eq!(txn_writes(tx2.version())?, 0);
+        tx2.rollback()?;
+        tx1.commit()?;
+        assert_eq!(txn_writes(tx1.version())?, 0);
+
+        let tx3 = mvcc.begin()?;
+        assert_eq!(
+            tx3.scan_prefix(b"key".to_vec())?,
+            vec![
+                ScanResult {
+                    key: b"key2".to_vec(),
+                    value: Bytes::from_static(b"val2-1"),
+                },
+                ScanResult {
+                    key: b"key3".to_vec(),
+                    value: Bytes::from_static(b"val3-1"),
+                },
+            ]
+        );
+        tx3.write_batch(vec![(b"key2".to_vec(), None)])?;
+        tx3.rollback()?;
+
+        // 只读事务不能写入
+        let tx4 = mvcc.begin_as_of(tx3.version())?;
+        assert_eq!(tx4.write_batch(vec![]), Err(Error::ReadOnly));
+        assert_eq!(
+            mvcc.begin()?.get(b"key2".to_vec())?,
+            Some(Bytes::from_static(b"val2-1"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_batch() -> Result<()> {
+        write_batch(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        write_batch(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
+
+    // 19. 批量读取
+    fn get_many(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.set(b"key3".to_vec(), b"val3".to_vec())?;
+        tx.commit()?;
+
+        let tx1 = mvcc.begin()?;
+        let tx2 = mvcc.begin()?;
+        tx1.set(b"key1".to_vec(), b"val1-1".to_vec())?;
+        tx1.delete(b"key2".to_vec())?;
+        let keys = vec![
+            b"key3".to_vec(),
+            b"key1".to_vec(),
+            b"key2".to_vec(),
+            b"key4".to_vec(),
+        ];
+        // 能看到自己的修改
+        assert_eq!(
+            tx1.get_many(keys.clone())?,
+            vec![
+                Some(Bytes::from_static(b"val3")),
+                Some(Bytes::from_static(b"val1-1")),
+                None,
+                None,
+            ]
+        );
+        // 看不到未提交的修改
+        assert_eq!(
+            tx2.get_many(keys.clone())?,
+            vec![
+                Some(Bytes::from_static(b"val3")),
+                Some(Bytes::from_static(b"val1")),
+                Some(Bytes::from_static(b"val2")),
+                None,
+            ]
+        );
+        tx1.commit()?;
+        let snapshot = mvcc.snapshot()?;
+        assert_eq!(
+            snapshot.get_many(keys)?,
+            vec![
+                Some(Bytes::from_static(b"val3")),
+                Some(Bytes::from_static(b"val1-1")),
+                None,
+                None,
+            ]
+        );
+        assert_eq!(tx2.get_many(vec![])?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_many() -> Result<()> {
+        get_many(MemoryEngine::new())?;
+        let p = tempfile::tempdir()?.keep().join("sqldb-log");
+        get_many(DiskEngine::new(p.clone())?)?;
+        std::fs::remove_dir_all(p.parent().unwrap())?;
+        Ok(())
+    }
 }
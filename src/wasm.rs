@@ -0,0 +1,200 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    sql::{
+        engine::{kv::KVEngine, Engine, Session},
+        executor::ResultSet,
+        types::Value,
+    },
+    storage::memory::MemoryEngine,
+};
+
+// 浏览器中使用的数据库，数据保存在内存中
+#[wasm_bindgen]
+pub struct Database {
+    session: Session<KVEngine<MemoryEngine>>,
+}
+
+#[wasm_bindgen]
+impl Database {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Result<Database, JsError> {
+        let session = KVEngine::new(MemoryEngine::new())
+            .session()
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Self { session })
+    }
+
+    // 执行 SQL 语句，返回 JS 对象
+    // 查询返回 { type: "query", columns: [...], rows: [[...], ...] }
+    pub fn execute(&mut self, sql: &str) -> Result<JsValue, JsError> {
+        let result = self
+            .session
+            .execute(sql)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(serde_wasm_bindgen::to_value(&JsResult::from(result))?)
+    }
+}
+
+// 返回给 JS 的结果
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum JsResult {
+    CreateTable {
+        table_name: String,
+    },
+    AlterTable {
+        table_name: String,
+    },
+    CreateView {
+        view_name: String,
+    },
+    DropView {
+        view_name: String,
+    },
+    CreateIndex {
+        index_name: String,
+        rows: usize,
+    },
+    DropIndex {
+        index_name: String,
+    },
+    Insert {
+        count: usize,
+    },
+    Update {
+        count: usize,
+    },
+    Delete {
+        count: usize,
+    },
+    Query {
+        columns: Vec<String>,
+        rows: Vec<Vec<JsCell>>,
+    },
+    Begin {
+        version: u64,
+    },
+    Commit {
+        version: u64,
+    },
+    Rollback {
+        version: u64,
+    },
+    RollbackTransaction {
+        version: u64,
+    },
+    Checkpoint,
+    Set {
+        name: String,
+        value: JsCell,
+    },
+    Kill {
+        session_id: u64,
+    },
+    Attach {
+        alias: String,
+    },
+    Detach {
+        alias: String,
+    },
+    Analyze {
+        table_name: String,
+        rows: usize,
+    },
+    Reindex {
+        table_name: String,
+        rows: usize,
+    },
+    Backup {
+        path: String,
+        version: u64,
+        keys: u64,
+        bytes: u64,
+    },
+}
+
+// Value 对应的 JS 原生类型
+#[derive(Serialize)]
+#[serde(untagged)]
+enum JsCell {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    // [x, y]
+    Point([f64; 2]),
+    // JS 的 number 不能精确表示，使用字符串
+    Decimal(String),
+    Uuid(String),
+    // JSON 文本，使用 JSON.parse 转换
+    Json(String),
+}
+
+impl From<Value> for JsCell {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => JsCell::Null,
+            Value::Boolean(b) => JsCell::Boolean(b),
+            Value::Integer(i) => JsCell::Integer(i),
+            Value::Float(f) => JsCell::Float(f),
+            Value::String(s) => JsCell::String(s),
+            Value::Point(x, y) => JsCell::Point([x, y]),
+            Value::Decimal(d) => JsCell::Decimal(d.to_string()),
+            Value::Uuid(u) => JsCell::Uuid(u.to_string()),
+            Value::Json(j) => JsCell::Json(j.to_string()),
+        }
+    }
+}
+
+impl From<ResultSet> for JsResult {
+    fn from(result: ResultSet) -> Self {
+        match result {
+            ResultSet::CreateTable { table_name } => JsResult::CreateTable { table_name },
+            ResultSet::AlterTable { table_name } => JsResult::AlterTable { table_name },
+            ResultSet::CreateView { view_name } => JsResult::CreateView { view_name },
+            ResultSet::DropView { view_name } => JsResult::DropView { view_name },
+            ResultSet::CreateIndex { index_name, rows } => {
+                JsResult::CreateIndex { index_name, rows }
+            }
+            ResultSet::DropIndex { index_name } => JsResult::DropIndex { index_name },
+            ResultSet::Insert { count } => JsResult::Insert { count },
+            ResultSet::Update { count } => JsResult::Update { count },
+            ResultSet::Delete { count } => JsResult::Delete { count },
+            ResultSet::Begin { version } => JsResult::Begin { version },
+            ResultSet::Commit { version } => JsResult::Commit { version },
+            ResultSet::Rollback { version } => JsResult::Rollback { version },
+            ResultSet::RollbackTransaction { version } => JsResult::RollbackTransaction { version },
+            ResultSet::Checkpoint => JsResult::Checkpoint,
+            ResultSet::Set { name, value } => JsResult::Set {
+                name,
+                value: value.into(),
+            },
+            ResultSet::Kill { session_id } => JsResult::Kill { session_id },
+            ResultSet::Attach { alias } => JsResult::Attach { alias },
+            ResultSet::Detach { alias } => JsResult::Detach { alias },
+            ResultSet::Analyze { table_name, rows } => JsResult::Analyze { table_name, rows },
+            ResultSet::Reindex { table_name, rows } => JsResult::Reindex { table_name, rows },
+            ResultSet::Backup {
+                path,
+                version,
+                keys,
+                bytes,
+            } => JsResult::Backup {
+                path,
+                version,
+                keys,
+                bytes,
+            },
+            ResultSet::Scan { columns, rows } => JsResult::Query {
+                columns,
+                rows: rows
+                    .into_iter()
+                    .map(|row| row.into_iter().map(JsCell::from).collect())
+                    .collect(),
+            },
+        }
+    }
+}